@@ -0,0 +1,57 @@
+//! Timing of amino acid kmer generation along a sequence.
+//!
+//! For each kmer size the time per kmer is printed for sequences of growing length.
+//! Kmers are rolled (one shift and one or per residue), so the time per kmer depends neither on kmer size nor on sequence length.
+//!
+//! usage : cargo run --release --example aakmerbench
+
+use std::time::Instant;
+
+use kmerutils::aautils::kmeraa::*;
+use kmerutils::base::kmertraits::*;
+
+
+// a pseudo random protein of length len
+fn random_protein(len : usize) -> SequenceAA {
+    let alphabet = b"ACDEFGHIKLMNPQRSTVWY";
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let residues : Vec<u8> = (0..len).map(|_| {
+            state ^= state << 13; state ^= state >> 7; state ^= state << 17;
+            alphabet[(state % 20) as usize]
+        }).collect();
+    SequenceAA::new(&residues).unwrap()
+}
+
+
+// returns ns per kmer and a checksum so generation is not optimized away
+fn time_kmers<Kmer>(seq : &SequenceAA, kmer_size : usize) -> (f64, u64)
+        where Kmer : CompressedKmerT + KmerBuilder<Kmer>, <Kmer as CompressedKmerT>::Val : Into<u64> {
+    let start_t = Instant::now();
+    let mut iter = KmerSeqIterator::<Kmer>::new(kmer_size, seq);
+    let mut nb_kmers = 0u64;
+    let mut checksum = 0u64;
+    while let Some(kmer) = iter.next() {
+        checksum = checksum.wrapping_add(kmer.get_compressed_value().into());
+        nb_kmers += 1;
+    }
+    let elapsed = start_t.elapsed().as_nanos() as f64;
+    (elapsed / nb_kmers.max(1) as f64, checksum)
+}
+
+
+fn main() {
+    let lengths = [10_000usize, 100_000, 1_000_000, 10_000_000];
+    let sequences : Vec<SequenceAA> = lengths.iter().map(|len| random_protein(*len)).collect();
+    println!("ns per kmer, sequence lengths : {:?}", lengths);
+    let mut checksum = 0u64;
+    for kmer_size in [3usize, 6, 9, 12] {
+        let mut line = format!("kmer size {:2} :", kmer_size);
+        for seq in &sequences {
+            let (ns, sum) = if kmer_size <= 6 { time_kmers::<KmerAA32bit>(seq, kmer_size) } else { time_kmers::<KmerAA64bit>(seq, kmer_size) };
+            checksum = checksum.wrapping_add(sum);
+            line.push_str(&format!(" {:8.3}", ns));
+        }
+        println!("{}", line);
+    }
+    println!("checksum : {:x}", checksum);
+} // end of main
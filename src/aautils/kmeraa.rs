@@ -13,19 +13,28 @@ use std::mem;
 use std::io;
 
 use std::str::FromStr;
+use std::fmt;
+use std::fmt::Write;
 
 
 use std::cmp::Ordering;
-use std::ops::Range;
+use std::hash::{Hash, Hasher};
+use std::ops::{Range, RangeBounds};
 
 use fnv::{FnvHashMap,FnvBuildHasher};
 
+use serde::{Deserialize, Serialize};
+
 
 #[allow(unused)]
 use log::{debug,info,error};
 
 
 use crate::base::kmertraits::*;
+use crate::base::kmersequence::{KmerSequence, for_each_encoded_kmer};
+use crate::base::kmergenerator::get_bounded_range;
+use crate::base::scratch::with_scratch_bytes;
+use super::simdaa::{get_residue_code, uppercase_residues, encode_residues};
 
 /// alphabet of RNA is encoded from 1 to 20 according to lexicographic order. 
 pub struct Alphabet {
@@ -66,17 +75,18 @@ impl Alphabet {
         return self.bases.len() as u8;
     }
 
+    /// checks for an upper case residue of alphabet. See [simdaa](super::simdaa) to check whole sequences.
     #[inline(always)]
     pub fn is_valid_base(&self, c: u8) -> bool {
-        self.bases.find(c as char).is_some() 
+        c.is_ascii_uppercase() && get_residue_code(c) != 0
     } // end is_valid_base
 
     pub fn get_nb_bits(&self) -> u8 { 
         5
     }
 
-    // encode a base into its bit pattern and returns it in a u8
-    fn encode(&self, c : u8) -> u8 {
+    /// encode a base into its bit pattern and returns it in a u8. Panics if c is not an upper case residue of alphabet.
+    pub fn encode(&self, c : u8) -> u8 {
         match c {
             b'A' => 0b00001,
             b'C' => 0b00010,
@@ -103,7 +113,7 @@ impl Alphabet {
     }   // end of encode
 
 
-    fn decode(&self, c:u8) -> u8 {
+    fn decode(c:u8) -> u8 {
         match c {
             0b00001 => b'A',
             0b00010 => b'C',
@@ -131,18 +141,76 @@ impl Alphabet {
 }  // end of impl Alphabet
 
 
+// mask of the 5 * nb_base lower bits coding residues of a kmer
+#[inline(always)]
+fn residue_mask<V : num::PrimInt>(nb_base : u8) -> V {
+    let nb_bits = 5 * nb_base as usize;
+    if nb_bits >= 8 * size_of::<V>() { V::max_value() } else { (V::one() << nb_bits) - V::one() }
+} // end of residue_mask
+
+
+// checks residues of a kmer to build, lower case residues being accepted. Returns residues in upper case.
+fn check_kmer_residues(residues : &[u8], nb_base_max : usize) -> Result<Vec<u8>, String> {
+    if residues.is_empty() || residues.len() > nb_base_max {
+        log::error!("kmer length {} not in 1..={}", residues.len(), nb_base_max);
+        return Err(format!("kmer length {} not in 1..={}", residues.len(), nb_base_max));
+    }
+    let alphabet = Alphabet::new();
+    let residues : Vec<u8> = residues.iter().map(|c| c.to_ascii_uppercase()).collect();
+    if let Some(pos) = residues.iter().position(|c| !alphabet.is_valid_base(*c)) {
+        log::error!("kmer character not in alphabet {:?} at position {}", residues[pos] as char, pos);
+        return Err(format!("character not in alphabet {:?} at position {}", residues[pos] as char, pos));
+    }
+    Ok(residues)
+} // end of check_kmer_residues
+
+
+// checks a deserialized kmer value : at most nb_base_max residues, all of them codes of Alphabet.
+// Returns the value with bits not coding residues reset to 0.
+fn check_kmer_codes<V : num::PrimInt>(aa : V, nb_base : u8, nb_base_max : usize) -> Result<V, String> {
+    if nb_base as usize > nb_base_max {
+        return Err(format!("kmer cannot store {} residues, max is {}", nb_base, nb_base_max));
+    }
+    let aa = aa & residue_mask::<V>(nb_base);
+    let is_code = |code : u8| (0b00001..=0b10101).contains(&code) && code != 0b01110;
+    if let Some(pos) = (0..nb_base as usize).find(|i| !is_code(((aa >> (5 * i)) & V::from(0b11111).unwrap()).to_u8().unwrap())) {
+        return Err(format!("kmer value has no residue code at position {} from the right", pos));
+    }
+    Ok(aa)
+} // end of check_kmer_codes
+
+
 //=======================================================================================
 /// A Kmer of amino acids represented on 32 bits, it can store up to 6 AA.  
 /// See also KmerAA64bit for less than 12 AA.
 /// We implement Amino Acid Kmer as packed in a u32 using 5bits by base, so we can go up to 6 bases.
 
-#[derive(Copy,Clone,Hash)]
+/// Bits above the 5 * nb_base bits coding residues are not significant : equality, hashing, ordering
+/// and [get_compressed_value](CompressedKmerT::get_compressed_value) use the masked (canonical) value.
+#[derive(Copy,Clone,Debug,Serialize,Deserialize)]
+#[serde(try_from = "KmerAA32bitRepr")]
 pub struct KmerAA32bit {
     aa      : u32,
     nb_base : u8,
  
 } // end of struct KmerAA128bit
 
+
+// serialized form, checked at deserialization, see check_kmer_codes
+#[derive(Deserialize)]
+struct KmerAA32bitRepr {
+    aa      : u32,
+    nb_base : u8,
+}
+
+impl TryFrom<KmerAA32bitRepr> for KmerAA32bit {
+    type Error = String;
+    fn try_from(repr : KmerAA32bitRepr) -> Result<Self, String> {
+        let aa = check_kmer_codes(repr.aa, repr.nb_base, Self::get_nb_base_max())?;
+        Ok(KmerAA32bit{aa, nb_base : repr.nb_base})
+    }
+}
+
 impl KmerAA32bit {
 
     pub fn new(nb_base : u8) -> Self {
@@ -152,6 +220,14 @@ impl KmerAA32bit {
         }
         KmerAA32bit{aa:0, nb_base}
     }
+
+    /// builds the kmer of residues (in upper or lower case).
+    /// Returns an error if a residue is not in [Alphabet] or if the number of residues is 0 or greater than [get_nb_base_max](CompressedKmerT::get_nb_base_max).
+    pub fn from_bytes(residues : &[u8]) -> Result<Self, String> {
+        let residues = check_kmer_residues(residues, Self::get_nb_base_max())?;
+        let kmer = KmerAA32bit{aa:0, nb_base : residues.len() as u8};
+        Ok(residues.iter().fold(kmer, |kmer, c| kmer.push(*c)))
+    } // end of from_bytes
 }  // end of impl KmerAA128bit
 
 
@@ -165,9 +241,10 @@ impl KmerT for KmerAA32bit {
     // 
     fn push(&self, c : u8) -> Self {
         // shift left 5 bits, insert new base and enforce 0 at upper bits
-        let value_mask :u32 = (0b1 << (5*self.get_nb_base())) - 1;
+        let value_mask :u32 = residue_mask(self.get_nb_base());
         // contrary to dna sequence base in seq is not encoded, we must encode it!!
-        let encoded_base = Alphabet::new().encode(c);
+        let encoded_base = get_residue_code(c);
+        assert!(encoded_base > 0, "push : not a code in alphabet for amino acid : {:x}", c);
         let new_kmer = ((self.aa << 5) & value_mask) | (encoded_base as u32 & 0b11111);
         log::trace!("after push {:#b}", new_kmer);
         KmerAA32bit{aa:new_kmer, nb_base:self.nb_base}
@@ -180,7 +257,7 @@ impl KmerT for KmerAA32bit {
 
     fn dump(&self, bufw: &mut dyn io::Write) -> io::Result<usize> {
         bufw.write(unsafe { &mem::transmute::<u8, [u8;1]>(self.nb_base) }).unwrap();
-        bufw.write(unsafe { &mem::transmute::<u32, [u8;4]>(self.aa) } )
+        bufw.write(unsafe { &mem::transmute::<u32, [u8;4]>(self.get_compressed_value()) } )
     } 
      
 } // end of impl KmerT block for KmerAA128bit
@@ -189,19 +266,40 @@ impl KmerT for KmerAA32bit {
 impl PartialEq for KmerAA32bit {
     // we must check equality of field
     fn eq(&self, other: &KmerAA32bit) -> bool {
-        if (self.aa == other.aa) & (self.nb_base ==other.nb_base) { true } else {false}
+        (self.get_compressed_value() == other.get_compressed_value()) & (self.nb_base == other.nb_base)
     }
 }  // end of impl PartialEq for KmerAA128bit
 
 impl Eq for KmerAA32bit {}
 
 
+// consistent with Eq, only significant bits are hashed
+impl Hash for KmerAA32bit {
+    fn hash<H : Hasher>(&self, state: &mut H) {
+        self.get_compressed_value().hash(state);
+        self.nb_base.hash(state);
+    }
+}  // end of impl Hash for KmerAA32bit
+
+
 
 /// We define ordering as a kind of "lexicographic" order by taking into account first number of base.
 /// The more the number of base the greater. Then we have integer comparison between aa parts
 /// 
 /// 
 
+impl KmerAA32bit {
+    // decoded residues, from left to right
+    fn residues(&self) -> impl Iterator<Item=u8> {
+        let nb_bases = self.nb_base as u32;
+        // get the residue coding part at left end of u32
+        let buf = self.aa.rotate_left(32 - 5 * nb_bases);
+        (1..=nb_bases).map(move |i| Alphabet::decode((buf.rotate_left(5 * i) & 0b11111) as u8))
+    }
+} // end of impl KmerAA32bit
+
+
+
 impl CompressedKmerT for KmerAA32bit {
     type Val = u32;
 
@@ -209,28 +307,17 @@ impl CompressedKmerT for KmerAA32bit {
 
     /// a decompressing function mainly for test and debugging purpose
     fn get_uncompressed_kmer(&self) -> Vec<u8> {
-        let nb_bases = self.nb_base;
-        let alphabet = Alphabet::new();
-        // we treat each block of 5 bits as u8 end call decoder of Alphabet
-        let mut decompressed_kmer = Vec::<u8>::with_capacity(nb_bases as usize);
-        let mut base:u8;
-        //
-        let mut buf = self.aa;
-        // get the base coding part at left end of u32
-        log::debug!("rotating left {}", 8 * size_of::<Self::Val>() - 5 * nb_bases as usize);
-        buf = buf.rotate_left(8 * size_of::<Self::Val>() as u32- 5 * nb_bases as u32);
-        for _ in 0..nb_bases {
-            buf = buf.rotate_left(5);
-            base = (buf & 0b11111) as u8; 
-            decompressed_kmer.push(alphabet.decode(base));
-        }
-        return decompressed_kmer;
+        self.residues().collect()
+    }
+    /// decodes in buf without allocation
+    fn write_uncompressed(&self, buf : &mut Vec<u8>) {
+        buf.extend(self.residues());
     }
 
         /// return the pure value with part coding number of bases reset to 0.
     #[inline(always)]    
     fn get_compressed_value(&self) -> Self::Val {
-        return self.aa;
+        self.aa & residue_mask::<u32>(self.nb_base)
     }
 
     #[inline(always)]    
@@ -246,10 +333,10 @@ impl  Ord for KmerAA32bit {
 
     fn cmp(&self, other: &KmerAA32bit) -> Ordering {
         if self.nb_base != other.nb_base {
-            return (self.nb_base).cmp(&(other.nb_base));
+            (self.nb_base).cmp(&(other.nb_base))
         }
         else {
-            return (self.aa).cmp(&(other.aa));
+            (self.get_compressed_value()).cmp(&(other.get_compressed_value()))
         }
     } // end cmp
 } // end impl Ord for KmerAA128bit 
@@ -267,21 +354,59 @@ impl PartialOrd for KmerAA32bit {
 impl KmerBuilder<KmerAA32bit> for KmerAA32bit {
     /// for Kmer32bit we encode the number of bases in the 4 upper bits
     fn build(val: u32, nb_base : u8) -> KmerAA32bit {
-        KmerAA32bit{aa : val, nb_base}
+        KmerAA32bit{aa : val & residue_mask::<u32>(nb_base), nb_base}
     }
 }
 
+
+impl FromStr for KmerAA32bit {
+    type Err = String;
+
+    /// see [KmerAA32bit::from_bytes]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KmerAA32bit::from_bytes(s.as_bytes())
+    }
+
+}  // end of impl FromStr for KmerAA32bit
+
+
+impl fmt::Display for KmerAA32bit {
+    /// writes the decoded residues, see [FromStr] for the reverse conversion
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.residues().try_for_each(|b| f.write_char(b as char))
+    }
+}  // end of impl Display for KmerAA32bit
+
 //======================================================================
 
 /// A Kmer of amino acids for less than 12 Amino Acid, stored on a u64.
 
-#[derive(Copy,Clone,Hash)]
+/// Bits above the 5 * nb_base bits coding residues are not significant : equality, hashing, ordering
+/// and [get_compressed_value](CompressedKmerT::get_compressed_value) use the masked (canonical) value.
+#[derive(Copy,Clone,Debug,Serialize,Deserialize)]
+#[serde(try_from = "KmerAA64bitRepr")]
 pub struct KmerAA64bit {
     aa      : u64,
     nb_base : u8,
  
 } // end of struct KmerAA64bit
 
+
+// serialized form, checked at deserialization, see check_kmer_codes
+#[derive(Deserialize)]
+struct KmerAA64bitRepr {
+    aa      : u64,
+    nb_base : u8,
+}
+
+impl TryFrom<KmerAA64bitRepr> for KmerAA64bit {
+    type Error = String;
+    fn try_from(repr : KmerAA64bitRepr) -> Result<Self, String> {
+        let aa = check_kmer_codes(repr.aa, repr.nb_base, Self::get_nb_base_max())?;
+        Ok(KmerAA64bit{aa, nb_base : repr.nb_base})
+    }
+}
+
 impl KmerAA64bit {
 
     pub fn new(nb_base : u8) -> Self {
@@ -290,6 +415,14 @@ impl KmerAA64bit {
         }
         KmerAA64bit{aa:0, nb_base}
     }
+
+    /// builds the kmer of residues (in upper or lower case).
+    /// Returns an error if a residue is not in [Alphabet] or if the number of residues is 0 or greater than [get_nb_base_max](CompressedKmerT::get_nb_base_max).
+    pub fn from_bytes(residues : &[u8]) -> Result<Self, String> {
+        let residues = check_kmer_residues(residues, Self::get_nb_base_max())?;
+        let kmer = KmerAA64bit{aa:0, nb_base : residues.len() as u8};
+        Ok(residues.iter().fold(kmer, |kmer, c| kmer.push(*c)))
+    } // end of from_bytes
 }  // end of impl KmerAA64bit
 
 
@@ -304,9 +437,10 @@ impl KmerT for KmerAA64bit {
     // 
     fn push(&self, c : u8) -> Self {
         // shift left 5 bits, insert new base and enforce 0 at upper bits
-        let value_mask :u64 = (0b1 << (5*self.get_nb_base())) - 1;
+        let value_mask :u64 = residue_mask(self.get_nb_base());
         // contrary to dna sequence base in seq is not encoded, we must encode it!!
-        let encoded_base = Alphabet::new().encode(c);
+        let encoded_base = get_residue_code(c);
+        assert!(encoded_base > 0, "push : not a code in alphabet for amino acid : {:x}", c);
         let new_kmer = ((self.aa << 5) & value_mask) | (encoded_base as u64 & 0b11111);
         log::trace!("after push {:#b}", new_kmer);
         KmerAA64bit{aa:new_kmer, nb_base:self.nb_base}
@@ -320,7 +454,7 @@ impl KmerT for KmerAA64bit {
 
     fn dump(&self, bufw: &mut dyn io::Write) -> io::Result<usize> {
         bufw.write(unsafe { &mem::transmute::<u8, [u8;1]>(self.nb_base) }).unwrap();
-        bufw.write(unsafe { &mem::transmute::<u64, [u8;8]>(self.aa) } )
+        bufw.write(unsafe { &mem::transmute::<u64, [u8;8]>(self.get_compressed_value()) } )
     } 
      
 } // end of impl KmerT block for KmerAA64bit
@@ -331,23 +465,32 @@ impl KmerT for KmerAA64bit {
 impl PartialEq for KmerAA64bit {
     // we must check equality of field
     fn eq(&self, other: &KmerAA64bit) -> bool {
-        if (self.aa == other.aa) & (self.nb_base ==other.nb_base) { true } else {false}
+        (self.get_compressed_value() == other.get_compressed_value()) & (self.nb_base == other.nb_base)
     }
 }  // end of impl PartialEq for KmerAA128bit
 
 impl Eq for KmerAA64bit {}
 
 
+// consistent with Eq, only significant bits are hashed
+impl Hash for KmerAA64bit {
+    fn hash<H : Hasher>(&self, state: &mut H) {
+        self.get_compressed_value().hash(state);
+        self.nb_base.hash(state);
+    }
+}  // end of impl Hash for KmerAA64bit
+
+
 
 
 impl  Ord for KmerAA64bit {
 
     fn cmp(&self, other: &KmerAA64bit) -> Ordering {
         if self.nb_base != other.nb_base {
-            return (self.nb_base).cmp(&(other.nb_base));
+            (self.nb_base).cmp(&(other.nb_base))
         }
         else {
-            return (self.aa).cmp(&(other.aa));
+            (self.get_compressed_value()).cmp(&(other.get_compressed_value()))
         }
     } // end cmp
 } // end impl Ord for KmerAA64bit 
@@ -361,6 +504,18 @@ impl PartialOrd for KmerAA64bit {
 } // end impl Ord for KmerAA128bit
 
 
+impl KmerAA64bit {
+    // decoded residues, from left to right
+    fn residues(&self) -> impl Iterator<Item=u8> {
+        let nb_bases = self.nb_base as u32;
+        // get the residue coding part at left end of u64
+        let buf = self.aa.rotate_left(64 - 5 * nb_bases);
+        (1..=nb_bases).map(move |i| Alphabet::decode((buf.rotate_left(5 * i) & 0b11111) as u8))
+    }
+} // end of impl KmerAA64bit
+
+
+
 impl CompressedKmerT for KmerAA64bit {
     type Val = u64;
 
@@ -368,28 +523,17 @@ impl CompressedKmerT for KmerAA64bit {
 
     /// a decompressing function mainly for test and debugging purpose
     fn get_uncompressed_kmer(&self) -> Vec<u8> {
-        let nb_bases = self.nb_base;
-        let alphabet = Alphabet::new();
-        //
-        let mut decompressed_kmer = Vec::<u8>::with_capacity(nb_bases as usize);
-        let mut base:u8;
-        //
-        let mut buf = self.aa;
-        // get the base coding part at left end of u32
-        log::debug!("rotating left {}", 64 - 5 * nb_bases);
-        buf = buf.rotate_left((64 - 5 * nb_bases) as u32);
-        for _ in 0..nb_bases {
-            buf = buf.rotate_left(5);
-            base = (buf & 0b11111) as u8; 
-            decompressed_kmer.push(alphabet.decode(base));
-        }
-        return decompressed_kmer;
+        self.residues().collect()
+    }
+    /// decodes in buf without allocation
+    fn write_uncompressed(&self, buf : &mut Vec<u8>) {
+        buf.extend(self.residues());
     }
 
         /// return the pure value with part coding number of bases reset to 0.
     #[inline(always)]    
     fn get_compressed_value(&self) -> u64 {
-        return self.aa;
+        self.aa & residue_mask::<u64>(self.nb_base)
     }
 
     #[inline(always)]    
@@ -401,14 +545,43 @@ impl CompressedKmerT for KmerAA64bit {
 impl KmerBuilder<KmerAA64bit> for KmerAA64bit {
     /// for Kmer32bit we encode the number of bases in the 4 upper bits
     fn build(val: u64, nb_base : u8) -> KmerAA64bit {
-        KmerAA64bit{aa : val, nb_base}
+        KmerAA64bit{aa : val & residue_mask::<u64>(nb_base), nb_base}
     }
 } // end of  KmerBuilder<KmerAA64bit>
 
 
+impl FromStr for KmerAA64bit {
+    type Err = String;
+
+    /// see [KmerAA64bit::from_bytes]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KmerAA64bit::from_bytes(s.as_bytes())
+    }
+
+}  // end of impl FromStr for KmerAA64bit
+
+
+impl fmt::Display for KmerAA64bit {
+    /// writes the decoded residues, see [FromStr] for the reverse conversion
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.residues().try_for_each(|b| f.write_char(b as char))
+    }
+}  // end of impl Display for KmerAA64bit
+
+
 
 //=======================================================================
 
+/// treatment of characters not in alphabet by [SequenceAA::new_lossy]
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum ResiduePolicy {
+    /// characters are dropped
+    Skip,
+    /// characters are replaced by a residue of alphabet
+    Replace(u8),
+}
+
+
 /// our sequence of Amino Acid is encoded on a byte (even if 5 bits are enough but we do not store sequences yet)
 /// If necessary an implementation on bitvec could be used using a struct SeqIterator as a bridge from
 /// Sequence to KmerSeqIterator
@@ -419,17 +592,46 @@ pub struct SequenceAA {
 
 impl SequenceAA {
 
-    /// allocates and check for compatibility with alphabet
-    pub fn new(str: &[u8]) -> Self {
+    /// allocates and check for compatibility with alphabet. Lower case residues are accepted and stored in upper case.
+    /// Returns an error on the first character not in alphabet, see [SequenceAA::new_lossy] to sanitize.
+    pub fn new(str: &[u8]) -> Result<Self, String> {
+        let mut seq = Vec::<u8>::with_capacity(str.len());
+        if let Some(pos) = uppercase_residues(str, &mut seq) {
+            log::error!("SequenceAA character not in alphabet {:?} at position {}", str[pos] as char, pos);
+            return Err(format!("character not in alphabet {:?} at position {}", str[pos] as char, pos));
+        }
+        Ok(SequenceAA{seq})
+    } // end of new
+
+
+    /// allocates a sequence, lower case residues being converted to upper case and characters not in alphabet
+    /// being treated according to policy. The replacement residue of [ResiduePolicy::Replace] must be in alphabet.
+    pub fn new_lossy(str: &[u8], policy : ResiduePolicy) -> Self {
         let alphabet = Alphabet::new();
-        let _res= str.iter().map(|c| if !alphabet.is_valid_base(*c) {
-            log::error!("SequenceAA str is : {:?}", str);
-            log::error!("SequenceAA character not in alphabet {}", c);
-            std::process::abort();
+        if let ResiduePolicy::Replace(r) = policy {
+            assert!(alphabet.is_valid_base(r), "replacement residue {:?} not in alphabet", r as char);
+        }
+        let mut seq = Vec::<u8>::with_capacity(str.len());
+        // residues are converted by blocks, characters not in alphabet are treated from the first one
+        let first_invalid = match uppercase_residues(str, &mut seq) {
+            Some(pos) => pos,
+            None => return SequenceAA{seq},
+        };
+        let mut kept = first_invalid;
+        for i in first_invalid..seq.len() {
+            let c = seq[i];
+            if alphabet.is_valid_base(c) {
+                seq[kept] = c;
+                kept += 1;
             }
-        );
-        SequenceAA{seq : str.to_vec()}
-    } // end of new
+            else if let ResiduePolicy::Replace(r) = policy {
+                seq[kept] = r;
+                kept += 1;
+            }
+        }
+        seq.truncate(kept);
+        SequenceAA{seq}
+    } // end of new_lossy
 
     pub fn len(&self) -> usize {
         self.seq.len()
@@ -470,16 +672,9 @@ impl SequenceAA {
 impl FromStr for SequenceAA {
     type Err = String;
 
+    /// see [SequenceAA::new]
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        //
-        let sbytes = s.as_bytes();
-        let alphabet = Alphabet::new();
-        //
-        let _res = sbytes.iter().map(|c| if !alphabet.is_valid_base(*c) {
-                panic!("character not in alphabet {}", c);
-            }
-        );
-        Ok(SequenceAA{seq:sbytes.to_vec()})
+        SequenceAA::new(s.as_bytes())
     }
 
 }  // end of FromStr
@@ -495,6 +690,75 @@ impl ToString for SequenceAA {
 }  //  end of ToString
 
 
+
+/// A view on a range of residues of a [SequenceAA], see [SequenceAA::view]. Residues are not copied, so windows or regions
+/// of a long sequence can be sent to kmer iterators (see [KmerSeqIterator::new_view]) and sketchers
+/// (see [SeqSketcherAAT::sketch_compressedkmeraa_views](crate::aautils::setsketchert::SeqSketcherAAT::sketch_compressedkmeraa_views)).
+#[derive(Copy,Clone)]
+pub struct SequenceAAView<'a> {
+    seq : &'a SequenceAA,
+    range : (usize, usize),
+}
+
+
+impl <'a> SequenceAAView<'a> {
+
+    /// returns the viewed sequence
+    pub fn get_sequence(&self) -> &'a SequenceAA {
+        self.seq
+    }
+
+    /// returns the range of viewed residues in the sequence
+    pub fn get_range(&self) -> Range<usize> {
+        self.range.0..self.range.1
+    }
+
+    /// number of residues in view
+    pub fn size(&self) -> usize {
+        self.range.1 - self.range.0
+    }
+
+    /// returns the residue at position pos of the view
+    pub fn get_base(&self, pos : usize) -> u8 {
+        assert!(pos < self.size(), "SequenceAAView::get_base position {} beyond view size {}", pos, self.size());
+        self.seq.seq[self.range.0 + pos]
+    }
+
+    /// residues of the view
+    pub fn as_bytes(&self) -> &'a [u8] {
+        &self.seq.seq[self.range.0..self.range.1]
+    }
+
+    /// a view on a range of this view, positions being relative to the view
+    pub fn view<R : RangeBounds<usize>>(&self, range : R) -> Result<SequenceAAView<'a>, String> {
+        let range = get_bounded_range(&range, self.size());
+        if range.start > range.end || range.end > self.size() {
+            log::error!("SequenceAAView::view bad range {:?}, view size : {}", range, self.size());
+            return Err(format!("bad range {:?}, view size : {}", range, self.size()));
+        }
+        Ok(SequenceAAView{seq : self.seq, range : (self.range.0 + range.start, self.range.0 + range.end)})
+    }
+
+    /// copies residues of the view in a new sequence
+    pub fn to_sequence(&self) -> SequenceAA {
+        SequenceAA{seq : self.as_bytes().to_vec()}
+    }
+
+} // end of impl SequenceAAView
+
+
+
+impl SequenceAA {
+
+    /// returns a view on a range of residues of the sequence, without copy.  
+    /// (2..10 and 2..=9 are the same range, .. is the whole sequence). Returns an error if range goes beyond the end of sequence.
+    pub fn view<R : RangeBounds<usize>>(&self, range : R) -> Result<SequenceAAView<'_>, String> {
+        SequenceAAView{seq : self, range : (0, self.len())}.view(range)
+    }
+
+} // end of impl SequenceAA for views
+
+
 //=========================================================================
 
 
@@ -513,8 +777,6 @@ pub struct KmerSeqIterator<'a, T> where T : CompressedKmerT {
     nb_base: usize,
     /// an iterator for base calling
     sequence: &'a SequenceAA,
-    /// The alphabet needed to encode base from sequence into a (encoded!!) Kmer
-    alphabet_aa : Alphabet,
     /// last position of last kmer returned. At the beginning its None
     previous: Option<T>,
 
@@ -527,27 +789,40 @@ pub struct KmerSeqIterator<'a, T> where T : CompressedKmerT {
 
 impl<'a, T> KmerSeqIterator<'a, T> where T:CompressedKmerT  {
 
+    /// iterator on all kmers of the sequence
     pub fn new(kmer_size : usize, seq : &'a SequenceAA) -> Self {
-        let alphabet_aa = Alphabet::new();
         let range = std::ops::Range{start : 0, end : seq.len()};
         let base_position = 0;
-        KmerSeqIterator{nb_base : kmer_size, sequence : seq, alphabet_aa, previous : None, range, base_position}
+        KmerSeqIterator{nb_base : kmer_size, sequence : seq, previous : None, range, base_position}
     }
 
+    /// iterator on kmers in a range of the sequence, see [Self::set_range]
+    pub fn new_range<R : RangeBounds<usize>>(kmer_size : usize, seq : &'a SequenceAA, range : R) -> std::result::Result<Self, String> {
+        let mut iter = KmerSeqIterator::new(kmer_size, seq);
+        iter.set_range(range)?;
+        Ok(iter)
+    }
 
+    /// iterator on kmers of a [SequenceAAView]. Returns an error if the view is empty.
+    pub fn new_view(kmer_size : usize, view : &SequenceAAView<'a>) -> std::result::Result<Self, String> {
+        KmerSeqIterator::new_range(kmer_size, view.get_sequence(), view.get_range()).map_err(|_| format!("empty view, range : {:?}", view.get_range()))
+    }
 
-    /// defines the range of kmer generation.  
-    /// All bases in kmer generated must be between in first..last last excluded!
-    pub fn set_range(&mut self, first: usize, last:usize) -> std::result::Result<(),()> { 
+    /// defines the range of kmer generation, with the same semantics as the DNA [KmerSeqIterator](crate::base::kmergenerator::KmerSeqIterator::set_range).  
+    /// All bases in kmer generated must be in range (2..10 and 2..=9 are the same range, .. is the whole sequence).
+    /// Returns an error if range is empty or goes beyond the end of sequence.
+    pub fn set_range<R : RangeBounds<usize>>(&mut self, range : R) -> std::result::Result<(),String> { 
+        let Range{start : first, end : last} = get_bounded_range(&range, self.sequence.len());
         if last <= first || last > self.sequence.len() {
             log::error!("KmerSeqIterator set_range first : {}, last : {}, length : {}, seq is : {:?}", 
                             first, last, self.sequence.len(), self.sequence.to_string());
-            return Err(());
+            Err(format!("invalid kmer range {}..{} for a sequence of {} residues", first, last, self.sequence.len()))
         }
         else {
             self.range = Range{start:first, end:last};
             self.base_position = first;
-            return Ok(());
+            self.previous = None;
+            Ok(())
         }
     } // end of set_range
 
@@ -560,52 +835,39 @@ impl <'a, Kmer>  KmerSeqIteratorT for KmerSeqIterator<'a, Kmer>
     
     type KmerVal = Kmer;
 
-        /// iterates...
+        /// iterates. The first kmer of the range is built from its kmer_size residues,
+        /// each following kmer with one shift and one or of the next residue code.
         fn next(&mut self) -> Option<Self::KmerVal> {
+            let end = self.sequence.len().min(self.range.end);
             // check for end of iterator
-            if self.base_position >= self.sequence.len().min(self.range.end) {
+            if self.base_position >= end {
                 log::trace!("iterator exiting at base pos {} range.end {} ", self.base_position, self.range.end);
                 return None;
             }
-            // check for too small seq
-            if self.sequence.len() < self.nb_base as usize {
-                return None;
-            }
-            // now we know we are not at end of iterator
-            // if we do not have a previous we have to contruct first kmer
-            // we have to push a base.
-            //
-            if let Some(kmer) = self.previous {
-                // in fact we have the base to push
-                let next_base = self.sequence.get_base(self.base_position);
-                log::trace!(" next pushing base : {}", char::from_u32(next_base as u32).unwrap());
-                self.previous = Some(kmer.push(next_base));
-                self.base_position += 1;
-                return self.previous;
-            }
-            else {
-                // we are at beginning of kmer construction sequence, we must push kmer_size bases
-                let kmer_size = self.nb_base as usize;
-                let nb_base_bits = 5;           
-                let pos = nb_base_bits*(kmer_size -1);
-                let next_base = self.sequence.get_base(self.base_position);
-                let encoded_base = self.alphabet_aa.encode(next_base);
-                self.base_position += 1;                
-                log::trace!(" init kmerAA base : {}", char::from_u32(next_base as u32).unwrap());
-                let mut new_kmer_val = <Kmer as CompressedKmerT>::Val::from(encoded_base) << pos;
-                for i in 0..(kmer_size-1) {
-                    let next_base = self.sequence.get_base(self.base_position);
-                    log::trace!(" next kmerAA base : {}", char::from_u32(next_base as u32).unwrap());
-                    let encoded_base = self.alphabet_aa.encode(next_base);
-                    let base_val = <Kmer as CompressedKmerT>::Val::from(encoded_base) << (pos - nb_base_bits - nb_base_bits*i);
-                    new_kmer_val = new_kmer_val | base_val;
-                    self.base_position += 1;                
-                }
-                // we know by assert that self.nb_base is less than 12 so can be seen as a u8
-                let new_kmer: Kmer = <Kmer as KmerBuilder<Kmer>>::build(new_kmer_val, self.nb_base as u8);
-                self.previous = Some(new_kmer);
-                return self.previous;
-            }
+            let residues = &self.sequence.seq;
+            let nb_base_bits = 5;
+            let value = match self.previous {
+                Some(kmer) => {
+                    // masking of upper bits is done by build
+                    let code = get_residue_code(residues[self.base_position]);
+                    self.base_position += 1;
+                    (kmer.get_compressed_value() << nb_base_bits) | <Kmer as CompressedKmerT>::Val::from(code)
+                },
+                None => {
+                    // check for too small seq or range
+                    if end - self.base_position < self.nb_base {
+                        return None;
+                    }
+                    let first = self.base_position;
+                    self.base_position += self.nb_base;
+                    residues[first..self.base_position].iter().fold(num::zero::<<Kmer as CompressedKmerT>::Val>(), |value, c| {
+                        (value << nb_base_bits) | <Kmer as CompressedKmerT>::Val::from(get_residue_code(*c))
+                    })
+                },
+            };
+            let new_kmer : Kmer = <Kmer as KmerBuilder<Kmer>>::build(value, self.nb_base as u8);
+            self.previous = Some(new_kmer);
+            self.previous
         } // end of next
 }  // end of impl  KmerSeqIteratorT for KmerSeqIterator<'a, Kmer> 
 
@@ -655,6 +917,10 @@ impl  <T:KmerT> KmerGenerator<T> {
     pub fn generate_weighted_kmer(&self, seq : &SequenceAA) -> FnvHashMap<T,usize>  where Self : KmerGenerationPattern<T> {
         self.generate_kmer_distribution(seq)
     }
+    /// kmers with their multiplicities, iterating over the distribution map without copying it in a Vec
+    pub fn generate_weighted_kmer_iter(&self, seq : &SequenceAA) -> impl Iterator<Item=(T, usize)>  where Self : KmerGenerationPattern<T> {
+        self.generate_kmer_distribution(seq).into_iter()
+    }
     ///
     pub fn get_kmer_size(&self) -> usize { self.kmer_size as usize}
 }  // end of impl KmerGenerator
@@ -664,23 +930,14 @@ impl  <T:KmerT> KmerGenerator<T> {
 /// A utility to convert FnvHashMap<T,usize> to Vec<(T, usize)> 
 
 pub fn hashmap_count_to_vec_count<T:CompressedKmerT + std::hash::Hash + Eq>(kmer_distribution: &FnvHashMap<T,usize>) -> Vec<(T, usize)> {
-    // convert to a Vec
-    let mut hashed_kmers = kmer_distribution.keys();
-    let mut weighted_kmer = Vec::<(T,usize)>::with_capacity(kmer_distribution.len());
-    loop {
-        match hashed_kmers.next() {
-            Some(key) => {
-                if let Some(weight) = kmer_distribution.get(key) {
-                    weighted_kmer.push((*key,*weight));
-                };
-            },
-            None => break,
-        }
-    }
-    //
-    return weighted_kmer;
+    hashmap_count_iter(kmer_distribution).collect()
 }   // end of hashmap_count_to_vec_count
 
+/// iterates over (kmer, count) of a kmer distribution without copying it
+pub fn hashmap_count_iter<T:CompressedKmerT + std::hash::Hash + Eq>(kmer_distribution: &FnvHashMap<T,usize>) -> impl Iterator<Item=(T, usize)> + '_ {
+    kmer_distribution.iter().map(|(kmer, count)| (*kmer, *count))
+}
+
 /*
     Now we have the basics of Kmer Traits we implement KmerSeqIterator and KmerGenerationPattern
     Implementation  of Kmer Generation for KmerAA128bit
@@ -697,13 +954,79 @@ pub(super) fn get_nbkmer_guess(seq : &SequenceAA) -> usize {
 } // end of get_nbkmer_guess
 
 
-// We need a guess to allocate HashMap used with Kmer Generation
-// for vector of sequenc coming from a non concatnated file, we must avoid nb_kmer to sequence length! Find a  good heuristic
-pub(super) fn get_nbkmer_guess_seqs(vseq : &Vec<&SequenceAA>) -> usize {
-    let total_nb_base = vseq.iter().fold(0, |acc, seq | acc+seq.size());
-    let nb_kmer = total_nb_base.min(10_000_000 * (1usize + total_nb_base.ilog2() as usize));
-    nb_kmer
-}  // end of get_nbkmer_guess_seqs
+// generates kmers of residues from their codes, computed by blocks in a scratch buffer of the thread.
+// Kmers containing a character not in alphabet are skipped.
+fn for_each_residue_kmer<Kmer, G>(residues : &[u8], kmer_size : usize, f : G)
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                G : FnMut(Kmer) {
+    with_scratch_bytes(residues.len(), |codes| {
+        encode_residues(residues, codes);
+        let nb_bits = Alphabet::new().get_nb_bits() as usize;
+        for_each_encoded_kmer(codes, kmer_size, nb_bits, |c| if c > 0 { Some(c) } else { None }, f);
+    })
+} // end of for_each_residue_kmer
+
+
+
+// enables generic sketching of amino acid sequences, see module sketching::seqsketchgen
+impl <Kmer> KmerSequence<Kmer> for SequenceAA
+        where Kmer : CompressedKmerT + KmerBuilder<Kmer> {
+    //
+    fn get_nb_bases(&self) -> usize {
+        self.size()
+    }
+
+    fn get_nbkmer_guess(&self) -> usize {
+        get_nbkmer_guess(self)
+    }
+
+    fn for_each_kmer<G : FnMut(Kmer)>(&self, kmer_size : usize, f : G) {
+        for_each_residue_kmer(&self.seq, kmer_size, f);
+    }
+} // end of impl KmerSequence for SequenceAA
+
+
+
+impl <Kmer> KmerSequence<Kmer> for SequenceAAView<'_>
+        where Kmer : CompressedKmerT + KmerBuilder<Kmer> {
+    //
+    fn get_nb_bases(&self) -> usize {
+        self.size()
+    }
+
+    fn get_nbkmer_guess(&self) -> usize {
+        self.size().min(10_000_000 * (1usize + self.size().max(1).ilog2() as usize))
+    }
+
+    fn for_each_kmer<G : FnMut(Kmer)>(&self, kmer_size : usize, f : G) {
+        for_each_residue_kmer(self.as_bytes(), kmer_size, f);
+    }
+} // end of impl KmerSequence for SequenceAAView
+
+
+
+/// An amino acid sequence given as raw bytes, for example a fasta record, sketched without copy in a [SequenceAA].  
+/// Residues are encoded during kmer generation, lower case being accepted. Kmers containing a byte outside the alphabet 
+/// (X, *, B, Z ...) are skipped.
+#[derive(Copy,Clone,Debug)]
+pub struct AaSlice<'a>(pub &'a [u8]);
+
+
+impl <Kmer> KmerSequence<Kmer> for AaSlice<'_>
+        where Kmer : CompressedKmerT + KmerBuilder<Kmer> {
+    //
+    fn get_nb_bases(&self) -> usize {
+        self.0.len()
+    }
+
+    fn get_nbkmer_guess(&self) -> usize {
+        self.0.len().min(10_000_000 * (1usize + self.0.len().ilog2() as usize))
+    }
+
+    fn for_each_kmer<G : FnMut(Kmer)>(&self, kmer_size : usize, f : G) {
+        for_each_residue_kmer(self.0, kmer_size, f);
+    }
+} // end of impl KmerSequence for AaSlice
 
 
 
@@ -774,7 +1097,7 @@ impl KmerGenerationPattern<KmerAA32bit> for KmerGenerator<KmerAA32bit> {
         let nb_kmer = nb_kmer.min(get_nbkmer_guess(&seq));
         let mut kmer_vect = Vec::<KmerAA32bit>::with_capacity(nb_kmer);
         let mut kmeriter = KmerSeqIterator::<KmerAA32bit>::new(kmer_size, seq);
-        kmeriter.set_range(begin, end).unwrap();
+        kmeriter.set_range(begin..end).unwrap();
         loop {
             match kmeriter.next() {
                 Some(kmer) => kmer_vect.push(kmer),
@@ -860,7 +1183,7 @@ impl KmerGenerationPattern<KmerAA64bit> for KmerGenerator<KmerAA64bit> {
         let nb_kmer = nb_kmer.min(get_nbkmer_guess(&seq));
         let mut kmer_vect = Vec::<KmerAA64bit>::with_capacity(nb_kmer);
         let mut kmeriter = KmerSeqIterator::<KmerAA64bit>::new(kmer_size, seq);
-        kmeriter.set_range(begin, end).unwrap();
+        kmeriter.set_range(begin..end).unwrap();
         loop {
             match kmeriter.next() {
                 Some(kmer) => kmer_vect.push(kmer),
@@ -907,13 +1230,12 @@ fn log_init_test() {
         // ask for Kmer of size 4
         let mut seq_iterator = KmerSeqIterator::<KmerAA32bit>::new(4, &seqaa);
         // set a range 
-        seq_iterator.set_range(3,10).unwrap();   // so that we have 4 4-Kmer  (4 = 10-1-kmer_size-3)
+        seq_iterator.set_range(3..10).unwrap();   // so that we have 4 4-Kmer  (4 = 10-1-kmer_size-3)
         // So we must havr from "QIEL" 
         let mut kmer_num = 0;
         let kmer_res = [ "QIEL" ,"IELI", "ELIK",  "LIKL"];
         while let Some(kmer) = seq_iterator.next() {
-            let k_uncompressed = kmer.get_uncompressed_kmer();
-            let kmer_str=  std::str::from_utf8(&k_uncompressed).unwrap();
+            let kmer_str = kmer.to_string();
 //            log::info!(" kmer {} = {:?}", kmer_num, kmer_str);
             if kmer_str != kmer_res[kmer_num] {
                 log::error!(" kmer {} = {:?}", kmer_num, kmer_str);
@@ -942,13 +1264,12 @@ fn log_init_test() {
         // ask for Kmer of size 4
         let mut seq_iterator = KmerSeqIterator::<KmerAA64bit>::new(4, &seqaa);
         // set a range 
-        seq_iterator.set_range(3,10).unwrap();   // so that we have 4 4-Kmer  (4 = 10-1-kmer_size-3)
+        seq_iterator.set_range(3..10).unwrap();   // so that we have 4 4-Kmer  (4 = 10-1-kmer_size-3)
         // So we must havr from "QIEL" 
         let mut kmer_num = 0;
         let kmer_res = [ "QIEL" ,"IELI", "ELIK",  "LIKL"];
         while let Some(kmer) = seq_iterator.next() {
-            let k_uncompressed = kmer.get_uncompressed_kmer();
-            let kmer_str=  std::str::from_utf8(&k_uncompressed).unwrap();
+            let kmer_str = kmer.to_string();
 //            log::info!(" kmer {} = {:?}", kmer_num, kmer_str);
             if kmer_str != kmer_res[kmer_num] {
                 log::error!(" kmer {} = {:?}", kmer_num, kmer_str);
@@ -966,6 +1287,40 @@ fn log_init_test() {
     } // end of test_seqaa_64bit_iterator_range 
 
 
+#[test]
+    fn test_range_semantics_dna_aa() {
+        log_init_test();
+        // DNA and AA iterators must generate the same number of kmers for the same range
+        let dna = crate::base::sequence::Sequence::new(b"ACGTTGCAAGGCTTACCGAT", 2);
+        let seqaa = SequenceAA::from_str("MTEQIELIKLYSTRILALAA").unwrap();
+        let count_dna = |range : Range<usize>| {
+            let mut iter = crate::base::kmergenerator::KmerSeqIterator::<crate::base::kmer32bit::Kmer32bit>::new_range(4, &dna, range).unwrap();
+            let mut nb = 0;
+            while crate::base::kmergenerator::KmerSeqIteratorT::next(&mut iter).is_some() { nb += 1; }
+            nb
+        };
+        let count_aa = |range : Range<usize>| {
+            let mut iter = KmerSeqIterator::<KmerAA32bit>::new_range(4, &seqaa, range).unwrap();
+            let mut nb = 0;
+            while iter.next().is_some() { nb += 1; }
+            nb
+        };
+        for range in [0..20, 3..10, 5..9, 5..8, 19..20] {
+            let expected = (range.len() + 1).saturating_sub(4);
+            assert_eq!(count_dna(range.clone()), expected);
+            assert_eq!(count_aa(range), expected);
+        }
+        // inclusive and unbounded ranges
+        let mut iter = KmerSeqIterator::<KmerAA32bit>::new_range(4, &seqaa, 3..=9).unwrap();
+        assert_eq!(iter.next().unwrap().to_string(), "QIEL");
+        assert!(KmerSeqIterator::<KmerAA32bit>::new_range(4, &seqaa, ..).is_ok());
+        // empty or out of sequence ranges are errors
+        assert!(KmerSeqIterator::<KmerAA32bit>::new_range(4, &seqaa, 5..5).is_err());
+        assert!(KmerSeqIterator::<KmerAA32bit>::new_range(4, &seqaa, 0..21).is_err());
+        assert!(crate::base::kmergenerator::KmerSeqIterator::<crate::base::kmer32bit::Kmer32bit>::new_range(4, &dna, 0..21).is_err());
+    } // end of test_range_semantics_dna_aa
+
+
     // test we arrive at end correctly
 #[test]
     fn test_seqaa_iterator_end() {
@@ -976,15 +1331,13 @@ fn log_init_test() {
         let str = "MTEQIELIKLYSTRILALAAQMPHVGSLDNPD";
         let seqaa = SequenceAA::from_str(str).unwrap();
         // ask for Kmer of size 8
-        let mut last_kmer : &str = "toto";
+        let mut last_kmer = String::from("toto");
         let mut seq_iterator = KmerSeqIterator::<KmerAA64bit>::new(8, &seqaa);
         let mut kmer_num = 0;
-        let mut k_uncompressed;
 
         while let Some(kmer) = seq_iterator.next() {
             log::debug!("in test_seqaa_iterator_end iteration {}", kmer_num);
-            k_uncompressed = kmer.get_uncompressed_kmer();
-            last_kmer =  std::str::from_utf8(&k_uncompressed).unwrap();
+            last_kmer = kmer.to_string();
             log::debug!(" kmer {} = {:?}", kmer_num, last_kmer);
             kmer_num += 1;
         }
@@ -1009,5 +1362,194 @@ fn log_init_test() {
     }
 
 
+#[test]
+    fn test_seqaa_validation() {
+        log_init_test();
+        //
+        assert_eq!(SequenceAA::new(b"mteqie").unwrap().to_string(), "MTEQIE");
+        assert!(SequenceAA::new(b"MTEXQIE").is_err());
+        assert!(SequenceAA::from_str("MTE*").is_err());
+        assert!(SequenceAA::from_str("MTE QIE").is_err());
+        //
+        assert_eq!(SequenceAA::new_lossy(b"MTeX*QIE", ResiduePolicy::Skip).to_string(), "MTEQIE");
+        assert_eq!(SequenceAA::new_lossy(b"MTeX*QIE", ResiduePolicy::Replace(b'A')).to_string(), "MTEAAQIE");
+        // long sequences are validated by blocks, kmers generated from codes are those of the iterator
+        let long : Vec<u8> = b"mteqielikLYSTRILALAAQMPHVGSLDNPDcw".iter().cycle().take(1000).copied().collect();
+        let seqaa = SequenceAA::new(&long).unwrap();
+        assert_eq!(seqaa.to_string(), String::from_utf8(long.to_ascii_uppercase()).unwrap());
+        let mut with_x = long.clone();
+        with_x[777] = b'X';
+        assert_eq!(SequenceAA::new(&with_x).err().unwrap(), "character not in alphabet 'X' at position 777");
+        assert_eq!(SequenceAA::new_lossy(&with_x, ResiduePolicy::Skip).len(), 999);
+        let mut kmers = Vec::<KmerAA64bit>::new();
+        <SequenceAA as KmerSequence<KmerAA64bit>>::for_each_kmer(&seqaa, 11, |kmer| kmers.push(kmer));
+        assert_eq!(kmers, KmerGenerator::<KmerAA64bit>::new(11).generate_kmer(&seqaa));
+        let mut nb_kmers_x = 0;
+        <AaSlice as KmerSequence<KmerAA64bit>>::for_each_kmer(&AaSlice(&with_x), 11, |_| nb_kmers_x += 1);
+        assert_eq!(nb_kmers_x, kmers.len() - 11);
+    } // end of test_seqaa_validation
+
+
+    #[test]
+    fn test_seqaa_view() {
+        log_init_test();
+        let seqaa = SequenceAA::from_str("MTEQIELIKLYSTRILALAA").unwrap();
+        let view = seqaa.view(3..15).unwrap();
+        assert_eq!(view.size(), 12);
+        assert_eq!(view.as_bytes(), b"QIELIKLYSTRI");
+        assert_eq!(view.get_base(1), b'I');
+        assert_eq!(view.to_sequence().to_string(), "QIELIKLYSTRI");
+        let sub = view.view(2..=5).unwrap();
+        assert_eq!(sub.get_range(), 5..9);
+        assert_eq!(sub.as_bytes(), b"ELIK");
+        assert!(seqaa.view(0..21).is_err());
+        assert!(view.view(5..13).is_err());
+        // kmers of a view are kmers of the range
+        let mut from_view = KmerSeqIterator::<KmerAA32bit>::new_view(4, &view).unwrap();
+        let mut from_range = KmerSeqIterator::<KmerAA32bit>::new_range(4, &seqaa, 3..15).unwrap();
+        let mut nb_kmers = 0;
+        while let Some(kmer) = from_view.next() {
+            assert_eq!(Some(kmer), from_range.next());
+            nb_kmers += 1;
+        }
+        assert!(from_range.next().is_none());
+        assert_eq!(nb_kmers, 9);
+    } // end of test_seqaa_view
+
+
+    #[test]
+    fn test_seqaa_rolling_kmers() {
+        log_init_test();
+        // each rolled kmer is the kmer built from its residues, the iterator restarting at set_range
+        let str = "MTEQIELIKLYSTRILALAAQMPHVGSLDNPDWC";
+        let seqaa = SequenceAA::from_str(str).unwrap();
+        for kmer_size in [1, 5, 12] {
+            let mut iter = KmerSeqIterator::<KmerAA64bit>::new(kmer_size, &seqaa);
+            let mut pos = 0;
+            while let Some(kmer) = iter.next() {
+                assert_eq!(kmer, KmerAA64bit::from_bytes(&str.as_bytes()[pos..pos+kmer_size]).unwrap());
+                pos += 1;
+            }
+            assert_eq!(pos, str.len() - kmer_size + 1);
+            iter.set_range(20..27).unwrap();
+            let from_range : Vec<KmerAA64bit> = std::iter::from_fn(|| iter.next()).collect();
+            let expected = if kmer_size <= 7 { 7 - kmer_size + 1 } else { 0 };
+            assert_eq!(from_range.len(), expected);
+            if expected > 0 {
+                assert_eq!(from_range[0], KmerAA64bit::from_bytes(&str.as_bytes()[20..20+kmer_size]).unwrap());
+            }
+            // the distribution counts all kmers, the iterator variant gives the pairs of the map
+            let generator = KmerGenerator::<KmerAA64bit>::new(kmer_size as u8);
+            let distribution = generator.generate_weighted_kmer(&seqaa);
+            assert_eq!(generator.generate_weighted_kmer_iter(&seqaa).map(|(_, c)| c).sum::<usize>(), str.len() - kmer_size + 1);
+            assert!(generator.generate_weighted_kmer_iter(&seqaa).all(|(kmer, c)| distribution.get(&kmer) == Some(&c)));
+        }
+    } // end of test_seqaa_rolling_kmers
+
+
+#[test]
+    fn test_kmeraa_from_bytes() {
+        log_init_test();
+        //
+        let kmer = KmerAA32bit::from_bytes(b"MtEq").unwrap();
+        assert_eq!(kmer.get_uncompressed_kmer(), b"MTEQ".to_vec());
+        assert_eq!(KmerAA32bit::from_str("MTEQ").unwrap(), kmer);
+        assert_eq!(KmerAA32bit::from_str("MTEQIE").unwrap().get_nb_base(), 6);
+        assert!(KmerAA32bit::from_str("MTEQIEL").is_err());
+        assert!(KmerAA32bit::from_str("").is_err());
+        assert!(KmerAA32bit::from_bytes(b"MTX").is_err());
+        // same kmer as generated along a sequence
+        let seq = SequenceAA::from_str("MTEQIELIKLYSTRIL").unwrap();
+        let kmers = KmerGenerator::<KmerAA64bit>::new(10).generate_kmer(&seq);
+        assert_eq!(kmers[2], KmerAA64bit::from_str("EQIELIKLYS").unwrap());
+        assert_eq!(KmerAA64bit::from_str("MTEQIELIKLYS").unwrap().get_uncompressed_kmer(), b"MTEQIELIKLYS".to_vec());
+        assert!(KmerAA64bit::from_bytes(b"MTEQIELIKLYST").is_err());
+    } // end of test_kmeraa_from_bytes
+
+
+#[test]
+    fn test_kmeraa_serde() {
+        log_init_test();
+        //
+        let kmers = vec![KmerAA32bit::from_str("MTEQ").unwrap(), KmerAA32bit::from_str("LYS").unwrap()];
+        let reloaded : Vec<KmerAA32bit> = serde_json::from_str(&serde_json::to_string(&kmers).unwrap()).unwrap();
+        assert_eq!(reloaded, kmers);
+        let kmer = KmerAA64bit::from_str("MTEQIELIKLYS").unwrap();
+        let reloaded : KmerAA64bit = serde_json::from_str(&serde_json::to_string(&kmer).unwrap()).unwrap();
+        assert_eq!(reloaded.get_uncompressed_kmer(), b"MTEQIELIKLYS".to_vec());
+        // number of residues is checked and unused bits are cleared
+        assert!(serde_json::from_str::<KmerAA32bit>(r#"{"aa":4294967295,"nb_base":200}"#).is_err());
+        assert!(serde_json::from_str::<KmerAA32bit>(r#"{"aa":1,"nb_base":7}"#).is_err());
+        assert!(serde_json::from_str::<KmerAA64bit>(r#"{"aa":1,"nb_base":13}"#).is_err());
+        assert!(serde_json::from_str::<KmerAA32bit>(r#"{"aa":4294967295,"nb_base":2}"#).is_err());
+        let reloaded : KmerAA32bit = serde_json::from_str(&format!(r#"{{"aa":{},"nb_base":2}}"#, u32::MAX << 10 | 0b00001_10101)).unwrap();
+        assert_eq!(reloaded.get_uncompressed_kmer(), b"AY".to_vec());
+    } // end of test_kmeraa_serde
+
+
+#[test]
+    fn test_kmeraa_display_roundtrip() {
+        log_init_test();
+        //
+        use rand::{Rng, SeedableRng};
+        let residues = Alphabet::new().bases;
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(3136);
+        let mut random_residues = |nb : usize| -> String {
+            (0..nb).map(|_| residues.as_bytes()[rng.gen_range(0..residues.len())] as char).collect()
+        };
+        for nb in (1..=6).cycle().take(300) {
+            let s = random_residues(nb);
+            let kmer = KmerAA32bit::from_str(&s).unwrap();
+            assert_eq!(kmer.to_string(), s);
+            assert_eq!(KmerAA32bit::from_str(&kmer.to_string()).unwrap(), kmer);
+            //
+            let s = random_residues(2 * nb);
+            let kmer = KmerAA64bit::from_str(&s).unwrap();
+            assert_eq!(kmer.to_string(), s);
+            assert_eq!(KmerAA64bit::from_str(&kmer.to_string()).unwrap(), kmer);
+        }
+    } // end of test_kmeraa_display_roundtrip
+
+
+#[test]
+    fn test_kmeraa_canonical_hash_eq() {
+        log_init_test();
+        //
+        use std::collections::hash_map::DefaultHasher;
+        use std::collections::HashSet;
+        let hash = |kmer : &dyn Fn(&mut DefaultHasher)| { let mut h = DefaultHasher::new(); kmer(&mut h); h.finish() };
+        let residues = Alphabet::new().bases.into_bytes();
+        // all kmers of 1, 2 and 3 residues
+        let mut all_kmers = vec![Vec::<u8>::new()];
+        let mut set32 = HashSet::<KmerAA32bit>::new();
+        let mut set64 = HashSet::<KmerAA64bit>::new();
+        for _ in 0..3 {
+            all_kmers = all_kmers.iter().flat_map(|k| residues.iter().map(move |r| [k.clone(), vec![*r]].concat())).collect();
+            for k in &all_kmers {
+                let kmer = KmerAA32bit::from_bytes(k).unwrap();
+                assert_eq!(kmer.to_string().as_bytes(), k.as_slice());
+                // garbage in non significant bits does not change equality, hash, order nor compressed value
+                let noisy = KmerAA32bit{aa : kmer.aa | (u32::MAX << (5 * k.len())), nb_base : kmer.nb_base};
+                assert_eq!(noisy, kmer);
+                assert_eq!(noisy.cmp(&kmer), Ordering::Equal);
+                assert_eq!(hash(&|h| noisy.hash(h)), hash(&|h| kmer.hash(h)));
+                assert_eq!(KmerAA32bit::build(noisy.aa, noisy.nb_base).aa, kmer.aa);
+                assert!(set32.insert(kmer));
+                assert!(!set32.insert(noisy));
+                //
+                let kmer = KmerAA64bit::from_bytes(k).unwrap();
+                let noisy = KmerAA64bit{aa : kmer.aa | (u64::MAX << (5 * k.len())), nb_base : kmer.nb_base};
+                assert_eq!(noisy, kmer);
+                assert_eq!(noisy.get_compressed_value(), kmer.get_compressed_value());
+                assert_eq!(hash(&|h| noisy.hash(h)), hash(&|h| kmer.hash(h)));
+                assert!(set64.insert(kmer));
+                assert!(!set64.insert(noisy));
+            }
+        }
+        assert_eq!(set32.len(), 20 + 400 + 8000);
+        assert_eq!(set64.len(), set32.len());
+    } // end of test_kmeraa_canonical_hash_eq
+
+
 
 }  // end of mod tests
\ No newline at end of file
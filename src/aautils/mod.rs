@@ -3,4 +3,10 @@
 
 pub mod kmeraa;
 
+pub mod simdaa;
+
 pub mod setsketchert;
+
+pub mod profile;
+
+pub mod translate;
@@ -0,0 +1,129 @@
+//! This module computes fixed dimension kmer profile vectors of amino acid sequences.
+//!
+//! For a kmer size k <= 3 a sequence is mapped to the vector of dimension 20^k of the frequencies of all kmers,
+//! amino acids being indexed in the lexicographic order of [Alphabet] (ACDEFGHIKLMNPQRSTVWY).
+//! Kmers containing a character outside the alphabet are skipped.
+//! The vectors are normalized to sum 1 (if the sequence has at least one kmer).
+//!
+//! The DNA counterpart is in module [base::profile](crate::base::profile).
+
+
+use ndarray::Array2;
+use crate::parallel::*;
+
+use super::kmeraa::{Alphabet, SequenceAA};
+
+
+/// maximal kmer size for amino acid profiles, giving vectors of dimension 20^3 = 8000
+pub const AA_PROFILE_MAX_KMER_SIZE : usize = 3;
+
+
+/// returns the dimension of amino acid profile vectors for kmer size k
+pub fn get_aa_profile_dim(kmer_size : usize) -> usize {
+    20usize.pow(kmer_size as u32)
+}
+
+
+// checks kmer size is in range 1..=AA_PROFILE_MAX_KMER_SIZE
+fn check_aa_profile_kmer_size(kmer_size : usize) -> Result<(), String> {
+    if kmer_size == 0 || kmer_size > AA_PROFILE_MAX_KMER_SIZE {
+        log::error!("aa kmer profile : kmer size must be between 1 and {}, got {}", AA_PROFILE_MAX_KMER_SIZE, kmer_size);
+        return Err(format!("aa kmer profile : kmer size must be between 1 and {}", AA_PROFILE_MAX_KMER_SIZE));
+    }
+    Ok(())
+}
+
+
+// accumulates kmer counts of a sequence in profile, returns number of kmers
+fn fill_aa_profile(seq : &SequenceAA, kmer_size : usize, profile : &mut [f32]) -> usize {
+    // rank of each byte in alphabet, 255 if not in alphabet
+    let mut ranks = [u8::MAX; 256];
+    for (i, c) in Alphabet::new().bases.bytes().enumerate() {
+        ranks[c as usize] = i as u8;
+    }
+    let dim = get_aa_profile_dim(kmer_size);
+    let mut nb_kmers = 0;
+    // rolling index of the current window and number of valid bases at its end
+    let mut index = 0usize;
+    let mut nb_valid = 0usize;
+    for pos in 0..seq.size() {
+        let rank = ranks[seq.get_base(pos) as usize];
+        if rank == u8::MAX {
+            nb_valid = 0;
+            index = 0;
+            continue;
+        }
+        index = (index * 20 + rank as usize) % dim;
+        nb_valid += 1;
+        if nb_valid >= kmer_size {
+            profile[index] += 1.;
+            nb_kmers += 1;
+        }
+    }
+    if nb_kmers > 0 {
+        let inv = 1. / nb_kmers as f32;
+        profile.iter_mut().for_each(|f| *f *= inv);
+    }
+    nb_kmers
+} // end of fill_aa_profile
+
+
+
+/// computes the normalized kmer frequency vector of an amino acid sequence. The vector has dimension 20^k.
+pub fn aa_kmer_profile(seq : &SequenceAA, kmer_size : usize) -> Result<Vec<f32>, String> {
+    check_aa_profile_kmer_size(kmer_size)?;
+    let mut profile = vec![0f32; get_aa_profile_dim(kmer_size)];
+    fill_aa_profile(seq, kmer_size, &mut profile);
+    Ok(profile)
+} // end of aa_kmer_profile
+
+
+
+/// computes in parallel profiles of amino acid sequences. Row i of the returned matrix is the profile of sequence i.
+pub fn aa_kmer_profiles(vseq : &Vec<&SequenceAA>, kmer_size : usize) -> Result<Array2<f32>, String> {
+    check_aa_profile_kmer_size(kmer_size)?;
+    let dim = get_aa_profile_dim(kmer_size);
+    let rows : Vec<Vec<f32>> = vseq.par_iter().map(|seq| {
+            let mut profile = vec![0f32; dim];
+            fill_aa_profile(seq, kmer_size, &mut profile);
+            profile
+        }).collect();
+    let profiles = Array2::<f32>::from_shape_vec((vseq.len(), dim), rows.concat()).unwrap();
+    Ok(profiles)
+} // end of aa_kmer_profiles
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_aa_profile() {
+        log_init_test();
+        let seq = SequenceAA::new(b"ACDAC").unwrap();
+        let profile = aa_kmer_profile(&seq, 2).unwrap();
+        assert_eq!(profile.len(), 400);
+        // kmers AC CD DA AC. A=0 C=1 D=2
+        assert!((profile[1] - 0.5).abs() < 1.0E-6);
+        assert!((profile[22] - 0.25).abs() < 1.0E-6);
+        assert!((profile[40] - 0.25).abs() < 1.0E-6);
+        assert!((profile.iter().sum::<f32>() - 1.).abs() < 1.0E-6);
+        assert!(aa_kmer_profile(&seq, 4).is_err());
+        //
+        let seq2 = SequenceAA::new(b"MKLVWYYQRST").unwrap();
+        let profiles = aa_kmer_profiles(&vec![&seq, &seq2], 3).unwrap();
+        assert_eq!(profiles.row(1).to_vec(), aa_kmer_profile(&seq2, 3).unwrap());
+        // Y Y Q : 19*400 + 19*20 + 13
+        assert!(profiles[[1, 19 * 400 + 19 * 20 + 13]] > 0.);
+    } // end of test_aa_profile
+
+} // end of mod tests
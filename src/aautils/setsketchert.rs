@@ -1,21 +1,16 @@
 //! provide minimal tool to sketch AA sequences by probminhash3a or superminhash and hyperloglog
+//! Sketching algorithms are shared with DNA sequences, see [seqsketchgen](crate::sketching::seqsketchgen).
 
 
 
 
 use std::marker::PhantomData;
+use std::borrow::Borrow;
 
-use std::io::{BufReader, BufWriter };
-
-
-use std::fs::OpenOptions;
 use std::fmt::Debug;
-use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
-use serde_json::to_writer;
 
-use fnv::{FnvHashMap, FnvBuildHasher};
 use std::hash::{BuildHasherDefault, Hasher};
 
 
@@ -29,15 +24,25 @@ use crate::nohasher::*;
 use crate::base::kmertraits::*;
 use crate::aautils::kmeraa::*;
 
-use rayon::prelude::*;
+use crate::parallel::*;
 
-use probminhash::{probminhasher::*, superminhasher::SuperMinHash, densminhash::*, setsketcher::SetSketcher, setsketcher::SetSketchParams};
+use probminhash::{setsketcher::SetSketcher, setsketcher::SetSketchParams};
 
 use crate::sketcharg::{SeqSketcherParams, SketchAlgo};
+use crate::sketching::legacysketch::{LegacySeqSketcher, Aa};
+use crate::sketching::seqsketchgen::*;
+use crate::sketching::sketchstats::*;
+use crate::sketching::filesketch::split_aa_runs;
 
 #[cfg(feature="sminhash2")]
 use probminhash::superminhasher2::SuperMinHash2;
 
+// views on the chunks of an AA sequence, see [get_chunk_ranges]
+fn get_chunk_views_aa(seq : &SequenceAA, kmer_size : usize, chunk_size : usize) -> Result<Vec<SequenceAAView<'_>>, String> {
+    get_chunk_ranges(seq.size(), kmer_size, chunk_size)?.into_iter().map(|range| seq.view(range)).collect()
+}
+
+
 /// This trait gathers interface to all sketcher : SuperMinhash, Probminhash3a, Probminhash3, ...  
 /// 
 /// It is useful when we need to send various sketchers in external functions as a impl Trait.
@@ -53,24 +58,152 @@ pub trait SeqSketcherAAT<Kmer>
     //
     fn get_algo(&self) -> SketchAlgo;
     /// This function receive a vector of concatenated sequences and returns for each sequence a sketch.
-    /// So the function returns a vector of Sketches.
+    /// So the function returns a vector of Sketches, in the order of vseq whatever the order in which threads complete them.
     /// F is a hashing function (possibly just extracting Kmer::Val) to apply to kmer before sending to sketcher.
-    fn sketch_compressedkmeraa<F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Self::Sig> > 
+    fn sketch_compressedkmeraa<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String> 
                     where F : Fn(&Kmer) -> Kmer::Val + Send + Sync; 
     /// This function implements the sketching a File of Sequences, 
     /// (The sequence are not concatenated, so we have many sequences) and make one sketch Vector for the sequence collection.
     /// It returns the same signature as sketch_compressedkmer for interface homogeneity (msg system for //) but
     /// but the returned vec has size 1!
-    fn sketch_compressedkmeraa_seqs<F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Self::Sig> > 
-                    where F : Fn(&Kmer) -> Kmer::Val + Send + Sync;  
-}
+    fn sketch_compressedkmeraa_seqs<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String> 
+                    where F : Fn(&Kmer) -> Kmer::Val + Send + Sync;
+
+    /// same as [Self::sketch_compressedkmeraa] for any iterable of sequences, references (slice, Vec\<&SequenceAA\>...) or owned sequences (a stream of records).  
+    /// Sequences are sketched by blocks of [SKETCH_ITER_BLOCK_SIZE], so owned sequences are released once sketched.
+    fn sketch_compressedkmeraa_iter<I, S, F>(&self, seqs : I, fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+                    where   I : IntoIterator<Item = S>,
+                            S : Borrow<SequenceAA>,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let mut sigs = Vec::<Vec<Self::Sig>>::new();
+        let mut seqs = seqs.into_iter();
+        loop {
+            let block : Vec<S> = seqs.by_ref().take(SKETCH_ITER_BLOCK_SIZE).collect();
+            if block.is_empty() {
+                break;
+            }
+            let vseq : Vec<&SequenceAA> = block.iter().map(|s| s.borrow()).collect();
+            sigs.append(&mut self.sketch_compressedkmeraa(&vseq, &fhash)?);
+        }
+        Ok(sigs)
+    } // end of sketch_compressedkmeraa_iter
+
+    /// same as [Self::sketch_compressedkmeraa_seqs] for any iterable of sequences, references or owned sequences.
+    fn sketch_compressedkmeraa_seqs_iter<I, S, F>(&self, seqs : I, fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+                    where   I : IntoIterator<Item = S>,
+                            S : Borrow<SequenceAA>,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let seqs : Vec<S> = seqs.into_iter().collect();
+        let vseq : Vec<&SequenceAA> = seqs.iter().map(|s| s.borrow()).collect();
+        self.sketch_compressedkmeraa_seqs(&vseq, fhash)
+    } // end of sketch_compressedkmeraa_seqs_iter
+
+    /// sketches each sequence of any [KmerSequence] type (raw bytes in an [AaSlice], views ...) in its own signature.  
+    /// Sketchers built on [seqsketchgen](crate::sketching::seqsketchgen) implement it, so that the *_bytes and *_views methods
+    /// sketch without copy. The default returns None, these methods then copy sequences in [SequenceAA]s.
+    fn sketch_kmerseq_each<Seq, F>(&self, _vseq : &[&Seq], _fhash : &F) -> Option<Result<Vec<Vec<Self::Sig>>, String>>
+                    where   Seq : KmerSequence<Kmer> + ?Sized,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        None
+    }
+
+    /// same as [Self::sketch_kmerseq_each], all sequences being sketched in one signature.
+    fn sketch_kmerseq_seqs<Seq, F>(&self, _vseq : &[&Seq], _fhash : &F) -> Option<Result<Vec<Self::Sig>, String>>
+                    where   Seq : KmerSequence<Kmer> + ?Sized,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        None
+    }
+
+    /// same as [Self::sketch_kmerseq_each], returning also the statistics of the kmers sketched for each sequence,
+    /// collected in the sketching pass after filtering (see [SketchStats]). The default returns None, the *_stats methods
+    /// then compute statistics in an additional pass on kmers.
+    fn sketch_kmerseq_stats<Seq, F>(&self, _vseq : &[&Seq], _fhash : &F) -> Option<Result<SigsWithStats<Self::Sig>, String>>
+                    where   Seq : KmerSequence<Kmer> + ?Sized,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        None
+    }
+
+    /// sketches each raw amino acid sequence (for example a fasta record, lower case accepted) in its own signature.
+    /// Kmers containing a byte outside the amino acid alphabet are skipped.  
+    /// Bytes are sketched without copy by [Self::sketch_kmerseq_each] if the sketcher implements it, otherwise valid runs are copied in [SequenceAA]s.
+    fn sketch_compressedkmeraa_bytes<F>(&self, vseq : &[&[u8]], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+                    where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let slices : Vec<AaSlice> = vseq.iter().map(|raw| AaSlice(raw)).collect();
+        if let Some(sigs) = self.sketch_kmerseq_each(&slices.iter().collect::<Vec<&AaSlice>>(), &fhash) {
+            return sigs;
+        }
+        vseq.iter().map(|raw| self.sketch_compressedkmeraa_bytes_seqs(&[*raw], &fhash).map(|mut v| v.pop().unwrap())).collect()
+    }
+
+    /// sketches raw amino acid sequences in one signature, see [Self::sketch_compressedkmeraa_bytes]. The returned vec has size 1.
+    fn sketch_compressedkmeraa_bytes_seqs<F>(&self, vseq : &[&[u8]], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+                    where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let slices : Vec<AaSlice> = vseq.iter().map(|raw| AaSlice(raw)).collect();
+        if let Some(sig) = self.sketch_kmerseq_seqs(&slices.iter().collect::<Vec<&AaSlice>>(), &fhash) {
+            return sig.map(|sig| vec![sig]);
+        }
+        let alphabet = Alphabet::new();
+        let runs : Vec<SequenceAA> = vseq.iter().flat_map(|raw| split_aa_runs(raw, self.get_kmer_size(), &alphabet)).collect();
+        let refs : Vec<&SequenceAA> = runs.iter().collect();
+        self.sketch_compressedkmeraa_seqs(&refs, fhash)
+    }
+
+    /// sketches each view of a sequence (see [SequenceAA::view]) in its own signature, as [Self::sketch_compressedkmeraa] on the viewed residues.  
+    /// Views are sketched without copy by [Self::sketch_kmerseq_each] if the sketcher implements it, otherwise copied in [SequenceAA]s.
+    fn sketch_compressedkmeraa_views<F>(&self, vseq : &[SequenceAAView], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+                    where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        if let Some(sigs) = self.sketch_kmerseq_each(&vseq.iter().collect::<Vec<&SequenceAAView>>(), &fhash) {
+            return sigs;
+        }
+        let seqs : Vec<SequenceAA> = vseq.iter().map(|v| v.to_sequence()).collect();
+        self.sketch_compressedkmeraa(&seqs.iter().collect::<Vec<&SequenceAA>>(), fhash)
+    }
+
+    /// sketches one huge sequence with all threads : the sequence is split in chunks of chunk_size kmers
+    /// overlapping by kmer_size - 1 residues (see [get_chunk_ranges]), chunks are sketched in parallel and their sketch states merged.
+    /// The signature is the one of [Self::sketch_compressedkmeraa] on the sequence, the returned vec has size 1.  
+    /// The default implementation, used by sketchers whose states cannot be merged (OptDens, RevOptDens, SuperHash2),
+    /// sketches the sequence on one thread.
+    fn sketch_compressedkmeraa_chunked<F>(&self, seq : &SequenceAA, chunk_size : usize, fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+                    where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        get_chunk_ranges(seq.size(), self.get_kmer_size(), chunk_size)?;
+        log::info!("sketch_compressedkmeraa_chunked : {:?} states cannot be merged, sketching on one thread", self.get_algo());
+        self.sketch_compressedkmeraa(&vec![seq], fhash)
+    }
+
+    /// same as [Self::sketch_compressedkmeraa], returning also the kmer statistics of each sequence (see [SketchStats]).
+    /// Statistics are collected in the sketching pass by [Self::sketch_kmerseq_stats] if the sketcher implements it,
+    /// otherwise they need an additional pass on kmers.
+    fn sketch_compressedkmeraa_stats<F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Result<SigsWithStats<Self::Sig>, String>
+                    where   Kmer::Val : std::hash::Hash + Debug,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        if let Some(sigs) = self.sketch_kmerseq_stats(vseq, &fhash) {
+            return sigs;
+        }
+        let sigs = self.sketch_compressedkmeraa(vseq, &fhash)?;
+        Ok((sigs, kmer_stats_each(vseq, self.get_kmer_size(), &fhash)))
+    }
+
+    /// same as [Self::sketch_compressedkmeraa_bytes], returning also the kmer statistics of each sequence,
+    /// including the number of kmers skipped as containing a byte outside the amino acid alphabet.
+    fn sketch_compressedkmeraa_bytes_stats<F>(&self, vseq : &[&[u8]], fhash : F) -> Result<SigsWithStats<Self::Sig>, String>
+                    where   Kmer::Val : std::hash::Hash + Debug,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let slices : Vec<AaSlice> = vseq.iter().map(|raw| AaSlice(raw)).collect();
+        if let Some(sigs) = self.sketch_kmerseq_stats(&slices.iter().collect::<Vec<&AaSlice>>(), &fhash) {
+            return sigs;
+        }
+        let sigs = self.sketch_compressedkmeraa_bytes(vseq, &fhash)?;
+        Ok((sigs, kmer_stats_each(&slices.iter().collect::<Vec<&AaSlice>>(), self.get_kmer_size(), &fhash)))
+    }
+} // end of SeqSketcherAAT
 
 
 //============================================================================================
 
 
 // A structure providing ProbMinHash3a sketching for SequenceAA by implementing the generic trait SeqSketcherAAT<Kmer>
-#[derive(Serialize,Deserialize,Copy,Clone)]
+#[derive(Serialize,Deserialize,Copy,Clone,Debug)]
 pub struct ProbHash3aSketch<Kmer> {
     //
     _kmer_marker: PhantomData<Kmer>,
@@ -85,13 +218,18 @@ impl <Kmer> ProbHash3aSketch<Kmer> {
         ProbHash3aSketch{_kmer_marker : PhantomData,  params : params.clone()}
     }
 
+    /// returns the sketching parameters
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
+
 } // end of impl ProbHash3aSketch
 
 
 
 impl <Kmer> SeqSketcherAAT<Kmer> for ProbHash3aSketch<Kmer> 
         where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
-                Kmer::Val : num::PrimInt + Send + Sync + Debug + Clone + Serialize,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + Clone + Serialize + 'static,
                 KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer> {
 
     type Sig = Kmer::Val;
@@ -109,92 +247,52 @@ impl <Kmer> SeqSketcherAAT<Kmer> for ProbHash3aSketch<Kmer>
         SketchAlgo::PROB3A
     }
 
-    fn sketch_compressedkmeraa<F> (&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Self::Sig> > 
+    fn sketch_compressedkmeraa<F> (&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String> 
             where  F : Fn(&Kmer) -> Kmer::Val + Send + Sync   {
         //
-        log::debug!("entering sketch_compressedkmeraa for probminhash");
+        log::debug!("entering sketch_compressedkmeraa for ProbHash3aSketch");
         //
-        let comput_closure = | seqb : &SequenceAA, i:usize | -> (usize,Vec<Kmer::Val>) {
-            // if we get very large sequence (many Gb length) we must be cautious on size of hashmap; i.e about number of different kmers!!! 
-            let nb_kmer = get_nbkmer_guess(seqb);
-            let mut wb : FnvHashMap::<Kmer::Val,u64> = FnvHashMap::with_capacity_and_hasher(nb_kmer, FnvBuildHasher::default());
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size(), &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        let hashval = fhash(&kmer);
-                        *wb.entry(hashval).or_insert(0) += 1;
-                    },
-                    None => break,
-                }
-            }  // end loop 
-            let mut pminhashb = ProbMinHash3a::<Kmer::Val,NoHashHasher>::new(self.get_sketch_size(), 
-                <Kmer::Val>::default());
-            pminhashb.hash_weigthed_hashmap(&wb);
-            let sigb = pminhashb.get_signature();
-            // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
-        };
-        //
-        let sig_with_rank : Vec::<(usize,Vec<Kmer::Val>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<Kmer::Val>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
-        jaccard_vec
+        Ok(sketch_each(vseq, |seq| probminhash3a_signature(&[seq], &self.params, &fhash)))
     }
 
 
     // recall we revceive a vecor of sequences originating from one file, we return a vector of size 1
     // containing a vecor of size sketch size and type Self::Sig
-    fn sketch_compressedkmeraa_seqs<F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmeraa_seqs<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
             where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
                     F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
                     Kmer::Val : num::PrimInt + Send + Sync + Debug,
                     KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer> {
         //
-        log::debug!("entering  sketch_compressedkmeraa_seqs for Probminhash");
-        // we must estimate nb kmer to avoid reallocation in FnvHashMap
-        let nb_kmer = get_nbkmer_guess_seqs(vseq);
+        log::debug!("entering sketch_compressedkmeraa_seqs for ProbHash3aSketch");
         //
-        let mut wb : FnvHashMap::<Kmer::Val,u64> = FnvHashMap::with_capacity_and_hasher(nb_kmer, FnvBuildHasher::default());
-        //
-        let mut nb_kmer_generated : u64 = 0;
-        // we loop on sequences and generate kmer. TODO // on sequences
-        for seq in vseq {
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size(), seq);
-            kmergen.set_range(0, seq.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        nb_kmer_generated += 1;
-                        let hashval = fhash(&kmer);
-                        *wb.entry(hashval).or_insert(0) += 1;
-                    },
-                    None => break,
-                }
-                if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % 500_000_000 == 0 {
-                    log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
-                }
-            }  // end loop 
-        }
-        let mut pminhashb : ProbMinHash3a<Kmer::Val, NoHashHasher> = ProbMinHash3a::<Kmer::Val,NoHashHasher>::new(self.get_sketch_size(),
-                    <Kmer::Val>::default());
-        //
-        pminhashb.hash_weigthed_hashmap(&wb);
-        let sigb = pminhashb.get_signature();
-        //
-        let mut v = Vec::<Vec<Self::Sig>>::with_capacity(1);
-        v.push(sigb.clone());
-        //
-        return v;
+        Ok(vec![probminhash3a_signature(vseq, &self.params, &fhash)])
+    }
+
+    // raw bytes and views are sketched without copy by the generic functions of seqsketchgen
+    fn sketch_kmerseq_each<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Vec<Self::Sig>>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(Ok(sketch_each(vseq, |seq| probminhash3a_signature(&[seq], &self.params, fhash))))
+    }
+
+    fn sketch_kmerseq_seqs<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(Ok(probminhash3a_signature(vseq, &self.params, fhash)))
+    }
+
+    fn sketch_kmerseq_stats<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<SigsWithStats<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(try_sketch_each_stats(vseq, |seq, stats| Ok(probminhash3a_signature_stats(&[seq], &self.params, fhash, Some(stats)))))
+    }
+
+
+    fn sketch_compressedkmeraa_chunked<F>(&self, seq : &SequenceAA, chunk_size : usize, fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+            where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let views = get_chunk_views_aa(seq, self.get_kmer_size(), chunk_size)?;
+        Ok(vec![probminhash3a_chunked_signature(&views.iter().collect::<Vec<&SequenceAAView>>(), &self.params, &fhash)])
     }
 
 }  // end of impl SeqSketcherAAT for ProHash3aSketch
@@ -207,7 +305,7 @@ impl <Kmer> SeqSketcherAAT<Kmer> for ProbHash3aSketch<Kmer>
 
 /// A structure providing SuperMinHash sketching for SequenceAAT by implementing the generic trait SeqSketcherAAT\<Kmer\>.  
 ///  The type argument S encodes for f32 or f64 as the SuperMinHash can sketch to f32 or f64
-#[derive(Serialize,Deserialize,Copy,Clone)]
+#[derive(Serialize,Deserialize,Copy,Clone,Debug)]
 pub struct SuperHashSketch<Kmer, S : num::Float> {
     //
     _kmer_marker: PhantomData<Kmer>,
@@ -225,13 +323,18 @@ impl <Kmer, S : num::Float> SuperHashSketch<Kmer, S> {
         SuperHashSketch{_kmer_marker : PhantomData, _sig_marker : PhantomData,  params : params.clone()}
     }
 
+    /// returns the sketching parameters
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
+
 } // end of impl SuperHashSketch
 
 impl <Kmer, S> SeqSketcherAAT<Kmer> for SuperHashSketch<Kmer, S> 
         where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
-                Kmer::Val : num::PrimInt + Send + Sync + Debug,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + 'static,
                 KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer>,
-                S : num::Float + SampleUniform + Send + Sync + Debug + Serialize {
+                S : num::Float + SampleUniform + Send + Sync + Debug + Serialize + 'static {
 
     type Sig = S;
 
@@ -251,97 +354,51 @@ impl <Kmer, S> SeqSketcherAAT<Kmer> for SuperHashSketch<Kmer, S>
     /// Kmer::Val is the base type u32, u64 on which compressed kmer representations relies.
     /// F is a hash function returning morally a u32, usize or u64.  
     /// The argument type of the hashing function F specify the type of Kmer to generate along the sequence.  
-    fn sketch_compressedkmeraa<F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmeraa<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
         where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
         //
-        log::debug!("entering sketch_compressedkmeraa for superminhash");
+        log::debug!("entering sketch_compressedkmeraa for SuperHashSketch");
         //
-        let comput_closure = | seqb : &SequenceAA, i:usize | -> (usize,Vec<Self::Sig>) {
-            //
-            log::trace!(" in sketch_compressedkmeraa (SuperMinHash), closure");
-            let mut nb_kmer_generated : u64 = 0;
-            //
-            let bh = BuildHasherDefault::<NoHashHasher>::default();
-            let mut sminhash : SuperMinHash<Self::Sig, Kmer::Val, NoHashHasher>= SuperMinHash::new(self.get_sketch_size(), bh);
-
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size(), &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        nb_kmer_generated += 1;
-                        let hashval = fhash(&kmer);
-                        if sminhash.sketch(&hashval).is_err() {
-                            log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                            std::panic!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                        }
-                    },
-                    None => break,
-                }
-                if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % 500_000_000 == 0 {
-                    log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
-                }
-            }  // end loop 
-            let sigb = sminhash.get_hsketch();
-            // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
-        };
-        //
-        let sig_with_rank : Vec::<(usize,Vec<Self::Sig>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<Self::Sig>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
-        jaccard_vec
+        try_sketch_each(vseq, |seq| superminhash_signature(&[seq], &self.params, &fhash, None))
     } // end of sketch_compressedkmeraa
 
 
 
-    fn sketch_compressedkmeraa_seqs<F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmeraa_seqs<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
             where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
                     F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
                     Kmer::Val : num::PrimInt + Send + Sync + Debug,
                     KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer> {
         //
-        log::debug!("entering  sketch_compressedkmeraa_seqs for SuperMinHashSketch");
-        //
-        let bh = BuildHasherDefault::<NoHashHasher>::default();
-        let mut setsketch : SuperMinHash<Self::Sig, Kmer::Val, NoHashHasher> = SuperMinHash::new(self.get_sketch_size(), bh);
-        //
-        let mut nb_kmer_generated : u64 = 0;
-        // we loop on sequences and generate kmer. TODO // on sequences
-        for seq in vseq {
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size(), &seq);
-            kmergen.set_range(0, seq.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        nb_kmer_generated += 1;
-                        let hashval = fhash(&kmer);
-                        if setsketch.sketch(&hashval).is_err() {
-                            log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                            std::panic!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                        }
-                    },
-                    None => break,
-                }
-                if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % 500_000_000 == 0 {
-                    log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
-                }
-            }  // end loop 
-        }
-        //
-        let mut v: Vec<Vec<S>> = Vec::<Vec<Self::Sig>>::with_capacity(1);
-        let sig = setsketch.get_hsketch();
-        v.push(sig.clone());
+        log::debug!("entering sketch_compressedkmeraa_seqs for SuperHashSketch");
         //
-        return v;
+        Ok(vec![superminhash_signature(vseq, &self.params, &fhash, None)?])
+    }
+
+    // raw bytes and views are sketched without copy by the generic functions of seqsketchgen
+    fn sketch_kmerseq_each<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Vec<Self::Sig>>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(try_sketch_each(vseq, |seq| superminhash_signature(&[seq], &self.params, fhash, None)))
+    }
+
+    fn sketch_kmerseq_seqs<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(superminhash_signature(vseq, &self.params, fhash, None))
+    }
+
+    fn sketch_kmerseq_stats<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<SigsWithStats<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(try_sketch_each_stats(vseq, |seq, stats| superminhash_signature_stats(&[seq], &self.params, fhash, None, Some(stats))))
+    }
+
+
+    fn sketch_compressedkmeraa_chunked<F>(&self, seq : &SequenceAA, chunk_size : usize, fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+            where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let views = get_chunk_views_aa(seq, self.get_kmer_size(), chunk_size)?;
+        Ok(vec![superminhash_chunked_signature(&views.iter().collect::<Vec<&SequenceAAView>>(), &self.params, &fhash)?])
     }
 
 } // end of SuperHashSketch
@@ -363,6 +420,14 @@ pub struct SuperHash2Sketch<Kmer, S: Integer  + Unsigned, H : Hasher + Default>
     params : SeqSketcherParams,
 }
 
+// hashers do not implement Debug
+#[cfg(feature="sminhash2")]
+impl <Kmer, S : Integer + Unsigned, H : Hasher + Default> Debug for SuperHash2Sketch<Kmer, S, H> {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SuperHash2Sketch").field("params", &self.params).finish()
+    }
+}
+
 #[cfg(feature="sminhash2")]
 impl <Kmer, S :  Integer  + Unsigned,  H : Hasher + Default> SuperHash2Sketch<Kmer,S, H> {
 
@@ -371,6 +436,11 @@ impl <Kmer, S :  Integer  + Unsigned,  H : Hasher + Default> SuperHash2Sketch<Km
         SuperHash2Sketch{_kmer_marker : PhantomData, _sig_marker: PhantomData,  build_hasher, params : params.clone()}
     }
 
+    /// returns the sketching parameters
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
+
 } 
 #[cfg(feature="sminhash2")]
 impl <Kmer,S, H> SeqSketcherAAT<Kmer> for SuperHash2Sketch<Kmer, S, H> 
@@ -398,12 +468,12 @@ impl <Kmer,S, H> SeqSketcherAAT<Kmer> for SuperHash2Sketch<Kmer, S, H>
     /// Kmer::Val is the base type u32, u64 on which compressed kmer representations relies.
     /// F is a hash function returning morally a u32, usize or u64.  
     /// The argument type of the hashing function F specify the type of Kmer to generate along the sequence.  
-    fn sketch_compressedkmeraa<F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmeraa<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
         where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
         //
         log::debug!("entering sketch_compressedkmeraa for superminhash2");
         //
-        let comput_closure = | seqb : &SequenceAA, i:usize | -> (usize,Vec<Self::Sig>) {
+        let comput_closure = | seqb : &SequenceAA | -> Result<Vec<Self::Sig>, String> {
             //
             log::debug!(" in sketch_compressedkmeraa (superminhash2), closure");
             let mut nb_kmer_generated : u64 = 0;
@@ -411,7 +481,7 @@ impl <Kmer,S, H> SeqSketcherAAT<Kmer> for SuperHash2Sketch<Kmer, S, H>
             let mut sminhash : SuperMinHash2<Self::Sig, Kmer::Val, H>= SuperMinHash2::new(self.get_sketch_size(), self.build_hasher.clone());
 
             let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size(), &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
+            kmergen.set_range(0..seqb.size()).unwrap();
             loop {
                 match kmergen.next() {
                     Some(kmer) => {
@@ -419,7 +489,7 @@ impl <Kmer,S, H> SeqSketcherAAT<Kmer> for SuperHash2Sketch<Kmer, S, H>
                         let hashval = fhash(&kmer);
                         if sminhash.sketch(&hashval).is_err() {
                             log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                            std::panic!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
+                            return Err(format!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer()));
                         }
                     },
                     None => break,
@@ -430,25 +500,15 @@ impl <Kmer,S, H> SeqSketcherAAT<Kmer> for SuperHash2Sketch<Kmer, S, H>
             }  // end loop 
             let sigb = sminhash.get_hsketch();
             // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
+            Ok(sigb.clone())
         };
         //
-        let sig_with_rank : Vec::<(usize,Vec<Self::Sig>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<Self::Sig>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
-        jaccard_vec
+        // signatures are collected in the order of vseq
+        try_sketch_each(vseq, comput_closure)
     } // end of sketch_compressedkmeraa
 
     #[cfg(feature="sminhash2")]
-    fn sketch_compressedkmeraa_seqs<F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmeraa_seqs<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
             where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
                     F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
                     Kmer::Val : num::PrimInt + Send + Sync + Debug,
@@ -463,7 +523,7 @@ impl <Kmer,S, H> SeqSketcherAAT<Kmer> for SuperHash2Sketch<Kmer, S, H>
         // we loop on sequences and generate kmer. TODO // on sequences
         for seq in vseq {
             let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size(), &seq);
-            kmergen.set_range(0, seq.size()).unwrap();
+            kmergen.set_range(0..seq.size()).unwrap();
             loop {
                 match kmergen.next() {
                     Some(kmer) => {
@@ -471,7 +531,7 @@ impl <Kmer,S, H> SeqSketcherAAT<Kmer> for SuperHash2Sketch<Kmer, S, H>
                         let hashval = fhash(&kmer);
                         if setsketch.sketch(&hashval).is_err() {
                             log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                            std::panic!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
+                            return Err(format!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer()));
                         }
                     },
                     None => break,
@@ -486,7 +546,7 @@ impl <Kmer,S, H> SeqSketcherAAT<Kmer> for SuperHash2Sketch<Kmer, S, H>
         let sig = setsketch.get_hsketch();
         v.push(sig.clone());
         //
-        return v;
+        Ok(v)
     } // end of sketch_compressedkmeraa_seqs
 
 
@@ -495,7 +555,7 @@ impl <Kmer,S, H> SeqSketcherAAT<Kmer> for SuperHash2Sketch<Kmer, S, H>
 
 ///  A structure providing Optimal Densification MinHash (OptDensMinHash in probminhash crate) sketching implementing the generic trait SeqSketcherT\<Kmer\>.  
 ///  The type argument S encodes for f32 or f64 as for SuperMinHash
-#[derive(Serialize,Deserialize,Copy,Clone)]
+#[derive(Serialize,Deserialize,Copy,Clone,Debug)]
 pub struct OptDensHashSketch<Kmer, S : num::Float> {
     //
     _kmer_marker: PhantomData<Kmer>,
@@ -510,14 +570,19 @@ impl <Kmer, S : num::Float> OptDensHashSketch<Kmer,S> {
     pub fn new(params : &SeqSketcherParams) -> Self {
         OptDensHashSketch{_kmer_marker : PhantomData, _sig_marker: PhantomData,  params : params.clone()}
     }
+
+    /// returns the sketching parameters
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
 }  // end of OptDensMinHashSketch
 
 
 impl <Kmer,S> SeqSketcherAAT<Kmer> for OptDensHashSketch<Kmer, S> 
         where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
-                Kmer::Val : num::PrimInt + Send + Sync + Debug,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + 'static,
                 KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer>,
-                S : num::Float + SampleUniform + Send + Sync + Debug + Serialize  {
+                S : num::Float + SampleUniform + Send + Sync + Debug + Serialize + 'static {
 
     type Sig = S;
 
@@ -537,97 +602,46 @@ impl <Kmer,S> SeqSketcherAAT<Kmer> for OptDensHashSketch<Kmer, S>
     /// Kmer::Val is the base type u32, u64 on which compressed kmer representations relies.
     /// F is a hash function returning morally a u32, usize or u64.  
     /// The argument type of the hashing function F specify the type of Kmer to generate along the sequence.  
-    fn sketch_compressedkmeraa<F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmeraa<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
         where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
         //
-        log::debug!("entering sketch_compressedkmeraa for superminhash");
-        //
-        let comput_closure = | seqb : &SequenceAA, i:usize | -> (usize,Vec<Self::Sig>) {
-            //
-            log::trace!(" in sketch_compressedkmeraa (OptDensHashSketch), closure");
-            let mut nb_kmer_generated : u64 = 0;
-            //
-            let bh = BuildHasherDefault::<NoHashHasher>::default();
-            let mut sminhash : OptDensMinHash<Self::Sig, Kmer::Val, NoHashHasher>= OptDensMinHash::new(self.get_sketch_size(), bh);
-
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size(), &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        nb_kmer_generated += 1;
-                        let hashval = fhash(&kmer);
-                        sminhash.sketch(&hashval);
-                    },
-                    None => break,
-                }
-                if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % 500_000_000 == 0 {
-                    log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
-                }
-            }  // end loop 
-            // DO NOT forget to call end_sketch as OptDensMinHash needs to be told we got end of stream
-            sminhash.end_sketch();
-            let sigb = sminhash.get_hsketch();
-            // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
-        };
+        log::debug!("entering sketch_compressedkmeraa for OptDensHashSketch");
         //
-        let sig_with_rank : Vec::<(usize,Vec<Self::Sig>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<Self::Sig>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
-        jaccard_vec
+        Ok(sketch_each(vseq, |seq| optdens_signature(&[seq], &self.params, &fhash, None)))
     } // end of sketch_compressedkmeraa
 
 
 
-    fn sketch_compressedkmeraa_seqs<F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmeraa_seqs<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
             where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
                     F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
                     Kmer::Val : num::PrimInt + Send + Sync + Debug,
                     KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer> {
         //
-        log::debug!("entering  OptDensHashSketch::sketch_compressedkmer_seqs");
+        log::debug!("entering sketch_compressedkmeraa_seqs for OptDensHashSketch");
         //
-        let bh = BuildHasherDefault::<NoHashHasher>::default();
-        let mut setsketch : OptDensMinHash<Self::Sig, Kmer::Val, NoHashHasher> = OptDensMinHash::new(self.get_sketch_size(), bh);
-        //
-        let mut nb_kmer_generated : u64 = 0;
-        // we loop on sequences and generate kmer. TODO // on sequences
-        for seq in vseq {
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size(), &seq);
-            kmergen.set_range(0, seq.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        nb_kmer_generated += 1;
-                        let hashval = fhash(&kmer);
-                        setsketch.sketch(&hashval);
-                    },
-                    None => break,
-                }
-                if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % 500_000_000 == 0 {
-                    log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
-                }
-            }  // end loop 
-        }
-        //
-        let mut v = Vec::<Vec<Self::Sig>>::with_capacity(1);
-        // do not forget to close sketching (it calls densification!)
-        setsketch.end_sketch();
-        let sig = setsketch.get_hsketch();
-        v.push(sig.clone());
-        //
-        return v;
+        Ok(vec![optdens_signature(vseq, &self.params, &fhash, None)])
     } // end of sketch_compressedkmer_seqs
 
+    // raw bytes and views are sketched without copy by the generic functions of seqsketchgen
+    fn sketch_kmerseq_each<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Vec<Self::Sig>>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(Ok(sketch_each(vseq, |seq| optdens_signature(&[seq], &self.params, fhash, None))))
+    }
+
+    fn sketch_kmerseq_seqs<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(Ok(optdens_signature(vseq, &self.params, fhash, None)))
+    }
+
+    fn sketch_kmerseq_stats<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<SigsWithStats<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(try_sketch_each_stats(vseq, |seq, stats| Ok(optdens_signature_stats(&[seq], &self.params, fhash, None, Some(stats)))))
+    }
+
 } // end impl block of SeqSketcherT for SeqSketcherAAT
 
 ///  A structure providing Reverse Optimal Densification MinHash (RevOptDensMinHash in probminhash crate) sketching implementing the generic trait SeqSketcherAAT\<Kmer\>.  
@@ -652,14 +666,19 @@ impl <Kmer, S : num::Float> RevOptDensHashSketch<Kmer,S> {
     pub fn new(params : &SeqSketcherParams) -> Self {
         RevOptDensHashSketch{_kmer_marker : PhantomData, _sig_marker: PhantomData,  params : params.clone()}
     } 
+
+    /// returns the sketching parameters
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
 }  // end of RevOptDensMinHashSketch
 
 
 impl <Kmer,S> SeqSketcherAAT<Kmer> for RevOptDensHashSketch<Kmer, S> 
         where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
-                Kmer::Val : num::PrimInt + Send + Sync + Debug,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + 'static,
                 KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer>,
-                S : num::Float + SampleUniform + Send + Sync + Debug + Serialize  {
+                S : num::Float + SampleUniform + Send + Sync + Debug + Serialize + 'static {
 
     type Sig = S;
 
@@ -675,97 +694,45 @@ impl <Kmer,S> SeqSketcherAAT<Kmer> for RevOptDensHashSketch<Kmer, S>
         SketchAlgo::REVOPTDENS
     }
 
-    fn sketch_compressedkmeraa<F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmeraa<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
     where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
         //
-       log::debug!("entering RevOptDensHashSketch::sketch_compressedkmeraa");
-              //
-            let comput_closure = | seqb : &SequenceAA, i:usize | -> (usize,Vec<Self::Sig>) {
-            //
-            log::debug!(" in sketch_compressedkmer, closure");
-            let mut nb_kmer_generated : u64 = 0;
-            //
-            let bh = BuildHasherDefault::<NoHashHasher>::default();
-            let mut sminhash : RevOptDensMinHash<Self::Sig, Kmer::Val, NoHashHasher>= RevOptDensMinHash::new(self.get_sketch_size(), bh);
-
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size(), &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        nb_kmer_generated += 1;
-                        let hashval = fhash(&kmer);
-                        sminhash.sketch(&hashval);
-                    },
-                    None => break,
-                }
-                if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % 500_000_000 == 0 {
-                    log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
-                }
-            }  // end loop 
-            // do not forget to close sketching (it calls densification!)
-            sminhash.end_sketch();
-            let sigb = sminhash.get_hsketch();
-            // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
-        };
-        //
-        let sig_with_rank : Vec::<(usize,Vec<Self::Sig>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<Self::Sig>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
+        log::debug!("entering sketch_compressedkmeraa for RevOptDensHashSketch");
         //
-        jaccard_vec
+        Ok(sketch_each(vseq, |seq| revoptdens_signature(&[seq], &self.params, &fhash, None)))
     } // end of sketch_compressedkmer
 
 
-    fn sketch_compressedkmeraa_seqs<F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmeraa_seqs<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
             where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
                     F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
                     Kmer::Val : num::PrimInt + Send + Sync + Debug,
                     KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer> {
         //
-        log::debug!("entering  RevOptDensHashSketch::sketch_compressedkmer_seqs");
-        //
-        let bh = BuildHasherDefault::<NoHashHasher>::default();
-        let mut setsketch : RevOptDensMinHash<Self::Sig, Kmer::Val, NoHashHasher> = RevOptDensMinHash::new(self.get_sketch_size(), bh);
-        //
-        let mut nb_kmer_generated : u64 = 0;
-        // we loop on sequences and generate kmer. TODO // on sequences
-        for seq in vseq {
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size(), &seq);
-            kmergen.set_range(0, seq.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        nb_kmer_generated += 1;
-                        let hashval = fhash(&kmer);
-                        setsketch.sketch(&hashval);
-                    },
-                    None => break,
-                }
-                if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % 500_000_000 == 0 {
-                    log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
-                }
-            }  // end loop 
-        }
-        //
-        let mut v = Vec::<Vec<Self::Sig>>::with_capacity(1);
-        // do not forget to close sketching (it calls densification!)
-        setsketch.end_sketch();
-        let sig = setsketch.get_hsketch();
-        v.push(sig.clone());
+        log::debug!("entering sketch_compressedkmeraa_seqs for RevOptDensHashSketch");
         //
-        return v;
+        Ok(vec![revoptdens_signature(vseq, &self.params, &fhash, None)])
     } // end of sketch_compressedkmer_seqs
 
+    // raw bytes and views are sketched without copy by the generic functions of seqsketchgen
+    fn sketch_kmerseq_each<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Vec<Self::Sig>>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(Ok(sketch_each(vseq, |seq| revoptdens_signature(&[seq], &self.params, fhash, None))))
+    }
+
+    fn sketch_kmerseq_seqs<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(Ok(revoptdens_signature(vseq, &self.params, fhash, None)))
+    }
+
+    fn sketch_kmerseq_stats<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<SigsWithStats<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(try_sketch_each_stats(vseq, |seq, stats| Ok(revoptdens_signature_stats(&[seq], &self.params, fhash, None, Some(stats)))))
+    }
+
 } // end of impl SeqSketcherAAT<Kmer> for RevOptDensHashSketch
 
 
@@ -815,7 +782,7 @@ impl Default for HllSeqsThreading {
 ///  **The number of threads is defined by (nb_bases/thread_threshold).ilog(3).min(HllSeqsThreading::nb_iter_thread).max(1).**  
 ///  This is useful if the caller use also multithreading.  
 
-#[derive(Serialize,Deserialize,Copy,Clone)]
+#[derive(Serialize,Deserialize,Copy,Clone,Debug)]
 pub struct HyperLogLogSketch<Kmer, S : num::Integer> {
     //
     params : SeqSketcherParams,
@@ -836,8 +803,13 @@ impl <Kmer, S : Integer> HyperLogLogSketch<Kmer, S> {
         HyperLogLogSketch{params : seq_params.clone(), hll_params, hll_threads, _kmer_marker : PhantomData, _sig_marker: PhantomData}
     }
 
+    /// returns the sketching parameters
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
+
         // building block for sketch_compressedkmer_seqs. sketch a list of sequence and return a sketch to merge!
-    pub fn sketch_compressedkmer_seqs_block<F>(&self, vseq : &[&SequenceAA], fhash : F) -> SetSketcher<S, Kmer::Val, NoHashHasher>
+    pub fn sketch_compressedkmer_seqs_block<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<SetSketcher<S, Kmer::Val, NoHashHasher>, String>
         where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
                 F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
                 Kmer::Val : num::PrimInt + Send + Sync + Debug,
@@ -853,7 +825,7 @@ impl <Kmer, S : Integer> HyperLogLogSketch<Kmer, S> {
     // we loop on sequences and generate kmer. TODO // on sequences
     for seq in vseq {
         let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size(), &seq);
-        kmergen.set_range(0, seq.size()).unwrap();
+        kmergen.set_range(0..seq.size()).unwrap();
         loop {
             match kmergen.next() {
                 Some(kmer) => {
@@ -861,7 +833,7 @@ impl <Kmer, S : Integer> HyperLogLogSketch<Kmer, S> {
                     let hashval = fhash(&kmer);
                     if setsketch.sketch(&hashval).is_err() {
                         log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                        std::panic!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
+                        return Err(format!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer()));
                     }
                 },
                 None => break,
@@ -872,10 +844,29 @@ impl <Kmer, S : Integer> HyperLogLogSketch<Kmer, S> {
         }  // end loop 
     }
     //
-    return setsketch;
+    Ok(setsketch)
 }
 
 
+    // signature of the union of sketches
+    fn merge_setsketches(&self, v_sketch : &[SetSketcher<S, Kmer::Val, NoHashHasher>]) -> Result<Vec<S>, String>
+            where   Kmer : CompressedKmerT,
+                    Kmer::Val : num::PrimInt + Debug,
+                    S : Integer + Bounded + Copy + Clone + FromPrimitive + ToPrimitive + Send + Sync + Debug + Serialize {
+        // we allocate a sketcher that will contain the union. Signature is initialized to 0.
+        let bh = BuildHasherDefault::<NoHashHasher>::default();
+        let mut setsketch : SetSketcher<S, Kmer::Val, NoHashHasher>= SetSketcher::new(self.hll_params, bh);
+        // now we can merge signatures
+        for sketch in v_sketch {
+            if setsketch.merge(sketch).is_err() {
+                log::error!("an error occurred in merging signatures");
+                return Err("an error occurred in merging signatures".to_string());
+            }
+        }
+        Ok(setsketch.get_signature().clone())
+    } // end of merge_setsketches
+
+
 } // en of impl HyperLogLogSketch
 
 
@@ -905,12 +896,12 @@ impl <Kmer,S> SeqSketcherAAT<Kmer> for HyperLogLogSketch<Kmer, S>
     /// Kmer::Val is the base type u32, u64 on which compressed kmer representations relies.
     /// F is a hash function returning morally a u32, usize or u64.  
     /// The argument type of the hashing function F specify the type of Kmer to generate along the sequence.  
-    fn sketch_compressedkmeraa<F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmeraa<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
         where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
         //
         log::debug!("entering sketch_compressedkmeraa for setsketch");
 
-        let comput_closure = | seqb : &SequenceAA, i:usize | -> (usize,Vec<Self::Sig>) {
+        let comput_closure = | seqb : &SequenceAA | -> Result<Vec<Self::Sig>, String> {
             //
             log::debug!(" in sketch_compressedkmeraa, closure");
             let mut nb_kmer_generated : u64 = 0;
@@ -919,7 +910,7 @@ impl <Kmer,S> SeqSketcherAAT<Kmer> for HyperLogLogSketch<Kmer, S>
             let mut setsketch : SetSketcher<Self::Sig, Kmer::Val, NoHashHasher>= SetSketcher::new(self.hll_params, bh);
 
             let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size(), &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
+            kmergen.set_range(0..seqb.size()).unwrap();
             loop {
                 match kmergen.next() {
                     Some(kmer) => {
@@ -927,7 +918,7 @@ impl <Kmer,S> SeqSketcherAAT<Kmer> for HyperLogLogSketch<Kmer, S>
                         let hashval = fhash(&kmer);
                         if setsketch.sketch(&hashval).is_err() {
                             log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                            std::panic!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
+                            return Err(format!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer()));
                         }
                     },
                     None => break,
@@ -938,25 +929,15 @@ impl <Kmer,S> SeqSketcherAAT<Kmer> for HyperLogLogSketch<Kmer, S>
             }  // end loop 
             let sigb = setsketch.get_signature();
             // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
+            Ok(sigb.clone())
         };
         //
-        let sig_with_rank : Vec::<(usize,Vec<Self::Sig>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<Self::Sig>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
-        jaccard_vec
+        // signatures are collected in the order of vseq
+        try_sketch_each(vseq, comput_closure)
     } // end of sketch_compressedkmeraa
 
 
-    fn sketch_compressedkmeraa_seqs<F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmeraa_seqs<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
         where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
                 F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
                 Kmer::Val : num::PrimInt + Send + Sync + Debug,
@@ -968,10 +949,10 @@ impl <Kmer,S> SeqSketcherAAT<Kmer> for HyperLogLogSketch<Kmer, S>
         const BASE_LOG : usize = 3;
         let total_size = vseq.iter().fold(0, |acc, s| acc + s.size() );
         if total_size <= BASE_LOG * thread_threshold {
-            let sketch =  self.sketch_compressedkmer_seqs_block(vseq, fhash);
+            let sketch =  self.sketch_compressedkmer_seqs_block(vseq, fhash)?;
             let mut v_sketch = Vec::<Vec<Self::Sig>>::new();
             v_sketch.push(sketch.get_signature().clone());
-            return v_sketch;
+            return Ok(v_sketch);
         };
         // we must split work in equal parts.  At most 4 , we do not have so many threads. The threading must be treated at a higher level 
         // to correctly dispatch tasks.
@@ -993,204 +974,45 @@ impl <Kmer,S> SeqSketcherAAT<Kmer> for HyperLogLogSketch<Kmer, S>
         }
         frontiers.push(nb_sequences);
         //
-        let v_sketch : Vec<SetSketcher<S, Kmer::Val, NoHashHasher> > = (0..nb_blocks).into_par_iter().map(|i| self.sketch_compressedkmer_seqs_block(&vseq[frontiers[i]..frontiers[i+1]], &fhash)).collect();
-        // we allocate a sketcher that will contain the union. Signature is initialized to 0.
-        let bh = BuildHasherDefault::<NoHashHasher>::default();
-        let mut setsketch : SetSketcher<S, Kmer::Val, NoHashHasher>= SetSketcher::new(self.hll_params, bh);
-        // now we can merge signatures
-        for sketch in v_sketch {
-            let res = setsketch.merge(&sketch);
-            if res.is_err() {
-                log::error!("an error occurred in merging signatures");
-                std::panic!("an error occurred in merging signatures");
-            }
-        }
-        //
-        let sig = setsketch.get_signature();
+        let v_sketch : Vec<SetSketcher<S, Kmer::Val, NoHashHasher> > = (0..nb_blocks).into_par_iter().map(|i| self.sketch_compressedkmer_seqs_block(&vseq[frontiers[i]..frontiers[i+1]], &fhash)).collect::<Result<_,_>>()?;
+        let sig = self.merge_setsketches(&v_sketch)?;
         let mut v = Vec::<Vec<Self::Sig>>::with_capacity(1);
-        v.push(sig.clone());
+        v.push(sig);
         //
-        return v;
+        Ok(v)
     } // end of sketch_compressedkmeraa_seqs
 
-} // end of impl SeqSketcherT
-
-
-//============================================================================================
-
-// TODO this should be factorized with DNA case.
-
-// This structure (deprecated, prefer ProbHash3aSketch and SuperHashSketch) describes the kmer size used in computing sketches and the number of sketch we want.
-/// It gathers methods for sketch_superminhash, sketch_probminhash3a 
-#[derive(Serialize,Deserialize,Copy,Clone)]
-pub struct SeqSketcher {
-    kmer_size : usize,
-    sketch_size : usize
-}  // end of SeqSketcher
-
-
-impl SeqSketcher {
-    /// 
-    pub fn new(kmer_size: usize, sketch_size : usize) -> Self {
-        SeqSketcher{kmer_size, sketch_size}
-    }
-
-    /// returns kmer size
-    pub fn get_kmer_size(&self) -> usize {
-        self.kmer_size
-    }
-
-    /// return sketch size
-    pub fn get_sketch_size(&self) -> usize {
-        self.sketch_size
-    }  
-    
-    /// serialized dump
-    pub fn dump_json(&self, filename : &String) -> Result<(), String> {
-        //
-        let filepath = PathBuf::from(filename.clone());
-        //
-        log::info!("dumping sketching parameters in json file : {}", filename);
-        //
-        let fileres = OpenOptions::new().write(true).create(true).truncate(true).open(&filepath);
-        if fileres.is_err() {
-            log::error!("SeqSketcher dump : dump could not open file {:?}", filepath.as_os_str());
-            println!("SeqSketcher dump: could not open file {:?}", filepath.as_os_str());
-            return Err("SeqSketcher dump failed".to_string());
-        }
-        // 
-        let mut writer = BufWriter::new(fileres.unwrap());
-        let _ = to_writer(&mut writer, &self).unwrap();
-        //
-        Ok(())
-    } // end of dump
-
-
-    /// reload from a json dump
-    pub fn reload_json(dirpath : &Path) -> Result<SeqSketcher, String> {
-        log::info!("in reload_json");
-        //
-        let filepath = dirpath.join("sketchparams_dump.json");
-        let fileres = OpenOptions::new().read(true).open(&filepath);
-        if fileres.is_err() {
-            log::error!("Sketcher reload_json : reload could not open file {:?}", filepath.as_os_str());
-            println!("Sketcher reload_json: could not open file {:?}", filepath.as_os_str());
-            return Err("Sketcher reload_json could not open file".to_string());            
-        }
-        //
-        let loadfile = fileres.unwrap();
-        let reader = BufReader::new(loadfile);
-        let sketch_params:SeqSketcher = serde_json::from_reader(reader).unwrap();
-        //
-        log::info!("SeqSketcher reload, kmer_size : {}, sketch_size : {}", 
-            sketch_params.get_kmer_size(), sketch_params.get_sketch_size());     
-        //
-        Ok(sketch_params)
-    } // end of reload_json
 
-
-
-
-
-    /// A generic version of sketching with probminhash3a on compressed kmer for amino acids
-    pub fn sketch_probminhash3a<'b, Kmer : CompressedKmerT + KmerBuilder<Kmer>, F>(&self, vseq : &'b Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Kmer::Val> >
-        where F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
-              Kmer::Val : num::PrimInt + Send + Sync + Debug,
-              KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer> {
-
-        let comput_closure = | seqb : &SequenceAA, i:usize | -> (usize,Vec<Kmer::Val>) {
-            // if we get very large sequence (many Gb length) we must be cautious on size of hashmap; i.e about number of different kmers!!! 
-            let nb_kmer = get_nbkmer_guess(seqb);
-            let mut wb : FnvHashMap::<Kmer::Val,u64> = FnvHashMap::with_capacity_and_hasher(nb_kmer, FnvBuildHasher::default());
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.kmer_size, &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        let hashval = fhash(&kmer);
-                        *wb.entry(hashval).or_insert(0) += 1;
-                    },
-                    None => break,
+    // chunks are sketched in parallel in SetSketchers and merged
+    fn sketch_compressedkmeraa_chunked<F>(&self, seq : &SequenceAA, chunk_size : usize, fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+            where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let views = get_chunk_views_aa(seq, self.get_kmer_size(), chunk_size)?;
+        let v_sketch : Vec<SetSketcher<S, Kmer::Val, NoHashHasher>> = views.par_iter().map(|view| {
+                let bh = BuildHasherDefault::<NoHashHasher>::default();
+                let mut setsketch : SetSketcher<S, Kmer::Val, NoHashHasher> = SetSketcher::new(self.hll_params, bh);
+                let mut error : Option<String> = None;
+                hash_kmers(&[view], self.get_kmer_size(), &fhash, None, |kmer : &Kmer, hashval| {
+                    if error.is_none() && setsketch.sketch(&hashval).is_err() {
+                        log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
+                        error = Some(format!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer()));
+                    }
+                });
+                match error {
+                    Some(e) => Err(e),
+                    None => Ok(setsketch),
                 }
-            }  // end loop 
-            let mut pminhashb = ProbMinHash3a::<Kmer::Val,NoHashHasher>::new(self.sketch_size, 
-                <Kmer::Val>::default());
-            pminhashb.hash_weigthed_hashmap(&wb);
-            let sigb = pminhashb.get_signature();
-            // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
-        };
-        //
-        let sig_with_rank : Vec::<(usize,Vec<Kmer::Val>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<Kmer::Val>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
-        jaccard_vec
-    }  // end of sketch_probminhash3a
+            }).collect::<Result<_,_>>()?;
+        Ok(vec![self.merge_setsketches(&v_sketch)?])
+    } // end of sketch_compressedkmeraa_chunked
 
+} // end of impl SeqSketcherT
 
-   //  Superminhash
 
-    /// a generic implementation of superminhash  against our standard compressed Kmer types.  
-    /// Kmer::Val is the base type u32, u64 on which compressed kmer representations relies.
-    /// F is a hash function returning morally a u32, usize or u64.  
-    /// The argument type of the hashing function F specify the type of Kmer to generate along the sequence.  
-    pub fn sketch_superminhash<'b, Kmer : CompressedKmerT + KmerBuilder<Kmer>, F>(&self, vseq : &'b Vec<&SequenceAA>, fhash : F) -> Vec<Vec<f64> >
-        where F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
-              Kmer::Val : num::PrimInt + Send + Sync + Debug,
-              KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer> {
-        //
-        log::debug!("entering sketch_superminhash_compressedkmer");
-        //
-        let comput_closure = | seqb : &SequenceAA, i:usize | -> (usize,Vec<f64>) {
-            //
-            log::debug!(" in sketch_superminhash_compressedkmer, closure");
-            //
-            let bh = BuildHasherDefault::<fnv::FnvHasher>::default();
-            // generic arg is here type sent to sketching
-            let mut sminhash : SuperMinHash<f64, Kmer::Val, fnv::FnvHasher>= SuperMinHash::new(self.sketch_size, bh);
-
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.kmer_size, &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        let hashval = fhash(&kmer);
-                        if sminhash.sketch(&hashval).is_err() {
-                            log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                            std::panic!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                        }
-                    },
-                    None => break,
-                }
-            }  // end loop 
-            let sigb = sminhash.get_hsketch();
-            // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
-        };
-        //
-        let sig_with_rank : Vec::<(usize,Vec<f64>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<f64>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
-        jaccard_vec
-    } // end of sketch_superminhash
+//============================================================================================
 
-} // end of SeqSketcher (AA case)
+/// The deprecated sketcher for amino acids (prefer ProbHash3aSketch and SuperHashSketch), shared with the DNA case.
+/// See [legacysketch](crate::sketching::legacysketch).
+pub type SeqSketcher = LegacySeqSketcher<Aa>;
 
 
 
@@ -1279,7 +1101,7 @@ use std::str::FromStr;
         log::debug!("mask = {:b}", mask);
         //
         log::info!("calling sketch_compressedkmeraa for ProbHash3aSketch::<KmerAA64bit>");
-        let signatures = sketcher.sketch_compressedkmeraa(&vseq, kmer_hash_fn); 
+        let signatures = sketcher.sketch_compressedkmeraa(&vseq, kmer_hash_fn).unwrap(); 
         // get distance between the 2 strings  
         let sig1 = &signatures[0];
         let sig2 = &signatures[1];
@@ -1288,6 +1110,12 @@ use std::str::FromStr;
         let dist = inter as f64/sig1.len() as f64;
         log::info!("inter : {:?} length {:?} jaccard distance {:?}", inter, sig1.len(), dist );
         assert!( (dist-0.5).abs() < 1./10.);
+        // slices and owned sequences give same signatures
+        let owned = || vec![SequenceAA::from_str(str1).unwrap(), SequenceAA::from_str(str2).unwrap()];
+        assert_eq!(sketcher.sketch_compressedkmeraa_iter(&owned()[..], kmer_hash_fn).unwrap(), signatures);
+        assert_eq!(sketcher.sketch_compressedkmeraa_iter(owned(), kmer_hash_fn).unwrap(), signatures);
+        let sig_seqs = sketcher.sketch_compressedkmeraa_seqs(&vseq, kmer_hash_fn).unwrap();
+        assert_eq!(sketcher.sketch_compressedkmeraa_seqs_iter(owned(), kmer_hash_fn).unwrap(), sig_seqs);
     } // end of test_seqaa_probminhash_64bit
 
 
@@ -1320,7 +1148,7 @@ use std::str::FromStr;
         // first we sketch with SuperHashSketch<f64>
         log::info!("calling sketch_compressedkmeraa for SuperHashSketch::<KmerAA64bit, f64>");
         let sketcher_f64 = SuperHashSketch::<KmerAA64bit, f64>::new(&sketch_args);
-        let signatures = sketcher_f64.sketch_compressedkmeraa(&vseq, kmer_hash_fn); 
+        let signatures = sketcher_f64.sketch_compressedkmeraa(&vseq, kmer_hash_fn).unwrap(); 
         // get distance between the 2 strings  
         let sig1 = &signatures[0];
         let sig2 = &signatures[1];
@@ -1332,7 +1160,7 @@ use std::str::FromStr;
         //
         // now we sketch with SuperHashSketch<f32>
         let sketcher_f32 = SuperHashSketch::<KmerAA64bit, f32>::new(&sketch_args);
-        let signatures = sketcher_f32.sketch_compressedkmeraa(&vseq, kmer_hash_fn); 
+        let signatures = sketcher_f32.sketch_compressedkmeraa(&vseq, kmer_hash_fn).unwrap(); 
         // get distance between the 2 strings  
         let sig1 = &signatures[0];
         let sig2 = &signatures[1];
@@ -1375,7 +1203,7 @@ use std::str::FromStr;
         // first we sketch with OptDensHashSketch<f64>
         log::info!("calling sketch_compressedkmeraa for OptDensHashSketch::<KmerAA32bit, f64>");
         let sketcher_f64 = OptDensHashSketch::<KmerAA32bit, f64>::new(&sketch_args);
-        let signatures = sketcher_f64.sketch_compressedkmeraa(&vseq, kmer_hash_fn); 
+        let signatures = sketcher_f64.sketch_compressedkmeraa(&vseq, kmer_hash_fn).unwrap(); 
         // get distance between the 2 strings  
         let sig1 = &signatures[0];
         let sig2 = &signatures[1];
@@ -1387,7 +1215,7 @@ use std::str::FromStr;
         //
         // now we sketch with SuperHashSketch<f32>
         let sketcher_f32 = OptDensHashSketch::<KmerAA32bit, f32>::new(&sketch_args);
-        let signatures = sketcher_f32.sketch_compressedkmeraa(&vseq, kmer_hash_fn); 
+        let signatures = sketcher_f32.sketch_compressedkmeraa(&vseq, kmer_hash_fn).unwrap(); 
         // get distance between the 2 strings  
         let sig1 = &signatures[0];
         let sig2 = &signatures[1];
@@ -1479,4 +1307,49 @@ use std::str::FromStr;
     } // end of test_seqaa_probminhash_32bit
 
 
+    #[test]
+    fn test_seqaa_sketch_views() {
+        log_init_test();
+        // sketching views must give the same signatures as sketching copies of the viewed residues
+        let seq = SequenceAA::from_str("MTEQIELIKLYSTRILALAAQMPHVGSLDNPDASAMKRSPLCGSKVTVDVIMQNGKITFDGFEVLAPASEYKNRHASILLSLDATAEACASIAAQNSA").unwrap();
+        let views = vec![seq.view(0..50).unwrap(), seq.view(40..).unwrap(), seq.view(10..30).unwrap()];
+        let copies : Vec<SequenceAA> = views.iter().map(|v| v.to_sequence()).collect();
+        let vcopies : Vec<&SequenceAA> = copies.iter().collect();
+        let fhash = |kmer : &KmerAA64bit| kmer.get_compressed_value();
+        //
+        let params = SeqSketcherParams::new(5, 100, SketchAlgo::PROB3A, DataType::AA);
+        let sketcher = ProbHash3aSketch::<KmerAA64bit>::new(&params);
+        assert_eq!(sketcher.sketch_compressedkmeraa_views(&views, fhash).unwrap(), sketcher.sketch_compressedkmeraa(&vcopies, fhash).unwrap());
+        let sketcher = SuperHashSketch::<KmerAA64bit, f32>::new(&params);
+        assert_eq!(sketcher.sketch_compressedkmeraa_views(&views, fhash).unwrap(), sketcher.sketch_compressedkmeraa(&vcopies, fhash).unwrap());
+        let sketcher = OptDensHashSketch::<KmerAA64bit, f32>::new(&params);
+        assert_eq!(sketcher.sketch_compressedkmeraa_views(&views, fhash).unwrap(), sketcher.sketch_compressedkmeraa(&vcopies, fhash).unwrap());
+        // default implementation
+        #[cfg(feature="sminhash2")]
+        {
+            let sketcher = SuperHash2Sketch::<KmerAA64bit, u64, fnv::FnvHasher>::new(&params, BuildHasherDefault::<fnv::FnvHasher>::default());
+            assert_eq!(sketcher.sketch_compressedkmeraa_views(&views, fhash).unwrap(), sketcher.sketch_compressedkmeraa(&vcopies, fhash).unwrap());
+        }
+    } // end of test_seqaa_sketch_views
+
+
+    #[test]
+    fn test_seqaa_sketch_chunked() {
+        log_init_test();
+        // merging sketch states of overlapping chunks must give the signature of the whole sequence
+        let seq = SequenceAA::from_str("MTEQIELIKLYSTRILALAAQMPHVGSLDNPDASAMKRSPLCGSKVTVDVIMQNGKITFDGFEVLAPASEYKNRHASILLSLDATAEACASIAAQNSA").unwrap();
+        let fhash = |kmer : &KmerAA64bit| kmer.get_compressed_value();
+        let params = SeqSketcherParams::new(5, 100, SketchAlgo::PROB3A, DataType::AA);
+        for chunk_size in [1, 7, 40, 1000] {
+            let sketcher = ProbHash3aSketch::<KmerAA64bit>::new(&params);
+            assert_eq!(sketcher.sketch_compressedkmeraa_chunked(&seq, chunk_size, fhash).unwrap(), sketcher.sketch_compressedkmeraa(&vec![&seq], fhash).unwrap());
+            let sketcher = SuperHashSketch::<KmerAA64bit, f32>::new(&params);
+            assert_eq!(sketcher.sketch_compressedkmeraa_chunked(&seq, chunk_size, fhash).unwrap(), sketcher.sketch_compressedkmeraa(&vec![&seq], fhash).unwrap());
+            let sketcher = HyperLogLogSketch::<KmerAA64bit, u16>::new(&params, SetSketchParams::default(), HllSeqsThreading::default());
+            assert_eq!(sketcher.sketch_compressedkmeraa_chunked(&seq, chunk_size, fhash).unwrap(), sketcher.sketch_compressedkmeraa(&vec![&seq], fhash).unwrap());
+        }
+        assert!(ProbHash3aSketch::<KmerAA64bit>::new(&params).sketch_compressedkmeraa_chunked(&seq, 0, fhash).is_err());
+    } // end of test_seqaa_sketch_chunked
+
+
 }  // end of mod tests in aautils::seqsketchjaccard
\ No newline at end of file
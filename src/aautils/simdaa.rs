@@ -0,0 +1,258 @@
+//! Validation and encoding of amino acid residues by blocks of 16 or 32 bytes.
+//!
+//! Residues are checked for membership in the 20 letters alphabet of [Alphabet](super::kmeraa::Alphabet) (lower case accepted)
+//! and encoded to their 5 bits code, one code by byte. On x86_64 AVX2 or SSSE3 instructions are used (detected at run time),
+//! NEON instructions on aarch64, otherwise a scalar loop with a lookup table. All paths give the same results.
+//!
+//! The simd kernels look up tables indexed by the 2 nibbles of a residue : upper case residues are in 0x41..0x59,
+//! a residue is valid if the bit of its high nibble (4 or 5) is set in the entry of its low nibble.
+
+
+use crate::base::pack2b::{get_simd_level, SimdLevel};
+
+
+// bit 0 : residue 0x4n valid, bit 1 : residue 0x5n valid
+const VALID_LOW : [u8;16] = [2, 3, 2, 3, 3, 1, 3, 3, 1, 3, 0, 1, 1, 1, 1, 0];
+const VALID_HIGH : [u8;16] = [0, 0, 0, 0, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+// codes of residues 0x4n and 0x5n, see Alphabet::encode
+const CODE_4 : [u8;16] = [0, 0b00001, 0, 0b00010, 0b00011, 0b00100, 0b00101, 0b00110, 0b00111, 0b01000, 0, 0b01001, 0b01010, 0b01011, 0b01100, 0];
+const CODE_5 : [u8;16] = [0b01101, 0b01111, 0b10000, 0b10001, 0b10010, 0, 0b10011, 0b10100, 0, 0b10101, 0, 0, 0, 0, 0, 0];
+
+
+// code of each byte, lower case included, 0 if not in alphabet
+const AA_CODE : [u8;256] = {
+    let mut table = [0u8;256];
+    let mut c = 0;
+    while c < 256 {
+        let u = (c as u8).to_ascii_uppercase();
+        table[c] = match u >> 4 {
+            4 => CODE_4[(u & 0x0F) as usize],
+            5 => CODE_5[(u & 0x0F) as usize],
+            _ => 0,
+        };
+        c += 1;
+    }
+    table
+};
+
+
+/// returns the 5 bits code of a residue (lower case accepted), 0 if not in alphabet
+#[inline(always)]
+pub fn get_residue_code(c : u8) -> u8 {
+    AA_CODE[c as usize]
+}
+
+
+/// pushes residues of raw converted to upper case in upper.
+/// Returns the position of the first character not in alphabet, None if all residues are valid.
+pub fn uppercase_residues(raw : &[u8], upper : &mut Vec<u8>) -> Option<usize> {
+    convert_with::<false>(get_simd_level(), raw, upper)
+}
+
+
+/// pushes the 5 bits code of each residue of raw (lower case accepted) in codes, 0 for a character not in alphabet.
+/// Returns the position of the first character not in alphabet, None if all residues are valid.
+pub fn encode_residues(raw : &[u8], codes : &mut Vec<u8>) -> Option<usize> {
+    convert_with::<true>(get_simd_level(), raw, codes)
+}
+
+
+
+// converts raw to upper case or to codes, returns first invalid position
+fn convert_with<const ENCODE : bool>(level : SimdLevel, raw : &[u8], out : &mut Vec<u8>) -> Option<usize> {
+    let start = out.len();
+    out.resize(start + raw.len(), 0);
+    let out = &mut out[start..];
+    let (done, first_invalid) = match level {
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => unsafe { x86::convert_avx2::<ENCODE>(raw, out) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Ssse3 => unsafe { x86::convert_ssse3::<ENCODE>(raw, out) },
+        #[cfg(target_arch = "aarch64")]
+        SimdLevel::Neon => unsafe { neon::convert_neon::<ENCODE>(raw, out) },
+        _ => (0, None),
+    };
+    let tail_invalid = convert_scalar::<ENCODE>(&raw[done..], &mut out[done..]).map(|pos| done + pos);
+    first_invalid.or(tail_invalid)
+} // end of convert_with
+
+
+
+fn convert_scalar<const ENCODE : bool>(raw : &[u8], out : &mut [u8]) -> Option<usize> {
+    let mut first_invalid = None;
+    for (i, (c, o)) in raw.iter().zip(out.iter_mut()).enumerate() {
+        let code = AA_CODE[*c as usize];
+        *o = if ENCODE { code } else { c.to_ascii_uppercase() };
+        if code == 0 && first_invalid.is_none() {
+            first_invalid = Some(i);
+        }
+    }
+    first_invalid
+} // end of convert_scalar
+
+
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+    use super::{VALID_LOW, VALID_HIGH, CODE_4, CODE_5};
+
+    // defines a kernel converting vectors of $lanes bytes with the intrinsics of this vector width
+    macro_rules! define_convert(
+        ($name:ident, $feature:literal, $vec:ty, $lanes:expr, $load:ident, $store:ident, $set1:ident, $broadcast:expr,
+                $and:ident, $andnot:ident, $or:ident, $sub:ident, $min:ident, $cmpeq:ident, $shuffle:ident, $srli:ident, $movemask:ident) => (
+            #[target_feature(enable = $feature)]
+            pub(super) unsafe fn $name<const ENCODE : bool>(raw : &[u8], out : &mut [u8]) -> (usize, Option<usize>) {
+                let valid_low : $vec = $broadcast(&VALID_LOW);
+                let valid_high : $vec = $broadcast(&VALID_HIGH);
+                let code_4 : $vec = $broadcast(&CODE_4);
+                let code_5 : $vec = $broadcast(&CODE_5);
+                let mut first_invalid = None;
+                let mut done = 0;
+                while done + $lanes <= raw.len() {
+                    let c = $load(raw.as_ptr().add(done) as *const $vec);
+                    // lower case residues : c - 'a' < 26
+                    let t = $sub(c, $set1(b'a' as i8));
+                    let lower = $cmpeq($min(t, $set1(25)), t);
+                    let u = $sub(c, $and(lower, $set1(0x20)));
+                    let low = $and(u, $set1(0x0F));
+                    let high = $and($srli(u, 4), $set1(0x0F));
+                    let valid = $and($shuffle(valid_low, low), $shuffle(valid_high, high));
+                    let invalid = $cmpeq(valid, $set1(0));
+                    let res = if ENCODE {
+                        let is_5 = $cmpeq(high, $set1(5));
+                        let code = $or($and(is_5, $shuffle(code_5, low)), $andnot(is_5, $shuffle(code_4, low)));
+                        $andnot(invalid, code)
+                    }
+                    else {
+                        u
+                    };
+                    $store(out.as_mut_ptr().add(done) as *mut $vec, res);
+                    let mask = $movemask(invalid);
+                    if mask != 0 && first_invalid.is_none() {
+                        first_invalid = Some(done + mask.trailing_zeros() as usize);
+                    }
+                    done += $lanes;
+                }
+                (done, first_invalid)
+            }
+        ) // end of match
+    ); // end of macro define_convert
+
+
+    #[inline(always)]
+    unsafe fn load_table(table : &[u8;16]) -> __m128i {
+        _mm_loadu_si128(table.as_ptr() as *const __m128i)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn broadcast_table(table : &[u8;16]) -> __m256i {
+        _mm256_broadcastsi128_si256(_mm_loadu_si128(table.as_ptr() as *const __m128i))
+    }
+
+    define_convert!(convert_ssse3, "ssse3", __m128i, 16, _mm_loadu_si128, _mm_storeu_si128, _mm_set1_epi8, load_table,
+        _mm_and_si128, _mm_andnot_si128, _mm_or_si128, _mm_sub_epi8, _mm_min_epu8, _mm_cmpeq_epi8, _mm_shuffle_epi8, _mm_srli_epi16, _mm_movemask_epi8);
+
+    define_convert!(convert_avx2, "avx2", __m256i, 32, _mm256_loadu_si256, _mm256_storeu_si256, _mm256_set1_epi8, broadcast_table,
+        _mm256_and_si256, _mm256_andnot_si256, _mm256_or_si256, _mm256_sub_epi8, _mm256_min_epu8, _mm256_cmpeq_epi8, _mm256_shuffle_epi8, _mm256_srli_epi16, _mm256_movemask_epi8);
+
+} // end of mod x86
+
+
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+    use super::{VALID_LOW, VALID_HIGH, CODE_4, CODE_5};
+
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn convert_neon<const ENCODE : bool>(raw : &[u8], out : &mut [u8]) -> (usize, Option<usize>) {
+        let valid_low = vld1q_u8(VALID_LOW.as_ptr());
+        let valid_high = vld1q_u8(VALID_HIGH.as_ptr());
+        let code_4 = vld1q_u8(CODE_4.as_ptr());
+        let code_5 = vld1q_u8(CODE_5.as_ptr());
+        let mut first_invalid = None;
+        let mut done = 0;
+        while done + 16 <= raw.len() {
+            let c = vld1q_u8(raw.as_ptr().add(done));
+            let lower = vcltq_u8(vsubq_u8(c, vdupq_n_u8(b'a')), vdupq_n_u8(26));
+            let u = vsubq_u8(c, vandq_u8(lower, vdupq_n_u8(0x20)));
+            let low = vandq_u8(u, vdupq_n_u8(0x0F));
+            let high = vshrq_n_u8(u, 4);
+            let valid = vandq_u8(vqtbl1q_u8(valid_low, low), vqtbl1q_u8(valid_high, high));
+            let invalid = vceqq_u8(valid, vdupq_n_u8(0));
+            let res = if ENCODE {
+                let code = vbslq_u8(vceqq_u8(high, vdupq_n_u8(5)), vqtbl1q_u8(code_5, low), vqtbl1q_u8(code_4, low));
+                vbicq_u8(code, invalid)
+            }
+            else {
+                u
+            };
+            vst1q_u8(out.as_mut_ptr().add(done), res);
+            if first_invalid.is_none() && vmaxvq_u8(invalid) != 0 {
+                first_invalid = raw[done..done+16].iter().position(|c| super::AA_CODE[*c as usize] == 0).map(|pos| done + pos);
+            }
+            done += 16;
+        }
+        (done, first_invalid)
+    } // end of convert_neon
+
+} // end of mod neon
+
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::aautils::kmeraa::Alphabet;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    fn available_levels() -> Vec<SimdLevel> {
+        let mut levels = vec![SimdLevel::Scalar];
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("ssse3") { levels.push(SimdLevel::Ssse3); }
+            if is_x86_feature_detected!("avx2") { levels.push(SimdLevel::Avx2); }
+        }
+        #[cfg(target_arch = "aarch64")]
+        levels.push(SimdLevel::Neon);
+        levels
+    }
+
+    #[test]
+    fn test_simdaa_levels() {
+        log_init_test();
+        let alphabet = Alphabet::new();
+        // all bytes, each byte at every position modulo 32
+        let raw : Vec<u8> = (0..=255u8).chain(0..=255u8).chain(b"MKLVWYYQRSTacdefghiklmnpqrstvwy".iter().copied()).collect();
+        for level in available_levels() {
+            for len in [0, 15, 16, 31, 32, 33, 100, raw.len()] {
+                let mut upper = Vec::new();
+                let mut codes = Vec::new();
+                let invalid = convert_with::<false>(level, &raw[..len], &mut upper);
+                assert_eq!(convert_with::<true>(level, &raw[..len], &mut codes), invalid);
+                assert_eq!(invalid, raw[..len].iter().position(|c| !alphabet.is_valid_base(c.to_ascii_uppercase())), "level {:?}", level);
+                for (i, c) in raw[..len].iter().enumerate() {
+                    assert_eq!(upper[i], c.to_ascii_uppercase(), "level {:?} byte {}", level, c);
+                    let expected = if alphabet.is_valid_base(upper[i]) { alphabet.encode(upper[i]) } else { 0 };
+                    assert_eq!(codes[i], expected, "level {:?} byte {}", level, c);
+                }
+            }
+            // a valid block followed by an invalid residue beyond the first vector
+            let mut protein = b"MTEQIELIKLYSTRILALAAMTEQIELIKLYSTRILALAA".to_vec();
+            assert_eq!(convert_with::<false>(level, &protein, &mut Vec::new()), None);
+            protein[35] = b'X';
+            assert_eq!(convert_with::<true>(level, &protein, &mut Vec::new()), Some(35));
+        }
+    } // end of test_simdaa_levels
+
+} // end of mod tests
@@ -0,0 +1,171 @@
+//! Translation of DNA sequences in amino acids with the standard genetic code : six-frame translation and ORF extraction.
+//!
+//! Frames are numbered +1, +2, +3 on the forward strand (translation beginning at base 0, 1, 2) and -1, -2, -3 on the
+//! reverse complement (beginning at base 0, 1, 2 of the reverse complement). Trailing bases not making a codon are dropped.
+//! Stop codons are translated in '*' and codons with a base other than ACGT (lower case accepted, U read as T) in 'X'.
+//!
+//! Translated sequences can be sketched as amino acid sequences, '*' and 'X' not being in [Alphabet](super::kmeraa::Alphabet)
+//! they break kmers (see [SequenceAA::new_lossy](super::kmeraa::SequenceAA::new_lossy)).
+
+
+/// the standard genetic code (NCBI table 1, also giving the amino acids of bacterial table 11), codons in TCAG order
+const STANDARD_CODE : &[u8; 64] = b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG";
+
+/// the six frames, in the order of [six_frame_translation]
+pub const FRAMES : [i8; 6] = [1, 2, 3, -1, -2, -3];
+
+
+// code of a base in TCAG order, None for other characters
+#[inline]
+fn get_base_rank(base : u8) -> Option<usize> {
+    match base.to_ascii_uppercase() {
+        b'T' | b'U' => Some(0),
+        b'C' => Some(1),
+        b'A' => Some(2),
+        b'G' => Some(3),
+        _ => None,
+    }
+}
+
+
+/// translates a codon (3 bases) : '*' for a stop codon, 'X' if a base is not in ACGT(U)
+pub fn translate_codon(codon : &[u8]) -> u8 {
+    assert_eq!(codon.len(), 3, "a codon has 3 bases");
+    match (get_base_rank(codon[0]), get_base_rank(codon[1]), get_base_rank(codon[2])) {
+        (Some(b1), Some(b2), Some(b3)) => STANDARD_CODE[16 * b1 + 4 * b2 + b3],
+        _ => b'X',
+    }
+}
+
+
+/// translates dna from its first base, trailing bases not making a codon being dropped
+pub fn translate(dna : &[u8]) -> Vec<u8> {
+    dna.chunks_exact(3).map(translate_codon).collect()
+}
+
+
+/// returns the reverse complement of an ASCII DNA sequence, in upper case. Characters other than ACGTU give N.
+pub fn reverse_complement(dna : &[u8]) -> Vec<u8> {
+    dna.iter().rev().map(|b| match b.to_ascii_uppercase() {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'T' | b'U' => b'A',
+            _ => b'N',
+        }).collect()
+}
+
+
+// translation of a frame given the reverse complement for negative frames
+fn translate_frame_with(dna : &[u8], revcomp : &[u8], frame : i8) -> Vec<u8> {
+    assert!(FRAMES.contains(&frame), "frame must be in +-1, +-2, +-3");
+    let offset = (frame.unsigned_abs() - 1) as usize;
+    let strand = if frame > 0 { dna } else { revcomp };
+    if strand.len() <= offset { Vec::new() } else { translate(&strand[offset..]) }
+}
+
+
+/// translates a frame (+1, +2, +3, -1, -2 or -3) of dna
+pub fn translate_frame(dna : &[u8], frame : i8) -> Vec<u8> {
+    let revcomp = if frame < 0 { reverse_complement(dna) } else { Vec::new() };
+    translate_frame_with(dna, &revcomp, frame)
+}
+
+
+/// returns the translations of the six frames, in the order of [FRAMES]
+pub fn six_frame_translation(dna : &[u8]) -> Vec<(i8, Vec<u8>)> {
+    let revcomp = reverse_complement(dna);
+    FRAMES.iter().map(|frame| (*frame, translate_frame_with(dna, &revcomp, *frame))).collect()
+}
+
+
+
+/// an open reading frame
+#[derive(Clone,Debug,PartialEq)]
+pub struct Orf {
+    /// frame of translation
+    pub frame : i8,
+    /// first base of the ORF on the forward strand (0 based)
+    pub start : usize,
+    /// end (excluded) of the ORF on the forward strand, stop codon excluded
+    pub end : usize,
+    /// translation, without stop
+    pub protein : Vec<u8>,
+}
+
+
+/// returns the ORFs of the six frames of dna having at least min_len residues, in the order of frames then of positions in the frame.
+/// An ORF ends before a stop codon or at the end of the frame (the sequence can be a fragment). If require_start it begins
+/// with the first M (ATG) following the previous stop, otherwise just after the previous stop (stop to stop translation).
+pub fn find_orfs(dna : &[u8], min_len : usize, require_start : bool) -> Vec<Orf> {
+    let mut orfs = Vec::<Orf>::new();
+    let n = dna.len();
+    for (frame, protein) in six_frame_translation(dna) {
+        let offset = (frame.unsigned_abs() - 1) as usize;
+        let mut begin = 0;
+        for segment in protein.split(|aa| *aa == b'*') {
+            let skip = if require_start { segment.iter().position(|aa| *aa == b'M').unwrap_or(segment.len()) } else { 0 };
+            let (a, b) = (begin + skip, begin + segment.len());
+            if b - a >= min_len.max(1) {
+                let (start, end) = if frame > 0 { (offset + 3 * a, offset + 3 * b) } else { (n - offset - 3 * b, n - offset - 3 * a) };
+                orfs.push(Orf{frame, start, end, protein : segment[skip..].to_vec()});
+            }
+            begin += segment.len() + 1;
+        }
+    }
+    orfs
+} // end of find_orfs
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_translate() {
+        let dna = b"ATGGCCATTGTAATGGGCCGCTGAAAGGGTGCCCGATAG";
+        assert_eq!(translate(dna), b"MAIVMGR*KGAR*");
+        assert_eq!(translate(&dna.to_ascii_lowercase()), b"MAIVMGR*KGAR*");
+        assert_eq!(translate(b"AUGNCCTT"), b"MX");
+        assert_eq!(reverse_complement(b"ACGTTn"), b"NAACGT");
+        // the reverse complement of a sequence has its frames exchanged
+        let frames = six_frame_translation(dna);
+        let rc_frames = six_frame_translation(&reverse_complement(dna));
+        assert_eq!(frames[0], (1, translate(dna)));
+        for i in 0..3 {
+            assert_eq!(frames[i].1, rc_frames[i + 3].1);
+            assert_eq!(translate_frame(dna, frames[i + 3].0), frames[i + 3].1);
+        }
+        assert!(translate_frame(b"AC", -3).is_empty());
+    } // end of test_translate
+
+
+    #[test]
+    fn test_find_orfs() {
+        // frame +1 : M A I V M G R stop K G A R stop
+        let dna = b"ATGGCCATTGTAATGGGCCGCTGAAAGGGTGCCCGATAG";
+        let orfs = find_orfs(dna, 4, true);
+        let forward : Vec<&Orf> = orfs.iter().filter(|o| o.frame == 1).collect();
+        assert_eq!(forward.len(), 1);
+        assert_eq!((forward[0].start, forward[0].end), (0, 21));
+        assert_eq!(forward[0].protein, b"MAIVMGR");
+        // stop to stop, KGAR follows the first stop
+        let orfs = find_orfs(dna, 4, false);
+        assert!(orfs.iter().any(|o| o.frame == 1 && o.protein == b"KGAR" && (o.start, o.end) == (24, 36)));
+        // on the reverse strand, coordinates are those of the forward strand
+        let rc = reverse_complement(dna);
+        let orfs = find_orfs(&rc, 4, true);
+        let orf = orfs.iter().find(|o| o.frame == -1).unwrap();
+        assert_eq!(orf.protein, b"MAIVMGR");
+        assert_eq!((orf.start, orf.end), (rc.len() - 21, rc.len()));
+        assert_eq!(translate(&reverse_complement(&rc[orf.start..orf.end])), b"MAIVMGR");
+        // an ORF can end with the sequence
+        let orfs = find_orfs(b"CCATGAAACCCGGG", 3, true);
+        assert!(orfs.iter().any(|o| o.frame == 3 && o.protein == b"MKPG" && (o.start, o.end) == (2, 14)));
+    } // end of test_find_orfs
+
+} // end of mod tests
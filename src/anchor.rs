@@ -390,7 +390,7 @@ impl  <T:CompressedKmerT> FastaAnchors<T> {
         let nb_bases = self.slice_params.get_kmer_size();
         let mut num_read = 0;
         let start_t = std::time::Instant::now();
-        let mut reader = needletail::parse_fastx_file(&path).expect("expecting valid filename");
+        let mut reader = crate::io::open_fastx_file(path).expect("expecting valid filename");
         while let Some(record) = reader.next() {
             let seqrec = record.expect("invalid record");
             let nb_bad = count_non_acgt(&seqrec.seq());
@@ -67,6 +67,14 @@ pub trait BaseCompress {
 /// - T maps to 0b11
 /// note : the lexicographic order is preserved and bases are conjugated
 
+/// decodes a 2 bits base code (only the 2 lower bits are used), without building an Alphabet2b
+#[inline(always)]
+pub fn decode_2b(c : u8) -> u8 {
+    b"ACGT"[(c & 0b11) as usize]
+}
+
+
+
 pub struct Alphabet2b {
     pub bases: String,
 }
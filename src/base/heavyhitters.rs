@@ -0,0 +1,225 @@
+//! This module extracts the most frequent kmers of a stream of sequences with bounded memory.
+//!
+//! It implements the Space-Saving algorithm of Metwally, Agrawal and El Abbadi (2005).
+//! At most capacity kmers are monitored, each with a counter. When a kmer not monitored arrives and all counters
+//! are used, the kmer with minimal counter is evicted and the new kmer inherits its counter + 1.
+//! Each counter overestimates the true count by at most the count of the evicted kmer, which is recorded as the error.
+//! Any kmer with frequency greater than N/capacity (N total number of kmers seen) is guaranteed to be monitored.
+//!
+//! It is useful to detect adapters, contaminants or repeats in read sets without counting all kmers.
+
+
+use std::collections::BTreeSet;
+use std::hash::Hash;
+
+use fnv::FnvHashMap;
+
+pub use super::{kmertraits::*, kmergenerator::*, sequence::Sequence};
+
+
+/// A kmer returned by [SpaceSaving::get_top] with its estimated count.
+#[derive(Copy,Clone,Debug)]
+pub struct HeavyHitter<Kmer> {
+    /// the kmer
+    pub kmer : Kmer,
+    /// estimated count, an upper bound of the true count
+    pub count : u64,
+    /// maximal overestimation of count. count - error is a lower bound of the true count.
+    pub error : u64,
+}
+
+
+/// Space-Saving structure monitoring at most capacity kmers.
+/// If canonical is true a kmer and its reverse complement are identified.
+pub struct SpaceSaving<Kmer> where Kmer : CompressedKmerT + Hash {
+    /// max number of kmers monitored
+    capacity : usize,
+    /// kmer size
+    kmer_size : usize,
+    /// true if kmers are stored canonically
+    canonical : bool,
+    /// total number of kmers seen
+    nb_seen : u64,
+    /// monitored kmers with their (count, error)
+    counters : FnvHashMap<Kmer, (u64, u64)>,
+    /// monitored kmers ordered by count, to find the minimal counter
+    by_count : BTreeSet<(u64, Kmer)>,
+} // end of SpaceSaving
+
+
+
+impl <Kmer> SpaceSaving<Kmer> where Kmer : CompressedKmerT + Hash {
+
+    /// allocates a structure monitoring at most capacity kmers
+    pub fn new(capacity : usize, kmer_size : usize, canonical : bool) -> Self {
+        let capacity = capacity.max(1);
+        let mut counters = FnvHashMap::<Kmer, (u64, u64)>::default();
+        counters.reserve(capacity);
+        SpaceSaving{capacity, kmer_size, canonical, nb_seen : 0, counters, by_count : BTreeSet::new()}
+    }
+
+    /// returns kmer size
+    pub fn get_kmer_size(&self) -> usize {
+        self.kmer_size
+    }
+
+    /// returns the max number of kmers monitored
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// returns the total number of kmers seen
+    pub fn get_nb_seen(&self) -> u64 {
+        self.nb_seen
+    }
+
+    // get the key under which a kmer is stored
+    #[inline]
+    fn get_key(&self, kmer : &Kmer) -> Kmer {
+        if self.canonical {
+            kmer.reverse_complement().min(*kmer)
+        }
+        else {
+            *kmer
+        }
+    }
+
+    /// records one occurrence of a kmer
+    pub fn insert(&mut self, kmer : &Kmer) {
+        let key = self.get_key(kmer);
+        self.nb_seen += 1;
+        if let Some((count, _)) = self.counters.get_mut(&key) {
+            self.by_count.remove(&(*count, key));
+            *count += 1;
+            self.by_count.insert((*count, key));
+        }
+        else if self.counters.len() < self.capacity {
+            self.counters.insert(key, (1, 0));
+            self.by_count.insert((1, key));
+        }
+        else {
+            // evict the kmer with minimal count
+            let (min_count, evicted) = self.by_count.pop_first().unwrap();
+            self.counters.remove(&evicted);
+            self.counters.insert(key, (min_count + 1, min_count));
+            self.by_count.insert((min_count + 1, key));
+        }
+    } // end of insert
+
+    /// returns the n monitored kmers with highest counts, sorted by decreasing count
+    pub fn get_top(&self, n : usize) -> Vec<HeavyHitter<Kmer>> {
+        self.by_count.iter().rev().take(n).map(|(count, kmer)| {
+                let error = self.counters.get(kmer).unwrap().1;
+                HeavyHitter{kmer : *kmer, count : *count, error}
+            }).collect()
+    } // end of get_top
+
+    /// returns the monitored kmers whose count lower bound is at least frequency * number of kmers seen.
+    /// These kmers are guaranteed to be frequent. frequency should be greater than 1/capacity.
+    pub fn get_frequent(&self, frequency : f64) -> Vec<HeavyHitter<Kmer>> {
+        let threshold = (frequency * self.nb_seen as f64).ceil() as u64;
+        self.get_top(self.capacity).into_iter().filter(|h| h.count - h.error >= threshold).collect()
+    }
+
+} // end of impl SpaceSaving
+
+
+
+impl <Kmer> SpaceSaving<Kmer> where Kmer : CompressedKmerT + KmerBuilder<Kmer> + Hash {
+
+    /// records all kmers of sequences (which must be 2 bits encoded)
+    pub fn insert_sequences(&mut self, vseq : &Vec<&Sequence>) {
+        let kmer_size = self.kmer_size as u8;
+        for seq in vseq {
+            let mut kmergen = KmerSeqIterator::<Kmer>::new(kmer_size, seq);
+            kmergen.set_range(0..seq.size()).unwrap();
+            while let Some(kmer) = kmergen.next() {
+                self.insert(&kmer);
+            }
+        }
+    } // end of insert_sequences
+
+} // end of impl SpaceSaving
+
+
+
+/// returns the nb_top most frequent kmers of sequences, monitoring at most capacity kmers.
+/// capacity should be several times nb_top for counts to be accurate.
+pub fn get_heavy_hitters<Kmer>(vseq : &Vec<&Sequence>, kmer_size : usize, canonical : bool, capacity : usize, nb_top : usize) -> Vec<HeavyHitter<Kmer>>
+        where Kmer : CompressedKmerT + KmerBuilder<Kmer> + Hash {
+    //
+    log::debug!("entering get_heavy_hitters, nb sequences : {}, capacity : {}", vseq.len(), capacity);
+    let mut space_saving = SpaceSaving::<Kmer>::new(capacity.max(nb_top), kmer_size, canonical);
+    space_saving.insert_sequences(vseq);
+    space_saving.get_top(nb_top)
+} // end of get_heavy_hitters
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::base::kmer32bit::Kmer32bit;
+    use crate::base::kmertable::KmerCountTable;
+    use rand::{Rng, SeedableRng};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_heavy_hitters_adapter() {
+        log_init_test();
+        // random reads, half of them ending with an adapter
+        let adapter = b"AGATCGGAAGAGCACACGTCTGAACTCCAGTCAC";
+        let alphabet = b"ACGT";
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(117);
+        let mut seqs = Vec::<Sequence>::new();
+        for i in 0..200 {
+            let mut read : Vec<u8> = (0..100).map(|_| alphabet[rng.gen_range(0..4)]).collect();
+            if i % 2 == 0 {
+                read.extend_from_slice(adapter);
+            }
+            seqs.push(Sequence::new(&read, 2));
+        }
+        let vseq : Vec<&Sequence> = seqs.iter().collect();
+        let kmer_size = 12;
+        let nb_top = 10;
+        let top = get_heavy_hitters::<Kmer32bit>(&vseq, kmer_size, false, 500, nb_top);
+        assert_eq!(top.len(), nb_top);
+        // all top kmers come from adapter and their count bounds contain the exact count
+        let exact = KmerCountTable::<Kmer32bit>::from_sequences(&vseq, kmer_size, false);
+        let adapter_seq = Sequence::new(adapter, 2);
+        let adapter_kmers : Vec<Kmer32bit> = KmerGenerator::new(kmer_size as u8).generate_kmer(&adapter_seq);
+        for h in &top {
+            assert!(adapter_kmers.contains(&h.kmer));
+            let true_count = exact.get_count(&h.kmer) as u64;
+            assert!(h.count - h.error <= true_count && true_count <= h.count);
+        }
+    } // end of test_heavy_hitters_adapter
+
+
+    #[test]
+    fn test_space_saving_frequent() {
+        log_init_test();
+        let kmers : Vec<Kmer32bit> = ["AAAA", "CCCC", "GGGG", "TTTT", "ACGT"].iter().map(|s| s.parse().unwrap()).collect();
+        let mut space_saving = SpaceSaving::<Kmer32bit>::new(3, 4, false);
+        for i in 0..1000 {
+            space_saving.insert(&kmers[0]);
+            if i % 2 == 0 {
+                space_saving.insert(&kmers[1]);
+            }
+            space_saving.insert(&kmers[2 + i % 3]);
+        }
+        assert_eq!(space_saving.get_nb_seen(), 2500);
+        let frequent = space_saving.get_frequent(0.3);
+        assert_eq!(frequent.len(), 1);
+        assert_eq!(frequent[0].kmer, kmers[0]);
+        assert_eq!(frequent[0].count, 1000);
+    } // end of test_space_saving_frequent
+
+} // end of mod tests
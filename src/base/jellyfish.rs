@@ -0,0 +1,331 @@
+//! This module provides import/export of kmer counts in the formats of the [Jellyfish](https://github.com/gmarcais/Jellyfish) counter.
+//!
+//! - The text formats are those produced by *jellyfish dump* : the column format (one line "kmer count" per kmer)
+//!   and the fasta format (a line ">count" followed by a line with the kmer).
+//! - The binary format is the *binary/sorted* format of jellyfish : a header made of its length written as 9 decimal digits
+//!   followed by a json description, then a list of records (kmer, count).
+//!   The kmer is 2 bits encoded (A=0, C=1, G=2, T=3, first base in the most significant bits) and written in little endian
+//!   on key_len/8 bytes, the count is written in little endian on counter_len bytes.
+//!   The binary format needs the feature *formats* (default) for its json header.
+//!
+//! Kmers are reloaded into a [KmerCountTable](super::kmertable::KmerCountTable).
+
+
+use std::io;
+use std::io::{Write, BufRead, BufReader, BufWriter};
+#[cfg(feature="formats")]
+use std::io::Read;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::hash::Hash;
+use std::str::FromStr;
+
+#[cfg(feature="formats")]
+use serde_json::json;
+
+pub use super::{kmertraits::*, kmertable::*, alphabet::*};
+
+
+/// the text formats of jellyfish dump
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum JellyfishTextFormat {
+    /// lines "kmer count" as obtained by jellyfish dump -c
+    Column,
+    /// lines ">count" followed by kmer, default output of jellyfish dump
+    Fasta,
+}
+
+/// size in bytes of counts in our binary dumps
+#[cfg(feature="formats")]
+const JELLYFISH_COUNTER_LEN : usize = 4;
+
+/// size in bytes of the header length prefix in jellyfish binary files
+#[cfg(feature="formats")]
+const JELLYFISH_HEADER_LEN_SIZE : usize = 9;
+
+
+// decode a 2bits encoded kmer value (first base in upper bits) in ascii
+#[cfg(feature="formats")]
+fn decode_kmer_value(val : u64, kmer_size : usize) -> String {
+    let alphabet = Alphabet2b::new();
+    let mut s = String::with_capacity(kmer_size);
+    for i in 0..kmer_size {
+        let base = ((val >> (2 * (kmer_size - 1 - i))) & 0b11) as u8;
+        s.push(alphabet.decode(base) as char);
+    }
+    s
+} // end of decode_kmer_value
+
+
+// encode an ascii kmer in 2 bits, first base in upper bits
+#[cfg(feature="formats")]
+fn encode_kmer_value(kmer : &[u8]) -> u64 {
+    let alphabet = Alphabet2b::new();
+    kmer.iter().fold(0u64, |acc, b| (acc << 2) | alphabet.encode(*b) as u64)
+}
+
+
+/// dumps a count table in a jellyfish text format. Returns the number of kmers dumped.
+pub fn dump_jellyfish_text<Kmer>(table : &KmerCountTable<Kmer>, fname : &Path, format : JellyfishTextFormat) -> io::Result<usize>
+        where Kmer : CompressedKmerT + Hash + std::fmt::Display {
+    //
+    log::info!("dumping kmer counts in jellyfish text format in file : {:?}", fname);
+    //
+    let file = OpenOptions::new().write(true).create(true).truncate(true).open(fname)?;
+    let mut bufw = BufWriter::new(file);
+    let mut nb_dumped = 0;
+    for (kmer, count) in table.get_sorted_counts() {
+        match format {
+            JellyfishTextFormat::Column => writeln!(bufw, "{} {}", kmer, count)?,
+            JellyfishTextFormat::Fasta  => writeln!(bufw, ">{}\n{}", count, kmer)?,
+        }
+        nb_dumped += 1;
+    }
+    bufw.flush()?;
+    //
+    log::info!("dump_jellyfish_text, number of kmer dumped : {}", nb_dumped);
+    Ok(nb_dumped)
+} // end of dump_jellyfish_text
+
+
+
+/// reloads a jellyfish text dump, possibly compressed (column or fasta format is detected from the first line).
+/// As jellyfish dumps do not keep track of counting mode, the user must tell if counts are canonical.
+pub fn reload_jellyfish_text<Kmer>(fname : &Path, canonical : bool) -> Result<KmerCountTable<Kmer>, String>
+        where Kmer : CompressedKmerT + Hash + FromStr {
+    //
+    log::info!("reloading jellyfish text dump : {:?}", fname);
+    //
+    let fileres = crate::io::open_decompressed(fname);
+    if fileres.is_err() {
+        log::error!("reload_jellyfish_text could not open file {:?}", fname.as_os_str());
+        println!("reload_jellyfish_text could not open file {:?}", fname.as_os_str());
+        return Err("reload_jellyfish_text could not open file".to_string());
+    }
+    let reader = BufReader::new(fileres.unwrap());
+    let mut table : Option<KmerCountTable<Kmer>> = None;
+    // in fasta format we store count waiting for kmer line
+    let mut pending_count : Option<u32> = None;
+    //
+    for (numline, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (kmer_str, count) = if let Some(c) = line.strip_prefix('>') {
+            let count = c.trim().parse::<u32>().map_err(|_| format!("bad count at line {}", numline+1))?;
+            pending_count = Some(count);
+            continue;
+        }
+        else if let Some(count) = pending_count.take() {
+            (line, count)
+        }
+        else {
+            let mut fields = line.split_whitespace();
+            let kmer_str = fields.next().unwrap();
+            let count = match fields.next() {
+                Some(c) => c.parse::<u32>().map_err(|_| format!("bad count at line {}", numline+1))?,
+                None    => return Err(format!("no count at line {}", numline+1)),
+            };
+            (kmer_str, count)
+        };
+        let kmer = match Kmer::from_str(kmer_str) {
+            Ok(kmer) => kmer,
+            Err(_)   => {
+                log::error!("reload_jellyfish_text cannot decode kmer {} at line {}", kmer_str, numline+1);
+                return Err(format!("cannot decode kmer at line {}", numline+1));
+            }
+        };
+        let table = table.get_or_insert_with(|| KmerCountTable::<Kmer>::new(kmer_str.len(), canonical));
+        if kmer_str.len() != table.get_kmer_size() {
+            return Err(format!("inconsistent kmer size at line {}", numline+1));
+        }
+        table.add_count(&kmer, count);
+    }
+    //
+    match table {
+        Some(table) => {
+            log::info!("reload_jellyfish_text, number of kmer loaded : {}", table.len());
+            Ok(table)
+        },
+        None => Err("reload_jellyfish_text : no kmer in file".to_string()),
+    }
+} // end of reload_jellyfish_text
+
+
+
+/// dumps a count table in jellyfish binary/sorted format. Returns the number of kmers dumped.
+/// Counts are written on 4 bytes.
+#[cfg(feature="formats")]
+pub fn dump_jellyfish_binary<Kmer>(table : &KmerCountTable<Kmer>, fname : &Path) -> io::Result<usize>
+        where Kmer : CompressedKmerT + Hash {
+    //
+    log::info!("dumping kmer counts in jellyfish binary format in file : {:?}", fname);
+    //
+    let kmer_size = table.get_kmer_size();
+    if kmer_size > 32 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "jellyfish dump supports kmer size up to 32"));
+    }
+    let key_len = 2 * kmer_size;
+    let key_bytes = key_len.div_ceil(8);
+    let header = json!({
+        "format" : "binary/sorted",
+        "key_len" : key_len,
+        "val_len" : 8 * JELLYFISH_COUNTER_LEN,
+        "counter_len" : JELLYFISH_COUNTER_LEN,
+        "canonical" : table.is_canonical(),
+        "size" : table.len(),
+    }).to_string();
+    //
+    let file = OpenOptions::new().write(true).create(true).truncate(true).open(fname)?;
+    let mut bufw = BufWriter::new(file);
+    write!(bufw, "{:09}", header.len())?;
+    bufw.write_all(header.as_bytes())?;
+    let mut nb_dumped = 0;
+    let mut bases = Vec::<u8>::with_capacity(kmer_size);
+    for (kmer, count) in table.get_sorted_counts() {
+        bases.clear();
+        kmer.write_uncompressed(&mut bases);
+        let val = encode_kmer_value(&bases);
+        bufw.write_all(&val.to_le_bytes()[0..key_bytes])?;
+        bufw.write_all(&count.to_le_bytes()[0..JELLYFISH_COUNTER_LEN])?;
+        nb_dumped += 1;
+    }
+    bufw.flush()?;
+    //
+    log::info!("dump_jellyfish_binary, number of kmer dumped : {}", nb_dumped);
+    Ok(nb_dumped)
+} // end of dump_jellyfish_binary
+
+
+
+/// reloads a jellyfish binary/sorted file (as produced by jellyfish count or [dump_jellyfish_binary]).
+#[cfg(feature="formats")]
+pub fn reload_jellyfish_binary<Kmer>(fname : &Path) -> Result<KmerCountTable<Kmer>, String>
+        where Kmer : CompressedKmerT + Hash + FromStr {
+    //
+    log::info!("reloading jellyfish binary file : {:?}", fname);
+    //
+    let fileres = OpenOptions::new().read(true).open(fname);
+    if fileres.is_err() {
+        log::error!("reload_jellyfish_binary could not open file {:?}", fname.as_os_str());
+        println!("reload_jellyfish_binary could not open file {:?}", fname.as_os_str());
+        return Err("reload_jellyfish_binary could not open file".to_string());
+    }
+    let mut reader = BufReader::new(fileres.unwrap());
+    // decode header
+    let mut len_buf = [0u8; JELLYFISH_HEADER_LEN_SIZE];
+    reader.read_exact(&mut len_buf).map_err(|e| e.to_string())?;
+    let header_len = std::str::from_utf8(&len_buf).ok().and_then(|s| s.trim().parse::<usize>().ok());
+    if header_len.is_none() {
+        log::error!("reload_jellyfish_binary : not a jellyfish file {:?}", fname.as_os_str());
+        return Err("reload_jellyfish_binary : bad header".to_string());
+    }
+    let mut header_buf = vec![0u8; header_len.unwrap()];
+    reader.read_exact(&mut header_buf).map_err(|e| e.to_string())?;
+    // jellyfish can pad header with 0
+    let header_str = String::from_utf8_lossy(&header_buf);
+    let header : serde_json::Value = serde_json::from_str(header_str.trim_end_matches(char::from(0)).trim()).map_err(|e| e.to_string())?;
+    log::debug!("jellyfish header : {:?}", header);
+    match header["format"].as_str() {
+        Some("binary/sorted") => (),
+        _ => {
+            log::error!("reload_jellyfish_binary : unsupported format {:?}", header["format"]);
+            return Err("reload_jellyfish_binary : only binary/sorted format is supported".to_string());
+        }
+    }
+    let key_len = header["key_len"].as_u64().ok_or("no key_len in header")? as usize;
+    let counter_len = header["counter_len"].as_u64().ok_or("no counter_len in header")? as usize;
+    let canonical = header["canonical"].as_bool().unwrap_or(false);
+    if key_len > 64 || key_len % 2 != 0 || counter_len > 8 {
+        return Err(format!("reload_jellyfish_binary : unsupported key_len {} or counter_len {}", key_len, counter_len));
+    }
+    let kmer_size = key_len / 2;
+    let key_bytes = key_len.div_ceil(8);
+    //
+    let mut table = KmerCountTable::<Kmer>::new(kmer_size, canonical);
+    let mut record = vec![0u8; key_bytes + counter_len];
+    loop {
+        match reader.read_exact(&mut record) {
+            Ok(_) => (),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.to_string()),
+        }
+        let mut val_buf = [0u8; 8];
+        val_buf[0..key_bytes].copy_from_slice(&record[0..key_bytes]);
+        let val = u64::from_le_bytes(val_buf);
+        let mut count_buf = [0u8; 8];
+        count_buf[0..counter_len].copy_from_slice(&record[key_bytes..]);
+        let count = u64::from_le_bytes(count_buf).min(u32::MAX as u64) as u32;
+        let kmer_str = decode_kmer_value(val, kmer_size);
+        match Kmer::from_str(&kmer_str) {
+            Ok(kmer) => table.add_count(&kmer, count),
+            Err(_)   => {
+                log::error!("reload_jellyfish_binary kmer type cannot store kmer of size {}", kmer_size);
+                return Err("reload_jellyfish_binary kmer type cannot store kmer".to_string());
+            }
+        }
+    }
+    //
+    log::info!("reload_jellyfish_binary, number of kmer loaded : {}", table.len());
+    Ok(table)
+} // end of reload_jellyfish_binary
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::base::{kmer32bit::Kmer32bit, kmer64bit::Kmer64bit, sequence::Sequence};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    fn check_equal_tables<Kmer : CompressedKmerT + Hash>(t1 : &KmerCountTable<Kmer>, t2 : &KmerCountTable<Kmer>) {
+        assert_eq!(t1.len(), t2.len());
+        assert_eq!(t1.get_kmer_size(), t2.get_kmer_size());
+        for (kmer, count) in t1.iter() {
+            assert_eq!(*count, t2.get_count(kmer));
+        }
+    }
+
+    #[test]
+    fn test_jellyfish_text() {
+        log_init_test();
+        let seq = Sequence::new(b"TCAAAGGGAAACATTCAAAATCAGTATGCGCCCGTTCAGTTACGTATTGCTCTCGCTAATGAGATGGGCTGGGTACAGAG", 2);
+        let table = KmerCountTable::<Kmer32bit>::from_sequences(&vec![&seq, &seq], 7, true);
+        let dir = crate::test_dir("jf_text");
+        for format in [JellyfishTextFormat::Column, JellyfishTextFormat::Fasta] {
+            let path = dir.join(format!("kmerutils_jf_{:?}.txt", format));
+            let nb_dumped = dump_jellyfish_text(&table, &path, format).unwrap();
+            assert_eq!(nb_dumped, table.len());
+            let reloaded = reload_jellyfish_text::<Kmer32bit>(&path, true).unwrap();
+            check_equal_tables(&table, &reloaded);
+            let _ = std::fs::remove_file(&path);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_jellyfish_text
+
+
+    #[cfg(feature="formats")]
+    #[test]
+    fn test_jellyfish_binary() {
+        log_init_test();
+        let dir = crate::test_dir("jf_binary");
+        let seq = Sequence::new(b"TCAAAGGGAAACATTCAAAATCAGTATGCGCCCGTTCAGTTACGTATTGCTCTCGCTAATGAGATGGGCTGGGTACAGAG", 2);
+        let table = KmerCountTable::<Kmer64bit>::from_sequences(&vec![&seq], 21, false);
+        let path = dir.join("kmerutils_jf.bin");
+        dump_jellyfish_binary(&table, &path).unwrap();
+        let reloaded = reload_jellyfish_binary::<Kmer64bit>(&path).unwrap();
+        assert!(!reloaded.is_canonical());
+        check_equal_tables(&table, &reloaded);
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_jellyfish_binary
+
+} // end of mod tests
@@ -0,0 +1,345 @@
+//! This module provides a reader for databases produced by the [KMC](https://github.com/refresh-bio/KMC) kmer counter.
+//!
+//! A KMC database consists in 2 files with suffixes *.kmc_pre* and *.kmc_suf*.
+//! Each kmer is split in a prefix of lut_prefix_length bases, stored implicitly in the prefix file as an array
+//! giving for each prefix the rank of its first kmer, and a suffix stored in the suffix file together with the count.
+//!
+//! Only the KMC1 layout (version 0, as written by `kmc_tools transform <db> sort <out>`) in counter mode is supported.
+//! The KMC2 layout (version 0x200) uses signature based bins and is rejected with an error.
+//!
+//! Kmers are reloaded into a [KmerCountTable](super::kmertable::KmerCountTable) which can be sketched with
+//! ProbHash3aSketch::sketch_count_table without recounting.
+
+
+use std::io;
+use std::io::{Read, BufReader};
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::hash::Hash;
+use std::str::FromStr;
+
+pub use super::{kmertraits::*, kmertable::*};
+
+const KMC_PRE_MARKER : &[u8;4] = b"KMCP";
+const KMC_SUF_MARKER : &[u8;4] = b"KMCS";
+
+// the encoding used by KMC
+const KMC_BASES : [u8;4] = [b'A', b'C', b'G', b'T'];
+
+
+/// The header of a KMC database as found at the end of kmc_pre file
+#[derive(Copy,Clone,Debug)]
+pub struct KmcHeader {
+    /// kmer size
+    pub kmer_length : usize,
+    /// 0 for counters, 1 for quality aware counters
+    pub mode : u32,
+    /// number of bytes used by counts
+    pub counter_size : usize,
+    /// number of bases in prefix
+    pub lut_prefix_length : usize,
+    /// counting threshold used by kmc
+    pub min_count : u32,
+    /// counting threshold used by kmc
+    pub max_count : u32,
+    /// number of kmers in database
+    pub total_kmers : u64,
+    /// true if kmers are canonical
+    pub both_strands : bool,
+    /// database format version
+    pub version : u32,
+} // end of KmcHeader
+
+
+
+fn read_u32(buf : &[u8], pos : usize) -> u32 {
+    u32::from_le_bytes(buf[pos..pos+4].try_into().unwrap())
+}
+
+fn read_u64(buf : &[u8], pos : usize) -> u64 {
+    u64::from_le_bytes(buf[pos..pos+8].try_into().unwrap())
+}
+
+
+// decode the header from the content of kmc_pre file
+fn decode_kmc_header(pre : &[u8]) -> Result<KmcHeader, String> {
+    let size = pre.len();
+    if size < 16 || &pre[0..4] != KMC_PRE_MARKER || &pre[size-4..] != KMC_PRE_MARKER {
+        return Err("not a kmc_pre file, bad markers".to_string());
+    }
+    let version = read_u32(pre, size - 12);
+    if version != 0 {
+        log::error!("kmc database version {:#x} not supported", version);
+        return Err(format!("kmc database version {:#x} not supported, only version 0", version));
+    }
+    let header_offset = read_u32(pre, size - 8) as usize;
+    if header_offset + 8 + 4 > size {
+        return Err("kmc_pre file, bad header offset".to_string());
+    }
+    let pos = size - 8 - header_offset;
+    let header = KmcHeader {
+        kmer_length : read_u32(pre, pos) as usize,
+        mode : read_u32(pre, pos + 4),
+        counter_size : read_u32(pre, pos + 8) as usize,
+        lut_prefix_length : read_u32(pre, pos + 12) as usize,
+        min_count : read_u32(pre, pos + 16),
+        max_count : read_u32(pre, pos + 20),
+        total_kmers : read_u64(pre, pos + 24),
+        // kmc stores the negation of both_strands
+        both_strands : pre[pos + 32] == 0,
+        version,
+    };
+    log::debug!("kmc header : {:?}", header);
+    Ok(header)
+} // end of decode_kmc_header
+
+
+
+/// A KMC database opened for reading
+pub struct KmcDatabase {
+    /// header of database
+    header : KmcHeader,
+    /// rank of first kmer of each prefix, with a last slot containing total_kmers
+    prefix_array : Vec<u64>,
+    /// path to kmc_suf file
+    suf_path : PathBuf,
+}
+
+
+impl KmcDatabase {
+
+    /// opens a database given its name without suffix (as given to kmc), i.e for files *name.kmc_pre* and *name.kmc_suf*
+    pub fn open(dbname : &Path) -> Result<KmcDatabase, String> {
+        let pre_path = dbname.with_extension("kmc_pre");
+        let suf_path = dbname.with_extension("kmc_suf");
+        log::info!("opening kmc database {:?}", pre_path);
+        let pre = match std::fs::read(&pre_path) {
+            Ok(pre) => pre,
+            Err(_) => {
+                log::error!("KmcDatabase::open could not read file {:?}", pre_path.as_os_str());
+                println!("KmcDatabase::open could not read file {:?}", pre_path.as_os_str());
+                return Err("KmcDatabase::open could not read kmc_pre file".to_string());
+            }
+        };
+        let header = decode_kmc_header(&pre)?;
+        if header.mode != 0 {
+            return Err("KmcDatabase::open, quality aware counters are not supported".to_string());
+        }
+        if header.counter_size > 4 || header.kmer_length > 32 || header.lut_prefix_length > header.kmer_length
+                || (header.kmer_length - header.lut_prefix_length) % 4 != 0 {
+            log::error!("KmcDatabase::open, unsupported header : {:?}", header);
+            return Err("KmcDatabase::open, unsupported header".to_string());
+        }
+        let nb_prefix = 1usize << (2 * header.lut_prefix_length);
+        if 4 + 8 * nb_prefix > pre.len() {
+            return Err("KmcDatabase::open, kmc_pre file too short".to_string());
+        }
+        let mut prefix_array : Vec<u64> = (0..nb_prefix).map(|i| read_u64(&pre, 4 + 8 * i)).collect();
+        prefix_array.push(header.total_kmers);
+        //
+        Ok(KmcDatabase{header, prefix_array, suf_path})
+    } // end of open
+
+
+    /// returns database header
+    pub fn get_header(&self) -> &KmcHeader {
+        &self.header
+    }
+
+    /// returns kmer size
+    pub fn get_kmer_size(&self) -> usize {
+        self.header.kmer_length
+    }
+
+    /// returns the number of kmers in database
+    pub fn get_nb_kmer(&self) -> u64 {
+        self.header.total_kmers
+    }
+
+    /// reloads all kmers with their count in a table. Kmers with count outside [min_count, max_count] are skipped.
+    pub fn load_count_table<Kmer>(&self, min_count : u32, max_count : u32) -> Result<KmerCountTable<Kmer>, String>
+            where Kmer : CompressedKmerT + Hash + FromStr {
+        //
+        let fileres = OpenOptions::new().read(true).open(&self.suf_path);
+        if fileres.is_err() {
+            log::error!("KmcDatabase could not open file {:?}", self.suf_path.as_os_str());
+            println!("KmcDatabase could not open file {:?}", self.suf_path.as_os_str());
+            return Err("KmcDatabase could not open kmc_suf file".to_string());
+        }
+        let mut reader = BufReader::new(fileres.unwrap());
+        let mut marker = [0u8; 4];
+        reader.read_exact(&mut marker).map_err(|e| e.to_string())?;
+        if &marker != KMC_SUF_MARKER {
+            return Err("not a kmc_suf file, bad marker".to_string());
+        }
+        //
+        let prefix_len = self.header.lut_prefix_length;
+        let kmer_size = self.header.kmer_length;
+        let suffix_size = (kmer_size - prefix_len) / 4;
+        let counter_size = self.header.counter_size;
+        let mut record = vec![0u8; suffix_size + counter_size];
+        let mut table = KmerCountTable::<Kmer>::with_capacity(kmer_size, self.header.both_strands, self.header.total_kmers as usize);
+        let mut kmer_str = String::with_capacity(kmer_size);
+        //
+        let mut prefix = 0usize;
+        for rank in 0..self.header.total_kmers {
+            // find prefix of kmer of this rank
+            while self.prefix_array[prefix + 1] <= rank {
+                prefix += 1;
+            }
+            reader.read_exact(&mut record).map_err(|e| match e.kind() {
+                io::ErrorKind::UnexpectedEof => "kmc_suf file too short".to_string(),
+                _ => e.to_string(),
+            })?;
+            let mut count_buf = [0u8; 4];
+            count_buf[0..counter_size].copy_from_slice(&record[suffix_size..]);
+            let count = u32::from_le_bytes(count_buf);
+            if count < min_count || count > max_count {
+                continue;
+            }
+            kmer_str.clear();
+            for i in 0..prefix_len {
+                kmer_str.push(KMC_BASES[(prefix >> (2 * (prefix_len - 1 - i))) & 0b11] as char);
+            }
+            for byte in &record[0..suffix_size] {
+                for j in 0..4 {
+                    kmer_str.push(KMC_BASES[((byte >> (6 - 2 * j)) & 0b11) as usize] as char);
+                }
+            }
+            match Kmer::from_str(&kmer_str) {
+                Ok(kmer) => table.add_count(&kmer, count),
+                Err(_)   => {
+                    log::error!("KmcDatabase kmer type cannot store kmer of size {}", kmer_size);
+                    return Err("KmcDatabase kmer type cannot store kmer".to_string());
+                }
+            }
+        }
+        //
+        log::info!("KmcDatabase, number of kmer loaded : {}", table.len());
+        Ok(table)
+    } // end of load_count_table
+
+} // end of impl KmcDatabase
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::base::kmer64bit::Kmer64bit;
+    use crate::base::sequence::Sequence;
+    use std::io::Write;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    // writes a kmc database (version 0 layout) from a list of (kmer, count) sorted by kmer
+    fn write_kmc_db(dbname : &Path, kmers : &[(&str, u32)], prefix_len : usize) {
+        let kmer_length = kmers[0].0.len();
+        let encode = |b : u8| KMC_BASES.iter().position(|&x| x == b).unwrap();
+        // suffix file and prefix array
+        let mut prefix_array = vec![0u64; 1 << (2 * prefix_len)];
+        let mut suf = Vec::<u8>::new();
+        suf.extend_from_slice(KMC_SUF_MARKER);
+        for (kmer, count) in kmers {
+            let bytes = kmer.as_bytes();
+            let prefix = bytes[0..prefix_len].iter().fold(0usize, |acc, b| (acc << 2) | encode(*b));
+            for p in (prefix+1)..prefix_array.len() {
+                prefix_array[p] += 1;
+            }
+            for chunk in bytes[prefix_len..].chunks(4) {
+                suf.push(chunk.iter().fold(0u8, |acc, b| (acc << 2) | encode(*b) as u8));
+            }
+            suf.push(*count as u8);
+        }
+        suf.extend_from_slice(KMC_SUF_MARKER);
+        //
+        let mut pre = Vec::<u8>::new();
+        pre.extend_from_slice(KMC_PRE_MARKER);
+        for p in &prefix_array {
+            pre.extend_from_slice(&p.to_le_bytes());
+        }
+        let mut header = Vec::<u8>::new();
+        for v in [kmer_length as u32, 0, 1, prefix_len as u32, 1, 255] {
+            header.extend_from_slice(&v.to_le_bytes());
+        }
+        header.extend_from_slice(&(kmers.len() as u64).to_le_bytes());
+        header.extend_from_slice(&[0u8, 0, 0, 0]);
+        // version
+        header.extend_from_slice(&0u32.to_le_bytes());
+        pre.extend_from_slice(&header);
+        pre.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        pre.extend_from_slice(KMC_PRE_MARKER);
+        //
+        std::fs::File::create(dbname.with_extension("kmc_pre")).unwrap().write_all(&pre).unwrap();
+        std::fs::File::create(dbname.with_extension("kmc_suf")).unwrap().write_all(&suf).unwrap();
+    } // end of write_kmc_db
+
+
+    #[test]
+    fn test_kmc_reload() {
+        log_init_test();
+        let dir = crate::test_dir("kmc");
+        let kmers = [("AACGTTAAC", 3u32), ("ACGGTTCAA", 1), ("CATTGACCA", 7), ("TTTTGACCA", 2)];
+        let dbname = dir.join("kmerutils_kmc_test");
+        write_kmc_db(&dbname, &kmers, 1);
+        //
+        let db = KmcDatabase::open(&dbname).unwrap();
+        assert_eq!(db.get_kmer_size(), 9);
+        assert_eq!(db.get_nb_kmer(), 4);
+        assert!(db.get_header().both_strands);
+        let table = db.load_count_table::<Kmer64bit>(1, u32::MAX).unwrap();
+        assert_eq!(table.len(), 4);
+        for (kmer, count) in kmers {
+            let kmer : Kmer64bit = kmer.parse().unwrap();
+            assert_eq!(table.get_count(&kmer), count);
+        }
+        // check thresholds
+        let table = db.load_count_table::<Kmer64bit>(2, 5).unwrap();
+        assert_eq!(table.len(), 2);
+        //
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_kmc_reload
+
+
+    // reloads the database produced by kmc from tests/data/kmc/reads.fa (see tests/data/kmc/README.md)
+    // and checks its counts against a recount of the reads
+    #[test]
+    #[ignore = "needs tests/data/kmc/reads.kmc_pre and reads.kmc_suf, generated with kmc as described in tests/data/kmc/README.md"]
+    fn test_kmc_real_database() {
+        log_init_test();
+        let datadir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/kmc");
+        let dbname = datadir.join("reads");
+        let db = KmcDatabase::open(&dbname).unwrap();
+        assert_eq!(db.get_kmer_size(), 21);
+        assert!(db.get_header().both_strands);
+        let table = db.load_count_table::<Kmer64bit>(1, u32::MAX).unwrap();
+        assert_eq!(table.len() as u64, db.get_nb_kmer());
+        // recount reads
+        let fasta = std::fs::read_to_string(datadir.join("reads.fa")).unwrap();
+        let mut reads = Vec::<String>::new();
+        for line in fasta.lines() {
+            if line.starts_with('>') {
+                reads.push(String::new());
+            }
+            else {
+                reads.last_mut().unwrap().push_str(line.trim());
+            }
+        }
+        let seqs : Vec<Sequence> = reads.iter().map(|r| Sequence::new(r.as_bytes(), 2)).collect();
+        let recount = KmerCountTable::<Kmer64bit>::from_sequences(&seqs.iter().collect(), 21, true);
+        assert_eq!(table.len(), recount.len());
+        assert_eq!(table.get_total_count(), recount.get_total_count());
+        for (kmer, count) in recount.iter() {
+            assert_eq!(table.get_count(kmer), *count);
+        }
+        // the 3 last reads are contained in the first one, so some kmers are seen more than once
+        assert!(recount.iter().any(|(_, count)| *count > 1));
+    } // end of test_kmc_real_database
+
+} // end of mod tests
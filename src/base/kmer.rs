@@ -153,10 +153,85 @@ mod tests {
 
 #[allow(unused)]
 use super::*;
+use std::str::FromStr;
 
 //
 
-
-    
+    #[test]
+    fn test_kmer_serde() {
+        // kmers keep their value and number of bases through serialization
+        let kmer32 = Kmer32bit::from_str("TACGAGTAGGAT").unwrap();
+        let dumped = serde_json::to_string(&vec![kmer32]).unwrap();
+        let reloaded : Vec<Kmer32bit> = serde_json::from_str(&dumped).unwrap();
+        assert_eq!(reloaded, vec![kmer32]);
+        assert_eq!(reloaded[0].get_nb_base(), 12);
+        //
+        let kmer64 = Kmer64bit::from_str("TACGAGTAGGATACTTGGAACGTT").unwrap();
+        let reloaded : Kmer64bit = serde_json::from_str(&serde_json::to_string(&kmer64).unwrap()).unwrap();
+        assert_eq!(reloaded, kmer64);
+        assert_eq!(reloaded.get_uncompressed_kmer(), kmer64.get_uncompressed_kmer());
+        //
+        // number of bases is checked and unused bits are cleared
+        assert!(serde_json::from_str::<Kmer32bit>(&(15u32 << 28).to_string()).is_err());
+        assert!(serde_json::from_str::<Kmer64bit>("[1,33]").is_err());
+        let reloaded : Kmer32bit = serde_json::from_str(&((2u32 << 28) | 0x0FFF_FFFF).to_string()).unwrap();
+        assert_eq!(reloaded.get_compressed_value(), 0b1111);
+        let reloaded : Kmer64bit = serde_json::from_str(&format!("[{},3]", u64::MAX)).unwrap();
+        assert_eq!(reloaded.get_uncompressed_kmer(), b"TTT".to_vec());
+        //
+        let kmer16 = Kmer16b32bit(0b11000110_00101100_10100011_00011100);
+        let reloaded : Kmer16b32bit = serde_json::from_str(&serde_json::to_string(&kmer16).unwrap()).unwrap();
+        assert_eq!(reloaded, kmer16);
+    } // end of test_kmer_serde
+
+
+    // random string of nb_bases ACGT bases
+    fn random_bases(rng : &mut impl rand::Rng, nb_bases : usize) -> String {
+        (0..nb_bases).map(|_| b"ACGT"[rng.gen_range(0..4)] as char).collect()
+    }
+
+    #[test]
+    fn test_kmer_display_roundtrip() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(3136);
+        for _ in 0..500 {
+            let nb_bases = rng.gen_range(1..=14);
+            let s = random_bases(&mut rng, nb_bases);
+            let kmer = Kmer32bit::from_str(&s).unwrap();
+            assert_eq!(kmer.to_string(), s);
+            assert_eq!(Kmer32bit::from_str(&kmer.to_string()).unwrap(), kmer);
+            //
+            let nb_bases = rng.gen_range(1..=32);
+            let s = random_bases(&mut rng, nb_bases);
+            let kmer = Kmer64bit::from_str(&s).unwrap();
+            assert_eq!(kmer.to_string(), s);
+            assert_eq!(Kmer64bit::from_str(&kmer.to_string()).unwrap(), kmer);
+            //
+            let s = random_bases(&mut rng, 16);
+            let kmer = Kmer16b32bit::from_str(&s).unwrap();
+            assert_eq!(kmer.to_string(), s);
+            assert_eq!(Kmer16b32bit::from_str(&kmer.to_string()).unwrap(), kmer);
+        }
+    } // end of test_kmer_display_roundtrip
+
+
+    #[test]
+    fn test_kmer_write_uncompressed() {
+        // decoding in a reused buffer appends the bases, as get_uncompressed_kmer
+        let kmers32 : Vec<Kmer32bit> = ["TACG", "", "GATTACAGATTACA"].iter().map(|s| Kmer32bit::from_str(s).unwrap()).collect();
+        let kmer64 = Kmer64bit::from_str("TACGAGTAGGATACTTGGAACGTTACGTACGT").unwrap();
+        let kmer16 = Kmer16b32bit::from_str("ACGTTGCAACGTTGCA").unwrap();
+        let mut buf = Vec::<u8>::new();
+        for kmer in &kmers32 {
+            kmer.write_uncompressed(&mut buf);
+        }
+        kmer64.write_uncompressed(&mut buf);
+        kmer16.write_uncompressed(&mut buf);
+        assert_eq!(buf, b"TACGGATTACAGATTACATACGAGTAGGATACTTGGAACGTTACGTACGTACGTTGCAACGTTGCA".to_vec());
+        assert_eq!(kmers32[2].get_uncompressed_kmer(), b"GATTACAGATTACA".to_vec());
+        buf.clear();
+        kmer16.write_uncompressed(&mut buf);
+        assert_eq!(buf, kmer16.get_uncompressed_kmer());
+    } // end of test_kmer_write_uncompressed
   
 } // end of mod test
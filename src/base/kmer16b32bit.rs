@@ -8,10 +8,14 @@ use std::io;
 
 use std::cmp::Ord;
 use std::str::FromStr;
+use std::fmt;
+use std::fmt::Write;
 
 #[allow(unused)]
 use log::{debug,trace};
 
+use serde::{Deserialize, Serialize};
+
 
 
 // our includes
@@ -21,7 +25,7 @@ pub use super::{nthash::*, alphabet::*};
 
 /// type supporting 16 bases kmer as u32.
 
-#[derive(Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Hash,Debug)]
+#[derive(Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Hash,Debug,Serialize,Deserialize)]
 pub struct Kmer16b32bit(pub u32);
 
 
@@ -60,6 +64,13 @@ impl KmerT for Kmer16b32bit {
         Kmer16b32bit(new_kmer)     
     }
 
+    /// the complement of base (2 bit encoded) enters at left end, as the reverse complement of a pushed kmer begins with it
+    #[inline]
+    fn push_reverse_complement(&self, base : u8) -> Kmer16b32bit {
+        let complement = (base as u32 & 0b11) ^ 0b11;
+        Kmer16b32bit((self.0 >> 2) | (complement << 30))
+    }
+
     ///
     fn dump(&self, bufw: &mut dyn io::Write) -> io::Result<usize> {
         bufw.write(unsafe { &mem::transmute::<u32, [u8;4]>(self.0) } )
@@ -71,24 +82,27 @@ impl KmerT for Kmer16b32bit {
 
 
 
+impl Kmer16b32bit {
+    // decoded bases, from left to right
+    fn bases(&self) -> impl Iterator<Item=u8> {
+        let buf = self.0;
+        (1..=16u32).map(move |i| decode_2b(buf.rotate_left(2 * i) as u8))
+    }
+} // end of impl Kmer16b32bit
+
+
+
 impl CompressedKmerT for Kmer16b32bit {
     type Val = u32;
     ///
     fn get_nb_base_max() -> usize { 16 }
     /// a decompressing function mainly for test and debugging purpose
     fn get_uncompressed_kmer(&self) -> Vec<u8> {
-        let alphabet = Alphabet2b::new();
-        // we treat each block of 2 bis as u8 end call decoder of Alphabet2b
-        let mut decompressed_kmer = Vec::<u8>::with_capacity(16);
-        let mut base:u8;
-        //
-        let mut buf = self.0;
-        for _ in 0..16 {
-            buf = buf.rotate_left(2);
-            base = (buf & 0b11) as u8; 
-            decompressed_kmer.push(alphabet.decode(base));
-        }
-        return decompressed_kmer;
+        self.bases().collect()
+    }
+    /// decodes in buf without allocation
+    fn write_uncompressed(&self, buf : &mut Vec<u8>) {
+        buf.extend(self.bases());
     }
     ///
     #[inline(always)]    
@@ -135,6 +149,14 @@ impl FromStr for Kmer16b32bit {
 } // end impl FromStr
 
 
+impl fmt::Display for Kmer16b32bit {
+    /// writes the decoded bases, see [FromStr] for the reverse conversion
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.bases().try_for_each(|b| f.write_char(b as char))
+    }
+}  // end of impl Display for Kmer16b32bit
+
+
 //========================================================
 
 #[cfg(test)]
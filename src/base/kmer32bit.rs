@@ -6,10 +6,14 @@ use std::io;
 use std::cmp::Ordering;
 use std::cmp::Ord;
 use std::str::FromStr;
+use std::fmt;
+use std::fmt::Write;
 
 #[allow(unused)]
 use log::{debug,trace};
 
+use serde::{Deserialize, Serialize};
+
 
 
 // our includes
@@ -20,9 +24,27 @@ pub use super::{nthash::*, alphabet::*};
 /// This is a type for Kmer less than 14 bases 2 bit encoded
 /// Real number of base is encoded in upper 4 bits!!!!
 
-#[derive(Clone,Copy,Debug, Hash)]
+#[derive(Clone,Copy,Debug, Serialize, Deserialize)]
+#[serde(try_from = "Kmer32bitRepr")]
 pub struct Kmer32bit(pub u32);
 
+
+// serialized form, checked at deserialization : at most 14 bases and bits not coding bases reset to 0
+#[derive(Deserialize)]
+struct Kmer32bitRepr(u32);
+
+impl TryFrom<Kmer32bitRepr> for Kmer32bit {
+    type Error = String;
+    fn try_from(repr : Kmer32bitRepr) -> Result<Self, String> {
+        let nb_bases = repr.0 >> 28;
+        if nb_bases as usize > Self::get_nb_base_max() {
+            return Err(format!("Kmer32bit cannot store {} bases", nb_bases));
+        }
+        let value_mask : u32 = (1 << (2 * nb_bases)) - 1;
+        Ok(Kmer32bit((repr.0 & value_mask) | (nb_bases << 28)))
+    }
+}
+
 // we need to implement PartialEq, Eq PartialOrd and Ord for Kmer32bit.
 // Note that as we have been very cautious in push method to not let garbage bits between upper fields used for coding
 // number of bases and lower field for kmer coding, we can be brutal now and just test value
@@ -50,6 +72,14 @@ impl PartialEq for Kmer32bit {
 impl Eq for Kmer32bit{}
 
 
+// consistent with eq, which compares number of bases and value, i.e the whole word
+impl std::hash::Hash for Kmer32bit {
+    fn hash<H : std::hash::Hasher>(&self, state : &mut H) {
+        self.0.hash(state);
+    }
+}
+
+
 /// We define ordering as a kind of "lexicographic" order by taking into account first number of base.
 /// The more the number of base the greater. Then we have integer comparison between lower kmer part
 /// which corresponds to lexicographic order as we have A < C < G < T in 2bit and 4bit encoding
@@ -152,6 +182,19 @@ impl KmerT for Kmer32bit {
         //        
         Kmer32bit(revcomp)
     }
+
+    /// the complement of base (2 bit encoded) enters at left end, as the reverse complement of a pushed kmer begins with it.
+    /// A kmer without base is returned unchanged.
+    #[inline]
+    fn push_reverse_complement(&self, base : u8) -> Kmer32bit {
+        if self.get_nb_base() == 0 {
+            return *self;
+        }
+        let nb_bases_mask : u32 = self.0 & 0xF0000000;
+        let complement = (base as u32 & 0b11) ^ 0b11;
+        let revcomp = ((self.0 & 0x0FFFFFFF) >> 2) | (complement << (2 * (self.get_nb_base() as u32 - 1)));
+        Kmer32bit(revcomp | nb_bases_mask)
+    }
     /// we just do a raw write. Error prone when reloading. Any dump file must have a header
     /// describing number of bases! to distinguish from Kmer16b32bit
     fn dump(&self, bufw: &mut dyn io::Write) -> io::Result<usize> {
@@ -161,27 +204,29 @@ impl KmerT for Kmer32bit {
 
 
 
+impl Kmer32bit {
+    // decoded bases, from left to right
+    fn bases(&self) -> impl Iterator<Item=u8> {
+        let nb_bases = self.0.rotate_left(4) & 0b1111;
+        // get the base coding part at left end of u32
+        let buf = self.0.rotate_left(32 - 2 * nb_bases);
+        (1..=nb_bases).map(move |i| decode_2b(buf.rotate_left(2 * i) as u8))
+    }
+} // end of impl Kmer32bit
+
+
+
 impl CompressedKmerT for Kmer32bit {
     type Val = u32;
     /// This type can store 14 base at max
     fn get_nb_base_max() -> usize { 14 }
     /// a decompressing function mainly for test and debugging purpose
     fn get_uncompressed_kmer(&self) -> Vec<u8> {
-        let nb_bases = (self.0.rotate_left(4) & 0b1111) as usize;
-        let alphabet = Alphabet2b::new();
-        // we treat each block of 2 bis as u8 end call decoder of Alphabet2b
-        let mut decompressed_kmer = Vec::<u8>::with_capacity(nb_bases);
-        let mut base:u8;
-        //
-        let mut buf = self.0;
-        // get the base coding part at left end of u32
-        buf = buf.rotate_left((self.get_bitsize() - 2 * nb_bases) as u32);
-        for _ in 0..nb_bases {
-            buf = buf.rotate_left(2);
-            base = (buf & 0b11) as u8; 
-            decompressed_kmer.push(alphabet.decode(base));
-        }
-        return decompressed_kmer;
+        self.bases().collect()
+    }
+    /// decodes in buf without allocation
+    fn write_uncompressed(&self, buf : &mut Vec<u8>) {
+        buf.extend(self.bases());
     }
     /// return the pure value with part coding number of bases reset to 0.
     #[inline(always)]    
@@ -226,6 +271,14 @@ impl FromStr for Kmer32bit {
 }  // end of impl FromStr for Kmer32bit
 
 
+impl fmt::Display for Kmer32bit {
+    /// writes the decoded bases, see [FromStr] for the reverse conversion
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.bases().try_for_each(|b| f.write_char(b as char))
+    }
+}  // end of impl Display for Kmer32bit
+
+
 
 impl KmerBuilder<Kmer32bit> for Kmer32bit {
     /// for Kmer32bit we encode the number of bases in the 4 upper bits
@@ -324,4 +377,11 @@ mod tests {
     } // end of test_OrdandEq_kmer32
 
 
-}
\ No newline at end of file
+
+    #[test]
+    fn test_push_reverse_complement_empty_kmer32bit() {
+        let empty = Kmer32bit::new(0);
+        assert_eq!(empty.push_reverse_complement(0b01), empty);
+        assert_eq!(empty.push_reverse_complement(0b01).get_nb_base(), 0);
+    } // end of test_push_reverse_complement_empty_kmer32bit
+}
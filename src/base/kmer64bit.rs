@@ -7,10 +7,14 @@ use std::io;
 use std::cmp::Ordering;
 use std::cmp::Ord;
 use std::str::FromStr;
+use std::fmt;
+use std::fmt::Write;
 
 #[allow(unused)]
 use log::{debug,trace};
 
+use serde::{Deserialize, Serialize};
+
 pub use super::kmertraits::*;
 pub use super::{nthash::*, alphabet::*};
 
@@ -19,10 +23,27 @@ pub use super::{nthash::*, alphabet::*};
 // This representation is consistent with Kmer32bit as self.0 gives the word supporting value of kmer
 
 /// The type supporting Kmer for number of bases between 17 and 32.
-#[derive(Clone,Copy,Debug, Hash)]
+#[derive(Clone,Copy,Debug, Serialize, Deserialize)]
+#[serde(try_from = "Kmer64bitRepr")]
 pub struct  Kmer64bit(pub u64, pub u8);
 
 
+// serialized form, checked at deserialization : at most 32 bases and bits not coding bases reset to 0
+#[derive(Deserialize)]
+struct Kmer64bitRepr(u64, u8);
+
+impl TryFrom<Kmer64bitRepr> for Kmer64bit {
+    type Error = String;
+    fn try_from(repr : Kmer64bitRepr) -> Result<Self, String> {
+        if repr.1 as usize > Self::get_nb_base_max() {
+            return Err(format!("Kmer64bit cannot store {} bases", repr.1));
+        }
+        let value_mask : u64 = 1u64.checked_shl(2 * repr.1 as u32).map_or(u64::MAX, |v| v - 1);
+        Ok(Kmer64bit(repr.0 & value_mask, repr.1))
+    }
+}
+
+
 impl Kmer64bit {
     pub fn new(nb_base: u8) -> Kmer64bit {
         Kmer64bit(0u64, nb_base)
@@ -43,6 +64,15 @@ impl PartialEq for Kmer64bit {
 impl Eq for Kmer64bit{}
 
 
+// consistent with eq, which compares value and number of bases
+impl std::hash::Hash for Kmer64bit {
+    fn hash<H : std::hash::Hasher>(&self, state : &mut H) {
+        self.0.hash(state);
+        self.1.hash(state);
+    }
+}
+
+
 /// We define ordering as a kind of "lexicographic" order by taking into account first number of base.
 /// The more the number of base the greater. Then we have integer comparison between lower kmer part
 /// which corresponds to lexicographic order as we have A < C < G < T in 2bit
@@ -82,7 +112,8 @@ impl KmerT for Kmer64bit {
         // It is useful when implementing PartialEq and compressed value 
         // We could use as a mask for value field : (0b1 << (2*self.get_nb_bases())) - 1 which enforce 0 bit between 4 upper bits
         // and lower bits coding value.
-        let value_mask :u64 = (0b1 << (2*self.get_nb_base())) - 1;
+        // (for 32 bases all bits code value)
+        let value_mask :u64 = 1u64.checked_shl(2*self.get_nb_base() as u32).map_or(u64::MAX, |v| v - 1);
         // shift left 2 bits, insert new base and enforce saved number of bases.
         let new_kmer = ((self.0 << 2) & value_mask) | (base as u64 & 0b11);
         trace!("in push new_kmer = {:b}",  new_kmer);
@@ -105,6 +136,17 @@ impl KmerT for Kmer64bit {
         Kmer64bit(revcomp, self.1)
     }
 
+    /// the complement of base (2 bit encoded) enters at left end, as the reverse complement of a pushed kmer begins with it.
+    /// A kmer without base is returned unchanged.
+    #[inline]
+    fn push_reverse_complement(&self, base : u8) -> Kmer64bit {
+        if self.1 == 0 {
+            return *self;
+        }
+        let complement = (base as u64 & 0b11) ^ 0b11;
+        Kmer64bit((self.0 >> 2) | (complement << (2 * (self.1 as u32 - 1))), self.1)
+    }
+
 
     fn dump(&self, bufw: &mut dyn io::Write) -> io::Result<usize> {
         bufw.write(unsafe { &mem::transmute::<u8, [u8;1]>(self.1) }).unwrap();
@@ -116,27 +158,29 @@ impl KmerT for Kmer64bit {
 
 
 
+impl Kmer64bit {
+    // decoded bases, from left to right
+    fn bases(&self) -> impl Iterator<Item=u8> {
+        let nb_bases = self.1 as u32;
+        // get the base coding part at left end of u64
+        let buf = self.0.rotate_left(64 - 2 * nb_bases);
+        (1..=nb_bases).map(move |i| decode_2b(buf.rotate_left(2 * i) as u8))
+    }
+} // end of impl Kmer64bit
+
+
+
 impl CompressedKmerT for Kmer64bit {
     type Val = u64;
     /// This type can store 14 base at max
     fn get_nb_base_max() -> usize { 32 }
     /// a decompressing function mainly for test and debugging purpose
     fn get_uncompressed_kmer(&self) -> Vec<u8> {
-        let nb_bases = self.1;
-        let alphabet = Alphabet2b::new();
-        // we treat each block of 2 bis as u8 end call decoder of Alphabet2b
-        let mut decompressed_kmer = Vec::<u8>::with_capacity(nb_bases as usize);
-        let mut base:u8;
-        //
-        let mut buf = self.0;
-        // get the base coding part at left end of u32
-        buf = buf.rotate_left((64 - 2 * nb_bases) as u32);
-        for _ in 0..nb_bases {
-            buf = buf.rotate_left(2);
-            base = (buf & 0b11) as u8; 
-            decompressed_kmer.push(alphabet.decode(base));
-        }
-        return decompressed_kmer;
+        self.bases().collect()
+    }
+    /// decodes in buf without allocation
+    fn write_uncompressed(&self, buf : &mut Vec<u8>) {
+        buf.extend(self.bases());
     }
     /// return the pure value with part coding number of bases reset to 0.
     #[inline(always)]    
@@ -187,6 +231,14 @@ impl FromStr for Kmer64bit {
 }  // end of impl FromStr for Kmer64bit
 
 
+impl fmt::Display for Kmer64bit {
+    /// writes the decoded bases, see [FromStr] for the reverse conversion
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.bases().try_for_each(|b| f.write_char(b as char))
+    }
+}  // end of impl Display for Kmer64bit
+
+
 //==================================================
 
 #[cfg(test)]
@@ -250,4 +302,11 @@ use super::*;
             assert!(revcomp.0 == should_be.0 );
         }
     } // end of test_reverse_complement_kmer32bit
-}
\ No newline at end of file
+
+    #[test]
+    fn test_push_reverse_complement_empty_kmer64bit() {
+        let empty = Kmer64bit::new(0);
+        assert_eq!(empty.push_reverse_complement(0b01), empty);
+        assert_eq!(empty.push_reverse_complement(0b01).get_nb_base(), 0);
+    } // end of test_push_reverse_complement_empty_kmer64bit
+}
@@ -3,6 +3,8 @@
 //
 use log::{Level, trace};
 
+use std::ops::{Bound, Range, RangeBounds};
+
 
 use fnv::{FnvHashMap, FnvBuildHasher};
 
@@ -38,6 +40,8 @@ pub struct KmerSeqIterator<'a , T > where T:CompressedKmerT + KmerBuilder<T> {
     seqiter: IterSequence<'a>,
     /// last kmer returned. At the beginning its None
     previous: Option<T>,
+    /// reverse complement of previous if the last kmer was returned by next_canonical
+    revcomp: Option<T>,
 }
 
 
@@ -49,16 +53,71 @@ impl<'a, T> KmerSeqIterator<'a, T>  where T:CompressedKmerT + KmerBuilder<T> {
             panic!("\n KmerSeqIterator cannot support so many bases for given kmer type, kmer size  {}", ksize);
         }
         let seqiter_arg = IterSequence::new(sequence, false);
-        KmerSeqIterator{nb_base: ksize, seqiter:seqiter_arg, previous:None}
+        KmerSeqIterator{nb_base: ksize, seqiter:seqiter_arg, previous:None, revcomp:None}
     } // end of new
-    /// Set the range from which all kmer of a given size are to be extracted from the sequence associated to the iterator.
-    pub fn set_range(&mut self, begin: usize, end: usize) -> std::result::Result<(),()> {
-        self.seqiter.set_range(begin, end)
+
+    /// Constructor of an iterator generating kmers in a range of the sequence, see [Self::set_range]
+    pub fn new_range<R : RangeBounds<usize>>(ksize: u8, sequence: &'a Sequence, range : R) -> std::result::Result<KmerSeqIterator<'a, T>, String> {
+        let mut iter = KmerSeqIterator::new(ksize, sequence);
+        iter.set_range(range)?;
+        Ok(iter)
+    }
+
+    /// Constructor of an iterator generating kmers of a [SequenceView]. Returns an error if the view is empty.
+    pub fn new_view(ksize: u8, view : &SequenceView<'a>) -> std::result::Result<KmerSeqIterator<'a, T>, String> {
+        KmerSeqIterator::new_range(ksize, view.get_sequence(), view.get_range()).map_err(|_| format!("empty view, range : {:?}", view.get_range()))
     }
+
+    /// Set the range from which all kmer of a given size are to be extracted from the sequence associated to the iterator.  
+    /// All bases of generated kmers are in range, so a range shorter than kmer size gives no kmer.
+    /// (2..10 and 2..=9 are the same range, .. is the whole sequence).  
+    /// Returns an error if range is empty or goes beyond the end of sequence.
+    pub fn set_range<R : RangeBounds<usize>>(&mut self, range : R) -> std::result::Result<(),String> {
+        let range = get_bounded_range(&range, self.seqiter.get_sequence_size());
+        self.previous = None;
+        self.revcomp = None;
+        self.seqiter.set_range(range.start, range.end)
+                .map_err(|_| format!("invalid kmer range {:?} for a sequence of {} bases", range, self.seqiter.get_sequence_size()))
+    }
+
+    /// get next kmer in canonical form (min of kmer and its reverse complement) or None.  
+    /// The reverse complement is updated with the pushed base (see [KmerT::push_reverse_complement]), it is computed
+    /// from the kmer only for the first kmer (or after a call to next), so canonical iteration costs about as much as next.
+    pub fn next_canonical(&mut self) -> Option<T> {
+        if let (Some(kmer), Some(revcomp)) = (self.previous.as_mut(), self.revcomp.as_mut()) {
+            let base = self.seqiter.next()?;
+            *kmer = kmer.push(base);
+            *revcomp = revcomp.push_reverse_complement(base);
+            return Some((*kmer).min(*revcomp));
+        }
+        let kmer = self.next()?;
+        let revcomp = kmer.reverse_complement();
+        self.revcomp = Some(revcomp);
+        Some(kmer.min(revcomp))
+    } // end of next_canonical
 } // end of impl for KmerSeqIterator
 
 
 
+/// converts range bounds to a range with excluded end, unbounded end being sequence length.
+/// Used by DNA and amino acid kmer iterators so that they have the same range semantics.
+/// Bounds at usize::MAX saturate, giving a range the callers reject as going beyond the sequence.
+pub fn get_bounded_range<R : RangeBounds<usize>>(range : &R, seq_len : usize) -> Range<usize> {
+    let start = match range.start_bound() {
+        Bound::Included(first) => *first,
+        Bound::Excluded(first) => first.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(last) => last.saturating_add(1),
+        Bound::Excluded(last) => *last,
+        Bound::Unbounded => seq_len,
+    };
+    start..end
+} // end of get_bounded_range
+
+
+
 // KmerSeqIterator impl over generic Kmer
 
 impl <'a, Kmer>  KmerSeqIteratorT for KmerSeqIterator<'a, Kmer> 
@@ -67,6 +126,8 @@ impl <'a, Kmer>  KmerSeqIteratorT for KmerSeqIterator<'a, Kmer>
     type KmerVal = Kmer;
 
     fn next(&mut self) -> Option<Kmer> {
+       // a reverse complement kept by next_canonical is no longer the one of previous
+       self.revcomp = None;
        // check for end of iterator
        let next_base;
        match self.seqiter.next() {
@@ -175,29 +236,24 @@ impl  <T:KmerT> KmerGenerator<T> {
     pub fn generate_weighted_kmer(&self, seq : &Sequence) -> FnvHashMap<T,u32>  where Self : KmerGenerationPattern<T> {
         self.generate_kmer_distribution(seq)
     }
+    /// kmers with their multiplicities, iterating over the distribution map without copying it in a Vec
+    pub fn generate_weighted_kmer_iter(&self, seq : &Sequence) -> impl Iterator<Item=(T, u32)>  where Self : KmerGenerationPattern<T> {
+        self.generate_kmer_distribution(seq).into_iter()
+    }
     ///
     pub fn get_kmer_size(&self) -> usize { self.kmer_size as usize}
 }  // end of impl KmerGenerator
 
 
-/// A utility to convert FnvHashMap<T,usize> to Vec<(T, usize)> 
+/// iterates over (kmer, count) of a kmer distribution without copying it
+pub fn hashmap_count_iter<T:CompressedKmerT+ std::hash::Hash>(kmer_distribution: &FnvHashMap<T,u32>) -> impl Iterator<Item=(T, u32)> + '_ {
+    kmer_distribution.iter().map(|(kmer, count)| (*kmer, *count))
+}
+
+/// A utility to convert FnvHashMap<T,u32> to Vec<(T, u32)>.
+/// To consume the pairs once, [hashmap_count_iter] or [KmerGenerator::generate_weighted_kmer_iter] avoid the Vec.
 pub fn hashmap_count_to_vec_count<T:CompressedKmerT+ std::hash::Hash>(kmer_distribution: &FnvHashMap<T,u32>) -> Vec<(T, u32)> {
-        // convert to a Vec
-        let mut hashed_kmers = kmer_distribution.keys();
-        let mut weighted_kmer = Vec::<(T,u32)>::with_capacity(kmer_distribution.len());
-        loop {
-            match hashed_kmers.next() {
-                Some(key) => {
-                    if let Some(weight) = kmer_distribution.get(key) {
-                        // get back to Kmer16b32bit from 
-                        weighted_kmer.push((*key,*weight));
-                    };
-                },
-                None => break,
-            }
-        }
-        //
-    return weighted_kmer;
+    hashmap_count_iter(kmer_distribution).collect()
 }   // end of hashmap_count_to_vec_count
 
 
@@ -289,7 +345,7 @@ impl KmerGenerationPattern<Kmer16b32bit> for KmerGenerator<Kmer16b32bit> {
         let nb_kmer = nb_kmer.min(get_nbkmer_guess(seq));
         let mut kmer_vect = Vec::<Kmer16b32bit>::with_capacity(nb_kmer);
         let mut kmeriter = KmerSeqIterator::<Kmer16b32bit>::new(self.kmer_size, seq);
-        kmeriter.set_range(begin, end).unwrap();
+        kmeriter.set_range(begin..end).unwrap();
         loop {
             match kmeriter.next() {
                 Some(kmer) => kmer_vect.push(kmer),
@@ -354,7 +410,7 @@ impl<'a> KmerGenerationPattern<Kmer32bit> for KmerGenerator<Kmer32bit> {
         loop {
             match kmeriter.next(){
                 Some(kmer) => {
-                    trace!(" storing kmer {} ", String::from_utf8_lossy(kmer.get_uncompressed_kmer().as_slice()));                    // we must convert Kmer64bit to u64 and be able to retrieve the original Kmer64bit
+                    trace!(" storing kmer {} ", kmer);                    // we must convert Kmer64bit to u64 and be able to retrieve the original Kmer64bit
                     *kmer_distribution.entry(kmer).or_insert(0) += 1;
                 },
                 None => break,
@@ -385,12 +441,12 @@ impl<'a> KmerGenerationPattern<Kmer32bit> for KmerGenerator<Kmer32bit> {
         let nb_kmer = nb_kmer.min(get_nbkmer_guess(seq));
         let mut kmer_vect = Vec::<Kmer32bit>::with_capacity(nb_kmer);
         let mut kmeriter = KmerSeqIterator::<Kmer32bit>::new(self.kmer_size, seq);
-        kmeriter.set_range(begin, end).unwrap();
+        kmeriter.set_range(begin..end).unwrap();
         // as we have set range in kmeriter we iter as long as we get a kmer
         loop {
             match kmeriter.next(){
                 Some(kmer) => {
-                    trace!(" storing kmer {} ", String::from_utf8_lossy(kmer.get_uncompressed_kmer().as_slice()));                    // we must convert Kmer64bit to u64 and be able to retrieve the original Kmer64bit
+                    trace!(" storing kmer {} ", kmer);                    // we must convert Kmer64bit to u64 and be able to retrieve the original Kmer64bit
                     kmer_vect.push(kmer);
                 },
                 None => break,
@@ -464,7 +520,7 @@ impl KmerGenerationPattern<Kmer64bit> for KmerGenerator<Kmer64bit> {
         loop {
             match kmeriter.next(){
                 Some(kmer) => {
-                    trace!(" storing {} ", String::from_utf8_lossy(kmer.get_uncompressed_kmer().as_slice()));                    // we must convert Kmer64bit to u64 and be able to retrieve the original Kmer64bit
+                    trace!(" storing {} ", kmer);                    // we must convert Kmer64bit to u64 and be able to retrieve the original Kmer64bit
                     *kmer_distribution.entry(kmer).or_insert(0) += 1;
                     if nb_base == 0 {
                         nb_base = kmer.1;
@@ -503,7 +559,7 @@ impl KmerGenerationPattern<Kmer64bit> for KmerGenerator<Kmer64bit> {
         let nb_kmer = nb_kmer.min(get_nbkmer_guess(seq));
         let mut kmer_vect = Vec::<Kmer64bit>::with_capacity(nb_kmer);
         let mut kmeriter = KmerSeqIterator::<Kmer64bit>::new(self.kmer_size, seq);
-        kmeriter.set_range(begin, end).unwrap();
+        kmeriter.set_range(begin..end).unwrap();
         // as we have set range in kmeriter we iter as long as we get a kmer
         loop {
             match kmeriter.next(){
@@ -657,8 +713,11 @@ mod tests {
         // get a sequence with 2 bits compression
         let seq = Sequence::new(&slu8,2);
         let mut kmergen32 = KmerSeqIterator::<Kmer16b32bit>::new(16, &seq);
+        // bounds at usize::MAX give a range beyond sequence end
+        assert!(kmergen32.set_range(3..=usize::MAX).is_err());
+        assert!(kmergen32.set_range((std::ops::Bound::Excluded(usize::MAX), std::ops::Bound::Unbounded)).is_err());
         //
-        kmergen32.set_range(3,25).unwrap();
+        kmergen32.set_range(3..25).unwrap();
         //
         for i in 3..25-16+1 {
             match kmergen32.next() {
@@ -835,6 +894,13 @@ mod tests {
             log::debug!("kmer {:?}  {} ,   weight {}, exact : {}", ukmer , String::from_utf8_lossy(ukmer.as_slice()), x.1, exact);
             assert_eq!(*exact, x.1);
         }
+        // iterator variant gives the same pairs
+        let mut from_iter : Vec<(Kmer32bit, u32)> = KmerGenerator::new(3).generate_weighted_kmer_iter(&seq).collect();
+        let mut from_vec = hashmap_count_to_vec_count(&weighted_kmer_h);
+        from_iter.sort_unstable();
+        from_vec.sort_unstable();
+        assert_eq!(from_iter, from_vec);
+        assert_eq!(hashmap_count_iter(&weighted_kmer_h).map(|(_, c)| c as usize).sum::<usize>(), seqstr.len() - 2);
     } // end of test_generate_weighted_kmer32bit
     
 
@@ -956,5 +1022,47 @@ mod tests {
         } // end match
     }  // end of test_gen_kmer64bit_50bases
 
-    
+
+    // canonical kmers of an iterator, with the reverse complement of each kmer
+    fn check_next_canonical<Kmer>(seq : &Sequence, kmer_size : u8)
+            where Kmer : CompressedKmerT + KmerBuilder<Kmer> + std::fmt::Debug {
+        let mut expected = Vec::<Kmer>::new();
+        let mut kmergen = KmerSeqIterator::<Kmer>::new(kmer_size, seq);
+        while let Some(kmer) = kmergen.next() {
+            expected.push(kmer.reverse_complement().min(kmer));
+        }
+        let mut kmergen = KmerSeqIterator::<Kmer>::new(kmer_size, seq);
+        let mut canonical = Vec::<Kmer>::new();
+        while let Some(kmer) = kmergen.next_canonical() {
+            canonical.push(kmer);
+        }
+        assert_eq!(canonical, expected);
+        // calls to next in between and a new range restart the reverse complement
+        let mut kmergen = KmerSeqIterator::<Kmer>::new(kmer_size, seq);
+        let mut mixed = Vec::<Kmer>::new();
+        for i in 0..expected.len() {
+            let kmer = if i % 5 == 3 { kmergen.next().map(|k| k.reverse_complement().min(k)) } else { kmergen.next_canonical() };
+            mixed.push(kmer.unwrap());
+        }
+        assert_eq!(mixed, expected);
+        kmergen.set_range(3..seq.size()).unwrap();
+        assert_eq!(kmergen.next_canonical(), Some(expected[3]));
+        assert_eq!(kmergen.next_canonical(), Some(expected[4]));
+    }
+
+    #[test]
+    fn test_next_canonical() {
+        log_init();
+        let seqstr = "TCAAAGGGAAACATTCAAAATCAGTATGCGCCCGTTCAGTTACGTATTGCTCTCGCTAATGAGATGGGCTGGGTACAGAG";
+        let seq = Sequence::new(seqstr.as_bytes(), 2);
+        check_next_canonical::<Kmer16b32bit>(&seq, 16);
+        check_next_canonical::<Kmer32bit>(&seq, 11);
+        check_next_canonical::<Kmer32bit>(&seq, 14);
+        check_next_canonical::<Kmer64bit>(&seq, 21);
+        check_next_canonical::<Kmer64bit>(&seq, 32);
+        // the reverse complement of a pushed kmer
+        let kmer = <Kmer32bit as std::str::FromStr>::from_str("ACGGT").unwrap();
+        assert_eq!(kmer.reverse_complement().push_reverse_complement(0b01), kmer.push(0b01).reverse_complement());
+    } // end of test_next_canonical
+
 }  // end of mod tests
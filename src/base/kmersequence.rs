@@ -0,0 +1,226 @@
+//! This module provides [KmerSequence], the kmer generation along a sequence on which generic kmer processing
+//! (sketching in [seqsketchgen](crate::sketching::seqsketchgen), counting ...) is written.
+//!
+//! It is implemented here for DNA sequences ([Sequence], [SequenceView], raw bytes in a [DnaSlice]) and in
+//! [kmeraa](crate::aautils::kmeraa) for amino acid sequences.
+
+
+use num::{Bounded, One, Zero};
+
+use super::{kmertraits::*, kmergenerator::*};
+
+
+// We need a guess to allocate HashMap used with Kmer Generation
+// for very long sequence we must avoid nb_kmer to sequence length! Find a  good heuristic
+/// guess of the number of kmers of a sequence, used to allocate hash tables
+pub fn get_nbkmer_guess(seq : &Sequence) -> usize {
+    get_nbkmer_guess_size(seq.size())
+} // end of get_nbkmer_guess
+
+
+/// same as [get_nbkmer_guess] for a sequence of size bases
+pub fn get_nbkmer_guess_size(size : usize) -> usize {
+    let nb = 100_000_000 * (1usize + size.max(1).ilog2() as usize);
+    size.min(nb)
+} // end of get_nbkmer_guess_size
+
+
+
+/// A sequence along which kmers of type Kmer can be generated.
+pub trait KmerSequence<Kmer> : Sync {
+    /// number of bases (residues for amino acid sequences)
+    fn get_nb_bases(&self) -> usize;
+    /// an upper bound guess of the number of kmers, used to allocate hash tables
+    fn get_nbkmer_guess(&self) -> usize;
+    /// calls f on each kmer of size kmer_size, in sequence order
+    fn for_each_kmer<G : FnMut(Kmer)>(&self, kmer_size : usize, f : G);
+    /// calls f on each kmer of size kmer_size in canonical form (min of kmer and its reverse complement), in sequence order.
+    /// DNA sequences update the reverse complement with each pushed base, the default computes it for each kmer.
+    fn for_each_canonical_kmer<G : FnMut(Kmer)>(&self, kmer_size : usize, mut f : G)
+            where Kmer : CompressedKmerT {
+        self.for_each_kmer(kmer_size, |kmer| f(kmer.reverse_complement().min(kmer)));
+    }
+}
+
+
+impl <Kmer> KmerSequence<Kmer> for Sequence
+        where Kmer : CompressedKmerT + KmerBuilder<Kmer> {
+    //
+    fn get_nb_bases(&self) -> usize {
+        self.size()
+    }
+
+    fn get_nbkmer_guess(&self) -> usize {
+        get_nbkmer_guess(self)
+    }
+
+    fn for_each_kmer<G : FnMut(Kmer)>(&self, kmer_size : usize, mut f : G) {
+        let mut kmergen = KmerSeqIterator::<Kmer>::new(kmer_size as u8, self);
+        kmergen.set_range(0..self.size()).unwrap();
+        while let Some(kmer) = kmergen.next() {
+            f(kmer);
+        }
+    }
+
+    fn for_each_canonical_kmer<G : FnMut(Kmer)>(&self, kmer_size : usize, mut f : G) {
+        let mut kmergen = KmerSeqIterator::<Kmer>::new(kmer_size as u8, self);
+        kmergen.set_range(0..self.size()).unwrap();
+        while let Some(kmer) = kmergen.next_canonical() {
+            f(kmer);
+        }
+    }
+} // end of impl KmerSequence for Sequence
+
+
+
+impl <Kmer> KmerSequence<Kmer> for SequenceView<'_>
+        where Kmer : CompressedKmerT + KmerBuilder<Kmer> {
+    //
+    fn get_nb_bases(&self) -> usize {
+        self.size()
+    }
+
+    fn get_nbkmer_guess(&self) -> usize {
+        get_nbkmer_guess_size(self.size())
+    }
+
+    fn for_each_kmer<G : FnMut(Kmer)>(&self, kmer_size : usize, mut f : G) {
+        if self.size() == 0 {
+            return;
+        }
+        let mut kmergen = KmerSeqIterator::<Kmer>::new_view(kmer_size as u8, self).unwrap();
+        while let Some(kmer) = kmergen.next() {
+            f(kmer);
+        }
+    }
+
+    fn for_each_canonical_kmer<G : FnMut(Kmer)>(&self, kmer_size : usize, mut f : G) {
+        if self.size() == 0 {
+            return;
+        }
+        let mut kmergen = KmerSeqIterator::<Kmer>::new_view(kmer_size as u8, self).unwrap();
+        while let Some(kmer) = kmergen.next_canonical() {
+            f(kmer);
+        }
+    }
+} // end of impl KmerSequence for SequenceView
+
+
+
+/// A DNA sequence given as raw bytes, for example a fasta record, sketched without copy in a [Sequence].  
+/// Bases are encoded during kmer generation, lower case being accepted. Kmers containing a byte other than ACGT
+/// (N, IUPAC codes ...) are skipped, as is done when records are split in ACGT runs (see [split_acgt_runs](crate::io::split_acgt_runs)).
+#[derive(Copy,Clone,Debug)]
+pub struct DnaSlice<'a>(pub &'a [u8]);
+
+
+// 2 bits encoding of base, as in Alphabet2b
+#[inline]
+fn encode_acgt(c : u8) -> Option<u8> {
+    match c {
+        b'A' | b'a' => Some(0b00),
+        b'C' | b'c' => Some(0b01),
+        b'G' | b'g' => Some(0b10),
+        b'T' | b't' => Some(0b11),
+        _ => None,
+    }
+} // end of encode_acgt
+
+
+/// generates kmers of size kmer_size along raw bytes, each byte being encoded on nb_bits bits by encode.
+/// Kmers containing a byte not encoded (encode returns None) are skipped.
+pub(crate) fn for_each_encoded_kmer<Kmer, E, G>(seq : &[u8], kmer_size : usize, nb_bits : usize, encode : E, mut f : G)
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                E : Fn(u8) -> Option<u8>,
+                G : FnMut(Kmer) {
+    //
+    assert!(kmer_size > 0 && kmer_size <= Kmer::get_nb_base_max(), "kmer size {} not supported by kmer type", kmer_size);
+    let val_bits = 8 * std::mem::size_of::<Kmer::Val>();
+    let mask : Kmer::Val = if nb_bits * kmer_size >= val_bits { Kmer::Val::max_value() } else { (Kmer::Val::one() << (nb_bits * kmer_size)) - Kmer::Val::one() };
+    let mut val = Kmer::Val::zero();
+    // number of valid bases at end of val
+    let mut nb_valid = 0usize;
+    for c in seq {
+        match encode(*c) {
+            Some(code) => {
+                val = ((val << nb_bits) | Kmer::Val::from(code)) & mask;
+                nb_valid += 1;
+                if nb_valid >= kmer_size {
+                    f(<Kmer as KmerBuilder<Kmer>>::build(val, kmer_size as u8));
+                }
+            },
+            None => {
+                val = Kmer::Val::zero();
+                nb_valid = 0;
+            },
+        }
+    }
+} // end of for_each_encoded_kmer
+
+
+impl <Kmer> KmerSequence<Kmer> for DnaSlice<'_>
+        where Kmer : CompressedKmerT + KmerBuilder<Kmer> {
+    //
+    fn get_nb_bases(&self) -> usize {
+        self.0.len()
+    }
+
+    fn get_nbkmer_guess(&self) -> usize {
+        get_nbkmer_guess_size(self.0.len())
+    }
+
+    fn for_each_kmer<G : FnMut(Kmer)>(&self, kmer_size : usize, f : G) {
+        for_each_encoded_kmer(self.0, kmer_size, 2, encode_acgt, f);
+    }
+
+    fn for_each_canonical_kmer<G : FnMut(Kmer)>(&self, kmer_size : usize, mut f : G) {
+        assert!(kmer_size > 0 && kmer_size <= Kmer::get_nb_base_max(), "kmer size {} not supported by kmer type", kmer_size);
+        let val_bits = 8 * std::mem::size_of::<Kmer::Val>();
+        let mask : Kmer::Val = if 2 * kmer_size >= val_bits { Kmer::Val::max_value() } else { (Kmer::Val::one() << (2 * kmer_size)) - Kmer::Val::one() };
+        // val and its reverse complement are updated with each base
+        let (mut val, mut revcomp) = (Kmer::Val::zero(), Kmer::Val::zero());
+        let mut nb_valid = 0usize;
+        for c in self.0 {
+            match encode_acgt(*c) {
+                Some(code) => {
+                    val = ((val << 2) | Kmer::Val::from(code)) & mask;
+                    revcomp = (revcomp >> 2) | (Kmer::Val::from(code ^ 0b11) << (2 * (kmer_size - 1)));
+                    nb_valid += 1;
+                    if nb_valid >= kmer_size {
+                        f(<Kmer as KmerBuilder<Kmer>>::build(val.min(revcomp), kmer_size as u8));
+                    }
+                },
+                None => {
+                    (val, revcomp) = (Kmer::Val::zero(), Kmer::Val::zero());
+                    nb_valid = 0;
+                },
+            }
+        }
+    } // end of for_each_canonical_kmer
+} // end of impl KmerSequence for DnaSlice
+
+
+
+/// A DNA sequence whose kmers are generated in canonical form (see [KmerSequence::for_each_canonical_kmer]).  
+/// Generic sketching functions ([probminhash3a_signature](crate::sketching::seqsketchgen::probminhash3a_signature) ...) given canonical sequences sketch canonical kmers
+/// with a hash function not computing reverse complements, at the cost of forward kmers.
+#[derive(Copy,Clone,Debug)]
+pub struct CanonicalKmers<'a, Seq : ?Sized>(pub &'a Seq);
+
+
+impl <Kmer, Seq> KmerSequence<Kmer> for CanonicalKmers<'_, Seq>
+        where   Kmer : CompressedKmerT,
+                Seq : KmerSequence<Kmer> + ?Sized {
+    //
+    fn get_nb_bases(&self) -> usize {
+        self.0.get_nb_bases()
+    }
+
+    fn get_nbkmer_guess(&self) -> usize {
+        self.0.get_nbkmer_guess()
+    }
+
+    fn for_each_kmer<G : FnMut(Kmer)>(&self, kmer_size : usize, f : G) {
+        self.0.for_each_canonical_kmer(kmer_size, f);
+    }
+} // end of impl KmerSequence for CanonicalKmers
@@ -0,0 +1,335 @@
+//! This module provides a kmer membership structure [KmerSet] answering "is this kmer in reference X" queries.
+//!
+//! The set can be backed by :
+//! - an exact hash set, no false positive but memory proportional to the number of distinct kmers,
+//! - a Bloom filter, with a fixed memory and a false positive rate chosen at construction,
+//! - a Cuckoo filter, also approximate with a fixed memory, but supporting removal of kmers.
+//!
+//! Removal is possible with the exact and Cuckoo backends, so dynamic sets can be maintained
+//! (for example removing host kmers from a sample set with [KmerSet::remove_sequences]).
+//!
+//! It can be built from sequences or from fasta/fastq files.
+
+
+use std::hash::Hash;
+#[cfg(feature="io")]
+use std::path::Path;
+
+use fnv::FnvHashSet;
+use bloom::{BloomFilter, ASMS};
+use cuckoofilter::CuckooFilter;
+use metrohash::MetroHash64;
+
+pub use super::{kmertraits::*, kmergenerator::*, sequence::Sequence};
+
+#[cfg(feature="io")]
+use crate::io::read_acgt_sequences;
+
+
+/// describes the structure storing kmers
+#[derive(Copy,Clone,Debug)]
+pub enum KmerSetBackend {
+    /// an exact hash set
+    Exact,
+    /// a Bloom filter with false positive rate fpr dimensioned for capacity kmers
+    Bloom{fpr : f32, capacity : usize},
+    /// a Cuckoo filter dimensioned for capacity kmers, supporting removal
+    Cuckoo{capacity : usize},
+}
+
+
+// the storage corresponding to KmerSetBackend
+enum KmerSetStore<Kmer> {
+    Exact(FnvHashSet<Kmer>),
+    Bloom(BloomFilter),
+    Cuckoo(CuckooFilter<MetroHash64>),
+}
+
+
+/// A set of kmers supporting insertion and membership queries.
+/// If the set is canonical a kmer and its reverse complement are identified.
+pub struct KmerSet<Kmer> where Kmer : CompressedKmerT + Hash {
+    /// kmer size
+    kmer_size : usize,
+    /// true if kmers are stored canonically
+    canonical : bool,
+    /// number of kmers present (approximate for Bloom and Cuckoo filters)
+    nb_inserted : usize,
+    //
+    store : KmerSetStore<Kmer>,
+} // end of KmerSet
+
+
+
+impl <Kmer> KmerSet<Kmer> where Kmer : CompressedKmerT + Hash {
+
+    /// allocates an empty set
+    pub fn new(kmer_size : usize, canonical : bool, backend : KmerSetBackend) -> Self {
+        let store = match backend {
+            KmerSetBackend::Exact => KmerSetStore::Exact(FnvHashSet::default()),
+            KmerSetBackend::Bloom{fpr, capacity} => {
+                let capacity = capacity.clamp(1, u32::MAX as usize) as u32;
+                KmerSetStore::Bloom(BloomFilter::with_rate(fpr, capacity))
+            },
+            KmerSetBackend::Cuckoo{capacity} => KmerSetStore::Cuckoo(CuckooFilter::with_capacity(capacity.max(1))),
+        };
+        KmerSet{kmer_size, canonical, nb_inserted : 0, store}
+    }
+
+    /// returns kmer size
+    pub fn get_kmer_size(&self) -> usize {
+        self.kmer_size
+    }
+
+    /// returns true if kmers are stored in canonical form
+    pub fn is_canonical(&self) -> bool {
+        self.canonical
+    }
+
+    /// returns the number of distinct kmers in the set (approximate for Bloom and Cuckoo backends)
+    pub fn len(&self) -> usize {
+        self.nb_inserted
+    }
+
+    /// returns true if no kmer was inserted
+    pub fn is_empty(&self) -> bool {
+        self.nb_inserted == 0
+    }
+
+    // get the key under which a kmer is stored
+    #[inline]
+    fn get_key(&self, kmer : &Kmer) -> Kmer {
+        if self.canonical {
+            kmer.reverse_complement().min(*kmer)
+        }
+        else {
+            *kmer
+        }
+    }
+
+    /// inserts a kmer. Returns true if the kmer was not already present.
+    pub fn insert(&mut self, kmer : &Kmer) -> bool {
+        let key = self.get_key(kmer);
+        let inserted = match &mut self.store {
+            KmerSetStore::Exact(set) => set.insert(key),
+            KmerSetStore::Bloom(bloom) => bloom.insert(&key),
+            KmerSetStore::Cuckoo(cuckoo) => {
+                match cuckoo.test_and_add(&key) {
+                    Ok(inserted) => inserted,
+                    Err(_) => {
+                        log::error!("KmerSet : cuckoo filter is full, an other kmer was evicted. Increase capacity");
+                        true
+                    },
+                }
+            },
+        };
+        if inserted {
+            self.nb_inserted += 1;
+        }
+        inserted
+    }
+
+    /// returns true if the kmer is in the set. Can give false positives with the Bloom filter backend.
+    pub fn contains(&self, kmer : &Kmer) -> bool {
+        let key = self.get_key(kmer);
+        match &self.store {
+            KmerSetStore::Exact(set) => set.contains(&key),
+            KmerSetStore::Bloom(bloom) => bloom.contains(&key),
+            KmerSetStore::Cuckoo(cuckoo) => cuckoo.contains(&key),
+        }
+    }
+
+    /// returns true if the backend supports removal of kmers (exact set and Cuckoo filter)
+    pub fn supports_removal(&self) -> bool {
+        !matches!(self.store, KmerSetStore::Bloom(_))
+    }
+
+    // error returned by removals with the Bloom backend
+    fn check_removal(&self) -> Result<(), String> {
+        if self.supports_removal() {
+            Ok(())
+        }
+        else {
+            log::error!("KmerSet : removal not possible with Bloom filter backend");
+            Err("KmerSet : removal not possible with Bloom filter backend".to_string())
+        }
+    }
+
+    /// removes a kmer. Returns true if the kmer was present.
+    /// With the Cuckoo backend only kmers previously inserted should be removed, otherwise a kmer sharing
+    /// the same fingerprint can be removed. With the Bloom backend removal is not possible and an error is returned.
+    pub fn remove(&mut self, kmer : &Kmer) -> Result<bool, String> {
+        self.check_removal()?;
+        Ok(self.remove_key(kmer))
+    }
+
+    // removes a kmer from a backend supporting removal
+    fn remove_key(&mut self, kmer : &Kmer) -> bool {
+        let key = self.get_key(kmer);
+        let removed = match &mut self.store {
+            KmerSetStore::Exact(set) => set.remove(&key),
+            KmerSetStore::Cuckoo(cuckoo) => cuckoo.delete(&key),
+            KmerSetStore::Bloom(_) => false,
+        };
+        if removed {
+            self.nb_inserted = self.nb_inserted.saturating_sub(1);
+        }
+        removed
+    }
+
+} // end of impl KmerSet
+
+
+
+impl <Kmer> KmerSet<Kmer> where Kmer : CompressedKmerT + KmerBuilder<Kmer> + Hash {
+
+    /// inserts all kmers of sequences (which must be 2 bits encoded)
+    pub fn insert_sequences(&mut self, vseq : &Vec<&Sequence>) {
+        let kmer_size = self.kmer_size as u8;
+        for seq in vseq {
+            let mut kmergen = KmerSeqIterator::<Kmer>::new(kmer_size, seq);
+            kmergen.set_range(0..seq.size()).unwrap();
+            while let Some(kmer) = kmergen.next() {
+                self.insert(&kmer);
+            }
+        }
+    } // end of insert_sequences
+
+    /// removes all kmers of sequences from the set. Returns the number of kmers removed,
+    /// or an error with the Bloom backend which does not support removal.
+    pub fn remove_sequences(&mut self, vseq : &Vec<&Sequence>) -> Result<usize, String> {
+        self.check_removal()?;
+        let kmer_size = self.kmer_size as u8;
+        let mut nb_removed = 0;
+        for seq in vseq {
+            let mut kmergen = KmerSeqIterator::<Kmer>::new(kmer_size, seq);
+            kmergen.set_range(0..seq.size()).unwrap();
+            while let Some(kmer) = kmergen.next() {
+                if self.remove_key(&kmer) {
+                    nb_removed += 1;
+                }
+            }
+        }
+        Ok(nb_removed)
+    } // end of remove_sequences
+
+    /// returns the number of kmers of a sequence found in the set and the total number of kmers of the sequence.
+    pub fn count_hits(&self, seq : &Sequence) -> (usize, usize) {
+        let mut nb_hits = 0;
+        let mut nb_kmers = 0;
+        let mut kmergen = KmerSeqIterator::<Kmer>::new(self.kmer_size as u8, seq);
+        kmergen.set_range(0..seq.size()).unwrap();
+        while let Some(kmer) = kmergen.next() {
+            nb_kmers += 1;
+            if self.contains(&kmer) {
+                nb_hits += 1;
+            }
+        }
+        (nb_hits, nb_kmers)
+    } // end of count_hits
+
+    /// builds a set from sequences
+    pub fn from_sequences(vseq : &Vec<&Sequence>, kmer_size : usize, canonical : bool, backend : KmerSetBackend) -> Self {
+        let mut set = KmerSet::<Kmer>::new(kmer_size, canonical, backend);
+        set.insert_sequences(vseq);
+        set
+    }
+
+    /// builds a set from a fasta/fastq file (possibly compressed with gzip, bzip2, xz or zstd). Records are split at non ACGT bases.
+    #[cfg(feature="io")]
+    pub fn from_file(path : &Path, kmer_size : usize, canonical : bool, backend : KmerSetBackend) -> Result<Self, String> {
+        let seqs = read_acgt_sequences(path, kmer_size)?;
+        let vseq : Vec<&Sequence> = seqs.iter().collect();
+        Ok(KmerSet::<Kmer>::from_sequences(&vseq, kmer_size, canonical, backend))
+    }
+
+} // end of impl KmerSet
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::base::kmer32bit::Kmer32bit;
+    #[cfg(feature="io")]
+    use std::io::Write;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_kmerset_backends() {
+        log_init_test();
+        let reference = Sequence::new(b"ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGC", 2);
+        let query = Sequence::new(b"GAAAATTTCCGGATCAT", 2);
+        let query_rc = query.get_reverse_complement();
+        let other = Sequence::new(b"GGGGGGGGGGGGGGGGGG", 2);
+        let kmer_size = 10;
+        let nb_distinct = reference.size() - kmer_size + 1;
+        for backend in [KmerSetBackend::Exact, KmerSetBackend::Bloom{fpr : 0.001, capacity : 1000}] {
+            let set = KmerSet::<Kmer32bit>::from_sequences(&vec![&reference], kmer_size, true, backend);
+            match backend {
+                KmerSetBackend::Exact => assert_eq!(set.len(), nb_distinct),
+                // a false positive at insertion is not counted
+                _ => assert!(set.len() <= nb_distinct && set.len() >= nb_distinct - 5),
+            }
+            let (nb_hits, nb_kmers) = set.count_hits(&query);
+            assert_eq!(nb_hits, nb_kmers);
+            let (nb_hits, nb_kmers) = set.count_hits(&query_rc);
+            assert_eq!(nb_hits, nb_kmers);
+            let (nb_hits, _) = set.count_hits(&other);
+            assert_eq!(nb_hits, 0);
+        }
+    } // end of test_kmerset_backends
+
+
+    #[test]
+    fn test_kmerset_removal() {
+        log_init_test();
+        let sample = Sequence::new(b"ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGC", 2);
+        let host = Sequence::new(b"GGATCATCGTACGGAGCATG", 2);
+        let kmer_size = 10;
+        for backend in [KmerSetBackend::Exact, KmerSetBackend::Cuckoo{capacity : 1000}] {
+            let mut set = KmerSet::<Kmer32bit>::from_sequences(&vec![&sample], kmer_size, true, backend);
+            assert!(set.supports_removal());
+            let nb_kmers = set.len();
+            let nb_removed = set.remove_sequences(&vec![&host]).unwrap();
+            assert_eq!(nb_removed, host.size() - kmer_size + 1);
+            assert_eq!(set.len(), nb_kmers - nb_removed);
+            assert_eq!(set.count_hits(&host).0, 0);
+            assert_eq!(set.count_hits(&Sequence::new(b"ATCATGCCCCTTTAGAAAA", 2)).0, 10);
+            // a removed kmer can be reinserted
+            let kmer : Kmer32bit = "GGATCATCGT".parse().unwrap();
+            assert!(set.insert(&kmer));
+            assert!(set.contains(&kmer));
+        }
+        let mut set = KmerSet::<Kmer32bit>::from_sequences(&vec![&sample], kmer_size, true, KmerSetBackend::Bloom{fpr : 0.01, capacity : 1000});
+        assert!(!set.supports_removal());
+        assert!(set.remove_sequences(&vec![&host]).is_err());
+        assert!(set.remove(&"GGATCATCGT".parse().unwrap()).is_err());
+    } // end of test_kmerset_removal
+
+
+    #[cfg(feature="io")]
+    #[test]
+    fn test_kmerset_from_file() {
+        log_init_test();
+        let dir = crate::test_dir("kmerset");
+        let path = dir.join("kmerutils_kmerset.fa");
+        let mut file = std::fs::File::create(&path).unwrap();
+        writeln!(file, ">seq1\nATCATGCCCCTTTAGAAAANTTTCCGGATCATCGTAC\n>seq2\nggagcatgcgtacaacg").unwrap();
+        drop(file);
+        let set = KmerSet::<Kmer32bit>::from_file(&path, 8, false, KmerSetBackend::Exact).unwrap();
+        // runs of length 19, 17 and 17 give 12 + 10 + 10 kmers
+        assert_eq!(set.len(), 32);
+        assert!(set.contains(&"GGAGCATG".parse().unwrap()));
+        assert!(!set.contains(&"AAAATTTC".parse().unwrap()));
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_kmerset_from_file
+
+} // end of mod tests
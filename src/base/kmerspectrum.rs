@@ -0,0 +1,131 @@
+//! This module estimates genome size, kmer coverage and sequencing error rate from the kmer abundance histogram (kmer spectrum) of a read set.
+//!
+//! The model is the simple one used by GenomeScope-like tools for haploid genomes :
+//! - the left part of the histogram, up to the first valley, is made of kmers containing sequencing errors,
+//! - the kmers of the genome are distributed around a peak at the kmer coverage.
+//!
+//! The kmer coverage is the mean abundance of solid kmers in a window around the peak, the genome size is the number
+//! of solid kmer occurrences divided by kmer coverage, and the error rate per base e is deduced from the fraction f
+//! of kmer occurrences in the error part by f = 1 - (1-e)^k.
+//!
+//! The histogram can be obtained from an exact count table with [KmerCountTable::get_abundance_histogram](super::kmertable::KmerCountTable::get_abundance_histogram).
+
+
+/// Result of spectrum analysis
+#[derive(Copy,Clone,Debug)]
+pub struct GenomeEstimate {
+    /// kmer size used for counting
+    pub kmer_size : usize,
+    /// abundance at the first valley of histogram. Kmers with smaller counts are considered errors.
+    pub error_threshold : usize,
+    /// abundance at the peak of solid kmers
+    pub peak : usize,
+    /// estimated kmer coverage
+    pub kmer_coverage : f64,
+    /// estimated genome size
+    pub genome_size : f64,
+    /// estimated sequencing error rate per base
+    pub error_rate : f64,
+} // end of GenomeEstimate
+
+
+impl GenomeEstimate {
+    /// converts kmer coverage to base coverage for reads of length read_len : C = Ck * L / (L - k + 1)
+    pub fn get_base_coverage(&self, read_len : usize) -> f64 {
+        if read_len < self.kmer_size {
+            return 0.;
+        }
+        self.kmer_coverage * read_len as f64 / (read_len - self.kmer_size + 1) as f64
+    }
+} // end of impl GenomeEstimate
+
+
+
+/// estimates genome features from an abundance histogram. histo\[i\] is the number of distinct kmers seen i times (histo\[0\] is ignored).
+/// The last slot of the histogram is generally a cumulated slot for high counts and is excluded from peak detection.
+pub fn estimate_genome_from_spectrum(histo : &[u64], kmer_size : usize) -> Result<GenomeEstimate, String> {
+    //
+    if histo.len() < 4 {
+        return Err("estimate_genome_from_spectrum : histogram too short".to_string());
+    }
+    let last = histo.len() - 1;
+    // first valley : first abundance where histogram increases
+    let mut valley = 1;
+    while valley < last && histo[valley + 1] <= histo[valley] {
+        valley += 1;
+    }
+    if valley >= last - 1 {
+        log::error!("estimate_genome_from_spectrum : no valley found in histogram");
+        return Err("estimate_genome_from_spectrum : no valley found, coverage too low or histogram too short".to_string());
+    }
+    // peak of solid kmers, excluding last slot
+    let mut peak = valley;
+    for i in valley..last {
+        if histo[i] > histo[peak] {
+            peak = i;
+        }
+    }
+    // kmer coverage : mean abundance in window around peak
+    let window_end = (2 * peak).min(last);
+    let window_start = valley.max(peak / 2);
+    let (sum_w, sum_iw) = (window_start..window_end).fold((0f64, 0f64), |acc, i| (acc.0 + histo[i] as f64, acc.1 + (i as f64) * histo[i] as f64));
+    if sum_w <= 0. {
+        return Err("estimate_genome_from_spectrum : empty peak".to_string());
+    }
+    let kmer_coverage = sum_iw / sum_w;
+    // occurrences of error kmers and solid kmers
+    let error_occ = (1..valley).fold(0f64, |acc, i| acc + (i as f64) * histo[i] as f64);
+    let solid_occ = (valley..histo.len()).fold(0f64, |acc, i| acc + (i as f64) * histo[i] as f64);
+    let genome_size = solid_occ / kmer_coverage;
+    let error_fraction = error_occ / (error_occ + solid_occ);
+    let error_rate = 1. - (1. - error_fraction).powf(1. / kmer_size as f64);
+    //
+    let estimate = GenomeEstimate{kmer_size, error_threshold : valley, peak, kmer_coverage, genome_size, error_rate};
+    log::info!("estimate_genome_from_spectrum : {:?}", estimate);
+    Ok(estimate)
+} // end of estimate_genome_from_spectrum
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::distributions::Distribution;
+    use rand_distr::Poisson;
+    use rand::SeedableRng;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_spectrum_estimate() {
+        log_init_test();
+        // simulate a genome of 200_000 distinct kmers at coverage 30, and error kmers seen 1 or 2 times
+        let genome_size = 200_000;
+        let coverage = 30.;
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(4664);
+        let poisson = Poisson::new(coverage).unwrap();
+        let mut histo = vec![0u64; 101];
+        for _ in 0..genome_size {
+            let c : f64 = poisson.sample(&mut rng);
+            histo[(c as usize).min(100)] += 1;
+        }
+        histo[1] += 300_000;
+        histo[2] += 20_000;
+        histo[3] += 1_000;
+        //
+        let estimate = estimate_genome_from_spectrum(&histo, 21).unwrap();
+        assert!((estimate.kmer_coverage - coverage).abs() < 1.);
+        assert!((estimate.genome_size - genome_size as f64).abs() / (genome_size as f64) < 0.05);
+        assert!(estimate.error_rate > 0. && estimate.error_rate < 0.01);
+        assert!(estimate.get_base_coverage(150) > estimate.kmer_coverage);
+        // a flat histogram has no valley
+        assert!(estimate_genome_from_spectrum(&[0, 10, 9, 8, 7, 6], 21).is_err());
+    } // end of test_spectrum_estimate
+
+} // end of mod tests
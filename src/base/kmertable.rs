@@ -0,0 +1,305 @@
+//! This module provides an exact kmer count table.
+//!
+//! Contrary to [KmerCounter](super::kmercount::KmerCounter) which relies on Bloom and Cuckoo filters
+//! and gives approximate counts, [KmerCountTable] stores each distinct kmer with its multiplicity in a hashmap.
+//! It is the structure used to exchange counts with other tools (see module [jellyfish](super::jellyfish))
+//! and should be used for moderate size data (genomes, not large read sets).
+
+
+use std::hash::Hash;
+
+use fnv::{FnvHashMap, FnvBuildHasher};
+
+pub use super::{kmertraits::*, kmergenerator::*, sequence::Sequence};
+
+
+/// An exact kmer count table.
+/// If the table is canonical each kmer is stored as the minimum of itself and its reverse complement.
+pub struct KmerCountTable<Kmer> where Kmer : CompressedKmerT + Hash {
+    /// kmer size
+    kmer_size : usize,
+    /// true if kmers are stored canonically
+    canonical : bool,
+    /// the counts
+    counts : FnvHashMap<Kmer, u32>,
+}  // end of KmerCountTable
+
+
+
+impl <Kmer> KmerCountTable<Kmer> where Kmer : CompressedKmerT + Hash {
+    /// allocates an empty table
+    pub fn new(kmer_size : usize, canonical : bool) -> Self {
+        KmerCountTable{kmer_size, canonical, counts : FnvHashMap::default()}
+    }
+
+    /// allocates an empty table with room for capacity kmers
+    pub fn with_capacity(kmer_size : usize, canonical : bool, capacity : usize) -> Self {
+        let counts = FnvHashMap::with_capacity_and_hasher(capacity, FnvBuildHasher::default());
+        KmerCountTable{kmer_size, canonical, counts}
+    }
+
+    /// returns kmer size
+    pub fn get_kmer_size(&self) -> usize {
+        self.kmer_size
+    }
+
+    /// returns true if kmers are stored in canonical form
+    pub fn is_canonical(&self) -> bool {
+        self.canonical
+    }
+
+    /// returns the number of distinct kmers
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// returns true if table contains no kmer
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// returns the sum of counts of all kmers
+    pub fn get_total_count(&self) -> u64 {
+        self.counts.values().fold(0u64, |acc, c| acc + *c as u64)
+    }
+
+    // get the key under which a kmer is stored
+    #[inline]
+    fn get_key(&self, kmer : &Kmer) -> Kmer {
+        if self.canonical {
+            kmer.reverse_complement().min(*kmer)
+        }
+        else {
+            *kmer
+        }
+    }
+
+    /// increment count of a kmer by 1
+    pub fn insert_kmer(&mut self, kmer : &Kmer) {
+        self.add_count(kmer, 1);
+    }
+
+    /// increment count of a kmer by count (saturating at u32::MAX)
+    pub fn add_count(&mut self, kmer : &Kmer, count : u32) {
+        let key = self.get_key(kmer);
+        let c = self.counts.entry(key).or_insert(0);
+        *c = c.saturating_add(count);
+    }
+
+    /// returns count of a kmer, 0 if not present
+    pub fn get_count(&self, kmer : &Kmer) -> u32 {
+        let key = self.get_key(kmer);
+        match self.counts.get(&key) {
+            Some(c) => *c,
+            None    => 0,
+        }
+    }
+
+    /// returns an iterator over (kmer, count)
+    pub fn iter(&self) -> impl Iterator<Item=(&Kmer, &u32)> {
+        self.counts.iter()
+    }
+
+    /// returns a vector of (kmer, count) sorted by kmer
+    pub fn get_sorted_counts(&self) -> Vec<(Kmer, u32)> {
+        let mut v : Vec<(Kmer, u32)> = self.counts.iter().map(|(k,c)| (*k, *c)).collect();
+        v.sort_unstable_by_key(|a| a.0);
+        v
+    }
+
+    /// returns the abundance histogram : slot i contains the number of distinct kmers seen i times.  
+    /// Kmers seen more than max_count times are accumulated in the last slot (of index max_count).
+    pub fn get_abundance_histogram(&self, max_count : usize) -> Vec<u64> {
+        let mut histo = vec![0u64; max_count + 1];
+        for c in self.counts.values() {
+            histo[(*c as usize).min(max_count)] += 1;
+        }
+        histo
+    }
+
+    /// keeps only kmers whose count is in \[min_count, max_count\]
+    pub fn retain_abundance(&mut self, min_count : u32, max_count : u32) {
+        self.counts.retain(|_, c| *c >= min_count && *c <= max_count);
+    }
+
+    /// returns a reference to the underlying hashmap
+    pub fn get_map(&self) -> &FnvHashMap<Kmer, u32> {
+        &self.counts
+    }
+
+    // tables must have same kmer size and canonical mode to be combined
+    fn check_compatible(&self, other : &Self) -> Result<(), String> {
+        if self.kmer_size != other.kmer_size || self.canonical != other.canonical {
+            log::error!("KmerCountTable : incompatible tables, kmer sizes : {} {}, canonical : {} {}",
+                    self.kmer_size, other.kmer_size, self.canonical, other.canonical);
+            return Err("KmerCountTable : incompatible tables".to_string());
+        }
+        Ok(())
+    }
+
+    // builds a table of kmers present in self or other, combining counts with fcount. Count 0 kmers are dropped.
+    fn combine<F>(&self, other : &Self, keep_other : bool, fcount : F) -> Result<Self, String>
+            where F : Fn(u32, u32) -> u32 {
+        self.check_compatible(other)?;
+        let mut table = KmerCountTable::<Kmer>::with_capacity(self.kmer_size, self.canonical, self.len());
+        for (kmer, c) in &self.counts {
+            let count = fcount(*c, other.counts.get(kmer).copied().unwrap_or(0));
+            if count > 0 {
+                table.counts.insert(*kmer, count);
+            }
+        }
+        if keep_other {
+            for (kmer, c) in &other.counts {
+                if !self.counts.contains_key(kmer) {
+                    let count = fcount(0, *c);
+                    if count > 0 {
+                        table.counts.insert(*kmer, count);
+                    }
+                }
+            }
+        }
+        Ok(table)
+    } // end of combine
+
+    /// returns the table of kmers present in self or other. Counts are added.
+    pub fn union(&self, other : &Self) -> Result<Self, String> {
+        self.combine(other, true, |a, b| a.saturating_add(b))
+    }
+
+    /// returns the table of kmers present in both tables. Counts are added.
+    pub fn intersection(&self, other : &Self) -> Result<Self, String> {
+        self.combine(other, false, |a, b| if b > 0 { a.saturating_add(b) } else { 0 })
+    }
+
+    /// returns the table of kmers of self not present in other, with their counts in self.
+    pub fn difference(&self, other : &Self) -> Result<Self, String> {
+        self.combine(other, false, |a, b| if b > 0 { 0 } else { a })
+    }
+
+    /// weighted union : each kmer gets the max of its counts in the 2 tables.
+    pub fn weighted_union(&self, other : &Self) -> Result<Self, String> {
+        self.combine(other, true, |a, b| a.max(b))
+    }
+
+    /// weighted intersection : each kmer gets the min of its counts in the 2 tables (kmers with min 0 are dropped).
+    pub fn weighted_intersection(&self, other : &Self) -> Result<Self, String> {
+        self.combine(other, false, |a, b| a.min(b))
+    }
+
+    /// weighted (multiset) difference : each kmer of self gets its count in self minus its count in other,
+    /// kmers with resulting count 0 are dropped.
+    pub fn weighted_difference(&self, other : &Self) -> Result<Self, String> {
+        self.combine(other, false, |a, b| a.saturating_sub(b))
+    }
+
+} // end of impl KmerCountTable
+
+
+
+impl <Kmer> KmerCountTable<Kmer> where Kmer : CompressedKmerT + KmerBuilder<Kmer> + Hash {
+
+    /// count all kmers of sequences (which must be 2 bits encoded) in the table
+    pub fn count_sequences(&mut self, vseq : &Vec<&Sequence>) {
+        let kmer_size = self.kmer_size as u8;
+        for seq in vseq {
+            let mut kmergen = KmerSeqIterator::<Kmer>::new(kmer_size, seq);
+            kmergen.set_range(0..seq.size()).unwrap();
+            while let Some(kmer) = kmergen.next() {
+                self.insert_kmer(&kmer);
+            }
+        }
+    } // end of count_sequences
+
+    /// builds a table from a list of sequences
+    pub fn from_sequences(vseq : &Vec<&Sequence>, kmer_size : usize, canonical : bool) -> Self {
+        let mut table = KmerCountTable::<Kmer>::new(kmer_size, canonical);
+        table.count_sequences(vseq);
+        table
+    }
+
+} // end of impl KmerCountTable
+
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::base::kmer32bit::Kmer32bit;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_count_table_canonical() {
+        log_init_test();
+        // sequence and its reverse complement
+        let seq = Sequence::new(b"AACGTTGCA", 2);
+        let seqrc = Sequence::new(b"TGCAACGTT", 2);
+        //
+        let table = KmerCountTable::<Kmer32bit>::from_sequences(&vec![&seq], 4, false);
+        assert_eq!(table.len(), 6);
+        assert_eq!(table.get_total_count(), 6);
+        //
+        let table = KmerCountTable::<Kmer32bit>::from_sequences(&vec![&seq, &seqrc], 4, true);
+        assert_eq!(table.get_total_count(), 12);
+        for (kmer, count) in table.iter() {
+            log::debug!("kmer {} count {}", kmer, count);
+            assert!(*count >= 2);
+        }
+        let kmer : Kmer32bit = "AACG".parse().unwrap();
+        assert_eq!(table.get_count(&kmer), table.get_count(&kmer.reverse_complement()));
+    } // end of test_count_table_canonical
+
+
+    #[test]
+    fn test_count_table_set_operations() {
+        log_init_test();
+        let k = |s : &str| -> Kmer32bit { s.parse().unwrap() };
+        let mut t1 = KmerCountTable::<Kmer32bit>::new(3, false);
+        t1.add_count(&k("AAC"), 3);
+        t1.add_count(&k("ACG"), 1);
+        t1.add_count(&k("CGT"), 2);
+        let mut t2 = KmerCountTable::<Kmer32bit>::new(3, false);
+        t2.add_count(&k("ACG"), 4);
+        t2.add_count(&k("CGT"), 2);
+        t2.add_count(&k("GTT"), 5);
+        //
+        let u = t1.union(&t2).unwrap();
+        assert_eq!(u.len(), 4);
+        assert_eq!(u.get_count(&k("ACG")), 5);
+        assert_eq!(u.get_count(&k("GTT")), 5);
+        let i = t1.intersection(&t2).unwrap();
+        assert_eq!(i.len(), 2);
+        assert_eq!(i.get_count(&k("CGT")), 4);
+        let d = t1.difference(&t2).unwrap();
+        assert_eq!(d.len(), 1);
+        assert_eq!(d.get_count(&k("AAC")), 3);
+        //
+        let wu = t1.weighted_union(&t2).unwrap();
+        assert_eq!(wu.len(), 4);
+        assert_eq!(wu.get_count(&k("ACG")), 4);
+        let wi = t1.weighted_intersection(&t2).unwrap();
+        assert_eq!(wi.len(), 2);
+        assert_eq!(wi.get_count(&k("ACG")), 1);
+        let wd = t2.weighted_difference(&t1).unwrap();
+        assert_eq!(wd.len(), 2);
+        assert_eq!(wd.get_count(&k("ACG")), 3);
+        assert_eq!(wd.get_count(&k("CGT")), 0);
+        // incompatible tables
+        let t3 = KmerCountTable::<Kmer32bit>::new(3, true);
+        assert!(t1.union(&t3).is_err());
+        // abundance filter
+        let mut f = t1.union(&t2).unwrap();
+        f.retain_abundance(3, 4);
+        assert_eq!(f.len(), 2);
+        assert_eq!(f.get_count(&k("AAC")), 3);
+        assert_eq!(f.get_count(&k("CGT")), 4);
+    } // end of test_count_table_set_operations
+
+} // end of mod tests
@@ -18,6 +18,13 @@ pub trait KmerT {
     fn reverse_complement(&self) -> Self;
     /// push a (compressed! or 2 bit encoded) base at right end of kmer
     fn push(&self, base : u8) -> Self;
+    /// self being the reverse complement of a kmer, returns the reverse complement of the kmer with base pushed.  
+    /// So reverse complements can be updated as kmers are generated (a shift and the complement of base at left end)
+    /// instead of being computed for each kmer.  
+    /// The default goes through [reverse_complement](Self::reverse_complement), DNA kmer types of the crate update the word in place.
+    fn push_reverse_complement(&self, base : u8) -> Self where Self : Sized {
+        self.reverse_complement().push(base).reverse_complement()
+    }
     /// each kmer type must know how to dump itself.
     fn dump(&self,  bufw : &mut dyn io::Write) -> io::Result<usize>;
 }
@@ -37,6 +44,11 @@ pub trait CompressedKmerT : KmerT+Ord+Copy  where Self::Val : Hash + Ord + From<
     fn get_compressed_value(&self) -> Self::Val;
     /// get Kmer as a Vec\<u8\>
     fn get_uncompressed_kmer(&self) -> Vec<u8>;
+    /// appends the decoded bases of Kmer to buf, so that decoding many kmers can reuse one buffer.
+    /// The default goes through [get_uncompressed_kmer](Self::get_uncompressed_kmer), kmer types of the crate decode in place.
+    fn write_uncompressed(&self, buf : &mut Vec<u8>) {
+        buf.extend_from_slice(&self.get_uncompressed_kmer());
+    }
     /// returns the size in bits of word supporting compressed kmer.
     /// not just number of base * size of base. In fact it is number of bits of Self::Val
     fn get_bitsize(&self) -> usize;
@@ -19,6 +19,7 @@ pub use alphabet::*;
 
 pub mod alphabet;
 pub mod sequence;
+pub mod pack2b;
 pub mod kmertraits;
 pub mod kmer;
 pub mod kmer32bit;
@@ -26,5 +27,17 @@ pub mod kmer16b32bit;
 pub mod kmer64bit;
 
 pub mod kmercount;
+pub mod kmertable;
+pub mod jellyfish;
+pub mod kmc;
+pub mod sharedkmers;
+pub mod kmerspectrum;
+pub mod kmerset;
+pub mod spillcount;
+pub mod heavyhitters;
+pub mod profile;
+pub mod profiledist;
 pub mod kmergenerator;
+pub mod kmersequence;
+pub mod scratch;
 
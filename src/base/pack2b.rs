@@ -0,0 +1,382 @@
+//! Packing of ASCII bases ACGT to 2 bits by base and unpacking, as stored in [Sequence](super::sequence::Sequence)
+//! with nb_bits = 2.
+//!
+//! Bases are packed 4 by byte, the first base in the 2 high bits, with the codes of [Alphabet2b](super::alphabet::Alphabet2b)
+//! (A -> 0b00, C -> 0b01, G -> 0b10, T -> 0b11).
+//! On x86_64 the packing uses AVX2 or SSSE3 instructions (detected at run time), on aarch64 NEON instructions,
+//! otherwise a scalar loop. All paths give the same bytes.
+
+
+/// instruction sets used for packing
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SimdLevel {
+    Scalar,
+    Ssse3,
+    Avx2,
+    Neon,
+}
+
+
+/// returns the best instruction set available on the running cpu
+pub fn get_simd_level() -> SimdLevel {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return SimdLevel::Avx2;
+        }
+        if is_x86_feature_detected!("ssse3") {
+            return SimdLevel::Ssse3;
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        // neon is mandatory on aarch64
+        return SimdLevel::Neon;
+    }
+    #[allow(unreachable_code)]
+    SimdLevel::Scalar
+} // end of get_simd_level
+
+
+
+// 2 bits code of an ASCII base, 4 if not in ACGT
+#[inline(always)]
+fn encode_2b(c : u8) -> u8 {
+    match c {
+        b'A' => 0b00,
+        b'C' => 0b01,
+        b'G' => 0b10,
+        b'T' => 0b11,
+        _    => 4,
+    }
+}
+
+const DECODE_2B : [u8;4] = [b'A', b'C', b'G', b'T'];
+
+
+/// packs bases of raw 4 by byte and pushes the bytes in packed, as long as the groups of 4 bases are in ACGT (upper case).
+/// Returns the number of bases packed, a multiple of 4. The caller encodes the remaining bases (the incomplete last group
+/// or the group beginning with the first invalid base).
+pub fn pack_2b(raw : &[u8], packed : &mut Vec<u8>) -> usize {
+    pack_2b_with(get_simd_level(), raw, packed)
+}
+
+
+/// unpacks all bytes of packed, 4 ASCII bases by byte, pushed in unpacked.
+pub fn unpack_2b(packed : &[u8], unpacked : &mut Vec<u8>) {
+    unpack_2b_with(get_simd_level(), packed, unpacked)
+}
+
+
+
+fn pack_2b_with(level : SimdLevel, raw : &[u8], packed : &mut Vec<u8>) -> usize {
+    let nb_groups = raw.len() / 4;
+    let start = packed.len();
+    packed.resize(start + nb_groups, 0);
+    let out = &mut packed[start..];
+    // the simd kernels pack full vectors and stop at the first vector with an invalid base
+    let done = match level {
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => unsafe { x86::pack_avx2(raw, out) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Ssse3 => unsafe { x86::pack_ssse3(raw, out) },
+        #[cfg(target_arch = "aarch64")]
+        SimdLevel::Neon => unsafe { neon::pack_neon(raw, out) },
+        _ => 0,
+    };
+    let nb_packed = done + pack_scalar(&raw[4*done..4*nb_groups], &mut out[done..]);
+    packed.truncate(start + nb_packed);
+    4 * nb_packed
+} // end of pack_2b_with
+
+
+
+fn unpack_2b_with(level : SimdLevel, packed : &[u8], unpacked : &mut Vec<u8>) {
+    let start = unpacked.len();
+    unpacked.resize(start + 4 * packed.len(), 0);
+    let out = &mut unpacked[start..];
+    let done = match level {
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => unsafe { x86::unpack_avx2(packed, out) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Ssse3 => unsafe { x86::unpack_ssse3(packed, out) },
+        #[cfg(target_arch = "aarch64")]
+        SimdLevel::Neon => unsafe { neon::unpack_neon(packed, out) },
+        _ => 0,
+    };
+    unpack_scalar(&packed[done..], &mut out[4*done..]);
+} // end of unpack_2b_with
+
+
+
+// packs groups of 4 bases until an invalid base, returns the number of bytes written
+fn pack_scalar(raw : &[u8], out : &mut [u8]) -> usize {
+    for (i, group) in raw.chunks_exact(4).enumerate() {
+        let codes = [encode_2b(group[0]), encode_2b(group[1]), encode_2b(group[2]), encode_2b(group[3])];
+        if codes.iter().any(|c| *c > 3) {
+            return i;
+        }
+        out[i] = (codes[0] << 6) | (codes[1] << 4) | (codes[2] << 2) | codes[3];
+    }
+    raw.len() / 4
+} // end of pack_scalar
+
+
+
+fn unpack_scalar(packed : &[u8], out : &mut [u8]) {
+    for (byte, bases) in packed.iter().zip(out.chunks_exact_mut(4)) {
+        bases[0] = DECODE_2B[(byte >> 6) as usize];
+        bases[1] = DECODE_2B[((byte >> 4) & 0b11) as usize];
+        bases[2] = DECODE_2B[((byte >> 2) & 0b11) as usize];
+        bases[3] = DECODE_2B[(byte & 0b11) as usize];
+    }
+} // end of unpack_scalar
+
+
+
+// The simd kernels index tables by the low nibble of a base : A -> 1, C -> 3, G -> 7, T -> 4.
+// A base is valid if it is equal to the base expected at its nibble (VALID_NIBBLE),
+// its code is then CODE_NIBBLE at its nibble. Slot 0 holds 0xFF which has nibble 15, so no byte matches it.
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const VALID_NIBBLE : [u8;16] = [0xFF, b'A', 0, b'C', b'T', 0, 0, b'G', 0, 0, 0, 0, 0, 0, 0, 0];
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const CODE_NIBBLE : [u8;16] = [0, 0b00, 0, 0b01, 0b11, 0, 0, 0b10, 0, 0, 0, 0, 0, 0, 0, 0];
+// ASCII base of a code
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const DECODE_NIBBLE : [u8;16] = [b'A', b'C', b'G', b'T', 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+    use super::{VALID_NIBBLE, CODE_NIBBLE, DECODE_NIBBLE};
+
+    // codes of 16 bases packed in the 4 low bytes, None if a base is not in ACGT
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    unsafe fn pack16(bases : __m128i) -> Option<i32> {
+        let valid = _mm_loadu_si128(VALID_NIBBLE.as_ptr() as *const __m128i);
+        let codes = _mm_loadu_si128(CODE_NIBBLE.as_ptr() as *const __m128i);
+        let nibbles = _mm_and_si128(bases, _mm_set1_epi8(0x0F));
+        let ok = _mm_cmpeq_epi8(_mm_shuffle_epi8(valid, nibbles), bases);
+        if _mm_movemask_epi8(ok) != 0xFFFF {
+            return None;
+        }
+        let c = _mm_shuffle_epi8(codes, nibbles);
+        // pairs c0*4+c1 in 16 bits, then quads (c0*4+c1)*16 + c2*4+c3 in 32 bits
+        let pairs = _mm_maddubs_epi16(c, _mm_set1_epi16(0x0104));
+        let quads = _mm_madd_epi16(pairs, _mm_set1_epi32(0x0001_0010));
+        let gather = _mm_setr_epi8(0, 4, 8, 12, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1);
+        Some(_mm_cvtsi128_si32(_mm_shuffle_epi8(quads, gather)))
+    }
+
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn pack_ssse3(raw : &[u8], out : &mut [u8]) -> usize {
+        let mut done = 0;
+        while 4 * done + 16 <= raw.len() {
+            let bases = _mm_loadu_si128(raw.as_ptr().add(4 * done) as *const __m128i);
+            match pack16(bases) {
+                Some(quad) => out[done..done+4].copy_from_slice(&quad.to_le_bytes()),
+                None => break,
+            }
+            done += 4;
+        }
+        done
+    } // end of pack_ssse3
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn pack_avx2(raw : &[u8], out : &mut [u8]) -> usize {
+        let valid = _mm256_broadcastsi128_si256(_mm_loadu_si128(VALID_NIBBLE.as_ptr() as *const __m128i));
+        let codes = _mm256_broadcastsi128_si256(_mm_loadu_si128(CODE_NIBBLE.as_ptr() as *const __m128i));
+        let gather = _mm256_setr_epi8(0, 4, 8, 12, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+                                      0, 4, 8, 12, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1);
+        let mut done = 0;
+        while 4 * done + 32 <= raw.len() {
+            let bases = _mm256_loadu_si256(raw.as_ptr().add(4 * done) as *const __m256i);
+            let nibbles = _mm256_and_si256(bases, _mm256_set1_epi8(0x0F));
+            let ok = _mm256_cmpeq_epi8(_mm256_shuffle_epi8(valid, nibbles), bases);
+            if _mm256_movemask_epi8(ok) != -1 {
+                break;
+            }
+            let c = _mm256_shuffle_epi8(codes, nibbles);
+            let pairs = _mm256_maddubs_epi16(c, _mm256_set1_epi16(0x0104));
+            let quads = _mm256_madd_epi16(pairs, _mm256_set1_epi32(0x0001_0010));
+            // 4 bytes in the low dword of each 128 bits lane, brought together in the low 64 bits
+            let gathered = _mm256_permutevar8x32_epi32(_mm256_shuffle_epi8(quads, gather), _mm256_setr_epi32(0, 4, 0, 0, 0, 0, 0, 0));
+            let octet = _mm_cvtsi128_si64(_mm256_castsi256_si128(gathered));
+            out[done..done+8].copy_from_slice(&octet.to_le_bytes());
+            done += 8;
+        }
+        // a last vector of 16 bases
+        if 4 * done + 16 <= raw.len() {
+            if let Some(quad) = pack16(_mm_loadu_si128(raw.as_ptr().add(4 * done) as *const __m128i)) {
+                out[done..done+4].copy_from_slice(&quad.to_le_bytes());
+                done += 4;
+            }
+        }
+        done
+    } // end of pack_avx2
+
+    // lane i of a vector of 16 bases keeps the code at bits 6 - 2*(i%4) of its byte
+    const SELECT : [[u8;16];4] = [
+        [3, 0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0],
+        [0, 3, 0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 3, 0, 0],
+        [0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 3, 0],
+        [0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 3],
+    ];
+
+    // 16 ASCII bases of the 4 low bytes of packed, each byte being broadcast to 4 lanes
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    unsafe fn unpack16(packed : __m128i) -> __m128i {
+        let spread = _mm_setr_epi8(0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3);
+        let b = _mm_shuffle_epi8(packed, spread);
+        // a 16 bits shift followed by a mask 0b11 gives bits of the same byte
+        let select = |k : usize| _mm_loadu_si128(SELECT[k].as_ptr() as *const __m128i);
+        let c = _mm_or_si128(_mm_or_si128(_mm_and_si128(_mm_srli_epi16(b, 6), select(0)), _mm_and_si128(_mm_srli_epi16(b, 4), select(1))),
+                    _mm_or_si128(_mm_and_si128(_mm_srli_epi16(b, 2), select(2)), _mm_and_si128(b, select(3))));
+        _mm_shuffle_epi8(_mm_loadu_si128(DECODE_NIBBLE.as_ptr() as *const __m128i), c)
+    }
+
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn unpack_ssse3(packed : &[u8], out : &mut [u8]) -> usize {
+        let mut done = 0;
+        while done + 4 <= packed.len() {
+            let quad = i32::from_le_bytes(packed[done..done+4].try_into().unwrap());
+            let bases = unpack16(_mm_cvtsi32_si128(quad));
+            _mm_storeu_si128(out.as_mut_ptr().add(4 * done) as *mut __m128i, bases);
+            done += 4;
+        }
+        done
+    } // end of unpack_ssse3
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn unpack_avx2(packed : &[u8], out : &mut [u8]) -> usize {
+        let mut done = 0;
+        while done + 8 <= packed.len() {
+            let octet = i64::from_le_bytes(packed[done..done+8].try_into().unwrap());
+            // bytes 0..4 in low lane, bytes 4..8 in high lane
+            let v = _mm_cvtsi64_si128(octet);
+            let low = unpack16(v);
+            let high = unpack16(_mm_srli_si128(v, 4));
+            _mm256_storeu_si256(out.as_mut_ptr().add(4 * done) as *mut __m256i, _mm256_setr_m128i(low, high));
+            done += 8;
+        }
+        done + unpack_ssse3(&packed[done..], &mut out[4*done..])
+    } // end of unpack_avx2
+
+} // end of mod x86
+
+
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+    use super::{VALID_NIBBLE, CODE_NIBBLE, DECODE_NIBBLE};
+
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn pack_neon(raw : &[u8], out : &mut [u8]) -> usize {
+        let valid = vld1q_u8(VALID_NIBBLE.as_ptr());
+        let codes = vld1q_u8(CODE_NIBBLE.as_ptr());
+        let mut done = 0;
+        while 4 * done + 16 <= raw.len() {
+            let bases = vld1q_u8(raw.as_ptr().add(4 * done));
+            let nibbles = vandq_u8(bases, vdupq_n_u8(0x0F));
+            let ok = vceqq_u8(vqtbl1q_u8(valid, nibbles), bases);
+            if vminvq_u8(ok) != 0xFF {
+                break;
+            }
+            let c = vreinterpretq_u16_u8(vqtbl1q_u8(codes, nibbles));
+            // pairs c0*4+c1 in 16 bits, then quads in 32 bits
+            let pairs = vreinterpretq_u32_u16(vorrq_u16(vshlq_n_u16(vandq_u16(c, vdupq_n_u16(0xFF)), 2), vshrq_n_u16(c, 8)));
+            let quads = vorrq_u32(vshlq_n_u32(vandq_u32(pairs, vdupq_n_u32(0xFFFF)), 4), vshrq_n_u32(pairs, 16));
+            let bytes = vmovn_u16(vcombine_u16(vmovn_u32(quads), vdup_n_u16(0)));
+            let mut tmp = [0u8;8];
+            vst1_u8(tmp.as_mut_ptr(), bytes);
+            out[done..done+4].copy_from_slice(&tmp[..4]);
+            done += 4;
+        }
+        done
+    } // end of pack_neon
+
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn unpack_neon(packed : &[u8], out : &mut [u8]) -> usize {
+        let spread : [u8;16] = [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3];
+        let shifts : [i8;16] = [-6, -4, -2, 0, -6, -4, -2, 0, -6, -4, -2, 0, -6, -4, -2, 0];
+        let spread = vld1q_u8(spread.as_ptr());
+        let shifts = vld1q_s8(shifts.as_ptr());
+        let decode = vld1q_u8(DECODE_NIBBLE.as_ptr());
+        let mut done = 0;
+        while done + 4 <= packed.len() {
+            let mut quad = [0u8;16];
+            quad[..4].copy_from_slice(&packed[done..done+4]);
+            let b = vqtbl1q_u8(vld1q_u8(quad.as_ptr()), spread);
+            let c = vandq_u8(vshlq_u8(b, shifts), vdupq_n_u8(3));
+            vst1q_u8(out.as_mut_ptr().add(4 * done), vqtbl1q_u8(decode, c));
+            done += 4;
+        }
+        done
+    } // end of unpack_neon
+
+} // end of mod neon
+
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    fn available_levels() -> Vec<SimdLevel> {
+        let mut levels = vec![SimdLevel::Scalar];
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("ssse3") { levels.push(SimdLevel::Ssse3); }
+            if is_x86_feature_detected!("avx2") { levels.push(SimdLevel::Avx2); }
+        }
+        #[cfg(target_arch = "aarch64")]
+        levels.push(SimdLevel::Neon);
+        levels
+    }
+
+    #[test]
+    fn test_pack2b_levels() {
+        log_init_test();
+        log::info!("simd level : {:?}", get_simd_level());
+        let mut state = 0x1234_5678_9abc_def0u64;
+        let raw : Vec<u8> = (0..1003).map(|_| { state ^= state << 13; state ^= state >> 7; state ^= state << 17; DECODE_2B[(state % 4) as usize] }).collect();
+        // an invalid base in the middle, packing stops at its group
+        let mut with_n = raw.clone();
+        with_n[517] = b'N';
+        for level in available_levels() {
+            for len in [0, 3, 4, 15, 16, 17, 31, 32, 33, 47, 64, 1003] {
+                let mut packed = Vec::new();
+                assert_eq!(pack_2b_with(level, &raw[..len], &mut packed), 4 * (len / 4), "level {:?}", level);
+                let mut reference = Vec::new();
+                pack_2b_with(SimdLevel::Scalar, &raw[..len], &mut reference);
+                assert_eq!(packed, reference, "level {:?} len {}", level, len);
+                let mut unpacked = Vec::new();
+                unpack_2b_with(level, &packed, &mut unpacked);
+                assert_eq!(&unpacked[..], &raw[..4 * (len / 4)], "level {:?} len {}", level, len);
+            }
+            let mut packed = vec![0xFF];
+            assert_eq!(pack_2b_with(level, &with_n, &mut packed), 516);
+            assert_eq!(packed.len(), 1 + 129);
+            let lower : Vec<u8> = raw[..64].to_ascii_lowercase();
+            assert_eq!(pack_2b_with(level, &lower, &mut Vec::new()), 0);
+        }
+        let mut packed = Vec::new();
+        pack_2b(b"ACGTTGCA", &mut packed);
+        assert_eq!(packed, vec![0b00_01_10_11, 0b11_10_01_00]);
+    } // end of test_pack2b_levels
+
+} // end of mod tests
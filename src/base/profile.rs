@@ -0,0 +1,127 @@
+//! This module computes fixed dimension kmer profile vectors of DNA sequences.
+//!
+//! For a small kmer size k (k <= 8) a sequence is mapped to the vector of dimension 4^k of the frequencies of all kmers,
+//! kmers being indexed in lexicographic order (A < C < G < T) by their 2 bits encoding.
+//! The vectors are normalized to sum 1 (if the sequence has at least one kmer) and can be used directly as features
+//! for machine learning embeddings.
+//!
+//! The amino acid counterpart is in module [aautils::profile](crate::aautils::profile).
+//! Distances between profiles are computed in module [base::profiledist](crate::base::profiledist).
+
+
+use ndarray::Array2;
+use crate::parallel::*;
+
+pub use super::{kmertraits::*, kmergenerator::*, kmer32bit::Kmer32bit, sequence::Sequence};
+
+
+/// maximal kmer size for DNA profiles, giving vectors of dimension 4^8 = 65536
+pub const DNA_PROFILE_MAX_KMER_SIZE : usize = 8;
+
+
+/// returns the dimension of profile vectors for kmer size k
+pub fn get_profile_dim(kmer_size : usize) -> usize {
+    1usize << (2 * kmer_size)
+}
+
+
+// checks kmer size is in range 1..=DNA_PROFILE_MAX_KMER_SIZE
+fn check_profile_kmer_size(kmer_size : usize) -> Result<(), String> {
+    if kmer_size == 0 || kmer_size > DNA_PROFILE_MAX_KMER_SIZE {
+        log::error!("kmer profile : kmer size must be between 1 and {}, got {}", DNA_PROFILE_MAX_KMER_SIZE, kmer_size);
+        return Err(format!("kmer profile : kmer size must be between 1 and {}", DNA_PROFILE_MAX_KMER_SIZE));
+    }
+    Ok(())
+}
+
+
+// accumulates kmer counts of a sequence in profile, returns number of kmers
+fn fill_profile(seq : &Sequence, kmer_size : usize, canonical : bool, profile : &mut [f32]) -> usize {
+    let mask = (get_profile_dim(kmer_size) - 1) as u32;
+    let mut nb_kmers = 0;
+    let mut kmergen = KmerSeqIterator::<Kmer32bit>::new(kmer_size as u8, seq);
+    kmergen.set_range(0..seq.size()).unwrap();
+    loop {
+        let next = if canonical { kmergen.next_canonical() } else { kmergen.next() };
+        match next {
+            Some(kmer) => {
+                profile[(kmer.get_compressed_value() & mask) as usize] += 1.;
+                nb_kmers += 1;
+            },
+            None => break,
+        }
+    }
+    if nb_kmers > 0 {
+        let inv = 1. / nb_kmers as f32;
+        profile.iter_mut().for_each(|f| *f *= inv);
+    }
+    nb_kmers
+} // end of fill_profile
+
+
+
+/// computes the normalized kmer frequency vector of a (2 bits encoded) sequence. The vector has dimension 4^k.
+/// If canonical is true a kmer and its reverse complement are counted at the index of the smallest one,
+/// so slots of non canonical kmers stay at 0.
+pub fn kmer_profile(seq : &Sequence, kmer_size : usize, canonical : bool) -> Result<Vec<f32>, String> {
+    check_profile_kmer_size(kmer_size)?;
+    let mut profile = vec![0f32; get_profile_dim(kmer_size)];
+    fill_profile(seq, kmer_size, canonical, &mut profile);
+    Ok(profile)
+} // end of kmer_profile
+
+
+
+/// computes in parallel profiles of sequences. Row i of the returned matrix is the profile of sequence i.
+pub fn kmer_profiles(vseq : &Vec<&Sequence>, kmer_size : usize, canonical : bool) -> Result<Array2<f32>, String> {
+    check_profile_kmer_size(kmer_size)?;
+    let dim = get_profile_dim(kmer_size);
+    let rows : Vec<Vec<f32>> = vseq.par_iter().map(|seq| {
+            let mut profile = vec![0f32; dim];
+            fill_profile(seq, kmer_size, canonical, &mut profile);
+            profile
+        }).collect();
+    let profiles = Array2::<f32>::from_shape_vec((vseq.len(), dim), rows.concat()).unwrap();
+    Ok(profiles)
+} // end of kmer_profiles
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_dna_profile() {
+        log_init_test();
+        let seq = Sequence::new(b"ACGTTA", 2);
+        let profile = kmer_profile(&seq, 2, false).unwrap();
+        assert_eq!(profile.len(), 16);
+        // kmers AC CG GT TT TA with indexes 1 6 11 15 12
+        for i in [1, 6, 11, 15, 12] {
+            assert_eq!(profile[i], 0.2);
+        }
+        assert!((profile.iter().sum::<f32>() - 1.).abs() < 1.0E-6);
+        // canonical : TT -> AA, TA stays, GT -> AC
+        let profile = kmer_profile(&seq, 2, true).unwrap();
+        assert_eq!(profile[1], 0.4);
+        assert_eq!(profile[0], 0.2);
+        assert_eq!(profile[15], 0.);
+        //
+        assert!(kmer_profile(&seq, 9, false).is_err());
+        // parallel version gives same rows
+        let seq2 = Sequence::new(b"ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGG", 2);
+        let profiles = kmer_profiles(&vec![&seq, &seq2], 3, false).unwrap();
+        assert_eq!(profiles.row(1).to_vec(), kmer_profile(&seq2, 3, false).unwrap());
+        assert_eq!(profiles.row(0).to_vec(), kmer_profile(&seq, 3, false).unwrap());
+    } // end of test_dna_profile
+
+} // end of mod tests
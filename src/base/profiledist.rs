@@ -0,0 +1,163 @@
+//! This module computes distances between kmer profile vectors (see [base::profile](crate::base::profile) and
+//! [aautils::profile](crate::aautils::profile)) : cosine, Euclidean and Jensen-Shannon distances.
+//!
+//! Contrary to Jaccard on sketches, which only sees presence of kmers of large size, these distances compare
+//! kmer frequencies and are suited to composition analyses with small k (tetranucleotide frequencies ...).
+//!
+//! [profile_distance_matrix] computes all pairwise distances between rows of a profile matrix, rows being processed in parallel.
+
+
+use ndarray::{Array2, ArrayView1};
+use crate::parallel::*;
+use serde::{Deserialize, Serialize};
+
+
+/// distance between profiles
+#[derive(Copy,Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub enum ProfileDistance {
+    /// 1 - cosine of the angle between profiles, in \[0, 1\] for non negative profiles
+    Cosine,
+    /// L2 distance
+    Euclidean,
+    /// square root of Jensen-Shannon divergence (log base 2) between normalized profiles, in \[0, 1\]
+    JensenShannon,
+}
+
+impl ProfileDistance {
+    /// distance between 2 profiles of same dimension
+    pub fn eval(&self, pa : &[f32], pb : &[f32]) -> f64 {
+        assert_eq!(pa.len(), pb.len(), "profiles must have same dimension");
+        match self {
+            ProfileDistance::Cosine => cosine_distance(pa, pb),
+            ProfileDistance::Euclidean => euclidean_distance(pa, pb),
+            ProfileDistance::JensenShannon => jensen_shannon_distance(pa, pb),
+        }
+    }
+} // end of impl ProfileDistance
+
+
+
+/// 1 - cosine similarity. Returns 1 if one profile is null (an empty sequence), 0 if both are.
+pub fn cosine_distance(pa : &[f32], pb : &[f32]) -> f64 {
+    let (mut dot, mut norma, mut normb) = (0f64, 0f64, 0f64);
+    for (a, b) in pa.iter().zip(pb) {
+        dot += *a as f64 * *b as f64;
+        norma += *a as f64 * *a as f64;
+        normb += *b as f64 * *b as f64;
+    }
+    if norma == 0. || normb == 0. {
+        return if norma == normb { 0. } else { 1. };
+    }
+    (1. - dot / (norma.sqrt() * normb.sqrt())).max(0.)
+} // end of cosine_distance
+
+
+
+/// Euclidean distance
+pub fn euclidean_distance(pa : &[f32], pb : &[f32]) -> f64 {
+    pa.iter().zip(pb).map(|(a, b)| { let d = *a as f64 - *b as f64; d * d }).sum::<f64>().sqrt()
+}
+
+
+
+/// Jensen-Shannon distance, the square root of the Jensen-Shannon divergence with log in base 2.
+/// Profiles are normalized to sum 1 and must have non negative values. Returns 1 if one profile is null, 0 if both are.
+pub fn jensen_shannon_distance(pa : &[f32], pb : &[f32]) -> f64 {
+    let suma : f64 = pa.iter().map(|a| *a as f64).sum();
+    let sumb : f64 = pb.iter().map(|b| *b as f64).sum();
+    if suma <= 0. || sumb <= 0. {
+        return if suma == sumb { 0. } else { 1. };
+    }
+    // sum over slots of p log(2p / (p+q)) + q log(2q / (p+q))
+    let mut divergence = 0f64;
+    for (a, b) in pa.iter().zip(pb) {
+        let p = *a as f64 / suma;
+        let q = *b as f64 / sumb;
+        let m = p + q;
+        if p > 0. {
+            divergence += p * (2. * p / m).log2();
+        }
+        if q > 0. {
+            divergence += q * (2. * q / m).log2();
+        }
+    }
+    (0.5 * divergence).clamp(0., 1.).sqrt()
+} // end of jensen_shannon_distance
+
+
+
+/// computes distances between all rows of profiles (as returned by kmer_profiles or aa_kmer_profiles).
+/// Returns the symmetric matrix of distances, rows being processed in parallel.
+pub fn profile_distance_matrix(profiles : &Array2<f32>, distance : ProfileDistance) -> Array2<f64> {
+    let nb = profiles.nrows();
+    log::debug!("profile_distance_matrix, nb profiles : {}, dim : {}, {:?}", nb, profiles.ncols(), distance);
+    // rows must be contiguous to be compared as slices
+    let profiles = profiles.as_standard_layout();
+    let rows : Vec<ArrayView1<f32>> = profiles.rows().into_iter().collect();
+    // row i holds distances to rows j > i
+    let upper : Vec<Vec<f64>> = (0..nb).into_par_iter().map(|i| {
+            let row_i = rows[i].as_slice().unwrap();
+            rows[i + 1..].iter().map(|row_j| distance.eval(row_i, row_j.as_slice().unwrap())).collect()
+        }).collect();
+    let mut matrix = Array2::<f64>::zeros((nb, nb));
+    for (i, dists) in upper.iter().enumerate() {
+        for (k, d) in dists.iter().enumerate() {
+            matrix[[i, i + 1 + k]] = *d;
+            matrix[[i + 1 + k, i]] = *d;
+        }
+    }
+    matrix
+} // end of profile_distance_matrix
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::base::profile::kmer_profiles;
+    use crate::base::sequence::Sequence;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_profile_distances() {
+        log_init_test();
+        let pa = [0.5f32, 0.5, 0., 0.];
+        let pb = [0f32, 0., 0.5, 0.5];
+        let pc = [1f32, 1., 0., 0.];
+        // disjoint supports
+        assert!((cosine_distance(&pa, &pb) - 1.).abs() < 1.0E-10);
+        assert!((jensen_shannon_distance(&pa, &pb) - 1.).abs() < 1.0E-10);
+        assert!((euclidean_distance(&pa, &pb) - 1.).abs() < 1.0E-10);
+        // same direction
+        assert!(cosine_distance(&pa, &pc).abs() < 1.0E-10);
+        assert!(jensen_shannon_distance(&pa, &pc).abs() < 1.0E-10);
+        assert!((euclidean_distance(&pa, &pc) - 0.5f64.sqrt()).abs() < 1.0E-6);
+        // p = (1, 0), q = (1/2, 1/2) : JS = 1/2 (log(4/3)) + 1/2 (1/2 log(2/3) + 1/2 log 2) in base 2
+        let js = 0.5 * (4f64 / 3.).log2() + 0.25 * (2f64 / 3.).log2() + 0.25;
+        assert!((jensen_shannon_distance(&[1., 0.], &[0.5, 0.5]) - js.sqrt()).abs() < 1.0E-6);
+        assert_eq!(cosine_distance(&[0., 0.], &pa[..2]), 1.);
+        //
+        let seqs = [Sequence::new(b"ACGTACGTACGTAAAC", 2), Sequence::new(b"ACGTACGTACGTAAAG", 2), Sequence::new(b"TTTTTTTTTTGGGGGG", 2)];
+        let profiles = kmer_profiles(&seqs.iter().collect(), 2, false).unwrap();
+        for kind in [ProfileDistance::Cosine, ProfileDistance::Euclidean, ProfileDistance::JensenShannon] {
+            let matrix = profile_distance_matrix(&profiles, kind);
+            assert_eq!(matrix.dim(), (3, 3));
+            for i in 0..3 {
+                assert_eq!(matrix[[i, i]], 0.);
+                for j in 0..3 {
+                    assert_eq!(matrix[[i, j]], matrix[[j, i]]);
+                }
+            }
+            assert_eq!(matrix[[0, 2]], kind.eval(profiles.row(0).as_slice().unwrap(), profiles.row(2).as_slice().unwrap()));
+            assert!(matrix[[0, 1]] < matrix[[0, 2]]);
+        }
+    } // end of test_profile_distances
+
+} // end of mod tests
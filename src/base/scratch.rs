@@ -0,0 +1,148 @@
+//! This module provides thread-local scratch buffers reused between sequences by the sketching closures of
+//! [seqsketchgen](crate::sketching::seqsketchgen) and by residue encoding in [kmeraa](crate::aautils::kmeraa).
+//!
+//! When millions of short sequences (proteins, reads) are sketched each in its own signature, allocating for each sequence
+//! a weight map (ProbMinHash3a) or a sketcher state (SuperMinHash, OptDensMinHash ...) costs more than sketching itself.
+//! Each thread (of the rayon pool or the calling thread) keeps instead a pool of buffers, indexed by their type and sketch size.
+//! A buffer is taken out of the pool during the sketching of a sequence, reset and given back at the end.
+//! Weight maps that grew beyond [MAX_SCRATCH_WEIGHTS] entries (a large genome) are released instead of being kept,
+//! as are byte buffers (encoded residues) beyond [MAX_SCRATCH_BYTES].
+
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+
+use fnv::FnvHashMap;
+
+
+/// maximum capacity of a weight map kept in a thread pool between sequences
+pub const MAX_SCRATCH_WEIGHTS : usize = 1 << 20;
+
+/// maximum capacity of a byte buffer kept in a thread pool between sequences
+pub const MAX_SCRATCH_BYTES : usize = 1 << 24;
+
+
+thread_local! {
+    // buffers of the current thread, indexed by their type and sketch size
+    static SCRATCH_POOL : RefCell<FnvHashMap<(TypeId, usize), Box<dyn Any>>> = RefCell::new(FnvHashMap::default());
+}
+
+
+/// calls f with a buffer of type T of the current thread, built by new if the thread has no buffer of this type and size.
+/// After f, reset is called on the buffer which is kept for the next call if reset returns true, otherwise it is dropped.
+/// The buffer is out of the pool during f, so f can itself use scratch buffers (a nested call just allocates a new one).
+pub fn with_scratch<T, R, N, G, C>(size : usize, new : N, f : G, reset : C) -> R
+        where   T : 'static,
+                N : FnOnce() -> T,
+                G : FnOnce(&mut T) -> R,
+                C : FnOnce(&mut T) -> bool {
+    let key = (TypeId::of::<T>(), size);
+    let pooled = SCRATCH_POOL.with(|pool| pool.borrow_mut().remove(&key));
+    let mut buffer = match pooled.map(|b| b.downcast::<T>()) {
+        Some(Ok(b)) => *b,
+        _ => new(),
+    };
+    let res = f(&mut buffer);
+    if reset(&mut buffer) {
+        SCRATCH_POOL.with(|pool| pool.borrow_mut().insert(key, Box::new(buffer)));
+    }
+    res
+} // end of with_scratch
+
+
+
+/// calls f with an empty weight map (as [KmerWeights](crate::sketching::seqsketchgen::KmerWeights)) of the current thread
+/// having room for at least capacity entries.
+/// The map is cleared after f and kept if its capacity does not exceed [MAX_SCRATCH_WEIGHTS].
+pub fn with_scratch_weights<V, S, R, G>(capacity : usize, f : G) -> R
+        where   V : Hash + Eq + 'static,
+                S : BuildHasher + Default + 'static,
+                G : FnOnce(&mut HashMap<V, u64, S>) -> R {
+    let new = || HashMap::<V, u64, S>::with_capacity_and_hasher(capacity, S::default());
+    with_scratch(0, new, |weights| {
+            weights.reserve(capacity);
+            f(weights)
+        },
+        |weights| {
+            weights.clear();
+            weights.capacity() <= MAX_SCRATCH_WEIGHTS
+        })
+} // end of with_scratch_weights
+
+
+
+/// calls f with an empty byte buffer of the current thread having room for at least capacity bytes.
+/// The buffer is cleared after f and kept if its capacity does not exceed [MAX_SCRATCH_BYTES].
+pub fn with_scratch_bytes<R, G>(capacity : usize, f : G) -> R
+        where   G : FnOnce(&mut Vec<u8>) -> R {
+    with_scratch(0, || Vec::<u8>::with_capacity(capacity), |bytes| {
+            bytes.reserve(capacity);
+            f(bytes)
+        },
+        |bytes| {
+            bytes.clear();
+            bytes.capacity() <= MAX_SCRATCH_BYTES
+        })
+} // end of with_scratch_bytes
+
+
+
+/// releases all scratch buffers of the current thread
+pub fn clear_scratch() {
+    SCRATCH_POOL.with(|pool| pool.borrow_mut().clear());
+} // end of clear_scratch
+
+
+// number of buffers held by the current thread
+#[cfg(test)]
+fn get_nb_scratch() -> usize {
+    SCRATCH_POOL.with(|pool| pool.borrow().len())
+}
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sketching::seqsketchgen::BuildKmerWeightHasher;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_scratch_reuse() {
+        log_init_test();
+        clear_scratch();
+        // a weight map is reused empty, with its capacity
+        let capacity = with_scratch_weights::<u64, BuildKmerWeightHasher, _, _>(1000, |w| { w.insert(1, 2); w.capacity() });
+        assert!(capacity >= 1000);
+        with_scratch_weights::<u64, BuildKmerWeightHasher, _, _>(10, |w| {
+            assert!(w.is_empty());
+            assert_eq!(w.capacity(), capacity);
+        });
+        assert_eq!(get_nb_scratch(), 1);
+        // a nested call gets its own buffer
+        with_scratch_weights::<u64, BuildKmerWeightHasher, _, _>(10, |w| {
+            w.insert(3, 1);
+            with_scratch_weights::<u64, BuildKmerWeightHasher, _, _>(10, |inner| assert!(inner.is_empty()));
+        });
+        // buffers are indexed by type and size, and dropped if reset refuses them
+        let nb_new = std::cell::Cell::new(0);
+        for size in [10, 10, 20] {
+            with_scratch(size, || { nb_new.set(nb_new.get() + 1); vec![0u8; size] }, |v| assert_eq!(v.len(), size), |_| true);
+        }
+        assert_eq!(nb_new.get(), 2);
+        with_scratch_weights::<u32, BuildKmerWeightHasher, _, _>(MAX_SCRATCH_WEIGHTS + 1, |_| ());
+        assert_eq!(get_nb_scratch(), 3);
+        clear_scratch();
+        assert_eq!(get_nb_scratch(), 0);
+    } // end of test_scratch_reuse
+
+} // end of mod tests
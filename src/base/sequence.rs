@@ -3,6 +3,11 @@
 
 pub use super::alphabet::*;
 
+use std::ops::{Range, RangeBounds};
+
+use super::kmergenerator::get_bounded_range;
+use super::pack2b::{pack_2b, unpack_2b};
+
 //
 //===================================================================================
 // Sequence
@@ -48,8 +53,10 @@ impl  Sequence {
                 seq.reserve(nb_bytes_needed);
                 // 4 bases per byte, we treat indexes i*4 , i*4+1 , i*4+2, i*4+3  in raw
                 let alfa2b = Alphabet2b::new();
+                // simd packing of full bytes, it stops at the first byte with a non ACGT base which is left to base_pack
+                let nb_packed = pack_2b(&raw[..4*nb_full_bytes], &mut seq) / 4;
                 // loop from 0 to nb_bytes_needed-1 include, here nb_bases_by_byte = 4
-                for i in 0..nb_full_bytes {
+                for i in nb_packed..nb_full_bytes {
                     let encoded4b = alfa2b.base_pack(&raw[4*i..4*(i+1)]);
                     seq.push(encoded4b);
                 }
@@ -172,24 +179,16 @@ impl  Sequence {
                 let alfa2b = Alphabet2b::new();
                 let nb_full_bytes = if self.nb_bases_in_last_byte() > 0 { self.seq.len()-1 } else { self.seq.len() };
                 let seqlen = nb_full_bytes * (4 as usize) + self.nb_bases_in_last_byte() as usize;
-                seqvec = (0..seqlen).map(|_| 0).collect();
-                // get a slice of 4 u8
-                let small_slice_ref : &mut [u8] = &mut [0;4];
-                let mut pos : usize = 0;
+                seqvec.reserve(seqlen);
                 // unpack full bytes
-                let nb_full_bytes = if self.nb_bases_in_last_byte() > 0 { self.seq.len()-1 } else { self.seq.len() };
-                for i in 0..nb_full_bytes {
-                    alfa2b.base_unpack(self.seq[i], small_slice_ref);
-                    //
-                    seqvec[pos..(pos+4)].copy_from_slice(small_slice_ref);
-                    pos = pos+4;
-                }
+                unpack_2b(&self.seq[..nb_full_bytes], &mut seqvec);
                 // unpack last byte if necessary
                 if self.nb_bases_in_last_byte() > 0 {
+                    let small_slice_ref : &mut [u8] = &mut [0;4];
                     alfa2b.base_unpack(self.seq[self.seq.len()-1], small_slice_ref);
                     // we have 1 2 or 3 bases more to push
                     let nb_bases = self.nb_bases_in_last_byte() as usize;
-                    seqvec[pos..pos+nb_bases].copy_from_slice(&small_slice_ref[0..nb_bases]);
+                    seqvec.extend_from_slice(&small_slice_ref[0..nb_bases]);
                 }
             },
             // case no compression.
@@ -409,6 +408,17 @@ impl  Sequence {
         // now we loop storing new encoded bytes in self.seq, we store 4 bases at each update until we encounter end of to_add
         // and we possibly fill an incomplete byte at end.
         while nb_scanned < to_add.len() {
+            if nb_bits == 2 {
+                // we are at a byte boundary, runs of ACGT are packed by simd, update_byte takes bases from the first non ACGT
+                let nb_packed = pack_2b(&to_add[nb_scanned..], &mut self.seq);
+                if nb_packed > 0 {
+                    nb_scanned += nb_packed;
+                    already = 0;
+                    if nb_scanned == to_add.len() {
+                        break;
+                    }
+                }
+            }
             to_encode = 0;
             already = 0;
             nb_scanned += update_byte(&mut to_encode, &mut already, alphabet, &to_add[nb_scanned..]);
@@ -559,6 +569,10 @@ impl<'a> IterSequence<'a> {
         //
         Ok(())
     }
+    /// returns the number of bases of the iterated sequence
+    pub fn get_sequence_size(&self) -> usize {
+        self.myseq.size()
+    }
     /// mostly used in Kmer generation to avoid decoding/encoding of each base
     ///
     pub fn set_decoding_state(&mut self, decode: bool) {
@@ -719,6 +733,82 @@ impl<'a> IntoIterator for &'a Sequence {
 }   // end of impl<'a> IntoIterator<'a>
 
 
+
+
+//===================================================================================
+// SequenceView
+//===================================================================================
+
+
+/// A view on a range of bases of a [Sequence], see [Sequence::view]. Bases are not copied, so windows or regions
+/// of a long sequence can be sent to kmer iterators (see [KmerSeqIterator::new_view](super::kmergenerator::KmerSeqIterator::new_view))
+/// and sketchers (see [SeqSketcherT::sketch_compressedkmer_views](crate::sketching::setsketchert::SeqSketcherT::sketch_compressedkmer_views)).
+#[derive(Copy,Clone)]
+pub struct SequenceView<'a> {
+    seq : &'a Sequence,
+    range : (usize, usize),
+}
+
+
+impl <'a> SequenceView<'a> {
+
+    /// returns the viewed sequence
+    pub fn get_sequence(&self) -> &'a Sequence {
+        self.seq
+    }
+
+    /// returns the range of viewed bases in the sequence
+    pub fn get_range(&self) -> Range<usize> {
+        self.range.0..self.range.1
+    }
+
+    /// number of bases in view
+    pub fn size(&self) -> usize {
+        self.range.1 - self.range.0
+    }
+
+    /// returns the encoded base at position pos of the view, see [Sequence::get_base]
+    pub fn get_base(&self, pos : usize) -> u8 {
+        assert!(pos < self.size(), "SequenceView::get_base position {} beyond view size {}", pos, self.size());
+        self.seq.get_base(self.range.0 + pos)
+    }
+
+    /// a view on a range of this view, positions being relative to the view
+    pub fn view<R : RangeBounds<usize>>(&self, range : R) -> Result<SequenceView<'a>, String> {
+        let range = get_bounded_range(&range, self.size());
+        if range.start > range.end || range.end > self.size() {
+            log::error!("SequenceView::view bad range {:?}, view size : {}", range, self.size());
+            return Err(format!("bad range {:?}, view size : {}", range, self.size()));
+        }
+        Ok(SequenceView{seq : self.seq, range : (self.range.0 + range.start, self.range.0 + range.end)})
+    }
+
+    /// copies bases of the view in a new sequence with the same encoding
+    pub fn to_sequence(&self) -> Sequence {
+        if self.size() == 0 {
+            return Sequence::new(&[], self.seq.nb_bits_by_base());
+        }
+        let mut iter = IterSequence::new(self.seq, true);
+        iter.set_range(self.range.0, self.range.1).unwrap();
+        let bases : Vec<u8> = iter.collect();
+        Sequence::new(&bases, self.seq.nb_bits_by_base())
+    }
+
+} // end of impl SequenceView
+
+
+
+impl Sequence {
+
+    /// returns a view on a range of bases of the sequence, without copy.  
+    /// (2..10 and 2..=9 are the same range, .. is the whole sequence). Returns an error if range goes beyond the end of sequence.
+    pub fn view<R : RangeBounds<usize>>(&self, range : R) -> Result<SequenceView<'_>, String> {
+        SequenceView{seq : self, range : (0, self.size())}.view(range)
+    }
+
+} // end of impl Sequence for views
+
+
 /////////////////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -1015,6 +1105,31 @@ mod tests {
     } // end of test_encode_and_add_with_n
 
 
+    #[test]
+    fn test_encode_and_add_simd_runs() {
+        log_init_test();
+        // long runs of ACGT packed by simd, separated by N, added in pieces not aligned on bytes
+        let run : Vec<u8> = b"ACGTTGCATTAGGCCA".iter().cycle().take(101).copied().collect();
+        let mut raw = run.clone();
+        raw.extend_from_slice(b"NN");
+        raw.extend_from_slice(&run[..67]);
+        raw.push(b'N');
+        raw.extend_from_slice(&run);
+        let expected : Vec<u8> = raw.iter().filter(|b| **b != b'N').copied().collect();
+        let alpha2b = Alphabet2b::new();
+        let mut seq = Sequence::with_capacity(2, 10);
+        for piece in raw.chunks(37) {
+            seq.encode_and_add(piece, &alpha2b);
+        }
+        assert_eq!(seq.size(), expected.len());
+        assert_eq!(seq.decompress(), expected);
+        // constructor and decompress agree with the byte by byte alphabet
+        let seqnew = Sequence::new(&expected, 2);
+        assert_eq!(seqnew.decompress(), expected);
+        assert!((0..expected.len()).all(|i| seqnew.get_base(i) == alpha2b.encode(expected[i])));
+    } // end of test_encode_and_add_simd_runs
+
+
     #[test]
     fn test_encode_and_add_very_small_seq() {
         log_init_test();
@@ -1138,6 +1253,41 @@ mod tests {
         assert_eq!(restored_str, seqstr);
     } // end of test_incremental_15b_seq_init
 
+
+    #[test]
+    fn test_sequence_view() {
+        log_init_test();
+        use crate::base::kmergenerator::{KmerSeqIterator, KmerSeqIteratorT};
+        use crate::base::kmer32bit::Kmer32bit;
+        //
+        let bases = b"ACGTTGCAAGGCTTACCGATTGCA";
+        let seq = Sequence::new(bases, 2);
+        let view = seq.view(3..15).unwrap();
+        assert_eq!(view.size(), 12);
+        assert_eq!(view.get_range(), 3..15);
+        assert_eq!(view.to_sequence().decompress(), bases[3..15].to_vec());
+        assert_eq!(view.get_base(0), seq.get_base(3));
+        // sub views are relative to the view
+        let sub = view.view(2..=5).unwrap();
+        assert_eq!(sub.get_range(), 5..9);
+        assert_eq!(sub.to_sequence().decompress(), bases[5..9].to_vec());
+        assert_eq!(seq.view(..).unwrap().size(), seq.size());
+        assert_eq!(view.view(12..).unwrap().to_sequence().size(), 0);
+        assert!(seq.view(0..25).is_err());
+        assert!(view.view(5..13).is_err());
+        // kmers of a view are kmers of the range
+        let mut from_view = KmerSeqIterator::<Kmer32bit>::new_view(4, &view).unwrap();
+        let mut from_range = KmerSeqIterator::<Kmer32bit>::new_range(4, &seq, 3..15).unwrap();
+        let mut nb_kmers = 0;
+        while let Some(kmer) = from_view.next() {
+            assert_eq!(Some(kmer), from_range.next());
+            nb_kmers += 1;
+        }
+        assert!(from_range.next().is_none());
+        assert_eq!(nb_kmers, 9);
+        assert!(KmerSeqIterator::<Kmer32bit>::new_view(4, &view.view(4..4).unwrap()).is_err());
+    } // end of test_sequence_view
+
 } // end module test
 
 
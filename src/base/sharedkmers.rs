@@ -0,0 +1,144 @@
+//! This module computes, for a list of sequences, the exact matrix of the number of distinct kmers shared by each pair of sequences.
+//!
+//! It is the ground truth companion of the Jaccard indexes estimated by sketching (see module sketching),
+//! useful to check sketch accuracy on moderate size data as it stores all distinct kmers of all sequences.
+//!
+//! Each sequence is represented by its sorted vector of distinct kmers, and intersections are computed by merging sorted vectors.
+//! The pairs (i,j) with i <= j are dispatched in parallel by square blocks of pairs.
+
+
+use std::hash::Hash;
+
+use ndarray::Array2;
+use crate::parallel::*;
+
+pub use super::{kmertraits::*, kmertable::*, sequence::Sequence};
+
+
+/// number of sequences by side of a block of pairs
+const SHARED_KMER_BLOCK_SIZE : usize = 64;
+
+
+// returns the size of intersection of 2 sorted vectors of distinct values
+fn sorted_intersection_size<T : Ord>(a : &[T], b : &[T]) -> u64 {
+    let mut i = 0;
+    let mut j = 0;
+    let mut nb_common = 0u64;
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less    => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal   => {
+                nb_common += 1;
+                i += 1;
+                j += 1;
+            },
+        }
+    }
+    nb_common
+} // end of sorted_intersection_size
+
+
+
+/// computes the symmetric matrix of the number of distinct kmers shared by each pair of sequences.
+/// The diagonal contains the number of distinct kmers of each sequence.
+/// If canonical is true a kmer and its reverse complement are identified.
+pub fn shared_kmer_matrix<Kmer>(vseq : &Vec<&Sequence>, kmer_size : usize, canonical : bool) -> Array2<u64>
+        where Kmer : CompressedKmerT + KmerBuilder<Kmer> + Hash + Send + Sync {
+    //
+    log::debug!("entering shared_kmer_matrix, nb sequences : {}", vseq.len());
+    //
+    let nb_seq = vseq.len();
+    // get for each sequence its sorted distinct kmers
+    let kmers : Vec<Vec<Kmer>> = vseq.par_iter().map(|seq| {
+            let table = KmerCountTable::<Kmer>::from_sequences(&vec![*seq], kmer_size, canonical);
+            table.get_sorted_counts().into_iter().map(|(k, _)| k).collect()
+        }).collect();
+    // dispatch blocks of pairs
+    let nb_blocks = nb_seq.div_ceil(SHARED_KMER_BLOCK_SIZE);
+    let mut block_pairs = Vec::<(usize, usize)>::with_capacity(nb_blocks * (nb_blocks + 1) / 2);
+    for bi in 0..nb_blocks {
+        for bj in bi..nb_blocks {
+            block_pairs.push((bi, bj));
+        }
+    }
+    let blocks : Vec<Vec<(usize, usize, u64)>> = block_pairs.par_iter().map(|(bi, bj)| {
+            let mut res = Vec::<(usize, usize, u64)>::new();
+            let irange = (bi * SHARED_KMER_BLOCK_SIZE)..(nb_seq.min((bi + 1) * SHARED_KMER_BLOCK_SIZE));
+            for i in irange {
+                let jrange = (i.max(bj * SHARED_KMER_BLOCK_SIZE))..(nb_seq.min((bj + 1) * SHARED_KMER_BLOCK_SIZE));
+                for j in jrange {
+                    res.push((i, j, sorted_intersection_size(&kmers[i], &kmers[j])));
+                }
+            }
+            res
+        }).collect();
+    //
+    let mut matrix = Array2::<u64>::zeros((nb_seq, nb_seq));
+    for block in blocks {
+        for (i, j, nb_common) in block {
+            matrix[[i, j]] = nb_common;
+            matrix[[j, i]] = nb_common;
+        }
+    }
+    log::debug!("exiting shared_kmer_matrix");
+    //
+    matrix
+} // end of shared_kmer_matrix
+
+
+
+/// converts a shared kmer matrix (as returned by [shared_kmer_matrix]) in the matrix of exact Jaccard indexes.
+pub fn shared_kmer_jaccard(shared : &Array2<u64>) -> Array2<f64> {
+    let nb_seq = shared.nrows();
+    let mut jaccard = Array2::<f64>::zeros((nb_seq, nb_seq));
+    for i in 0..nb_seq {
+        for j in 0..nb_seq {
+            let union = shared[[i, i]] + shared[[j, j]] - shared[[i, j]];
+            if union > 0 {
+                jaccard[[i, j]] = shared[[i, j]] as f64 / union as f64;
+            }
+        }
+    }
+    jaccard
+} // end of shared_kmer_jaccard
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::base::kmer32bit::Kmer32bit;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_shared_kmer_matrix() {
+        log_init_test();
+        let str1 = b"ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGC";
+        let seqs : Vec<Sequence> = (0..100).map(|i| Sequence::new(&str1[(i % 20)..], 2)).collect();
+        let vseq : Vec<&Sequence> = seqs.iter().collect();
+        let kmer_size = 8;
+        let matrix = shared_kmer_matrix::<Kmer32bit>(&vseq, kmer_size, false);
+        // compare with count tables
+        for i in [0, 5, 63, 64, 99] {
+            let ti = KmerCountTable::<Kmer32bit>::from_sequences(&vec![vseq[i]], kmer_size, false);
+            assert_eq!(matrix[[i, i]], ti.len() as u64);
+            for j in [0, 17, 64, 98] {
+                let tj = KmerCountTable::<Kmer32bit>::from_sequences(&vec![vseq[j]], kmer_size, false);
+                assert_eq!(matrix[[i, j]], ti.intersection(&tj).unwrap().len() as u64);
+            }
+        }
+        let jaccard = shared_kmer_jaccard(&matrix);
+        assert_eq!(jaccard[[3, 3]], 1.);
+        assert_eq!(jaccard[[0, 20]], 1.);
+        assert!(jaccard[[0, 10]] < 1.);
+    } // end of test_shared_kmer_matrix
+
+} // end of mod tests
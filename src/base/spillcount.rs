@@ -0,0 +1,299 @@
+//! This module provides exact kmer counting with a bounded memory, spilling partial tables to disk.
+//!
+//! Kmers are counted in an in memory [KmerCountTable]. When the number of distinct kmers in the table reaches
+//! the limit deduced from the memory ceiling, the table is sorted by kmer value and written to a temporary run file,
+//! then cleared. At the end all runs are merged (a k-way merge on sorted files) so that each distinct kmer
+//! is delivered once with its total count, without ever holding all distinct kmers in memory.
+//!
+//! Run files are removed when the counter is dropped.
+
+
+use std::collections::BinaryHeap;
+use std::cmp::Reverse;
+use std::hash::Hash;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use num::{NumCast, ToPrimitive};
+
+pub use super::{kmertraits::*, kmergenerator::*, kmertable::*, sequence::Sequence};
+
+
+/// estimated memory used by an entry of the in memory table in addition to the kmer itself (count and hash table overhead)
+const SPILL_ENTRY_OVERHEAD : usize = 12;
+
+/// minimal number of distinct kmers kept in memory before spilling
+const SPILL_MIN_ENTRIES : usize = 1024;
+
+// to give distinct names to run files of counters living in the same process
+static SPILL_COUNTER_ID : AtomicUsize = AtomicUsize::new(0);
+
+
+// reader of a run file of (u64 kmer value, u32 count) records
+struct SpillRunReader {
+    reader : BufReader<File>,
+}
+
+impl SpillRunReader {
+    fn next_record(&mut self) -> io::Result<Option<(u64, u32)>> {
+        let mut buf = [0u8; 12];
+        match self.reader.read_exact(&mut buf) {
+            Ok(()) => {
+                let key = u64::from_le_bytes(buf[0..8].try_into().unwrap());
+                let count = u32::from_le_bytes(buf[8..12].try_into().unwrap());
+                Ok(Some((key, count)))
+            },
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+} // end of impl SpillRunReader
+
+
+
+/// A kmer counter with a memory ceiling, spilling sorted partial counts to temporary files.
+pub struct SpillingKmerCounter<Kmer> where Kmer : CompressedKmerT + Hash {
+    /// in memory table
+    table : KmerCountTable<Kmer>,
+    /// max number of distinct kmers in memory
+    max_entries : usize,
+    /// directory of run files
+    spill_dir : PathBuf,
+    /// prefix of run files names
+    prefix : String,
+    /// run files written
+    runs : Vec<PathBuf>,
+} // end of SpillingKmerCounter
+
+
+
+impl <Kmer> SpillingKmerCounter<Kmer> where Kmer : CompressedKmerT + KmerBuilder<Kmer> + Hash {
+
+    /// allocates a counter using at most approximately max_memory bytes for its in memory table.
+    /// Run files are written in spill_dir, or in the system temporary directory if None.
+    pub fn new(kmer_size : usize, canonical : bool, max_memory : usize, spill_dir : Option<&Path>) -> Self {
+        let entry_size = std::mem::size_of::<Kmer>() + SPILL_ENTRY_OVERHEAD;
+        let max_entries = (max_memory / entry_size).max(SPILL_MIN_ENTRIES);
+        let spill_dir = match spill_dir {
+            Some(dir) => dir.to_path_buf(),
+            None => std::env::temp_dir(),
+        };
+        let prefix = format!("kmerutils_spill_{}_{}", std::process::id(), SPILL_COUNTER_ID.fetch_add(1, Ordering::Relaxed));
+        log::debug!("SpillingKmerCounter max entries in memory : {}, spill directory : {:?}", max_entries, spill_dir);
+        SpillingKmerCounter{table : KmerCountTable::<Kmer>::new(kmer_size, canonical), max_entries, spill_dir, prefix, runs : Vec::new()}
+    }
+
+    /// returns the maximum number of distinct kmers kept in memory
+    pub fn get_max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    /// returns the number of run files written so far
+    pub fn get_nb_spills(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// counts one occurrence of a kmer, spilling the in memory table if it is full
+    pub fn insert_kmer(&mut self, kmer : &Kmer) -> io::Result<()> {
+        self.table.insert_kmer(kmer);
+        if self.table.len() >= self.max_entries {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// counts all kmers of sequences (which must be 2 bits encoded)
+    pub fn count_sequences(&mut self, vseq : &Vec<&Sequence>) -> io::Result<()> {
+        let kmer_size = self.table.get_kmer_size() as u8;
+        for seq in vseq {
+            let mut kmergen = KmerSeqIterator::<Kmer>::new(kmer_size, seq);
+            kmergen.set_range(0..seq.size()).unwrap();
+            while let Some(kmer) = kmergen.next() {
+                self.insert_kmer(&kmer)?;
+            }
+        }
+        Ok(())
+    } // end of count_sequences
+
+    // returns in memory counts sorted by kmer value
+    fn get_sorted_values(&self) -> Vec<(u64, u32)> {
+        let mut values : Vec<(u64, u32)> = self.table.iter().map(|(k, c)| (k.get_compressed_value().to_u64().unwrap(), *c)).collect();
+        values.sort_unstable_by_key(|v| v.0);
+        values
+    }
+
+    // writes the in memory table in a new run file and clears it
+    fn spill(&mut self) -> io::Result<()> {
+        let path = self.spill_dir.join(format!("{}_{}.bin", self.prefix, self.runs.len()));
+        log::debug!("SpillingKmerCounter spilling {} kmers in {:?}", self.table.len(), path);
+        let file = OpenOptions::new().write(true).create(true).truncate(true).open(&path)?;
+        self.runs.push(path);
+        let mut bufw = BufWriter::new(file);
+        for (key, count) in self.get_sorted_values() {
+            bufw.write_all(&key.to_le_bytes())?;
+            bufw.write_all(&count.to_le_bytes())?;
+        }
+        bufw.flush()?;
+        self.table = KmerCountTable::<Kmer>::new(self.table.get_kmer_size(), self.table.is_canonical());
+        Ok(())
+    } // end of spill
+
+    // rebuilds a kmer from the value stored in run files
+    fn build_kmer(&self, key : u64) -> Kmer {
+        let val : Kmer::Val = NumCast::from(key).unwrap();
+        Kmer::build(val, self.table.get_kmer_size() as u8)
+    }
+
+    /// merges run files and in memory counts, calling f once for each distinct kmer with its total count.
+    /// Kmers are delivered in increasing order of their compressed value.
+    pub fn for_each_count<F>(&self, mut f : F) -> io::Result<()>
+            where F : FnMut(Kmer, u32) {
+        //
+        let memory = self.get_sorted_values();
+        let mut readers = Vec::<SpillRunReader>::with_capacity(self.runs.len());
+        for path in &self.runs {
+            readers.push(SpillRunReader{reader : BufReader::new(File::open(path)?)});
+        }
+        // heap of (key, count, source), source == readers.len() is the in memory table
+        let mut heap = BinaryHeap::<Reverse<(u64, u32, usize)>>::new();
+        for (i, reader) in readers.iter_mut().enumerate() {
+            if let Some((key, count)) = reader.next_record()? {
+                heap.push(Reverse((key, count, i)));
+            }
+        }
+        let mut memory_pos = 0;
+        if memory_pos < memory.len() {
+            heap.push(Reverse((memory[0].0, memory[0].1, readers.len())));
+            memory_pos += 1;
+        }
+        //
+        let mut current : Option<(u64, u32)> = None;
+        while let Some(Reverse((key, count, source))) = heap.pop() {
+            // refill from source
+            if source < readers.len() {
+                if let Some((next_key, next_count)) = readers[source].next_record()? {
+                    heap.push(Reverse((next_key, next_count, source)));
+                }
+            }
+            else if memory_pos < memory.len() {
+                heap.push(Reverse((memory[memory_pos].0, memory[memory_pos].1, source)));
+                memory_pos += 1;
+            }
+            current = match current {
+                Some((ckey, ccount)) if ckey == key => Some((ckey, ccount.saturating_add(count))),
+                Some((ckey, ccount)) => {
+                    f(self.build_kmer(ckey), ccount);
+                    Some((key, count))
+                },
+                None => Some((key, count)),
+            };
+        }
+        if let Some((ckey, ccount)) = current {
+            f(self.build_kmer(ckey), ccount);
+        }
+        Ok(())
+    } // end of for_each_count
+
+    /// merges all counts in a [KmerCountTable], keeping only kmers with count >= min_count.
+    /// The result must fit in memory, use [Self::for_each_count] otherwise.
+    pub fn get_count_table(&self, min_count : u32) -> io::Result<KmerCountTable<Kmer>> {
+        let mut table = KmerCountTable::<Kmer>::new(self.table.get_kmer_size(), self.table.is_canonical());
+        self.for_each_count(|kmer, count| {
+                if count >= min_count {
+                    table.add_count(&kmer, count);
+                }
+            })?;
+        Ok(table)
+    } // end of get_count_table
+
+} // end of impl SpillingKmerCounter
+
+
+
+impl <Kmer> Drop for SpillingKmerCounter<Kmer> where Kmer : CompressedKmerT + Hash {
+    fn drop(&mut self) {
+        for path in &self.runs {
+            if let Err(e) = std::fs::remove_file(path) {
+                log::error!("SpillingKmerCounter could not remove run file {:?} : {:?}", path, e);
+            }
+        }
+    }
+} // end of impl Drop for SpillingKmerCounter
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::base::kmer32bit::Kmer32bit;
+    use crate::base::kmer64bit::Kmer64bit;
+    use rand::{Rng, SeedableRng};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    fn random_sequences(nb_seq : usize, len : usize, seed : u64) -> Vec<Sequence> {
+        let alphabet = b"ACGT";
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed);
+        (0..nb_seq).map(|_| {
+                let raw : Vec<u8> = (0..len).map(|_| alphabet[rng.gen_range(0..4)]).collect();
+                Sequence::new(&raw, 2)
+            }).collect()
+    }
+
+    #[test]
+    fn test_spill_count_equals_exact() {
+        log_init_test();
+        let seqs = random_sequences(50, 300, 1789);
+        // some repeated sequences to get counts > 1
+        let mut vseq : Vec<&Sequence> = seqs.iter().collect();
+        vseq.extend(seqs[0..10].iter());
+        let kmer_size = 9;
+        let exact = KmerCountTable::<Kmer32bit>::from_sequences(&vseq, kmer_size, true);
+        //
+        let mut counter = SpillingKmerCounter::<Kmer32bit>::new(kmer_size, true, 0, None);
+        counter.count_sequences(&vseq).unwrap();
+        assert!(counter.get_nb_spills() >= 5);
+        let spilled = counter.get_count_table(1).unwrap();
+        assert_eq!(spilled.len(), exact.len());
+        for (kmer, count) in exact.iter() {
+            assert_eq!(spilled.get_count(kmer), *count);
+        }
+        // filtered and sorted delivery
+        let filtered = counter.get_count_table(2).unwrap();
+        assert_eq!(filtered.len(), exact.iter().filter(|(_, c)| **c >= 2).count());
+        let mut last : Option<Kmer32bit> = None;
+        counter.for_each_count(|kmer, _| {
+                assert!(last.map(|l| l < kmer).unwrap_or(true));
+                last = Some(kmer);
+            }).unwrap();
+        // run files are removed at drop
+        let runs = counter.runs.clone();
+        drop(counter);
+        assert!(runs.iter().all(|p| !p.exists()));
+    } // end of test_spill_count_equals_exact
+
+
+    #[test]
+    fn test_spill_count_kmer64() {
+        log_init_test();
+        let seqs = random_sequences(20, 500, 4);
+        let vseq : Vec<&Sequence> = seqs.iter().collect();
+        let kmer_size = 25;
+        let exact = KmerCountTable::<Kmer64bit>::from_sequences(&vseq, kmer_size, false);
+        let mut counter = SpillingKmerCounter::<Kmer64bit>::new(kmer_size, false, 0, None);
+        counter.count_sequences(&vseq).unwrap();
+        assert!(counter.get_nb_spills() > 0);
+        let spilled = counter.get_count_table(1).unwrap();
+        assert_eq!(spilled.len(), exact.len());
+        assert_eq!(spilled.get_total_count(), exact.get_total_count());
+    } // end of test_spill_count_kmer64
+
+} // end of mod tests
@@ -58,7 +58,7 @@ fn main() {
                     .required(true)
                     .action(ArgAction::Set)
                     .value_parser(clap::value_parser!(String))
-                    .help("expecting a fastq file"),
+                    .help("expecting a fastq file, - for standard input"),
             )
             .arg(
                 Arg::new("sketch_size")
@@ -196,19 +196,23 @@ fn main() {
 
     //
     let path = Path::new(&fname);
-    let f_info_res = path.metadata();
-    match f_info_res {
-        Ok(meta) => {
-            let filesize = meta.len();
-            info!("sketching file {}   size : {}", fname, filesize);
-        }
-        Err(_e) => {
-            error!("file does not exist: {:?}", fname);
-            process::exit(1);
+    if kmerutils::io::is_stdin(path) {
+        info!("sketching standard input");
+    } else {
+        let f_info_res = path.metadata();
+        match f_info_res {
+            Ok(meta) => {
+                let filesize = meta.len();
+                info!("sketching file {}   size : {}", fname, filesize);
+            }
+            Err(_e) => {
+                error!("file does not exist: {:?}", fname);
+                process::exit(1);
+            }
         }
     }
     let start_t = Instant::now();
-    let mut reader = needletail::parse_fastx_file(&path).expect("expecting valid filename");
+    let mut reader = kmerutils::io::open_fastx_file(path).expect("expecting valid filename");
     let sequence_pack = if sketch_block { 5000 } else { 10000 };
     // dumping info
     log::info!("sketching sequences by pack size {:?}", sequence_pack);
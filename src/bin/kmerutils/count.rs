@@ -0,0 +1,221 @@
+//! subcommand count : counts exactly the kmers of DNA fasta/fastq files (possibly compressed, - for standard input).
+//!
+//! usage :
+//!    kmerutils count -k kmer_size [--min-count c] [--max-count c] [--no-canonical] [-o output [--format f]] [--histo histo_file] file1 [file2 ...]
+//! -   -k kmer_size : at most 32
+//! -   --min-count, --max-count : only kmers whose count is in \[min_count, max_count\] are dumped
+//! -   --no-canonical : a kmer and its reverse complement are counted separately
+//! -   -o output : dump of counts, in a format of jellyfish : column (lines "kmer count", default), fasta or binary
+//! -   --histo histo_file : abundance histogram of all kmers (before filtering), lines "count nb_kmers" for non empty bins,
+//!     counts above --histo-max (default 10000) being accumulated in the last bin
+//!
+//! Counts of all files are added in one table (module base::kmertable) held in memory, so this is meant
+//! for genomes or moderate read sets. Files are counted in parallel.
+
+use std::fmt::Display;
+use std::hash::Hash;
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use rayon::prelude::*;
+
+use kmerutils::base::kmer::{Kmer32bit, Kmer64bit};
+use kmerutils::base::kmertable::*;
+use kmerutils::base::jellyfish::{dump_jellyfish_text, dump_jellyfish_binary, JellyfishTextFormat};
+use kmerutils::io::read_acgt_sequences;
+
+use crate::get_output_writer;
+
+
+pub fn get_command() -> Command {
+    Command::new("count")
+        .about("counts kmers of DNA fasta/fastq files")
+        .arg(
+            Arg::new("files")
+                .required(true)
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("fasta/fastq files, possibly compressed, - for standard input"),
+        )
+        .arg(
+            Arg::new("kmer_size")
+                .long("kmer")
+                .short('k')
+                .required(true)
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(usize))
+                .help("kmer size, at most 32"),
+        )
+        .arg(
+            Arg::new("min_count")
+                .long("min-count")
+                .action(ArgAction::Set)
+                .default_value("1")
+                .value_parser(clap::value_parser!(u32))
+                .help("kmers seen less than min_count times are not dumped"),
+        )
+        .arg(
+            Arg::new("max_count")
+                .long("max-count")
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(u32))
+                .help("kmers seen more than max_count times are not dumped"),
+        )
+        .arg(
+            Arg::new("no_canonical")
+                .long("no-canonical")
+                .action(ArgAction::SetTrue)
+                .help("counts a kmer and its reverse complement separately"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("file of kmer counts"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .action(ArgAction::Set)
+                .default_value("column")
+                .value_parser(["column", "fasta", "binary"])
+                .help("format of kmer counts (jellyfish dump formats)"),
+        )
+        .arg(
+            Arg::new("histo")
+                .long("histo")
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("file of the abundance histogram, - for standard output"),
+        )
+        .arg(
+            Arg::new("histo_max")
+                .long("histo-max")
+                .action(ArgAction::Set)
+                .default_value("10000")
+                .value_parser(clap::value_parser!(usize))
+                .help("last bin of the histogram"),
+        )
+        .group(ArgGroup::new("outputs").args(["output", "histo"]).multiple(true).required(true))
+} // end of get_command
+
+
+
+// writes the non empty bins of an histogram
+fn write_histogram(writer : &mut dyn Write, histo : &[u64]) -> Result<(), String> {
+    for (count, nb) in histo.iter().enumerate().filter(|(_, nb)| **nb > 0) {
+        writeln!(writer, "{} {}", count, nb).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+
+// counts kmers of files for a kmer type, then dumps histogram and counts
+fn count_files<Kmer>(matches : &ArgMatches, files : &[PathBuf], kmer_size : usize) -> Result<(), String>
+        where Kmer : CompressedKmerT + KmerBuilder<Kmer> + Hash + Display + Send,
+              KmerGenerator<Kmer> : KmerGenerationPattern<Kmer> {
+    let canonical = !matches.get_flag("no_canonical");
+    let tables = files.par_iter().map(|f| {
+            let seqs = read_acgt_sequences(f, kmer_size).map_err(|e| format!("could not read {:?} : {}", f, e))?;
+            Ok(KmerCountTable::<Kmer>::from_sequences(&seqs.iter().collect(), kmer_size, canonical))
+        }).collect::<Result<Vec<_>, String>>()?;
+    let mut tables = tables.into_iter();
+    let mut table = tables.next().unwrap();
+    for other in tables {
+        table = table.union(&other)?;
+    }
+    // standard output can receive the histogram, statistics go to the log
+    log::info!("nb distinct kmers : {}, total count : {}", table.len(), table.get_total_count());
+    //
+    if let Some(histo_path) = matches.get_one::<PathBuf>("histo") {
+        let histo = table.get_abundance_histogram(*matches.get_one::<usize>("histo_max").unwrap());
+        let histo_path = if kmerutils::io::is_stdin(histo_path) { None } else { Some(histo_path) };
+        write_histogram(&mut get_output_writer(histo_path)?, &histo)?;
+    }
+    if let Some(output) = matches.get_one::<PathBuf>("output") {
+        let min_count = *matches.get_one::<u32>("min_count").unwrap();
+        let max_count = matches.get_one::<u32>("max_count").copied().unwrap_or(u32::MAX);
+        if min_count > max_count {
+            return Err(format!("min count {} greater than max count {}", min_count, max_count));
+        }
+        table.retain_abundance(min_count, max_count);
+        let nb_dumped = match matches.get_one::<String>("format").unwrap().as_str() {
+            "fasta" => dump_jellyfish_text(&table, output, JellyfishTextFormat::Fasta),
+            "binary" => dump_jellyfish_binary(&table, output),
+            _ => dump_jellyfish_text(&table, output, JellyfishTextFormat::Column),
+        }.map_err(|e| format!("could not dump counts in {:?} : {}", output, e))?;
+        log::info!("{} kmers written in {:?}", nb_dumped, output);
+    }
+    Ok(())
+} // end of count_files
+
+
+
+/// runs subcommand count
+pub fn run(matches : &ArgMatches) -> Result<(), String> {
+    let files : Vec<PathBuf> = matches.get_many::<PathBuf>("files").unwrap().cloned().collect();
+    let kmer_size = *matches.get_one::<usize>("kmer_size").unwrap();
+    if files.iter().filter(|f| kmerutils::io::is_stdin(f)).count() > 1 {
+        return Err("standard input can be given only once".to_string());
+    }
+    log::info!("counting kmers of size {} in {} files", kmer_size, files.len());
+    match kmer_size {
+        0 => Err("kmer size must be > 0".to_string()),
+        1..=14 => count_files::<Kmer32bit>(matches, &files, kmer_size),
+        15..=32 => count_files::<Kmer64bit>(matches, &files, kmer_size),
+        _ => Err(format!("kmer size must be <= 32, got {}", kmer_size)),
+    }
+} // end of run
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use kmerutils::base::jellyfish::{reload_jellyfish_text, reload_jellyfish_binary};
+
+    #[test]
+    fn test_count_command() {
+        let dir = crate::sketchfiles::test_dir("count");
+        let fasta = dir.join("reads.fa");
+        // AACG is seen 3 times (once as its reverse complement CGTT)
+        std::fs::write(&fasta, ">r1\nAACGT\n>r2\nAACGNCGTT\n").unwrap();
+        let (output, histo) = (dir.join("counts.txt"), dir.join("histo.txt"));
+        let run_args = |args : &[&str]| {
+            let mut all = vec!["count", "-k", "4", "--histo", histo.to_str().unwrap(), "-o", output.to_str().unwrap()];
+            all.extend(args);
+            all.push(fasta.to_str().unwrap());
+            run(&get_command().try_get_matches_from(all).unwrap())
+        };
+        run_args(&[]).unwrap();
+        let table = reload_jellyfish_text::<Kmer32bit>(&output, true).unwrap();
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get_count(&"AACG".parse().unwrap()), 3);
+        assert_eq!(table.get_count(&"ACGT".parse().unwrap()), 1);
+        assert_eq!(std::fs::read_to_string(&histo).unwrap(), "1 1\n3 1\n");
+        // abundance filter and binary format
+        run_args(&["--min-count", "2", "--format", "binary"]).unwrap();
+        let table = reload_jellyfish_binary::<Kmer32bit>(&output).unwrap();
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.get_count(&"CGTT".parse().unwrap()), 3);
+        // kmers counted separately from their reverse complement
+        run_args(&["--no-canonical"]).unwrap();
+        let table = reload_jellyfish_text::<Kmer32bit>(&output, false).unwrap();
+        assert_eq!(table.len(), 3);
+        // an output is required
+        assert!(get_command().try_get_matches_from(["count", "-k", "4", fasta.to_str().unwrap()]).is_err());
+        // standard input given twice
+        let matches = get_command().try_get_matches_from(["count", "-k", "4", "--histo", histo.to_str().unwrap(), "-", "-"]).unwrap();
+        assert!(run(&matches).is_err());
+        //
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_count_command
+
+} // end of mod tests
@@ -0,0 +1,206 @@
+//! subcommand dist : compares the sketches of sketch files.
+//!
+//! usage :
+//!    kmerutils dist [-q query_file ...] [--phylip] [-o output] file1 [file2 ...]
+//! -   without -q all sketches of the files are compared with each other (all-vs-all), each pair once
+//! -   -q query_file : the sketches of query files are compared to all sketches of the files (one-vs-many, pairwise with single sketch files)
+//! -   --phylip : writes the square matrix of Mash distances of the all-vs-all comparison in PHYLIP format
+//! -   -o output : output file, standard output by default
+//!
+//! The default output is a tab separated table with a header and one row by pair :
+//! query, target, jaccard, mash_distance, ani, shared (number of equal signature values).
+//! ani is deduced from Jaccard with the Mash formula (an amino acid identity for amino acid sketches).
+//! Rows are written as they are computed. HyperLogLog sketch files (algo hll) are refused, their registers cannot be compared slot by slot.
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use kmerutils::sketching::blockcompare::{equal_counts_matrix, TILE_SIZE};
+use kmerutils::sketching::csvexport::PairwiseResult;
+use kmerutils::sketching::distances::{compute_distance_matrix, write_phylip, DistanceKind, DistanceMatrix, PhylipLayout};
+use kmerutils::sketching::mashdist::mash_distance;
+use kmerutils::sketching::sketchcontainer::{SketchContainer, SketchContainerSig};
+
+use crate::sketchfiles::{check_slot_signatures, dispatch_sig_type, load_signatures, open_sketch_files};
+use crate::get_output_writer;
+
+
+pub fn get_command() -> Command {
+    Command::new("dist")
+        .about("compares sketches (all-vs-all or queries vs references)")
+        .arg(
+            Arg::new("files")
+                .required(true)
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("sketch files"),
+        )
+        .arg(
+            Arg::new("query")
+                .long("query")
+                .short('q')
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("sketch file of queries, compared to the sketches of files"),
+        )
+        .arg(
+            Arg::new("phylip")
+                .long("phylip")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("query")
+                .help("writes the matrix of Mash distances in PHYLIP format"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("output file, standard output by default"),
+        )
+} // end of get_command
+
+
+
+// writes a row of pairwise results, adding the Mash distance
+fn write_pairs<W : Write>(writer : &mut csv::Writer<W>, results : &[PairwiseResult], kmer_size : usize) -> Result<(), String> {
+    for res in results {
+        let mash = mash_distance(res.jaccard, kmer_size);
+        writer.write_record([res.query.as_str(), res.target.as_str(), &res.jaccard.to_string(), &mash.to_string(), &res.ani.to_string(), &res.common.to_string()])
+                .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+} // end of write_pairs
+
+
+
+// compares sketches, queries being empty for an all-vs-all comparison.
+// Pairs are compared by rows (a query against targets) and each row is written before the next is computed,
+// so memory does not grow with the number of pairs
+fn compare<Sig>(queries : &[SketchContainer], targets : &[SketchContainer], phylip : bool, writer : &mut dyn Write) -> Result<(), String>
+        where Sig : SketchContainerSig + PartialEq + Send + Sync {
+    let kmer_size = targets[0].get_params().get_kmer_size();
+    let (target_metadata, target_sigs) = load_signatures::<Sig>(targets)?;
+    if phylip {
+        let names : Vec<String> = target_metadata.into_iter().map(|m| m.name).collect();
+        let matrix : DistanceMatrix<f64> = compute_distance_matrix(&target_sigs, DistanceKind::Mash(kmer_size))?;
+        return write_phylip(writer, &names, &matrix, PhylipLayout::Square);
+    }
+    let all_vs_all = queries.is_empty();
+    let (query_metadata, query_sigs) = if all_vs_all { (Vec::new(), Vec::new()) } else { load_signatures::<Sig>(queries)? };
+    let (query_metadata, query_sigs) = if all_vs_all { (&target_metadata, &target_sigs) } else { (&query_metadata, &query_sigs) };
+    //
+    let mut writer = csv::WriterBuilder::new().delimiter(b'\t').from_writer(writer);
+    writer.write_record(["query", "target", "jaccard", "mash_distance", "ani", "shared"]).map_err(|e| e.to_string())?;
+    // queries are compared by blocks of rows with the tiled kernel, each block being written as soon as computed.
+    // A file may hold no sketch (sketch --per-record of records without kmer), then only the header is written
+    let sketch_size = targets[0].get_params().get_sketch_size();
+    let block_size = TILE_SIZE * rayon::current_num_threads();
+    let mut nb_pairs = 0;
+    for start in (0..query_sigs.len()).step_by(block_size) {
+        let end = (start + block_size).min(query_sigs.len());
+        // in all vs all mode only targets after the first query of the block are needed
+        let first = if all_vs_all { start } else { 0 };
+        let nb_cols = target_sigs.len() - first;
+        let counts = equal_counts_matrix(&query_sigs[start..end], &target_sigs[first..])?;
+        let mut block = Vec::<PairwiseResult>::new();
+        for i in start..end {
+            let row = &counts[(i - start) * nb_cols..(i - start + 1) * nb_cols];
+            for (j, common) in row.iter().enumerate().map(|(c, common)| (first + c, *common as usize)) {
+                if !all_vs_all || j > i {
+                    block.push(PairwiseResult::from_common(&query_metadata[i].name, &target_metadata[j].name, common, sketch_size, kmer_size));
+                }
+            }
+        }
+        write_pairs(&mut writer, &block, kmer_size)?;
+        nb_pairs += block.len();
+    }
+    log::info!("dist, nb pairs compared : {}", nb_pairs);
+    writer.flush().map_err(|e| e.to_string())
+} // end of compare
+
+
+
+/// runs subcommand dist
+pub fn run(matches : &ArgMatches) -> Result<(), String> {
+    let files : Vec<PathBuf> = matches.get_many::<PathBuf>("files").unwrap().cloned().collect();
+    let query_files : Vec<PathBuf> = matches.get_many::<PathBuf>("query").map(|q| q.cloned().collect()).unwrap_or_default();
+    // queries and targets are opened together so that they are checked against each other
+    let mut containers = open_sketch_files(&[query_files.clone(), files.clone()].concat())?;
+    let targets = containers.split_off(query_files.len());
+    check_slot_signatures(&targets[0], &files[0])?;
+    let sig_type = targets[0].get_header().sig_type.clone();
+    let mut writer = get_output_writer(matches.get_one::<PathBuf>("output"))?;
+    dispatch_sig_type!(sig_type.as_str(), compare(&containers, &targets, matches.get_flag("phylip"), &mut writer))?;
+    writer.flush().map_err(|e| e.to_string())
+} // end of run
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use kmerutils::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
+    use kmerutils::sketching::sketchcontainer::{dump_sketch_container, SketchMetadata};
+
+    #[test]
+    fn test_dist_command() {
+        let dir = crate::sketchfiles::test_dir("dist");
+        let params = SeqSketcherParams::new(21, 4, SketchAlgo::PROB3A, DataType::DNA);
+        let metadata = |names : &[&str]| -> Vec<SketchMetadata> { names.iter().map(|n| SketchMetadata{name : n.to_string(), seq_len : 100}).collect() };
+        let refs = dir.join("refs.sk");
+        dump_sketch_container(&refs, &params, &metadata(&["a", "b"]), &[vec![1u64, 2, 3, 4], vec![1u64, 2, 5, 6]]).unwrap();
+        let query = dir.join("query.sk");
+        dump_sketch_container(&query, &params, &metadata(&["q"]), &[vec![1u64, 7, 3, 4]]).unwrap();
+        let output = dir.join("dist.tsv");
+        let run_args = |args : &[&str]| {
+            let mut all = vec!["dist", "-o", output.to_str().unwrap()];
+            all.extend(args);
+            run(&get_command().try_get_matches_from(all).unwrap())
+        };
+        // all-vs-all
+        run_args(&[refs.to_str().unwrap()]).unwrap();
+        let lines : Vec<String> = std::fs::read_to_string(&output).unwrap().lines().map(|l| l.to_string()).collect();
+        assert_eq!(lines[0], "query\ttarget\tjaccard\tmash_distance\tani\tshared");
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("a\tb\t0.5\t"));
+        assert!(lines[1].ends_with("\t2"));
+        // query vs references
+        run_args(&["-q", query.to_str().unwrap(), refs.to_str().unwrap()]).unwrap();
+        let lines : Vec<String> = std::fs::read_to_string(&output).unwrap().lines().map(|l| l.to_string()).collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("q\ta\t0.75\t") && lines[2].starts_with("q\tb\t0.25\t"));
+        // phylip matrix of all sketches given
+        run_args(&["--phylip", refs.to_str().unwrap(), query.to_str().unwrap()]).unwrap();
+        let lines : Vec<String> = std::fs::read_to_string(&output).unwrap().lines().map(|l| l.to_string()).collect();
+        assert_eq!(lines[0], "3");
+        assert!(lines[3].starts_with("q\t") && lines[3].ends_with("\t0"));
+        // incompatible parameters
+        let other = dir.join("other.sk");
+        dump_sketch_container(&other, &SeqSketcherParams::new(15, 4, SketchAlgo::PROB3A, DataType::DNA), &metadata(&["c"]), &[vec![1u64, 2, 3, 4]]).unwrap();
+        assert!(run_args(&[refs.to_str().unwrap(), other.to_str().unwrap()]).is_err());
+        // HyperLogLog registers are not compared slot by slot
+        let hll = dir.join("hll.sk");
+        dump_sketch_container(&hll, &SeqSketcherParams::new(21, 4, SketchAlgo::HLL, DataType::DNA), &metadata(&["h1", "h2"]), &[vec![1u64, 2, 3, 4], vec![1u64, 2, 3, 5]]).unwrap();
+        assert!(run_args(&[hll.to_str().unwrap()]).unwrap_err().contains("HyperLogLog"));
+        // a file without sketch gives no pair
+        let empty = dir.join("empty.sk");
+        dump_sketch_container(&empty, &params, &[], &Vec::<Vec<u64>>::new()).unwrap();
+        for args in [vec![empty.to_str().unwrap()], vec!["-q", query.to_str().unwrap(), empty.to_str().unwrap()],
+                        vec!["-q", empty.to_str().unwrap(), refs.to_str().unwrap()]] {
+            run_args(&args).unwrap();
+            assert_eq!(std::fs::read_to_string(&output).unwrap(), "query\ttarget\tjaccard\tmash_distance\tani\tshared\n");
+        }
+        run_args(&["--phylip", empty.to_str().unwrap()]).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap().trim(), "0");
+        //
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_dist_command
+
+} // end of mod tests
@@ -0,0 +1,203 @@
+//! subcommand info : describes sketch files.
+//!
+//! usage :
+//!    kmerutils info [--summary] [-o output] file1 [file2 ...]
+//! -   --summary : does not list sketches
+//! -   -o output : output file, standard output by default
+//!
+//! For each file are printed its format version and provenance (crate version, creation time, input files and whether they
+//! changed since sketching), the result of the data checksum verification, the sketching parameters, the signature type,
+//! the number of sketches and, unless --summary is given, a line by sketch : rank, name, sequence length and xxh3 hash of its signature.
+//! Corrupted entries (invalid name offsets) are reported as such and counted, the file is still described.
+//! With several files, the compatibility of each file with the first one is reported, as checked before comparing sketches.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use xxhash_rust::xxh3::xxh3_64;
+
+use kmerutils::sketching::sketchcontainer::SketchContainer;
+
+use crate::get_output_writer;
+
+
+pub fn get_command() -> Command {
+    Command::new("info")
+        .about("describes sketch files : parameters, provenance, sketches")
+        .arg(
+            Arg::new("files")
+                .required(true)
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("sketch files"),
+        )
+        .arg(
+            Arg::new("summary")
+                .long("summary")
+                .action(ArgAction::SetTrue)
+                .help("does not list sketches"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("output file, standard output by default"),
+        )
+} // end of get_command
+
+
+
+// writes the description of a container
+fn write_info(w : &mut dyn Write, path : &Path, container : &SketchContainer, list_sketches : bool) -> std::io::Result<()> {
+    let header = container.get_header();
+    let params = container.get_params();
+    writeln!(w, "file : {}", path.display())?;
+    writeln!(w, "format version : {}", container.get_version())?;
+    match container.get_provenance() {
+        Some(provenance) => {
+            writeln!(w, "written by kmerutils {}, created at {} (unix time)", provenance.crate_version, provenance.created)?;
+            let status = if container.verify().is_ok() { "ok" } else { "MISMATCH, file is corrupted" };
+            writeln!(w, "data checksum : {:#018x} {}", provenance.data_xxh3, status)?;
+            let stale = container.get_stale_inputs();
+            writeln!(w, "nb inputs : {}", provenance.inputs.len())?;
+            for input in &provenance.inputs {
+                let state = if stale.contains(&input.path) { "  changed or missing" } else { "" };
+                writeln!(w, "    {}\t{} bytes\txxh3 {:#018x}{}", input.path, input.size, input.xxh3, state)?;
+            }
+        },
+        None => writeln!(w, "no provenance")?,
+    }
+    writeln!(w, "algorithm : {:?}", params.get_algo())?;
+    writeln!(w, "data type : {:?}", params.get_data_t())?;
+    writeln!(w, "kmer size : {}", params.get_kmer_size())?;
+    writeln!(w, "sketch size : {}", params.get_sketch_size())?;
+    if let Some(scale) = params.get_scaled() {
+        writeln!(w, "FracMinHash scale : {}", scale)?;
+    }
+    writeln!(w, "signature type : {} ({} bytes)", header.sig_type, header.sig_size)?;
+    writeln!(w, "kmer hash : {:?}, seed : {}", params.get_hash(), params.get_seed())?;
+    writeln!(w, "canonical : {}", params.is_canonical())?;
+    if params.get_algo().has_float_sig() {
+        writeln!(w, "float signature : {:?}", params.get_float_sig())?;
+    }
+    if params.has_abundance_filter() {
+        writeln!(w, "abundance filter : [{}, {}]", params.get_min_count(), params.get_max_count())?;
+    }
+    if params.get_singleton_removal() {
+        writeln!(w, "singleton removal : true")?;
+    }
+    if params.get_min_quality() > 0 {
+        writeln!(w, "min quality : {}", params.get_min_quality())?;
+    }
+    if params.get_quality_weighting() {
+        writeln!(w, "quality weighting : true")?;
+    }
+    writeln!(w, "nb sketches : {}", container.len())?;
+    if list_sketches {
+        writeln!(w, "rank\tname\tseq_len\tsignature_xxh3")?;
+    }
+    // entries are checked one by one, a corrupted entry is reported and does not stop the description
+    let mut nb_corrupted = 0;
+    for i in 0..container.len() {
+        let entry = container.get_name(i).and_then(|name| Ok((name, container.get_signature_bytes(i)?)));
+        match (entry, list_sketches) {
+            (Ok((name, sig)), true) => writeln!(w, "{}\t{}\t{}\t{:#018x}", i, name, container.get_seq_len(i), xxh3_64(sig))?,
+            (Ok(_), false) => (),
+            (Err(e), _) => {
+                nb_corrupted += 1;
+                if list_sketches {
+                    writeln!(w, "{}\tCORRUPTED : {}", i, e)?;
+                }
+            },
+        }
+    }
+    if nb_corrupted > 0 {
+        writeln!(w, "nb corrupted sketches : {}", nb_corrupted)?;
+    }
+    Ok(())
+} // end of write_info
+
+
+
+/// runs subcommand info
+pub fn run(matches : &ArgMatches) -> Result<(), String> {
+    let files : Vec<PathBuf> = matches.get_many::<PathBuf>("files").unwrap().cloned().collect();
+    let list_sketches = !matches.get_flag("summary");
+    let mut writer = get_output_writer(matches.get_one::<PathBuf>("output"))?;
+    // files are not verified at opening so that corrupted files can be described
+    let mut first : Option<SketchContainer> = None;
+    for (rank, path) in files.iter().enumerate() {
+        let container = SketchContainer::open(path).map_err(|e| format!("could not open sketch file {:?} : {}", path, e))?;
+        if rank > 0 {
+            writeln!(writer).map_err(|e| e.to_string())?;
+        }
+        write_info(&mut writer, path, &container, list_sketches).map_err(|e| e.to_string())?;
+        match &first {
+            Some(first) => {
+                let compatibility = match first.get_params().check_compatible(container.get_params()) {
+                    Ok(()) if first.get_header().sig_type != container.get_header().sig_type => "no, signature types differ".to_string(),
+                    Ok(()) => "yes".to_string(),
+                    Err(e) => format!("no, {}", e),
+                };
+                writeln!(writer, "compatible with {} : {}", files[0].display(), compatibility).map_err(|e| e.to_string())?;
+            },
+            None => first = Some(container),
+        }
+    }
+    writer.flush().map_err(|e| e.to_string())
+} // end of run
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use kmerutils::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
+    use kmerutils::sketching::sketchcontainer::{dump_sketch_container, SketchMetadata};
+
+    #[test]
+    fn test_info_command() {
+        let dir = crate::sketchfiles::test_dir("info");
+        let metadata = vec![SketchMetadata{name : "genome a".to_string(), seq_len : 1000}, SketchMetadata{name : "b".to_string(), seq_len : 20}];
+        let file1 = dir.join("s1.sk");
+        dump_sketch_container(&file1, &SeqSketcherParams::new(21, 3, SketchAlgo::PROB3A, DataType::DNA), &metadata, &[vec![1u64, 2, 3], vec![4u64, 5, 6]]).unwrap();
+        let file2 = dir.join("s2.sk");
+        dump_sketch_container(&file2, &SeqSketcherParams::new(15, 3, SketchAlgo::PROB3A, DataType::DNA), &metadata[..1], &[vec![1u64, 2, 3]]).unwrap();
+        let output = dir.join("info.txt");
+        let matches = get_command().try_get_matches_from(["info", "-o", output.to_str().unwrap(), file1.to_str().unwrap(), file2.to_str().unwrap()]).unwrap();
+        run(&matches).unwrap();
+        let info = std::fs::read_to_string(&output).unwrap();
+        assert!(info.contains("algorithm : PROB3A\n"));
+        assert!(info.contains("kmer size : 21\n") && info.contains("kmer size : 15\n"));
+        assert!(info.contains("signature type : u64 (8 bytes)\n"));
+        assert!(info.contains(" ok\n"));
+        assert!(info.contains("nb sketches : 2\n"));
+        assert!(info.contains(&format!("1\tb\t20\t{:#018x}\n", xxh3_64(&[4u64, 5, 6].iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>()))));
+        assert!(info.contains("compatible with") && info.contains(": no, kmer sizes differ : 21 and 15"));
+        // summary only
+        let matches = get_command().try_get_matches_from(["info", "--summary", "-o", output.to_str().unwrap(), file1.to_str().unwrap()]).unwrap();
+        run(&matches).unwrap();
+        assert!(!std::fs::read_to_string(&output).unwrap().contains("genome a"));
+        // a corrupted name offset is reported with the checksum mismatch
+        let mut bytes = std::fs::read(&file1).unwrap();
+        let len = bytes.len();
+        bytes[len - 9 - 16 + 7] ^= 0x10;
+        std::fs::write(&file1, &bytes).unwrap();
+        let matches = get_command().try_get_matches_from(["info", "-o", output.to_str().unwrap(), file1.to_str().unwrap()]).unwrap();
+        run(&matches).unwrap();
+        let info = std::fs::read_to_string(&output).unwrap();
+        assert!(info.contains("MISMATCH, file is corrupted"));
+        assert!(info.contains("0\tCORRUPTED : ") && info.contains("1\tCORRUPTED : "));
+        assert!(info.contains("nb corrupted sketches : 2\n"));
+        //
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_info_command
+
+} // end of mod tests
@@ -0,0 +1,78 @@
+//! kmerutils : command line access to the sketching tools of the crate.
+//!
+//! usage :
+//!    kmerutils subcommand [options]
+//!
+//! subcommands :
+//! -   sketch : sketches fasta/fastq files in a sketch container, see module sketch
+//! -   dist : compares sketches of sketch files (all-vs-all or queries vs references), see module dist
+//! -   count : counts kmers of fasta/fastq files, dumping counts and abundance histogram, see module count
+//! -   info : describes sketch files (parameters, provenance, sketches), see module info
+//! -   translate : translates DNA files in six frames or open reading frames, see module translate
+//! -   paste (or merge) : merges sketch files in one sketch file, see module paste
+//! -   search : searches the sketches most similar to queries (sketch or fasta/fastq files) in a sketch database, see module search
+//!
+//! Each subcommand describes its options with kmerutils subcommand --help.
+//! Logging is controlled by the RUST_LOG environment variable and the number of threads by RAYON_NUM_THREADS.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+
+use clap::Command;
+
+mod sketchfiles;
+mod sketch;
+mod dist;
+mod count;
+mod info;
+mod translate;
+mod paste;
+mod search;
+
+
+// returns a buffered writer on file path, or on standard output if path is None
+pub(crate) fn get_output_writer(path : Option<&PathBuf>) -> Result<Box<dyn Write>, String> {
+    match path {
+        Some(path) => {
+            let file = std::fs::File::create(path).map_err(|e| format!("could not create file {:?} : {}", path, e))?;
+            Ok(Box::new(std::io::BufWriter::new(file)))
+        },
+        None => Ok(Box::new(std::io::BufWriter::new(std::io::stdout()))),
+    }
+}
+
+
+fn main() {
+    let _ = env_logger::Builder::from_default_env().try_init();
+    //
+    let matches = Command::new("kmerutils")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("kmer sketching tools")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(sketch::get_command())
+        .subcommand(dist::get_command())
+        .subcommand(count::get_command())
+        .subcommand(info::get_command())
+        .subcommand(translate::get_command())
+        .subcommand(paste::get_command())
+        .subcommand(search::get_command())
+        .get_matches();
+    //
+    let res = match matches.subcommand() {
+        Some(("sketch", sub_matches)) => sketch::run(sub_matches),
+        Some(("dist", sub_matches)) => dist::run(sub_matches),
+        Some(("count", sub_matches)) => count::run(sub_matches),
+        Some(("info", sub_matches)) => info::run(sub_matches),
+        Some(("translate", sub_matches)) => translate::run(sub_matches),
+        Some(("paste", sub_matches)) => paste::run(sub_matches),
+        Some(("search", sub_matches)) => search::run(sub_matches),
+        _ => unreachable!("subcommand is required"),
+    };
+    if let Err(e) = res {
+        log::error!("kmerutils : {}", e);
+        eprintln!("kmerutils : {}", e);
+        process::exit(1);
+    }
+} // end of main
@@ -0,0 +1,138 @@
+//! subcommand paste (alias merge) : concatenates sketch files in one sketch file.
+//!
+//! usage :
+//!    kmerutils paste -o output file1 [file2 ...]
+//! -   -o output : the merged sketch file
+//!
+//! Sketching can thus be distributed (for example one job by genome batch on a cluster) and its results gathered in a database file
+//! used by subcommands dist and search. Sketches are written in the order of files.
+//! Files are verified and must have the same signature type and the same sketching parameters, including abundance and quality
+//! filters as the merged file records one set of parameters. The provenance of the merged file keeps the inputs of all files.
+//! Sketch names seen more than once are reported but kept.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use kmerutils::sketching::sketchcontainer::{dump_sketch_container_with_checksums, InputChecksum, SketchContainer, SketchContainerSig};
+
+use crate::sketchfiles::{dispatch_sig_type, load_signatures, open_sketch_files};
+
+
+pub fn get_command() -> Command {
+    Command::new("paste")
+        .visible_alias("merge")
+        .about("merges sketch files in one sketch file")
+        .arg(
+            Arg::new("files")
+                .required(true)
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("sketch files"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .required(true)
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("merged sketch file"),
+        )
+} // end of get_command
+
+
+
+// writes all sketches of containers in output, returns the number of sketches
+fn paste<Sig : SketchContainerSig>(containers : &[SketchContainer], output : &PathBuf) -> Result<usize, String> {
+    let (metadata, signatures) = load_signatures::<Sig>(containers)?;
+    let mut names = HashSet::<&str>::with_capacity(metadata.len());
+    let nb_duplicates = metadata.iter().filter(|m| !names.insert(m.name.as_str())).count();
+    if nb_duplicates > 0 {
+        log::warn!("paste, {} sketches have a name already seen", nb_duplicates);
+    }
+    // inputs of all files, once each
+    let mut inputs = Vec::<InputChecksum>::new();
+    for input in containers.iter().filter_map(|c| c.get_provenance()).flat_map(|p| p.inputs.iter()) {
+        if !inputs.contains(input) {
+            inputs.push(input.clone());
+        }
+    }
+    dump_sketch_container_with_checksums(output, containers[0].get_params(), &metadata, &signatures, inputs)?;
+    Ok(signatures.len())
+} // end of paste
+
+
+
+/// runs subcommand paste
+pub fn run(matches : &ArgMatches) -> Result<(), String> {
+    let files : Vec<PathBuf> = matches.get_many::<PathBuf>("files").unwrap().cloned().collect();
+    let output = matches.get_one::<PathBuf>("output").unwrap();
+    // paths are compared once resolved, an output not yet existing cannot be an input
+    if let Ok(resolved) = output.canonicalize() {
+        if files.iter().any(|f| f.canonicalize().is_ok_and(|f| f == resolved)) {
+            return Err(format!("output {:?} is also an input", output));
+        }
+    }
+    let containers = open_sketch_files(&files)?;
+    if let Some(i) = containers.iter().position(|c| c.get_params() != containers[0].get_params()) {
+        return Err(format!("sketch files {:?} and {:?} have different sketching parameters (filters or options), a merged file records one set of parameters",
+                files[0], files[i]));
+    }
+    let sig_type = containers[0].get_header().sig_type.clone();
+    let nb_sketches = dispatch_sig_type!(sig_type.as_str(), paste(&containers, output))?;
+    println!("{} sketches of {} files written in {:?}", nb_sketches, files.len(), output);
+    Ok(())
+} // end of run
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use kmerutils::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
+    use kmerutils::sketching::sketchcontainer::{dump_sketch_container, dump_sketch_container_with_inputs, SketchMetadata};
+
+    #[test]
+    fn test_paste_command() {
+        let dir = crate::sketchfiles::test_dir("paste");
+        let params = SeqSketcherParams::new(21, 2, SketchAlgo::PROB3A, DataType::DNA);
+        let metadata = |name : &str| vec![SketchMetadata{name : name.to_string(), seq_len : 10}];
+        let input = dir.join("a.fa");
+        std::fs::write(&input, ">a\nACGTACGTAC\n").unwrap();
+        let (file1, file2) = (dir.join("a.sk"), dir.join("b.sk"));
+        dump_sketch_container_with_inputs(&file1, &params, &metadata("a"), &[vec![1u64, 2]], &[input.as_path()]).unwrap();
+        dump_sketch_container(&file2, &params, &metadata("b"), &[vec![3u64, 4]]).unwrap();
+        let output = dir.join("db.sk");
+        let matches = get_command().try_get_matches_from(["paste", "-o", output.to_str().unwrap(), file1.to_str().unwrap(), file2.to_str().unwrap()]).unwrap();
+        run(&matches).unwrap();
+        let merged = SketchContainer::open(&output).unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged.get_params(), &params);
+        assert_eq!((merged.get_name(0).unwrap(), merged.get_name(1).unwrap()), ("a", "b"));
+        assert_eq!(merged.get_signature::<u64>(1).unwrap(), vec![3u64, 4]);
+        assert_eq!(merged.get_provenance().unwrap().inputs, SketchContainer::open(&file1).unwrap().get_provenance().unwrap().inputs);
+        // incompatible signature type, parameters differing by a filter, output among inputs
+        let other = dir.join("c.sk");
+        dump_sketch_container(&other, &params, &metadata("c"), &[vec![3u32, 4]]).unwrap();
+        let run_args = |files : &[&PathBuf]| {
+            let mut all = vec!["merge".to_string(), "-o".to_string(), output.to_str().unwrap().to_string()];
+            all.extend(files.iter().map(|f| f.to_str().unwrap().to_string()));
+            run(&get_command().try_get_matches_from(all).unwrap())
+        };
+        assert!(run_args(&[&file1, &other]).is_err());
+        let filtered = SeqSketcherParams::builder().kmer_size(21).sketch_size(2).abundance_filter(2, 100).build_with_max_kmer_size(32).unwrap();
+        dump_sketch_container(&other, &filtered, &metadata("c"), &[vec![3u64, 4]]).unwrap();
+        assert!(run_args(&[&file1, &other]).unwrap_err().contains("different sketching parameters"));
+        assert!(run_args(&[&file1, &output]).unwrap_err().contains("also an input"));
+        assert!(run_args(&[&file1, &dir.join(".").join("db.sk")]).unwrap_err().contains("also an input"));
+        //
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_paste_command
+
+} // end of mod tests
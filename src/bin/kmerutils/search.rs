@@ -0,0 +1,287 @@
+//! subcommand search : searches the sketches most similar to queries in a sketch database.
+//!
+//! usage :
+//!    kmerutils search -d database [-n nb_hits] [--min-ani a] [--min-containment c] [--per-record] [--verify] [-o output] query1 [query2 ...]
+//! -   -d database : sketch file, for example pasted from several sketch files (see subcommand paste). It is memory mapped,
+//!     signatures being compared in place. HyperLogLog sketch files (algo hll) are refused.
+//! -   queries : sketch files, or fasta/fastq files (possibly compressed, - for standard input) sketched with the parameters of the database
+//! -   --per-record : a fasta/fastq query gives one sketch by record instead of one by file
+//! -   -n nb_hits : number of hits reported by query sketch, default 10
+//! -   --min-ani a : only hits with ani >= a are reported
+//! -   --min-containment c : only hits with containment of the query in the target >= c are reported
+//! -   --verify : verifies the checksum of the database before searching, which reads the whole file
+//! -   -o output : output file, standard output by default
+//!
+//! The output is a tab separated table with a header and, for each query sketch, its hits by decreasing Jaccard index :
+//! query, target, jaccard, ani, containment, shared (number of equal signature values). Only hits sharing a signature value are reported.
+//! ani is deduced from Jaccard (an amino acid identity for amino acid sketches). As sketches estimate Jaccard,
+//! containment is deduced from Jaccard and sequence lengths, which stand for the numbers of kmers.
+
+use std::borrow::Cow;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use kmerutils::sketching::blockcompare::knn_filtered;
+use kmerutils::sketching::containment::containment_from_jaccard;
+use kmerutils::sketching::filesketch::FileSignatures;
+use kmerutils::sketching::mashdist::identity_from_jaccard;
+use kmerutils::sketching::sketchcontainer::{SketchContainer, SketchContainerSig, SketchMetadata};
+
+use crate::sketchfiles::{check_compatible_files, check_slot_signatures, dispatch_sig_type, is_sketch_file};
+use crate::get_output_writer;
+
+
+pub fn get_command() -> Command {
+    Command::new("search")
+        .about("searches the sketches most similar to queries in a sketch database")
+        .arg(
+            Arg::new("queries")
+                .required(true)
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("sketch files or fasta/fastq files, - for standard input"),
+        )
+        .arg(
+            Arg::new("database")
+                .long("db")
+                .short('d')
+                .required(true)
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("sketch file searched"),
+        )
+        .arg(
+            Arg::new("nb_hits")
+                .long("nb-hits")
+                .short('n')
+                .action(ArgAction::Set)
+                .default_value("10")
+                .value_parser(clap::value_parser!(usize))
+                .help("number of hits by query"),
+        )
+        .arg(
+            Arg::new("min_ani")
+                .long("min-ani")
+                .action(ArgAction::Set)
+                .default_value("0")
+                .value_parser(clap::value_parser!(f64))
+                .help("minimal ani of hits"),
+        )
+        .arg(
+            Arg::new("min_containment")
+                .long("min-containment")
+                .action(ArgAction::Set)
+                .default_value("0")
+                .value_parser(clap::value_parser!(f64))
+                .help("minimal containment of the query in hits"),
+        )
+        .arg(
+            Arg::new("per_record")
+                .long("per-record")
+                .action(ArgAction::SetTrue)
+                .help("one query sketch by record of fasta/fastq queries"),
+        )
+        .arg(
+            Arg::new("verify")
+                .long("verify")
+                .action(ArgAction::SetTrue)
+                .help("verifies the checksum of the database before searching (reads the whole file)"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("output file, standard output by default"),
+        )
+} // end of get_command
+
+
+
+// a hit of a query
+struct Hit {
+    // rank of target in database
+    target : usize,
+    jaccard : f64,
+    ani : f64,
+    containment : f64,
+    common : usize,
+}
+
+
+// thresholds and number of hits
+struct SearchParams {
+    nb_hits : usize,
+    min_ani : f64,
+    min_containment : f64,
+}
+
+
+
+// converts signatures to the signature type of the database, types must be the same
+fn convert_signatures<T : SketchContainerSig, Sig : SketchContainerSig>(signatures : Vec<Vec<T>>) -> Result<Vec<Vec<Sig>>, String> {
+    if T::NAME != Sig::NAME {
+        return Err(format!("fasta queries give {} signatures, database has {} signatures", T::NAME, Sig::NAME));
+    }
+    Ok(signatures.iter().map(|sig| {
+            let mut bytes = Vec::<u8>::with_capacity(sig.len() * T::SIZE);
+            sig.iter().for_each(|v| v.write_le(&mut bytes).unwrap());
+            bytes.chunks_exact(Sig::SIZE).map(Sig::from_le_slice).collect()
+        }).collect())
+}
+
+
+
+// returns the query sketches of a file : signatures of a sketch file, or sketches of a fasta/fastq file
+fn load_queries<Sig : SketchContainerSig>(path : &Path, db : &SketchContainer, db_path : &Path, per_record : bool) -> Result<(Vec<SketchMetadata>, Vec<Vec<Sig>>), String> {
+    if is_sketch_file(path) {
+        let container = SketchContainer::open_verified(path).map_err(|e| format!("could not open sketch file {:?} : {}", path, e))?;
+        check_compatible_files(db, db_path, &container, path)?;
+        return container.iter_signatures::<Sig>()?.collect::<Result<Vec<(SketchMetadata, Vec<Sig>)>, String>>().map(|v| v.into_iter().unzip());
+    }
+    let (metadata, signatures) = crate::sketch::sketch_input(path, db.get_params(), per_record).map_err(|e| format!("could not sketch {:?} : {}", path, e))?;
+    let signatures = match signatures {
+        FileSignatures::Int(s) => convert_signatures::<u64, Sig>(s.signatures)?,
+        FileSignatures::Float(s) => convert_signatures::<f32, Sig>(s.signatures)?,
+        FileSignatures::Double(s) => convert_signatures::<f64, Sig>(s.signatures)?,
+    };
+    Ok((metadata, signatures))
+} // end of load_queries
+
+
+
+// computes a hit from the number of equal signature values of a query and a target
+fn get_hit(query : &SketchMetadata, target : usize, common : usize, db : &SketchContainer) -> Hit {
+    let jaccard = common as f64 / db.get_params().get_sketch_size() as f64;
+    let ani = identity_from_jaccard(jaccard, db.get_params());
+    let containment = containment_from_jaccard(jaccard, query.seq_len, db.get_seq_len(target));
+    Hit{target, jaccard, ani, containment, common}
+} // end of get_hit
+
+
+
+// searches the queries of all files in the database and writes hits, by decreasing Jaccard index then database rank.
+// Signatures of the database are borrowed from the mapped file and compared to queries by tiles (see blockcompare).
+fn search<Sig>(query_files : &[PathBuf], db : &SketchContainer, db_path : &Path, per_record : bool, params : &SearchParams, writer : &mut dyn Write) -> Result<(), String>
+        where Sig : SketchContainerSig + PartialEq + Send + Sync {
+    let targets = (0..db.len()).map(|i| db.get_signature_ref::<Sig>(i)).collect::<Result<Vec<Cow<[Sig]>>, String>>()?;
+    let mut writer = csv::WriterBuilder::new().delimiter(b'\t').from_writer(writer);
+    writer.write_record(["query", "target", "jaccard", "ani", "containment", "shared"]).map_err(|e| e.to_string())?;
+    let mut nb_queries = 0;
+    for path in query_files {
+        let (metadata, signatures) = load_queries::<Sig>(path, db, db_path, per_record)?;
+        let neighbours = knn_filtered(&signatures, &targets, params.nb_hits, |i, j, common| {
+                let hit = get_hit(&metadata[i], j, common, db);
+                hit.common > 0 && hit.ani >= params.min_ani && hit.containment >= params.min_containment
+            })?;
+        for (query, hits) in metadata.iter().zip(neighbours) {
+            for (target, common) in hits {
+                let hit = get_hit(query, target, common, db);
+                writer.write_record([query.name.as_str(), db.get_name(hit.target)?, &hit.jaccard.to_string(), &hit.ani.to_string(),
+                        &hit.containment.to_string(), &hit.common.to_string()]).map_err(|e| e.to_string())?;
+            }
+        }
+        nb_queries += signatures.len();
+    }
+    log::info!("search, nb queries : {}, database size : {}", nb_queries, db.len());
+    writer.flush().map_err(|e| e.to_string())
+} // end of search
+
+
+
+/// runs subcommand search
+pub fn run(matches : &ArgMatches) -> Result<(), String> {
+    let query_files : Vec<PathBuf> = matches.get_many::<PathBuf>("queries").unwrap().cloned().collect();
+    let db_path = matches.get_one::<PathBuf>("database").unwrap();
+    let params = SearchParams{nb_hits : *matches.get_one::<usize>("nb_hits").unwrap(), min_ani : *matches.get_one::<f64>("min_ani").unwrap(),
+                min_containment : *matches.get_one::<f64>("min_containment").unwrap()};
+    if query_files.iter().filter(|f| kmerutils::io::is_stdin(f)).count() > 1 {
+        return Err("standard input can be given only once".to_string());
+    }
+    let db = SketchContainer::open(db_path).map_err(|e| format!("could not open sketch file {:?} : {}", db_path, e))?;
+    if matches.get_flag("verify") {
+        db.verify().map_err(|e| format!("sketch file {:?} : {}", db_path, e))?;
+    }
+    check_slot_signatures(&db, db_path)?;
+    let sig_type = db.get_header().sig_type.clone();
+    let mut writer = get_output_writer(matches.get_one::<PathBuf>("output"))?;
+    dispatch_sig_type!(sig_type.as_str(), search(&query_files, &db, db_path, matches.get_flag("per_record"), &params, &mut writer))?;
+    writer.flush().map_err(|e| e.to_string())
+} // end of run
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use kmerutils::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
+    use kmerutils::sketching::sketchcontainer::dump_sketch_container;
+
+    #[test]
+    fn test_search_command() {
+        let dir = crate::sketchfiles::test_dir("search");
+        let output = dir.join("hits.tsv");
+        let run_args = |args : &[&str]| {
+            let mut all = vec!["search", "-o", output.to_str().unwrap()];
+            all.extend(args);
+            run(&get_command().try_get_matches_from(all).unwrap())
+        };
+        let read_lines = || -> Vec<String> { std::fs::read_to_string(&output).unwrap().lines().map(|l| l.to_string()).collect() };
+        // sketch queries
+        let params = SeqSketcherParams::new(21, 4, SketchAlgo::PROB3A, DataType::DNA);
+        let metadata = |names : &[(&str, u64)]| -> Vec<SketchMetadata> { names.iter().map(|(n, l)| SketchMetadata{name : n.to_string(), seq_len : *l}).collect() };
+        let db = dir.join("db.sk");
+        dump_sketch_container(&db, &params, &metadata(&[("a", 1000), ("b", 1000), ("c", 10000)]), &[vec![1u64, 2, 3, 4], vec![1u64, 7, 8, 9], vec![1u64, 2, 3, 9]]).unwrap();
+        let query = dir.join("query.sk");
+        dump_sketch_container(&query, &params, &metadata(&[("q", 1000)]), &[vec![1u64, 2, 3, 5]]).unwrap();
+        run_args(&["-d", db.to_str().unwrap(), query.to_str().unwrap()]).unwrap();
+        let lines = read_lines();
+        assert_eq!(lines[0], "query\ttarget\tjaccard\tani\tcontainment\tshared");
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("q\ta\t0.75\t") && lines[1].ends_with("\t3"));
+        assert!(lines[2].starts_with("q\tc\t0.75\t") && lines[3].starts_with("q\tb\t0.25\t"));
+        // number of hits and thresholds, q is contained in c but not in a
+        run_args(&["-d", db.to_str().unwrap(), "-n", "1", query.to_str().unwrap()]).unwrap();
+        assert_eq!(read_lines().len(), 2);
+        run_args(&["-d", db.to_str().unwrap(), "--min-containment", "0.95", query.to_str().unwrap()]).unwrap();
+        let lines = read_lines();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("q\tc\t"));
+        run_args(&["-d", db.to_str().unwrap(), "--min-ani", "0.995", query.to_str().unwrap()]).unwrap();
+        assert_eq!(read_lines().len(), 1);
+        // fasta queries are sketched with the parameters of the database
+        let fasta = dir.join("genomes.fa");
+        std::fs::write(&fasta, ">r1\nACGTTGCAAGGCTTACCGATTGACCAGT\n>r2\nGGGCATTACCAGGTTACATTGACC\n").unwrap();
+        let fasta_db = dir.join("genomes.sk");
+        let sketch_matches = crate::sketch::get_command().try_get_matches_from(["sketch", "-k", "8", "-s", "50", "--per-record", "-o",
+                fasta_db.to_str().unwrap(), fasta.to_str().unwrap()]).unwrap();
+        crate::sketch::run(&sketch_matches).unwrap();
+        run_args(&["-d", fasta_db.to_str().unwrap(), "--per-record", fasta.to_str().unwrap()]).unwrap();
+        let lines = read_lines();
+        assert!(lines[1].starts_with("r1\tr1\t1\t1\t1\t50"));
+        assert!(lines.iter().any(|l| l.starts_with("r2\tr2\t1\t")));
+        // incompatible query sketches
+        assert!(run_args(&["-d", fasta_db.to_str().unwrap(), query.to_str().unwrap()]).is_err());
+        // checksum verification, a corrupted signature value is found only with --verify
+        let mut bytes = std::fs::read(&db).unwrap();
+        let last_sig = bytes.len() - 3 * 8 - 4 * 8 - 3 - 8;
+        bytes[last_sig] ^= 1;
+        std::fs::write(&db, &bytes).unwrap();
+        run_args(&["-d", db.to_str().unwrap(), query.to_str().unwrap()]).unwrap();
+        assert!(run_args(&["-d", db.to_str().unwrap(), "--verify", query.to_str().unwrap()]).unwrap_err().contains("checksum"));
+        // HyperLogLog registers are not compared slot by slot
+        let hll = dir.join("hll.sk");
+        dump_sketch_container(&hll, &SeqSketcherParams::new(21, 4, SketchAlgo::HLL, DataType::DNA), &metadata(&[("h", 1000)]), &[vec![1u64, 2, 3, 4]]).unwrap();
+        assert!(run_args(&["-d", hll.to_str().unwrap(), query.to_str().unwrap()]).unwrap_err().contains("HyperLogLog"));
+        //
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_search_command
+
+} // end of mod tests
@@ -0,0 +1,261 @@
+//! subcommand sketch : sketches fasta/fastq files (possibly compressed, - for standard input) in one sketch container.
+//!
+//! usage :
+//!    kmerutils sketch -k kmer_size -s sketch_size -o output [-a algo] [--scaled scale] [-t dna|aa] [--per-record] [--f64] file1 [file2 ...]
+//! -   -k kmer_size : at most 32 for DNA, 12 for amino acids
+//! -   -s sketch_size : number of values of a signature, with --scaled the maximal number of hashes kept
+//! -   --scaled scale : FracMinHash sketching, keeping the kmer hashes below u64::MAX / scale (about 1 kmer out of scale)
+//! -   -a algo : prob3a (default), super, super2, optdens, revoptdens or hll
+//! -   -t alphabet : dna (default, kmers in canonical form) or aa
+//! -   --per-record : one signature by record instead of one by file
+//! -   --f64 : f64 signatures instead of f32 for super, optdens and revoptdens
+//! -   -o output : the sketch container written, see module sketching::sketchcontainer
+//!
+//! Entries of the container are named by the input path (or by the record id with --per-record), with the total number of bases.
+//! Files are sketched in parallel, records being packed as they are parsed.
+//! Containers store rows of sketch_size values, so FracMinHash sketches (see kmerutils::sketching::containment::fracminhash_sketch)
+//! keep their sketch_size smallest hashes and shorter ones are padded with [SCALED_PADDING]. The scale is recorded in the
+//! parameters of the container, the algorithm then being ignored.
+
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use rayon::prelude::*;
+
+use kmerutils::io::is_stdin;
+use kmerutils::sketcharg::{SeqSketcherParams, SketchAlgo, DataType, FloatSig};
+use kmerutils::sketching::filesketch::{sketch_fasta_file, FileSketchMode, FileSignatures};
+use kmerutils::sketching::sketchcontainer::{dump_sketch_container_with_inputs, SketchMetadata};
+
+
+const ALGO_NAMES : [&str; 6] = ["prob3a", "super", "super2", "optdens", "revoptdens", "hll"];
+
+/// value completing FracMinHash sketches shorter than the sketch size in a container, above the threshold of any scale > 1
+pub const SCALED_PADDING : u64 = u64::MAX;
+
+
+pub fn get_command() -> Command {
+    Command::new("sketch")
+        .about("sketches fasta/fastq files in a sketch container")
+        .arg(
+            Arg::new("files")
+                .required(true)
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("fasta/fastq files, possibly compressed, - for standard input"),
+        )
+        .arg(
+            Arg::new("kmer_size")
+                .long("kmer")
+                .short('k')
+                .required(true)
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(usize))
+                .help("kmer size"),
+        )
+        .arg(
+            Arg::new("sketch_size")
+                .long("sketch")
+                .short('s')
+                .required(true)
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(usize))
+                .help("sketch size"),
+        )
+        .arg(
+            Arg::new("algo")
+                .long("algo")
+                .short('a')
+                .action(ArgAction::Set)
+                .default_value("prob3a")
+                .value_parser(ALGO_NAMES)
+                .help("sketching algorithm"),
+        )
+        .arg(
+            Arg::new("scaled")
+                .long("scaled")
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(u64).range(2..))
+                .help("FracMinHash sketching with this scale, keeping at most sketch size hashes"),
+        )
+        .arg(
+            Arg::new("alphabet")
+                .long("alphabet")
+                .short('t')
+                .action(ArgAction::Set)
+                .default_value("dna")
+                .value_parser(["dna", "aa"])
+                .help("dna or aa (amino acids)"),
+        )
+        .arg(
+            Arg::new("per_record")
+                .long("per-record")
+                .action(ArgAction::SetTrue)
+                .help("one signature by record instead of one by file"),
+        )
+        .arg(
+            Arg::new("f64")
+                .long("f64")
+                .action(ArgAction::SetTrue)
+                .help("f64 signatures for super, optdens and revoptdens (default f32)"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .required(true)
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("sketch container to write"),
+        )
+} // end of get_command
+
+
+
+fn get_algo(name : &str) -> SketchAlgo {
+    match name {
+        "super" => SketchAlgo::SUPER,
+        "super2" => SketchAlgo::SUPER2,
+        "optdens" => SketchAlgo::OPTDENS,
+        "revoptdens" => SketchAlgo::REVOPTDENS,
+        "hll" => SketchAlgo::HLL,
+        _ => SketchAlgo::PROB3A,
+    }
+}
+
+
+// returns sketching parameters given by options
+fn get_params(matches : &ArgMatches) -> Result<SeqSketcherParams, String> {
+    let data_t = match matches.get_one::<String>("alphabet").unwrap().as_str() {
+        "aa" => DataType::AA,
+        _ => DataType::DNA,
+    };
+    let float_sig = if matches.get_flag("f64") { FloatSig::F64 } else { FloatSig::F32 };
+    let max_kmer_size = match data_t {
+        DataType::DNA => 32,
+        DataType::AA => 12,
+    };
+    let mut builder = SeqSketcherParams::builder()
+            .kmer_size(*matches.get_one::<usize>("kmer_size").unwrap())
+            .sketch_size(*matches.get_one::<usize>("sketch_size").unwrap())
+            .algo(get_algo(matches.get_one::<String>("algo").unwrap()))
+            .data_t(data_t)
+            .float_sig(float_sig);
+    if let Some(scale) = matches.get_one::<u64>("scaled") {
+        builder = builder.scaled(*scale);
+    }
+    let params = builder.build_with_max_kmer_size(max_kmer_size)?;
+    Ok(params)
+} // end of get_params
+
+
+
+// sketches a file, returns the metadata of its signatures with the signatures. Also used to sketch queries of subcommand search.
+pub(crate) fn sketch_input(path : &Path, params : &SeqSketcherParams, per_record : bool) -> Result<(Vec<SketchMetadata>, FileSignatures), String> {
+    let mode = if per_record { FileSketchMode::PerRecord } else { FileSketchMode::PerFile };
+    let signatures = sketch_fasta_file(path, params, mode)?;
+    let metadata : Vec<SketchMetadata> = signatures.get_names().iter().zip(signatures.get_seq_lens())
+            .map(|(name, seq_len)| SketchMetadata{name : name.clone(), seq_len : *seq_len}).collect();
+    log::info!("sketched {:?}, nb signatures : {}", path, metadata.len());
+    Ok((metadata, signatures))
+} // end of sketch_input
+
+
+
+/// runs subcommand sketch
+pub fn run(matches : &ArgMatches) -> Result<(), String> {
+    let params = get_params(matches)?;
+    let files : Vec<PathBuf> = matches.get_many::<PathBuf>("files").unwrap().cloned().collect();
+    let output = matches.get_one::<PathBuf>("output").unwrap();
+    let per_record = matches.get_flag("per_record");
+    if files.iter().filter(|f| is_stdin(f)).count() > 1 {
+        return Err("standard input can be given only once".to_string());
+    }
+    log::info!("sketching {} files, params : {:?}", files.len(), params);
+    //
+    let sketched = files.par_iter().map(|f| sketch_input(f, &params, per_record)
+            .map_err(|e| format!("could not sketch {:?} : {}", f, e))).collect::<Result<Vec<_>, String>>()?;
+    let mut metadata = Vec::<SketchMetadata>::new();
+    let mut int_sigs = Vec::<Vec<u64>>::new();
+    let mut float_sigs = Vec::<Vec<f32>>::new();
+    let mut double_sigs = Vec::<Vec<f64>>::new();
+    for (file_metadata, signatures) in sketched {
+        metadata.extend(file_metadata);
+        match signatures {
+            FileSignatures::Int(s) if params.get_scaled().is_some() => {
+                int_sigs.extend(s.signatures.into_iter().map(|mut sig| { sig.resize(params.get_sketch_size(), SCALED_PADDING); sig }));
+            },
+            FileSignatures::Int(s) => int_sigs.extend(s.signatures),
+            FileSignatures::Float(s) => float_sigs.extend(s.signatures),
+            FileSignatures::Double(s) => double_sigs.extend(s.signatures),
+        }
+    }
+    // checksums of inputs are recorded in the container provenance
+    let inputs : Vec<&Path> = files.iter().filter(|f| !is_stdin(f)).map(|f| f.as_path()).collect();
+    let nb_bytes = if !float_sigs.is_empty() {
+        dump_sketch_container_with_inputs(output, &params, &metadata, &float_sigs, &inputs)?
+    }
+    else if !double_sigs.is_empty() {
+        dump_sketch_container_with_inputs(output, &params, &metadata, &double_sigs, &inputs)?
+    }
+    else {
+        dump_sketch_container_with_inputs(output, &params, &metadata, &int_sigs, &inputs)?
+    };
+    println!("{} signatures written in {:?} ({} bytes)", metadata.len(), output, nb_bytes);
+    Ok(())
+} // end of run
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use kmerutils::sketching::sketchcontainer::SketchContainer;
+
+    #[test]
+    fn test_sketch_command() {
+        let dir = crate::sketchfiles::test_dir("sketch");
+        let fasta = dir.join("genome.fa");
+        std::fs::write(&fasta, ">r1\nACGTTGCAAGGCTTACCGATTGACCAGT\n>r2\nNN\n>r3\nGGGCATTACCAGGTTACATTGACC\n").unwrap();
+        let output = dir.join("sketches.sk");
+        let args = |extra : &[&str]| {
+            let mut args = vec!["sketch", "-k", "8", "-s", "50", "-o", output.to_str().unwrap()];
+            args.extend(extra);
+            args.push(fasta.to_str().unwrap());
+            get_command().try_get_matches_from(args).unwrap()
+        };
+        // one signature for the file
+        run(&args(&[])).unwrap();
+        let container = SketchContainer::open(&output).unwrap();
+        assert_eq!(container.len(), 1);
+        assert_eq!(container.get_name(0).unwrap(), fasta.to_str().unwrap());
+        assert_eq!(container.get_seq_len(0), 54);
+        assert_eq!(container.get_signature::<u64>(0).unwrap().len(), 50);
+        // one by record, r2 has no kmer
+        run(&args(&["--per-record", "-a", "optdens"])).unwrap();
+        let container = SketchContainer::open(&output).unwrap();
+        assert_eq!(container.get_params().get_algo(), SketchAlgo::OPTDENS);
+        assert_eq!((0..container.len()).map(|i| (container.get_name(i).unwrap(), container.get_seq_len(i))).collect::<Vec<_>>(), vec![("r1", 28), ("r3", 24)]);
+        assert!(container.get_signature::<f32>(1).is_ok());
+        // FracMinHash sketches padded to the sketch size, scale recorded in the container
+        run(&args(&["--scaled", "2"])).unwrap();
+        let container = SketchContainer::open(&output).unwrap();
+        assert_eq!(container.get_params().get_scaled(), Some(2));
+        let sig = container.get_signature::<u64>(0).unwrap();
+        let nb_hashes = sig.iter().filter(|h| **h != SCALED_PADDING).count();
+        assert!(nb_hashes > 0 && nb_hashes < 50);
+        assert!(sig.windows(2).all(|w| w[0] < w[1] || w[1] == SCALED_PADDING));
+        assert!(sig[..nb_hashes].iter().all(|h| *h <= u64::MAX / 2));
+        assert!(get_command().try_get_matches_from(["sketch", "-k", "8", "-s", "50", "--scaled", "1", "-o", output.to_str().unwrap(), fasta.to_str().unwrap()]).is_err());
+        // kmer size checked
+        let matches = get_command().try_get_matches_from(["sketch", "-t", "aa", "-k", "13", "-s", "50", "-o", output.to_str().unwrap(), fasta.to_str().unwrap()]).unwrap();
+        assert!(run(&matches).is_err());
+        //
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_sketch_command
+
+} // end of mod tests
@@ -0,0 +1,114 @@
+//! opening of the sketch files given to subcommands.
+//!
+//! Sketch files are containers written by subcommand sketch (see module sketching::sketchcontainer).
+//! Files used together must have compatible sketching parameters and the same signature type.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use kmerutils::sketcharg::SketchAlgo;
+use kmerutils::sketching::sketchcontainer::{SketchContainer, SketchContainerSig, SketchMetadata, MAGIC_SKETCH_CONTAINER};
+
+
+/// calls a function generic in the signature type with the type named by a container header
+/// (u16, u32, u64, f32 or f64). The function must return a Result<_, String>.
+macro_rules! dispatch_sig_type {
+    ($sig_type:expr, $f:ident ( $($arg:expr),* )) => {
+        match $sig_type {
+            "u16" => $f::<u16>($($arg),*),
+            "u32" => $f::<u32>($($arg),*),
+            "u64" => $f::<u64>($($arg),*),
+            "f32" => $f::<f32>($($arg),*),
+            "f64" => $f::<f64>($($arg),*),
+            other => Err(format!("unknown signature type {}", other)),
+        }
+    };
+}
+pub(crate) use dispatch_sig_type;
+
+
+
+/// returns true if path is a sketch file (a container), false for other files (fasta ...) or standard input
+pub fn is_sketch_file(path : &Path) -> bool {
+    if kmerutils::io::is_stdin(path) {
+        return false;
+    }
+    let mut magic = [0u8; 4];
+    match std::fs::File::open(path) {
+        Ok(mut file) => file.read_exact(&mut magic).is_ok() && u32::from_le_bytes(magic) == MAGIC_SKETCH_CONTAINER,
+        Err(_) => false,
+    }
+}
+
+
+
+/// checks that signatures of container (file path) can be compared to those of first (file first_path) :
+/// compatible parameters (see SeqSketcherParams::check_compatible) and same signature type.
+pub fn check_compatible_files(first : &SketchContainer, first_path : &Path, container : &SketchContainer, path : &Path) -> Result<(), String> {
+    first.get_params().check_compatible(container.get_params())
+            .map_err(|e| format!("sketch files {:?} and {:?} are not compatible : {}", first_path, path, e))?;
+    if first.get_header().sig_type != container.get_header().sig_type {
+        return Err(format!("sketch files {:?} and {:?} are not compatible : signature types {} and {}", first_path, path,
+                first.get_header().sig_type, container.get_header().sig_type));
+    }
+    Ok(())
+} // end of check_compatible_files
+
+
+
+/// refuses HyperLogLog sketch files (algo hll) and FracMinHash sketch files (option --scaled) : Jaccard indexes are estimated
+/// by the fraction of equal signature values, which does not hold for HyperLogLog registers (see kmerutils::sketching::sigdistance::HllSignature)
+/// nor for sorted FracMinHash hashes (see kmerutils::sketching::containment).
+pub fn check_slot_signatures(container : &SketchContainer, path : &Path) -> Result<(), String> {
+    if let Some(scale) = container.get_params().get_scaled() {
+        log::error!("sketch file {:?} holds FracMinHash sketches (scale {}), they cannot be compared slot by slot", path, scale);
+        return Err(format!("sketch file {:?} : FracMinHash sketches (--scaled) are not supported, they cannot be compared slot by slot", path));
+    }
+    if container.get_params().get_algo() == SketchAlgo::HLL {
+        log::error!("sketch file {:?} holds HyperLogLog registers, they cannot be compared slot by slot", path);
+        return Err(format!("sketch file {:?} : HyperLogLog sketches (algo hll) are not supported, their registers cannot be compared slot by slot", path));
+    }
+    Ok(())
+} // end of check_slot_signatures
+
+
+
+/// opens (and verifies) sketch files, checking that their signatures can be compared (see [check_compatible_files]).
+pub fn open_sketch_files(paths : &[PathBuf]) -> Result<Vec<SketchContainer>, String> {
+    let mut containers = Vec::<SketchContainer>::with_capacity(paths.len());
+    for path in paths {
+        let container = SketchContainer::open_verified(path).map_err(|e| format!("could not open sketch file {:?} : {}", path, e))?;
+        if let Some(first) = containers.first() {
+            check_compatible_files(first, &paths[0], &container, path)?;
+        }
+        containers.push(container);
+    }
+    Ok(containers)
+} // end of open_sketch_files
+
+
+
+/// returns metadata and signatures of all sketches of containers, in order
+pub fn load_signatures<Sig : SketchContainerSig>(containers : &[SketchContainer]) -> Result<(Vec<SketchMetadata>, Vec<Vec<Sig>>), String> {
+    let mut metadata = Vec::<SketchMetadata>::new();
+    let mut signatures = Vec::<Vec<Sig>>::new();
+    for container in containers {
+        for item in container.iter_signatures::<Sig>()? {
+            let (m, sig) = item?;
+            metadata.push(m);
+            signatures.push(sig);
+        }
+    }
+    Ok((metadata, signatures))
+} // end of load_signatures
+
+
+/// returns an empty directory for the test of a subcommand, unique to the running process
+/// so that concurrent test runs do not share files.
+#[cfg(test)]
+pub fn test_dir(name : &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("kmerutils_cli_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+} // end of test_dir
@@ -0,0 +1,157 @@
+//! subcommand translate : translates DNA fasta/fastq files (possibly compressed, - for standard input) in amino acid fasta.
+//!
+//! usage :
+//!    kmerutils translate [--orfs [--min-len l] [--no-start]] [-o output] file1 [file2 ...]
+//! -   without --orfs each record gives its six frames, named id_frame (id_+1, ..., id_-3), stops being written as '*'
+//! -   --orfs : writes the open reading frames of the six frames, from ATG to the stop codon (excluded) or the end of the record,
+//!     named id_orf_rank with frame and forward strand coordinates (0 based, end excluded) in the description
+//! -   --min-len l : minimal length of ORFs in amino acids, default 30
+//! -   --no-start : ORFs run from stop to stop, without requiring an ATG
+//! -   -o output : output file, standard output by default
+//!
+//! The standard genetic code is used, see module aautils::translate. The output can be sketched with kmerutils sketch -t aa.
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use kmerutils::aautils::translate::{find_orfs, six_frame_translation};
+use kmerutils::io::{is_stdin, read_fastx_records, read_fastx_records_from};
+
+use crate::get_output_writer;
+
+
+// length of fasta lines
+const FASTA_LINE_LEN : usize = 60;
+
+
+pub fn get_command() -> Command {
+    Command::new("translate")
+        .about("translates DNA fasta/fastq files in six frames or ORFs (amino acid fasta)")
+        .arg(
+            Arg::new("files")
+                .required(true)
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("fasta/fastq files, possibly compressed, - for standard input"),
+        )
+        .arg(
+            Arg::new("orfs")
+                .long("orfs")
+                .action(ArgAction::SetTrue)
+                .help("writes open reading frames instead of the six frames"),
+        )
+        .arg(
+            Arg::new("min_len")
+                .long("min-len")
+                .action(ArgAction::Set)
+                .default_value("30")
+                .value_parser(clap::value_parser!(usize))
+                .requires("orfs")
+                .help("minimal length of ORFs in amino acids"),
+        )
+        .arg(
+            Arg::new("no_start")
+                .long("no-start")
+                .action(ArgAction::SetTrue)
+                .requires("orfs")
+                .help("ORFs run from stop to stop without requiring a start codon"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("output file, standard output by default"),
+        )
+} // end of get_command
+
+
+
+// writes a fasta record
+fn write_fasta(writer : &mut dyn Write, header : &str, seq : &[u8]) -> std::io::Result<()> {
+    writeln!(writer, ">{}", header)?;
+    for line in seq.chunks(FASTA_LINE_LEN) {
+        writer.write_all(line)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+
+
+/// runs subcommand translate
+pub fn run(matches : &ArgMatches) -> Result<(), String> {
+    let files : Vec<PathBuf> = matches.get_many::<PathBuf>("files").unwrap().cloned().collect();
+    let orfs = matches.get_flag("orfs");
+    let min_len = *matches.get_one::<usize>("min_len").unwrap();
+    let require_start = !matches.get_flag("no_start");
+    let mut writer = get_output_writer(matches.get_one::<PathBuf>("output"))?;
+    let mut nb_written = 0usize;
+    for path in &files {
+        let records = if is_stdin(path) { read_fastx_records_from(std::io::stdin())? } else { read_fastx_records(path)? };
+        for record in &records {
+            // the id is the first word of the header
+            let id = record.id.split_whitespace().next().unwrap_or("");
+            if orfs {
+                for (rank, orf) in find_orfs(&record.seq, min_len, require_start).iter().enumerate() {
+                    let header = format!("{}_orf_{} frame={:+} start={} end={}", id, rank, orf.frame, orf.start, orf.end);
+                    write_fasta(&mut writer, &header, &orf.protein).map_err(|e| e.to_string())?;
+                    nb_written += 1;
+                }
+            }
+            else {
+                for (frame, protein) in six_frame_translation(&record.seq) {
+                    write_fasta(&mut writer, &format!("{}_{:+}", id, frame), &protein).map_err(|e| e.to_string())?;
+                    nb_written += 1;
+                }
+            }
+        }
+    }
+    log::info!("translate, nb amino acid records written : {}", nb_written);
+    writer.flush().map_err(|e| e.to_string())
+} // end of run
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_translate_command() {
+        let dir = crate::sketchfiles::test_dir("translate");
+        let fasta = dir.join("genes.fa");
+        std::fs::write(&fasta, ">g1 a gene\nATGGCCATTGTAATGGGCCGCTGAAAGGGTGCCCGATAG\n").unwrap();
+        let output = dir.join("proteins.fa");
+        let run_args = |args : &[&str]| {
+            let mut all = vec!["translate", "-o", output.to_str().unwrap()];
+            all.extend(args);
+            all.push(fasta.to_str().unwrap());
+            run(&get_command().try_get_matches_from(all).unwrap())
+        };
+        run_args(&[]).unwrap();
+        let records = read_fastx_records(&output).unwrap();
+        assert_eq!(records.len(), 6);
+        assert_eq!(records[0].id, "g1_+1");
+        assert_eq!(records[0].seq, b"MAIVMGR*KGAR*");
+        assert_eq!(records[5].id, "g1_-3");
+        // orfs
+        run_args(&["--orfs", "--min-len", "5"]).unwrap();
+        let records = read_fastx_records(&output).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].id, "g1_orf_0 frame=+1 start=0 end=21");
+        assert_eq!(records[0].seq, b"MAIVMGR");
+        // min-len requires --orfs
+        assert!(get_command().try_get_matches_from(["translate", "--min-len", "5", fasta.to_str().unwrap()]).is_err());
+        //
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_translate_command
+
+} // end of mod tests
@@ -1,6 +1,8 @@
 //! Basic stuff about hashed items
 
 use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::hash::Hash;
 use std::marker::PhantomData;
 
 
@@ -10,6 +12,39 @@ use std::marker::PhantomData;
 pub type ItemHash = u64;
 
 
+/// Type of hash values kept in minhash sketches ([MinHashCount](crate::sketching::minhash::MinHashCount)) :
+/// ItemHash (u64) or u32 when hashes fit in 32 bits (invertible 32 bit hash of kmers of at most 16 bases, NoHashHasher on u32 kmers),
+/// halving the memory of hashes kept for large sketch sizes.
+pub trait SketchHashT : Copy + Ord + Hash + Debug + Send + Sync + 'static {
+    /// largest hash value
+    const MAX : Self;
+    /// keeps the (low) bits of a Hasher output
+    fn from_item_hash(hash : ItemHash) -> Self;
+    /// converts to ItemHash, preserving order
+    fn to_item_hash(self) -> ItemHash;
+}
+
+impl SketchHashT for u64 {
+    const MAX : Self = u64::MAX;
+    fn from_item_hash(hash : ItemHash) -> Self {
+        hash
+    }
+    fn to_item_hash(self) -> ItemHash {
+        self
+    }
+}
+
+impl SketchHashT for u32 {
+    const MAX : Self = u32::MAX;
+    fn from_item_hash(hash : ItemHash) -> Self {
+        hash as u32
+    }
+    fn to_item_hash(self) -> ItemHash {
+        self as ItemHash
+    }
+}
+
+
 
 // If we use an inversible hash we do not need to keep item (the kmer)
 // for other hash  we need copying and storing of Kmer... whence the Option<T> field
@@ -20,40 +55,40 @@ pub type ItemHash = u64;
 /// are stored in a u32 or a u64 (as in some Kmer representation) we can retrive objects
 /// from hashed value. (See module invhash)
 #[derive(Debug,Clone,Copy)]
-pub struct HashedItem<T:Clone+Copy> {
-    pub(crate) hash: ItemHash,
+pub struct HashedItem<T:Clone+Copy, Hv:SketchHashT = ItemHash> {
+    pub(crate) hash: Hv,
     ///
 #[allow(unused)]
     pub(crate) item: Option<T>,
 }
 
-impl<T:Clone+Copy> PartialEq for HashedItem<T> {
-    fn eq(&self, other: &HashedItem<T>) -> bool {
+impl<T:Clone+Copy, Hv:SketchHashT> PartialEq for HashedItem<T, Hv> {
+    fn eq(&self, other: &HashedItem<T, Hv>) -> bool {
         other.hash.eq(&self.hash)
     }
 }
 
-impl<T:Clone+Copy> Eq for HashedItem<T> {}
+impl<T:Clone+Copy, Hv:SketchHashT> Eq for HashedItem<T, Hv> {}
 
-impl<T:Clone+Copy> Ord for HashedItem<T> {
-    fn cmp(&self, other: &HashedItem<T>) -> Ordering {
+impl<T:Clone+Copy, Hv:SketchHashT> Ord for HashedItem<T, Hv> {
+    fn cmp(&self, other: &HashedItem<T, Hv>) -> Ordering {
         self.hash.cmp(&other.hash)
     }
 }
 
-impl<T:Clone+Copy> PartialOrd for HashedItem<T> {
-    fn partial_cmp(&self, other: &HashedItem<T>) -> Option<Ordering> {
-        Some(self.hash.cmp(&other.hash))
+impl<T:Clone+Copy, Hv:SketchHashT> PartialOrd for HashedItem<T, Hv> {
+    fn partial_cmp(&self, other: &HashedItem<T, Hv>) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
 
 
-// size is 2*8+2 bytes !!
+// size is 2*8+2 bytes !! (with u32 items and hashes 2*4+4+2)
 /// to store count of object
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct HashCount<T:Clone+Copy> {
-    pub hashed: HashedItem<T>,
+pub struct HashCount<T:Clone+Copy, Hv:SketchHashT = ItemHash> {
+    pub hashed: HashedItem<T, Hv>,
     pub count: u16,
 }
 
@@ -2,15 +2,18 @@
 
 // for needletail
 
-
+#[cfg(feature="io")]
 use std::time::*;
 
 use std::path::Path;
+use std::io::Read;
 
 use crate::base::{sequence::*};
+#[cfg(feature="io")]
 use crate::parsearg::*;
 
 
+#[cfg(feature="io")]
 pub fn parse_with_needletail(parsed_args: ParseFastqArgs) ->  std::result::Result<Vec<Sequence>, &'static str > {
     //
     println!("parsing with needletail file : {} ", parsed_args.filename);
@@ -21,6 +24,10 @@ pub fn parse_with_needletail(parsed_args: ParseFastqArgs) ->  std::result::Resul
         Ok(meta) => {
             filesize = meta.len();           
         },
+        // stdin size is unknown
+        Err(_e) if is_stdin(path) => {
+            filesize = 0;
+        },
         Err(_e) => {
             println!("file does not exist: {:?}", parsed_args.filename);
             return Err("file does not exist");
@@ -39,7 +46,7 @@ pub fn parse_with_needletail(parsed_args: ParseFastqArgs) ->  std::result::Resul
     
     
     let start_t = Instant::now();
-    let mut reader = needletail::parse_fastx_file(&path).expect("expecting valid filename");
+    let mut reader = open_fastx_file(path).expect("expecting valid filename");
     while let Some(record) = reader.next() {
         let seqrec = record.expect("invalid record");
         n_bases += seqrec.num_bases();
@@ -77,3 +84,444 @@ pub fn parse_with_needletail(parsed_args: ParseFastqArgs) ->  std::result::Resul
     return Ok(seq_array);
 }  // end of parse_with_needletail
 
+
+
+
+// packs a run of ACGT bases in 2 bits, runs with lower case bases being first converted in upper (reusing buffer upper)
+fn pack_acgt_run(run : &[u8], upper : &mut Vec<u8>) -> Sequence {
+    if run.iter().all(|b| b.is_ascii_uppercase()) {
+        Sequence::new(run, 2)
+    }
+    else {
+        upper.clear();
+        upper.extend(run.iter().map(|b| b.to_ascii_uppercase()));
+        Sequence::new(upper, 2)
+    }
+} // end of pack_acgt_run
+
+
+/// splits a raw sequence (as read from a fasta/fastq record) into maximal runs of ACGT bases (lower case bases are accepted)
+/// and returns the 2 bits encoded sequences of the runs with at least min_len bases.  
+/// This avoids dropping a whole genome record because of some N or IUPAC codes.
+/// Runs are packed directly from raw, only runs with lower case bases being copied.
+pub fn split_acgt_runs(raw : &[u8], min_len : usize) -> Vec<Sequence> {
+    let mut upper = Vec::<u8>::new();
+    raw.split(|b| !is_acgt(b.to_ascii_uppercase())).filter(|run| run.len() >= min_len.max(1)).map(|run| pack_acgt_run(run, &mut upper)).collect()
+} // end of split_acgt_runs
+
+
+
+/// reads all records of a fasta/fastq file (possibly compressed) and returns them as lists of 2 bits encoded ACGT runs,
+/// see [split_acgt_runs]. Runs shorter than min_len are skipped.
+#[cfg(feature="io")]
+pub fn read_acgt_sequences(path : &Path, min_len : usize) -> std::result::Result<Vec<Sequence>, String> {
+    //
+    log::debug!("read_acgt_sequences, reading file {:?}", path);
+    let mut reader = open_fastx_file(path)?;
+    let mut seq_array = Vec::<Sequence>::new();
+    while let Some(record) = reader.next() {
+        let seqrec = record.map_err(|e| format!("invalid record : {}", e))?;
+        seq_array.extend(split_acgt_runs(&seqrec.seq(), min_len));
+    }
+    log::debug!("read_acgt_sequences, nb sequences : {}", seq_array.len());
+    Ok(seq_array)
+} // end of read_acgt_sequences
+
+
+
+/// a fasta/fastq record : its id, its raw sequence and its quality bytes (fastq only)
+#[derive(Clone,Debug)]
+pub struct FastxRecord {
+    pub id : String,
+    pub seq : Vec<u8>,
+    pub qual : Option<Vec<u8>>,
+}
+
+
+/// reads all records of a fasta/fastq file (possibly compressed) and converts each record, as soon as it is parsed, by f applied
+/// to its raw sequence and its quality (fastq only). Returns the ids of records with their conversion.  
+/// Bases can thus be packed (for example by [split_acgt_runs]) directly from the parser buffer, without keeping the ASCII sequences
+/// of all records as [read_fastx_records] does, which halves the peak memory when a whole file is loaded.
+#[cfg(feature="io")]
+pub fn read_fastx_mapped<T, F>(path : &Path, mut f : F) -> std::result::Result<Vec<(String, T)>, String>
+        where F : FnMut(&[u8], Option<&[u8]>) -> T {
+    //
+    log::debug!("read_fastx_mapped, reading file {:?}", path);
+    let mut reader = open_fastx_file(path)?;
+    let mut records = Vec::<(String, T)>::new();
+    while let Some(record) = reader.next() {
+        let seqrec = record.map_err(|e| format!("invalid record : {}", e))?;
+        let id = String::from_utf8_lossy(seqrec.id()).to_string();
+        records.push((id, f(&seqrec.seq(), seqrec.qual())));
+    }
+    log::debug!("read_fastx_mapped, nb records : {}", records.len());
+    Ok(records)
+} // end of read_fastx_mapped
+
+
+
+/// reads all records of a fasta/fastq file (possibly compressed).
+/// Sequences are not checked against an alphabet, so the function serves both DNA and amino acid files.
+#[cfg(feature="io")]
+pub fn read_fastx_records(path : &Path) -> std::result::Result<Vec<FastxRecord>, String> {
+    //
+    log::debug!("read_fastx_records, reading file {:?}", path);
+    let reader = open_fastx_file(path)?;
+    collect_fastx_records(reader)
+} // end of read_fastx_records
+
+
+/// reads all records of a fasta/fastq stream, possibly compressed (see [open_decompressed_reader]).
+/// This serves in memory data, for example members of a tar or zip archive.
+#[cfg(feature="io")]
+pub fn read_fastx_records_from<R : Read + Send + 'static>(reader : R) -> std::result::Result<Vec<FastxRecord>, String> {
+    let reader = open_decompressed_reader(reader).map_err(|e| {
+            log::error!("read_fastx_records_from could not decode stream : {}", e);
+            format!("could not decode stream : {}", e)
+        })?;
+    let reader = needletail::parse_fastx_reader(reader).map_err(|e| {
+            log::error!("read_fastx_records_from could not parse stream : {}", e);
+            format!("could not parse stream : {}", e)
+        })?;
+    collect_fastx_records(reader)
+} // end of read_fastx_records_from
+
+
+#[cfg(feature="io")]
+fn collect_fastx_records(mut reader : Box<dyn needletail::FastxReader>) -> std::result::Result<Vec<FastxRecord>, String> {
+    let mut records = Vec::<FastxRecord>::new();
+    while let Some(record) = reader.next() {
+        let seqrec = record.map_err(|e| format!("invalid record : {}", e))?;
+        let id = String::from_utf8_lossy(seqrec.id()).to_string();
+        let qual = seqrec.qual().map(|q| q.to_vec());
+        records.push(FastxRecord{id, seq : seqrec.seq().to_vec(), qual});
+    }
+    log::debug!("read_fastx_records, nb records : {}", records.len());
+    Ok(records)
+}
+
+
+
+/// splits a fastq record in runs of ACGT bases with Phred quality (quality byte - 33) >= min_quality,
+/// and returns the 2 bits encoded runs of length >= min_len with their quality bytes.
+pub fn split_acgt_qual_runs(raw : &[u8], qual : &[u8], min_len : usize, min_quality : u8) -> Vec<(Sequence, Vec<u8>)> {
+    assert_eq!(raw.len(), qual.len(), "sequence and quality must have same length");
+    let mut runs = Vec::<(Sequence, Vec<u8>)>::new();
+    let mut upper = Vec::<u8>::new();
+    let mut start = 0;
+    for i in 0..=raw.len() {
+        let valid = i < raw.len() && is_acgt(raw[i].to_ascii_uppercase()) && qual[i].saturating_sub(33) >= min_quality;
+        if !valid {
+            if i - start >= min_len.max(1) {
+                runs.push((pack_acgt_run(&raw[start..i], &mut upper), qual[start..i].to_vec()));
+            }
+            start = i + 1;
+        }
+    }
+    runs
+} // end of split_acgt_qual_runs
+
+
+
+/// compression formats of input files, recognized by their magic number
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+
+/// path designating standard input in all file level entry points
+pub const STDIN_PATH : &str = "-";
+
+
+/// returns true if path designates standard input ([STDIN_PATH])
+pub fn is_stdin(path : &Path) -> bool {
+    path.as_os_str() == STDIN_PATH
+}
+
+
+// recognizes compression from magic bytes
+fn get_compression_from_magic(magic : &[u8]) -> Compression {
+    if magic.starts_with(&[0x1f, 0x8b]) {
+        Compression::Gzip
+    } else if magic.starts_with(b"BZh") {
+        Compression::Bzip2
+    } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Compression::Xz
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Compression::Zstd
+    } else {
+        Compression::None
+    }
+}
+
+
+// reads up to 6 bytes, less only at end of stream
+fn read_magic<R : Read>(reader : &mut R) -> std::io::Result<Vec<u8>> {
+    let mut magic = [0u8; 6];
+    let mut nb_read = 0;
+    while nb_read < magic.len() {
+        let n = reader.read(&mut magic[nb_read..])?;
+        if n == 0 {
+            break;
+        }
+        nb_read += n;
+    }
+    Ok(magic[..nb_read].to_vec())
+}
+
+
+/// detects compression of a file from its first bytes (file extension is not used).
+/// Standard input cannot be inspected without consuming it, use [open_decompressed] which handles it.
+pub fn detect_compression(path : &Path) -> std::io::Result<Compression> {
+    let mut file = std::fs::File::open(path)?;
+    Ok(get_compression_from_magic(&read_magic(&mut file)?))
+} // end of detect_compression
+
+
+
+// wraps a reader in the decoder corresponding to compression
+fn get_decoder<R : Read + Send + 'static>(reader : R, compression : Compression) -> std::io::Result<Box<dyn Read + Send>> {
+    let decoder : Box<dyn Read + Send> = match compression {
+        Compression::None  => Box::new(reader),
+        #[cfg(feature="io")]
+        Compression::Gzip  => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        #[cfg(feature="io")]
+        Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+        #[cfg(feature="io")]
+        Compression::Xz    => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        #[cfg(feature="zstd")]
+        Compression::Zstd  => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        #[cfg(not(feature="zstd"))]
+        Compression::Zstd  => {
+            log::error!("input is zstd compressed, feature zstd is needed");
+            return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "zstd compressed input needs feature zstd"));
+        },
+        #[cfg(not(feature="io"))]
+        _ => {
+            log::error!("input is {:?} compressed, feature io is needed", compression);
+            return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "compressed input needs feature io"));
+        },
+    };
+    Ok(decoder)
+}
+
+
+/// decompresses on the fly any stream (pipe, socket, stdin ...) if it is gzip, bzip2, xz or zstd (with feature zstd) compressed.
+/// The compression is recognized from the first bytes of the stream, which are then replayed.
+pub fn open_decompressed_reader<R : Read + Send + 'static>(mut reader : R) -> std::io::Result<Box<dyn Read + Send>> {
+    let magic = read_magic(&mut reader)?;
+    let compression = get_compression_from_magic(&magic);
+    log::debug!("open_decompressed_reader, compression : {:?}", compression);
+    get_decoder(std::io::Cursor::new(magic).chain(reader), compression)
+} // end of open_decompressed_reader
+
+
+
+/// opens a file, decompressing it on the fly if it is gzip, bzip2, xz or zstd (with feature zstd) compressed.
+/// The path [STDIN_PATH] ("-") reads standard input.
+pub fn open_decompressed(path : &Path) -> std::io::Result<Box<dyn Read + Send>> {
+    if is_stdin(path) {
+        log::debug!("open_decompressed, reading stdin");
+        return open_decompressed_reader(std::io::stdin());
+    }
+    let compression = detect_compression(path)?;
+    log::debug!("open_decompressed {:?}, compression : {:?}", path, compression);
+    let file = std::fs::File::open(path)?;
+    get_decoder(file, compression).map_err(|e| {
+        log::error!("open_decompressed : could not decode file {:?} : {}", path, e);
+        e
+    })
+} // end of open_decompressed
+
+
+
+/// opens a fasta/fastq file, possibly compressed (see [open_decompressed]), with a needletail reader.
+/// Fasta or fastq format is recognized from content, the path [STDIN_PATH] ("-") reads standard input.
+#[cfg(feature="io")]
+pub fn open_fastx_file(path : &Path) -> std::result::Result<Box<dyn needletail::FastxReader>, String> {
+    let reader = match open_decompressed(path) {
+        Ok(reader) => reader,
+        Err(e) => {
+            log::error!("open_fastx_file could not open file {:?}, {}", path, e);
+            return Err(format!("could not open file {:?} : {}", path, e));
+        }
+    };
+    needletail::parse_fastx_reader(reader).map_err(|e| {
+        log::error!("open_fastx_file could not parse file {:?}, {}", path, e);
+        format!("could not parse file {:?} : {}", path, e)
+    })
+} // end of open_fastx_file
+
+
+
+/// dumps any serializable value (signatures, [SeqSketcherParams](crate::sketcharg::SeqSketcherParams), distances ...)
+/// in compact binary format (bincode). Returns the number of bytes written.
+/// This is much smaller and faster to reload than json.
+pub fn dump_bin<T : serde::Serialize>(value : &T, path : &Path) -> std::result::Result<usize, String> {
+    //
+    log::info!("dump_bin, dumping in file : {:?}", path);
+    //
+    let fileres = std::fs::OpenOptions::new().write(true).create(true).truncate(true).open(path);
+    if fileres.is_err() {
+        log::error!("dump_bin could not open file {:?}", path.as_os_str());
+        println!("dump_bin could not open file {:?}", path.as_os_str());
+        return Err("dump_bin could not open file".to_string());
+    }
+    let mut writer = std::io::BufWriter::new(fileres.unwrap());
+    bincode::serialize_into(&mut writer, value).map_err(|e| {
+        log::error!("dump_bin, serialization failed : {}", e);
+        e.to_string()
+    })?;
+    std::io::Write::flush(&mut writer).map_err(|e| e.to_string())?;
+    Ok(bincode::serialized_size(value).map_err(|e| e.to_string())? as usize)
+} // end of dump_bin
+
+
+
+/// reloads a value dumped by [dump_bin]. The file can be compressed (see [open_decompressed]).
+pub fn reload_bin<T : serde::de::DeserializeOwned>(path : &Path) -> std::result::Result<T, String> {
+    //
+    log::info!("reload_bin, reloading file : {:?}", path);
+    //
+    let reader = match open_decompressed(path) {
+        Ok(reader) => std::io::BufReader::new(reader),
+        Err(e) => {
+            log::error!("reload_bin could not open file {:?}, {}", path, e);
+            println!("reload_bin could not open file {:?}", path.as_os_str());
+            return Err("reload_bin could not open file".to_string());
+        }
+    };
+    bincode::deserialize_from(reader).map_err(|e| {
+        log::error!("reload_bin, deserialization of {:?} failed : {}", path, e);
+        e.to_string()
+    })
+} // end of reload_bin
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    #[cfg(feature="io")]
+    use std::io::Write;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[cfg(feature="io")]
+    #[test]
+    fn test_compressed_fastx() {
+        log_init_test();
+        let content = b">r1\nACGTNACGTACGT\n>r2\nTTTTGGGG\n";
+        let dir = crate::test_dir("compressed_fastx");
+        // plain and gzip
+        let plain = dir.join("kmerutils_io_test.fa");
+        std::fs::write(&plain, content).unwrap();
+        let gz = dir.join("kmerutils_io_test.fa.gz");
+        let mut encoder = flate2::write::GzEncoder::new(std::fs::File::create(&gz).unwrap(), flate2::Compression::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap();
+        let xz = dir.join("kmerutils_io_test.fa.xz");
+        let mut encoder = xz2::write::XzEncoder::new(std::fs::File::create(&xz).unwrap(), 6);
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap();
+        let mut files = vec![(plain, Compression::None), (gz, Compression::Gzip), (xz, Compression::Xz)];
+        #[cfg(feature="zstd")]
+        {
+            let zst = dir.join("kmerutils_io_test.fa.zst");
+            std::fs::write(&zst, zstd::encode_all(&content[..], 3).unwrap()).unwrap();
+            files.push((zst, Compression::Zstd));
+        }
+        for (path, compression) in &files {
+            assert_eq!(detect_compression(path).unwrap(), *compression);
+            let records = read_fastx_records(path).unwrap();
+            assert_eq!(records.len(), 2);
+            assert_eq!(records[1].id, "r2");
+            assert_eq!(records[1].seq, b"TTTTGGGG".to_vec());
+            assert!(records[1].qual.is_none());
+            let runs = read_acgt_sequences(path, 4).unwrap();
+            assert_eq!(runs.len(), 3);
+            let _ = std::fs::remove_file(path);
+        }
+        // stream with compression detected on the fly, short stream shorter than magic
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(content).unwrap();
+        let mut decoded = Vec::new();
+        open_decompressed_reader(std::io::Cursor::new(encoder.finish().unwrap())).unwrap().read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, content.to_vec());
+        let mut decoded = Vec::new();
+        open_decompressed_reader(std::io::Cursor::new(b">r".to_vec())).unwrap().read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, b">r".to_vec());
+        assert!(is_stdin(Path::new(STDIN_PATH)));
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_compressed_fastx
+
+
+    #[cfg(feature="io")]
+    #[test]
+    fn test_read_fastx_mapped() {
+        log_init_test();
+        let dir = crate::test_dir("fastx_mapped");
+        // multi line records with lower case bases and N : runs packed while parsing are those of records read in memory
+        let content = b">r1 first\nACGTacgtNNacgtACGTAC\nGGTTaa\n>r2\nttttGGGGnCCCCAAAA\n>r3\nNNN\n";
+        let path = dir.join("kmerutils_io_mapped_test.fa");
+        std::fs::write(&path, content).unwrap();
+        let mapped = read_fastx_mapped(&path, |seq, qual| { assert!(qual.is_none()); split_acgt_runs(seq, 4) }).unwrap();
+        let records = read_fastx_records(&path).unwrap();
+        assert_eq!(mapped.len(), records.len());
+        for ((id, runs), record) in mapped.iter().zip(&records) {
+            assert_eq!(id, &record.id);
+            let expected : Vec<Vec<u8>> = record.seq.to_ascii_uppercase().split(|b| !is_acgt(*b)).filter(|run| run.len() >= 4).map(|run| run.to_vec()).collect();
+            assert_eq!(runs.iter().map(|s| s.decompress()).collect::<Vec<Vec<u8>>>(), expected);
+        }
+        assert_eq!(mapped[0].0, "r1 first");
+        assert_eq!(mapped[0].1[1].decompress(), b"ACGTACGTACGGTTAA".to_vec());
+        assert!(mapped[2].1.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_read_fastx_mapped
+
+
+    #[test]
+    fn test_split_acgt_qual_runs() {
+        log_init_test();
+        // qualities : 'I' is 40, '#' is 2
+        let raw = b"ACGTACGTNACGTACGTACGT";
+        let qual = b"IIIIIIIIIIIII#IIIIIII";
+        let runs = split_acgt_qual_runs(raw, qual, 5, 20);
+        // the run of 4 bases between N and the low quality base is too short
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].0.size(), 8);
+        assert_eq!(runs[1].0.size(), 7);
+        let runs = split_acgt_qual_runs(raw, qual, 5, 0);
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[1].1.len(), 12);
+    } // end of test_split_acgt_qual_runs
+
+
+    #[test]
+    fn test_dump_reload_bin() {
+        log_init_test();
+        let dir = crate::test_dir("dump_reload_bin");
+        let path = dir.join("kmerutils_io_test.bin");
+        let params = crate::sketcharg::SeqSketcherParams::new(21, 100, crate::sketcharg::SketchAlgo::HLL, crate::sketcharg::DataType::DNA);
+        let signatures : Vec<Vec<u32>> = vec![vec![1, 2, 3], vec![4, 5, 6]];
+        let nb_bytes = dump_bin(&(params, signatures.clone()), &path).unwrap();
+        assert_eq!(nb_bytes as u64, std::fs::metadata(&path).unwrap().len());
+        let (reloaded, sigs) : (crate::sketcharg::SeqSketcherParams, Vec<Vec<u32>>) = reload_bin(&path).unwrap();
+        assert_eq!(reloaded.get_kmer_size(), 21);
+        assert_eq!(sigs, signatures);
+        assert!(reload_bin::<Vec<u64>>(&dir.join("kmerutils_no_such_file.bin")).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_dump_reload_bin
+
+} // end of mod tests
@@ -12,12 +12,15 @@ pub mod base;
 pub mod aautils;
 
 pub mod statutils;
+// parallel iteration, sequential without the feature parallel
+pub(crate) mod parallel;
 pub mod io;
 pub mod parsearg;
 pub mod sketcharg;
 pub mod groups;
 
 // quality stuff
+#[cfg(feature="io")]
 pub mod quality;
 
 // hashing stuff
@@ -31,7 +34,9 @@ pub mod sketching;
 
 // contig generation
 
+#[cfg(feature="io")]
 pub mod anchor;
+#[cfg(feature="io")]
 pub mod redisbase;
 
 
@@ -52,3 +57,14 @@ fn init_log() -> u64 {
     println!("\n ************** initializing logger from env *****************\n");    
     return 1;
 }
+
+
+// returns an empty directory for test name, unique to the running process
+// so that concurrent test runs do not share files
+#[cfg(test)]
+pub(crate) fn test_dir(name : &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("kmerutils_test_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -1,13 +1,21 @@
-//! This provides a struct implementing Hasher trait
-//! for u64 hashed values and doing nothing.  
-//! It was taken from the finch crate
-//! to use for example in counting structures when we
-//! manipulate already hashed values!
+//! This module provides hashers doing (almost) nothing, to use in hash maps and sketchers
+//! when keys are already hashed values, for example kmers hashed by an invertible hash in a fhash closure
+//! (see [invhash](probminhash::invhash)).
+//!
+//! - [NoHashHasher] was taken from the finch crate. It accepts u32 and u64 keys and is the hasher used by
+//!   the sketchers of this crate. Its output (native bytes read in big endian order) must not change as signatures depend on it.
+//! - [NoHashHasher32], [NoHashHasher64] and [NoHashHasher128] are identity hashers for keys of type u32, u64 and u128 :
+//!   the hash of a key is its value (for u128 the xor of its 2 halves). Writing any other type panics.
+//!
+//! Each hasher has a BuildHasher alias, so a map keyed by hashed values is declared as
+//! `HashMap::<u64, u32, BuildNoHashHasher64>::default()`.
 
 
-use std::hash::Hasher;
+use std::hash::{BuildHasherDefault, Hasher};
 
 
+/// a hasher for u32 or u64 hashed values. The native bytes of the value are read in big endian order,
+/// so the hash is the value with its bytes swapped on little endian machines.
 pub struct NoHashHasher(u64);
 
 impl Default for NoHashHasher {
@@ -28,7 +36,7 @@ impl Hasher for NoHashHasher {
                         ((bytes[2] as u64) << 8)  |
                         (bytes[3] as u64));
             },
-            
+
             8 => {
                 *self = NoHashHasher(
                     ((bytes[0] as u64) << 56) |
@@ -37,13 +45,111 @@ impl Hasher for NoHashHasher {
                         ((bytes[3] as u64) << 32) |
                         ((bytes[4] as u64) << 24) |
                         ((bytes[5] as u64) << 16) |
-                        ((bytes[6] as u64) << 8)  |                      
+                        ((bytes[6] as u64) << 8)  |
                         (bytes[7] as u64));
             },
-            
+
             _ => panic!("bad slice len in NoHashHasher write"),
         } // end match
     }
     //
     fn finish(&self) -> u64 { self.0 }
 }
+
+
+/// BuildHasher of [NoHashHasher]
+pub type BuildNoHashHasher = BuildHasherDefault<NoHashHasher>;
+
+
+
+// defines an identity hasher for keys of type $ty, $to_u64 converting the key to the hash value
+macro_rules! implement_identity_hasher(
+    ($name:ident, $ty:ty, $write:ident, $to_u64:expr) => (
+        impl Hasher for $name {
+            #[inline]
+            fn $write(&mut self, key : $ty) {
+                self.0 = $to_u64(key);
+            }
+            //
+            fn write(&mut self, bytes : &[u8]) {
+                panic!("{} accepts only {} keys, got {} bytes", stringify!($name), stringify!($ty), bytes.len());
+            }
+            //
+            #[inline]
+            fn finish(&self) -> u64 { self.0 }
+        }
+    ) // end of match
+); // end of macro implement_identity_hasher
+
+
+/// identity hasher for u32 keys
+#[derive(Copy,Clone,Debug,Default)]
+pub struct NoHashHasher32(u64);
+
+implement_identity_hasher!(NoHashHasher32, u32, write_u32, |key : u32| key as u64);
+
+/// BuildHasher of [NoHashHasher32]
+pub type BuildNoHashHasher32 = BuildHasherDefault<NoHashHasher32>;
+
+
+/// identity hasher for u64 keys
+#[derive(Copy,Clone,Debug,Default)]
+pub struct NoHashHasher64(u64);
+
+implement_identity_hasher!(NoHashHasher64, u64, write_u64, |key : u64| key);
+
+/// BuildHasher of [NoHashHasher64]
+pub type BuildNoHashHasher64 = BuildHasherDefault<NoHashHasher64>;
+
+
+/// identity hasher for u128 keys, the hash value being the xor of the 2 halves of the key
+#[derive(Copy,Clone,Debug,Default)]
+pub struct NoHashHasher128(u64);
+
+implement_identity_hasher!(NoHashHasher128, u128, write_u128, |key : u128| (key as u64) ^ ((key >> 64) as u64));
+
+/// BuildHasher of [NoHashHasher128]
+pub type BuildNoHashHasher128 = BuildHasherDefault<NoHashHasher128>;
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::hash::{BuildHasher, Hash};
+    use std::collections::HashMap;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    fn hash_with<B : BuildHasher, T : Hash>(build : &B, key : T) -> u64 {
+        let mut hasher = build.build_hasher();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_nohashers() {
+        log_init_test();
+        assert_eq!(hash_with(&BuildNoHashHasher32::default(), 0xdead_beefu32), 0xdead_beef);
+        assert_eq!(hash_with(&BuildNoHashHasher64::default(), u64::MAX - 3), u64::MAX - 3);
+        let key : u128 = (5u128 << 64) | 3;
+        assert_eq!(hash_with(&BuildNoHashHasher128::default(), key), 6);
+        // NoHashHasher keeps its big endian byte order
+        assert_eq!(hash_with(&BuildNoHashHasher::default(), 1u32), u32::from_ne_bytes(1u32.to_be_bytes()) as u64);
+        // maps keyed by hashed values
+        let mut map = HashMap::<u64, u32, BuildNoHashHasher64>::default();
+        for i in 0..1000u64 {
+            *map.entry(i.wrapping_mul(0x9E37_79B9_7F4A_7C15)).or_insert(0) += 1;
+        }
+        assert_eq!(map.len(), 1000);
+        let res = std::panic::catch_unwind(|| hash_with(&BuildNoHashHasher32::default(), 1u64));
+        assert!(res.is_err());
+    } // end of test_nohashers
+
+} // end of mod tests
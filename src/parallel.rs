@@ -0,0 +1,153 @@
+//! Parallel iteration used by sketchers, distances and searches.
+//!
+//! With the feature *parallel* (default) this is the rayon prelude. Without it the same method names
+//! (par_iter, into_par_iter ...) are provided on standard iterators, so the code runs sequentially
+//! in the calling thread without any change.
+
+#[cfg(feature="parallel")]
+pub use rayon::prelude::*;
+
+#[cfg(not(feature="parallel"))]
+pub use self::sequential::*;
+
+
+#[cfg(not(feature="parallel"))]
+mod sequential {
+
+    /// sequential version of rayon IntoParallelIterator
+    pub trait IntoParallelIterator : IntoIterator + Sized {
+        fn into_par_iter(self) -> Self::IntoIter {
+            self.into_iter()
+        }
+    }
+
+    impl <I : IntoIterator> IntoParallelIterator for I {}
+
+
+    /// sequential version of rayon IntoParallelRefIterator
+    pub trait IntoParallelRefIterator<'data> {
+        type Iter : Iterator;
+        fn par_iter(&'data self) -> Self::Iter;
+    }
+
+    impl <'data, I : 'data + ?Sized> IntoParallelRefIterator<'data> for I where &'data I : IntoIterator {
+        type Iter = <&'data I as IntoIterator>::IntoIter;
+        fn par_iter(&'data self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+
+    /// sequential version of rayon IntoParallelRefMutIterator
+    pub trait IntoParallelRefMutIterator<'data> {
+        type Iter : Iterator;
+        fn par_iter_mut(&'data mut self) -> Self::Iter;
+    }
+
+    impl <'data, I : 'data + ?Sized> IntoParallelRefMutIterator<'data> for I where &'data mut I : IntoIterator {
+        type Iter = <&'data mut I as IntoIterator>::IntoIter;
+        fn par_iter_mut(&'data mut self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+
+    /// rayon methods of ParallelIterator not present in Iterator
+    pub trait ParallelIterator : Iterator + Sized {
+        fn flat_map_iter<U, F>(self, f : F) -> std::iter::FlatMap<Self, U, F>
+                where U : IntoIterator, F : FnMut(Self::Item) -> U {
+            self.flat_map(f)
+        }
+    }
+
+    impl <I : Iterator> ParallelIterator for I {}
+
+
+    /// sequential version of rayon ParallelSlice
+    pub trait ParallelSlice<T> {
+        fn par_chunks(&self, chunk_size : usize) -> std::slice::Chunks<'_, T>;
+    }
+
+    impl <T> ParallelSlice<T> for [T] {
+        fn par_chunks(&self, chunk_size : usize) -> std::slice::Chunks<'_, T> {
+            self.chunks(chunk_size)
+        }
+    }
+
+
+    /// sequential version of rayon ParallelSliceMut
+    pub trait ParallelSliceMut<T> {
+        fn par_sort_unstable(&mut self) where T : Ord;
+        fn par_chunks_mut(&mut self, chunk_size : usize) -> std::slice::ChunksMut<'_, T>;
+    }
+
+    impl <T> ParallelSliceMut<T> for [T] {
+        fn par_sort_unstable(&mut self) where T : Ord {
+            self.sort_unstable()
+        }
+
+        fn par_chunks_mut(&mut self, chunk_size : usize) -> std::slice::ChunksMut<'_, T> {
+            self.chunks_mut(chunk_size)
+        }
+    }
+
+} // end of mod sequential
+
+
+
+#[cfg(feature="parallel")]
+pub(crate) use rayon::ThreadPool;
+
+
+/// stands for a rayon thread pool when the feature *parallel* is not set : closures run in the calling thread.
+// (used only by batch and archive sketching)
+#[cfg(not(feature="parallel"))]
+#[allow(dead_code)]
+pub(crate) struct ThreadPool;
+
+#[cfg(not(feature="parallel"))]
+#[allow(dead_code)]
+impl ThreadPool {
+
+    pub(crate) fn install<R, F : FnOnce() -> R>(&self, f : F) -> R {
+        f()
+    }
+
+    pub(crate) fn current_num_threads(&self) -> usize {
+        1
+    }
+
+} // end of impl ThreadPool
+
+
+
+/// number of threads of the current pool, 1 without the feature *parallel*
+pub(crate) fn current_num_threads() -> usize {
+    #[cfg(feature="parallel")]
+    {
+        rayon::current_num_threads()
+    }
+    #[cfg(not(feature="parallel"))]
+    {
+        1
+    }
+} // end of current_num_threads
+
+
+
+/// builds a pool of nb_threads threads (0 means rayon default). Without the feature *parallel* nb_threads is ignored.
+#[allow(dead_code)]
+pub(crate) fn build_thread_pool(nb_threads : usize) -> Result<ThreadPool, String> {
+    #[cfg(feature="parallel")]
+    {
+        rayon::ThreadPoolBuilder::new().num_threads(nb_threads).build().map_err(|e| {
+                log::error!("could not build thread pool : {}", e);
+                e.to_string()
+            })
+    }
+    #[cfg(not(feature="parallel"))]
+    {
+        log::debug!("build_thread_pool : sequential run, nb_threads {} ignored", nb_threads);
+        Ok(ThreadPool)
+    }
+} // end of build_thread_pool
@@ -192,7 +192,7 @@ pub fn load_quality_wm(filename: &String) ->  Result<Vec<QSequenceWM>, &'static
     let mut n_read = 0;
     let mut nb_bad_read = 0;    // reads with quality missing
 
-    let mut reader = needletail::parse_fastx_file(&path).expect("expecting valid filename");
+    let mut reader = crate::io::open_fastx_file(path).expect("expecting valid filename");
     while let Some(record) = reader.next() {
         n_read = n_read+1;
         //
@@ -1,18 +1,25 @@
 //! describe sketching paramaters, kmer size, sketching size and sketching algorithms.
 
 
+#[cfg(feature="formats")]
 use std::io::{BufReader, BufWriter };
 
+#[cfg(feature="formats")]
 use std::fs::OpenOptions;
-use std::path::{Path, PathBuf};
+use std::path::Path;
+#[cfg(feature="formats")]
+use std::path::PathBuf;
 
 
 use serde::{Deserialize, Serialize};
+#[cfg(feature="formats")]
 use serde_json::to_writer;
 
+use crate::base::kmertraits::CompressedKmerT;
+
 
 /// specify if we process DNA sequence or AA sequences
-#[derive(Copy,Clone,Serialize,Deserialize,Debug)]
+#[derive(Copy,Clone,Serialize,Deserialize,Debug,PartialEq,Eq)]
 pub enum DataType {
     DNA,
     AA,
@@ -20,12 +27,12 @@ pub enum DataType {
 
 /// Specify which algo we use for sketching :  Probminhash or SuperMinHash or Hyperloglog (SetSketch) algorithms.  
 /// - PROB3A is the value for asking ProbMinHashh3a, 
-/// - SUPER for first version SuperMinHash.(f64 signature)
+/// - SUPER for first version SuperMinHash.(f32 or f64 signature)
 /// - SUPER2 for second version of superminhash (u64 signature)
 /// - OPTDENS for optimal densification  (u43, u64 , f32 or f64 signature)
 /// - REVOPTDENS for optimal densification with small variance in case where there can many sequences smaller than size of sketching. (u43, u64 , f32 or f64 signature)
 /// - HLL for SetSketch based on hyperloglog (u16, u32 or more signature)
-#[derive(Copy,Clone,Serialize,Deserialize,Debug)]
+#[derive(Copy,Clone,Serialize,Deserialize,Debug,PartialEq,Eq)]
 pub enum SketchAlgo {
     PROB3A,
     SUPER,
@@ -34,24 +41,273 @@ pub enum SketchAlgo {
     REVOPTDENS,
     HLL,
 }
+
+impl SketchAlgo {
+    /// returns true for algorithms with float signatures (SUPER, OPTDENS, REVOPTDENS), see [FloatSig]
+    pub fn has_float_sig(&self) -> bool {
+        matches!(self, SketchAlgo::SUPER | SketchAlgo::OPTDENS | SketchAlgo::REVOPTDENS)
+    }
+}
+
+
+/// float type of the signatures of SuperMinHash, OptDens and RevOptDens sketchers when chosen at run time
+/// (files, batches and [make_dyn_sketcher](crate::sketching::dynsketch::make_dyn_sketcher)).
+/// f32 signatures take half the storage of f64 signatures. Other algorithms ignore it.
+#[derive(Copy,Clone,Serialize,Deserialize,Debug,Default,PartialEq,Eq)]
+pub enum FloatSig {
+    #[default]
+    F32,
+    F64,
+}
 // This is redundant with struct Sketcher for DNA case and RNA case, but it makes
 // possible the factorization of all parameters
 
 /// describe sketching paramaters, kmer size, sketching size and sketching algorithms.
 /// 
-#[derive(Copy,Clone,Serialize,Deserialize,Debug)]
+#[derive(Copy,Clone,Serialize,Deserialize,Debug,PartialEq,Eq)]
 pub struct SeqSketcherParams {
     kmer_size : usize,
     sketch_size : usize,
     algo : SketchAlgo, 
-    data_t : DataType
+    data_t : DataType,
+    /// kmers seen less than min_count times are not sketched (0 or 1 means no filtering)
+    #[serde(default)]
+    min_count : u32,
+    /// kmers seen more than max_count times are not sketched
+    #[serde(default="default_max_count")]
+    max_count : u32,
+    /// if true kmers seen only once are not sketched
+    #[serde(default)]
+    singleton_removal : bool,
+    /// kmers containing a base with Phred quality below min_quality are not sketched (0 means no filtering, fastq only)
+    #[serde(default)]
+    min_quality : u8,
+    /// if true ProbMinHash3a weights kmers by their probability of being error free, computed from base qualities (fastq only)
+    #[serde(default)]
+    quality_weighting : bool,
+    /// seed of the kmer hash function (unused by [KmerHash::Identity])
+    #[serde(default)]
+    seed : u64,
+    /// hash function applied to compressed kmer values before sketching
+    #[serde(default)]
+    hash : KmerHash,
+    /// if true DNA kmers are sketched in canonical form (min of kmer and its reverse complement)
+    #[serde(default="default_canonical")]
+    canonical : bool,
+    /// scale of FracMinHash sketching, None for bottom-k sketching. See [containment](crate::sketching::containment)
+    #[serde(default)]
+    scaled : Option<u64>,
+    /// float type of signatures for SUPER, OPTDENS and REVOPTDENS
+    #[serde(default)]
+    float_sig : FloatSig,
+}
+
+
+fn default_max_count() -> u32 {
+    u32::MAX
+}
+
+fn default_canonical() -> bool {
+    true
+}
+
+
+/// hash function applied to the compressed value of a kmer before sketching
+#[derive(Copy,Clone,Serialize,Deserialize,Debug,Default,PartialEq,Eq)]
+pub enum KmerHash {
+    /// the compressed kmer value itself (the sketchers hash it again with their own random generators)
+    #[default]
+    Identity,
+    /// xxh3 64 bits hash of the compressed value, with the seed of parameters, truncated to the kmer value bits
+    Xxh3,
+}
+
+impl KmerHash {
+    /// hashes a compressed kmer value, the result is masked by mask (the bits of the kmer value type used).
+    pub fn hash_value<V : num::PrimInt>(&self, value : V, seed : u64, mask : V) -> V {
+        match self {
+            KmerHash::Identity => value & mask,
+            KmerHash::Xxh3 => {
+                let h = xxhash_rust::xxh3::xxh3_64_with_seed(&value.to_u64().unwrap().to_le_bytes(), seed);
+                V::from(h & mask.to_u64().unwrap()).unwrap()
+            },
+        }
+    } // end of hash_value
+} // end of impl KmerHash
+
+
+
+/// errors detected by [SeqSketcherParamsBuilder::build]
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum SketchParamsError {
+    /// kmer size was not set or is 0
+    NullKmerSize,
+    /// kmer size is greater than the number of bases the kmer type can store
+    KmerSizeTooLarge{kmer_size : usize, max : usize},
+    /// sketch size was not set or is 0
+    NullSketchSize,
+    /// min_count greater than max_count in abundance filter
+    AbundanceBounds{min_count : u32, max_count : u32},
+    /// abundance filter asked with an algorithm that does not count kmers (all but PROB3A)
+    AbundanceAlgo(SketchAlgo),
+    /// FracMinHash scale must be > 0
+    NullScale,
+    /// amino acid kmers have no reverse complement
+    CanonicalAA,
+}
+
+impl std::fmt::Display for SketchParamsError {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SketchParamsError::NullKmerSize => write!(f, "kmer size must be > 0"),
+            SketchParamsError::KmerSizeTooLarge{kmer_size, max} => write!(f, "kmer size {} greater than kmer type capacity {}", kmer_size, max),
+            SketchParamsError::NullSketchSize => write!(f, "sketch size must be > 0"),
+            SketchParamsError::AbundanceBounds{min_count, max_count} => write!(f, "min_count {} greater than max_count {}", min_count, max_count),
+            SketchParamsError::AbundanceAlgo(algo) => write!(f, "abundance filter is applied only by PROB3A, not by {:?}", algo),
+            SketchParamsError::NullScale => write!(f, "scale must be > 0"),
+            SketchParamsError::CanonicalAA => write!(f, "canonical kmers are not defined for amino acids"),
+        }
+    }
+} // end of impl Display for SketchParamsError
+
+impl std::error::Error for SketchParamsError {}
+
+// so that builder errors propagate with ? in functions returning Result<_, String>
+impl From<SketchParamsError> for String {
+    fn from(e : SketchParamsError) -> String {
+        e.to_string()
+    }
+}
+
+
+
+/// a sketching parameter differing between two sets of signatures, detected by [SeqSketcherParams::check_compatible].
+/// Signatures obtained with different parameters cannot be compared : their Jaccard index would be meaningless.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum IncompatibleParams {
+    KmerSize{left : usize, right : usize},
+    SketchSize{left : usize, right : usize},
+    Algo{left : SketchAlgo, right : SketchAlgo},
+    DataType{left : DataType, right : DataType},
+    Hash{left : KmerHash, right : KmerHash},
+    Seed{left : u64, right : u64},
+    Canonical{left : bool, right : bool},
+    FloatSig{left : FloatSig, right : FloatSig},
+    Scaled{left : Option<u64>, right : Option<u64>},
+}
+
+impl std::fmt::Display for IncompatibleParams {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IncompatibleParams::KmerSize{left, right} => write!(f, "kmer sizes differ : {} and {}", left, right),
+            IncompatibleParams::SketchSize{left, right} => write!(f, "sketch sizes differ : {} and {}", left, right),
+            IncompatibleParams::Algo{left, right} => write!(f, "sketching algorithms differ : {:?} and {:?}", left, right),
+            IncompatibleParams::DataType{left, right} => write!(f, "data types differ : {:?} and {:?}", left, right),
+            IncompatibleParams::Hash{left, right} => write!(f, "kmer hash functions differ : {:?} and {:?}", left, right),
+            IncompatibleParams::Seed{left, right} => write!(f, "kmer hash seeds differ : {} and {}", left, right),
+            IncompatibleParams::Canonical{left, right} => write!(f, "canonical kmer modes differ : {} and {}", left, right),
+            IncompatibleParams::FloatSig{left, right} => write!(f, "float signature types differ : {:?} and {:?}", left, right),
+            IncompatibleParams::Scaled{left, right} => write!(f, "FracMinHash scales differ : {:?} and {:?}", left, right),
+        }
+    }
+} // end of impl Display for IncompatibleParams
+
+impl std::error::Error for IncompatibleParams {}
+
+impl From<IncompatibleParams> for String {
+    fn from(e : IncompatibleParams) -> String {
+        e.to_string()
+    }
 }
 
 
 impl SeqSketcherParams {
     /// 
     pub fn new(kmer_size: usize, sketch_size : usize, algo : SketchAlgo, data_t: DataType) -> Self {
-        SeqSketcherParams{kmer_size, sketch_size, algo, data_t}
+        SeqSketcherParams{kmer_size, sketch_size, algo, data_t, min_count : 0, max_count : default_max_count(), singleton_removal : false,
+                min_quality : 0, quality_weighting : false, seed : 0, hash : KmerHash::Identity, 
+                canonical : matches!(data_t, DataType::DNA), scaled : None, float_sig : FloatSig::default()}
+    }
+
+    /// returns a builder checking parameters, see [SeqSketcherParamsBuilder]
+    pub fn builder() -> SeqSketcherParamsBuilder {
+        SeqSketcherParamsBuilder::default()
+    }
+
+    /// returns a builder initialized with these parameters, so that parameters obtained with [Self::new]
+    /// or reloaded from a file can be checked by [SeqSketcherParamsBuilder::build]
+    pub fn to_builder(&self) -> SeqSketcherParamsBuilder {
+        let abundance = if self.min_count > 0 || self.max_count < u32::MAX { Some((self.min_count, self.max_count)) } else { None };
+        SeqSketcherParamsBuilder{kmer_size : self.kmer_size, sketch_size : self.sketch_size, algo : self.algo, data_t : self.data_t, abundance,
+                singleton_removal : self.singleton_removal, min_quality : self.min_quality, quality_weighting : self.quality_weighting,
+                seed : self.seed, hash : self.hash, canonical : Some(self.is_canonical()), scaled : self.scaled, float_sig : self.float_sig}
+    }
+
+    /// sets an abundance filter : kmers with a multiplicity outside [min_count, max_count] are excluded from sketching.  
+    /// This is used to get rid of sequencing errors (low counts) and extreme repeats (high counts).  
+    /// The filter is applied on the kmer weight map of the weighted sketchers (ProbHash3aSketch) as they already count kmers,
+    /// other sketchers ignore it ([SeqSketcherParamsBuilder::build] rejects it for them). See [Self::set_singleton_removal] for these sketchers.
+    pub fn set_abundance_filter(&mut self, min_count : u32, max_count : u32) {
+        assert!(min_count <= max_count, "min_count must be less than max_count");
+        self.min_count = min_count;
+        self.max_count = max_count;
+    }
+
+    /// returns min_count of abundance filter
+    pub fn get_min_count(&self) -> u32 {
+        self.min_count
+    }
+
+    /// returns max_count of abundance filter
+    pub fn get_max_count(&self) -> u32 {
+        self.max_count
+    }
+
+    /// asks for removal of kmers seen only once (sequencing errors in read sets).  
+    /// Weighted sketchers (ProbHash3aSketch) count kmers exactly and just discard kmers with count 1.
+    /// Other sketchers run, in *sketch_compressedkmer_seqs*, a first pass recording kmers in Bloom filters
+    /// and skip kmers never seen twice during sketching. See module [sketching::kmerfilter](crate::sketching::kmerfilter).
+    pub fn set_singleton_removal(&mut self, singleton_removal : bool) {
+        self.singleton_removal = singleton_removal;
+    }
+
+    /// returns true if singleton removal is asked
+    pub fn get_singleton_removal(&self) -> bool {
+        self.singleton_removal
+    }
+
+    /// sets a quality filter : when sketching fastq files, kmers containing a base with Phred quality
+    /// (quality byte - 33) below min_quality are skipped. 0 disables filtering.
+    pub fn set_quality_filter(&mut self, min_quality : u8) {
+        self.min_quality = min_quality;
+    }
+
+    /// returns the Phred quality threshold of the quality filter (0 if no filtering)
+    pub fn get_min_quality(&self) -> u8 {
+        self.min_quality
+    }
+
+    /// asks ProbMinHash3a to weight each kmer occurrence, when sketching fastq files, by the product over its bases
+    /// of (1 - error probability) instead of 1, so that low quality kmers have small weights.
+    pub fn set_quality_weighting(&mut self, quality_weighting : bool) {
+        self.quality_weighting = quality_weighting;
+    }
+
+    /// returns true if quality weighting is asked
+    pub fn get_quality_weighting(&self) -> bool {
+        self.quality_weighting
+    }
+
+    /// returns true if an abundance filter is set (singleton removal included)
+    pub fn has_abundance_filter(&self) -> bool {
+        self.min_count > 1 || self.max_count < u32::MAX || self.singleton_removal
+    }
+
+    /// returns true if a kmer count passes the abundance filter (singleton removal included)
+    #[inline]
+    pub fn is_count_accepted(&self, count : u64) -> bool {
+        let min_count = if self.singleton_removal { self.min_count.max(2) } else { self.min_count };
+        count >= min_count as u64 && count <= self.max_count as u64
     }
 
     /// returns kmer size
@@ -74,50 +330,452 @@ impl SeqSketcherParams {
         self.data_t
     }
 
-    /// serialized dump
+    /// returns seed of kmer hash function
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// returns hash function applied to kmers
+    pub fn get_hash(&self) -> KmerHash {
+        self.hash
+    }
+
+    /// returns true if DNA kmers are sketched in canonical form. Always false for AA.
+    pub fn is_canonical(&self) -> bool {
+        self.canonical && matches!(self.data_t, DataType::DNA)
+    }
+
+    /// returns FracMinHash scale if any
+    pub fn get_scaled(&self) -> Option<u64> {
+        self.scaled
+    }
+
+    /// sets the float type of signatures of SUPER, OPTDENS and REVOPTDENS
+    pub fn set_float_sig(&mut self, float_sig : FloatSig) {
+        self.float_sig = float_sig;
+    }
+
+    /// returns the float type of signatures of SUPER, OPTDENS and REVOPTDENS
+    pub fn get_float_sig(&self) -> FloatSig {
+        self.float_sig
+    }
+
+    /// checks that signatures sketched with self and other can be compared : kmer size, sketch size, algorithm, data type,
+    /// kmer hash (and its seed if the hash is not [KmerHash::Identity]), canonical mode and, for algorithms with float signatures,
+    /// the float type must be equal.
+    /// Filters (abundance, quality, singleton removal) only change the set sketched and are not checked.
+    pub fn check_compatible(&self, other : &SeqSketcherParams) -> Result<(), IncompatibleParams> {
+        let res = if self.kmer_size != other.kmer_size {
+            Err(IncompatibleParams::KmerSize{left : self.kmer_size, right : other.kmer_size})
+        }
+        else if self.sketch_size != other.sketch_size {
+            Err(IncompatibleParams::SketchSize{left : self.sketch_size, right : other.sketch_size})
+        }
+        else if self.algo != other.algo {
+            Err(IncompatibleParams::Algo{left : self.algo, right : other.algo})
+        }
+        else if self.data_t != other.data_t {
+            Err(IncompatibleParams::DataType{left : self.data_t, right : other.data_t})
+        }
+        else if self.hash != other.hash {
+            Err(IncompatibleParams::Hash{left : self.hash, right : other.hash})
+        }
+        else if self.hash != KmerHash::Identity && self.seed != other.seed {
+            Err(IncompatibleParams::Seed{left : self.seed, right : other.seed})
+        }
+        else if self.is_canonical() != other.is_canonical() {
+            Err(IncompatibleParams::Canonical{left : self.is_canonical(), right : other.is_canonical()})
+        }
+        else if self.algo.has_float_sig() && self.float_sig != other.float_sig {
+            Err(IncompatibleParams::FloatSig{left : self.float_sig, right : other.float_sig})
+        }
+        else if self.scaled != other.scaled {
+            Err(IncompatibleParams::Scaled{left : self.scaled, right : other.scaled})
+        }
+        else {
+            Ok(())
+        };
+        if let Err(e) = &res {
+            log::error!("check_compatible : {}", e);
+        }
+        res
+    } // end of check_compatible
+
+    /// serialized dump in json. If filename is a directory the dump goes in [DEFAULT_PARAMS_DUMP_NAME] in it.
+    /// To store parameters together with signatures see [sketchcontainer](crate::sketching::sketchcontainer)
+    #[cfg(feature="formats")]
     pub fn dump_json(&self, filename : &String) -> Result<(), String> {
-        //
-        let filepath = PathBuf::from(filename.clone());
-        //
-        log::info!("dumping sketching parameters in json file : {}", filename);
-        //
-        let fileres = OpenOptions::new().write(true).create(true).truncate(true).open(&filepath);
+        self.dump_params(Path::new(filename))
+    } // end of dump
+
+
+    /// reload from a json dump. path is the dump file or the directory containing [DEFAULT_PARAMS_DUMP_NAME].
+    #[cfg(feature="formats")]
+    pub fn reload_json(path : &Path) -> Result<SeqSketcherParams, String> {
+        let sketch_params = Self::reload_params(path)?;
+        log::info!("SeqSketcher reload, kmer_size : {}, sketch_size : {}", 
+            sketch_params.get_kmer_size(), sketch_params.get_sketch_size());     
+        Ok(sketch_params)
+    } // end of reload_json
+
+
+    /// dumps parameters in binary format (bincode), see [dump_bin](crate::io::dump_bin)
+    pub fn dump_bin(&self, filepath : &Path) -> Result<usize, String> {
+        log::info!("dumping sketching parameters in binary file : {:?}", filepath);
+        crate::io::dump_bin(self, filepath)
+    }
+
+    /// reloads parameters dumped by [Self::dump_bin]
+    pub fn reload_bin(filepath : &Path) -> Result<SeqSketcherParams, String> {
+        let sketch_params : SeqSketcherParams = crate::io::reload_bin(filepath)?;
+        log::info!("SeqSketcher reload_bin, kmer_size : {}, sketch_size : {}", sketch_params.get_kmer_size(), sketch_params.get_sketch_size());
+        Ok(sketch_params)
+    }
+
+}  // end of SeqSketcherParams
+
+
+/// Builder of [SeqSketcherParams] checking consistency of parameters.
+/// 
+/// Kmer size and sketch size must be set, others fields have defaults : algorithm PROB3A, DNA data, no filtering, 
+/// seed 0, [KmerHash::Identity], canonical kmers for DNA, bottom-k sketching (no scale), f32 float signatures.
+/// ```
+/// use kmerutils::sketcharg::*;
+/// use kmerutils::base::kmer::Kmer64bit;
+/// let params = SeqSketcherParams::builder().kmer_size(21).sketch_size(1000).algo(SketchAlgo::OPTDENS).build::<Kmer64bit>().unwrap();
+/// assert!(params.is_canonical());
+/// assert!(SeqSketcherParams::builder().kmer_size(40).sketch_size(1000).build::<Kmer64bit>().is_err());
+/// ```
+#[derive(Clone,Debug)]
+pub struct SeqSketcherParamsBuilder {
+    kmer_size : usize,
+    sketch_size : usize,
+    algo : SketchAlgo,
+    data_t : DataType,
+    abundance : Option<(u32, u32)>,
+    singleton_removal : bool,
+    min_quality : u8,
+    quality_weighting : bool,
+    seed : u64,
+    hash : KmerHash,
+    // None means default according to data type
+    canonical : Option<bool>,
+    scaled : Option<u64>,
+    float_sig : FloatSig,
+}
+
+
+impl Default for SeqSketcherParamsBuilder {
+    fn default() -> Self {
+        SeqSketcherParamsBuilder{kmer_size : 0, sketch_size : 0, algo : SketchAlgo::PROB3A, data_t : DataType::DNA, abundance : None,
+                singleton_removal : false, min_quality : 0, quality_weighting : false, seed : 0, hash : KmerHash::Identity, canonical : None, scaled : None,
+                float_sig : FloatSig::default()}
+    }
+}
+
+
+impl SeqSketcherParamsBuilder {
+    /// kmer size, required
+    pub fn kmer_size(mut self, kmer_size : usize) -> Self {
+        self.kmer_size = kmer_size;
+        self
+    }
+
+    /// sketch size, required
+    pub fn sketch_size(mut self, sketch_size : usize) -> Self {
+        self.sketch_size = sketch_size;
+        self
+    }
+
+    /// sketching algorithm, default PROB3A
+    pub fn algo(mut self, algo : SketchAlgo) -> Self {
+        self.algo = algo;
+        self
+    }
+
+    /// DNA or AA, default DNA
+    pub fn data_t(mut self, data_t : DataType) -> Self {
+        self.data_t = data_t;
+        self
+    }
+
+    /// abundance filter, only applied by PROB3A, see [SeqSketcherParams::set_abundance_filter]
+    pub fn abundance_filter(mut self, min_count : u32, max_count : u32) -> Self {
+        self.abundance = Some((min_count, max_count));
+        self
+    }
+
+    /// see [SeqSketcherParams::set_singleton_removal]
+    pub fn singleton_removal(mut self, singleton_removal : bool) -> Self {
+        self.singleton_removal = singleton_removal;
+        self
+    }
+
+    /// see [SeqSketcherParams::set_quality_filter]
+    pub fn quality_filter(mut self, min_quality : u8) -> Self {
+        self.min_quality = min_quality;
+        self
+    }
+
+    /// see [SeqSketcherParams::set_quality_weighting]
+    pub fn quality_weighting(mut self, quality_weighting : bool) -> Self {
+        self.quality_weighting = quality_weighting;
+        self
+    }
+
+    /// seed of kmer hash function, default 0
+    pub fn seed(mut self, seed : u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// kmer hash function, default [KmerHash::Identity]
+    pub fn hash(mut self, hash : KmerHash) -> Self {
+        self.hash = hash;
+        self
+    }
+
+    /// canonical kmers, default true for DNA and false for AA
+    pub fn canonical(mut self, canonical : bool) -> Self {
+        self.canonical = Some(canonical);
+        self
+    }
+
+    /// FracMinHash scale, default None (bottom-k sketching)
+    pub fn scaled(mut self, scale : u64) -> Self {
+        self.scaled = Some(scale);
+        self
+    }
+
+    /// float type of signatures of SUPER, OPTDENS and REVOPTDENS, default [FloatSig::F32]
+    pub fn float_sig(mut self, float_sig : FloatSig) -> Self {
+        self.float_sig = float_sig;
+        self
+    }
+
+    /// checks parameters for kmer type Kmer and returns them
+    pub fn build<Kmer : CompressedKmerT>(&self) -> Result<SeqSketcherParams, SketchParamsError> {
+        self.build_with_max_kmer_size(Kmer::get_nb_base_max())
+    }
+
+    /// checks parameters for a kmer type storing at most max_kmer_size bases and returns them
+    pub fn build_with_max_kmer_size(&self, max_kmer_size : usize) -> Result<SeqSketcherParams, SketchParamsError> {
+        let res = self.check(max_kmer_size);
+        if let Err(e) = &res {
+            log::error!("SeqSketcherParamsBuilder::build : {}", e);
+        }
+        res
+    } // end of build_with_max_kmer_size
+
+
+    fn check(&self, max_kmer_size : usize) -> Result<SeqSketcherParams, SketchParamsError> {
+        if self.kmer_size == 0 {
+            return Err(SketchParamsError::NullKmerSize);
+        }
+        if self.kmer_size > max_kmer_size {
+            return Err(SketchParamsError::KmerSizeTooLarge{kmer_size : self.kmer_size, max : max_kmer_size});
+        }
+        if self.sketch_size == 0 {
+            return Err(SketchParamsError::NullSketchSize);
+        }
+        let (min_count, max_count) = self.abundance.unwrap_or((0, default_max_count()));
+        if min_count > max_count {
+            return Err(SketchParamsError::AbundanceBounds{min_count, max_count});
+        }
+        if (min_count > 1 || max_count < u32::MAX) && self.algo != SketchAlgo::PROB3A {
+            return Err(SketchParamsError::AbundanceAlgo(self.algo));
+        }
+        if self.scaled == Some(0) {
+            return Err(SketchParamsError::NullScale);
+        }
+        let canonical = match (self.canonical, self.data_t) {
+            (Some(true), DataType::AA) => return Err(SketchParamsError::CanonicalAA),
+            (Some(c), _) => c,
+            (None, data_t) => matches!(data_t, DataType::DNA),
+        };
+        Ok(SeqSketcherParams{kmer_size : self.kmer_size, sketch_size : self.sketch_size, algo : self.algo, data_t : self.data_t, 
+                min_count, max_count, singleton_removal : self.singleton_removal, min_quality : self.min_quality, 
+                quality_weighting : self.quality_weighting, seed : self.seed, hash : self.hash, canonical, scaled : self.scaled,
+                float_sig : self.float_sig})
+    } // end of check
+
+} // end of impl SeqSketcherParamsBuilder
+
+
+
+/// default name of a parameter dump when a directory is given to [ParamsDump] methods
+#[cfg(feature="formats")]
+pub const DEFAULT_PARAMS_DUMP_NAME : &str = "sketchparams_dump.json";
+
+
+// a directory path is completed with the default dump name
+#[cfg(feature="formats")]
+fn get_params_dump_path(path : &Path) -> PathBuf {
+    if path.is_dir() {
+        path.join(DEFAULT_PARAMS_DUMP_NAME)
+    }
+    else {
+        path.to_path_buf()
+    }
+}
+
+
+/// json persistence of sketching parameters, shared by [SeqSketcherParams] and the DNA and AA SeqSketcher.  
+/// The path given can be a file or a directory, in which case the file [DEFAULT_PARAMS_DUMP_NAME] in it is used,
+/// so dump and reload are symmetric.
+#[cfg(feature="formats")]
+pub trait ParamsDump : Serialize + for<'de> Deserialize<'de> + Sized {
+
+    /// dumps in json. The dump is atomic : it is written in a temporary file renamed at the end,
+    /// so that a crash never leaves a truncated dump.
+    fn dump_params(&self, path : &Path) -> Result<(), String> {
+        let filepath = get_params_dump_path(path);
+        log::info!("dumping sketching parameters in json file : {:?}", filepath);
+        let mut tmpname = filepath.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+        tmpname.push(format!(".tmp{}", std::process::id()));
+        let tmppath = filepath.with_file_name(tmpname);
+        let fileres = OpenOptions::new().write(true).create(true).truncate(true).open(&tmppath);
         if fileres.is_err() {
-            log::error!("SeqSketcher dump : dump could not open file {:?}", filepath.as_os_str());
-            println!("SeqSketcher dump: could not open file {:?}", filepath.as_os_str());
+            log::error!("SeqSketcher dump : dump could not open file {:?}", tmppath.as_os_str());
+            println!("SeqSketcher dump: could not open file {:?}", tmppath.as_os_str());
             return Err("SeqSketcher dump failed".to_string());
         }
-        // 
         let mut writer = BufWriter::new(fileres.unwrap());
-        let _ = to_writer(&mut writer, &self).unwrap();
-        //
+        let res = to_writer(&mut writer, &self).map_err(|e| e.to_string())
+                .and_then(|_| writer.into_inner().map_err(|e| e.to_string()))
+                .and_then(|file| file.sync_all().map_err(|e| e.to_string()))
+                .and_then(|_| std::fs::rename(&tmppath, &filepath).map_err(|e| e.to_string()));
+        if let Err(e) = res {
+            log::error!("SeqSketcher dump in {:?} failed : {}", filepath.as_os_str(), e);
+            let _ = std::fs::remove_file(&tmppath);
+            return Err(format!("SeqSketcher dump failed : {}", e));
+        }
         Ok(())
-    } // end of dump
-
+    } // end of dump_params
 
-    /// reload from a json dump
-    pub fn reload_json(dirpath : &Path) -> Result<SeqSketcherParams, String> {
-        log::info!("in reload_json");
-        //
-        let filepath = dirpath.join("sketchparams_dump.json");
+    /// reloads a dump written by [Self::dump_params]
+    fn reload_params(path : &Path) -> Result<Self, String> {
+        let filepath = get_params_dump_path(path);
+        log::info!("reloading sketching parameters from {:?}", filepath);
         let fileres = OpenOptions::new().read(true).open(&filepath);
         if fileres.is_err() {
             log::error!("Sketcher reload_json : reload could not open file {:?}", filepath.as_os_str());
             println!("Sketcher reload_json: could not open file {:?}", filepath.as_os_str());
             return Err("Sketcher reload_json could not open file".to_string());            
         }
+        let reader = BufReader::new(fileres.unwrap());
+        serde_json::from_reader(reader).map_err(|e| {
+            log::error!("Sketcher reload_json : could not decode {:?} : {}", filepath.as_os_str(), e);
+            format!("Sketcher reload_json could not decode file : {}", e)
+        })
+    } // end of reload_params
+
+} // end of trait ParamsDump
+
+
+#[cfg(feature="formats")]
+impl ParamsDump for SeqSketcherParams {}
+
+
+//==========================================================================================
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[cfg(feature="formats")]
+    #[test]
+    fn test_params_dump_reload() {
+        log_init_test();
+        let dir = crate::test_dir("params");
+        let params = SeqSketcherParams::new(17, 300, SketchAlgo::OPTDENS, DataType::DNA);
+        // in a file
+        let file = dir.join("kmerutils_test_params.json");
+        params.dump_params(&file).unwrap();
+        assert_eq!(SeqSketcherParams::reload_params(&file).unwrap().get_kmer_size(), 17);
+        let _ = std::fs::remove_file(&file);
+        // in a directory, with default name
+        params.dump_json(&dir.to_string_lossy().to_string()).unwrap();
+        assert!(dir.join(DEFAULT_PARAMS_DUMP_NAME).exists());
+        assert_eq!(SeqSketcherParams::reload_json(&dir).unwrap().get_sketch_size(), 300);
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_params_dump_reload
+
+
+    #[test]
+    fn test_params_builder() {
+        log_init_test();
+        use crate::base::kmer::Kmer32bit;
+        let params = SeqSketcherParams::builder().kmer_size(14).sketch_size(200).abundance_filter(2, 100)
+                .seed(17).hash(KmerHash::Xxh3).build::<Kmer32bit>().unwrap();
+        assert_eq!((params.get_kmer_size(), params.get_min_count(), params.get_seed()), (14, 2, 17));
+        assert!(params.is_canonical() && params.get_scaled().is_none());
         //
-        let loadfile = fileres.unwrap();
-        let reader = BufReader::new(loadfile);
-        let sketch_params:SeqSketcherParams = serde_json::from_reader(reader).unwrap();
-        //
-        log::info!("SeqSketcher reload, kmer_size : {}, sketch_size : {}", 
-            sketch_params.get_kmer_size(), sketch_params.get_sketch_size());     
-        //
-        Ok(sketch_params)
-    } // end of reload_json
+        assert_eq!(SeqSketcherParams::builder().sketch_size(200).build::<Kmer32bit>().unwrap_err(), SketchParamsError::NullKmerSize);
+        assert_eq!(SeqSketcherParams::builder().kmer_size(15).sketch_size(200).build::<Kmer32bit>().unwrap_err(), 
+                SketchParamsError::KmerSizeTooLarge{kmer_size : 15, max : 14});
+        assert_eq!(SeqSketcherParams::builder().kmer_size(12).build::<Kmer32bit>().unwrap_err(), SketchParamsError::NullSketchSize);
+        assert!(SeqSketcherParams::builder().kmer_size(12).sketch_size(10).abundance_filter(5, 2).build::<Kmer32bit>().is_err());
+        // only ProbHash3a counts kmers, other algorithms accept singleton removal only
+        let superhash = SeqSketcherParams::builder().kmer_size(12).sketch_size(10).algo(SketchAlgo::SUPER);
+        assert_eq!(superhash.clone().abundance_filter(2, 100).build::<Kmer32bit>().unwrap_err(), SketchParamsError::AbundanceAlgo(SketchAlgo::SUPER));
+        assert!(superhash.singleton_removal(true).build::<Kmer32bit>().is_ok());
+        assert_eq!(SeqSketcherParams::builder().kmer_size(12).sketch_size(10).scaled(0).build::<Kmer32bit>().unwrap_err(), SketchParamsError::NullScale);
+        let scaled = SeqSketcherParams::builder().kmer_size(12).sketch_size(10).scaled(1000).build::<Kmer32bit>().unwrap();
+        assert_eq!(scaled.get_scaled(), Some(1000));
+        assert_eq!(scaled.to_builder().build::<Kmer32bit>().unwrap().get_scaled(), Some(1000));
+        // AA kmers are not canonical
+        let aa = SeqSketcherParams::builder().kmer_size(5).sketch_size(10).data_t(DataType::AA);
+        assert!(!aa.build_with_max_kmer_size(6).unwrap().is_canonical());
+        assert_eq!(aa.canonical(true).build_with_max_kmer_size(6).unwrap_err(), SketchParamsError::CanonicalAA);
+        // hash
+        assert_eq!(KmerHash::Identity.hash_value(0xFFu32, 0, 0x0F), 0x0F);
+        let h = KmerHash::Xxh3.hash_value(0xFFu32, 1, 0xFFFF);
+        assert!(h <= 0xFFFF && h != KmerHash::Xxh3.hash_value(0xFFu32, 2, 0xFFFF));
+    } // end of test_params_builder
 
-}  // end of SeqSketcherParams
+    #[test]
+    fn test_check_compatible() {
+        log_init_test();
+        let params = SeqSketcherParams::new(12, 100, SketchAlgo::PROB3A, DataType::DNA);
+        // filters do not matter
+        let mut filtered = params;
+        filtered.set_abundance_filter(2, 100);
+        assert!(params.check_compatible(&filtered).is_ok());
+        assert_eq!(params.check_compatible(&SeqSketcherParams::new(13, 100, SketchAlgo::PROB3A, DataType::DNA)),
+                Err(IncompatibleParams::KmerSize{left : 12, right : 13}));
+        assert_eq!(params.check_compatible(&SeqSketcherParams::new(12, 200, SketchAlgo::PROB3A, DataType::DNA)),
+                Err(IncompatibleParams::SketchSize{left : 100, right : 200}));
+        assert_eq!(params.check_compatible(&SeqSketcherParams::new(12, 100, SketchAlgo::SUPER, DataType::DNA)),
+                Err(IncompatibleParams::Algo{left : SketchAlgo::PROB3A, right : SketchAlgo::SUPER}));
+        assert!(params.check_compatible(&SeqSketcherParams::new(12, 100, SketchAlgo::PROB3A, DataType::AA)).is_err());
+        // the seed matters only with a seeded hash
+        let builder = SeqSketcherParams::builder().kmer_size(12).sketch_size(100);
+        let seeded = builder.clone().seed(1).build_with_max_kmer_size(14).unwrap();
+        assert!(params.check_compatible(&seeded).is_ok());
+        let xxh3 = builder.clone().hash(KmerHash::Xxh3).seed(1).build_with_max_kmer_size(14).unwrap();
+        assert_eq!(params.check_compatible(&xxh3), Err(IncompatibleParams::Hash{left : KmerHash::Identity, right : KmerHash::Xxh3}));
+        let xxh3_other = builder.clone().hash(KmerHash::Xxh3).seed(2).build_with_max_kmer_size(14).unwrap();
+        assert_eq!(xxh3.check_compatible(&xxh3_other), Err(IncompatibleParams::Seed{left : 1, right : 2}));
+        let scaled = builder.clone().scaled(1000).build_with_max_kmer_size(14).unwrap();
+        assert_eq!(params.check_compatible(&scaled), Err(IncompatibleParams::Scaled{left : None, right : Some(1000)}));
+        let not_canonical = builder.canonical(false).build_with_max_kmer_size(14).unwrap();
+        let err : String = params.check_compatible(&not_canonical).unwrap_err().into();
+        assert!(err.contains("canonical"));
+        // float type matters only for float signatures
+        let mut f64_params = params;
+        f64_params.set_float_sig(FloatSig::F64);
+        assert!(params.check_compatible(&f64_params).is_ok());
+        let superhash = SeqSketcherParams::builder().kmer_size(12).sketch_size(100).algo(SketchAlgo::SUPER);
+        assert_eq!(superhash.clone().build_with_max_kmer_size(14).unwrap().check_compatible(&superhash.float_sig(FloatSig::F64).build_with_max_kmer_size(14).unwrap()),
+                Err(IncompatibleParams::FloatSig{left : FloatSig::F32, right : FloatSig::F64}));
+    } // end of test_check_compatible
 
 
-//==========================================================================================
\ No newline at end of file
+} // end of mod tests
@@ -0,0 +1,308 @@
+//! This module sketches genome collections packed in an archive, one fasta/fastq file by member, without extraction to disk.
+//!
+//! Archives can be tar files, possibly compressed (.tar.gz, .tgz, .tar.bz2, .tar.xz, .tar.zst), or zip files.
+//! The format is recognized from content. Members are themselves possibly compressed (gzip, bzip2, xz, zstd).
+//! Members whose name does not have a fasta/fastq extension (see [is_fastx_file_name]) are ignored.
+//!
+//! Members are read sequentially and sketched in parallel by batches, so that at most a batch of members
+//! (twice the number of threads) is held in memory.
+//! A tar archive can be read from standard input ([STDIN_PATH](crate::io::STDIN_PATH)), a zip archive cannot as it needs seeking.
+
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::parallel::*;
+
+use crate::io::{is_stdin, open_decompressed, read_fastx_records_from};
+use crate::sketcharg::SeqSketcherParams;
+
+use super::batchsketch::is_fastx_file_name;
+use super::filesketch::{sketch_fastx_records, FileSignatures, FileSketchMode};
+
+
+// zip local file header signature
+const ZIP_MAGIC : [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+
+
+/// format of a genome archive
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum ArchiveFormat {
+    /// tar file, possibly compressed
+    Tar,
+    /// zip file
+    Zip,
+}
+
+
+/// signatures of the members of an archive
+#[derive(Clone,Debug,Default)]
+pub struct ArchiveSketch {
+    /// member names (path in archive) and their signatures, in archive order
+    pub sketches : Vec<(String, FileSignatures)>,
+    /// members that could not be read or sketched
+    pub failed : Vec<String>,
+}
+
+
+/// returns the format of an archive, zip files being recognized by their first bytes. Standard input is a tar stream.
+pub fn get_archive_format(path : &Path) -> Result<ArchiveFormat, String> {
+    if is_stdin(path) {
+        return Ok(ArchiveFormat::Tar);
+    }
+    let mut file = std::fs::File::open(path).map_err(|e| {
+            log::error!("get_archive_format could not open file {:?} : {}", path, e);
+            format!("could not open file {:?} : {}", path, e)
+        })?;
+    let mut magic = [0u8; 4];
+    let format = match file.read_exact(&mut magic) {
+        Ok(_) if magic == ZIP_MAGIC => ArchiveFormat::Zip,
+        _ => ArchiveFormat::Tar,
+    };
+    Ok(format)
+} // end of get_archive_format
+
+
+
+// sketches a batch of members in the pool and appends results
+fn sketch_batch(pool : &ThreadPool, batch : &mut Vec<(String, Vec<u8>)>, params : &SeqSketcherParams, mode : &FileSketchMode, result : &mut ArchiveSketch) {
+    let members = std::mem::take(batch);
+    let sketches : Vec<(String, Result<FileSignatures, String>)> = pool.install(|| {
+            members.into_par_iter().map(|(name, data)| {
+                    let res = read_fastx_records_from(std::io::Cursor::new(data))
+                            .and_then(|records| sketch_fastx_records(Path::new(&name), records, params, mode.clone()));
+                    (name, res)
+                }).collect()
+        });
+    for (name, res) in sketches {
+        match res {
+            Ok(signatures) => result.sketches.push((name, signatures)),
+            Err(e) => {
+                log::error!("sketch_archive : could not sketch member {} : {}", name, e);
+                result.failed.push(name);
+            },
+        }
+    }
+} // end of sketch_batch
+
+
+
+// capacity to reserve for a member declaring size bytes. The size comes from the archive header and is not trusted :
+// it is capped by the archive file length, the buffer grows if the (decompressed) member is larger.
+fn member_capacity(size : u64, archive_len : u64) -> usize {
+    size.min(archive_len).try_into().unwrap_or(0)
+}
+
+
+
+// reads the members of a tar archive and sketches them by batches
+fn sketch_tar(path : &Path, params : &SeqSketcherParams, mode : &FileSketchMode, pool : &ThreadPool) -> Result<ArchiveSketch, String> {
+    let reader = open_decompressed(path).map_err(|e| {
+            log::error!("sketch_archive could not open file {:?} : {}", path, e);
+            format!("could not open file {:?} : {}", path, e)
+        })?;
+    let archive_len = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let mut archive = tar::Archive::new(reader);
+    let entries = archive.entries().map_err(|e| format!("invalid tar archive {:?} : {}", path, e))?;
+    let batch_size = 2 * pool.current_num_threads();
+    let mut batch = Vec::<(String, Vec<u8>)>::with_capacity(batch_size);
+    let mut result = ArchiveSketch::default();
+    for entry in entries {
+        let mut entry = entry.map_err(|e| {
+                log::error!("sketch_archive : invalid tar entry in {:?} : {}", path, e);
+                format!("invalid tar entry in {:?} : {}", path, e)
+            })?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = match entry.path() {
+            Ok(name) => name.to_string_lossy().to_string(),
+            Err(_) => continue,
+        };
+        if !is_fastx_file_name(Path::new(&name)) {
+            log::debug!("sketch_archive : skipping member {}", name);
+            continue;
+        }
+        let mut data = Vec::<u8>::with_capacity(member_capacity(entry.size(), archive_len));
+        if let Err(e) = entry.read_to_end(&mut data) {
+            log::error!("sketch_archive : could not read member {} : {}", name, e);
+            result.failed.push(name);
+            continue;
+        }
+        batch.push((name, data));
+        if batch.len() >= batch_size {
+            sketch_batch(pool, &mut batch, params, mode, &mut result);
+        }
+    }
+    sketch_batch(pool, &mut batch, params, mode, &mut result);
+    Ok(result)
+} // end of sketch_tar
+
+
+
+// reads the members of a zip archive and sketches them by batches
+fn sketch_zip(path : &Path, params : &SeqSketcherParams, mode : &FileSketchMode, pool : &ThreadPool) -> Result<ArchiveSketch, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("could not open file {:?} : {}", path, e))?;
+    let archive_len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let mut archive = zip::ZipArchive::new(std::io::BufReader::new(file)).map_err(|e| {
+            log::error!("sketch_archive : invalid zip archive {:?} : {}", path, e);
+            format!("invalid zip archive {:?} : {}", path, e)
+        })?;
+    let batch_size = 2 * pool.current_num_threads();
+    let mut batch = Vec::<(String, Vec<u8>)>::with_capacity(batch_size);
+    let mut result = ArchiveSketch::default();
+    for i in 0..archive.len() {
+        let mut member = match archive.by_index(i) {
+            Ok(member) => member,
+            Err(e) => {
+                log::error!("sketch_archive : could not read member {} of {:?} : {}", i, path, e);
+                result.failed.push(format!("#{}", i));
+                continue;
+            },
+        };
+        let name = member.name().to_string();
+        if !member.is_file() || !is_fastx_file_name(Path::new(&name)) {
+            log::debug!("sketch_archive : skipping member {}", name);
+            continue;
+        }
+        let mut data = Vec::<u8>::with_capacity(member_capacity(member.size(), archive_len));
+        if let Err(e) = member.read_to_end(&mut data) {
+            log::error!("sketch_archive : could not read member {} : {}", name, e);
+            result.failed.push(name);
+            continue;
+        }
+        batch.push((name, data));
+        if batch.len() >= batch_size {
+            sketch_batch(pool, &mut batch, params, mode, &mut result);
+        }
+    }
+    sketch_batch(pool, &mut batch, params, mode, &mut result);
+    Ok(result)
+} // end of sketch_zip
+
+
+
+/// sketches each fasta/fastq member of a tar or zip archive with at most nb_threads threads (0 means rayon default).
+/// Signatures of a member are named as in [sketch_fasta_file](super::filesketch::sketch_fasta_file), the member path replacing the file path.
+/// Members that cannot be sketched are reported in [ArchiveSketch::failed] and do not stop the sketching.
+pub fn sketch_archive(path : &Path, params : &SeqSketcherParams, mode : FileSketchMode, nb_threads : usize) -> Result<ArchiveSketch, String> {
+    //
+    let format = get_archive_format(path)?;
+    log::info!("sketch_archive {:?}, format : {:?}, nb threads : {}, params : {:?}", path, format, nb_threads, params);
+    //
+    let pool = build_thread_pool(nb_threads)?;
+    let result = match format {
+        ArchiveFormat::Tar => sketch_tar(path, params, &mode, &pool)?,
+        ArchiveFormat::Zip => sketch_zip(path, params, &mode, &pool)?,
+    };
+    log::info!("sketch_archive, nb members sketched : {}, nb failures : {}", result.sketches.len(), result.failed.len());
+    Ok(result)
+} // end of sketch_archive
+
+
+/// returns paths of the fasta/fastq members of an archive, in archive order, without reading their content.
+pub fn list_archive_members(path : &Path) -> Result<Vec<PathBuf>, String> {
+    let mut members = Vec::<PathBuf>::new();
+    match get_archive_format(path)? {
+        ArchiveFormat::Tar => {
+            let reader = open_decompressed(path).map_err(|e| format!("could not open file {:?} : {}", path, e))?;
+            let mut archive = tar::Archive::new(reader);
+            for entry in archive.entries().map_err(|e| e.to_string())? {
+                let entry = entry.map_err(|e| e.to_string())?;
+                if let Ok(name) = entry.path() {
+                    if entry.header().entry_type().is_file() && is_fastx_file_name(&name) {
+                        members.push(name.to_path_buf());
+                    }
+                }
+            }
+        },
+        ArchiveFormat::Zip => {
+            let file = std::fs::File::open(path).map_err(|e| format!("could not open file {:?} : {}", path, e))?;
+            let archive = zip::ZipArchive::new(std::io::BufReader::new(file)).map_err(|e| e.to_string())?;
+            members = (0..archive.len()).filter_map(|i| archive.name_for_index(i)).map(PathBuf::from).filter(|name| is_fastx_file_name(name)).collect();
+        },
+    }
+    Ok(members)
+} // end of list_archive_members
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::io::Write;
+    use crate::sketcharg::{SketchAlgo, DataType};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    const GENOME_A : &[u8] = b">r1\nACGTTGCAAGGCTTACCGATTGACCAGT\n>r2\nGGGCATTACCAGGTTACATTGACC\n";
+    const GENOME_B : &[u8] = b">r1\nTTGACCAGTACGTTGCAAGGCTTACCGA\n";
+
+    fn gzip(data : &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn get_signature(sketch : &FileSignatures) -> Vec<u64> {
+        match sketch {
+            FileSignatures::Int(s) => s.signatures[0].clone(),
+            _ => panic!("expected integer signature"),
+        }
+    }
+
+    #[test]
+    fn test_sketch_archive() {
+        log_init_test();
+        let dir = crate::test_dir("archive");
+        let params = SeqSketcherParams::new(11, 50, SketchAlgo::PROB3A, DataType::DNA);
+        let members : Vec<(&str, Vec<u8>)> = vec![("genomes/a.fa", GENOME_A.to_vec()), ("README", b"not a genome".to_vec()),
+                    ("genomes/b.fna.gz", gzip(GENOME_B)), ("genomes/short.fa", b">r1\nACG\n".to_vec())];
+        // tar.gz
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in &members {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, data.as_slice()).unwrap();
+        }
+        let tar_path = dir.join("genomes.tar.gz");
+        std::fs::write(&tar_path, gzip(&builder.into_inner().unwrap())).unwrap();
+        assert_eq!(get_archive_format(&tar_path).unwrap(), ArchiveFormat::Tar);
+        assert_eq!(list_archive_members(&tar_path).unwrap().len(), 3);
+        let tar_sketch = sketch_archive(&tar_path, &params, FileSketchMode::PerFile, 2).unwrap();
+        assert_eq!(tar_sketch.failed, vec!["genomes/short.fa".to_string()]);
+        assert_eq!(tar_sketch.sketches.len(), 2);
+        assert_eq!(tar_sketch.sketches[1].0, "genomes/b.fna.gz");
+        // zip
+        let zip_path = dir.join("genomes.zip");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+        for (name, data) in &members {
+            writer.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap();
+        assert_eq!(get_archive_format(&zip_path).unwrap(), ArchiveFormat::Zip);
+        let zip_sketch = sketch_archive(&zip_path, &params, FileSketchMode::PerFile, 2).unwrap();
+        assert_eq!(zip_sketch.sketches.len(), 2);
+        // same signatures as files on disk
+        let a_path = dir.join("a.fa");
+        std::fs::write(&a_path, GENOME_A).unwrap();
+        let a_sketch = super::super::filesketch::sketch_fasta_file(&a_path, &params, FileSketchMode::PerFile).unwrap();
+        assert_eq!(get_signature(&tar_sketch.sketches[0].1), get_signature(&a_sketch));
+        assert_eq!(get_signature(&zip_sketch.sketches[0].1), get_signature(&a_sketch));
+        assert_eq!(get_signature(&zip_sketch.sketches[1].1), get_signature(&tar_sketch.sketches[1].1));
+        let _ = std::fs::remove_dir_all(&dir);
+        // sizes declared in headers do not drive allocation
+        assert_eq!(member_capacity(u64::MAX, 1000), 1000);
+        assert_eq!(member_capacity(10, 1000), 10);
+    } // end of test_sketch_archive
+
+} // end of mod tests
@@ -0,0 +1,323 @@
+//! This module sketches collections of fasta/fastq files : all files of a directory or a list of files.
+//!
+//! Files are sketched in parallel in a thread pool of bounded size, each file giving one signature
+//! (see [sketch_fasta_file] in mode [FileSketchMode::PerFile]).
+//! Each signature is written in its own [sketch container](super::sketchcontainer) and a manifest
+//! describing all files (name, path, number of sequences, total length, checksum of the signature)
+//! is written in json in the output directory.
+
+
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+
+use fnv::FnvHasher;
+use serde::{Deserialize, Serialize};
+
+use crate::parallel::*;
+use crate::io::{is_stdin, open_fastx_file};
+use crate::sketcharg::SeqSketcherParams;
+
+use super::filesketch::{sketch_fasta_file, FileSignatures, FileSketchMode};
+use super::sketchcontainer::{dump_sketch_container_with_inputs, SketchContainerSig, SketchMetadata};
+use super::progress::{ProgressCallback, ProgressStage, ProgressTracker};
+use super::cancel::CancelToken;
+
+
+/// name of the manifest file written in the output directory
+pub const MANIFEST_NAME : &str = "manifest.json";
+
+/// suffix of signature files
+pub const SIGNATURE_SUFFIX : &str = ".sk";
+
+/// extensions of files recognized as fasta/fastq, possibly followed by a compression extension
+const FASTX_EXTENSIONS : [&str; 8] = ["fa", "fasta", "fna", "ffn", "faa", "fas", "fq", "fastq"];
+
+const COMPRESSION_EXTENSIONS : [&str; 5] = ["gz", "bz2", "xz", "zst", "zstd"];
+
+
+/// description of a sketched file
+#[derive(Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct ManifestEntry {
+    /// name of the sketch (file name, made unique in the batch)
+    pub name : String,
+    /// path of the sketched file
+    pub path : PathBuf,
+    /// path of the signature file
+    pub signature_path : PathBuf,
+    /// number of sequences in file
+    pub nb_seqs : usize,
+    /// total number of bases
+    pub total_len : u64,
+    /// fnv checksum of the signature (little endian bytes)
+    pub checksum : u64,
+}
+
+
+/// result of a batch : manifest of sketched files and files that could not be sketched.
+#[derive(Clone,Debug,Default)]
+pub struct BatchSketchResult {
+    pub manifest : Vec<ManifestEntry>,
+    pub failed : Vec<PathBuf>,
+}
+
+
+/// returns true if the file name has a fasta/fastq extension, possibly compressed
+pub fn is_fastx_file_name(path : &Path) -> bool {
+    let name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name.to_ascii_lowercase(),
+        None => return false,
+    };
+    let mut parts : Vec<&str> = name.split('.').collect();
+    if parts.len() > 2 && COMPRESSION_EXTENSIONS.contains(parts.last().unwrap()) {
+        parts.pop();
+    }
+    parts.len() > 1 && FASTX_EXTENSIONS.contains(parts.last().unwrap())
+}
+
+
+/// returns the fasta/fastq files of a directory, sorted by path. If recursive, sub directories are explored.
+pub fn list_fastx_files(dir : &Path, recursive : bool) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::<PathBuf>::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(d) = dirs.pop() {
+        let entries = std::fs::read_dir(&d).map_err(|e| {
+                log::error!("list_fastx_files could not read directory {:?} : {}", d, e);
+                e.to_string()
+            })?;
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path.is_dir() {
+                if recursive {
+                    dirs.push(path);
+                }
+            }
+            else if is_fastx_file_name(&path) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+} // end of list_fastx_files
+
+
+
+// returns number of sequences and total length of a file
+fn get_fastx_stats(path : &Path) -> Result<(usize, u64), String> {
+    let mut reader = open_fastx_file(path)?;
+    let mut nb_seqs = 0;
+    let mut total_len = 0u64;
+    while let Some(record) = reader.next() {
+        let seqrec = record.map_err(|e| format!("invalid record : {}", e))?;
+        nb_seqs += 1;
+        total_len += seqrec.num_bases() as u64;
+    }
+    Ok((nb_seqs, total_len))
+}
+
+
+// fnv hash of the little endian bytes of a signature
+fn signature_checksum<Sig : SketchContainerSig>(sig : &[Sig]) -> u64 {
+    let mut bytes = Vec::<u8>::with_capacity(sig.len() * Sig::SIZE);
+    for v in sig {
+        v.write_le(&mut bytes).unwrap();
+    }
+    let mut hasher = FnvHasher::default();
+    hasher.write(&bytes);
+    hasher.finish()
+}
+
+
+// sketches a file and writes its signature, returns its manifest entry
+fn sketch_one_file(path : &Path, name : &str, params : &SeqSketcherParams, outdir : &Path) -> Result<ManifestEntry, String> {
+    let (nb_seqs, total_len) = get_fastx_stats(path)?;
+    let signature_path = outdir.join(format!("{}{}", name, SIGNATURE_SUFFIX));
+    let metadata = [SketchMetadata{name : name.to_string(), seq_len : total_len}];
+    let checksum = match sketch_fasta_file(path, params, FileSketchMode::PerFile)? {
+        FileSignatures::Int(sketch) => {
+            dump_sketch_container_with_inputs(&signature_path, params, &metadata, &sketch.signatures, &[path])?;
+            signature_checksum(&sketch.signatures[0])
+        },
+        FileSignatures::Float(sketch) => {
+            dump_sketch_container_with_inputs(&signature_path, params, &metadata, &sketch.signatures, &[path])?;
+            signature_checksum(&sketch.signatures[0])
+        },
+        FileSignatures::Double(sketch) => {
+            dump_sketch_container_with_inputs(&signature_path, params, &metadata, &sketch.signatures, &[path])?;
+            signature_checksum(&sketch.signatures[0])
+        },
+    };
+    Ok(ManifestEntry{name : name.to_string(), path : path.to_path_buf(), signature_path, nb_seqs, total_len, checksum})
+}
+
+
+// gives each file a unique name : its file name, suffixed with a rank if already used
+fn get_unique_names(files : &[PathBuf]) -> Vec<String> {
+    let mut used = std::collections::HashSet::<String>::new();
+    files.iter().map(|f| {
+            let base = f.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            let mut name = base.clone();
+            let mut rank = 1;
+            while !used.insert(name.clone()) {
+                name = format!("{}_{}", base, rank);
+                rank += 1;
+            }
+            name
+        }).collect()
+}
+
+
+
+/// sketches each file of a list in one signature, with at most nb_threads threads (0 means rayon default).
+/// Signatures are written in outdir, with the manifest [MANIFEST_NAME].
+/// Files that cannot be sketched are reported in [BatchSketchResult::failed] and do not stop the batch.
+pub fn sketch_files(files : &[PathBuf], params : &SeqSketcherParams, nb_threads : usize, outdir : &Path) -> Result<BatchSketchResult, String> {
+    sketch_files_with_progress(files, params, nb_threads, outdir, None)
+} // end of sketch_files
+
+
+
+/// same as [sketch_files], progress being reported to callback after each file (sketched or failed), see [progress](super::progress).
+pub fn sketch_files_with_progress(files : &[PathBuf], params : &SeqSketcherParams, nb_threads : usize, outdir : &Path, 
+            progress : Option<&ProgressCallback>) -> Result<BatchSketchResult, String> {
+    sketch_files_cancellable(files, params, nb_threads, outdir, progress, &CancelToken::new())
+} // end of sketch_files_with_progress
+
+
+
+/// same as [sketch_files_with_progress], the batch being stopped when cancel is cancelled (see [cancel](super::cancel)).
+/// Files being sketched at cancellation are completed, remaining files are skipped, no manifest is written and an error is returned.
+pub fn sketch_files_cancellable(files : &[PathBuf], params : &SeqSketcherParams, nb_threads : usize, outdir : &Path, 
+            progress : Option<&ProgressCallback>, cancel : &CancelToken) -> Result<BatchSketchResult, String> {
+    //
+    log::info!("sketch_files, nb files : {}, nb threads : {}, output in {:?}", files.len(), nb_threads, outdir);
+    //
+    // files are read twice (statistics and sketching)
+    if files.iter().any(|f| is_stdin(f)) {
+        log::error!("sketch_files : standard input cannot be sketched in a batch");
+        return Err("sketch_files : standard input cannot be sketched in a batch".to_string());
+    }
+    std::fs::create_dir_all(outdir).map_err(|e| {
+            log::error!("sketch_files could not create directory {:?} : {}", outdir, e);
+            e.to_string()
+        })?;
+    let pool = build_thread_pool(nb_threads)?;
+    let names = get_unique_names(files);
+    let tracker = ProgressTracker::new(progress, ProgressStage::Sketching, files.len());
+    let results : Vec<Result<ManifestEntry, String>> = pool.install(|| {
+            files.par_iter().zip(names.par_iter()).map(|(f, name)| {
+                    if cancel.is_cancelled() {
+                        return Err("cancelled".to_string());
+                    }
+                    let res = sketch_one_file(f, name, params, outdir);
+                    tracker.advance(1, res.as_ref().map(|entry| entry.total_len).unwrap_or(0));
+                    res
+                }).collect()
+        });
+    cancel.check("sketch_files")?;
+    let mut batch = BatchSketchResult::default();
+    for (f, res) in files.iter().zip(results) {
+        match res {
+            Ok(entry) => batch.manifest.push(entry),
+            Err(e) => {
+                log::error!("sketch_files : could not sketch file {:?} : {}", f, e);
+                batch.failed.push(f.clone());
+            },
+        }
+    }
+    dump_manifest(&batch.manifest, &outdir.join(MANIFEST_NAME))?;
+    log::info!("sketch_files, nb files sketched : {}, nb failures : {}", batch.manifest.len(), batch.failed.len());
+    Ok(batch)
+} // end of sketch_files_cancellable
+
+
+
+/// sketches all fasta/fastq files of a directory. See [sketch_files] and [list_fastx_files].
+pub fn sketch_directory(dir : &Path, recursive : bool, params : &SeqSketcherParams, nb_threads : usize, outdir : &Path) -> Result<BatchSketchResult, String> {
+    let files = list_fastx_files(dir, recursive)?;
+    if files.is_empty() {
+        log::error!("sketch_directory : no fasta/fastq file in {:?}", dir);
+        return Err(format!("no fasta/fastq file in {:?}", dir));
+    }
+    sketch_files(&files, params, nb_threads, outdir)
+} // end of sketch_directory
+
+
+
+/// dumps a manifest in json
+pub fn dump_manifest(manifest : &[ManifestEntry], path : &Path) -> Result<(), String> {
+    let file = std::fs::File::create(path).map_err(|e| {
+            log::error!("dump_manifest could not open file {:?} : {}", path, e);
+            e.to_string()
+        })?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(file), manifest).map_err(|e| e.to_string())
+}
+
+
+/// reloads a manifest dumped by [dump_manifest]
+pub fn reload_manifest(path : &Path) -> Result<Vec<ManifestEntry>, String> {
+    let file = std::fs::File::open(path).map_err(|e| {
+            log::error!("reload_manifest could not open file {:?} : {}", path, e);
+            e.to_string()
+        })?;
+    serde_json::from_reader(std::io::BufReader::new(file)).map_err(|e| e.to_string())
+}
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sketcharg::{SketchAlgo, DataType};
+    use crate::sketching::sketchcontainer::SketchContainer;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_sketch_directory() {
+        log_init_test();
+        let dir = crate::test_dir("batch");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a.fa"), ">r1\nACGTTGCAAGGCTTACCGATTGACCAGT\n>r2\nGGGCATTACCAGGTTACATTGACC\n").unwrap();
+        std::fs::write(dir.join("sub").join("a.fa"), ">r1\nACGTTGCAAGGCTTACCGATTGACCAGT\n").unwrap();
+        std::fs::write(dir.join("empty.fasta"), ">r1\nACG\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a fasta").unwrap();
+        assert!(is_fastx_file_name(Path::new("x.fq.gz")));
+        assert!(!is_fastx_file_name(Path::new("gz")));
+        //
+        let params = SeqSketcherParams::new(11, 50, SketchAlgo::PROB3A, DataType::DNA);
+        let outdir = dir.join("out");
+        let batch = sketch_directory(&dir, true, &params, 2, &outdir).unwrap();
+        // progress is reported for each file, failed or not
+        let nb_reports = std::sync::atomic::AtomicUsize::new(0);
+        let callback = |p : crate::sketching::progress::SketchProgress| { 
+            assert_eq!(p.nb_total, 3);
+            nb_reports.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        };
+        let files = list_fastx_files(&dir, true).unwrap();
+        sketch_files_with_progress(&files, &params, 2, &dir.join("out2"), Some(&callback)).unwrap();
+        assert_eq!(nb_reports.into_inner(), 3);
+        // a cancelled batch sketches nothing
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        assert!(sketch_files_cancellable(&files, &params, 2, &dir.join("out3"), None, &cancel).is_err());
+        assert!(!dir.join("out3").join(MANIFEST_NAME).exists());
+        // empty.fasta has no kmer
+        assert_eq!(batch.failed.len(), 1);
+        assert_eq!(batch.manifest.len(), 2);
+        let entry = &batch.manifest[0];
+        assert_eq!((entry.name.as_str(), entry.nb_seqs, entry.total_len), ("a.fa", 2, 52));
+        assert_eq!(batch.manifest[1].name, "a.fa_1");
+        assert_eq!(reload_manifest(&outdir.join(MANIFEST_NAME)).unwrap(), batch.manifest);
+        let container = SketchContainer::open(&entry.signature_path).unwrap();
+        assert_eq!(signature_checksum(&container.get_signature::<u64>(0).unwrap()), entry.checksum);
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_sketch_directory
+
+} // end of mod tests
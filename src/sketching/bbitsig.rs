@@ -0,0 +1,184 @@
+//! This module provides comparison paths counting equal slots on machine words instead of slot after slot.
+//!
+//! - b-bit signatures (Li and König, b-bit minwise hashing, see [BbitSignature](super::sigdistance::BbitSignature)) keep
+//!   only the b low bits of each slot, packed 64 / b slots by u64 word. Equal slots of two packed signatures are counted
+//!   by a XOR of words then a popcount. With b = 8 a signature of 10000 u64 slots takes 10 kb
+//!   instead of 80 kb, at the price of a larger variance for small Jaccard indexes.
+//! - [count_equal_u32] and [count_equal_u64] count equal slots of integer signatures with AVX2 comparisons
+//!   (detected at run time), falling back to a scalar loop.
+
+
+use crate::base::pack2b::{get_simd_level, SimdLevel};
+
+
+// counts slots differing in 2 packed signatures : in the XOR of words the bits of each slot are ORed in its low bit,
+// then low bits of slots are counted. Padding slots of the last word are 0 in both signatures.
+#[inline(always)]
+fn count_diff_words(words1 : &[u64], words2 : &[u64], nb_bits : u32) -> usize {
+    let low_bits = u64::MAX / ((1u128 << nb_bits) - 1) as u64;
+    let mut nb_diff = 0;
+    for (w1, w2) in words1.iter().zip(words2) {
+        let mut x = w1 ^ w2;
+        let mut shift = 1;
+        while shift < nb_bits {
+            x |= x >> shift;
+            shift *= 2;
+        }
+        nb_diff += (x & low_bits).count_ones() as usize;
+    }
+    nb_diff
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "popcnt")]
+unsafe fn count_diff_words_popcnt(words1 : &[u64], words2 : &[u64], nb_bits : u32) -> usize {
+    count_diff_words(words1, words2, nb_bits)
+}
+
+/// returns the number of slots differing in 2 signatures of nb_bits bits by slot packed in u64 words,
+/// slots not crossing words and padding slots of the last word being 0.
+pub(crate) fn count_diff_slots(words1 : &[u64], words2 : &[u64], nb_bits : u32) -> usize {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("popcnt") {
+            return unsafe { count_diff_words_popcnt(words1, words2, nb_bits) };
+        }
+    }
+    count_diff_words(words1, words2, nb_bits)
+}
+
+
+
+/// returns the number of equal slots of 2 u64 signatures (up to the length of the shorter one)
+pub fn count_equal_u64(sig1 : &[u64], sig2 : &[u64]) -> usize {
+    count_equal_u64_with(get_simd_level(), sig1, sig2)
+}
+
+/// returns the number of equal slots of 2 u32 signatures (up to the length of the shorter one)
+pub fn count_equal_u32(sig1 : &[u32], sig2 : &[u32]) -> usize {
+    count_equal_u32_with(get_simd_level(), sig1, sig2)
+}
+
+
+fn count_equal_u64_with(level : SimdLevel, sig1 : &[u64], sig2 : &[u64]) -> usize {
+    let len = sig1.len().min(sig2.len());
+    let done = match level {
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => Some(unsafe { x86::count_equal_u64_avx2(&sig1[..len], &sig2[..len]) }),
+        _ => None,
+    };
+    let (start, count) = done.unwrap_or((0, 0));
+    count + count_equal_scalar(&sig1[start..len], &sig2[start..len])
+}
+
+fn count_equal_u32_with(level : SimdLevel, sig1 : &[u32], sig2 : &[u32]) -> usize {
+    let len = sig1.len().min(sig2.len());
+    let done = match level {
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => Some(unsafe { x86::count_equal_u32_avx2(&sig1[..len], &sig2[..len]) }),
+        _ => None,
+    };
+    let (start, count) = done.unwrap_or((0, 0));
+    count + count_equal_scalar(&sig1[start..len], &sig2[start..len])
+}
+
+#[inline]
+fn count_equal_scalar<T : PartialEq>(sig1 : &[T], sig2 : &[T]) -> usize {
+    sig1.iter().zip(sig2).filter(|(a, b)| a == b).count()
+}
+
+
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    // sums the 64 bits lanes of v
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn sum_lanes_u64(v : __m256i) -> usize {
+        let mut lanes = [0u64; 4];
+        _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, v);
+        lanes.iter().sum::<u64>() as usize
+    }
+
+    // counts equal slots by vectors of 4 slots (2 vectors by iteration), returns (nb slots done, count).
+    // Equal lanes compare to -1, which is subtracted from the accumulators.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn count_equal_u64_avx2(sig1 : &[u64], sig2 : &[u64]) -> (usize, usize) {
+        let (mut acc1, mut acc2) = (_mm256_setzero_si256(), _mm256_setzero_si256());
+        let mut done = 0;
+        while done + 8 <= sig1.len() {
+            let (p1, p2) = (sig1.as_ptr().add(done) as *const __m256i, sig2.as_ptr().add(done) as *const __m256i);
+            acc1 = _mm256_sub_epi64(acc1, _mm256_cmpeq_epi64(_mm256_loadu_si256(p1), _mm256_loadu_si256(p2)));
+            acc2 = _mm256_sub_epi64(acc2, _mm256_cmpeq_epi64(_mm256_loadu_si256(p1.add(1)), _mm256_loadu_si256(p2.add(1))));
+            done += 8;
+        }
+        (done, sum_lanes_u64(_mm256_add_epi64(acc1, acc2)))
+    } // end of count_equal_u64_avx2
+
+    // as count_equal_u64_avx2 with vectors of 8 slots. 32 bits lanes are widened to 64 bits every 2^31 iterations at most
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn count_equal_u32_avx2(sig1 : &[u32], sig2 : &[u32]) -> (usize, usize) {
+        let mut count = 0;
+        let mut done = 0;
+        while done + 16 <= sig1.len() {
+            let block_end = (done + 16 * (1 << 30)).min(sig1.len());
+            let (mut acc1, mut acc2) = (_mm256_setzero_si256(), _mm256_setzero_si256());
+            while done + 16 <= block_end {
+                let (p1, p2) = (sig1.as_ptr().add(done) as *const __m256i, sig2.as_ptr().add(done) as *const __m256i);
+                acc1 = _mm256_sub_epi32(acc1, _mm256_cmpeq_epi32(_mm256_loadu_si256(p1), _mm256_loadu_si256(p2)));
+                acc2 = _mm256_sub_epi32(acc2, _mm256_cmpeq_epi32(_mm256_loadu_si256(p1.add(1)), _mm256_loadu_si256(p2.add(1))));
+                done += 16;
+            }
+            let acc = _mm256_add_epi64(_mm256_cvtepu32_epi64(_mm256_castsi256_si128(acc1)), _mm256_cvtepu32_epi64(_mm256_extracti128_si256(acc1, 1)));
+            let acc = _mm256_add_epi64(acc, _mm256_cvtepu32_epi64(_mm256_castsi256_si128(acc2)));
+            count += sum_lanes_u64(_mm256_add_epi64(acc, _mm256_cvtepu32_epi64(_mm256_extracti128_si256(acc2, 1))));
+        }
+        (done, count)
+    } // end of count_equal_u32_avx2
+
+} // end of mod x86
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    fn available_levels() -> Vec<SimdLevel> {
+        let mut levels = vec![SimdLevel::Scalar];
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") { levels.push(SimdLevel::Avx2); }
+        }
+        levels
+    }
+
+    #[test]
+    fn test_count_equal_levels() {
+        log_init_test();
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(3);
+        let sig1 : Vec<u64> = (0..1003).map(|_| rng.gen_range(0..4)).collect();
+        let sig2 : Vec<u64> = (0..1003).map(|_| rng.gen_range(0..4)).collect();
+        let (s1, s2) : (Vec<u32>, Vec<u32>) = (sig1.iter().map(|s| *s as u32).collect(), sig2.iter().map(|s| *s as u32).collect());
+        for level in available_levels() {
+            for len in [0, 7, 8, 15, 16, 17, 1003] {
+                let expected = count_equal_scalar(&sig1[..len], &sig2[..len]);
+                assert_eq!(count_equal_u64_with(level, &sig1[..len], &sig2[..len]), expected, "level {:?} len {}", level, len);
+                assert_eq!(count_equal_u32_with(level, &s1[..len], &s2[..len]), expected, "level {:?} len {}", level, len);
+            }
+        }
+        assert_eq!(count_equal_u64(&sig1, &sig2[..10]), count_equal_scalar(&sig1[..10], &sig2[..10]));
+    } // end of test_count_equal_levels
+
+} // end of mod tests
@@ -0,0 +1,255 @@
+//! This module provides a cache blocked kernel counting equal slots between two collections of signatures (queries x targets).
+//!
+//! Comparing pairs one after the other reads both signatures entirely for each pair : with long signatures (10000 slots
+//! of u64 are 80 kb) a block of targets does not stay in cache and each pair goes to memory.
+//! The kernel computes tiles of [TILE_SIZE] queries x [TILE_SIZE] targets, and inside a tile processes slots by ranges of
+//! [SLOT_BLOCK_SIZE] so that the slot ranges of the tile (2 * 64 * 256 u64, 256 kb) stay in cache while all pairs of the tile
+//! are counted. Tiles of queries are computed in parallel with rayon.
+//!
+//! Signatures are given as any slice of rows implementing `AsRef<[Sig]>` : `Vec<Sig>`, or slices borrowed from a memory mapped
+//! [SketchContainer](super::sketchcontainer::SketchContainer) (see [get_signature_ref](super::sketchcontainer::SketchContainer::get_signature_ref)).
+//!
+//! - [equal_counts_matrix] returns the counts of all pairs (queries x targets),
+//! - [knn] returns for each query the k targets with most equal slots, without storing the whole matrix,
+//!   [knn_filtered] keeps only the pairs accepted by a filter,
+//! - [equal_counts_lower_tile] is the building block of the distance matrix of [distances](super::distances).
+//!
+//! Signatures which are not compared slot by slot (b-bit, HyperLogLog, bottom-k ...) get their nearest neighbours
+//! from [knn_signatures], pair by pair through [SignatureDistance].
+
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::parallel::*;
+use super::sigdistance::SignatureDistance;
+
+
+/// number of queries (and targets) of a tile
+pub const TILE_SIZE : usize = 64;
+
+/// number of slots compared at once in a tile
+pub const SLOT_BLOCK_SIZE : usize = 256;
+
+
+// min heap of the best targets of a query, ordered by number of equal slots then by reversed target rank
+type NeighbourHeap = BinaryHeap<Reverse<(u32, Reverse<usize>)>>;
+
+
+/// returns the number of equal slots of 2 signatures of same length.
+/// (written as a filter count, which the compiler vectorizes best for integer slots)
+#[inline]
+pub fn count_equal<Sig : PartialEq>(sig1 : &[Sig], sig2 : &[Sig]) -> usize {
+    sig1.iter().zip(sig2).filter(|(a, b)| a == b).count()
+} // end of count_equal
+
+
+
+// checks that all signatures have the same non null size and returns it
+fn check_sizes<Sig, Q : AsRef<[Sig]>, T : AsRef<[Sig]>>(queries : &[Q], targets : &[T]) -> Result<usize, String> {
+    let sketch_size = queries.first().map(|s| s.as_ref().len()).or(targets.first().map(|s| s.as_ref().len())).unwrap_or(1);
+    if sketch_size == 0 || queries.iter().any(|s| s.as_ref().len() != sketch_size) || targets.iter().any(|s| s.as_ref().len() != sketch_size) {
+        log::error!("blockcompare : signatures must have same non null size");
+        return Err("signatures must have same non null size".to_string());
+    }
+    Ok(sketch_size)
+} // end of check_sizes
+
+
+
+// adds to counts (row major, queries.len() x targets.len()) the number of equal slots of each pair of a tile.
+// If lower is Some((queries_start, targets_start)) only pairs (i, j) with targets_start + j < queries_start + i are counted
+// (strict lower triangle of the comparison of a collection with itself), the other counts are left unchanged.
+fn count_tile<Sig, Q, T>(queries : &[Q], targets : &[T], lower : Option<(usize, usize)>, counts : &mut [u32])
+        where Sig : PartialEq, Q : AsRef<[Sig]>, T : AsRef<[Sig]> {
+    let nb_targets = targets.len();
+    let sketch_size = queries.first().map(|s| s.as_ref().len()).unwrap_or(0);
+    for slot_start in (0..sketch_size).step_by(SLOT_BLOCK_SIZE) {
+        let slot_end = (slot_start + SLOT_BLOCK_SIZE).min(sketch_size);
+        for (i, query) in queries.iter().enumerate() {
+            let query = &query.as_ref()[slot_start..slot_end];
+            let nb_cols = match lower {
+                Some((queries_start, targets_start)) => (queries_start + i).saturating_sub(targets_start).min(nb_targets),
+                None => nb_targets,
+            };
+            for (count, target) in counts[i * nb_targets..i * nb_targets + nb_cols].iter_mut().zip(targets) {
+                *count += count_equal(query, &target.as_ref()[slot_start..slot_end]) as u32;
+            }
+        }
+    }
+} // end of count_tile
+
+
+
+/// counts equal slots between signatures[row_start..row_end] and signatures[col_start..col_end], for pairs (i,j) with j < i only.
+/// counts is row major, (row_end - row_start) x (col_end - col_start), and must be filled with 0.
+pub fn equal_counts_lower_tile<Sig : PartialEq, S : AsRef<[Sig]>>(signatures : &[S], rows : std::ops::Range<usize>, cols : std::ops::Range<usize>, counts : &mut [u32]) {
+    assert_eq!(counts.len(), rows.len() * cols.len(), "counts size must be the tile size");
+    count_tile(&signatures[rows.clone()], &signatures[cols.clone()], Some((rows.start, cols.start)), counts);
+} // end of equal_counts_lower_tile
+
+
+
+/// returns the number of equal slots of all pairs (query, target), row major : count of (i,j) at index i * targets.len() + j.
+/// All signatures must have the same non null size.
+pub fn equal_counts_matrix<Sig, Q, T>(queries : &[Q], targets : &[T]) -> Result<Vec<u32>, String>
+        where Sig : PartialEq, Q : AsRef<[Sig]> + Sync, T : AsRef<[Sig]> + Sync {
+    check_sizes(queries, targets)?;
+    let nb_targets = targets.len();
+    let mut counts = vec![0u32; queries.len() * nb_targets];
+    if nb_targets == 0 {
+        return Ok(counts);
+    }
+    counts.par_chunks_mut(TILE_SIZE * nb_targets).enumerate().for_each(|(tile, rows)| {
+        let query_tile = &queries[tile * TILE_SIZE..(tile * TILE_SIZE + TILE_SIZE).min(queries.len())];
+        // counts of a tile of targets, then copied in rows
+        let mut tile_counts = vec![0u32; query_tile.len() * TILE_SIZE];
+        for target_start in (0..nb_targets).step_by(TILE_SIZE) {
+            let target_tile = &targets[target_start..(target_start + TILE_SIZE).min(nb_targets)];
+            let tile_counts = &mut tile_counts[..query_tile.len() * target_tile.len()];
+            tile_counts.fill(0);
+            count_tile(query_tile, target_tile, None, tile_counts);
+            for (i, row) in tile_counts.chunks(target_tile.len()).enumerate() {
+                rows[i * nb_targets + target_start..i * nb_targets + target_start + target_tile.len()].copy_from_slice(row);
+            }
+        }
+    });
+    Ok(counts)
+} // end of equal_counts_matrix
+
+
+
+/// returns for each query the k targets with most equal slots as (target rank, number of equal slots),
+/// by decreasing number of equal slots (ties by increasing target rank).
+/// Memory is proportional to k and to the number of queries, not to the number of pairs.
+pub fn knn<Sig, Q, T>(queries : &[Q], targets : &[T], k : usize) -> Result<Vec<Vec<(usize, usize)>>, String>
+        where Sig : PartialEq, Q : AsRef<[Sig]> + Sync, T : AsRef<[Sig]> + Sync {
+    knn_filtered(queries, targets, k, |_, _, _| true)
+} // end of knn
+
+
+
+/// same as [knn], keeping only pairs for which filter(query rank, target rank, number of equal slots) is true,
+/// for example to apply thresholds depending on the sequences sketched before the k best targets are retained.
+pub fn knn_filtered<Sig, Q, T, F>(queries : &[Q], targets : &[T], k : usize, filter : F) -> Result<Vec<Vec<(usize, usize)>>, String>
+        where   Sig : PartialEq,
+                Q : AsRef<[Sig]> + Sync,
+                T : AsRef<[Sig]> + Sync,
+                F : Fn(usize, usize, usize) -> bool + Sync {
+    check_sizes(queries, targets)?;
+    let nb_targets = targets.len();
+    let neighbours = queries.par_chunks(TILE_SIZE).enumerate().flat_map_iter(|(tile, query_tile)| {
+            let mut heaps : Vec<NeighbourHeap> = (0..query_tile.len()).map(|_| BinaryHeap::with_capacity(k + 1)).collect();
+            let mut tile_counts = vec![0u32; query_tile.len() * TILE_SIZE];
+            for target_start in (0..nb_targets).step_by(TILE_SIZE) {
+                let target_tile = &targets[target_start..(target_start + TILE_SIZE).min(nb_targets)];
+                let tile_counts = &mut tile_counts[..query_tile.len() * target_tile.len()];
+                tile_counts.fill(0);
+                count_tile(query_tile, target_tile, None, tile_counts);
+                for (i, (heap, row)) in heaps.iter_mut().zip(tile_counts.chunks(target_tile.len())).enumerate() {
+                    for (j, count) in row.iter().enumerate() {
+                        if !filter(tile * TILE_SIZE + i, target_start + j, *count as usize) {
+                            continue;
+                        }
+                        heap.push(Reverse((*count, Reverse(target_start + j))));
+                        if heap.len() > k {
+                            heap.pop();
+                        }
+                    }
+                }
+            }
+            heaps.into_iter().map(|heap| {
+                heap.into_sorted_vec().into_iter().map(|Reverse((count, Reverse(j)))| (j, count as usize)).collect::<Vec<(usize, usize)>>()
+            }).collect::<Vec<_>>()
+        }).collect();
+    Ok(neighbours)
+} // end of knn_filtered
+
+
+
+/// returns for each query the k targets of largest Jaccard index given by [SignatureDistance::jaccard], as (target rank, Jaccard index),
+/// by decreasing Jaccard index (ties by increasing target rank). Queries are processed in parallel.
+pub fn knn_signatures<S : SignatureDistance + Sync>(queries : &[S], targets : &[S], k : usize) -> Vec<Vec<(usize, f64)>> {
+    queries.par_iter().map(|query| {
+            let mut best = Vec::<(usize, f64)>::with_capacity(k + 1);
+            for (j, target) in targets.iter().enumerate() {
+                let jaccard = query.jaccard(target);
+                // best stays sorted by decreasing Jaccard index, a target goes after those with equal index
+                let rank = best.partition_point(|(_, b)| *b >= jaccard);
+                if rank < k {
+                    best.insert(rank, (j, jaccard));
+                    best.truncate(k);
+                }
+            }
+            best
+        }).collect()
+} // end of knn_signatures
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_block_compare() {
+        log_init_test();
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(17);
+        // sizes not multiple of tile and slot blocks, few values so that counts vary
+        let sketch_size = 2 * SLOT_BLOCK_SIZE + 11;
+        let queries : Vec<Vec<u32>> = (0..TILE_SIZE + 5).map(|_| (0..sketch_size).map(|_| rng.gen_range(0..3)).collect()).collect();
+        let targets : Vec<Vec<u32>> = (0..2 * TILE_SIZE + 9).map(|_| (0..sketch_size).map(|_| rng.gen_range(0..3)).collect()).collect();
+        let counts = equal_counts_matrix(&queries, &targets).unwrap();
+        for (i, q) in queries.iter().enumerate() {
+            for (j, t) in targets.iter().enumerate() {
+                assert_eq!(counts[i * targets.len() + j] as usize, q.iter().zip(t).filter(|(a, b)| a == b).count());
+            }
+        }
+        // knn gives the best targets of each row of counts
+        let neighbours = knn(&queries, &targets, 5).unwrap();
+        assert_eq!(neighbours.len(), queries.len());
+        for (i, nn) in neighbours.iter().enumerate() {
+            let mut expected : Vec<(usize, usize)> = (0..targets.len()).map(|j| (j, counts[i * targets.len() + j] as usize)).collect();
+            expected.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            assert_eq!(nn, &expected[..5]);
+        }
+        // filtered, on borrowed rows
+        let borrowed : Vec<&[u32]> = targets.iter().map(|t| t.as_slice()).collect();
+        let filtered = knn_filtered(&queries, &borrowed, 5, |i, j, count| (i + j) % 2 == 0 && count > 0).unwrap();
+        for (i, nn) in filtered.iter().enumerate() {
+            let mut expected : Vec<(usize, usize)> = (0..targets.len()).map(|j| (j, counts[i * targets.len() + j] as usize))
+                    .filter(|(j, count)| (i + j) % 2 == 0 && *count > 0).collect();
+            expected.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            expected.truncate(5);
+            assert_eq!(nn, &expected);
+        }
+        // lower triangle tile
+        let mut tile = vec![0u32; 70 * 80];
+        equal_counts_lower_tile(&targets, 60..130, 50..130, &mut tile);
+        for i in 60..130 {
+            for j in 50..130 {
+                let expected = if j < i { count_equal(&targets[i], &targets[j]) as u32 } else { 0 };
+                assert_eq!(tile[(i - 60) * 80 + j - 50], expected);
+            }
+        }
+        // errors
+        assert!(equal_counts_matrix(&queries, &vec![vec![0u32; 3]]).is_err());
+        assert!(knn(&queries, &Vec::<Vec<u32>>::new(), 3).unwrap().iter().all(|nn| nn.is_empty()));
+        // through SignatureDistance, the same neighbours as knn for slot signatures
+        let by_jaccard = knn_signatures(&queries, &targets, 5);
+        for (nn, nn_jaccard) in neighbours.iter().zip(&by_jaccard) {
+            let expected : Vec<(usize, f64)> = nn.iter().map(|(j, count)| (*j, *count as f64 / sketch_size as f64)).collect();
+            assert_eq!(nn_jaccard, &expected);
+        }
+    } // end of test_block_compare
+
+} // end of mod tests
@@ -0,0 +1,72 @@
+//! This module defines cooperative cancellation of long parallel runs : batch sketching of files
+//! ([sketch_files_cancellable](super::batchsketch::sketch_files_cancellable)) and all-vs-all distances
+//! ([compute_distance_matrix_cancellable](super::distances::compute_distance_matrix_cancellable)).
+//!
+//! A [CancelToken] is cloned and shared between the thread running the computation and a controlling thread (a server request handler for example).
+//! The computation checks the token in its rayon closures before each unit of work (a file, a block of distances),
+//! so that after a call to [CancelToken::cancel] the run stops after the units in progress and returns an error.
+
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+
+/// a shared flag requesting the stop of a computation
+#[derive(Clone,Debug,Default)]
+pub struct CancelToken {
+    cancelled : Arc<AtomicBool>,
+}
+
+
+impl CancelToken {
+
+    pub fn new() -> Self {
+        CancelToken{cancelled : Arc::new(AtomicBool::new(false))}
+    }
+
+    /// requests cancellation of all computations using this token (or a clone of it)
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// returns an error if cancellation was requested, context being the name of the computation
+    pub fn check(&self, context : &str) -> Result<(), String> {
+        if self.is_cancelled() {
+            log::info!("{} : cancelled", context);
+            return Err(format!("{} : cancelled", context));
+        }
+        Ok(())
+    } // end of check
+
+} // end of impl CancelToken
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_cancel_token() {
+        log_init_test();
+        let token = CancelToken::new();
+        let shared = token.clone();
+        assert!(token.check("test").is_ok());
+        shared.cancel();
+        assert!(token.is_cancelled());
+        assert!(token.check("test").unwrap_err().contains("cancelled"));
+    } // end of test_cancel_token
+
+} // end of mod tests
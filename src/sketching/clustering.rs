@@ -0,0 +1,389 @@
+//! This module clusters signature collections.
+//!
+//! - [hierarchical_clustering] builds the dendrogram of a [DistanceMatrix] with single or average linkage (UPGMA),
+//!   using the nearest neighbour chain algorithm (O(n^2) time, the condensed matrix being copied once).
+//!   The dendrogram is cut at a distance threshold by [Dendrogram::cut] and [medoid_clustering] chooses representatives.
+//! - [greedy_clustering] is a dereplication : genomes are visited in a given order (best quality first, for example),
+//!   each one joining the cluster of its most similar representative if identity is above threshold,
+//!   or becoming the representative of a new cluster. Dereplication at 95% ANI needs no distance matrix.
+//!
+//! Dendrograms are written as Newick trees by [write_newick], for visualization in tree viewers.
+
+
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::parallel::*;
+use serde::{Deserialize, Serialize};
+
+use crate::sketcharg::SeqSketcherParams;
+use super::blockcompare::count_equal;
+use super::distances::{create_file, DistanceKind, DistanceMatrix};
+use super::mashdist::identity_from_jaccard;
+
+
+/// how distance between clusters is computed from distances between their members
+#[derive(Copy,Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub enum Linkage {
+    /// minimal distance
+    Single,
+    /// mean distance (UPGMA)
+    Average,
+}
+
+
+/// a merge of 2 clusters. Clusters 0..n are the leaves, cluster n+i is created by merge i.
+#[derive(Copy,Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct Merge {
+    pub left : usize,
+    pub right : usize,
+    /// distance between merged clusters
+    pub distance : f64,
+    /// number of leaves of the new cluster
+    pub size : usize,
+}
+
+
+/// result of hierarchical clustering : the n-1 merges by increasing distance
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct Dendrogram {
+    nb_leaves : usize,
+    linkage : Linkage,
+    merges : Vec<Merge>,
+}
+
+
+impl Dendrogram {
+    /// returns number of leaves
+    pub fn get_nb_leaves(&self) -> usize {
+        self.nb_leaves
+    }
+
+    /// returns linkage used
+    pub fn get_linkage(&self) -> Linkage {
+        self.linkage
+    }
+
+    /// returns merges by increasing distance
+    pub fn get_merges(&self) -> &[Merge] {
+        &self.merges
+    }
+
+    /// returns cluster of each leaf when merges at distance <= threshold are applied.
+    /// Clusters are numbered 0.. in order of their first leaf.
+    pub fn cut(&self, threshold : f64) -> Vec<usize> {
+        let mut parent : Vec<usize> = (0..self.nb_leaves + self.merges.len()).collect();
+        for (i, merge) in self.merges.iter().enumerate().take_while(|(_, m)| m.distance <= threshold) {
+            parent[merge.left] = self.nb_leaves + i;
+            parent[merge.right] = self.nb_leaves + i;
+        }
+        let root = |mut c : usize| { while parent[c] != c { c = parent[c]; } c };
+        let mut numbering = std::collections::HashMap::<usize, usize>::new();
+        (0..self.nb_leaves).map(|leaf| {
+                let next = numbering.len();
+                *numbering.entry(root(leaf)).or_insert(next)
+            }).collect()
+    } // end of cut
+} // end of impl Dendrogram
+
+
+
+/// cluster assignments with a representative for each cluster
+#[derive(Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct Clustering {
+    /// cluster of each item
+    pub assignments : Vec<usize>,
+    /// representative item of each cluster
+    pub representatives : Vec<usize>,
+}
+
+impl Clustering {
+    /// returns number of clusters
+    pub fn get_nb_clusters(&self) -> usize {
+        self.representatives.len()
+    }
+
+    /// returns items of cluster c
+    pub fn get_members(&self, c : usize) -> Vec<usize> {
+        self.assignments.iter().enumerate().filter(|(_, a)| **a == c).map(|(i, _)| i).collect()
+    }
+} // end of impl Clustering
+
+
+
+// distance stored in the matrix, similarities being converted
+fn matrix_distance<F : num::Float>(matrix : &DistanceMatrix<F>, i : usize, j : usize) -> f64 {
+    let v = matrix.get(i, j).to_f64().unwrap();
+    if matrix.get_kind() == DistanceKind::Jaccard { 1. - v } else { v }
+}
+
+
+// index in condensed matrix of (i,j), i != j
+fn pair_index(i : usize, j : usize) -> usize {
+    let (i, j) = if i > j { (i, j) } else { (j, i) };
+    i * (i - 1) / 2 + j
+}
+
+
+
+/// hierarchical clustering of the rows of matrix. A matrix of Jaccard indexes is converted to 1 - Jaccard.
+pub fn hierarchical_clustering<F : num::Float>(matrix : &DistanceMatrix<F>, linkage : Linkage) -> Dendrogram {
+    let nb = matrix.len();
+    log::info!("hierarchical_clustering, nb leaves : {}, linkage : {:?}", nb, linkage);
+    // working copy of distances between active clusters, cluster being identified by the slot of one of its leaves
+    let mut dist : Vec<f64> = (1..nb).flat_map(|i| (0..i).map(move |j| (i, j))).map(|(i, j)| matrix_distance(matrix, i, j)).collect();
+    let mut active = vec![true; nb];
+    let mut size = vec![1usize; nb];
+    let mut pairs = Vec::<(usize, usize, f64)>::with_capacity(nb.saturating_sub(1));
+    let mut chain = Vec::<usize>::new();
+    //
+    while pairs.len() + 1 < nb {
+        if chain.is_empty() {
+            chain.push(active.iter().position(|a| *a).unwrap());
+        }
+        // grow the chain of nearest neighbours until 2 clusters are reciprocal nearest neighbours
+        let (a, b, d) = loop {
+            let a = *chain.last().unwrap();
+            let prev = if chain.len() >= 2 { Some(chain[chain.len() - 2]) } else { None };
+            let mut best = prev;
+            let mut best_dist = prev.map(|p| dist[pair_index(a, p)]).unwrap_or(f64::INFINITY);
+            for c in (0..nb).filter(|c| active[*c] && *c != a) {
+                let d = dist[pair_index(a, c)];
+                if d < best_dist {
+                    best = Some(c);
+                    best_dist = d;
+                }
+            }
+            let best = best.unwrap();
+            if Some(best) == prev {
+                chain.truncate(chain.len() - 2);
+                break (a, best, best_dist);
+            }
+            chain.push(best);
+        };
+        // merged cluster keeps slot b, Lance-Williams update
+        for c in (0..nb).filter(|c| active[*c] && *c != a && *c != b) {
+            let (dac, dbc) = (dist[pair_index(a, c)], dist[pair_index(b, c)]);
+            dist[pair_index(b, c)] = match linkage {
+                Linkage::Single => dac.min(dbc),
+                Linkage::Average => (size[a] as f64 * dac + size[b] as f64 * dbc) / (size[a] + size[b]) as f64,
+            };
+        }
+        active[a] = false;
+        size[b] += size[a];
+        pairs.push((a, b, d));
+    }
+    // merges by increasing distance, slots relabelled with cluster numbers
+    pairs.sort_by(|x, y| x.2.total_cmp(&y.2));
+    let mut cluster_of_slot : Vec<usize> = (0..nb).collect();
+    let mut leaves_of_slot = vec![1usize; nb];
+    let mut merges = Vec::<Merge>::with_capacity(pairs.len());
+    for (i, (a, b, d)) in pairs.iter().enumerate() {
+        let (ca, cb) = (cluster_of_slot[*a], cluster_of_slot[*b]);
+        let size = leaves_of_slot[*a] + leaves_of_slot[*b];
+        merges.push(Merge{left : ca.min(cb), right : ca.max(cb), distance : *d, size});
+        // with single and average linkage merges of a slot happen by increasing distance, so b holds the new cluster
+        cluster_of_slot[*b] = nb + i;
+        leaves_of_slot[*b] = size;
+    }
+    Dendrogram{nb_leaves : nb, linkage, merges}
+} // end of hierarchical_clustering
+
+
+
+/// chooses as representative of each cluster of assignments its medoid, the member with minimal sum of distances to other members.
+pub fn medoid_clustering<F : num::Float + Sync>(matrix : &DistanceMatrix<F>, assignments : Vec<usize>) -> Clustering {
+    assert_eq!(matrix.len(), assignments.len(), "assignments and matrix must have same length");
+    let nb_clusters = assignments.iter().max().map(|m| m + 1).unwrap_or(0);
+    let representatives = (0..nb_clusters).into_par_iter().map(|c| {
+            let members : Vec<usize> = (0..assignments.len()).filter(|i| assignments[*i] == c).collect();
+            let sum = |i : usize| members.iter().map(|j| if i == *j { 0. } else { matrix_distance(matrix, i, *j) }).sum::<f64>();
+            *members.iter().min_by(|i, j| sum(**i).total_cmp(&sum(**j))).unwrap()
+        }).collect();
+    Clustering{assignments, representatives}
+} // end of medoid_clustering
+
+
+
+/// greedy dereplication of signatures obtained with params. Signatures are visited in order, a permutation of their ranks
+/// (rank order if None), a signature joining the cluster of the representative with highest identity
+/// (see [identity_from_jaccard]) if it is >= min_identity, or else becoming a new representative.
+pub fn greedy_clustering<Sig : PartialEq + Sync>(signatures : &[Vec<Sig>], params : &SeqSketcherParams, min_identity : f64, order : Option<&[usize]>) -> Result<Clustering, String> {
+    let nb = signatures.len();
+    log::info!("greedy_clustering, nb signatures : {}, min identity : {}", nb, min_identity);
+    let order : Vec<usize> = match order {
+        Some(order) => {
+            let mut sorted = order.to_vec();
+            sorted.sort_unstable();
+            if sorted != (0..nb).collect::<Vec<usize>>() {
+                log::error!("greedy_clustering : order must be a permutation of 0..{}", nb);
+                return Err("greedy_clustering : order must be a permutation of signatures".to_string());
+            }
+            order.to_vec()
+        },
+        None => (0..nb).collect(),
+    };
+    if signatures.iter().any(|s| s.len() != params.get_sketch_size()) {
+        log::error!("greedy_clustering : signature size does not match sketch size {}", params.get_sketch_size());
+        return Err("greedy_clustering : signature size does not match sketch size".to_string());
+    }
+    //
+    let mut assignments = vec![0usize; nb];
+    let mut representatives = Vec::<usize>::new();
+    for i in order {
+        let best = representatives.par_iter().enumerate().map(|(c, r) : (usize, &usize)| {
+                let equal = count_equal(&signatures[i], &signatures[*r]);
+                (c, identity_from_jaccard(equal as f64 / signatures[i].len() as f64, params))
+            })
+            .max_by(|x, y| x.1.total_cmp(&y.1).then(y.0.cmp(&x.0)));
+        match best {
+            Some((c, identity)) if identity >= min_identity => assignments[i] = c,
+            _ => {
+                assignments[i] = representatives.len();
+                representatives.push(i);
+            },
+        }
+    }
+    log::info!("greedy_clustering, nb clusters : {}", representatives.len());
+    Ok(Clustering{assignments, representatives})
+} // end of greedy_clustering
+
+
+// Newick names cannot contain blanks nor ( ) [ ] , : ; '
+fn get_newick_name(name : &str) -> String {
+    name.chars().map(|c| if c.is_whitespace() || "()[],:;'".contains(c) { '_' } else { c }).collect()
+}
+
+
+/// returns the Newick representation of dendrogram, leaf i being named names\[i\].
+/// The tree is ultrametric : a merge at distance d is a node at height d/2, and branch lengths are differences of heights.
+pub fn to_newick(dendrogram : &Dendrogram, names : &[String]) -> Result<String, String> {
+    let nb = dendrogram.get_nb_leaves();
+    if names.len() != nb || nb == 0 {
+        log::error!("to_newick : {} names for {} leaves", names.len(), nb);
+        return Err("to_newick : names must match a non empty dendrogram".to_string());
+    }
+    // subtree text and height of each cluster, built bottom-up (no recursion for deep trees)
+    let mut subtrees : Vec<String> = names.iter().map(|n| get_newick_name(n)).collect();
+    let mut heights = vec![0f64; nb + dendrogram.merges.len()];
+    for (i, merge) in dendrogram.merges.iter().enumerate() {
+        let height = merge.distance / 2.;
+        let left = std::mem::take(&mut subtrees[merge.left]);
+        let right = std::mem::take(&mut subtrees[merge.right]);
+        subtrees.push(format!("({}:{},{}:{})", left, (height - heights[merge.left]).max(0.), right, (height - heights[merge.right]).max(0.)));
+        heights[nb + i] = height;
+    }
+    let mut newick = subtrees.pop().unwrap();
+    newick.push(';');
+    Ok(newick)
+} // end of to_newick
+
+
+/// writes dendrogram as a Newick tree, see [to_newick]
+pub fn write_newick<W : Write>(writer : W, dendrogram : &Dendrogram, names : &[String]) -> Result<(), String> {
+    let newick = to_newick(dendrogram, names)?;
+    let mut writer = BufWriter::new(writer);
+    writeln!(writer, "{}", newick).map_err(|e| e.to_string())?;
+    writer.flush().map_err(|e| e.to_string())
+}
+
+
+/// dumps dendrogram in a Newick file, see [to_newick]
+pub fn dump_newick(path : &Path, dendrogram : &Dendrogram, names : &[String]) -> Result<(), String> {
+    log::info!("dumping dendrogram in newick file {:?}", path);
+    write_newick(create_file(path)?, dendrogram, names)
+}
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use crate::sketcharg::{SketchAlgo, DataType};
+    use crate::sketching::distances::compute_distance_matrix;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    // 3 groups of 5 signatures, members of a group being copies of a center with 1 slot out of 50 changed
+    fn grouped_signatures() -> Vec<Vec<u64>> {
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(3112);
+        let mut signatures = Vec::<Vec<u64>>::new();
+        for _ in 0..3 {
+            let center : Vec<u64> = (0..500).map(|_| rng.gen()).collect();
+            for _ in 0..5 {
+                signatures.push(center.iter().map(|v| if rng.gen_range(0..50) == 0 { rng.gen() } else { *v }).collect());
+            }
+        }
+        signatures
+    }
+
+    #[test]
+    fn test_hierarchical_clustering() {
+        log_init_test();
+        let signatures = grouped_signatures();
+        let matrix = compute_distance_matrix::<u64, f64>(&signatures, DistanceKind::JaccardDistance).unwrap();
+        for linkage in [Linkage::Single, Linkage::Average] {
+            let dendrogram = hierarchical_clustering(&matrix, linkage);
+            let merges = dendrogram.get_merges();
+            assert_eq!(merges.len(), 14);
+            assert!(merges.windows(2).all(|w| w[0].distance <= w[1].distance));
+            assert_eq!(merges[13].size, 15);
+            assert_eq!(dendrogram.cut(0.5), vec![0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2]);
+            assert_eq!(dendrogram.cut(1.), vec![0; 15]);
+            assert_eq!(dendrogram.cut(-1.), (0..15).collect::<Vec<usize>>());
+            let clustering = medoid_clustering(&matrix, dendrogram.cut(0.5));
+            assert_eq!(clustering.get_nb_clusters(), 3);
+            assert_eq!(clustering.get_members(1), vec![5, 6, 7, 8, 9]);
+            assert!(clustering.representatives.iter().enumerate().all(|(c, r)| clustering.assignments[*r] == c));
+        }
+        // check single linkage merge distances against minimal distances between groups
+        let dendrogram = hierarchical_clustering(&matrix, Linkage::Single);
+        let min_between : f64 = (0..5).flat_map(|i| (5..10).map(move |j| (i, j))).map(|(i, j)| matrix.get(i, j)).fold(f64::MAX, f64::min);
+        let merge = dendrogram.get_merges().iter().find(|m| m.size == 10).unwrap();
+        assert!(merge.distance <= min_between);
+        assert_eq!(hierarchical_clustering(&compute_distance_matrix::<u64, f64>(&signatures[..1], DistanceKind::Jaccard).unwrap(), Linkage::Single).cut(0.), vec![0]);
+    } // end of test_hierarchical_clustering
+
+    #[test]
+    fn test_greedy_clustering() {
+        log_init_test();
+        let signatures = grouped_signatures();
+        let params = SeqSketcherParams::new(21, 500, SketchAlgo::PROB3A, DataType::DNA);
+        let clustering = greedy_clustering(&signatures, &params, 0.95, None).unwrap();
+        assert_eq!(clustering.representatives, vec![0, 5, 10]);
+        assert_eq!(clustering.assignments, vec![0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2]);
+        // visiting in reverse order gives last of each group as representative
+        let order : Vec<usize> = (0..15).rev().collect();
+        let clustering = greedy_clustering(&signatures, &params, 0.95, Some(&order)).unwrap();
+        assert_eq!(clustering.representatives, vec![14, 9, 4]);
+        assert_eq!(clustering.get_members(2), vec![0, 1, 2, 3, 4]);
+        // at identity 1 no genome is dereplicated
+        assert_eq!(greedy_clustering(&signatures, &params, 1., None).unwrap().get_nb_clusters(), 15);
+        assert!(greedy_clustering(&signatures, &params, 0.95, Some(&[0, 1])).is_err());
+    } // end of test_greedy_clustering
+
+    #[test]
+    fn test_newick() {
+        log_init_test();
+        let signatures : Vec<Vec<u32>> = vec![vec![1, 2, 3, 4], vec![1, 2, 5, 6], vec![1, 7, 8, 9]];
+        let names : Vec<String> = vec!["a".into(), "genome b".into(), "c:1".into()];
+        let matrix = compute_distance_matrix::<u32, f64>(&signatures, DistanceKind::JaccardDistance).unwrap();
+        let dendrogram = hierarchical_clustering(&matrix, Linkage::Average);
+        // a and b merged at 0.5, then c at 0.75
+        assert_eq!(to_newick(&dendrogram, &names).unwrap(), "(c_1:0.375,(a:0.25,genome_b:0.25):0.125);");
+        let mut buffer = Vec::<u8>::new();
+        write_newick(&mut buffer, &dendrogram, &names).unwrap();
+        assert!(String::from_utf8(buffer).unwrap().ends_with(";\n"));
+        let single = hierarchical_clustering(&compute_distance_matrix::<u32, f64>(&signatures[..1], DistanceKind::JaccardDistance).unwrap(), Linkage::Single);
+        assert_eq!(to_newick(&single, &names[..1]).unwrap(), "a;");
+        assert!(to_newick(&dendrogram, &names[..2]).is_err());
+    } // end of test_newick
+
+} // end of mod tests
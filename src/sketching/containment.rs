@@ -0,0 +1,213 @@
+//! This module estimates the containment index C(A in B) = |A ∩ B| / |A| between two kmer sets from their sketches.
+//!
+//! Contrary to Jaccard, containment is asymmetric and answers questions such as "how much of genome A is in metagenome B"
+//! even when B is much larger than A.
+//!
+//! Sketches are sets of hash values :
+//! - bottom-k sketches (the k smallest hashes, as kept by [MinHashCount](super::minhash::MinHashCount)).
+//!   Only hashes below the smallest of the two sketch maxima are compared, as above it the bottom-k of the big set is not known.
+//! - FracMinHash sketches (all hashes below u64::MAX / scale, see [fracminhash_sketch]). Sketches of different sizes are compared directly,
+//!   every hash of the small sketch being looked up in the big one, see [fracminhash_containment].
+//!
+//! Containment is converted to ANI by C^(1/k) (see [ani_from_containment](super::mashdist::ani_from_containment)).
+//!
+//! Fixed size signatures (ProbMinHash, SuperMinHash ...) only estimate Jaccard, containment is then deduced from Jaccard
+//! and the sizes of the sets with [containment_from_jaccard].
+
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashed::{HashCount, SketchHashT};
+use super::mashdist::ani_from_containment;
+
+
+/// containment of a small set in a big set estimated from sketches
+#[derive(Copy,Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct ContainmentEstimate {
+    /// estimate of |A ∩ B| / |A|
+    pub containment : f64,
+    /// number of hashes of the small sketch found in the big sketch
+    pub common : usize,
+    /// number of hashes of the small sketch compared
+    pub nb_compared : usize,
+    /// ANI deduced from containment
+    pub ani : f64,
+}
+
+
+
+// returns number of hashes of small compared, and the number of them in big. Slices must be sorted and deduplicated.
+// Bottom-k sketches are compared below the smallest of their maxima, FracMinHash sketches (truncate = false) on all hashes of small.
+fn count_common<H : Ord>(small : &[H], big : &[H], truncate : bool) -> (usize, usize) {
+    let threshold = match (small.last(), big.last()) {
+        (Some(s), Some(b)) => if truncate { s.min(b) } else { s },
+        (Some(_), None) if !truncate => return (0, small.len()),
+        _ => return (0, 0),
+    };
+    let mut common = 0;
+    let mut nb_compared = 0;
+    let mut j = 0;
+    for h in small.iter().take_while(|h| *h <= threshold) {
+        nb_compared += 1;
+        while j < big.len() && big[j] < *h {
+            j += 1;
+        }
+        if j < big.len() && big[j] == *h {
+            common += 1;
+        }
+    }
+    (common, nb_compared)
+} // end of count_common
+
+
+fn sorted_dedup<H : Ord + Copy>(sig : &[H]) -> Vec<H> {
+    let mut v = sig.to_vec();
+    v.sort_unstable();
+    v.dedup();
+    v
+}
+
+
+fn estimate<H : Ord + Copy>(sig_small : &[H], sig_big : &[H], truncate : bool, kmer_size : usize) -> ContainmentEstimate {
+    let (common, nb_compared) = count_common(&sorted_dedup(sig_small), &sorted_dedup(sig_big), truncate);
+    let containment = if nb_compared == 0 { 0. } else { common as f64 / nb_compared as f64 };
+    ContainmentEstimate{containment, common, nb_compared, ani : ani_from_containment(containment, kmer_size)}
+}
+
+
+/// returns the containment of sig_small in sig_big, where signatures are bottom-k sketches (hashes in any order).
+/// Returns 0. if no hash can be compared.
+pub fn containment<H : Ord + Copy>(sig_small : &[H], sig_big : &[H]) -> f64 {
+    let (common, nb_compared) = count_common(&sorted_dedup(sig_small), &sorted_dedup(sig_big), true);
+    if nb_compared == 0 { 0. } else { common as f64 / nb_compared as f64 }
+}
+
+
+/// returns containment of bottom-k sketch sig_small in sig_big, with matches count and ANI for kmers of size kmer_size.
+pub fn containment_estimate<H : Ord + Copy>(sig_small : &[H], sig_big : &[H], kmer_size : usize) -> ContainmentEstimate {
+    estimate(sig_small, sig_big, true, kmer_size)
+} // end of containment_estimate
+
+
+/// returns the containment of sig_small in sig_big, where signatures are FracMinHash sketches with the same scale.
+/// All hashes of sig_small are compared, a hash above the maximum of sig_big being a miss. Returns 0. for an empty sig_small.
+pub fn fracminhash_containment<H : Ord + Copy>(sig_small : &[H], sig_big : &[H]) -> f64 {
+    let (common, nb_compared) = count_common(&sorted_dedup(sig_small), &sorted_dedup(sig_big), false);
+    if nb_compared == 0 { 0. } else { common as f64 / nb_compared as f64 }
+}
+
+
+/// returns containment of FracMinHash sketch sig_small in sig_big, with matches count and ANI for kmers of size kmer_size.
+pub fn fracminhash_containment_estimate<H : Ord + Copy>(sig_small : &[H], sig_big : &[H], kmer_size : usize) -> ContainmentEstimate {
+    estimate(sig_small, sig_big, false, kmer_size)
+} // end of fracminhash_containment_estimate
+
+
+/// containment estimate between sketches returned by [MinHashCount::get_sketchcount](super::minhash::MinHashCount::get_sketchcount)
+pub fn containment_hashcount<T : Clone + Copy, Hv : SketchHashT>(sketch_small : &[HashCount<T, Hv>], sketch_big : &[HashCount<T, Hv>], kmer_size : usize) -> ContainmentEstimate {
+    let small : Vec<Hv> = sketch_small.iter().map(|h| h.hashed.hash).collect();
+    let big : Vec<Hv> = sketch_big.iter().map(|h| h.hashed.hash).collect();
+    containment_estimate(&small, &big, kmer_size)
+}
+
+
+/// returns the containment of a set of size size_small in a set of size size_big, deduced from their Jaccard index :
+/// |A ∩ B| = J (|A| + |B|) / (1 + J). Returns 0. for an empty small set.
+pub fn containment_from_jaccard(jaccard : f64, size_small : u64, size_big : u64) -> f64 {
+    if size_small == 0 {
+        return 0.;
+    }
+    let intersection = jaccard * (size_small + size_big) as f64 / (1. + jaccard);
+    (intersection / size_small as f64).clamp(0., 1.)
+}
+
+
+/// builds a FracMinHash sketch : the sorted distinct hashes below u64::MAX / scale, keeping about 1 hash out of scale.
+pub fn fracminhash_sketch<I : IntoIterator<Item = u64>>(hashes : I, scale : u64) -> Vec<u64> {
+    assert!(scale > 0, "scale must be > 0");
+    let threshold = u64::MAX / scale;
+    let mut sketch : Vec<u64> = hashes.into_iter().filter(|h| *h <= threshold).collect();
+    sketch.sort_unstable();
+    sketch.dedup();
+    sketch
+} // end of fracminhash_sketch
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    fn bottom_k(set : &[u64], k : usize) -> Vec<u64> {
+        let mut v = set.to_vec();
+        v.sort_unstable();
+        v.truncate(k);
+        v
+    }
+
+    #[test]
+    fn test_containment() {
+        log_init_test();
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(4664397);
+        // A has 10000 hashes, 3000 of them in B which has 100000 hashes : exact containment of A in B is 0.3
+        let a : Vec<u64> = (0..10_000).map(|_| rng.gen::<u64>()).collect();
+        let mut b : Vec<u64> = (0..97_000).map(|_| rng.gen::<u64>()).collect();
+        b.extend_from_slice(&a[..3000]);
+        //
+        let c = containment(&bottom_k(&a, 2000), &bottom_k(&b, 2000));
+        log::info!("bottom-k containment : {}", c);
+        assert!((c - 0.3).abs() < 0.1);
+        let c_rev = containment(&bottom_k(&b, 2000), &bottom_k(&a, 2000));
+        assert!((c_rev - 0.03).abs() < 0.02);
+        //
+        let frac_a = fracminhash_sketch(a.iter().copied(), 20);
+        let frac_b = fracminhash_sketch(b.iter().copied(), 20);
+        let estimate = fracminhash_containment_estimate(&frac_a, &frac_b, 21);
+        log::info!("fracminhash containment : {:?}", estimate);
+        assert!((estimate.containment - 0.3).abs() < 0.1);
+        assert_eq!(estimate.nb_compared, frac_a.len());
+        assert!((estimate.ani - 0.3f64.powf(1. / 21.)).abs() < 0.01);
+        // identical sets
+        assert_eq!(fracminhash_containment(&frac_a, &frac_a), 1.);
+        assert_eq!(fracminhash_containment::<u64>(&[], &frac_a), 0.);
+        assert_eq!(fracminhash_containment(&frac_a, &[]), 0.);
+        // A of size 1000 in B of size 10000 : J = 1000 / 10000
+        assert!((containment_from_jaccard(0.1, 1000, 10_000) - 1.).abs() < 1.0e-10);
+        assert!((containment_from_jaccard(0.1, 10_000, 1000) - 0.1).abs() < 1.0e-10);
+        assert_eq!(containment_from_jaccard(0.5, 0, 1000), 0.);
+    } // end of test_containment
+
+    #[test]
+    fn test_fracminhash_short_big_sketch() {
+        log_init_test();
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(3095);
+        // A has 100000 hashes. B keeps the hashes of 10000 of them below u64::MAX / 1000 and 300 other hashes below this bound,
+        // so that the FracMinHash sketch of B (scale 100) is much shorter than the sketch of A and has a low maximum.
+        let a : Vec<u64> = (0..100_000).map(|_| rng.gen::<u64>()).collect();
+        let mut b : Vec<u64> = a[..10_000].iter().filter(|h| **h < u64::MAX / 1000).copied().collect();
+        b.extend((0..300).map(|_| rng.gen_range(0..u64::MAX / 1000)));
+        let frac_a = fracminhash_sketch(a.iter().copied(), 100);
+        let frac_b = fracminhash_sketch(b.iter().copied(), 100);
+        assert!(frac_b.len() > 200 && frac_b.len() < frac_a.len() / 2);
+        // about 10 common hashes out of about 1000 hashes of A
+        let estimate = fracminhash_containment_estimate(&frac_a, &frac_b, 21);
+        log::info!("fracminhash containment with short big sketch : {:?}", estimate);
+        assert_eq!(estimate.nb_compared, frac_a.len());
+        assert!(estimate.containment < 0.03);
+        // truncation at the maximum of B only compares the hashes of A below u64::MAX / 1000 and overestimates containment
+        let truncated = containment_estimate(&frac_a, &frac_b, 21);
+        assert_eq!(truncated.common, estimate.common);
+        assert!(truncated.nb_compared < frac_a.len() / 5);
+        assert!(truncated.containment > 3. * estimate.containment);
+    } // end of test_fracminhash_short_big_sketch
+
+} // end of mod tests
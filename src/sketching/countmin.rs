@@ -0,0 +1,298 @@
+//! This module provides a ProbMinHash sketching mode with bounded memory for enormous sequence collections
+//! (a metagenome assembly of 10 Gbp has billions of distinct kmers and its exact kmer weight map does not fit in memory).
+//!
+//! Kmer multiplicities are estimated by a Count-Min sketch (Cormode and Muthukrishnan 2005) of depth rows of width counters,
+//! updated conservatively. Each kmer occurrence is sent to a ProbMinHash3 sketcher with its current estimated count as weight.
+//! For a given kmer ProbMinHash3 generates the same random values at each call, scaled by the inverse of the weight, so
+//! sending a kmer several times with increasing weights gives the same signature as sending it once with its final weight.
+//! As ProbMinHash3 and ProbMinHash3a generate the same values, the signature is the one of [ProbHash3aSketch] on the estimated weights.
+//!
+//! **Accuracy trade-off**. Count-Min estimates never underestimate counts. With a probability at least 1 - exp(-depth)
+//! the overestimation of a count is at most e * N / width where N is the number of kmers sketched.
+//! - if width is well above the number of distinct kmers, estimates are nearly exact and the signature is that of the exact weights,
+//! - otherwise weights of rare kmers are inflated by collisions, weights flatten and the probability Jaccard index
+//!   between two signatures moves toward the (unweighted) Jaccard index of kmer sets.
+//!
+//! Memory is 4 * depth * width bytes whatever the number of kmers, time is one Count-Min update and one ProbMinHash3 item
+//! by kmer occurrence, on one thread.
+//! The max_count of an abundance filter cannot be applied : a kmer already sketched cannot be removed when its count exceeds max_count.
+//! Kmers are sent to the sketcher only once their count reaches min_count (2 with singleton removal).
+
+
+use std::fmt::Debug;
+
+use serde::{Deserialize, Serialize};
+
+use probminhash::probminhasher::ProbMinHash3;
+
+use crate::base::{kmer::*, kmergenerator::*};
+use crate::nohasher::NoHashHasher;
+use crate::sketcharg::SeqSketcherParams;
+
+use super::seqsketchgen::{KmerSequence, hash_kmers};
+use super::setsketchert::ProbHash3aSketch;
+
+
+/// default number of rows of a Count-Min sketch
+pub const DEFAULT_COUNTMIN_DEPTH : usize = 4;
+
+/// default number of counters by row of a Count-Min sketch (with default depth the sketch needs 256 Mb)
+pub const DEFAULT_COUNTMIN_WIDTH : usize = 1 << 24;
+
+// seeds of row hash functions, at most 16 rows
+const ROW_SEEDS : [u64; 16] = [0x9e37_79b9_7f4a_7c15, 0xbf58_476d_1ce4_e5b9, 0x94d0_49bb_1331_11eb, 0x2545_f491_4f6c_dd1d,
+                               0x243f_6a88_85a3_08d3, 0x1319_8a2e_0370_7344, 0xa409_3822_299f_31d0, 0x082e_fa98_ec4e_6c89,
+                               0x4528_21e6_38d0_1377, 0xbe54_66cf_34e9_0c6c, 0xc0ac_29b7_c97c_50dd, 0x3f84_d5b5_b547_0917,
+                               0x9216_d5d9_8979_fb1b, 0xd131_0ba6_98df_b5ac, 0x2ffd_72db_d01a_dfb7, 0xb8e1_afed_6a26_7e96];
+
+
+/// Dimensions of a Count-Min sketch
+#[derive(Serialize,Deserialize,Copy,Clone,Debug,PartialEq,Eq)]
+pub struct CountMinParams {
+    /// number of rows (hash functions), between 1 and 16
+    depth : usize,
+    /// number of counters by row, a power of 2
+    width : usize,
+}
+
+
+impl CountMinParams {
+    /// depth must be between 1 and 16, width is rounded to the next power of 2.
+    pub fn new(depth : usize, width : usize) -> Result<Self, String> {
+        if depth == 0 || depth > ROW_SEEDS.len() {
+            log::error!("CountMinParams : depth {} must be between 1 and {}", depth, ROW_SEEDS.len());
+            return Err(format!("Count-Min depth {} must be between 1 and {}", depth, ROW_SEEDS.len()));
+        }
+        if width == 0 {
+            log::error!("CountMinParams : width must be positive");
+            return Err(String::from("Count-Min width must be positive"));
+        }
+        Ok(CountMinParams{depth, width : width.next_power_of_two()})
+    }
+
+    /// largest sketch of default depth fitting in nb_bytes
+    pub fn with_memory(nb_bytes : usize) -> Result<Self, String> {
+        let width = nb_bytes / (DEFAULT_COUNTMIN_DEPTH * std::mem::size_of::<u32>());
+        if width == 0 {
+            log::error!("CountMinParams : {} bytes cannot hold a Count-Min sketch", nb_bytes);
+            return Err(format!("{} bytes cannot hold a Count-Min sketch", nb_bytes));
+        }
+        // largest power of 2 below width
+        CountMinParams::new(DEFAULT_COUNTMIN_DEPTH, 1 << width.ilog2())
+    }
+
+    /// returns the number of rows
+    pub fn get_depth(&self) -> usize {
+        self.depth
+    }
+
+    /// returns the number of counters by row
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    /// returns the memory needed by counters in bytes
+    pub fn get_memory(&self) -> usize {
+        self.depth * self.width * std::mem::size_of::<u32>()
+    }
+} // end of impl CountMinParams
+
+
+impl Default for CountMinParams {
+    fn default() -> Self {
+        CountMinParams{depth : DEFAULT_COUNTMIN_DEPTH, width : DEFAULT_COUNTMIN_WIDTH}
+    }
+}
+
+
+
+/// A Count-Min sketch of u64 values with conservative update, counters saturating at u32::MAX.
+pub struct CountMinSketch {
+    params : CountMinParams,
+    /// depth rows of width counters
+    counters : Vec<u32>,
+}
+
+
+impl CountMinSketch {
+    pub fn new(params : &CountMinParams) -> Self {
+        CountMinSketch{params : *params, counters : vec![0u32; params.depth * params.width]}
+    }
+
+    // index of value in counters of row
+    #[inline]
+    fn get_index(&self, row : usize, val : u64) -> usize {
+        // splitmix64 finalizer of the seeded value
+        let mut h = val ^ ROW_SEEDS[row];
+        h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        h ^= h >> 31;
+        row * self.params.width + (h as usize & (self.params.width - 1))
+    }
+
+    /// records one occurrence of val and returns its estimated count.
+    /// Only the counters equal to the minimum are incremented (conservative update).
+    #[inline]
+    pub fn add(&mut self, val : u64) -> u32 {
+        let mut indexes = [0usize; 16];
+        let mut estimate = u32::MAX;
+        for (row, index) in indexes.iter_mut().enumerate().take(self.params.depth) {
+            *index = self.get_index(row, val);
+            estimate = estimate.min(self.counters[*index]);
+        }
+        let estimate = estimate.saturating_add(1);
+        for index in &indexes[..self.params.depth] {
+            let counter = &mut self.counters[*index];
+            *counter = (*counter).max(estimate);
+        }
+        estimate
+    } // end of add
+
+    /// returns the estimated count of val, never below its true count
+    pub fn estimate(&self, val : u64) -> u32 {
+        (0..self.params.depth).map(|row| self.counters[self.get_index(row, val)]).min().unwrap()
+    }
+
+    /// returns the dimensions of the sketch
+    pub fn get_params(&self) -> &CountMinParams {
+        &self.params
+    }
+} // end of impl CountMinSketch
+
+
+
+/// ProbMinHash sketcher fed with kmer counts estimated by a [CountMinSketch], see the module documentation.
+/// Sequences can be added in several calls (chunks of a stream), the signature is that of all kmers added.
+pub struct CountMinProbHash<V>
+        where V : num::PrimInt + Debug + std::hash::Hash {
+    counts : CountMinSketch,
+    pminhash : ProbMinHash3<V, NoHashHasher>,
+    kmer_size : usize,
+    min_count : u32,
+    nb_kmers : u64,
+} // end of CountMinProbHash
+
+
+impl <V> CountMinProbHash<V>
+        where V : num::PrimInt + Debug + std::hash::Hash {
+    /// params gives kmer size, sketch size and abundance filter (only min_count and singleton removal are applied).
+    pub fn new(params : &SeqSketcherParams, cm_params : &CountMinParams) -> Self {
+        if params.get_max_count() < u32::MAX {
+            log::warn!("CountMinProbHash : max_count {} of abundance filter is not applied", params.get_max_count());
+        }
+        let min_count = if params.get_singleton_removal() { params.get_min_count().max(2) } else { params.get_min_count() };
+        log::info!("CountMinProbHash : Count-Min depth {}, width {}, memory {} Mb", cm_params.get_depth(), cm_params.get_width(), cm_params.get_memory() >> 20);
+        CountMinProbHash{counts : CountMinSketch::new(cm_params), pminhash : ProbMinHash3::new(params.get_sketch_size(), V::zero()),
+                kmer_size : params.get_kmer_size(), min_count : min_count.max(1), nb_kmers : 0}
+    }
+
+    /// adds the kmers of sequences. fhash must be the same for all calls.
+    pub fn add_seqs<Kmer, Seq, F>(&mut self, vseq : &[&Seq], fhash : &F)
+            where   Kmer : CompressedKmerT<Val = V>,
+                    Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> V {
+        let counts = &mut self.counts;
+        let pminhash = &mut self.pminhash;
+        let min_count = self.min_count;
+        let mut nb_kmers = 0u64;
+        hash_kmers(vseq, self.kmer_size, fhash, None, |_, hashval| {
+            nb_kmers += 1;
+            let count = counts.add(hashval.to_u64().unwrap());
+            if count >= min_count {
+                pminhash.hash_item(hashval, &count);
+            }
+        });
+        self.nb_kmers += nb_kmers;
+    } // end of add_seqs
+
+    /// returns the number of kmers added
+    pub fn get_nb_kmers(&self) -> u64 {
+        self.nb_kmers
+    }
+
+    /// returns the signature of all kmers added
+    pub fn get_signature(&self) -> Vec<V> {
+        self.pminhash.get_signature().clone()
+    }
+} // end of impl CountMinProbHash
+
+
+
+impl <Kmer> ProbHash3aSketch<Kmer>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                Kmer::Val : num::PrimInt + Debug + std::hash::Hash {
+    /// sketches sequences in one signature with kmer counts estimated by a Count-Min sketch of dimensions cm_params,
+    /// so that memory does not depend on the number of distinct kmers. See [countmin](crate::sketching::countmin) for the accuracy trade-off.
+    pub fn sketch_compressedkmer_countmin<F>(&self, vseq : &[&Sequence], cm_params : &CountMinParams, fhash : F) -> Vec<Kmer::Val>
+            where F : Fn(&Kmer) -> Kmer::Val {
+        let mut sketcher = CountMinProbHash::<Kmer::Val>::new(self.get_params(), cm_params);
+        sketcher.add_seqs(vseq, &fhash);
+        sketcher.get_signature()
+    } // end of sketch_compressedkmer_countmin
+}
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sketcharg::{SketchAlgo, DataType};
+    use crate::sketching::setsketchert::SeqSketcherT;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_countmin_sketch() {
+        log_init_test();
+        assert!(CountMinParams::new(0, 16).is_err());
+        assert!(CountMinParams::new(17, 16).is_err());
+        assert_eq!(CountMinParams::new(4, 1000).unwrap().get_width(), 1024);
+        assert_eq!(CountMinParams::with_memory(1 << 20).unwrap().get_memory(), 1 << 20);
+        // estimates never underestimate, and are exact with few values
+        let mut cm = CountMinSketch::new(&CountMinParams::new(4, 1 << 12).unwrap());
+        for val in 0..100u64 {
+            for i in 0..=val % 5 {
+                assert_eq!(cm.add(val * 7919), i as u32 + 1);
+            }
+        }
+        assert!((0..100u64).all(|val| cm.estimate(val * 7919) == (val % 5) as u32 + 1));
+        // a narrow sketch overestimates
+        let mut cm = CountMinSketch::new(&CountMinParams::new(2, 16).unwrap());
+        (0..1000u64).for_each(|val| { cm.add(val); });
+        assert!((0..1000u64).all(|val| cm.estimate(val) >= 1));
+        assert!((0..1000u64).any(|val| cm.estimate(val) > 1));
+    } // end of test_countmin_sketch
+
+
+    #[test]
+    fn test_countmin_probhash() {
+        log_init_test();
+        let seqs = vec![Sequence::new(b"ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGC", 2),
+                        Sequence::new(b"TTTAGAAAATTTCCGGATCATCGTACGG", 2), Sequence::new(b"GCATGCGTACAACGTCG", 2)];
+        let vseq : Vec<&Sequence> = seqs.iter().collect();
+        let fhash = |kmer : &Kmer32bit| kmer.get_compressed_value();
+        let mut params = SeqSketcherParams::new(7, 50, SketchAlgo::PROB3A, DataType::DNA);
+        let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&params);
+        // with a wide sketch counts are exact and the signature is the one of exact weights
+        let cm_params = CountMinParams::new(4, 1 << 16).unwrap();
+        let exact = sketcher.sketch_compressedkmer_seqs(&vseq, fhash).unwrap().pop().unwrap();
+        assert_eq!(sketcher.sketch_compressedkmer_countmin(&vseq, &cm_params, fhash), exact);
+        // sequences can be added in chunks
+        let mut cmsketch = CountMinProbHash::<u32>::new(&params, &cm_params);
+        cmsketch.add_seqs(&vseq[..1], &fhash);
+        cmsketch.add_seqs(&vseq[1..], &fhash);
+        assert_eq!(cmsketch.get_signature(), exact);
+        assert_eq!(cmsketch.get_nb_kmers(), 54 + 22 + 11);
+        // singleton removal
+        params.set_singleton_removal(true);
+        let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&params);
+        assert_eq!(sketcher.sketch_compressedkmer_countmin(&vseq, &cm_params, fhash), sketcher.sketch_compressedkmer_seqs(&vseq, fhash).unwrap()[0]);
+    } // end of test_countmin_probhash
+
+} // end of mod tests
@@ -0,0 +1,225 @@
+//! This module writes signatures and pairwise comparison results in csv or tsv tables,
+//! so that outputs can be loaded directly with pandas (read_csv) or R (read.table).
+//!
+//! - signatures are written one row by sequence : id, seq_len, then one column by signature value (h0, h1, ...),
+//! - pairwise results are written one row by pair : query, target, jaccard, ani, common.
+//!
+//! The format is chosen from the file extension ([TableFormat::from_path]), .tsv and .tab giving tab separated values.
+//! Writing and reading tables needs the feature *formats* (default).
+
+
+#[cfg(feature="formats")]
+use std::fmt::Display;
+#[cfg(feature="formats")]
+use std::fs::OpenOptions;
+#[cfg(feature="formats")]
+use std::io::Write;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::blockcompare::count_equal;
+use super::mashdist::ani_from_jaccard;
+#[cfg(feature="formats")]
+use super::sketchcontainer::SketchMetadata;
+
+
+/// format of exported tables
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum TableFormat {
+    /// comma separated values
+    Csv,
+    /// tab separated values
+    Tsv,
+}
+
+impl TableFormat {
+    /// returns Tsv for files with extension tsv or tab, Csv otherwise
+    pub fn from_path(path : &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()) {
+            Some(ext) if ext == "tsv" || ext == "tab" => TableFormat::Tsv,
+            _ => TableFormat::Csv,
+        }
+    }
+
+    /// returns the field delimiter
+    pub fn get_delimiter(&self) -> u8 {
+        match self {
+            TableFormat::Csv => b',',
+            TableFormat::Tsv => b'\t',
+        }
+    }
+} // end of impl TableFormat
+
+
+
+/// result of the comparison of two signatures
+#[derive(Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct PairwiseResult {
+    /// name of query
+    pub query : String,
+    /// name of target
+    pub target : String,
+    /// jaccard index estimate
+    pub jaccard : f64,
+    /// average nucleotide identity estimate (average amino acid identity for amino acid sketches)
+    pub ani : f64,
+    /// number of signature values in common
+    pub common : usize,
+}
+
+
+impl PairwiseResult {
+    /// compares two signatures of same length, obtained with kmers of size kmer_size.
+    /// jaccard is estimated by the fraction of equal signature values and converted to ani
+    /// with the Mash formula (see [ani_from_jaccard]).
+    pub fn from_signatures<Sig : PartialEq>(query : &str, target : &str, sig_query : &[Sig], sig_target : &[Sig], kmer_size : usize) -> Self {
+        assert_eq!(sig_query.len(), sig_target.len(), "signatures must have same length");
+        Self::from_common(query, target, count_equal(sig_query, sig_target), sig_query.len(), kmer_size)
+    }
+
+    /// same as [Self::from_signatures], the number of equal values of signatures of size sketch_size being already counted
+    /// (see [blockcompare](super::blockcompare)).
+    pub fn from_common(query : &str, target : &str, common : usize, sketch_size : usize, kmer_size : usize) -> Self {
+        let jaccard = if sketch_size == 0 { 0. } else { common as f64 / sketch_size as f64 };
+        let ani = ani_from_jaccard(jaccard, kmer_size);
+        PairwiseResult{query : query.to_string(), target : target.to_string(), jaccard, ani, common}
+    }
+} // end of impl PairwiseResult
+
+
+
+// opens a file for writing
+#[cfg(feature="formats")]
+fn create_file(path : &Path) -> Result<std::io::BufWriter<std::fs::File>, String> {
+    let file = OpenOptions::new().write(true).create(true).truncate(true).open(path).map_err(|e| {
+            log::error!("could not open file {:?} : {}", path.as_os_str(), e);
+            println!("could not open file {:?}", path.as_os_str());
+            e.to_string()
+        })?;
+    Ok(std::io::BufWriter::new(file))
+}
+
+
+/// writes signatures in a table on any writer. metadata\[i\] describes signatures\[i\]. Returns the number of rows written.
+#[cfg(feature="formats")]
+pub fn write_signatures<W : Write, Sig : Display>(writer : W, format : TableFormat, metadata : &[SketchMetadata], signatures : &[Vec<Sig>]) -> Result<usize, String> {
+    if metadata.len() != signatures.len() {
+        log::error!("write_signatures : {} metadata for {} signatures", metadata.len(), signatures.len());
+        return Err("write_signatures : metadata and signatures must have same length".to_string());
+    }
+    let mut writer = csv::WriterBuilder::new().delimiter(format.get_delimiter()).from_writer(writer);
+    let sketch_size = signatures.first().map(|s| s.len()).unwrap_or(0);
+    let mut header = vec!["id".to_string(), "seq_len".to_string()];
+    header.extend((0..sketch_size).map(|i| format!("h{}", i)));
+    writer.write_record(&header).map_err(|e| e.to_string())?;
+    for (m, sig) in metadata.iter().zip(signatures) {
+        if sig.len() != sketch_size {
+            log::error!("write_signatures : signature of {} has size {}, expected {}", m.name, sig.len(), sketch_size);
+            return Err("write_signatures : signatures must have same size".to_string());
+        }
+        let mut record = vec![m.name.clone(), m.seq_len.to_string()];
+        record.extend(sig.iter().map(|v| v.to_string()));
+        writer.write_record(&record).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+    Ok(signatures.len())
+} // end of write_signatures
+
+
+/// writes pairwise results in a table on any writer. Returns the number of rows written.
+#[cfg(feature="formats")]
+pub fn write_pairwise<W : Write>(writer : W, format : TableFormat, results : &[PairwiseResult]) -> Result<usize, String> {
+    let mut writer = csv::WriterBuilder::new().delimiter(format.get_delimiter()).from_writer(writer);
+    for res in results {
+        writer.serialize(res).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+    Ok(results.len())
+} // end of write_pairwise
+
+
+
+/// writes signatures in a csv or tsv file, format being deduced from extension. Returns the number of rows written.
+#[cfg(feature="formats")]
+pub fn export_signatures_table<Sig : Display>(path : &Path, metadata : &[SketchMetadata], signatures : &[Vec<Sig>]) -> Result<usize, String> {
+    //
+    log::info!("exporting {} signatures in table {:?}", signatures.len(), path);
+    //
+    let format = TableFormat::from_path(path);
+    let writer = create_file(path)?;
+    write_signatures(writer, format, metadata, signatures)
+} // end of export_signatures_table
+
+
+/// writes pairwise results in a csv or tsv file, format being deduced from extension. Returns the number of rows written.
+#[cfg(feature="formats")]
+pub fn export_pairwise_table(path : &Path, results : &[PairwiseResult]) -> Result<usize, String> {
+    //
+    log::info!("exporting {} pairwise results in table {:?}", results.len(), path);
+    //
+    let format = TableFormat::from_path(path);
+    let writer = create_file(path)?;
+    write_pairwise(writer, format, results)
+} // end of export_pairwise_table
+
+
+/// reloads pairwise results written by [export_pairwise_table]
+#[cfg(feature="formats")]
+pub fn reload_pairwise_table(path : &Path) -> Result<Vec<PairwiseResult>, String> {
+    let format = TableFormat::from_path(path);
+    let mut reader = csv::ReaderBuilder::new().delimiter(format.get_delimiter()).from_path(path).map_err(|e| {
+            log::error!("reload_pairwise_table could not open file {:?} : {}", path.as_os_str(), e);
+            e.to_string()
+        })?;
+    reader.deserialize().map(|r| r.map_err(|e| e.to_string())).collect()
+} // end of reload_pairwise_table
+
+
+//=========================================================================================
+
+
+#[cfg(all(test, feature="formats"))]
+mod tests {
+
+    use super::*;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_export_tables() {
+        log_init_test();
+        let dir = crate::test_dir("export_tables");
+        let signatures : Vec<Vec<u64>> = vec![vec![1, 2, 3, 4], vec![1, 2, 5, 6], vec![7, 8, 9, 10]];
+        let metadata : Vec<SketchMetadata> = ["a", "b,with comma", "c"].iter().enumerate()
+                    .map(|(i, n)| SketchMetadata{name : n.to_string(), seq_len : 100 * i as u64}).collect();
+        let mut buffer = Vec::<u8>::new();
+        assert_eq!(write_signatures(&mut buffer, TableFormat::Tsv, &metadata, &signatures).unwrap(), 3);
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(text.lines().next().unwrap(), "id\tseq_len\th0\th1\th2\th3");
+        assert_eq!(text.lines().nth(2).unwrap(), "b,with comma\t100\t1\t2\t5\t6");
+        //
+        let res = PairwiseResult::from_signatures("a", "b", &signatures[0], &signatures[1], 21);
+        assert_eq!((res.common, res.jaccard), (2, 0.5));
+        assert!((res.ani - (1. + (2. / 3f64).ln() / 21.)).abs() < 1.0e-10);
+        assert_eq!(PairwiseResult::from_signatures("a", "c", &signatures[0], &signatures[2], 21).ani, 0.);
+        //
+        let results : Vec<PairwiseResult> = (0..3).flat_map(|i| (0..3).map(move |j| (i, j)))
+                    .map(|(i, j) : (usize, usize)| PairwiseResult::from_signatures(&metadata[i].name, &metadata[j].name, &signatures[i], &signatures[j], 21))
+                    .collect();
+        for name in ["kmerutils_test_pairs.csv", "kmerutils_test_pairs.tsv"] {
+            let path = dir.join(name);
+            assert_eq!(export_pairwise_table(&path, &results).unwrap(), 9);
+            assert_eq!(reload_pairwise_table(&path).unwrap(), results);
+            let _ = std::fs::remove_file(&path);
+        }
+        let path = dir.join("kmerutils_test_sigs.csv");
+        export_signatures_table(&path, &metadata, &signatures).unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().contains("\"b,with comma\",100,1,2,5,6"));
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_export_tables
+
+} // end of mod tests
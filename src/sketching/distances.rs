@@ -0,0 +1,545 @@
+//! This module computes all-vs-all distance matrices between signatures.
+//!
+//! Signatures are those of ProbMinHash, SuperMinHash, OptDens ... where the Jaccard index is estimated by the
+//! fraction of equal slots. The matrix being symmetric, only the strict lower triangle is stored, row by row
+//! (condensed form, value (i,j) with j < i at index i(i-1)/2 + j).
+//!
+//! Rows are grouped in blocks of [BLOCK_SIZE] rows, each block owning a contiguous part of the condensed matrix,
+//! and blocks are computed in parallel with rayon. Inside a block, columns are also processed by blocks
+//! whose equal slots are counted by the kernel of [blockcompare](super::blockcompare), so that the signatures compared stay in cache.
+//!
+//! [compute_signature_distance_matrix] computes the matrix of any signature implementing [SignatureDistance]
+//! (b-bit, HyperLogLog, bottom-k ...), pair by pair with the same row blocks.
+//!
+//! Values are stored in f64 or f32 (halving memory for large N). [DistanceMatrix::get_estimate] gives the standard error
+//! and confidence interval of the Jaccard estimate of a pair.
+//!
+//! [bootstrap_distance_matrix] resamples sketch slots with replacement to give the bootstrap variability of each distance,
+//! and replicate matrices from which trees with support values can be built.
+//!
+//! Matrices can be written in PHYLIP format, lower triangle or square ([write_phylip]), for tree building tools
+//! (neighbor, fastme, rapidnj ...), or as a square table with a header row ([write_square_matrix]).
+
+
+use std::fmt::Display;
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use rand::{Rng, SeedableRng};
+use crate::parallel::*;
+use serde::{Deserialize, Serialize};
+
+use super::mashdist::{jaccard_from_mash_distance, mash_distance, JaccardEstimate};
+use super::progress::{ProgressCallback, ProgressStage, ProgressTracker};
+use super::cancel::CancelToken;
+use super::blockcompare::equal_counts_lower_tile;
+use super::sigdistance::SignatureDistance;
+
+
+/// number of rows (and columns) of a block
+pub const BLOCK_SIZE : usize = 64;
+
+
+/// value stored in the matrix
+#[derive(Copy,Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub enum DistanceKind {
+    /// Jaccard index (a similarity, 1 on diagonal)
+    Jaccard,
+    /// 1 - Jaccard index
+    JaccardDistance,
+    /// Mash distance for kmers of given size (see [mash_distance])
+    Mash(usize),
+}
+
+impl DistanceKind {
+    /// converts a Jaccard index
+    pub fn from_jaccard(&self, jaccard : f64) -> f64 {
+        match self {
+            DistanceKind::Jaccard => jaccard,
+            DistanceKind::JaccardDistance => 1. - jaccard,
+            DistanceKind::Mash(kmer_size) => mash_distance(jaccard, *kmer_size),
+        }
+    }
+
+    /// converts a value back to a Jaccard index
+    pub fn to_jaccard(&self, value : f64) -> f64 {
+        match self {
+            DistanceKind::Jaccard => value,
+            DistanceKind::JaccardDistance => 1. - value,
+            DistanceKind::Mash(kmer_size) => if value >= 1. { 0. } else { jaccard_from_mash_distance(value, *kmer_size) },
+        }
+    }
+
+    /// value between a signature and itself
+    pub fn get_diagonal(&self) -> f64 {
+        self.from_jaccard(1.)
+    }
+} // end of impl DistanceKind
+
+
+
+/// A symmetric matrix of distances stored in condensed form, F being f64 or f32.
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct DistanceMatrix<F> {
+    nb : usize,
+    kind : DistanceKind,
+    sketch_size : usize,
+    values : Vec<F>,
+}
+
+
+impl <F : num::Float> DistanceMatrix<F> {
+    /// returns number of rows
+    pub fn len(&self) -> usize {
+        self.nb
+    }
+
+    /// true if matrix has no row
+    pub fn is_empty(&self) -> bool {
+        self.nb == 0
+    }
+
+    /// returns kind of values stored
+    pub fn get_kind(&self) -> DistanceKind {
+        self.kind
+    }
+
+    /// returns value at (i,j)
+    pub fn get(&self, i : usize, j : usize) -> F {
+        assert!(i < self.nb && j < self.nb, "index out of matrix");
+        match i.cmp(&j) {
+            std::cmp::Ordering::Equal => F::from(self.kind.get_diagonal()).unwrap(),
+            std::cmp::Ordering::Greater => self.values[condensed_index(i, j)],
+            std::cmp::Ordering::Less => self.values[condensed_index(j, i)],
+        }
+    }
+
+    /// returns the Jaccard estimate at (i,j) with its standard error and confidence interval at normal quantile z.
+    pub fn get_estimate(&self, i : usize, j : usize, z : f64) -> JaccardEstimate {
+        let jaccard = self.kind.to_jaccard(self.get(i, j).to_f64().unwrap());
+        let common = (jaccard * self.sketch_size as f64).round() as usize;
+        JaccardEstimate::new(common.min(self.sketch_size), self.sketch_size, z)
+    }
+
+    /// returns the strict lower triangle, row by row
+    pub fn get_condensed(&self) -> &[F] {
+        &self.values
+    }
+
+    /// returns row i, diagonal included
+    pub fn get_row(&self, i : usize) -> Vec<F> {
+        (0..self.nb).map(|j| self.get(i, j)).collect()
+    }
+} // end of impl DistanceMatrix
+
+
+// index in condensed matrix of (i,j) with j < i
+fn condensed_index(i : usize, j : usize) -> usize {
+    i * (i - 1) / 2 + j
+}
+
+
+/// computes the matrix of distances of given kind between all pairs of signatures, in parallel.
+/// All signatures must have the same non null size.
+pub fn compute_distance_matrix<Sig, F>(signatures : &[Vec<Sig>], kind : DistanceKind) -> Result<DistanceMatrix<F>, String>
+        where   Sig : PartialEq + Sync,
+                F : num::Float + Send {
+    compute_distance_matrix_with_progress(signatures, kind, None)
+} // end of compute_distance_matrix
+
+
+
+/// same as [compute_distance_matrix], progress (in number of pairs) being reported to callback after each block of distances,
+/// see [progress](super::progress).
+pub fn compute_distance_matrix_with_progress<Sig, F>(signatures : &[Vec<Sig>], kind : DistanceKind, progress : Option<&ProgressCallback>) -> Result<DistanceMatrix<F>, String>
+        where   Sig : PartialEq + Sync,
+                F : num::Float + Send {
+    compute_distance_matrix_cancellable(signatures, kind, progress, &CancelToken::new())
+} // end of compute_distance_matrix_with_progress
+
+
+
+/// same as [compute_distance_matrix_with_progress], the computation being stopped (and an error returned)
+/// when cancel is cancelled, see [cancel](super::cancel). The token is checked before each block of distances.
+pub fn compute_distance_matrix_cancellable<Sig, F>(signatures : &[Vec<Sig>], kind : DistanceKind, progress : Option<&ProgressCallback>,
+            cancel : &CancelToken) -> Result<DistanceMatrix<F>, String>
+        where   Sig : PartialEq + Sync,
+                F : num::Float + Send {
+    //
+    let nb = signatures.len();
+    log::info!("compute_distance_matrix, nb signatures : {}, kind : {:?}", nb, kind);
+    //
+    let sketch_size = signatures.first().map(|s| s.len()).unwrap_or(1);
+    if sketch_size == 0 || signatures.iter().any(|s| s.len() != sketch_size) {
+        log::error!("compute_distance_matrix : signatures must have same non null size");
+        return Err("compute_distance_matrix : signatures must have same non null size".to_string());
+    }
+    let mut values = vec![F::zero(); nb * nb.saturating_sub(1) / 2];
+    let tracker = ProgressTracker::new(progress, ProgressStage::Distances, values.len());
+    split_row_blocks(&mut values, nb).into_par_iter().for_each(|(row_start, part)| {
+        let row_end = (row_start + BLOCK_SIZE).min(nb);
+        let offset = row_start * row_start.saturating_sub(1) / 2;
+        let mut counts = vec![0u32; BLOCK_SIZE * BLOCK_SIZE];
+        for col_start in (0..row_end).step_by(BLOCK_SIZE) {
+            if cancel.is_cancelled() {
+                return;
+            }
+            let col_block_end = (col_start + BLOCK_SIZE).min(row_end);
+            let tile_width = col_block_end - col_start;
+            let counts = &mut counts[..(row_end - row_start) * tile_width];
+            counts.fill(0);
+            equal_counts_lower_tile(signatures, row_start..row_end, col_start..col_block_end, counts);
+            let mut nb_pairs = 0;
+            for i in row_start..row_end {
+                let col_end = col_block_end.min(i);
+                for j in col_start..col_end {
+                    let jaccard = counts[(i - row_start) * tile_width + j - col_start] as f64 / sketch_size as f64;
+                    part[condensed_index(i, j) - offset] = F::from(kind.from_jaccard(jaccard)).unwrap();
+                }
+                nb_pairs += col_end.saturating_sub(col_start);
+            }
+            tracker.advance(nb_pairs, 0);
+        }
+    });
+    cancel.check("compute_distance_matrix")?;
+    Ok(DistanceMatrix{nb, kind, sketch_size, values})
+} // end of compute_distance_matrix_cancellable
+
+
+
+/// computes the matrix of distances of given kind between all pairs of signatures, the Jaccard index of a pair being given
+/// by [SignatureDistance::jaccard]. Signatures must not be empty, the sketch size of the matrix (used by [DistanceMatrix::get_estimate])
+/// is the smallest [SignatureDistance::get_sketch_size].
+/// For slot signatures (`Vec<u32>`, `Vec<u64>` ...) [compute_distance_matrix] gives the same values faster.
+pub fn compute_signature_distance_matrix<S, F>(signatures : &[S], kind : DistanceKind) -> Result<DistanceMatrix<F>, String>
+        where   S : SignatureDistance + Sync,
+                F : num::Float + Send {
+    //
+    let nb = signatures.len();
+    log::info!("compute_signature_distance_matrix, nb signatures : {}, kind : {:?}", nb, kind);
+    //
+    let sketch_size = signatures.iter().map(|s| s.get_sketch_size()).min().unwrap_or(1);
+    if sketch_size == 0 {
+        log::error!("compute_signature_distance_matrix : signatures must not be empty");
+        return Err("compute_signature_distance_matrix : signatures must not be empty".to_string());
+    }
+    let mut values = vec![F::zero(); nb * nb.saturating_sub(1) / 2];
+    split_row_blocks(&mut values, nb).into_par_iter().for_each(|(row_start, part)| {
+        let offset = row_start * row_start.saturating_sub(1) / 2;
+        for i in row_start..(row_start + BLOCK_SIZE).min(nb) {
+            for j in 0..i {
+                let jaccard = signatures[i].jaccard(&signatures[j]);
+                part[condensed_index(i, j) - offset] = F::from(kind.from_jaccard(jaccard)).unwrap();
+            }
+        }
+    });
+    Ok(DistanceMatrix{nb, kind, sketch_size, values})
+} // end of compute_signature_distance_matrix
+
+
+
+// splits the condensed matrix of nb rows in parts owned by blocks of BLOCK_SIZE rows, with the first row of each block
+fn split_row_blocks<F>(values : &mut [F], nb : usize) -> Vec<(usize, &mut [F])> {
+    let mut parts = Vec::<(usize, &mut [F])>::with_capacity(nb.div_ceil(BLOCK_SIZE));
+    let mut rest : &mut [F] = values;
+    for row_start in (0..nb).step_by(BLOCK_SIZE) {
+        let row_end = (row_start + BLOCK_SIZE).min(nb);
+        let part_len = row_end * row_end.saturating_sub(1) / 2 - row_start * row_start.saturating_sub(1) / 2;
+        let (part, tail) = rest.split_at_mut(part_len);
+        parts.push((row_start, part));
+        rest = tail;
+    }
+    parts
+} // end of split_row_blocks
+
+
+
+/// bootstrap replicates of a distance matrix
+pub struct DistanceBootstrap<F> {
+    /// one matrix by replicate
+    replicates : Vec<DistanceMatrix<F>>,
+    // standard deviation of replicate values, condensed form
+    std_dev : Vec<f64>,
+}
+
+impl <F : num::Float> DistanceBootstrap<F> {
+    /// returns number of replicates
+    pub fn get_nb_replicates(&self) -> usize {
+        self.replicates.len()
+    }
+
+    /// returns matrix of replicate b
+    pub fn get_replicate(&self, b : usize) -> &DistanceMatrix<F> {
+        &self.replicates[b]
+    }
+
+    /// returns the standard deviation of value (i,j) over replicates, 0 on diagonal
+    pub fn get_std_dev(&self, i : usize, j : usize) -> f64 {
+        match i.cmp(&j) {
+            std::cmp::Ordering::Equal => 0.,
+            std::cmp::Ordering::Greater => self.std_dev[condensed_index(i, j)],
+            std::cmp::Ordering::Less => self.std_dev[condensed_index(j, i)],
+        }
+    }
+
+    /// returns the percentile interval of value (i,j) over replicates, at confidence level in ]0, 1[ (0.95 for 95%)
+    pub fn get_interval(&self, i : usize, j : usize, level : f64) -> (f64, f64) {
+        assert!(level > 0. && level < 1., "level must be in ]0, 1[");
+        let mut values : Vec<f64> = self.replicates.iter().map(|m| m.get(i, j).to_f64().unwrap()).collect();
+        values.sort_by(|a, b| a.total_cmp(b));
+        let last = (values.len() - 1) as f64;
+        let low = ((1. - level) / 2. * last).round() as usize;
+        let high = ((1. + level) / 2. * last).round() as usize;
+        (values[low], values[high])
+    }
+} // end of impl DistanceBootstrap
+
+
+
+/// computes nb_replicates matrices, each on signatures whose slots are resampled with replacement
+/// (the same slots for all signatures of a replicate). seed makes the resampling reproducible.
+pub fn bootstrap_distance_matrix<Sig, F>(signatures : &[Vec<Sig>], kind : DistanceKind, nb_replicates : usize, seed : u64) -> Result<DistanceBootstrap<F>, String>
+        where   Sig : PartialEq + Clone + Sync,
+                F : num::Float + Send + Sync {
+    //
+    log::info!("bootstrap_distance_matrix, nb signatures : {}, nb replicates : {}", signatures.len(), nb_replicates);
+    //
+    if nb_replicates == 0 {
+        log::error!("bootstrap_distance_matrix : nb replicates must be > 0");
+        return Err("bootstrap_distance_matrix : nb replicates must be > 0".to_string());
+    }
+    let sketch_size = signatures.first().map(|s| s.len()).unwrap_or(1);
+    if sketch_size == 0 || signatures.iter().any(|s| s.len() != sketch_size) {
+        log::error!("bootstrap_distance_matrix : signatures must have same non null size");
+        return Err("bootstrap_distance_matrix : signatures must have same non null size".to_string());
+    }
+    let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed);
+    let mut replicates = Vec::<DistanceMatrix<F>>::with_capacity(nb_replicates);
+    for _ in 0..nb_replicates {
+        let slots : Vec<usize> = (0..sketch_size).map(|_| rng.gen_range(0..sketch_size)).collect();
+        let resampled : Vec<Vec<Sig>> = signatures.iter().map(|s| slots.iter().map(|k| s[*k].clone()).collect()).collect();
+        replicates.push(compute_distance_matrix(&resampled, kind)?);
+    }
+    let nb_values = replicates[0].values.len();
+    let std_dev : Vec<f64> = (0..nb_values).into_par_iter().map(|k| {
+            let mean = replicates.iter().map(|m| m.values[k].to_f64().unwrap()).sum::<f64>() / nb_replicates as f64;
+            let var = replicates.iter().map(|m| (m.values[k].to_f64().unwrap() - mean).powi(2)).sum::<f64>() / nb_replicates as f64;
+            var.sqrt()
+        }).collect();
+    Ok(DistanceBootstrap{replicates, std_dev})
+} // end of bootstrap_distance_matrix
+
+
+
+/// layout of PHYLIP output
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum PhylipLayout {
+    /// row i has the values (i,j) for j < i
+    LowerTriangle,
+    /// full rows, diagonal included
+    Square,
+}
+
+
+// PHYLIP names cannot contain blanks
+fn get_phylip_name(name : &str) -> String {
+    name.chars().map(|c| if c.is_whitespace() { '_' } else { c }).collect()
+}
+
+
+// checks names against matrix
+fn check_names<F : num::Float>(names : &[String], matrix : &DistanceMatrix<F>) -> Result<(), String> {
+    if names.len() != matrix.len() {
+        log::error!("{} names for a matrix of {} rows", names.len(), matrix.len());
+        return Err("names and matrix must have same length".to_string());
+    }
+    Ok(())
+}
+
+
+/// writes a matrix in relaxed PHYLIP format : the number of rows on the first line, then each row
+/// begins with its name (blanks replaced by _) followed by its values, separated by tabs.
+pub fn write_phylip<W : Write, F : num::Float + Display>(writer : W, names : &[String], matrix : &DistanceMatrix<F>, layout : PhylipLayout) -> Result<(), String> {
+    check_names(names, matrix)?;
+    let mut writer = BufWriter::new(writer);
+    writeln!(writer, "{}", matrix.len()).map_err(|e| e.to_string())?;
+    for (i, name) in names.iter().enumerate() {
+        write!(writer, "{}", get_phylip_name(name)).map_err(|e| e.to_string())?;
+        let nb_cols = match layout {
+            PhylipLayout::LowerTriangle => i,
+            PhylipLayout::Square => matrix.len(),
+        };
+        for j in 0..nb_cols {
+            write!(writer, "\t{}", matrix.get(i, j)).map_err(|e| e.to_string())?;
+        }
+        writeln!(writer).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+} // end of write_phylip
+
+
+/// writes a square matrix with a header row of names, each row beginning with its name. delimiter is typically b'\t' or b','.
+/// Names are quoted if needed (see [csvexport](super::csvexport)).
+#[cfg(feature="formats")]
+pub fn write_square_matrix<W : Write, F : num::Float + Display>(writer : W, names : &[String], matrix : &DistanceMatrix<F>, delimiter : u8) -> Result<(), String> {
+    check_names(names, matrix)?;
+    let mut writer = csv::WriterBuilder::new().delimiter(delimiter).from_writer(writer);
+    let mut header = vec![String::new()];
+    header.extend(names.iter().cloned());
+    writer.write_record(&header).map_err(|e| e.to_string())?;
+    for (i, name) in names.iter().enumerate() {
+        let mut record = vec![name.clone()];
+        record.extend((0..matrix.len()).map(|j| matrix.get(i, j).to_string()));
+        writer.write_record(&record).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+} // end of write_square_matrix
+
+
+// opens a file for writing
+pub(super) fn create_file(path : &Path) -> Result<std::fs::File, String> {
+    OpenOptions::new().write(true).create(true).truncate(true).open(path).map_err(|e| {
+            log::error!("could not open file {:?} : {}", path.as_os_str(), e);
+            println!("could not open file {:?}", path.as_os_str());
+            e.to_string()
+        })
+}
+
+
+/// dumps a matrix in a PHYLIP file, see [write_phylip]
+pub fn dump_phylip<F : num::Float + Display>(path : &Path, names : &[String], matrix : &DistanceMatrix<F>, layout : PhylipLayout) -> Result<(), String> {
+    log::info!("dumping distance matrix in phylip file {:?}", path);
+    write_phylip(create_file(path)?, names, matrix, layout)
+}
+
+
+/// dumps a square matrix in a file, tab separated if extension is tsv or tab, comma separated otherwise. See [write_square_matrix].
+#[cfg(feature="formats")]
+pub fn dump_square_matrix<F : num::Float + Display>(path : &Path, names : &[String], matrix : &DistanceMatrix<F>) -> Result<(), String> {
+    log::info!("dumping distance matrix in file {:?}", path);
+    let delimiter = super::csvexport::TableFormat::from_path(path).get_delimiter();
+    write_square_matrix(create_file(path)?, names, matrix, delimiter)
+}
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use crate::sketching::sigdistance::BbitSignature;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    // returns the fraction of equal slots
+    fn slot_jaccard<Sig : PartialEq>(sig1 : &[Sig], sig2 : &[Sig]) -> f64 {
+        super::super::blockcompare::count_equal(sig1, sig2) as f64 / sig1.len() as f64
+    }
+
+    #[test]
+    fn test_distance_matrix() {
+        log_init_test();
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(117);
+        // more than 2 blocks, values in a small range to get equal slots
+        let nb = 2 * BLOCK_SIZE + 17;
+        let signatures : Vec<Vec<u32>> = (0..nb).map(|_| (0..50).map(|_| rng.gen_range(0..4)).collect()).collect();
+        let matrix = compute_distance_matrix::<u32, f64>(&signatures, DistanceKind::JaccardDistance).unwrap();
+        assert_eq!(matrix.len(), nb);
+        assert_eq!(matrix.get_condensed().len(), nb * (nb - 1) / 2);
+        for i in 0..nb {
+            assert_eq!(matrix.get(i, i), 0.);
+            for j in 0..i {
+                let expected = 1. - slot_jaccard(&signatures[i], &signatures[j]);
+                assert_eq!(matrix.get(i, j), expected);
+                assert_eq!(matrix.get(j, i), expected);
+            }
+        }
+        let matrix32 = compute_distance_matrix::<u32, f32>(&signatures, DistanceKind::Mash(21)).unwrap();
+        assert!((matrix32.get(3, nb - 1) as f64 - mash_distance(slot_jaccard(&signatures[3], &signatures[nb - 1]), 21)).abs() < 1.0e-6);
+        assert_eq!(matrix32.get_row(5).len(), nb);
+        let estimate = matrix32.get_estimate(3, nb - 1, crate::sketching::mashdist::Z_95);
+        assert_eq!(estimate, JaccardEstimate::from_signatures(&signatures[3], &signatures[nb - 1], crate::sketching::mashdist::Z_95));
+        //
+        assert!(compute_distance_matrix::<u32, f64>(&[vec![1, 2], vec![1]], DistanceKind::Jaccard).is_err());
+        assert!(compute_distance_matrix::<u32, f64>(&[], DistanceKind::Jaccard).unwrap().is_empty());
+        // progress reports all pairs
+        let nb_pairs = std::sync::atomic::AtomicUsize::new(0);
+        let callback = |p : crate::sketching::progress::SketchProgress| { nb_pairs.fetch_max(p.nb_done, std::sync::atomic::Ordering::Relaxed); };
+        let with_progress = compute_distance_matrix_with_progress::<u32, f64>(&signatures, DistanceKind::JaccardDistance, Some(&callback)).unwrap();
+        assert_eq!(with_progress.get_condensed(), matrix.get_condensed());
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        assert!(compute_distance_matrix_cancellable::<u32, f64>(&signatures, DistanceKind::JaccardDistance, None, &cancel).is_err());
+        assert_eq!(nb_pairs.into_inner(), nb * (nb - 1) / 2);
+        // through SignatureDistance, same values for slot signatures, and other signature types
+        let generic = compute_signature_distance_matrix::<Vec<u32>, f64>(&signatures, DistanceKind::JaccardDistance).unwrap();
+        assert_eq!(generic.get_condensed(), matrix.get_condensed());
+        assert_eq!(generic.get_estimate(3, nb - 1, crate::sketching::mashdist::Z_95), matrix.get_estimate(3, nb - 1, crate::sketching::mashdist::Z_95));
+        let bbits : Vec<BbitSignature> = signatures.iter().map(|s| {
+                BbitSignature::from_slots(&s.iter().map(|v| *v as u64).collect::<Vec<u64>>(), 8).unwrap()
+            }).collect();
+        let bbit_matrix = compute_signature_distance_matrix::<BbitSignature, f64>(&bbits, DistanceKind::Jaccard).unwrap();
+        assert_eq!(bbit_matrix.get(nb - 1, 3), bbits[nb - 1].jaccard(&bbits[3]));
+        assert!(compute_signature_distance_matrix::<Vec<u32>, f64>(&[vec![1], vec![]], DistanceKind::Jaccard).is_err());
+    } // end of test_distance_matrix
+
+
+    #[test]
+    fn test_bootstrap_distance_matrix() {
+        log_init_test();
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(3111);
+        // signature 1 is a copy of 0 with half slots changed, 2 is a copy of 0
+        let sig0 : Vec<u64> = (0..400).map(|_| rng.gen()).collect();
+        let sig1 : Vec<u64> = sig0.iter().enumerate().map(|(i, v)| if i % 2 == 0 { rng.gen() } else { *v }).collect();
+        let signatures = vec![sig0.clone(), sig1, sig0];
+        let bootstrap = bootstrap_distance_matrix::<u64, f64>(&signatures, DistanceKind::JaccardDistance, 200, 17).unwrap();
+        assert_eq!(bootstrap.get_nb_replicates(), 200);
+        assert_eq!(bootstrap.get_replicate(7).len(), 3);
+        // std dev of a proportion 0.5 on 400 slots is 0.025
+        let std_dev = bootstrap.get_std_dev(0, 1);
+        log::info!("bootstrap std dev : {:.3e}", std_dev);
+        assert!((std_dev - 0.025).abs() < 0.005);
+        assert_eq!(bootstrap.get_std_dev(1, 0), std_dev);
+        let (low, high) = bootstrap.get_interval(0, 1, 0.95);
+        assert!(low < 0.5 && high > 0.5 && high - low < 0.15);
+        // identical signatures never vary
+        assert_eq!(bootstrap.get_std_dev(0, 2), 0.);
+        assert_eq!(bootstrap.get_interval(2, 0, 0.9), (0., 0.));
+        // reproducible with seed
+        let again = bootstrap_distance_matrix::<u64, f64>(&signatures, DistanceKind::JaccardDistance, 200, 17).unwrap();
+        assert_eq!(again.get_replicate(3).get_condensed(), bootstrap.get_replicate(3).get_condensed());
+        assert!(bootstrap_distance_matrix::<u64, f64>(&signatures, DistanceKind::Jaccard, 0, 17).is_err());
+    } // end of test_bootstrap_distance_matrix
+
+
+    #[test]
+    fn test_matrix_writers() {
+        log_init_test();
+        let signatures : Vec<Vec<u32>> = vec![vec![1, 2, 3, 4], vec![1, 2, 5, 6], vec![1, 7, 8, 9]];
+        let names : Vec<String> = vec!["a".into(), "genome b".into(), "c".into()];
+        let matrix = compute_distance_matrix::<u32, f32>(&signatures, DistanceKind::JaccardDistance).unwrap();
+        let mut buffer = Vec::<u8>::new();
+        write_phylip(&mut buffer, &names, &matrix, PhylipLayout::LowerTriangle).unwrap();
+        assert_eq!(String::from_utf8(buffer).unwrap(), "3\na\ngenome_b\t0.5\nc\t0.75\t0.75\n");
+        let mut buffer = Vec::<u8>::new();
+        write_phylip(&mut buffer, &names, &matrix, PhylipLayout::Square).unwrap();
+        assert_eq!(String::from_utf8(buffer).unwrap().lines().nth(1).unwrap(), "a\t0\t0.5\t0.75");
+        #[cfg(feature="formats")]
+        {
+            let mut buffer = Vec::<u8>::new();
+            write_square_matrix(&mut buffer, &names, &matrix, b',').unwrap();
+            let text = String::from_utf8(buffer).unwrap();
+            assert_eq!(text.lines().next().unwrap(), ",a,genome b,c");
+            assert_eq!(text.lines().nth(3).unwrap(), "c,0.75,0.75,0");
+            assert!(write_square_matrix(Vec::<u8>::new(), &names[..2], &matrix, b',').is_err());
+        }
+    } // end of test_matrix_writers
+
+} // end of mod tests
@@ -0,0 +1,372 @@
+//! This module provides a type erased sketcher, [DynSeqSketcher], so that the sketching algorithm, the data type and the kmer size
+//! can be chosen at run time (from a configuration file or command line) without generic parameters in the caller.
+//!
+//! A sketcher is built from a [SeqSketcherParams] by [make_dyn_sketcher]. Kmer type and hashing function are chosen as in
+//! [sketch_fasta_file](super::filesketch::sketch_fasta_file), and sequences are given as raw bytes (DNA or amino acids according to params).
+//! Signatures are returned as [SigEnum] : integer signatures (ProbMinHash3a, SuperMinHash2, HyperLogLog) are converted to u64,
+//! float signatures (SuperMinHash, OptDens, RevOptDens) are f32 or f64 according to [SeqSketcherParams::get_float_sig].
+//!
+//! ```
+//! use kmerutils::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
+//! use kmerutils::sketching::dynsketch::make_dyn_sketcher;
+//!
+//! // algo could come from a command line argument
+//! let params = SeqSketcherParams::new(8, 100, SketchAlgo::OPTDENS, DataType::DNA);
+//! let sketcher = make_dyn_sketcher(&params).unwrap();
+//! let sigs = sketcher.sketch_bytes(&[b"ACGTTGCAAGGCTTACCGATTGACCAGT", b"GGGCATTACCAGGTTACATTGACC"]).unwrap();
+//! assert_eq!(sigs.len(), 2);
+//! assert_eq!(sigs[0].len(), 100);
+//! ```
+
+
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+use num::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::base::{kmer::*, kmergenerator::*};
+use crate::aautils::kmeraa::{self as kmeraa, KmerAA32bit, KmerAA64bit};
+use crate::aautils::setsketchert as aasketch;
+use crate::aautils::setsketchert::SeqSketcherAAT;
+use crate::sketcharg::{SeqSketcherParams, SketchAlgo, DataType, FloatSig};
+
+use super::setsketchert::*;
+use super::filesketch::{get_dna_kmer_forward_hash, get_aa_kmer_hash, get_hll_params};
+use super::bbitsig::count_equal_u64;
+
+
+/// a signature returned by a [DynSeqSketcher]
+#[derive(Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub enum SigEnum {
+    /// ProbMinHash3a, SuperMinHash2 and HyperLogLog signatures
+    Int(Vec<u64>),
+    /// SuperMinHash, OptDensMinHash and RevOptDensMinHash signatures with [FloatSig::F32]
+    Float(Vec<f32>),
+    /// SuperMinHash, OptDensMinHash and RevOptDensMinHash signatures with [FloatSig::F64]
+    Double(Vec<f64>),
+}
+
+
+impl SigEnum {
+
+    /// returns the number of slots of the signature
+    pub fn len(&self) -> usize {
+        match self {
+            SigEnum::Int(sig) => sig.len(),
+            SigEnum::Float(sig) => sig.len(),
+            SigEnum::Double(sig) => sig.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// returns the signature as little endian bytes (8 bytes by slot for Int and Double, 4 bytes for Float)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            SigEnum::Int(sig) => sig.iter().flat_map(|s| s.to_le_bytes()).collect(),
+            SigEnum::Float(sig) => sig.iter().flat_map(|s| s.to_le_bytes()).collect(),
+            SigEnum::Double(sig) => sig.iter().flat_map(|s| s.to_le_bytes()).collect(),
+        }
+    }
+
+    /// fraction of equal slots between two signatures, i.e the Jaccard index estimate for all algorithms.
+    /// Returns an error if signatures do not have the same kind or the same length.
+    pub fn get_jaccard(&self, other : &SigEnum) -> Result<f64, String> {
+        let (nb_equal, len) = match (self, other) {
+            (SigEnum::Int(s1), SigEnum::Int(s2)) if s1.len() == s2.len() => (count_equal_u64(s1, s2), s1.len()),
+            (SigEnum::Float(s1), SigEnum::Float(s2)) if s1.len() == s2.len() => (s1.iter().zip(s2).filter(|(a, b)| a == b).count(), s1.len()),
+            (SigEnum::Double(s1), SigEnum::Double(s2)) if s1.len() == s2.len() => (s1.iter().zip(s2).filter(|(a, b)| a == b).count(), s1.len()),
+            _ => {
+                log::error!("SigEnum::get_jaccard : signatures of different kind or length");
+                return Err("SigEnum::get_jaccard : signatures of different kind or length".to_string());
+            }
+        };
+        if len == 0 {
+            return Err("SigEnum::get_jaccard : empty signatures".to_string());
+        }
+        Ok(nb_equal as f64 / len as f64)
+    } // end of get_jaccard
+
+} // end of impl SigEnum
+
+
+
+// conversions of signatures of generic sketchers
+fn int_sig<S : ToPrimitive>(sig : Vec<S>) -> SigEnum {
+    SigEnum::Int(sig.into_iter().map(|s| s.to_u64().unwrap()).collect())
+}
+
+fn float_sig<S : ToPrimitive>(sig : Vec<S>) -> SigEnum {
+    SigEnum::Float(sig.into_iter().map(|s| s.to_f32().unwrap()).collect())
+}
+
+fn double_sig<S : ToPrimitive>(sig : Vec<S>) -> SigEnum {
+    SigEnum::Double(sig.into_iter().map(|s| s.to_f64().unwrap()).collect())
+}
+
+
+
+/// An object safe sketcher. Sequences are raw bytes : DNA (ACGT, lower case accepted) or amino acids according to
+/// the data type of params. Kmers containing an invalid byte are skipped.
+pub trait DynSeqSketcher : Send + Sync {
+    /// parameters the sketcher was built with
+    fn get_params(&self) -> &SeqSketcherParams;
+    /// sketches each sequence in its own signature
+    fn sketch_bytes(&self, vseq : &[&[u8]]) -> Result<Vec<SigEnum>, String>;
+    /// sketches all sequences (the records of a file for example) in one signature
+    fn sketch_bytes_seqs(&self, vseq : &[&[u8]]) -> Result<SigEnum, String>;
+}
+
+
+
+// a DNA sketcher with its kmer hash function and signature conversion
+struct DnaDynSketcher<Kmer, Sketcher, F>
+        where   Sketcher : SeqSketcherT<Kmer>, Kmer : CompressedKmerT + KmerBuilder<Kmer>, KmerGenerator<Kmer> : KmerGenerationPattern<Kmer> {
+    params : SeqSketcherParams,
+    sketcher : Sketcher,
+    fhash : F,
+    to_sig : fn(Vec<Sketcher::Sig>) -> SigEnum,
+    _kmer_marker : PhantomData<fn(Kmer)>,
+}
+
+
+impl <Kmer, Sketcher, F> DynSeqSketcher for DnaDynSketcher<Kmer, Sketcher, F>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                Sketcher : SeqSketcherT<Kmer> + Send + Sync,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+
+    fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
+
+    fn sketch_bytes(&self, vseq : &[&[u8]]) -> Result<Vec<SigEnum>, String> {
+        let sigs = self.sketcher.sketch_compressedkmer_bytes(vseq, &self.fhash)?;
+        Ok(sigs.into_iter().map(self.to_sig).collect())
+    }
+
+    fn sketch_bytes_seqs(&self, vseq : &[&[u8]]) -> Result<SigEnum, String> {
+        let mut sigs = self.sketcher.sketch_compressedkmer_bytes_seqs(vseq, &self.fhash)?;
+        sigs.pop().map(self.to_sig).ok_or_else(|| String::from("sketch_bytes_seqs : no signature"))
+    }
+} // end of impl DynSeqSketcher for DnaDynSketcher
+
+
+
+// an amino acid sketcher with its kmer hash function and signature conversion
+struct AaDynSketcher<Kmer, Sketcher, F>
+        where   Sketcher : SeqSketcherAAT<Kmer>, Kmer : CompressedKmerT + KmerBuilder<Kmer>, kmeraa::KmerGenerator<Kmer> : kmeraa::KmerGenerationPattern<Kmer> {
+    params : SeqSketcherParams,
+    sketcher : Sketcher,
+    fhash : F,
+    to_sig : fn(Vec<Sketcher::Sig>) -> SigEnum,
+    _kmer_marker : PhantomData<fn(Kmer)>,
+}
+
+
+impl <Kmer, Sketcher, F> DynSeqSketcher for AaDynSketcher<Kmer, Sketcher, F>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                kmeraa::KmerGenerator<Kmer> : kmeraa::KmerGenerationPattern<Kmer>,
+                Sketcher : SeqSketcherAAT<Kmer> + Send + Sync,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+
+    fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
+
+    fn sketch_bytes(&self, vseq : &[&[u8]]) -> Result<Vec<SigEnum>, String> {
+        let sigs = self.sketcher.sketch_compressedkmeraa_bytes(vseq, &self.fhash)?;
+        Ok(sigs.into_iter().map(self.to_sig).collect())
+    }
+
+    fn sketch_bytes_seqs(&self, vseq : &[&[u8]]) -> Result<SigEnum, String> {
+        let mut sigs = self.sketcher.sketch_compressedkmeraa_bytes_seqs(vseq, &self.fhash)?;
+        sigs.pop().map(self.to_sig).ok_or_else(|| String::from("sketch_bytes_seqs : no signature"))
+    }
+} // end of impl DynSeqSketcher for AaDynSketcher
+
+
+
+// boxes a DNA sketcher for a given kmer type, wrapped in a CanonicalSketcher if params ask for canonical kmers
+fn box_dna<Kmer, Sketcher>(params : &SeqSketcherParams, sketcher : Sketcher, to_sig : fn(Vec<Sketcher::Sig>) -> SigEnum) -> Box<dyn DynSeqSketcher>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + 'static,
+                Kmer::Val : num::PrimInt + Send + Sync,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                Sketcher : SeqSketcherT<Kmer> + Send + Sync + 'static {
+    let fhash = get_dna_kmer_forward_hash::<Kmer>(params);
+    if params.is_canonical() {
+        let sketcher = CanonicalSketcher::new(sketcher);
+        Box::new(DnaDynSketcher{params : *params, sketcher, fhash, to_sig, _kmer_marker : PhantomData})
+    }
+    else {
+        Box::new(DnaDynSketcher{params : *params, sketcher, fhash, to_sig, _kmer_marker : PhantomData})
+    }
+}
+
+
+// boxes an amino acid sketcher for a given kmer type
+fn box_aa<Kmer, Sketcher>(params : &SeqSketcherParams, sketcher : Sketcher, to_sig : fn(Vec<Sketcher::Sig>) -> SigEnum) -> Box<dyn DynSeqSketcher>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + 'static,
+                Kmer::Val : num::PrimInt + Send + Sync,
+                kmeraa::KmerGenerator<Kmer> : kmeraa::KmerGenerationPattern<Kmer>,
+                Sketcher : SeqSketcherAAT<Kmer> + Send + Sync + 'static {
+    let fhash = get_aa_kmer_hash::<Kmer>(params);
+    Box::new(AaDynSketcher{params : *params, sketcher, fhash, to_sig, _kmer_marker : PhantomData})
+}
+
+
+
+// DNA sketcher for a given kmer type, dispatching on algorithm
+fn make_dna_sketcher<Kmer>(params : &SeqSketcherParams) -> Result<Box<dyn DynSeqSketcher>, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync + 'static,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + Clone + Serialize,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer> {
+    let sketcher = match params.get_algo() {
+        SketchAlgo::PROB3A => box_dna(params, ProbHash3aSketch::<Kmer>::new(params), int_sig),
+        SketchAlgo::SUPER => match params.get_float_sig() {
+            FloatSig::F32 => box_dna(params, SuperHashSketch::<Kmer, f32>::new(params), float_sig),
+            FloatSig::F64 => box_dna(params, SuperHashSketch::<Kmer, f64>::new(params), double_sig),
+        },
+        #[cfg(feature="sminhash2")]
+        SketchAlgo::SUPER2 => box_dna(params, SuperHash2Sketch::<Kmer, u64, fnv::FnvHasher>::new(params, std::hash::BuildHasherDefault::<fnv::FnvHasher>::default()), int_sig),
+        #[cfg(not(feature="sminhash2"))]
+        SketchAlgo::SUPER2 => {
+            log::error!("make_dyn_sketcher : SUPER2 needs feature sminhash2");
+            return Err("make_dyn_sketcher : SUPER2 needs feature sminhash2".to_string());
+        },
+        SketchAlgo::OPTDENS => match params.get_float_sig() {
+            FloatSig::F32 => box_dna(params, OptDensHashSketch::<Kmer, f32>::new(params), float_sig),
+            FloatSig::F64 => box_dna(params, OptDensHashSketch::<Kmer, f64>::new(params), double_sig),
+        },
+        SketchAlgo::REVOPTDENS => match params.get_float_sig() {
+            FloatSig::F32 => box_dna(params, RevOptDensHashSketch::<Kmer, f32>::new(params), float_sig),
+            FloatSig::F64 => box_dna(params, RevOptDensHashSketch::<Kmer, f64>::new(params), double_sig),
+        },
+        SketchAlgo::HLL => box_dna(params, HyperLogLogSketch::<Kmer, u16>::new(params, get_hll_params(params), HllSeqsThreading::default()), int_sig),
+    };
+    Ok(sketcher)
+} // end of make_dna_sketcher
+
+
+
+// amino acid sketcher for a given kmer type, dispatching on algorithm
+fn make_aa_sketcher<Kmer>(params : &SeqSketcherParams) -> Result<Box<dyn DynSeqSketcher>, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync + 'static,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + Clone + Serialize,
+                kmeraa::KmerGenerator<Kmer> : kmeraa::KmerGenerationPattern<Kmer> {
+    let sketcher = match params.get_algo() {
+        SketchAlgo::PROB3A => box_aa(params, aasketch::ProbHash3aSketch::<Kmer>::new(params), int_sig),
+        SketchAlgo::SUPER => match params.get_float_sig() {
+            FloatSig::F32 => box_aa(params, aasketch::SuperHashSketch::<Kmer, f32>::new(params), float_sig),
+            FloatSig::F64 => box_aa(params, aasketch::SuperHashSketch::<Kmer, f64>::new(params), double_sig),
+        },
+        #[cfg(feature="sminhash2")]
+        SketchAlgo::SUPER2 => box_aa(params, aasketch::SuperHash2Sketch::<Kmer, u64, fnv::FnvHasher>::new(params, std::hash::BuildHasherDefault::<fnv::FnvHasher>::default()), int_sig),
+        #[cfg(not(feature="sminhash2"))]
+        SketchAlgo::SUPER2 => {
+            log::error!("make_dyn_sketcher : SUPER2 needs feature sminhash2");
+            return Err("make_dyn_sketcher : SUPER2 needs feature sminhash2".to_string());
+        },
+        SketchAlgo::OPTDENS => match params.get_float_sig() {
+            FloatSig::F32 => box_aa(params, aasketch::OptDensHashSketch::<Kmer, f32>::new(params), float_sig),
+            FloatSig::F64 => box_aa(params, aasketch::OptDensHashSketch::<Kmer, f64>::new(params), double_sig),
+        },
+        SketchAlgo::REVOPTDENS => match params.get_float_sig() {
+            FloatSig::F32 => box_aa(params, aasketch::RevOptDensHashSketch::<Kmer, f32>::new(params), float_sig),
+            FloatSig::F64 => box_aa(params, aasketch::RevOptDensHashSketch::<Kmer, f64>::new(params), double_sig),
+        },
+        SketchAlgo::HLL => box_aa(params, aasketch::HyperLogLogSketch::<Kmer, u16>::new(params, get_hll_params(params), aasketch::HllSeqsThreading::default()), int_sig),
+    };
+    Ok(sketcher)
+} // end of make_aa_sketcher
+
+
+
+/// builds a sketcher from params : data type, kmer size, sketch size, algorithm, hash and seed.
+/// DNA kmers are stored in Kmer32bit up to 14 bases and Kmer64bit up to 32 bases,
+/// amino acid kmers in KmerAA32bit up to 6 residues and KmerAA64bit up to 12 residues.
+/// params are checked by [SeqSketcherParamsBuilder::build_with_max_kmer_size](crate::sketcharg::SeqSketcherParamsBuilder::build_with_max_kmer_size)
+/// (null kmer or sketch size ...), an error is returned for invalid params.
+pub fn make_dyn_sketcher(params : &SeqSketcherParams) -> Result<Box<dyn DynSeqSketcher>, String> {
+    //
+    let max_kmer_size = match params.get_data_t() {
+        DataType::DNA => 32,
+        DataType::AA => 12,
+    };
+    params.to_builder().build_with_max_kmer_size(max_kmer_size).map_err(|e| format!("make_dyn_sketcher : {}", e))?;
+    let kmer_size = params.get_kmer_size();
+    match params.get_data_t() {
+        DataType::DNA => {
+            if kmer_size <= 14 {
+                make_dna_sketcher::<Kmer32bit>(params)
+            }
+            else {
+                make_dna_sketcher::<Kmer64bit>(params)
+            }
+        },
+        DataType::AA => {
+            if kmer_size <= 6 {
+                make_aa_sketcher::<KmerAA32bit>(params)
+            }
+            else {
+                make_aa_sketcher::<KmerAA64bit>(params)
+            }
+        },
+    }
+} // end of make_dyn_sketcher
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sketching::filesketch::get_dna_kmer_hash;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_dyn_sketcher() {
+        log_init_test();
+        let dna : [&[u8]; 2] = [b"ACGTTGCAAGGCTTACCGATTGACCAGTNNACGGT", b"ACGTTGCAAGGCTTACCGATTGACCAGT"];
+        // same signatures as the generic sketcher with the same hash
+        let params = SeqSketcherParams::new(11, 50, SketchAlgo::PROB3A, DataType::DNA);
+        let sketcher = make_dyn_sketcher(&params).unwrap();
+        let sigs = sketcher.sketch_bytes(&dna).unwrap();
+        let generic = ProbHash3aSketch::<Kmer32bit>::new(&params);
+        let expected = generic.sketch_compressedkmer_bytes(&dna, get_dna_kmer_hash::<Kmer32bit>(&params)).unwrap();
+        assert_eq!(sigs[1], int_sig(expected[1].clone()));
+        assert!(sigs[0].get_jaccard(&sigs[1]).unwrap() > 0.5);
+        // all algorithms and data types can be chosen at run time
+        for algo in [SketchAlgo::PROB3A, SketchAlgo::SUPER, SketchAlgo::OPTDENS, SketchAlgo::REVOPTDENS, SketchAlgo::HLL] {
+            for (data_t, kmer_size) in [(DataType::DNA, 8), (DataType::DNA, 21), (DataType::AA, 5), (DataType::AA, 8)] {
+                let params = SeqSketcherParams::new(kmer_size, 50, algo, data_t);
+                let sketcher = make_dyn_sketcher(&params).unwrap();
+                let sig = sketcher.sketch_bytes_seqs(&dna).unwrap();
+                assert_eq!(sig.len(), 50);
+                assert_eq!(sig.to_bytes().len(), if matches!(sig, SigEnum::Float(_)) { 200 } else { 400 });
+            }
+        }
+        // f64 signatures on demand, equal to those of the generic sketcher
+        let mut params = SeqSketcherParams::new(11, 50, SketchAlgo::SUPER, DataType::DNA);
+        params.set_float_sig(FloatSig::F64);
+        let sigs = make_dyn_sketcher(&params).unwrap().sketch_bytes(&dna).unwrap();
+        let generic = SuperHashSketch::<Kmer32bit, f64>::new(&params);
+        let expected = generic.sketch_compressedkmer_bytes(&dna, get_dna_kmer_hash::<Kmer32bit>(&params)).unwrap();
+        assert_eq!(sigs[1], SigEnum::Double(expected[1].clone()));
+        assert_eq!(sigs[1].to_bytes().len(), 400);
+        assert!(make_dyn_sketcher(&SeqSketcherParams::new(33, 50, SketchAlgo::PROB3A, DataType::DNA)).is_err());
+        assert!(make_dyn_sketcher(&SeqSketcherParams::new(0, 50, SketchAlgo::PROB3A, DataType::DNA)).is_err());
+        assert!(make_dyn_sketcher(&SeqSketcherParams::new(8, 0, SketchAlgo::OPTDENS, DataType::DNA)).is_err());
+        assert!(make_dyn_sketcher(&SeqSketcherParams::new(13, 50, SketchAlgo::PROB3A, DataType::AA)).is_err());
+    } // end of test_dyn_sketcher
+
+} // end of mod tests
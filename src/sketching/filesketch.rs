@@ -0,0 +1,810 @@
+//! This module provides direct sketching of fasta/fastq files (possibly compressed with gzip, bzip2, xz or zstd).
+//!
+//! Records are read with needletail, split at characters outside the alphabet (N for DNA, X, * ... for amino acids)
+//! so that no kmer straddles an invalid character, converted to [Sequence] or [SequenceAA] and sketched.
+//! The file can be sketched record by record ([FileSketchMode::PerRecord]), as a whole ([FileSketchMode::PerFile])
+//! or by groups of records defined by a regex on headers ([FileSketchMode::ByHeaderRegex]), for example by chromosome or plasmid.
+//!
+//! - [sketch_fasta_file_with] and [sketch_fasta_file_aa_with] take any sketcher implementing [SeqSketcherT] or [SeqSketcherAAT]
+//!   and a kmer hashing function,
+//! - [sketch_fasta_file] chooses kmer type, sketcher and hashing function from a [SeqSketcherParams].
+//!   DNA kmers are hashed in canonical form (min of kmer and its reverse complement) unless [SeqSketcherParams::is_canonical] is false,
+//!   the hash function being given by [SeqSketcherParams::get_hash]. Canonical kmers are generated by a [CanonicalSketcher],
+//!   updating reverse complements as bases are pushed.
+//!
+//! When [SeqSketcherParams::get_scaled] gives a scale, [sketch_fasta_file] returns FracMinHash sketches instead (see [containment](super::containment)) :
+//! kmer hashes are mixed to 64 bits with xxh3 and the sorted distinct hashes below u64::MAX / scale are kept,
+//! at most sketch size of them (the smallest), so that signatures have variable lengths.
+//!
+//! For fastq files, kmers containing bases with a low quality can be skipped (see [SeqSketcherParams::set_quality_filter]),
+//! and ProbMinHash3a can weight kmers by their probability of being error free ([sketch_fasta_file_qual_weighted]).
+
+
+use std::fmt::Debug;
+use std::path::Path;
+
+use indexmap::IndexMap;
+use num::ToPrimitive;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::base::{kmer::*, kmergenerator::*};
+use crate::aautils::kmeraa::{self as kmeraa, Alphabet, ResiduePolicy, SequenceAA, KmerAA32bit, KmerAA64bit, KmerSeqIteratorT as _};
+use crate::aautils::setsketchert as aasketch;
+use crate::aautils::setsketchert::SeqSketcherAAT;
+#[cfg(feature="io")]
+use crate::io::read_fastx_mapped;
+use crate::io::{split_acgt_runs, split_acgt_qual_runs, FastxRecord};
+use crate::sketcharg::{SeqSketcherParams, SketchAlgo, DataType, FloatSig};
+
+use super::setsketchert::*;
+use super::containment::fracminhash_sketch;
+
+use probminhash::setsketcher::SetSketchParams;
+
+
+/// Specify how the records of a file are aggregated in signatures
+#[derive(Clone,Debug)]
+pub enum FileSketchMode {
+    /// one signature by record
+    PerRecord,
+    /// one signature for the whole file (union of the kmers of all records)
+    PerFile,
+    /// one signature by group of records, the group of a record being given by a regex applied to its header :
+    /// the first capture group if the regex has one, the whole match otherwise.
+    /// Records whose header does not match form their own group, named by the header.
+    /// For example `^(\S+)_plasmid` groups the plasmids of a strain.
+    ByHeaderRegex(Regex),
+}
+
+
+/// Signatures of a file with their names : record ids in mode PerRecord, the file path in mode PerFile, group keys in mode ByHeaderRegex.
+/// Records without any kmer (too short or only invalid characters) are skipped.
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct FileSketch<Sig> {
+    pub names : Vec<String>,
+    pub signatures : Vec<Vec<Sig>>,
+    /// total length of the records sketched in each signature, invalid characters included
+    #[serde(default)]
+    pub seq_lens : Vec<u64>,
+}
+
+impl <Sig> FileSketch<Sig> {
+    /// returns the number of signatures
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    /// returns true if there is no signature
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+} // end of impl FileSketch
+
+
+/// signatures returned by [sketch_fasta_file]. Integer signatures (ProbMinHash3a, SuperMinHash2, HyperLogLog)
+/// are converted to u64, float signatures (SuperMinHash, OptDens, RevOptDens) are f32 or f64 according to [SeqSketcherParams::get_float_sig].
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub enum FileSignatures {
+    Int(FileSketch<u64>),
+    Float(FileSketch<f32>),
+    Double(FileSketch<f64>),
+}
+
+impl FileSignatures {
+    /// returns the names of signatures
+    pub fn get_names(&self) -> &Vec<String> {
+        match self {
+            FileSignatures::Int(s) => &s.names,
+            FileSignatures::Float(s) => &s.names,
+            FileSignatures::Double(s) => &s.names,
+        }
+    }
+
+    /// returns the total length of the records sketched in each signature
+    pub fn get_seq_lens(&self) -> &Vec<u64> {
+        match self {
+            FileSignatures::Int(s) => &s.seq_lens,
+            FileSignatures::Float(s) => &s.seq_lens,
+            FileSignatures::Double(s) => &s.seq_lens,
+        }
+    }
+} // end of impl FileSignatures
+
+
+
+// splits an amino acid record in runs of valid amino acids of length >= min_len
+pub(crate) fn split_aa_runs(raw : &[u8], min_len : usize, alphabet : &Alphabet) -> Vec<SequenceAA> {
+    // runs are taken in raw, SequenceAA converting residues in upper case
+    raw.split(|b| !alphabet.is_valid_base(b.to_ascii_uppercase())).filter(|run| run.len() >= min_len.max(1))
+            .map(|run| SequenceAA::new_lossy(run, ResiduePolicy::Skip)).collect()
+}
+
+
+
+// ids and lengths of the records of a source, in record order
+type RecordNames = Vec<(String, u64)>;
+
+// records to sketch : records already in memory, or the records of a file, each one being split in packed runs
+// as soon as it is parsed (see read_fastx_mapped) so that the ASCII sequences of the whole file are never held in memory.
+enum RecordSource {
+    Memory(Vec<FastxRecord>),
+    #[cfg(feature="io")]
+    File(std::path::PathBuf),
+}
+
+impl RecordSource {
+    // returns ids and lengths of records and the conversion of their sequence and quality by f.
+    // Records in memory are dropped as soon as they are converted.
+    fn map_records<T, F>(self, mut f : F) -> Result<(RecordNames, Vec<T>), String>
+            where F : FnMut(&[u8], Option<&[u8]>) -> T {
+        match self {
+            RecordSource::Memory(records) => Ok(records.into_iter().map(|r| { let t = f(&r.seq, r.qual.as_deref()); ((r.id, r.seq.len() as u64), t) }).unzip()),
+            #[cfg(feature="io")]
+            RecordSource::File(path) => Ok(read_fastx_mapped(&path, |seq, qual| (seq.len() as u64, f(seq, qual)))?.into_iter()
+                    .map(|(id, (len, t))| ((id, len), t)).unzip()),
+        }
+    }
+} // end of impl RecordSource
+
+
+// returns the group key of a record header in mode ByHeaderRegex
+fn get_header_group(re : &Regex, header : &str) -> String {
+    match re.captures(header) {
+        Some(caps) => caps.get(1).or_else(|| caps.get(0)).map(|m| m.as_str().to_string()).unwrap_or_default(),
+        None => header.to_string(),
+    }
+}
+
+
+// dispatches sketching of records (given by their ids and lengths) as lists of runs.
+// fsketch sketches a list of sequences each in its own signature, fsketch_seqs sketches a list of sequences in one signature
+fn sketch_runs<T, Sig, F1, F2>(path : &Path, names : RecordNames, runs : &[Vec<T>], mode : FileSketchMode, fsketch : F1, fsketch_seqs : F2) -> Result<FileSketch<Sig>, String>
+        where   Sig : Clone,
+                F1 : Fn(&Vec<&T>) -> Result<Vec<Vec<Sig>>, String>,
+                F2 : Fn(&Vec<&T>) -> Result<Vec<Vec<Sig>>, String> {
+    //
+    match mode {
+        FileSketchMode::PerFile => {
+            let vseq : Vec<&T> = runs.iter().flatten().collect();
+            if vseq.is_empty() {
+                log::error!("sketch_fasta_file : no kmer found in file {:?}", path);
+                return Err(format!("no kmer found in file {:?}", path));
+            }
+            let signatures = fsketch_seqs(&vseq)?;
+            Ok(FileSketch{names : vec![path.to_string_lossy().to_string()], signatures, seq_lens : vec![names.iter().map(|(_, len)| len).sum()]})
+        },
+        FileSketchMode::ByHeaderRegex(re) => {
+            // groups in order of first appearance
+            let mut groups = IndexMap::<String, (Vec<&T>, u64)>::new();
+            for ((name, len), record_runs) in names.iter().zip(runs) {
+                let group = groups.entry(get_header_group(&re, name)).or_default();
+                group.0.extend(record_runs.iter());
+                group.1 += len;
+            }
+            let mut sketch = FileSketch{names : Vec::with_capacity(groups.len()), signatures : Vec::with_capacity(groups.len()), seq_lens : Vec::with_capacity(groups.len())};
+            for (key, (vseq, len)) in groups {
+                if vseq.is_empty() {
+                    log::warn!("sketch_fasta_file : group {} has no kmer, skipped", key);
+                    continue;
+                }
+                if let Some(sig) = fsketch_seqs(&vseq)?.pop() {
+                    sketch.names.push(key);
+                    sketch.signatures.push(sig);
+                    sketch.seq_lens.push(len);
+                }
+            }
+            Ok(sketch)
+        },
+        FileSketchMode::PerRecord => {
+            // records made of one run are sketched together, the others one by one
+            let mut signatures : Vec<Option<Vec<Sig>>> = vec![None; runs.len()];
+            let single : Vec<usize> = (0..runs.len()).filter(|i| runs[*i].len() == 1).collect();
+            let vseq : Vec<&T> = single.iter().map(|i| &runs[*i][0]).collect();
+            if !vseq.is_empty() {
+                for (i, sig) in single.iter().zip(fsketch(&vseq)?) {
+                    signatures[*i] = Some(sig);
+                }
+            }
+            for (i, record_runs) in runs.iter().enumerate() {
+                if record_runs.len() > 1 {
+                    let vseq : Vec<&T> = record_runs.iter().collect();
+                    signatures[i] = fsketch_seqs(&vseq)?.pop();
+                }
+            }
+            let mut sketch = FileSketch{names : Vec::with_capacity(runs.len()), signatures : Vec::with_capacity(runs.len()), seq_lens : Vec::with_capacity(runs.len())};
+            for ((name, len), sig) in names.into_iter().zip(signatures) {
+                match sig {
+                    Some(sig) => {
+                        sketch.names.push(name);
+                        sketch.signatures.push(sig);
+                        sketch.seq_lens.push(len);
+                    },
+                    None => log::warn!("sketch_fasta_file : record {} has no kmer, skipped", name),
+                }
+            }
+            Ok(sketch)
+        },
+    }
+} // end of sketch_runs
+
+
+
+// splits a DNA record in ACGT runs, with their qualities. If the record has no quality (fasta)
+// bases are given the highest quality.
+fn split_record_qual(seq : &[u8], qual : Option<&[u8]>, kmer_size : usize, min_quality : u8) -> Vec<(Sequence, Vec<u8>)> {
+    match qual {
+        Some(qual) => split_acgt_qual_runs(seq, qual, kmer_size, min_quality),
+        None => split_acgt_runs(seq, kmer_size).into_iter().map(|s| { let q = vec![b'~'; s.size()]; (s, q) }).collect(),
+    }
+}
+
+
+// converts references to runs with qualities to the pairs expected by ProbHash3aSketch
+fn as_qual_refs<'a>(vseq : &[&'a (Sequence, Vec<u8>)]) -> Vec<(&'a Sequence, &'a [u8])> {
+    vseq.iter().map(|(s, q)| (s, q.as_slice())).collect()
+}
+
+
+// splits a DNA record in ACGT runs, skipping bases with quality below min_quality if the record has qualities
+#[cfg(feature="io")]
+pub(crate) fn split_record(record : &FastxRecord, kmer_size : usize, min_quality : u8) -> Vec<Sequence> {
+    split_raw_record(&record.seq, record.qual.as_deref(), kmer_size, min_quality)
+}
+
+
+// splits a raw DNA sequence with its qualities (if any) in ACGT runs, see split_record
+fn split_raw_record(seq : &[u8], qual : Option<&[u8]>, kmer_size : usize, min_quality : u8) -> Vec<Sequence> {
+    match qual {
+        Some(qual) if min_quality > 0 => split_acgt_qual_runs(seq, qual, kmer_size, min_quality).into_iter().map(|(s, _)| s).collect(),
+        _ => split_acgt_runs(seq, kmer_size),
+    }
+}
+
+
+/// sketches a DNA fasta/fastq file with a sketcher and a kmer hashing function.
+/// For fastq records, kmers containing a base with Phred quality below min_quality are skipped (0 disables filtering).
+#[cfg(feature="io")]
+pub fn sketch_fasta_file_with<Kmer, Sketcher, F>(path : &Path, sketcher : &Sketcher, mode : FileSketchMode, min_quality : u8, fhash : F) -> Result<FileSketch<Sketcher::Sig>, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                Sketcher : SeqSketcherT<Kmer>,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+    //
+    log::debug!("sketch_fasta_file_with, file : {:?}, mode : {:?}", path, mode);
+    sketch_records_with(path, RecordSource::File(path.to_path_buf()), sketcher, mode, min_quality, fhash)
+} // end of sketch_fasta_file_with
+
+
+// sketches DNA records already read, path is used to name the signature in mode PerFile
+fn sketch_records_with<Kmer, Sketcher, F>(path : &Path, records : RecordSource, sketcher : &Sketcher, mode : FileSketchMode, min_quality : u8, fhash : F) -> Result<FileSketch<Sketcher::Sig>, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                Sketcher : SeqSketcherT<Kmer>,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+    //
+    let kmer_size = sketcher.get_kmer_size();
+    let (names, runs) = records.map_records(|seq, qual| split_raw_record(seq, qual, kmer_size, min_quality))?;
+    sketch_runs(path, names, &runs, mode, |vseq| sketcher.sketch_compressedkmer(vseq, &fhash), |vseq| sketcher.sketch_compressedkmer_seqs(vseq, &fhash))
+} // end of sketch_records_with
+
+
+
+/// sketches a DNA fasta/fastq file with ProbMinHash3a, weighting each kmer occurrence by the product over its bases
+/// of (1 - error probability) deduced from qualities. Fasta records get weight 1 for each kmer occurrence.
+/// Kmers containing a base with Phred quality below min_quality are skipped (0 disables filtering).
+#[cfg(feature="io")]
+pub fn sketch_fasta_file_qual_weighted<Kmer, F>(path : &Path, sketcher : &ProbHash3aSketch<Kmer>, mode : FileSketchMode, min_quality : u8, fhash : F) -> Result<FileSketch<Kmer::Val>, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + Serialize + 'static,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+    //
+    log::debug!("sketch_fasta_file_qual_weighted, file : {:?}, mode : {:?}", path, mode);
+    sketch_records_qual_weighted(path, RecordSource::File(path.to_path_buf()), sketcher, mode, min_quality, fhash)
+} // end of sketch_fasta_file_qual_weighted
+
+
+// quality weighted sketching of DNA records already read
+fn sketch_records_qual_weighted<Kmer, F>(path : &Path, records : RecordSource, sketcher : &ProbHash3aSketch<Kmer>, mode : FileSketchMode, min_quality : u8, fhash : F) -> Result<FileSketch<Kmer::Val>, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + Serialize + 'static,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+    //
+    let kmer_size = sketcher.get_kmer_size();
+    let (names, runs) = records.map_records(|seq, qual| split_record_qual(seq, qual, kmer_size, min_quality))?;
+    sketch_runs(path, names, &runs, mode, |vseq| Ok(sketcher.sketch_compressedkmer_qual(&as_qual_refs(vseq), &fhash)),
+                |vseq| Ok(sketcher.sketch_compressedkmer_qual_seqs(&as_qual_refs(vseq), &fhash)))
+} // end of sketch_records_qual_weighted
+
+
+
+/// sketches an amino acid fasta/fastq file with a sketcher and a kmer hashing function.
+#[cfg(feature="io")]
+pub fn sketch_fasta_file_aa_with<Kmer, Sketcher, F>(path : &Path, sketcher : &Sketcher, mode : FileSketchMode, fhash : F) -> Result<FileSketch<Sketcher::Sig>, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                kmeraa::KmerGenerator<Kmer> : kmeraa::KmerGenerationPattern<Kmer>,
+                Sketcher : SeqSketcherAAT<Kmer>,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+    //
+    log::debug!("sketch_fasta_file_aa_with, file : {:?}, mode : {:?}", path, mode);
+    sketch_records_aa_with(path, RecordSource::File(path.to_path_buf()), sketcher, mode, fhash)
+} // end of sketch_fasta_file_aa_with
+
+
+// sketches amino acid records already read
+fn sketch_records_aa_with<Kmer, Sketcher, F>(path : &Path, records : RecordSource, sketcher : &Sketcher, mode : FileSketchMode, fhash : F) -> Result<FileSketch<Sketcher::Sig>, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                kmeraa::KmerGenerator<Kmer> : kmeraa::KmerGenerationPattern<Kmer>,
+                Sketcher : SeqSketcherAAT<Kmer>,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+    //
+    let kmer_size = sketcher.get_kmer_size();
+    let alphabet = Alphabet::new();
+    let (names, runs) = records.map_records(|seq, _| split_aa_runs(seq, kmer_size, &alphabet))?;
+    sketch_runs(path, names, &runs, mode, |vseq| sketcher.sketch_compressedkmeraa(vseq, &fhash), |vseq| sketcher.sketch_compressedkmeraa_seqs(vseq, &fhash))
+} // end of sketch_records_aa_with
+
+
+
+// converts integer signatures to u64
+fn to_u64_sketch<Sig : ToPrimitive>(sketch : FileSketch<Sig>) -> FileSignatures {
+    let signatures = sketch.signatures.into_iter().map(|sig| sig.into_iter().map(|s| s.to_u64().unwrap()).collect()).collect();
+    FileSignatures::Int(FileSketch{names : sketch.names, signatures, seq_lens : sketch.seq_lens})
+}
+
+
+// returns a mask keeping the lower nb_bits bits
+pub(crate) fn get_value_mask<V : num::PrimInt>(nb_bits : usize) -> V {
+    if nb_bits >= 8 * std::mem::size_of::<V>() {
+        V::max_value()
+    }
+    else {
+        (V::one() << nb_bits) - V::one()
+    }
+}
+
+
+// returns SetSketchParams for HyperLogLog with sketch size of params
+pub(crate) fn get_hll_params(params : &SeqSketcherParams) -> SetSketchParams {
+    let default = SetSketchParams::default();
+    SetSketchParams::new(default.get_b(), params.get_sketch_size() as u64, default.get_a(), default.get_q())
+}
+
+
+/// returns the hashing function of DNA kmers defined by params : canonical form if [SeqSketcherParams::is_canonical],
+/// then hash [SeqSketcherParams::get_hash] with seed [SeqSketcherParams::get_seed].  
+/// The reverse complement is computed for each kmer, sketchers wrapped in a [CanonicalSketcher] with [get_dna_kmer_forward_hash]
+/// give the same signatures at the cost of forward kmers.
+pub fn get_dna_kmer_hash<Kmer>(params : &SeqSketcherParams) -> impl Fn(&Kmer) -> Kmer::Val + Send + Sync + Copy
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : num::PrimInt + Send + Sync {
+    let forward = get_dna_kmer_forward_hash::<Kmer>(params);
+    let canonical = params.is_canonical();
+    move | kmer : &Kmer | -> Kmer::Val {
+        if canonical { forward(&kmer.reverse_complement().min(*kmer)) } else { forward(kmer) }
+    }
+} // end of get_dna_kmer_hash
+
+
+/// returns the hashing function of DNA kmers as they are generated (hash [SeqSketcherParams::get_hash] with seed [SeqSketcherParams::get_seed]),
+/// whatever [SeqSketcherParams::is_canonical]. Canonical kmers are then obtained by wrapping the sketcher in a [CanonicalSketcher].
+pub fn get_dna_kmer_forward_hash<Kmer>(params : &SeqSketcherParams) -> impl Fn(&Kmer) -> Kmer::Val + Send + Sync + Copy
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : num::PrimInt + Send + Sync {
+    let mask : Kmer::Val = get_value_mask(2 * params.get_kmer_size());
+    let (hash, seed) = (params.get_hash(), params.get_seed());
+    move | kmer : &Kmer | -> Kmer::Val { hash.hash_value(kmer.get_compressed_value(), seed, mask) }
+} // end of get_dna_kmer_forward_hash
+
+
+
+/// returns the hashing function of amino acid kmers defined by params (hash and seed).
+pub fn get_aa_kmer_hash<Kmer>(params : &SeqSketcherParams) -> impl Fn(&Kmer) -> Kmer::Val + Send + Sync + Copy
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : num::PrimInt + Send + Sync {
+    let nb_bits = Alphabet::new().get_nb_bits() as usize;
+    let mask : Kmer::Val = get_value_mask(nb_bits * params.get_kmer_size());
+    let (hash, seed) = (params.get_hash(), params.get_seed());
+    move | kmer : &Kmer | -> Kmer::Val { hash.hash_value(kmer.get_compressed_value(), seed, mask) }
+} // end of get_aa_kmer_hash
+
+
+
+// sketches DNA records with a sketcher, wrapped in a CanonicalSketcher if params ask for canonical kmers
+fn sketch_dna_records_with<Kmer, Sketcher>(path : &Path, records : RecordSource, sketcher : Sketcher, params : &SeqSketcherParams, mode : FileSketchMode) -> Result<FileSketch<Sketcher::Sig>, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                Kmer::Val : num::PrimInt + Send + Sync,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                Sketcher : SeqSketcherT<Kmer> {
+    let fhash = get_dna_kmer_forward_hash::<Kmer>(params);
+    if params.is_canonical() {
+        sketch_records_with(path, records, &CanonicalSketcher::new(sketcher), mode, params.get_min_quality(), fhash)
+    }
+    else {
+        sketch_records_with(path, records, &sketcher, mode, params.get_min_quality(), fhash)
+    }
+} // end of sketch_dna_records_with
+
+
+// sketches DNA records for a given kmer type, dispatching on algorithm
+fn sketch_dna_records<Kmer>(path : &Path, records : RecordSource, params : &SeqSketcherParams, mode : FileSketchMode) -> Result<FileSignatures, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + Clone + Serialize + 'static,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer> {
+    //
+    match params.get_algo() {
+        SketchAlgo::PROB3A => {
+            let sketcher = ProbHash3aSketch::<Kmer>::new(params);
+            if params.get_quality_weighting() {
+                let fhash = get_dna_kmer_hash::<Kmer>(params);
+                Ok(to_u64_sketch(sketch_records_qual_weighted(path, records, &sketcher, mode, params.get_min_quality(), fhash)?))
+            }
+            else {
+                Ok(to_u64_sketch(sketch_dna_records_with(path, records, sketcher, params, mode)?))
+            }
+        },
+        SketchAlgo::SUPER => match params.get_float_sig() {
+            FloatSig::F32 => Ok(FileSignatures::Float(sketch_dna_records_with(path, records, SuperHashSketch::<Kmer, f32>::new(params), params, mode)?)),
+            FloatSig::F64 => Ok(FileSignatures::Double(sketch_dna_records_with(path, records, SuperHashSketch::<Kmer, f64>::new(params), params, mode)?)),
+        },
+        #[cfg(feature="sminhash2")]
+        SketchAlgo::SUPER2 => {
+            let sketcher = SuperHash2Sketch::<Kmer, u64, fnv::FnvHasher>::new(params, std::hash::BuildHasherDefault::<fnv::FnvHasher>::default());
+            Ok(FileSignatures::Int(sketch_dna_records_with(path, records, sketcher, params, mode)?))
+        },
+        #[cfg(not(feature="sminhash2"))]
+        SketchAlgo::SUPER2 => {
+            log::error!("sketch_fasta_file : SUPER2 needs feature sminhash2");
+            Err("sketch_fasta_file : SUPER2 needs feature sminhash2".to_string())
+        },
+        SketchAlgo::OPTDENS => match params.get_float_sig() {
+            FloatSig::F32 => Ok(FileSignatures::Float(sketch_dna_records_with(path, records, OptDensHashSketch::<Kmer, f32>::new(params), params, mode)?)),
+            FloatSig::F64 => Ok(FileSignatures::Double(sketch_dna_records_with(path, records, OptDensHashSketch::<Kmer, f64>::new(params), params, mode)?)),
+        },
+        SketchAlgo::REVOPTDENS => match params.get_float_sig() {
+            FloatSig::F32 => Ok(FileSignatures::Float(sketch_dna_records_with(path, records, RevOptDensHashSketch::<Kmer, f32>::new(params), params, mode)?)),
+            FloatSig::F64 => Ok(FileSignatures::Double(sketch_dna_records_with(path, records, RevOptDensHashSketch::<Kmer, f64>::new(params), params, mode)?)),
+        },
+        SketchAlgo::HLL => {
+            let sketcher = HyperLogLogSketch::<Kmer, u16>::new(params, get_hll_params(params), HllSeqsThreading::default());
+            Ok(to_u64_sketch(sketch_dna_records_with(path, records, sketcher, params, mode)?))
+        },
+    }
+} // end of sketch_dna_records
+
+
+
+// sketches amino acid records for a given kmer type, dispatching on algorithm
+fn sketch_aa_records<Kmer>(path : &Path, records : RecordSource, params : &SeqSketcherParams, mode : FileSketchMode) -> Result<FileSignatures, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + Clone + Serialize + 'static,
+                kmeraa::KmerGenerator<Kmer> : kmeraa::KmerGenerationPattern<Kmer> {
+    //
+    let fhash = get_aa_kmer_hash::<Kmer>(params);
+    match params.get_algo() {
+        SketchAlgo::PROB3A => {
+            let sketcher = aasketch::ProbHash3aSketch::<Kmer>::new(params);
+            Ok(to_u64_sketch(sketch_records_aa_with(path, records, &sketcher, mode, fhash)?))
+        },
+        SketchAlgo::SUPER => match params.get_float_sig() {
+            FloatSig::F32 => Ok(FileSignatures::Float(sketch_records_aa_with(path, records, &aasketch::SuperHashSketch::<Kmer, f32>::new(params), mode, fhash)?)),
+            FloatSig::F64 => Ok(FileSignatures::Double(sketch_records_aa_with(path, records, &aasketch::SuperHashSketch::<Kmer, f64>::new(params), mode, fhash)?)),
+        },
+        #[cfg(feature="sminhash2")]
+        SketchAlgo::SUPER2 => {
+            let sketcher = aasketch::SuperHash2Sketch::<Kmer, u64, fnv::FnvHasher>::new(params, std::hash::BuildHasherDefault::<fnv::FnvHasher>::default());
+            Ok(FileSignatures::Int(sketch_records_aa_with(path, records, &sketcher, mode, fhash)?))
+        },
+        #[cfg(not(feature="sminhash2"))]
+        SketchAlgo::SUPER2 => {
+            log::error!("sketch_fasta_file : SUPER2 needs feature sminhash2");
+            Err("sketch_fasta_file : SUPER2 needs feature sminhash2".to_string())
+        },
+        SketchAlgo::OPTDENS => match params.get_float_sig() {
+            FloatSig::F32 => Ok(FileSignatures::Float(sketch_records_aa_with(path, records, &aasketch::OptDensHashSketch::<Kmer, f32>::new(params), mode, fhash)?)),
+            FloatSig::F64 => Ok(FileSignatures::Double(sketch_records_aa_with(path, records, &aasketch::OptDensHashSketch::<Kmer, f64>::new(params), mode, fhash)?)),
+        },
+        SketchAlgo::REVOPTDENS => match params.get_float_sig() {
+            FloatSig::F32 => Ok(FileSignatures::Float(sketch_records_aa_with(path, records, &aasketch::RevOptDensHashSketch::<Kmer, f32>::new(params), mode, fhash)?)),
+            FloatSig::F64 => Ok(FileSignatures::Double(sketch_records_aa_with(path, records, &aasketch::RevOptDensHashSketch::<Kmer, f64>::new(params), mode, fhash)?)),
+        },
+        SketchAlgo::HLL => {
+            let sketcher = aasketch::HyperLogLogSketch::<Kmer, u16>::new(params, get_hll_params(params), aasketch::HllSeqsThreading::default());
+            Ok(to_u64_sketch(sketch_records_aa_with(path, records, &sketcher, mode, fhash)?))
+        },
+    }
+} // end of sketch_aa_records
+
+
+
+// FracMinHash sketch of the kmers given by fkmers (which calls its argument on each kmer hash) : hashes are mixed to 64 bits
+// with xxh3 and the seed of params, at most sketch size of them are kept.
+fn fracminhash_kmers<V, G>(params : &SeqSketcherParams, scale : u64, fkmers : G) -> Vec<u64>
+        where   V : ToPrimitive,
+                G : FnOnce(&mut dyn FnMut(V)) {
+    let threshold = u64::MAX / scale;
+    let seed = params.get_seed();
+    let mut hashes = Vec::<u64>::new();
+    fkmers(&mut |v : V| {
+        let h = xxhash_rust::xxh3::xxh3_64_with_seed(&v.to_u64().unwrap().to_le_bytes(), seed);
+        if h <= threshold {
+            hashes.push(h);
+        }
+    });
+    let mut sketch = fracminhash_sketch(hashes, scale);
+    sketch.truncate(params.get_sketch_size());
+    sketch
+} // end of fracminhash_kmers
+
+
+// FracMinHash sketches of DNA records, kmers being hashed as for other sketchers (see get_dna_kmer_hash)
+fn sketch_dna_records_scaled<Kmer>(path : &Path, records : RecordSource, params : &SeqSketcherParams, scale : u64, mode : FileSketchMode) -> Result<FileSketch<u64>, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                Kmer::Val : num::PrimInt + Send + Sync,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer> {
+    let kmer_size = params.get_kmer_size();
+    let fhash = get_dna_kmer_hash::<Kmer>(params);
+    let fsketch_seqs = |vseq : &[&Sequence]| -> Vec<u64> {
+        fracminhash_kmers(params, scale, |f| {
+            for seq in vseq {
+                let mut kmergen = KmerSeqIterator::<Kmer>::new(kmer_size as u8, seq);
+                kmergen.set_range(0..seq.size()).unwrap();
+                while let Some(kmer) = kmergen.next() {
+                    f(fhash(&kmer));
+                }
+            }
+        })
+    };
+    let (names, runs) = records.map_records(|seq, qual| split_raw_record(seq, qual, kmer_size, params.get_min_quality()))?;
+    sketch_runs(path, names, &runs, mode, |vseq| Ok(vseq.iter().map(|seq| fsketch_seqs(&[*seq])).collect()), |vseq| Ok(vec![fsketch_seqs(vseq)]))
+} // end of sketch_dna_records_scaled
+
+
+// FracMinHash sketches of amino acid records, see sketch_dna_records_scaled
+fn sketch_aa_records_scaled<Kmer>(path : &Path, records : RecordSource, params : &SeqSketcherParams, scale : u64, mode : FileSketchMode) -> Result<FileSketch<u64>, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                Kmer::Val : num::PrimInt + Send + Sync,
+                kmeraa::KmerGenerator<Kmer> : kmeraa::KmerGenerationPattern<Kmer> {
+    let kmer_size = params.get_kmer_size();
+    let fhash = get_aa_kmer_hash::<Kmer>(params);
+    let fsketch_seqs = |vseq : &[&SequenceAA]| -> Vec<u64> {
+        fracminhash_kmers(params, scale, |f| {
+            for seq in vseq {
+                let mut kmergen = kmeraa::KmerSeqIterator::<Kmer>::new(kmer_size, seq);
+                kmergen.set_range(0..seq.len()).unwrap();
+                while let Some(kmer) = kmergen.next() {
+                    f(fhash(&kmer));
+                }
+            }
+        })
+    };
+    let alphabet = Alphabet::new();
+    let (names, runs) = records.map_records(|seq, _| split_aa_runs(seq, kmer_size, &alphabet))?;
+    sketch_runs(path, names, &runs, mode, |vseq| Ok(vseq.iter().map(|seq| fsketch_seqs(&[*seq])).collect()), |vseq| Ok(vec![fsketch_seqs(vseq)]))
+} // end of sketch_aa_records_scaled
+
+
+
+/// sketches a fasta/fastq file according to params : data type, kmer size, sketch size and algorithm.
+/// If params have a scale ([SeqSketcherParams::get_scaled]) signatures are FracMinHash sketches of at most sketch size hashes, the algorithm being ignored.
+/// DNA kmers are stored in Kmer32bit up to 14 bases and Kmer64bit up to 32 bases,
+/// amino acid kmers in KmerAA32bit up to 6 bases and KmerAA64bit up to 12 bases.
+#[cfg(feature="io")]
+pub fn sketch_fasta_file(path : &Path, params : &SeqSketcherParams, mode : FileSketchMode) -> Result<FileSignatures, String> {
+    //
+    log::info!("sketch_fasta_file {:?}, params : {:?}, mode : {:?}", path, params, mode);
+    sketch_record_source(path, RecordSource::File(path.to_path_buf()), params, mode)
+} // end of sketch_fasta_file
+
+
+
+/// sketches records already in memory (see [read_fastx_records_from](crate::io::read_fastx_records_from)) as [sketch_fasta_file] does.
+/// path is only used to name the signature in mode [FileSketchMode::PerFile] and in messages, it need not exist.
+pub fn sketch_fastx_records(path : &Path, records : Vec<FastxRecord>, params : &SeqSketcherParams, mode : FileSketchMode) -> Result<FileSignatures, String> {
+    sketch_record_source(path, RecordSource::Memory(records), params, mode)
+} // end of sketch_fastx_records
+
+
+// sketches records in memory or read from a file, dispatching on data type and kmer size
+fn sketch_record_source(path : &Path, records : RecordSource, params : &SeqSketcherParams, mode : FileSketchMode) -> Result<FileSignatures, String> {
+    //
+    let kmer_size = params.get_kmer_size();
+    if let Some(scale) = params.get_scaled() {
+        return sketch_record_source_scaled(path, records, params, scale, mode).map(FileSignatures::Int);
+    }
+    match params.get_data_t() {
+        DataType::DNA => {
+            if kmer_size <= 14 {
+                sketch_dna_records::<Kmer32bit>(path, records, params, mode)
+            }
+            else if kmer_size <= 32 {
+                sketch_dna_records::<Kmer64bit>(path, records, params, mode)
+            }
+            else {
+                log::error!("sketch_fasta_file : DNA kmer size must be <= 32, got {}", kmer_size);
+                Err(format!("sketch_fasta_file : DNA kmer size must be <= 32, got {}", kmer_size))
+            }
+        },
+        DataType::AA => {
+            if kmer_size <= 6 {
+                sketch_aa_records::<KmerAA32bit>(path, records, params, mode)
+            }
+            else if kmer_size <= 12 {
+                sketch_aa_records::<KmerAA64bit>(path, records, params, mode)
+            }
+            else {
+                log::error!("sketch_fasta_file : amino acid kmer size must be <= 12, got {}", kmer_size);
+                Err(format!("sketch_fasta_file : amino acid kmer size must be <= 12, got {}", kmer_size))
+            }
+        },
+    }
+} // end of sketch_record_source
+
+
+// FracMinHash sketches of records, dispatching on data type and kmer size
+fn sketch_record_source_scaled(path : &Path, records : RecordSource, params : &SeqSketcherParams, scale : u64, mode : FileSketchMode) -> Result<FileSketch<u64>, String> {
+    let kmer_size = params.get_kmer_size();
+    match params.get_data_t() {
+        DataType::DNA if kmer_size <= 14 => sketch_dna_records_scaled::<Kmer32bit>(path, records, params, scale, mode),
+        DataType::DNA if kmer_size <= 32 => sketch_dna_records_scaled::<Kmer64bit>(path, records, params, scale, mode),
+        DataType::AA if kmer_size <= 6 => sketch_aa_records_scaled::<KmerAA32bit>(path, records, params, scale, mode),
+        DataType::AA if kmer_size <= 12 => sketch_aa_records_scaled::<KmerAA64bit>(path, records, params, scale, mode),
+        _ => {
+            log::error!("sketch_fasta_file : kmer size {} too large for {:?}", kmer_size, params.get_data_t());
+            Err(format!("sketch_fasta_file : kmer size {} too large for {:?}", kmer_size, params.get_data_t()))
+        },
+    }
+} // end of sketch_record_source_scaled
+
+
+//=========================================================================================
+
+
+// tests read fasta files
+#[cfg(all(test, feature="io"))]
+mod tests {
+
+    use super::*;
+    use std::io::Write;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    fn write_file(dir : &std::path::Path, name : &str, content : &str) -> std::path::PathBuf {
+        let path = dir.join(name);
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_sketch_fasta_file_dna() {
+        log_init_test();
+        let seq = "ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGCATCATGCCCCTTTAGAAAATTTCCGG";
+        let content = format!(">r1\n{}\n>r2\n{}\n>r3 short\nACGT\n>r4 with N\n{}N{}\n", seq, seq, &seq[0..40], &seq[40..]);
+        let dir = crate::test_dir("filesketch_dna");
+        let path = write_file(&dir, "kmerutils_filesketch.fa", &content);
+        let params = SeqSketcherParams::new(11, 200, SketchAlgo::PROB3A, DataType::DNA);
+        let sketch = match sketch_fasta_file(&path, &params, FileSketchMode::PerRecord).unwrap() {
+            FileSignatures::Int(s) => s,
+            _ => panic!("PROB3A must give integer signatures"),
+        };
+        // r3 has no kmer
+        assert_eq!(sketch.names, vec!["r1", "r2", "r4 with N"]);
+        assert_eq!(sketch.seq_lens, vec![seq.len() as u64, seq.len() as u64, seq.len() as u64 + 1]);
+        assert_eq!(sketch.signatures[0], sketch.signatures[1]);
+        assert_eq!(sketch.signatures[0].len(), 200);
+        // per file, and all other algorithms run
+        for algo in [SketchAlgo::PROB3A, SketchAlgo::SUPER, SketchAlgo::OPTDENS, SketchAlgo::REVOPTDENS, SketchAlgo::HLL] {
+            let params = SeqSketcherParams::new(21, 128, algo, DataType::DNA);
+            let signatures = sketch_fasta_file(&path, &params, FileSketchMode::PerFile).unwrap();
+            assert_eq!(signatures.get_names().len(), 1);
+            assert_eq!(signatures.get_seq_lens(), &vec![3 * seq.len() as u64 + 5]);
+        }
+        // FracMinHash sketches : sorted hashes below u64::MAX / scale, at most sketch size of them
+        let scaled = SeqSketcherParams::builder().kmer_size(11).sketch_size(20).scaled(4).build::<Kmer32bit>().unwrap();
+        let sketch = match sketch_fasta_file(&path, &scaled, FileSketchMode::PerRecord).unwrap() {
+            FileSignatures::Int(s) => s,
+            _ => panic!("FracMinHash must give integer signatures"),
+        };
+        assert_eq!(sketch.names, vec!["r1", "r2", "r4 with N"]);
+        assert_eq!(sketch.signatures[0], sketch.signatures[1]);
+        assert!(!sketch.signatures[0].is_empty() && sketch.signatures[0].len() <= 20);
+        assert!(sketch.signatures[0].windows(2).all(|w| w[0] < w[1]) && sketch.signatures[0].iter().all(|h| *h <= u64::MAX / 4));
+        let large = scaled.to_builder().sketch_size(1000).build::<Kmer32bit>().unwrap();
+        let whole = match sketch_fasta_file(&path, &large, FileSketchMode::PerRecord).unwrap() {
+            FileSignatures::Int(s) => s,
+            _ => panic!("FracMinHash must give integer signatures"),
+        };
+        assert!(whole.signatures[0].len() < 1000 && whole.signatures[0].starts_with(&sketch.signatures[0]));
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_sketch_fasta_file_dna
+
+
+    #[test]
+    fn test_sketch_fasta_file_grouped() {
+        log_init_test();
+        let seq = "ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGC";
+        let content = format!(">strainA_chr\n{}\n>strainB_chr\n{}\n>strainA_plasmid1\n{}\n>other\n{}\n", &seq[0..30], seq, &seq[30..], seq);
+        let dir = crate::test_dir("filesketch_grouped");
+        let path = write_file(&dir, "kmerutils_filesketch_grouped.fa", &content);
+        let params = SeqSketcherParams::new(11, 100, SketchAlgo::PROB3A, DataType::DNA);
+        let mode = FileSketchMode::ByHeaderRegex(Regex::new(r"^(strain\w)_").unwrap());
+        let sketch = match sketch_fasta_file(&path, &params, mode).unwrap() {
+            FileSignatures::Int(s) => s,
+            _ => panic!("PROB3A must give integer signatures"),
+        };
+        assert_eq!(sketch.names, vec!["strainA", "strainB", "other"]);
+        assert_eq!(sketch.seq_lens, vec![seq.len() as u64; 3]);
+        // strainA is the union of its 2 records, which have no common kmer
+        assert_ne!(sketch.signatures[0], sketch.signatures[1]);
+        assert_eq!(sketch.signatures[1], sketch.signatures[2]);
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_sketch_fasta_file_grouped
+
+
+    #[test]
+    fn test_sketch_fasta_file_aa() {
+        log_init_test();
+        let content = ">p1\nMKLVWYYQRSTACDEFGHIKLMNPQRSTVWY\n>p2\nMKLVWYYQRSTACDEFGHIKLMNPQRSTVWY\n>p3\nMKLVWYYQRSTAC*DEFGHIKLMNPQRSTVWY\n";
+        let dir = crate::test_dir("filesketch_aa");
+        let path = write_file(&dir, "kmerutils_filesketch_aa.fa", content);
+        let params = SeqSketcherParams::new(5, 100, SketchAlgo::OPTDENS, DataType::AA);
+        let sketch = match sketch_fasta_file(&path, &params, FileSketchMode::PerRecord).unwrap() {
+            FileSignatures::Float(s) => s,
+            _ => panic!("OPTDENS must give f32 signatures"),
+        };
+        assert_eq!(sketch.len(), 3);
+        assert_eq!(sketch.signatures[0], sketch.signatures[1]);
+        // f64 signatures asked in params
+        let mut params_f64 = params;
+        params_f64.set_float_sig(FloatSig::F64);
+        match sketch_fasta_file(&path, &params_f64, FileSketchMode::PerRecord).unwrap() {
+            FileSignatures::Double(s) => assert_eq!(s.signatures[0], s.signatures[1]),
+            _ => panic!("OPTDENS with FloatSig::F64 must give f64 signatures"),
+        };
+        assert!(sketch_fasta_file(&path, &SeqSketcherParams::new(13, 100, SketchAlgo::OPTDENS, DataType::AA), FileSketchMode::PerFile).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_sketch_fasta_file_aa
+
+
+    #[test]
+    fn test_sketch_fastq_quality() {
+        log_init_test();
+        // r2 is r1 with errors in a low quality tail
+        let seq1 = "ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGC";
+        let seq2 = "ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAAGCTGCTAAG";
+        let qual1 = "I".repeat(seq1.len());
+        let qual2 = format!("{}{}", "I".repeat(50), "#".repeat(10));
+        let content = format!("@r1\n{}\n+\n{}\n@r2\n{}\n+\n{}\n", seq1, qual1, seq2, qual2);
+        let dir = crate::test_dir("filesketch_fastq");
+        let path = write_file(&dir, "kmerutils_filesketch.fq", &content);
+        let get_sigs = |params : &SeqSketcherParams| -> FileSketch<u64> {
+            match sketch_fasta_file(&path, params, FileSketchMode::PerRecord).unwrap() {
+                FileSignatures::Int(s) => s,
+                _ => panic!("PROB3A must give integer signatures"),
+            }
+        };
+        let mut params = SeqSketcherParams::new(11, 200, SketchAlgo::PROB3A, DataType::DNA);
+        let sketch = get_sigs(&params);
+        assert_ne!(sketch.signatures[0], sketch.signatures[1]);
+        // skipping low quality bases, r2 kmers are a subset of r1 kmers
+        params.set_quality_filter(20);
+        let sketch = get_sigs(&params);
+        assert_eq!(sketch.len(), 2);
+        let nb_equal = sketch.signatures[0].iter().zip(sketch.signatures[1].iter()).filter(|(a, b)| a == b).count();
+        // jaccard is 40/50
+        assert!(nb_equal > 120);
+        // weighting runs and keeps low quality kmers with small weights
+        params.set_quality_filter(0);
+        params.set_quality_weighting(true);
+        let weighted = get_sigs(&params);
+        assert_eq!(weighted.len(), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_sketch_fastq_quality
+
+} // end of mod tests
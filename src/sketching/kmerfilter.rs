@@ -0,0 +1,118 @@
+//! This module provides a Bloom filter based two pass filtering of kmers seen only once.
+//!
+//! In read sets most kmers seen once come from sequencing errors. A first streaming pass over the sequences
+//! records kmers in a first Bloom filter and kmers found a second time in another Bloom filter.
+//! The sketching pass then skips kmers absent from the second filter, i.e kmers never seen twice.
+//! Memory is bounded by the size of Bloom filters, the price is a small rate of singleton kmers (false positives) kept.
+//!
+//! The mode is activated by [SeqSketcherParams::set_singleton_removal](crate::sketcharg::SeqSketcherParams::set_singleton_removal).
+
+
+use std::hash::Hash;
+
+use bloom::{BloomFilter, ASMS};
+
+use crate::base::{kmer::*, kmergenerator::*};
+
+use super::seqsketchgen::{KmerSequence, get_nbkmer_guess_seqs, hash_kmers};
+
+
+/// false positive rate of Bloom filters
+const SOLID_FILTER_FPR : f32 = 0.01;
+
+
+/// A structure storing kmers (or their hashed values) seen at least twice.
+pub struct SolidKmerFilter {
+    /// kmers seen at least once
+    seen_once : BloomFilter,
+    /// kmers seen at least twice
+    seen_twice : BloomFilter,
+}
+
+
+impl SolidKmerFilter {
+    /// allocates filters with false positive rate fpr for an expected number of distinct kmers
+    pub fn new(fpr : f32, capacity : usize) -> Self {
+        let capacity = capacity.clamp(1, u32::MAX as usize) as u32;
+        SolidKmerFilter{seen_once : BloomFilter::with_rate(fpr, capacity), seen_twice : BloomFilter::with_rate(fpr, capacity)}
+    }
+
+    /// records one occurrence of a value
+    pub fn insert<T : Hash>(&mut self, val : &T) {
+        if !self.seen_once.insert(val) {
+            // was already present in seen_once
+            self.seen_twice.insert(val);
+        }
+    }
+
+    /// returns true if the value has been seen at least twice (up to Bloom filter false positives)
+    #[inline]
+    pub fn is_solid<T : Hash>(&self, val : &T) -> bool {
+        self.seen_twice.contains(val)
+    }
+} // end of impl SolidKmerFilter
+
+
+
+/// First pass of singleton removal : inserts all hashed kmers of sequences in a [SolidKmerFilter].
+/// fhash must be the hashing function used in the sketching pass.
+pub fn build_solid_kmer_filter<Kmer, F>(vseq : &[&Sequence], kmer_size : usize, fhash : &F) -> SolidKmerFilter
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                F : Fn(&Kmer) -> Kmer::Val {
+    build_solid_filter(vseq, kmer_size, fhash)
+} // end of build_solid_kmer_filter
+
+
+
+/// same as [build_solid_kmer_filter] for any sequence type implementing [KmerSequence] (raw bytes, amino acid sequences).
+pub fn build_solid_filter<Kmer, Seq, F>(vseq : &[&Seq], kmer_size : usize, fhash : &F) -> SolidKmerFilter
+        where   Kmer : CompressedKmerT,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                F : Fn(&Kmer) -> Kmer::Val {
+    //
+    log::debug!("entering build_solid_filter");
+    //
+    let nb_kmer = if vseq.iter().any(|s| s.get_nb_bases() > 0) { get_nbkmer_guess_seqs(vseq) } else { 1 };
+    let mut filter = SolidKmerFilter::new(SOLID_FILTER_FPR, nb_kmer);
+    hash_kmers(vseq, kmer_size, fhash, None, |_, hashval| filter.insert(&hashval));
+    log::debug!("exiting build_solid_filter");
+    //
+    filter
+} // end of build_solid_filter
+
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_solid_kmer_filter() {
+        log_init_test();
+        let seq1 = Sequence::new(b"ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGC", 2);
+        let seq2 = Sequence::new(b"TTTAGAAAATTTCCGGATC", 2);
+        let kmer_size = 8;
+        let fhash = | kmer : &Kmer32bit | -> u32 { kmer.get_compressed_value() & 0x0FFFFFFF };
+        let filter = build_solid_kmer_filter::<Kmer32bit, _>(&[&seq1, &seq2], kmer_size, &fhash);
+        // kmers of seq2 are all seen twice
+        let kmers : Vec<Kmer32bit> = KmerGenerator::new(kmer_size as u8).generate_kmer(&seq2);
+        for kmer in &kmers {
+            assert!(filter.is_solid(&fhash(kmer)));
+        }
+        // kmers of beginning of seq1 are seen once
+        let kmers : Vec<Kmer32bit> = KmerGenerator::new(kmer_size as u8).generate_kmer_in_range(&seq1, 0, 12);
+        let nb_solid = kmers.iter().filter(|k| filter.is_solid(&fhash(k))).count();
+        log::debug!("nb false positive : {}", nb_solid);
+        assert!(nb_solid <= 1);
+    } // end of test_solid_kmer_filter
+
+} // end of mod tests
@@ -0,0 +1,222 @@
+//! This module provides the implementation shared by the deprecated `SeqSketcher` structures of
+//! [seqsketchjaccard](super::seqsketchjaccard::SeqSketcher) (DNA) and [aautils](crate::aautils::setsketchert::SeqSketcher) (amino acids).
+//!
+//! Both are type aliases of [LegacySeqSketcher] kept for compatibility. They only store a kmer size and a sketch size,
+//! their sketching methods are routed to the sketchers based upon the traits [SeqSketcherT] and [SeqSketcherAAT]
+//! ([ProbHash3aSketch](super::setsketchert::ProbHash3aSketch), [SuperHashSketch](super::setsketchert::SuperHashSketch) and their AA counterparts).
+//! New code should use these sketchers directly.
+
+
+use std::fmt::Debug;
+use std::marker::PhantomData;
+#[cfg(feature="formats")]
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use rand_distr::uniform::SampleUniform;
+
+use crate::base::{kmertraits::*, kmergenerator::*};
+use crate::aautils::kmeraa::{self as kmeraa, SequenceAA};
+use crate::aautils::setsketchert::{SeqSketcherAAT, ProbHash3aSketch as ProbHash3aSketchAA, SuperHashSketch as SuperHashSketchAA};
+use crate::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
+#[cfg(feature="formats")]
+use crate::sketcharg::ParamsDump;
+
+use super::setsketchert::{SeqSketcherT, ProbHash3aSketch, SuperHashSketch};
+
+
+/// marker of a [LegacySeqSketcher] sketching DNA sequences
+#[derive(Copy,Clone,Debug)]
+pub struct Dna;
+
+/// marker of a [LegacySeqSketcher] sketching amino acid sequences
+#[derive(Copy,Clone,Debug)]
+pub struct Aa;
+
+
+/// The deprecated sketcher, describing the kmer size used in computing sketches and the number of sketch we want.
+/// D is [Dna] or [Aa]. The json dump only contains kmer size and sketch size, as before the factorization.
+#[derive(Serialize,Deserialize,Copy,Clone,Debug)]
+pub struct LegacySeqSketcher<D> {
+    kmer_size : usize,
+    sketch_size : usize,
+    #[serde(skip)]
+    _data_marker : PhantomData<D>,
+}  // end of LegacySeqSketcher
+
+
+#[cfg(feature="formats")]
+impl <D> ParamsDump for LegacySeqSketcher<D> {}
+
+
+impl <D> LegacySeqSketcher<D> {
+    /// allocates a sketcher for kmers of size kmer_size and sketches of size sketch_size
+    pub fn new(kmer_size: usize, sketch_size : usize) -> Self {
+        LegacySeqSketcher{kmer_size, sketch_size, _data_marker : PhantomData}
+    }
+
+    /// returns kmer size
+    pub fn get_kmer_size(&self) -> usize {
+        self.kmer_size
+    }
+
+    /// return sketch size
+    pub fn get_sketch_size(&self) -> usize {
+        self.sketch_size
+    }
+
+    /// serialized dump, see [ParamsDump](crate::sketcharg::ParamsDump)
+    #[cfg(feature="formats")]
+    pub fn dump_json(&self, filename : &String) -> Result<(), String> {
+        self.dump_params(Path::new(filename))
+    } // end of dump
+
+
+    /// reload from a json dump. path is the dump file or the directory containing [DEFAULT_PARAMS_DUMP_NAME](crate::sketcharg::DEFAULT_PARAMS_DUMP_NAME).
+    #[cfg(feature="formats")]
+    pub fn reload_json(path : &Path) -> Result<Self, String> {
+        let sketch_params = Self::reload_params(path)?;
+        log::info!("SeqSketcher reload, kmer_size : {}, sketch_size : {}",
+            sketch_params.get_kmer_size(), sketch_params.get_sketch_size());
+        Ok(sketch_params)
+    } // end of reload_json
+
+    // parameters of the trait based sketcher doing the job
+    fn get_params(&self, algo : SketchAlgo, data_t : DataType) -> SeqSketcherParams {
+        SeqSketcherParams::new(self.kmer_size, self.sketch_size, algo, data_t)
+    }
+
+} // end of impl LegacySeqSketcher
+
+
+
+// the legacy interface returns signatures directly and panics on error
+fn unwrap_signatures<Sig>(res : Result<Vec<Vec<Sig>>, String>, fname : &str) -> Vec<Vec<Sig>> {
+    match res {
+        Ok(signatures) => signatures,
+        Err(e) => {
+            log::error!("{} failed : {}", fname, e);
+            std::panic!("{} failed : {}", fname, e);
+        },
+    }
+} // end of unwrap_signatures
+
+
+
+impl LegacySeqSketcher<Dna> {
+
+    /// This function computes and return signatures of a vector of sequences by generating kmers of size kmer_size.
+    /// The sketch is done with probminhash3a algorithm, see [ProbHash3aSketch](super::setsketchert::ProbHash3aSketch).
+    ///
+    /// fhash is any hash function, but usually it is identity, invhash on kmer or on min of kmer and reverse complement.
+    /// The argument type of the hashing function F specify the type of Kmer to generate along the sequence.
+    pub fn sketch_probminhash3a<Kmer, F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<Kmer::Val> >
+        where Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
+              F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
+              Kmer::Val : num::PrimInt + Send + Sync + Debug + Serialize + 'static,
+              KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer> {
+        //
+        log::debug!("entering sketch_probminhash3a (legacy DNA)");
+        //
+        let sketcher = ProbHash3aSketch::<Kmer>::new(&self.get_params(SketchAlgo::PROB3A, DataType::DNA));
+        unwrap_signatures(sketcher.sketch_compressedkmer(vseq, fhash), "sketch_probminhash3a")
+    }  // end of sketch_probminhash3a
+
+
+    /// superminhash sketching of each sequence, see [SuperHashSketch](super::setsketchert::SuperHashSketch).
+    /// S is for f32 of f64 depending on the signature we want from SuperMinHash.
+    /// The argument type of the hashing function F specify the type of Kmer to generate along the sequence.
+    pub fn sketch_superminhash<Kmer, S, F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<S> >
+        where Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
+              F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
+              Kmer::Val : num::PrimInt + Send + Sync + Debug + 'static,
+              KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer>,
+              S : num::Float + SampleUniform + Debug + Send + Sync + Serialize + 'static {
+        //
+        log::debug!("entering sketch_superminhash (legacy DNA)");
+        //
+        let sketcher = SuperHashSketch::<Kmer, S>::new(&self.get_params(SketchAlgo::SUPER, DataType::DNA));
+        unwrap_signatures(sketcher.sketch_compressedkmer(vseq, fhash), "sketch_superminhash")
+    } // end of sketch_superminhash
+
+} // end of impl LegacySeqSketcher<Dna>
+
+
+
+impl LegacySeqSketcher<Aa> {
+
+    /// A generic version of sketching with probminhash3a on compressed kmer for amino acids,
+    /// see [ProbHash3aSketch](crate::aautils::setsketchert::ProbHash3aSketch).
+    pub fn sketch_probminhash3a<Kmer, F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<Kmer::Val> >
+        where Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
+              F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
+              Kmer::Val : num::PrimInt + Send + Sync + Debug + Serialize + 'static,
+              kmeraa::KmerGenerator<Kmer> :  kmeraa::KmerGenerationPattern<Kmer> {
+        //
+        log::debug!("entering sketch_probminhash3a (legacy AA)");
+        //
+        let sketcher = ProbHash3aSketchAA::<Kmer>::new(&self.get_params(SketchAlgo::PROB3A, DataType::AA));
+        unwrap_signatures(sketcher.sketch_compressedkmeraa(vseq, fhash), "sketch_probminhash3a")
+    }  // end of sketch_probminhash3a
+
+
+    /// superminhash sketching of each sequence in f64, see [SuperHashSketch](crate::aautils::setsketchert::SuperHashSketch).
+    /// The argument type of the hashing function F specify the type of Kmer to generate along the sequence.
+    pub fn sketch_superminhash<Kmer, F>(&self, vseq : &Vec<&SequenceAA>, fhash : F) -> Vec<Vec<f64> >
+        where Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
+              F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
+              Kmer::Val : num::PrimInt + Send + Sync + Debug + 'static,
+              kmeraa::KmerGenerator<Kmer> :  kmeraa::KmerGenerationPattern<Kmer> {
+        //
+        log::debug!("entering sketch_superminhash (legacy AA)");
+        //
+        let sketcher = SuperHashSketchAA::<Kmer, f64>::new(&self.get_params(SketchAlgo::SUPER, DataType::AA));
+        unwrap_signatures(sketcher.sketch_compressedkmeraa(vseq, fhash), "sketch_superminhash")
+    } // end of sketch_superminhash
+
+} // end of impl LegacySeqSketcher<Aa>
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::base::kmer::Kmer32bit;
+    use crate::aautils::kmeraa::KmerAA32bit;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_legacy_sketcher() {
+        log_init_test();
+        // DNA
+        let seqs = [Sequence::new(b"ACGTTGCAAGGCTTACCGATTGACCAGT", 2), Sequence::new(b"GGGCATTACCAGGTTACATTGACC", 2)];
+        let vseq : Vec<&Sequence> = seqs.iter().collect();
+        let fhash = |kmer : &Kmer32bit| kmer.get_compressed_value();
+        let sketcher = LegacySeqSketcher::<Dna>::new(8, 50);
+        let params = SeqSketcherParams::new(8, 50, SketchAlgo::PROB3A, DataType::DNA);
+        assert_eq!(sketcher.sketch_probminhash3a(&vseq, fhash), ProbHash3aSketch::<Kmer32bit>::new(&params).sketch_compressedkmer(&vseq, fhash).unwrap());
+        let sigs : Vec<Vec<f32>> = sketcher.sketch_superminhash(&vseq, fhash);
+        assert_eq!((sigs.len(), sigs[0].len()), (2, 50));
+        // AA
+        let seqs = [SequenceAA::new(b"MTEQIELIKLYSTRILALAAQMPHVGSLDNPD").unwrap(), SequenceAA::new(b"MKVLAAGIVGLLLAAQMPHV").unwrap()];
+        let vseq : Vec<&SequenceAA> = seqs.iter().collect();
+        let fhash = |kmer : &KmerAA32bit| kmer.get_compressed_value();
+        let sketcher = LegacySeqSketcher::<Aa>::new(4, 50);
+        let params = SeqSketcherParams::new(4, 50, SketchAlgo::SUPER, DataType::AA);
+        let sigs = sketcher.sketch_superminhash(&vseq, fhash);
+        assert_eq!(sigs, SuperHashSketchAA::<KmerAA32bit, f64>::new(&params).sketch_compressedkmeraa(&vseq, fhash).unwrap());
+        // json dump is unchanged by the factorization
+        assert_eq!(serde_json::to_string(&sketcher).unwrap(), "{\"kmer_size\":4,\"sketch_size\":50}");
+        let reloaded : LegacySeqSketcher<Dna> = serde_json::from_str("{\"kmer_size\":12,\"sketch_size\":200}").unwrap();
+        assert_eq!((reloaded.get_kmer_size(), reloaded.get_sketch_size()), (12, 200));
+    } // end of test_legacy_sketcher
+
+} // end of mod tests
@@ -0,0 +1,218 @@
+//! This module provides a locality sensitive hashing index (banding of signatures) to generate candidate pairs of
+//! similar signatures without the quadratic all-vs-all comparison.
+//!
+//! Signatures of size n are cut in b bands of r slots (b * r <= n). Two signatures are candidates if they are equal
+//! on at least one band. Two signatures with Jaccard index j become candidates with probability 1 - (1 - j^r)^b,
+//! an S-curve whose threshold is about (1/b)^(1/r). [LshIndex::from_threshold] chooses b and r for a target threshold.
+//!
+//! Bands are hashed (fnv on the little endian bytes of slots) and signatures indexed by band hash, bands being processed in parallel.
+//! Candidates can then be verified with the Jaccard estimate ([lsh_similar_pairs]).
+
+
+use std::hash::Hasher;
+
+use fnv::{FnvHashMap, FnvHasher};
+use crate::parallel::*;
+
+use super::blockcompare::count_equal;
+use super::sketchcontainer::SketchContainerSig;
+
+
+/// A banding index of signatures, signatures being identified by their rank of insertion.
+pub struct LshIndex {
+    nb_bands : usize,
+    band_size : usize,
+    // for each band, band hash to ranks of signatures
+    buckets : Vec<FnvHashMap<u64, Vec<usize>>>,
+    nb_signatures : usize,
+}
+
+
+// hashes a band of a signature
+fn hash_band<Sig : SketchContainerSig>(band : &[Sig]) -> u64 {
+    let mut bytes = Vec::<u8>::with_capacity(band.len() * Sig::SIZE);
+    for v in band {
+        v.write_le(&mut bytes).unwrap();
+    }
+    let mut hasher = FnvHasher::default();
+    hasher.write(&bytes);
+    hasher.finish()
+}
+
+
+impl LshIndex {
+    /// an index with nb_bands bands of band_size slots
+    pub fn new(nb_bands : usize, band_size : usize) -> Self {
+        assert!(nb_bands > 0 && band_size > 0, "number of bands and band size must be > 0");
+        LshIndex{nb_bands, band_size, buckets : (0..nb_bands).map(|_| FnvHashMap::default()).collect(), nb_signatures : 0}
+    }
+
+    /// chooses the bands for signatures of size sketch_size so that the threshold (1/b)^(1/r) is nearest to threshold.
+    pub fn from_threshold(sketch_size : usize, threshold : f64) -> Self {
+        assert!(threshold > 0. && threshold < 1., "threshold must be in ]0, 1[");
+        let mut best = (1, sketch_size.max(1));
+        let mut best_gap = f64::MAX;
+        for band_size in 1..=sketch_size.max(1) {
+            let nb_bands = sketch_size.max(1) / band_size;
+            let gap = (Self::band_threshold(nb_bands, band_size) - threshold).abs();
+            if gap < best_gap {
+                best_gap = gap;
+                best = (nb_bands, band_size);
+            }
+        }
+        log::info!("LshIndex for threshold {}, nb bands : {}, band size : {}", threshold, best.0, best.1);
+        Self::new(best.0, best.1)
+    } // end of from_threshold
+
+    fn band_threshold(nb_bands : usize, band_size : usize) -> f64 {
+        (1. / nb_bands as f64).powf(1. / band_size as f64)
+    }
+
+    /// returns the approximate Jaccard threshold (1/b)^(1/r) of the index
+    pub fn get_threshold(&self) -> f64 {
+        Self::band_threshold(self.nb_bands, self.band_size)
+    }
+
+    /// returns number of bands and band size
+    pub fn get_bands(&self) -> (usize, usize) {
+        (self.nb_bands, self.band_size)
+    }
+
+    /// returns the number of signatures indexed
+    pub fn len(&self) -> usize {
+        self.nb_signatures
+    }
+
+    /// true if no signature is indexed
+    pub fn is_empty(&self) -> bool {
+        self.nb_signatures == 0
+    }
+
+    /// returns the probability that two signatures with Jaccard index j are candidates
+    pub fn get_candidate_probability(&self, jaccard : f64) -> f64 {
+        1. - (1. - jaccard.powi(self.band_size as i32)).powi(self.nb_bands as i32)
+    }
+
+    // checks signature size
+    fn check_size(&self, size : usize) -> Result<(), String> {
+        if size < self.nb_bands * self.band_size {
+            log::error!("LshIndex : signature size {} smaller than nb bands x band size {}", size, self.nb_bands * self.band_size);
+            return Err("LshIndex : signature too small for bands".to_string());
+        }
+        Ok(())
+    }
+
+    /// indexes signatures. Their ranks follow those of signatures already indexed.
+    pub fn insert<Sig : SketchContainerSig + Sync>(&mut self, signatures : &[Vec<Sig>]) -> Result<(), String> {
+        for sig in signatures {
+            self.check_size(sig.len())?;
+        }
+        let first = self.nb_signatures;
+        let band_size = self.band_size;
+        self.buckets.par_iter_mut().enumerate().for_each(|(band, buckets)| {
+            for (i, sig) in signatures.iter().enumerate() {
+                let h = hash_band(&sig[band * band_size..(band + 1) * band_size]);
+                buckets.entry(h).or_default().push(first + i);
+            }
+        });
+        self.nb_signatures += signatures.len();
+        Ok(())
+    } // end of insert
+
+    /// returns ranks of indexed signatures sharing a band with sig, sorted
+    pub fn query<Sig : SketchContainerSig>(&self, sig : &[Sig]) -> Result<Vec<usize>, String> {
+        self.check_size(sig.len())?;
+        let mut candidates = Vec::<usize>::new();
+        for (band, buckets) in self.buckets.iter().enumerate() {
+            if let Some(ranks) = buckets.get(&hash_band(&sig[band * self.band_size..(band + 1) * self.band_size])) {
+                candidates.extend_from_slice(ranks);
+            }
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+        Ok(candidates)
+    } // end of query
+
+    /// returns pairs (i,j), i < j, of indexed signatures sharing at least one band, sorted
+    pub fn candidate_pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs : Vec<(usize, usize)> = self.buckets.par_iter().flat_map_iter(|buckets| {
+                let mut band_pairs = Vec::<(usize, usize)>::new();
+                for ranks in buckets.values().filter(|r| r.len() > 1) {
+                    for (k, i) in ranks.iter().enumerate() {
+                        band_pairs.extend(ranks[k + 1..].iter().map(|j| (*i, *j)));
+                    }
+                }
+                band_pairs
+            }).collect();
+        pairs.par_sort_unstable();
+        pairs.dedup();
+        log::debug!("LshIndex, nb candidate pairs : {}", pairs.len());
+        pairs
+    } // end of candidate_pairs
+
+} // end of impl LshIndex
+
+
+
+/// returns pairs (i, j, jaccard), i < j, of signatures with a Jaccard estimate (fraction of equal slots) >= threshold.
+/// Candidates are generated by an [LshIndex] tuned for threshold, so some similar pairs can be missed.
+pub fn lsh_similar_pairs<Sig : SketchContainerSig + PartialEq + Sync>(signatures : &[Vec<Sig>], threshold : f64) -> Result<Vec<(usize, usize, f64)>, String> {
+    let sketch_size = match signatures.first() {
+        Some(sig) => sig.len(),
+        None => return Ok(Vec::new()),
+    };
+    let mut index = LshIndex::from_threshold(sketch_size, threshold);
+    index.insert(signatures)?;
+    let pairs = index.candidate_pairs().into_par_iter().filter_map(|(i, j)| {
+            let jaccard = count_equal(&signatures[i], &signatures[j]) as f64 / sketch_size as f64;
+            if jaccard >= threshold { Some((i, j, jaccard)) } else { None }
+        }).collect();
+    Ok(pairs)
+} // end of lsh_similar_pairs
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_lsh_pairs() {
+        log_init_test();
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(91);
+        let sketch_size = 128;
+        // 1000 random signatures, and for 50 of them a copy with 5% of slots changed
+        let mut signatures : Vec<Vec<u64>> = (0..1000).map(|_| (0..sketch_size).map(|_| rng.gen::<u64>()).collect()).collect();
+        for i in 0..50 {
+            let mut sig = signatures[i].clone();
+            for v in sig.iter_mut().step_by(20) {
+                *v = rng.gen();
+            }
+            signatures.push(sig);
+        }
+        let index = LshIndex::from_threshold(sketch_size, 0.8);
+        assert!((index.get_threshold() - 0.8).abs() < 0.05);
+        assert!(index.get_candidate_probability(0.95) > 0.99);
+        let pairs = lsh_similar_pairs(&signatures, 0.8).unwrap();
+        assert_eq!(pairs.len(), 50);
+        for (k, (i, j, jaccard)) in pairs.iter().enumerate() {
+            assert_eq!((*i, *j), (k, 1000 + k));
+            assert!(*jaccard > 0.9);
+        }
+        //
+        let mut index = LshIndex::new(16, 8);
+        index.insert(&signatures[..1000]).unwrap();
+        assert_eq!(index.query(&signatures[1003]).unwrap(), vec![3]);
+        assert!(index.query(&signatures[0][..100]).is_err());
+    } // end of test_lsh_pairs
+
+} // end of mod tests
@@ -0,0 +1,259 @@
+//! This module converts Jaccard index estimates to Mash distance and ANI (average nucleotide identity).
+//!
+//! With kmers of size k and a Jaccard index j between two kmer sets, the Mash distance (Ondov et al. 2016) is
+//! D = -ln(2j/(1+j))/k and ANI is estimated by 1 - D.
+//!
+//! The Jaccard estimate of a sketch of size n is the fraction of matching slots, a binomial proportion,
+//! with standard error sqrt(j(1-j)/n) ([JaccardEstimate]). Error bounds are obtained by a Wilson score interval on j,
+//! then mapped to distance and ANI (both monotonic in j).
+//!
+//! The functions take a Jaccard estimate so they can be used with the output of any sketcher
+//! (ProbMinHash, SuperMinHash, OptDens, HyperLogLog ...).
+//!
+//! For amino acid sketches the same formula on amino acid kmers gives a raw identity which is mapped to
+//! AAI (average amino acid identity) by a linear [AaiCalibration], as kmer based identities are biased
+//! for divergent proteomes. The default calibration is the identity map and can be fitted against alignment based AAI.
+
+
+use serde::{Deserialize, Serialize};
+
+use crate::sketcharg::{DataType, SeqSketcherParams};
+use super::blockcompare::count_equal;
+
+
+/// quantile of the standard normal distribution for a two sided 95% confidence interval
+pub const Z_95 : f64 = 1.959963984540054;
+
+
+/// returns the Mash distance D = -ln(2j/(1+j))/k, in \[0, 1\]. A null Jaccard index gives distance 1.
+pub fn mash_distance(jaccard : f64, kmer_size : usize) -> f64 {
+    assert!(kmer_size > 0, "kmer size must be > 0");
+    if jaccard <= 0. {
+        return 1.;
+    }
+    let jaccard = jaccard.min(1.);
+    // written as ln((1+j)/2j) so that identical sketches give 0 and not -0
+    (((1. + jaccard) / (2. * jaccard)).ln() / kmer_size as f64).clamp(0., 1.)
+} // end of mash_distance
+
+
+/// returns the ANI estimate 1 - D where D is the Mash distance
+pub fn ani_from_jaccard(jaccard : f64, kmer_size : usize) -> f64 {
+    1. - mash_distance(jaccard, kmer_size)
+}
+
+
+/// returns the ANI estimate C^(1/k) from a containment index C (see [containment](super::containment)).
+pub fn ani_from_containment(containment : f64, kmer_size : usize) -> f64 {
+    assert!(kmer_size > 0, "kmer size must be > 0");
+    containment.clamp(0., 1.).powf(1. / kmer_size as f64)
+}
+
+
+/// linear calibration aai = slope * raw_identity + intercept, result clamped to \[0, 1\]
+#[derive(Copy,Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct AaiCalibration {
+    /// multiplies the raw identity, 1 by default
+    pub slope : f64,
+    /// added to the scaled raw identity, 0 by default
+    pub intercept : f64,
+}
+
+impl AaiCalibration {
+    /// a calibration with given slope and intercept, for example fitted by a linear regression of alignment based AAI
+    /// on raw identities of proteome pairs
+    pub fn new(slope : f64, intercept : f64) -> Self {
+        AaiCalibration{slope, intercept}
+    }
+
+    /// applies calibration to a raw identity
+    pub fn apply(&self, raw_identity : f64) -> f64 {
+        (self.slope * raw_identity + self.intercept).clamp(0., 1.)
+    }
+} // end of impl AaiCalibration
+
+impl Default for AaiCalibration {
+    fn default() -> Self {
+        AaiCalibration{slope : 1., intercept : 0.}
+    }
+}
+
+
+/// returns the AAI estimate from the Jaccard index of amino acid kmers of size kmer_size.
+/// The raw identity 1 + ln(2j/(1+j))/k is mapped by calibration. A null Jaccard index gives 0.
+pub fn aai_from_jaccard(jaccard : f64, kmer_size : usize, calibration : &AaiCalibration) -> f64 {
+    if jaccard <= 0. {
+        return 0.;
+    }
+    calibration.apply(1. - mash_distance(jaccard, kmer_size))
+}
+
+
+/// returns ANI for DNA sketches and AAI with default calibration for amino acid sketches
+pub fn identity_from_jaccard(jaccard : f64, params : &SeqSketcherParams) -> f64 {
+    match params.get_data_t() {
+        DataType::DNA => ani_from_jaccard(jaccard, params.get_kmer_size()),
+        DataType::AA => aai_from_jaccard(jaccard, params.get_kmer_size(), &AaiCalibration::default()),
+    }
+}
+
+
+/// returns the Jaccard index corresponding to a Mash distance (inverse of [mash_distance])
+pub fn jaccard_from_mash_distance(distance : f64, kmer_size : usize) -> f64 {
+    let e = (-distance * kmer_size as f64).exp();
+    e / (2. - e)
+}
+
+
+/// returns the Wilson score interval of a Jaccard estimate obtained from a sketch of size sketch_size.
+/// z is the normal quantile of the confidence level ([Z_95] for 95%).
+pub fn jaccard_interval(jaccard : f64, sketch_size : usize, z : f64) -> (f64, f64) {
+    assert!(sketch_size > 0, "sketch size must be > 0");
+    let n = sketch_size as f64;
+    let p = jaccard.clamp(0., 1.);
+    let z2 = z * z;
+    let center = (p + z2 / (2. * n)) / (1. + z2 / n);
+    let half = z * (p * (1. - p) / n + z2 / (4. * n * n)).sqrt() / (1. + z2 / n);
+    ((center - half).max(0.), (center + half).min(1.))
+} // end of jaccard_interval
+
+
+
+/// returns the standard error sqrt(j(1-j)/n) of a Jaccard estimate from a sketch of size n
+pub fn jaccard_std_error(jaccard : f64, sketch_size : usize) -> f64 {
+    assert!(sketch_size > 0, "sketch size must be > 0");
+    let p = jaccard.clamp(0., 1.);
+    (p * (1. - p) / sketch_size as f64).sqrt()
+}
+
+
+/// A Jaccard estimate with its standard error and confidence interval
+#[derive(Copy,Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct JaccardEstimate {
+    /// point estimate, common / sketch_size
+    pub jaccard : f64,
+    /// number of matching slots
+    pub common : usize,
+    /// sketch size
+    pub sketch_size : usize,
+    /// standard error of the estimate
+    pub std_error : f64,
+    /// Wilson score interval (see [jaccard_interval])
+    pub interval : (f64, f64),
+}
+
+impl JaccardEstimate {
+    /// estimate from the number of matching slots among sketch_size, interval at normal quantile z ([Z_95] for 95%)
+    pub fn new(common : usize, sketch_size : usize, z : f64) -> Self {
+        assert!(common <= sketch_size, "common must be <= sketch size");
+        let jaccard = common as f64 / sketch_size as f64;
+        JaccardEstimate{jaccard, common, sketch_size, std_error : jaccard_std_error(jaccard, sketch_size),
+                interval : jaccard_interval(jaccard, sketch_size, z)}
+    }
+
+    /// compares two signatures slot by slot, as probminhash::jaccard::compute_probminhash_jaccard does,
+    /// and returns the estimate with its error.
+    pub fn from_signatures<Sig : PartialEq>(siga : &[Sig], sigb : &[Sig], z : f64) -> Self {
+        assert_eq!(siga.len(), sigb.len(), "signatures must have same length");
+        Self::new(count_equal(siga, sigb), siga.len(), z)
+    }
+} // end of impl JaccardEstimate
+
+
+
+/// Mash distance and ANI with error bounds deduced from a Jaccard estimate
+#[derive(Copy,Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct AniEstimate {
+    /// Jaccard estimate
+    pub jaccard : f64,
+    /// Mash distance
+    pub distance : f64,
+    /// lower and upper bounds of the distance
+    pub distance_bounds : (f64, f64),
+    /// ANI estimate, 1 - distance
+    pub ani : f64,
+    /// lower and upper bounds of ANI
+    pub ani_bounds : (f64, f64),
+}
+
+
+impl AniEstimate {
+    /// converts a Jaccard estimate from sketches of size sketch_size built on kmers of size kmer_size.
+    /// Bounds are at confidence given by normal quantile z ([Z_95] for 95%).
+    pub fn new(jaccard : f64, sketch_size : usize, kmer_size : usize, z : f64) -> Self {
+        let (j_low, j_high) = jaccard_interval(jaccard, sketch_size, z);
+        let distance = mash_distance(jaccard, kmer_size);
+        // distance decreases with jaccard
+        let distance_bounds = (mash_distance(j_high, kmer_size), mash_distance(j_low, kmer_size));
+        AniEstimate{jaccard, distance, distance_bounds, ani : 1. - distance, ani_bounds : (1. - distance_bounds.1, 1. - distance_bounds.0)}
+    }
+} // end of impl AniEstimate
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_mash_distance() {
+        log_init_test();
+        assert_eq!(mash_distance(1., 21), 0.);
+        assert_eq!(mash_distance(0., 21), 1.);
+        // -ln(0.2/1.1)/21
+        let d = mash_distance(0.1, 21);
+        assert!((d - 0.0811785).abs() < 1.0e-6);
+        assert!((jaccard_from_mash_distance(d, 21) - 0.1).abs() < 1.0e-12);
+        assert!((ani_from_jaccard(0.1, 21) - (1. - d)).abs() < 1.0e-12);
+        //
+        let (low, high) = jaccard_interval(0.1, 1000, Z_95);
+        assert!(low < 0.1 && 0.1 < high);
+        assert!(high - low < 0.04);
+        let (low_small, high_small) = jaccard_interval(0.1, 100, Z_95);
+        assert!(low_small < low && high_small > high);
+        let estimate = AniEstimate::new(0.1, 1000, 21, Z_95);
+        assert!(estimate.ani_bounds.0 < estimate.ani && estimate.ani < estimate.ani_bounds.1);
+        assert!(estimate.distance_bounds.0 < estimate.distance && estimate.distance < estimate.distance_bounds.1);
+        let exact = AniEstimate::new(1., 1000, 21, Z_95);
+        assert_eq!((exact.ani, exact.ani_bounds.1), (1., 1.));
+        assert!(exact.ani_bounds.0 < 1.);
+    } // end of test_mash_distance
+
+
+    #[test]
+    fn test_aai() {
+        log_init_test();
+        let raw = ani_from_jaccard(0.2, 7);
+        assert_eq!(aai_from_jaccard(0.2, 7, &AaiCalibration::default()), raw);
+        let calibration = AaiCalibration::new(1.2, -0.2);
+        assert!((aai_from_jaccard(0.2, 7, &calibration) - (1.2 * raw - 0.2)).abs() < 1.0e-12);
+        assert_eq!(aai_from_jaccard(1., 7, &calibration), 1.);
+        assert_eq!(aai_from_jaccard(0., 7, &calibration), 0.);
+        let params = crate::sketcharg::SeqSketcherParams::new(7, 100, crate::sketcharg::SketchAlgo::PROB3A, DataType::AA);
+        assert_eq!(identity_from_jaccard(0.2, &params), raw);
+    } // end of test_aai
+
+
+    #[test]
+    fn test_jaccard_estimate() {
+        log_init_test();
+        let siga : Vec<u64> = (0..400).collect();
+        let sigb : Vec<u64> = (0..400).map(|i| if i % 4 == 0 { i } else { 1000 + i }).collect();
+        let estimate = JaccardEstimate::from_signatures(&siga, &sigb, Z_95);
+        assert_eq!((estimate.jaccard, estimate.common, estimate.sketch_size), (0.25, 100, 400));
+        assert!((estimate.std_error - (0.25f64 * 0.75 / 400.).sqrt()).abs() < 1.0e-12);
+        // wilson interval is close to normal interval for this size
+        assert!((estimate.interval.0 - (0.25 - Z_95 * estimate.std_error)).abs() < 0.005);
+        assert!((estimate.interval.1 - (0.25 + Z_95 * estimate.std_error)).abs() < 0.005);
+        assert_eq!(JaccardEstimate::new(0, 100, Z_95).std_error, 0.);
+    } // end of test_jaccard_estimate
+
+} // end of mod tests
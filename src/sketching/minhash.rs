@@ -19,6 +19,7 @@
 use log::{debug, trace};
 
 
+use std::cell::OnceCell;
 use std::collections::{BinaryHeap, HashMap};
 
 use std::hash::{BuildHasher, BuildHasherDefault, Hasher, Hash};
@@ -31,25 +32,63 @@ use crate::hashed::*;
 pub use crate::base::{kmer::*, sequence::*};
 use probminhash::invhash::*;
 
-/// result of minhash distance computations a tuple for containment, jaccard, common, total
-pub struct MinHashDist(pub f64, pub f64, pub u64, pub u64);
+/// result of minhash distance computations between the sketches of 2 sets A and B
+#[derive(Copy,Clone,Debug,serde::Serialize,serde::Deserialize)]
+pub struct MinHashDist {
+    /// estimate of |A ∩ B| / |A|
+    pub containment_a_in_b : f64,
+    /// estimate of |A ∩ B| / |B|
+    pub containment_b_in_a : f64,
+    /// estimate of |A ∩ B| / |A ∪ B|, common / total
+    pub jaccard : f64,
+    /// number of hashes common to both sketches among the total smallest hashes of the union
+    pub common : u64,
+    /// number of smallest hashes of the union compared : the size of the smallest sketch
+    pub total : u64,
+}
 
-pub struct MinHashCount<T: Hash+Clone+Copy+Debug, H: Hasher+Default> {
+/// bottom-k sketch : keeps the size smallest hashes of pushed items with their number of occurrences.
+/// Hashes and counts are kept inline in one flat array : a prefix sorted by increasing hash (at most size entries)
+/// followed by a small buffer of pending hashes below the current maximum. When the buffer is full it is sorted with the prefix,
+/// equal hashes are merged adding their counts and only the size smallest are kept. A push is thus a comparison with the
+/// maximum for most items. A hash in the final sketch was never dropped (the maximum only decreases), so its count is
+/// its number of occurrences. Merged entries and the heap returned by [get_signature](MinHashCount::get_signature) are cached
+/// until the next insertion.  
+/// Hashes are kept as Hv, ItemHash (u64) by default, or u32 (see [SketchHashT]) with 32 bit hashes, halving the memory of hashes kept.
+pub struct MinHashCount<T: Hash+Clone+Copy+Debug, H: Hasher+Default, Hv: SketchHashT = ItemHash> {
     // if set to true the hashed item is pushed into HashItem along the hasshed value
     keep_item:bool,
-    hashes: BinaryHeap<HashedItem<T>>,
+    // sorted and deduplicated prefix of nb_sorted entries, then pending entries
+    entries: Vec<HashCount<T, Hv>>,
+    nb_sorted: usize,
+    // max hash of sorted prefix if it has size entries, Hv::MAX before
+    max_hash: Hv,
     b_hasher: BuildHasherDefault<H>,
-    counts: HashMap<ItemHash, u16, BuildHasherDefault<H>>,
     total_count: u64,
     size: usize,
-    // heap_lock: Mutex<()>,
-    // instead of map_lock, look into using https://docs.rs/chashmap/2.2
-    // map_lock: Mutex<()>,
+    // entries merged when pending entries remain, and heap of merged hashes, reset at each insertion
+    merged: OnceCell<Vec<HashCount<T, Hv>>>,
+    heap: OnceCell<BinaryHeap<HashedItem<T, Hv>>>,
 }
 
 
+// sorts entries, merges counts of equal hashes and keeps the size smallest
+fn merge_bottom_k<T : Clone+Copy, Hv : SketchHashT>(entries : &mut Vec<HashCount<T, Hv>>, size : usize) {
+    // stable sort takes advantage of the sorted prefix
+    entries.sort_by_key(|e| e.hashed.hash);
+    entries.dedup_by(|e, kept| {
+        let equal = e.hashed.hash == kept.hashed.hash;
+        if equal {
+            kept.count = kept.count.saturating_add(e.count);
+        }
+        equal
+    });
+    entries.truncate(size);
+} // end of merge_bottom_k
+
+
 
-impl <T:Hash + Clone + Copy + Debug ,  H : Hasher+Default> MinHashCount<T, H> {
+impl <T:Hash + Clone + Copy + Debug ,  H : Hasher+Default, Hv : SketchHashT> MinHashCount<T, H, Hv> {
     /// an allocator , size is capacity measured as  max number of hashed item
     /// keep_item is to ask( or not) to keep the objects (kmers) hashed.
     /// if using an invertible hasher for compressed kmers we do not need to keep track of kmers
@@ -58,153 +97,225 @@ impl <T:Hash + Clone + Copy + Debug ,  H : Hasher+Default> MinHashCount<T, H> {
         MinHashCount {
             keep_item: keep_item,
             b_hasher: BuildHasherDefault::<H>::default(),
-            hashes: BinaryHeap::with_capacity(size + 1),
-            counts: HashMap::with_capacity_and_hasher(size, BuildHasherDefault::<H>::default()),
+            entries: Vec::with_capacity(size + Self::get_max_pending(size)),
+            nb_sorted: 0,
+            max_hash: if size > 0 { Hv::MAX } else { Hv::from_item_hash(0) },
             total_count: 0,
             size: size,
-            // heap_lock: Mutex::new(()),
-            // map_lock: Mutex::new(()),
+            merged: OnceCell::new(),
+            heap: OnceCell::new(),
         }
     }  // end of new
 
 
+    // number of pending entries triggering a merge
+    fn get_max_pending(size : usize) -> usize {
+        (size / 4).max(16)
+    }
+
+
+    // merges pending entries in sorted prefix
+    fn merge_pending(&mut self) {
+        merge_bottom_k(&mut self.entries, self.size);
+        self.nb_sorted = self.entries.len();
+        if self.nb_sorted == self.size {
+            self.max_hash = self.entries.last().map(|e| e.hashed.hash).unwrap_or(Hv::from_item_hash(0));
+        }
+    } // end of merge_pending
+
+
     /// push an item in the sketching
     pub fn push(&mut self, item : &T) {
         //
         // hash
         let mut hasher = self.b_hasher.build_hasher();
         item.hash(&mut hasher);
-        let new_hash : u64 = hasher.finish();
-        //
-        // trace!(" pushing item {:?}, hash {}", item, new_hash);
+        let new_hash = Hv::from_item_hash(hasher.finish());
         // do we insert
-        let add_hash = match self.hashes.peek() {
-            None => true,
-            Some(old_max_hash) => (new_hash <= (*old_max_hash).hash) || (self.hashes.len() < self.size),
-        };
-        // if add_hash is true we must insert in hashes, 
-        if add_hash {
-            self.total_count += 1;
-            if self.counts.contains_key(&new_hash) {
-                // the item was already seen once.
-                // let _lock = self.map_lock.lock().unwrap();
-                let count = self.counts.entry(new_hash).or_insert(0u16);
-                (*count) += 1;
-                // drop(_lock);
-            } else {
-                // newhash is encountered for the first time
-                // let _ = self.heap_lock.lock().unwrap();
-                self.hashes.push(HashedItem {
-                    hash: new_hash,
-                    item: if self.keep_item {
-                        Some(*item)
-                    }
-                    else {
-                        None
-                    }
-                });
-                // 
-                self.counts.insert(new_hash, 1u16);
-                if self.hashes.len() > self.size {
-                    let hashitem = self.hashes.pop().unwrap();
-                    let _old_count = self.counts.remove(&hashitem.hash).unwrap();
-                }
-                // drop(_lock);
-                // drop(_map_lock);
-            }
-        } // end if add_hash        
+        if self.size == 0 || new_hash > self.max_hash {
+            return;
+        }
+        self.total_count += 1;
+        self.merged.take();
+        self.heap.take();
+        let item = if self.keep_item { Some(*item) } else { None };
+        self.entries.push(HashCount{hashed: HashedItem{hash: new_hash, item}, count: 1});
+        if self.entries.len() - self.nb_sorted >= Self::get_max_pending(self.size) {
+            self.merge_pending();
+        }
     } // end push
 
     /// push a slice in the sketching
     pub fn sketch_slice(&mut self, to_sketch : &[T]) {
         trace!("sketching slice");
-        to_sketch.into_iter().for_each(|x| self.push(x));
+        to_sketch.iter().for_each(|x| self.push(x));
     } // end of sketch_slice
 
 
-    /// returns a sorted vecotr of the sketch
-    pub fn get_sketchcount(&self) -> Vec<HashCount<T> > {
-        trace!("get_sketchcount  got nb hashes : {} ",self.hashes.len());
-        let mut results = Vec::with_capacity(self.hashes.len());
-        for item in self.hashes.iter() {
-            trace!(" got hash : {:?}", item.hash);
-            let counts = *self.counts.get(&item.hash).unwrap();
-            let counted_item = HashCount {
-                hashed: *item,
-                count: counts,
-            };
-            results.push(counted_item);
+    // entries merged, sorted by increasing hash
+    fn get_merged(&self) -> &[HashCount<T, Hv>] {
+        if self.entries.len() == self.nb_sorted {
+            return &self.entries;
         }
+        self.merged.get_or_init(|| {
+            let mut merged = self.entries.clone();
+            merge_bottom_k(&mut merged, self.size);
+            merged
+        })
+    } // end of get_merged
+
+
+    /// returns the sketch, sorted by increasing hash
+    pub fn get_sketchcount(&self) -> Vec<HashCount<T, Hv> > {
+        let results = self.get_merged().to_vec();
+        trace!("get_sketchcount  got nb hashes : {} ", results.len());
         results
     }  // end of get_sketchcount
 
     /// returns if keep_item was set to false
-    pub fn get_signature(&self) -> Option<&BinaryHeap<HashedItem<T>> > {
-        if self.keep_item == true {
-            return None;
+    pub fn get_signature(&self) -> Option<&BinaryHeap<HashedItem<T, Hv>> > {
+        if self.keep_item {
+            None
         }
         else {
-            return Some(&self.hashes);
+            Some(self.heap.get_or_init(|| self.get_merged().iter().map(|e| e.hashed).collect()))
         }
     } // end of get_signature
 
+    /// returns a copy of the hashes kept, sorted by increasing hash, if keep_item was set to false
+    pub fn get_sorted_signature(&self) -> Option<Vec<HashedItem<T, Hv>> > {
+        if self.keep_item {
+            None
+        }
+        else {
+            Some(self.get_merged().iter().map(|e| e.hashed).collect())
+        }
+    } // end of get_sorted_signature
+
 
 
 }  // end of impl MinHashCount
 
 
 
-/// compute different distances from sketch.
-pub fn minhash_distance<T:Hash+Clone+Copy>(sketch1: &Vec<HashCount<T> >, sketch2: &Vec<HashCount<T> >) ->  MinHashDist {
-    let mut i: usize = 0;
-    let mut j: usize = 0;
+// computes distances from sorted, deduplicated hashes of the 2 sketches
+fn sorted_hashes_distance(items1 : &[ItemHash], items2 : &[ItemHash]) -> MinHashDist {
+    let size = items1.len().min(items2.len());
+    //
+    // jaccard : the size smallest hashes of the union, a bottom-k sketch of the union
+    let (mut i, mut j) = (0, 0);
     let mut common: u64 = 0;
     let mut total: u64 = 0;
-    let sketch_size = sketch1.len();
-    //
-    trace!("sketch1 len : {}, sketch2 len : {}", sketch1.len(), sketch2.len());
+    while total < size as u64 {
+        match items1[i].cmp(&items2[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                i += 1;
+                j += 1;
+                common += 1;
+            },
+        }
+        total += 1;
+        if i >= items1.len() || j >= items2.len() {
+            // the smallest sketch is exhausted, so are the size smallest hashes of the union
+            break;
+        }
+    }
     //
-    let mut items1 : Vec<HashedItem<T>> = sketch1.iter().map(|x| x.hashed).collect();
+    // containments : hashes up to the smallest of the 2 sketch maxima are known in both sketches
+    let (mut n1, mut n2, mut inter) = (0u64, 0u64, 0u64);
+    if size > 0 {
+        let threshold = items1[items1.len() - 1].min(items2[items2.len() - 1]);
+        let (mut i, mut j) = (0, 0);
+        while i < items1.len() && items1[i] <= threshold || j < items2.len() && items2[j] <= threshold {
+            if j >= items2.len() || items2[j] > threshold || (i < items1.len() && items1[i] < items2[j]) {
+                i += 1;
+                n1 += 1;
+            } else if i >= items1.len() || items1[i] > threshold || items2[j] < items1[i] {
+                j += 1;
+                n2 += 1;
+            } else {
+                i += 1;
+                j += 1;
+                n1 += 1;
+                n2 += 1;
+                inter += 1;
+            }
+        }
+    }
+    let containment_a_in_b = if n1 > 0 { inter as f64 / n1 as f64 } else { 0. };
+    let containment_b_in_a = if n2 > 0 { inter as f64 / n2 as f64 } else { 0. };
+    let jaccard = if total > 0 { common as f64 / total as f64 } else { 0. };
+    trace!("sketch1 len : {}, sketch2 len : {}, common : {}, total : {}", items1.len(), items2.len(), common, total);
+    MinHashDist{containment_a_in_b, containment_b_in_a, jaccard, common, total}
+}  // end of sorted_hashes_distance
+
+
+/// compute different distances from sketches of sets A and B, as returned by get_sketchcount. Sketches can have different sizes.
+/// Jaccard is estimated on the smallest hashes of the union, as many as the size of the smallest sketch.
+/// Containments are estimated on the hashes below the smallest of the 2 sketch maxima.
+pub fn minhash_distance<T:Hash+Clone+Copy, Hv:SketchHashT>(sketch1: &Vec<HashCount<T, Hv> >, sketch2: &Vec<HashCount<T, Hv> >) ->  MinHashDist {
+    let mut items1 : Vec<ItemHash> = sketch1.iter().map(|x| x.hashed.hash.to_item_hash()).collect();
     items1.sort_unstable();
-    let mut items2 : Vec<HashedItem<T>> = sketch2.iter().map(|x| x.hashed).collect();
+    items1.dedup();
+    let mut items2 : Vec<ItemHash> = sketch2.iter().map(|x| x.hashed.hash.to_item_hash()).collect();
     items2.sort_unstable();
-    //    
-    while i < items1.len() && j < items2.len() {
-        if items1[i] < items2[j] {
+    items2.dedup();
+    sorted_hashes_distance(&items1, &items2)
+}  // end of minhash_distance
+
+
+
+/// result of weighted distance computations : weighted jaccard, Bray-Curtis dissimilarity, number of hashes compared
+#[derive(Copy,Clone,Debug,serde::Serialize,serde::Deserialize)]
+pub struct WeightedMinHashDist {
+    /// sum of min counts / sum of max counts
+    pub weighted_jaccard : f64,
+    /// 1 - 2 * sum of min counts / (sum of counts of sketch1 + sum of counts of sketch2)
+    pub bray_curtis : f64,
+    /// number of hashes compared
+    pub nb_compared : u64,
+}
+
+
+/// compute abundance weighted distances from sketches, using the count field of HashCount.
+/// The hashes compared are the smallest hashes of the union of the 2 sketches, as many as the size of the smallest sketch.
+/// A hash absent of a sketch has count 0 in it.
+pub fn minhash_weighted_distance<T:Hash+Clone+Copy, Hv:SketchHashT>(sketch1: &[HashCount<T, Hv>], sketch2: &[HashCount<T, Hv>]) -> WeightedMinHashDist {
+    let mut items1 : Vec<(Hv, u16)> = sketch1.iter().map(|x| (x.hashed.hash, x.count)).collect();
+    items1.sort_unstable();
+    let mut items2 : Vec<(Hv, u16)> = sketch2.iter().map(|x| (x.hashed.hash, x.count)).collect();
+    items2.sort_unstable();
+    let size = items1.len().min(items2.len());
+    //
+    let (mut i, mut j) = (0, 0);
+    let (mut sum_min, mut sum_max, mut sum1, mut sum2) = (0u64, 0u64, 0u64, 0u64);
+    let mut nb_compared = 0;
+    while nb_compared < size as u64 && (i < items1.len() || j < items2.len()) {
+        let (c1, c2) = if j >= items2.len() || (i < items1.len() && items1[i].0 < items2[j].0) {
             i += 1;
-        } else if items2[j] < items1[i] {
+            (items1[i-1].1 as u64, 0)
+        } else if i >= items1.len() || items2[j].0 < items1[i].0 {
             j += 1;
+            (0, items2[j-1].1 as u64)
         } else {
             i += 1;
             j += 1;
-            common += 1;
-        }
-        total += 1;
-        if total >= sketch1.len() as u64 {
-            break;
-        }
-    } // end while
-    //
-    // try to increase total up to asked sketch size
-    //
-    if total < items1.len() as u64 {
-        // try to increase total.
-        if i < items1.len() {
-            total += (items1.len() - i) as u64;
-        }
-        if j < items1.len() {
-            total += (items1.len() - j) as u64;
-        }
-        // now if ever total increase too much we truncate it
-        if total > sketch_size as u64 {
-            total = sketch_size as u64;
-        }            
-    }        
+            (items1[i-1].1 as u64, items2[j-1].1 as u64)
+        };
+        sum_min += c1.min(c2);
+        sum_max += c1.max(c2);
+        sum1 += c1;
+        sum2 += c2;
+        nb_compared += 1;
+    }
     //
-    let containment: f64 = common as f64 / i as f64;
-    let jaccard: f64 = common as f64 / total as f64;
-    MinHashDist(containment, jaccard, common, total)
-}  // end of minhash_distance
+    let weighted_jaccard = if sum_max > 0 { sum_min as f64 / sum_max as f64 } else { 0. };
+    let bray_curtis = if sum1 + sum2 > 0 { 1. - 2. * sum_min as f64 / (sum1 + sum2) as f64 } else { 1. };
+    trace!("minhash_weighted_distance nb compared : {}, weighted jaccard : {}, bray curtis : {}", nb_compared, weighted_jaccard, bray_curtis);
+    WeightedMinHashDist{weighted_jaccard, bray_curtis, nb_compared}
+}  // end of minhash_weighted_distance
 
 
 // The same for invhash
@@ -310,52 +421,17 @@ impl <T:CompressedKmerT ,  H : Hasher+Default> MinInvHashCountKmer<T, H> {
 
 
 
-/// compute different distances from sketch.
-/// The arguments are supposed to come from get_sketchcount method that returns sorted (!!!) InvHashCountKmer
+/// compute different distances from sketches, see [minhash_distance].
 // What do we do of counts? See ProbMinHash
 pub fn mininvhash_distance<T:CompressedKmerT>(sketch1: &Vec<InvHashCount<T> >, sketch2: &Vec<InvHashCount<T> >) ->  MinHashDist {
-    let mut i: usize = 0;
-    let mut j: usize = 0;
-    let mut common: u64 = 0;
-    let mut total: u64 = 0;
-    let sketch_size = sketch1.len();
-
-    while i < sketch1.len() && j < sketch2.len() {
-        if sketch1[i].hashed < sketch2[j].hashed {
-            i += 1;
-        } else if sketch2[j].hashed < sketch1[i].hashed {
-            j += 1;
-        } else {
-            i += 1;
-            j += 1;
-            common += 1;
-        }
-        total += 1;
-        if total >= sketch1.len() as u64 {
-            break;
-        }
-    } // end while
-    //
-    // try to increase total up to asked sketch size
-    //
-    if total < sketch1.len() as u64 {
-        // try to increase total.
-        if i < sketch1.len() {
-            total += (sketch1.len() - i) as u64;
-        }
-        if j < sketch1.len() {
-            total += (sketch1.len() - j) as u64;
-        }
-        // now if ever total increase too much we truncate it
-        if total > sketch_size as u64 {
-            total = sketch_size as u64;
-        }            
-    }        
-    //
-    let containment: f64 = common as f64 / i as f64;
-    let jaccard: f64 = common as f64 / total as f64;
-    MinHashDist(containment, jaccard, common, total)
-}  // end of minhash_distance
+    let mut items1 : Vec<ItemHash> = sketch1.iter().map(|x| x.hashed.hash).collect();
+    items1.sort_unstable();
+    items1.dedup();
+    let mut items2 : Vec<ItemHash> = sketch2.iter().map(|x| x.hashed.hash).collect();
+    items2.sort_unstable();
+    items2.dedup();
+    sorted_hashes_distance(&items1, &items2)
+}  // end of mininvhash_distance
 
 ////////////////////////////////////////////////////////////////////////////////////////:
 
@@ -396,19 +472,111 @@ mod tests {
         let sketch_b = minhash_b.get_sketchcount();
         // 
         let resdist = minhash_distance(&sketch_a, &sketch_b);
-        log::info!("distance minhash (contain, dist, common, total):  {:.3e}  {:.3e}   {:.3e}  {:.3e} ",
-               resdist.0, resdist.1, resdist.2, resdist.3);
+        log::info!("distance minhash : {:?}", resdist);
         if let Some(opthashes) = minhash_a.get_signature() {
             trace!(" nb objects {} ", opthashes.len());
         }
         else {
             trace!("minhash_a.get_signature() returned None");
         }
-        // 
-        assert!(resdist.2 > 0);
+        assert!(resdist.common > 0);
+        assert_eq!(resdist.total, 80);
         //
     } // end of test_range_intersection
 
+
+    #[test]
+    fn test_minhash_count_bottom_k() {
+        init_log_test();
+        // items repeated with different multiplicities, pushed in several slices : sketch must be the exact bottom-k with counts
+        let items : Vec<usize> = (0..30000).map(|i| (i * 7919) % 5000).chain((0..2000).map(|i| i % 13)).collect();
+        for size in [0usize, 10, 100, 7000] {
+            let mut minhash : MinHashCount<usize, FnvHasher> = MinHashCount::new(size, false);
+            for slice in items.chunks(999) {
+                minhash.sketch_slice(slice);
+            }
+            let mut exact = std::collections::BTreeMap::<u64, u16>::new();
+            for item in &items {
+                let mut hasher = FnvHasher::default();
+                item.hash(&mut hasher);
+                *exact.entry(hasher.finish()).or_insert(0) += 1;
+            }
+            let expected : Vec<(u64, u16)> = exact.into_iter().take(size).collect();
+            let sketch : Vec<(u64, u16)> = minhash.get_sketchcount().iter().map(|e| (e.hashed.hash, e.count)).collect();
+            assert_eq!(sketch, expected);
+            let signature = minhash.get_sorted_signature().unwrap();
+            assert!(signature.iter().map(|h| h.hash).eq(expected.iter().map(|e| e.0)));
+            let heap = minhash.get_signature().unwrap();
+            assert_eq!(heap.len(), expected.len());
+            assert_eq!(heap.peek().map(|h| h.hash), expected.last().map(|e| e.0));
+        }
+    } // end of test_minhash_count_bottom_k
+
+
+    #[test]
+    fn test_minhash_count_hash32() {
+        init_log_test();
+        // u32 items hashed by NoHashHasher : 32 bit hashes give the same sketch as u64 hashes, with smaller entries
+        let va : Vec<u32> = (0..20000u32).map(|i| i.wrapping_mul(2654435761)).collect();
+        let vb : Vec<u32> = (10000..40000u32).map(|i| i.wrapping_mul(2654435761)).collect();
+        let mut minhash64_a : MinHashCount<u32, NoHashHasher> = MinHashCount::new(1000, false);
+        let mut minhash64_b : MinHashCount<u32, NoHashHasher> = MinHashCount::new(1000, false);
+        let mut minhash32_a : MinHashCount<u32, NoHashHasher, u32> = MinHashCount::new(1000, false);
+        let mut minhash32_b : MinHashCount<u32, NoHashHasher, u32> = MinHashCount::new(1000, false);
+        minhash64_a.sketch_slice(&va);
+        minhash64_b.sketch_slice(&vb);
+        minhash32_a.sketch_slice(&va);
+        minhash32_b.sketch_slice(&vb);
+        let (sketch64_a, sketch32_a) = (minhash64_a.get_sketchcount(), minhash32_a.get_sketchcount());
+        assert!(sketch64_a.iter().map(|e| (e.hashed.hash, e.count)).eq(sketch32_a.iter().map(|e| (e.hashed.hash as u64, e.count))));
+        let dist64 = minhash_distance(&sketch64_a, &minhash64_b.get_sketchcount());
+        let dist32 = minhash_distance(&sketch32_a, &minhash32_b.get_sketchcount());
+        log::info!("distance minhash 32 bit hashes : {:?}", dist32);
+        assert_eq!((dist64.common, dist64.total, dist64.jaccard), (dist32.common, dist32.total, dist32.jaccard));
+        assert!(mem::size_of::<HashCount<u32, u32>>() < mem::size_of::<HashCount<u32>>());
+    } // end of test_minhash_count_hash32
+
+
+    #[test]
+    fn test_minhash_distance_containment() {
+        init_log_test();
+        // A = [0..20000], B = [10000..40000] : jaccard 1/4, A in B 1/2, B in A 1/3. Sketches of different sizes.
+        let va : Vec<usize> = (0..20000).collect();
+        let vb : Vec<usize> = (10000..40000).collect();
+        let mut minhash_a : MinHashCount<usize, FnvHasher>= MinHashCount::new(2000, false);
+        let mut minhash_b : MinHashCount<usize, FnvHasher>= MinHashCount::new(4000, false);
+        minhash_a.sketch_slice(&va);
+        minhash_b.sketch_slice(&vb);
+        let resdist = minhash_distance(&minhash_a.get_sketchcount(), &minhash_b.get_sketchcount());
+        log::info!("distance minhash : {:?}", resdist);
+        assert_eq!(resdist.total, 2000);
+        assert!((resdist.jaccard - 0.25).abs() < 0.05);
+        assert!((resdist.containment_a_in_b - 0.5).abs() < 0.05);
+        assert!((resdist.containment_b_in_a - 1. / 3.).abs() < 0.05);
+        // arguments swapped
+        let swapped = minhash_distance(&minhash_b.get_sketchcount(), &minhash_a.get_sketchcount());
+        assert_eq!(swapped.jaccard, resdist.jaccard);
+        assert_eq!((swapped.containment_a_in_b, swapped.containment_b_in_a), (resdist.containment_b_in_a, resdist.containment_a_in_b));
+        // C = [0..2000] is included in B' = [0..20000] so C in B' is exactly 1
+        let mut minhash_c : MinHashCount<usize, FnvHasher>= MinHashCount::new(200, false);
+        minhash_c.sketch_slice(&(0..2000).collect::<Vec<usize>>());
+        let resdist = minhash_distance(&minhash_c.get_sketchcount(), &minhash_a.get_sketchcount());
+        assert_eq!(resdist.containment_a_in_b, 1.);
+        assert!((resdist.containment_b_in_a - 0.1).abs() < 0.05);
+        assert!((resdist.jaccard - 0.1).abs() < 0.05);
+        // sketches larger than sets, containment of included set is exact
+        let mut minhash_d : MinHashCount<usize, FnvHasher>= MinHashCount::new(500, false);
+        minhash_d.sketch_slice(&(0..100).collect::<Vec<usize>>());
+        let mut minhash_e : MinHashCount<usize, FnvHasher>= MinHashCount::new(500, false);
+        minhash_e.sketch_slice(&(0..300).collect::<Vec<usize>>());
+        let resdist = minhash_distance(&minhash_d.get_sketchcount(), &minhash_e.get_sketchcount());
+        assert_eq!(resdist.total, 100);
+        assert_eq!(resdist.containment_a_in_b, 1.);
+        assert!((resdist.jaccard - 1. / 3.).abs() < 0.15);
+        let self_dist = minhash_distance(&minhash_e.get_sketchcount(), &minhash_e.get_sketchcount());
+        assert_eq!((self_dist.jaccard, self_dist.containment_a_in_b, self_dist.containment_b_in_a), (1., 1., 1.));
+    } // end of test_minhash_distance_containment
+
     #[test]
     fn test_mininvhash_count_range_intersection_fnv() {
         init_log_test();
@@ -430,10 +598,37 @@ mod tests {
         let sketch_b = minhash_b.get_sketchcount();
         // 
         let resdist = mininvhash_distance(&sketch_a, &sketch_b);
-        trace!("distance minhash (contain, dist, common, total):  {}  {}   {}  {} ",
-               resdist.0, resdist.1, resdist.2, resdist.3);
-        assert!(resdist.2 > 0);
+        trace!("distance minhash : {:?}", resdist);
+        assert!(resdist.common > 0);
         //
     } // end of test_range_intersection
 
+
+    #[test]
+    fn test_minhash_weighted_distance() {
+        init_log_test();
+        // a has items 0..50 twice, b has 0..100 once
+        let mut va : Vec<usize> = (0..50).collect();
+        va.extend(0..50);
+        let vb : Vec<usize> = (0..100).collect();
+        let mut minhash_a : MinHashCount<usize, FnvHasher>= MinHashCount::new(500, false);
+        let mut minhash_b : MinHashCount<usize, FnvHasher>= MinHashCount::new(500, false);
+        minhash_a.sketch_slice(&va);
+        minhash_b.sketch_slice(&vb);
+        let dist = minhash_weighted_distance(&minhash_a.get_sketchcount(), &minhash_b.get_sketchcount());
+        log::info!("weighted distance : {:?}", dist);
+        // sketch of a has 50 hashes, so the 50 smallest hashes of the union are compared
+        assert_eq!(dist.nb_compared, 50);
+        let full = minhash_weighted_distance(&minhash_b.get_sketchcount(), &minhash_b.get_sketchcount());
+        assert_eq!((full.weighted_jaccard, full.bray_curtis, full.nb_compared), (1., 0., 100));
+        // with sketches larger than sets, distances are exact
+        let mut minhash_c : MinHashCount<usize, FnvHasher>= MinHashCount::new(500, false);
+        let mut vc : Vec<usize> = (0..100).collect();
+        vc.extend(0..50);
+        minhash_c.sketch_slice(&vc);
+        let dist = minhash_weighted_distance(&minhash_c.get_sketchcount(), &minhash_b.get_sketchcount());
+        assert!((dist.weighted_jaccard - 100. / 150.).abs() < 1.0e-10);
+        assert!((dist.bray_curtis - (1. - 200. / 250.)).abs() < 1.0e-10);
+    } // end of test_minhash_weighted_distance
+
 }  // end of mod test
@@ -2,8 +2,51 @@
 
 pub mod minhash;
 pub mod seqsketchjaccard;
+#[cfg(feature="io")]
 pub mod seqblocksketch;
 pub mod seqminhash;
+pub mod legacysketch;
 
 pub mod nbkmerguess;
 pub mod setsketchert;
+pub mod seqsketchgen;
+pub mod smallseq;
+pub mod kmerfilter;
+pub mod countmin;
+pub mod filesketch;
+pub mod dynsketch;
+#[cfg(feature="parallel")]
+pub mod sketchpool;
+pub mod sketchset;
+pub mod sketchstats;
+#[cfg(feature="io")]
+pub mod streamsketch;
+#[cfg(feature="io")]
+pub mod pipeline;
+pub mod sketchcontainer;
+#[cfg(all(feature="io", feature="formats"))]
+pub mod batchsketch;
+#[cfg(all(feature="io", feature="formats"))]
+pub mod archivesketch;
+pub mod csvexport;
+pub mod mashdist;
+pub mod containment;
+pub mod blockcompare;
+pub mod bbitsig;
+pub mod distances;
+pub mod search;
+pub mod lsh;
+#[cfg(feature="formats")]
+pub mod sketchdb;
+pub mod sigdistance;
+pub mod screen;
+pub mod multikdist;
+pub mod clustering;
+pub mod progress;
+pub mod cancel;
+#[cfg(feature="parquet")]
+pub mod parquetexport;
+#[cfg(feature="rkyv")]
+pub mod rkyvarchive;
+#[cfg(feature="hnsw")]
+pub mod sketchhnsw;
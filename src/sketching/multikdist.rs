@@ -0,0 +1,130 @@
+//! This module combines distances between two genomes estimated with several kmer sizes into one consensus distance.
+//!
+//! Small k saturate for distant genomes (random matches), large k lose signal (Jaccard goes to 0 as soon as the genomes diverge).
+//! Mash distances, which estimate the per base divergence, are comparable across k, so they can be aggregated :
+//! - [KAggregation::Median] : the median of the Mash distances of all ks.
+//! - [KAggregation::WinnerTakeAll] : the distance of the k whose estimate has the narrowest confidence interval
+//!   (see [AniEstimate]), kmer sizes with a null Jaccard index being discarded.
+//!
+//! The per k estimates are kept in the output record [MultiKDistance].
+
+
+use serde::{Deserialize, Serialize};
+
+use super::mashdist::{AniEstimate, Z_95};
+
+
+/// how distances of the different kmer sizes are combined
+#[derive(Copy,Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub enum KAggregation {
+    /// median of distances
+    Median,
+    /// distance of kmer size with narrowest confidence interval
+    WinnerTakeAll,
+}
+
+
+/// estimate for one kmer size
+#[derive(Copy,Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct KDistance {
+    pub kmer_size : usize,
+    /// Mash distance, ANI and 95% bounds deduced from the Jaccard index
+    pub estimate : AniEstimate,
+}
+
+
+/// consensus distance with the per k estimates it comes from
+#[derive(Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct MultiKDistance {
+    /// consensus Mash distance
+    pub distance : f64,
+    /// 1 - distance
+    pub ani : f64,
+    pub aggregation : KAggregation,
+    /// kmer size giving the distance for WinnerTakeAll, None for Median
+    pub winner : Option<usize>,
+    /// estimates by increasing kmer size
+    pub per_k : Vec<KDistance>,
+}
+
+
+
+/// aggregates Jaccard estimates (kmer_size, jaccard) obtained with sketches of size sketch_size.
+/// Returns an error if no estimate is given, or for WinnerTakeAll if all Jaccard indexes are null.
+pub fn aggregate_distances(jaccards : &[(usize, f64)], sketch_size : usize, aggregation : KAggregation) -> Result<MultiKDistance, String> {
+    if jaccards.is_empty() {
+        log::error!("aggregate_distances : no kmer size given");
+        return Err("aggregate_distances : no kmer size given".to_string());
+    }
+    let mut per_k : Vec<KDistance> = jaccards.iter()
+            .map(|(k, j)| KDistance{kmer_size : *k, estimate : AniEstimate::new(*j, sketch_size, *k, Z_95)})
+            .collect();
+    per_k.sort_by_key(|d| d.kmer_size);
+    //
+    let (distance, winner) = match aggregation {
+        KAggregation::Median => {
+            let mut distances : Vec<f64> = per_k.iter().map(|d| d.estimate.distance).collect();
+            distances.sort_by(|a, b| a.total_cmp(b));
+            let n = distances.len();
+            let median = if n % 2 == 1 { distances[n / 2] } else { 0.5 * (distances[n / 2 - 1] + distances[n / 2]) };
+            (median, None)
+        },
+        KAggregation::WinnerTakeAll => {
+            let width = |d : &KDistance| d.estimate.distance_bounds.1 - d.estimate.distance_bounds.0;
+            let best = per_k.iter().filter(|d| d.estimate.jaccard > 0.).min_by(|a, b| width(a).total_cmp(&width(b)));
+            match best {
+                Some(d) => (d.estimate.distance, Some(d.kmer_size)),
+                None => {
+                    log::error!("aggregate_distances : all jaccard indexes are null");
+                    return Err("aggregate_distances : all jaccard indexes are null".to_string());
+                },
+            }
+        },
+    };
+    log::debug!("aggregate_distances {:?}, distance : {:.3e}, winner : {:?}", aggregation, distance, winner);
+    Ok(MultiKDistance{distance, ani : 1. - distance, aggregation, winner, per_k})
+} // end of aggregate_distances
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sketching::mashdist::mash_distance;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    // Jaccard index giving Mash distance d for kmer size k : x/(2-x) with x = exp(-dk)
+    fn jaccard_at(d : f64, k : usize) -> f64 {
+        let x = (-d * k as f64).exp();
+        x / (2. - x)
+    }
+
+    #[test]
+    fn test_aggregate_distances() {
+        log_init_test();
+        let jaccards : Vec<(usize, f64)> = [31usize, 15, 21].iter().map(|k| (*k, jaccard_at(0.02, *k))).collect();
+        let median = aggregate_distances(&jaccards, 1000, KAggregation::Median).unwrap();
+        assert!((median.distance - 0.02).abs() < 1.0E-10);
+        assert_eq!(median.per_k.iter().map(|d| d.kmer_size).collect::<Vec<usize>>(), vec![15, 21, 31]);
+        assert_eq!(median.winner, None);
+        // k = 31 lost signal (null jaccard) : median is not moved, winner ignores it
+        let jaccards = vec![(15, jaccard_at(0.1, 15)), (21, jaccard_at(0.1, 21)), (31, 0.)];
+        let median = aggregate_distances(&jaccards, 1000, KAggregation::Median).unwrap();
+        assert!((median.distance - mash_distance(jaccard_at(0.1, 21), 21)).abs() < 1.0E-10);
+        let winner = aggregate_distances(&jaccards, 1000, KAggregation::WinnerTakeAll).unwrap();
+        assert!(winner.winner == Some(15) || winner.winner == Some(21));
+        assert!((winner.distance - 0.1).abs() < 1.0E-10);
+        assert!((winner.ani - 0.9).abs() < 1.0E-10);
+        //
+        assert!(aggregate_distances(&[], 1000, KAggregation::Median).is_err());
+        assert!(aggregate_distances(&[(21, 0.)], 1000, KAggregation::WinnerTakeAll).is_err());
+    } // end of test_aggregate_distances
+
+} // end of mod tests
@@ -1,24 +1,172 @@
-/// try to guess an upper bound for nb kmer encountered in large seq or files (tens of gb bases)
-/// 
+//! try to guess an upper bound for nb kmer encountered in large seq or files (tens of gb bases)
+//! and estimate memory needed by sketching or counting jobs.
+//!
+//! The estimates ([estimate_sketch_memory], [estimate_count_memory]) only need sequence lengths, so a pipeline can check
+//! before reading data whether a job fits in RAM and otherwise switch to streaming sketching or to a disk based counter
+//! (see [SpillingKmerCounter](crate::base::spillcount::SpillingKmerCounter)).
+//! They are upper bounds : the number of distinct kmers is bounded by the number of kmers and by the number of possible kmers.
 
 
-use crate::base::sequence::Sequence;
+use crate::base::kmertraits::CompressedKmerT;
+// the guesses for a sequence are defined with the kmer generation along sequences
+pub use crate::base::kmersequence::{get_nbkmer_guess, get_nbkmer_guess_size};
+use crate::sketcharg::{SeqSketcherParams, SketchAlgo, DataType, FloatSig};
+use crate::parallel::current_num_threads;
 
-// We need a guess to allocate HashMap used with Kmer Generation
-// for very long sequence we must avoid nb_kmer to sequence length! Find a  good heuristic
-pub(crate) fn get_nbkmer_guess(seq : &Sequence) -> usize {
-    let nb = 100_000_000 * (1usize + seq.size().ilog2() as usize);
-    let nb_kmer = seq.size().min(nb);
-    return nb_kmer;
-} // end of get_nbkmer_guess
 
+/// size in bytes of the Vec header of each signature
+const VEC_HEADER_SIZE : usize = 24;
 
+/// bits per kmer of the Bloom filters used for singleton removal (2 filters with false positive rate 0.01)
+const SOLID_FILTER_BITS_PER_KMER : usize = 20;
 
-// We need a guess to allocate HashMap used with Kmer Generation
-// for vector of sequenc coming from a non concatnated file, we must avoid nb_kmer to sequence length! Find a  good heuristic
-pub(crate) fn get_nbkmer_guess_seqs(vseq : &Vec<&Sequence>) -> usize {
-    let total_nb_base = vseq.iter().fold(0, |acc, seq | acc+seq.size());
-    // for small files do not forget upperbound by size of file (or seq list)
-    let nb_kmer = total_nb_base.min(10_000_000 * (1usize + total_nb_base.ilog2() as usize));
-    nb_kmer
-}  // end of get_nbkmer_guess_seqs
+
+// the guess for a collection of sequences is seqsketchgen::get_nbkmer_guess_seqs, generic over sequence types
+
+
+
+// number of distinct kmers of size kmer_size, saturating at usize::MAX
+fn get_nb_possible_kmers(kmer_size : usize, data_t : DataType) -> usize {
+    let alphabet_size : usize = match data_t {
+        DataType::DNA => 4,
+        DataType::AA => 20,
+    };
+    (0..kmer_size).fold(1usize, |acc, _| acc.saturating_mul(alphabet_size))
+} // end of get_nb_possible_kmers
+
+
+// size in bytes of the compressed value of a kmer (u32, u64 or u128) : 2 bits by base for DNA, 5 bits by residue for AA
+fn get_kmer_value_size(kmer_size : usize, data_t : DataType) -> usize {
+    let nb_bits = match data_t {
+        DataType::DNA => 2 * kmer_size,
+        DataType::AA => 5 * kmer_size,
+    };
+    match nb_bits {
+        0..=32 => 4,
+        33..=64 => 8,
+        _ => 16,
+    }
+} // end of get_kmer_value_size
+
+
+/// size in bytes of a signature item for the algorithm of params.
+/// ProbMinHash3a signatures are kmer values, SuperMinHash and densification sketchers have the float type of params
+/// ([SeqSketcherParams::get_float_sig]), SuperMinHash2 is counted as 8 bytes (u64 signatures) and HyperLogLog as 4 bytes.
+/// Smaller types chosen by the caller only decrease memory.
+pub fn get_signature_item_size(params : &SeqSketcherParams) -> usize {
+    match params.get_algo() {
+        SketchAlgo::PROB3A => get_kmer_value_size(params.get_kmer_size(), params.get_data_t()),
+        SketchAlgo::SUPER | SketchAlgo::OPTDENS | SketchAlgo::REVOPTDENS => match params.get_float_sig() {
+            FloatSig::F32 => 4,
+            FloatSig::F64 => 8,
+        },
+        SketchAlgo::SUPER2 => 8,
+        SketchAlgo::HLL => 4,
+    }
+} // end of get_signature_item_size
+
+
+/// memory in bytes of a hash table (std or fnv HashMap) allocated for nb_entries entries of entry_size bytes.
+/// The table keeps a load factor of at most 7/8 with a power of 2 number of buckets, each with one control byte.
+pub fn estimate_hashmap_memory(nb_entries : usize, entry_size : usize) -> usize {
+    if nb_entries == 0 {
+        return 0;
+    }
+    let nb_buckets = if nb_entries < 8 { 2 * nb_entries } else { nb_entries.saturating_mul(8) / 7 };
+    nb_buckets.checked_next_power_of_two().unwrap_or(usize::MAX).saturating_mul(entry_size + 1)
+} // end of estimate_hashmap_memory
+
+
+
+// memory used to sketch one sequence of nb_bases bases, in addition to its signature
+fn estimate_sketch_work_memory(params : &SeqSketcherParams, nb_bases : usize) -> usize {
+    let kmer_size = params.get_kmer_size();
+    if nb_bases < kmer_size || kmer_size == 0 {
+        return 0;
+    }
+    let nb_distinct = get_nbkmer_guess_size(nb_bases).min(get_nb_possible_kmers(kmer_size, params.get_data_t()));
+    let mut memory = match params.get_algo() {
+        // weights of distinct kmers : kmer value and u64 count
+        SketchAlgo::PROB3A => {
+            let value_size = get_kmer_value_size(kmer_size, params.get_data_t());
+            estimate_hashmap_memory(nb_distinct, value_size + 8)
+        },
+        // kmers are streamed to the sketcher, its state is a few values by sketch item
+        _ => 3 * 8 * params.get_sketch_size(),
+    };
+    if params.get_singleton_removal() {
+        memory = memory.saturating_add(nb_distinct.saturating_mul(SOLID_FILTER_BITS_PER_KMER) / 8);
+    }
+    memory
+} // end of estimate_sketch_work_memory
+
+
+
+/// estimates the peak memory in bytes needed to sketch sequences of lengths seq_lens, each sequence in its own signature
+/// (as done by [sketch_compressedkmer](super::setsketchert::SeqSketcherT::sketch_compressedkmer)).
+/// Sequences are sketched in parallel, so the working memory of the largest sequences sketched at the same time
+/// (one by thread of the current pool) is added to the memory of all signatures. Memory of sequences themselves is not included.
+pub fn estimate_sketch_memory(params : &SeqSketcherParams, seq_lens : &[usize]) -> usize {
+    let sig_size = seq_lens.len().saturating_mul(VEC_HEADER_SIZE + params.get_sketch_size() * get_signature_item_size(params));
+    let mut work : Vec<usize> = seq_lens.iter().map(|len| estimate_sketch_work_memory(params, *len)).collect();
+    work.sort_unstable_by(|a, b| b.cmp(a));
+    let nb_threads = current_num_threads().max(1);
+    let memory = work.iter().take(nb_threads).fold(sig_size, |acc, m| acc.saturating_add(*m));
+    log::debug!("estimate_sketch_memory nb seqs : {}, nb threads : {}, memory : {}", seq_lens.len(), nb_threads, memory);
+    memory
+} // end of estimate_sketch_memory
+
+
+
+/// estimates the memory in bytes of an exact count of kmers of size kmer_size in sequences of lengths seq_lens,
+/// i.e the size of a [KmerCountTable](crate::base::kmertable::KmerCountTable) holding all their distinct kmers.
+/// Kmer is the type of kmer counted. If the result exceeds available memory a
+/// [SpillingKmerCounter](crate::base::spillcount::SpillingKmerCounter) should be used.
+pub fn estimate_count_memory<Kmer : CompressedKmerT>(kmer_size : usize, seq_lens : &[usize]) -> usize {
+    let nb_kmers = seq_lens.iter().fold(0usize, |acc, len| acc.saturating_add(len.saturating_sub(kmer_size.max(1) - 1)));
+    let nb_distinct = get_nbkmer_guess_size(nb_kmers).min(get_nb_possible_kmers(kmer_size, DataType::DNA));
+    // kmer and u32 count
+    let memory = estimate_hashmap_memory(nb_distinct, std::mem::size_of::<Kmer>() + 4);
+    log::debug!("estimate_count_memory nb kmers : {}, nb distinct guess : {}, memory : {}", nb_kmers, nb_distinct, memory);
+    memory
+} // end of estimate_count_memory
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::base::kmer::Kmer32bit;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_estimate_memory() {
+        log_init_test();
+        assert_eq!(estimate_hashmap_memory(0, 16), 0);
+        assert_eq!(estimate_hashmap_memory(3, 16), 8 * 17);
+        assert_eq!(estimate_hashmap_memory(1000, 16), 2048 * 17);
+        // kmers of size 8 : at most 4^8 = 65536 distinct kmers whatever the sequence length
+        let params = SeqSketcherParams::new(8, 100, SketchAlgo::PROB3A, DataType::DNA);
+        assert_eq!(estimate_sketch_memory(&params, &[]), 0);
+        let sig_size = VEC_HEADER_SIZE + 100 * 4;
+        assert_eq!(estimate_sketch_memory(&params, &[5]), sig_size);
+        let large = estimate_sketch_memory(&params, &[1_000_000_000]);
+        assert_eq!(large, sig_size + estimate_hashmap_memory(65536, 12));
+        assert!(estimate_sketch_memory(&params, &[1_000_000_000, 100_000]) >= large);
+        // superminhash does not store kmers
+        let params = SeqSketcherParams::new(21, 100, SketchAlgo::SUPER, DataType::DNA);
+        assert!(estimate_sketch_memory(&params, &[1_000_000_000]) < 10_000);
+        // counting
+        assert_eq!(estimate_count_memory::<Kmer32bit>(12, &[11]), 0);
+        let count = estimate_count_memory::<Kmer32bit>(12, &[10_000, 10_000]);
+        assert_eq!(count, estimate_hashmap_memory(2 * 9989, std::mem::size_of::<Kmer32bit>() + 4));
+    } // end of test_estimate_memory
+
+} // end of mod tests
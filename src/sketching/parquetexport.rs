@@ -0,0 +1,158 @@
+//! This module exports sketch collections as Apache Arrow tables, written in Parquet or Arrow ipc files,
+//! so that signatures can be queried from Python (pyarrow, pandas, polars) or Spark without a custom parser.
+//!
+//! The table has columns :
+//! - id : Utf8, the sequence name,
+//! - seq_len : UInt64, the sequence length,
+//! - signature : FixedSizeList of sketch_size values (UInt16, UInt32, UInt64, Float32 or Float64).
+//!
+//! Sketching parameters are stored in json in the schema metadata under key [PARAMS_METADATA_KEY].
+//!
+//! Requires feature *parquet*.
+
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, FixedSizeListArray, PrimitiveArray, RecordBatch, StringArray, UInt64Array};
+use arrow_array::types::{ArrowPrimitiveType, UInt16Type, UInt32Type, UInt64Type, Float32Type, Float64Type};
+use arrow_schema::{DataType, Field, Schema};
+
+use crate::sketcharg::SeqSketcherParams;
+use super::sketchcontainer::{SketchContainerSig, SketchMetadata};
+
+
+/// key of schema metadata containing sketching parameters in json
+pub const PARAMS_METADATA_KEY : &str = "kmerutils.params";
+
+
+/// signature types that can be exported, associating the arrow type.
+pub trait ArrowSketchSig : SketchContainerSig {
+    /// arrow type with Self as native type
+    type ArrowType : ArrowPrimitiveType<Native = Self>;
+}
+
+impl ArrowSketchSig for u16 { type ArrowType = UInt16Type; }
+impl ArrowSketchSig for u32 { type ArrowType = UInt32Type; }
+impl ArrowSketchSig for u64 { type ArrowType = UInt64Type; }
+impl ArrowSketchSig for f32 { type ArrowType = Float32Type; }
+impl ArrowSketchSig for f64 { type ArrowType = Float64Type; }
+
+
+
+/// builds an arrow record batch from signatures and their metadata. metadata\[i\] describes signatures\[i\].
+pub fn sketches_to_record_batch<Sig : ArrowSketchSig>(params : &SeqSketcherParams, metadata : &[SketchMetadata], signatures : &[Vec<Sig>]) -> Result<RecordBatch, String> {
+    //
+    if metadata.len() != signatures.len() {
+        log::error!("sketches_to_record_batch : {} metadata for {} signatures", metadata.len(), signatures.len());
+        return Err("sketches_to_record_batch : metadata and signatures must have same length".to_string());
+    }
+    let sketch_size = params.get_sketch_size();
+    if signatures.iter().any(|s| s.len() != sketch_size) {
+        log::error!("sketches_to_record_batch : signature size does not match sketch size {}", sketch_size);
+        return Err("sketches_to_record_batch : signature size does not match sketch size".to_string());
+    }
+    let ids = StringArray::from_iter_values(metadata.iter().map(|m| m.name.as_str()));
+    let lengths = UInt64Array::from_iter_values(metadata.iter().map(|m| m.seq_len));
+    let values = PrimitiveArray::<Sig::ArrowType>::from_iter_values(signatures.iter().flatten().copied());
+    let item = Arc::new(Field::new("item", Sig::ArrowType::DATA_TYPE, false));
+    let sig_array = FixedSizeListArray::try_new(item.clone(), sketch_size as i32, Arc::new(values), None).map_err(|e| e.to_string())?;
+    //
+    let params_json = serde_json::to_string(params).map_err(|e| e.to_string())?;
+    let schema = Schema::new_with_metadata(vec![
+                Field::new("id", DataType::Utf8, false),
+                Field::new("seq_len", DataType::UInt64, false),
+                Field::new("signature", DataType::FixedSizeList(item, sketch_size as i32), false),
+            ], HashMap::from([(PARAMS_METADATA_KEY.to_string(), params_json)]));
+    let columns : Vec<ArrayRef> = vec![Arc::new(ids), Arc::new(lengths), Arc::new(sig_array)];
+    RecordBatch::try_new(Arc::new(schema), columns).map_err(|e| {
+            log::error!("sketches_to_record_batch : {}", e);
+            e.to_string()
+        })
+} // end of sketches_to_record_batch
+
+
+
+// opens a file for writing
+fn create_file(path : &Path) -> Result<std::fs::File, String> {
+    OpenOptions::new().write(true).create(true).truncate(true).open(path).map_err(|e| {
+            log::error!("could not open file {:?} : {}", path.as_os_str(), e);
+            println!("could not open file {:?}", path.as_os_str());
+            e.to_string()
+        })
+}
+
+
+/// writes signatures in a Parquet file. Returns the number of rows written.
+pub fn export_parquet<Sig : ArrowSketchSig>(path : &Path, params : &SeqSketcherParams, metadata : &[SketchMetadata], signatures : &[Vec<Sig>]) -> Result<usize, String> {
+    //
+    log::info!("exporting {} signatures in parquet file {:?}", signatures.len(), path);
+    //
+    let batch = sketches_to_record_batch(params, metadata, signatures)?;
+    let file = create_file(path)?;
+    let mut writer = parquet::arrow::ArrowWriter::try_new(file, batch.schema(), None).map_err(|e| e.to_string())?;
+    writer.write(&batch).map_err(|e| e.to_string())?;
+    writer.close().map_err(|e| e.to_string())?;
+    Ok(batch.num_rows())
+} // end of export_parquet
+
+
+/// writes signatures in an Arrow ipc file (feather v2). Returns the number of rows written.
+pub fn export_arrow_ipc<Sig : ArrowSketchSig>(path : &Path, params : &SeqSketcherParams, metadata : &[SketchMetadata], signatures : &[Vec<Sig>]) -> Result<usize, String> {
+    //
+    log::info!("exporting {} signatures in arrow file {:?}", signatures.len(), path);
+    //
+    let batch = sketches_to_record_batch(params, metadata, signatures)?;
+    let file = create_file(path)?;
+    let mut writer = arrow_ipc::writer::FileWriter::try_new(file, &batch.schema()).map_err(|e| e.to_string())?;
+    writer.write(&batch).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(batch.num_rows())
+} // end of export_arrow_ipc
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use arrow_array::Array;
+    use crate::sketcharg::{SketchAlgo, DataType as SeqDataType};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_export_parquet() {
+        log_init_test();
+        let dir = crate::test_dir("export");
+        let params = SeqSketcherParams::new(21, 4, SketchAlgo::PROB3A, SeqDataType::DNA);
+        let signatures : Vec<Vec<u32>> = (0..3).map(|i| (0..4).map(|j| i * 10 + j).collect()).collect();
+        let metadata : Vec<SketchMetadata> = (0..3).map(|i| SketchMetadata{name : format!("seq{}", i), seq_len : 100 + i}).collect();
+        let path = dir.join("kmerutils_test_export.parquet");
+        assert_eq!(export_parquet(&path, &params, &metadata, &signatures).unwrap(), 3);
+        // reload
+        let file = std::fs::File::open(&path).unwrap();
+        let builder = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+        let schema = builder.schema().clone();
+        assert!(schema.metadata().get(PARAMS_METADATA_KEY).unwrap().contains("PROB3A"));
+        let batch = builder.build().unwrap().next().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        let sigs = batch.column(2).as_any().downcast_ref::<FixedSizeListArray>().unwrap();
+        let row = sigs.value(2);
+        let row = row.as_any().downcast_ref::<PrimitiveArray<UInt32Type>>().unwrap();
+        assert_eq!(row.values().to_vec(), signatures[2]);
+        let _ = std::fs::remove_file(&path);
+        //
+        let path = dir.join("kmerutils_test_export.arrow");
+        assert_eq!(export_arrow_ipc(&path, &params, &metadata, &signatures).unwrap(), 3);
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_export_parquet
+
+} // end of mod tests
@@ -0,0 +1,251 @@
+//! This module runs batch sketching of fasta/fastq records as a pipeline of threads connected by bounded channels :
+//!
+//! - a reader thread parses the input by chunks of records (see [FastxChunkReader]),
+//! - a pool of workers sketches each record of a chunk in its own signature,
+//! - a writer thread hands the ids and signatures of each chunk, in input order, to a sink (writing a file, a database...).
+//!
+//! Parsing, sketching and writing overlap, and bounded queues give backpressure : when the sink is slower than
+//! the workers the queues fill up and the workers, then the reader, wait. The reader also waits while
+//! 2 * queue_size + nb_workers chunks are read and not yet written, so that chunks sketched ahead of a slow chunk
+//! and waiting to be written in order are bounded too. Memory is thus bounded by 2 * queue_size + nb_workers chunks.
+//!
+//! The first error (parsing, sketching or writing) stops all stages and is returned.
+
+
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use crossbeam::channel::bounded;
+
+use crate::base::{kmer::*, kmergenerator::*};
+use crate::io::FastxRecord;
+use crate::parallel::current_num_threads;
+
+use super::setsketchert::*;
+use super::streamsketch::{FastxChunkReader, sketch_records_each, SketchedChunk, DEFAULT_STREAM_CHUNK_SIZE};
+
+
+/// sizes of a sketching pipeline
+#[derive(Copy, Clone, Debug)]
+pub struct PipelineParams {
+    /// number of records of a chunk, the unit passed between stages
+    chunk_size : usize,
+    /// number of sketching threads
+    nb_workers : usize,
+    /// capacity (in chunks) of each of the 2 queues (reader to workers, workers to writer)
+    queue_size : usize,
+} // end of PipelineParams
+
+
+impl PipelineParams {
+    /// all sizes are at least 1
+    pub fn new(chunk_size : usize, nb_workers : usize, queue_size : usize) -> Self {
+        PipelineParams{chunk_size : chunk_size.max(1), nb_workers : nb_workers.max(1), queue_size : queue_size.max(1)}
+    }
+
+    pub fn get_chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn get_nb_workers(&self) -> usize {
+        self.nb_workers
+    }
+
+    pub fn get_queue_size(&self) -> usize {
+        self.queue_size
+    }
+
+    /// maximum number of chunks read and not yet written, which bounds chunks waiting to be written in order
+    pub fn get_reorder_window(&self) -> usize {
+        2 * self.queue_size + self.nb_workers
+    }
+} // end of impl PipelineParams
+
+
+impl Default for PipelineParams {
+    /// chunks of [DEFAULT_STREAM_CHUNK_SIZE] records, 2 workers (at most one by thread), queues of 2 chunks by worker.
+    /// Sketchers already run records of a chunk in parallel on the rayon pool, so more workers would oversubscribe the cpus.
+    fn default() -> Self {
+        let nb_workers = current_num_threads().clamp(1, 2);
+        PipelineParams::new(DEFAULT_STREAM_CHUNK_SIZE, nb_workers, 2 * nb_workers)
+    }
+} // end of impl Default for PipelineParams
+
+
+
+// converts the panic of a pipeline thread in an error
+fn thread_panic(stage : &str) -> String {
+    log::error!("sketch_pipeline : {} thread panicked", stage);
+    format!("{} thread panicked", stage)
+}
+
+
+/// sketches each DNA record of input in its own signature with a pipeline reader / workers / writer (see module doc).
+/// sink is called from the writer thread with the ids and signatures of each chunk, chunks in input order (records without kmers are skipped).
+/// For fastq records, kmers containing a base with Phred quality below min_quality are skipped (0 disables filtering).
+/// Returns the number of records read, the signatures are the same as those of [sketch_stream_records](super::streamsketch::sketch_stream_records).
+pub fn sketch_pipeline<R, Kmer, Sketcher, F, W>(input : R, sketcher : &Sketcher, params : &PipelineParams, min_quality : u8, fhash : F, mut sink : W) -> Result<usize, String>
+        where   R : Read + Send + 'static,
+                Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                Sketcher : SeqSketcherT<Kmer> + Sync,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
+                W : FnMut(Vec<String>, Vec<Vec<Sketcher::Sig>>) -> Result<(), String> + Send {
+    //
+    let mut reader = FastxChunkReader::new(input, params.get_chunk_size())?;
+    let (chunk_sender, chunk_receiver) = bounded::<(usize, Vec<FastxRecord>)>(params.get_queue_size());
+    let (sig_sender, sig_receiver) = bounded::<(usize, Result<SketchedChunk<Sketcher::Sig>, String>)>(params.get_queue_size());
+    // a chunk is read only with a credit, given back by the writer once a chunk is written
+    let (credit_sender, credit_receiver) = bounded::<()>(params.get_reorder_window());
+    for _ in 0..params.get_reorder_window() {
+        credit_sender.send(()).unwrap();
+    }
+    let fhash = &fhash;
+    //
+    let joined = crossbeam::thread::scope(|scope| {
+        // reader, stops at end of input, on a parse error or when workers or writer are gone
+        let reader_handle = scope.spawn(move |_| -> Result<usize, String> {
+            let mut rank = 0;
+            while credit_receiver.recv().is_ok() {
+                match reader.next_chunk()? {
+                    Some(records) => {
+                        if chunk_sender.send((rank, records)).is_err() {
+                            break;
+                        }
+                        rank += 1;
+                    },
+                    None => break,
+                }
+            }
+            Ok(reader.get_nb_read())
+        });
+        // workers, a worker stops on its first error or when the writer is gone
+        for _ in 0..params.get_nb_workers() {
+            let chunk_receiver = chunk_receiver.clone();
+            let sig_sender = sig_sender.clone();
+            scope.spawn(move |_| {
+                for (rank, records) in chunk_receiver.iter() {
+                    let sketched = sketch_records_each(records, sketcher, min_quality, fhash);
+                    let failed = sketched.is_err();
+                    if sig_sender.send((rank, sketched)).is_err() || failed {
+                        break;
+                    }
+                }
+            });
+        }
+        // only threads keep channel ends, so that disconnection propagates
+        drop(chunk_receiver);
+        drop(sig_sender);
+        // writer, restores input order. Returning drops the receiver, which stops workers then reader
+        let writer_handle = scope.spawn(move |_| -> Result<(), String> {
+            let mut pending = BTreeMap::<usize, SketchedChunk<Sketcher::Sig>>::new();
+            let mut next_rank = 0;
+            for (rank, sketched) in sig_receiver.iter() {
+                pending.insert(rank, sketched?);
+                while let Some((names, signatures)) = pending.remove(&next_rank) {
+                    sink(names, signatures)?;
+                    next_rank += 1;
+                    // the reader may be gone at end of input
+                    let _ = credit_sender.send(());
+                }
+            }
+            Ok(())
+        });
+        let written = writer_handle.join().map_err(|_| thread_panic("writer"));
+        let read = reader_handle.join().map_err(|_| thread_panic("reader"));
+        (read, written)
+    });
+    let (read, written) = joined.map_err(|_| thread_panic("worker"))?;
+    // a sketching or writing error stops reading, so it comes first
+    written??;
+    let nb_read = read??;
+    log::debug!("sketch_pipeline, nb records read : {}", nb_read);
+    Ok(nb_read)
+} // end of sketch_pipeline
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::io::Cursor;
+    use rand::{Rng, SeedableRng};
+    use crate::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
+    use crate::sketching::streamsketch::sketch_stream_records;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    fn hash32(kmer : &Kmer32bit) -> u32 {
+        kmer.get_compressed_value() & 0x00ffffff
+    }
+
+    // random records, some with N and some too short
+    fn random_fasta(nb_records : usize) -> Vec<u8> {
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(5);
+        let mut fasta = Vec::<u8>::new();
+        for i in 0..nb_records {
+            let len = rng.gen_range(5..300);
+            fasta.extend(format!(">r{}\n", i).as_bytes());
+            fasta.extend((0..len).map(|_| b"ACGTACGTACGTACGTN"[rng.gen_range(0..17)]));
+            fasta.push(b'\n');
+        }
+        fasta
+    }
+
+    #[test]
+    fn test_sketch_pipeline() {
+        log_init_test();
+        let params = SeqSketcherParams::new(12, 50, SketchAlgo::PROB3A, DataType::DNA);
+        let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&params);
+        let fasta = random_fasta(500);
+        let mut expected = (Vec::<String>::new(), Vec::<Vec<u32>>::new());
+        let nb_read = sketch_stream_records(Cursor::new(fasta.clone()), &sketcher, 100, 0, hash32, |n, s| {
+                expected.0.extend(n);
+                expected.1.extend(s);
+            }).unwrap();
+        assert_eq!(nb_read, 500);
+        // small chunks and queues so that workers run ahead and chunks are reordered
+        for nb_workers in [1, 4] {
+            let mut names = Vec::<String>::new();
+            let mut sigs = Vec::<Vec<u32>>::new();
+            let pipeline = PipelineParams::new(7, nb_workers, 2);
+            let nb_read = sketch_pipeline(Cursor::new(fasta.clone()), &sketcher, &pipeline, 0, hash32, |n, s| {
+                    names.extend(n);
+                    sigs.extend(s);
+                    Ok(())
+                }).unwrap();
+            assert_eq!(nb_read, 500);
+            assert_eq!(names, expected.0);
+            assert_eq!(sigs, expected.1);
+        }
+        // a sink error stops the pipeline and is returned
+        let mut nb_calls = 0;
+        let res = sketch_pipeline(Cursor::new(fasta.clone()), &sketcher, &PipelineParams::new(7, 4, 2), 0, hash32, |_, _| {
+                nb_calls += 1;
+                if nb_calls == 3 { Err("disk full".to_string()) } else { Ok(()) }
+            });
+        assert_eq!(res, Err("disk full".to_string()));
+        assert_eq!(nb_calls, 3);
+        // a parse error is returned after the chunks read before it are written
+        let mut bad = b"@r0\nACGTTGCAAGGCTTACCG\n+\nIIIIIIIIIIIIIIIIII\n".to_vec();
+        bad.extend(b"@r1\nACGTTGCAAGGCTTACCG\n+\nII\n");
+        let mut nb_written = 0;
+        let res = sketch_pipeline(Cursor::new(bad), &sketcher, &PipelineParams::new(1, 2, 1), 0, hash32, |n, _| {
+                nb_written += n.len();
+                Ok(())
+            });
+        assert!(res.is_err());
+        assert_eq!(nb_written, 1);
+        // default keeps few workers, sketchers being parallel
+        let pipeline = PipelineParams::default();
+        assert!(pipeline.get_nb_workers() <= 2);
+        assert_eq!(pipeline.get_reorder_window(), 5 * pipeline.get_nb_workers());
+    } // end of test_sketch_pipeline
+
+} // end of mod tests
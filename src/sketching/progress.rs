@@ -0,0 +1,111 @@
+//! This module defines progress reporting of long runs : batch sketching of files ([sketch_files_with_progress](super::batchsketch::sketch_files_with_progress))
+//! and all-vs-all distances ([compute_distance_matrix_with_progress](super::distances::compute_distance_matrix_with_progress)).
+//!
+//! The caller gives a callback receiving a [SketchProgress] each time a unit of work (a file, a block of distances) is done,
+//! for example to update a progress bar. The callback is called from worker threads, possibly concurrently,
+//! so it must be Sync and should be fast.
+
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+
+/// the step of work reported
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum ProgressStage {
+    /// sketching of files, units are files
+    Sketching,
+    /// distance computations, units are pairs of signatures
+    Distances,
+}
+
+
+/// state of a run sent to progress callbacks
+#[derive(Copy,Clone,Debug)]
+pub struct SketchProgress {
+    pub stage : ProgressStage,
+    /// number of units done
+    pub nb_done : usize,
+    /// total number of units
+    pub nb_total : usize,
+    /// number of kmers hashed up to now (estimated by the number of bases sketched), 0 for distances
+    pub nb_kmers : u64,
+    /// time since start
+    pub elapsed : Duration,
+    /// estimated remaining time, assuming constant speed. None before the first unit is done.
+    pub eta : Option<Duration>,
+}
+
+
+/// type of progress callbacks, the lifetime enabling closures borrowing local data
+pub type ProgressCallback<'a> = dyn Fn(SketchProgress) + Send + Sync + 'a;
+
+
+// accumulates work done by threads and calls the callback
+pub(crate) struct ProgressTracker<'a> {
+    callback : Option<&'a ProgressCallback<'a>>,
+    stage : ProgressStage,
+    nb_total : usize,
+    nb_done : AtomicUsize,
+    nb_kmers : AtomicU64,
+    start : Instant,
+}
+
+
+impl <'a> ProgressTracker<'a> {
+
+    pub(crate) fn new(callback : Option<&'a ProgressCallback<'a>>, stage : ProgressStage, nb_total : usize) -> Self {
+        ProgressTracker{callback, stage, nb_total, nb_done : AtomicUsize::new(0), nb_kmers : AtomicU64::new(0), start : Instant::now()}
+    }
+
+    /// records nb_units done with nb_kmers hashed and reports progress
+    pub(crate) fn advance(&self, nb_units : usize, nb_kmers : u64) {
+        let callback = match self.callback {
+            Some(callback) => callback,
+            None => return,
+        };
+        let nb_done = self.nb_done.fetch_add(nb_units, Ordering::Relaxed) + nb_units;
+        let nb_kmers = self.nb_kmers.fetch_add(nb_kmers, Ordering::Relaxed) + nb_kmers;
+        let elapsed = self.start.elapsed();
+        let eta = if nb_done > 0 {
+            Some(elapsed.mul_f64(self.nb_total.saturating_sub(nb_done) as f64 / nb_done as f64))
+        } else { None };
+        callback(SketchProgress{stage : self.stage, nb_done, nb_total : self.nb_total, nb_kmers, elapsed, eta});
+    } // end of advance
+
+} // end of impl ProgressTracker
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::sync::Mutex;
+    use crate::parallel::*;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_progress_tracker() {
+        log_init_test();
+        let reports = Mutex::new(Vec::<SketchProgress>::new());
+        let callback = |p : SketchProgress| reports.lock().unwrap().push(p);
+        let tracker = ProgressTracker::new(Some(&callback), ProgressStage::Sketching, 100);
+        (0..100).into_par_iter().for_each(|_| tracker.advance(1, 10));
+        let reports = reports.into_inner().unwrap();
+        assert_eq!(reports.len(), 100);
+        let last = reports.iter().max_by_key(|p| p.nb_done).unwrap();
+        assert_eq!((last.nb_done, last.nb_total), (100, 100));
+        assert_eq!(reports.iter().map(|p| p.nb_kmers).max(), Some(1000));
+        assert_eq!(last.eta, Some(Duration::ZERO));
+        // no callback, nothing to do
+        ProgressTracker::new(None, ProgressStage::Distances, 10).advance(1, 0);
+    } // end of test_progress_tracker
+
+} // end of mod tests
@@ -0,0 +1,238 @@
+//! This module provides rkyv archives of signature collections.
+//!
+//! An archive is accessed directly in a memory mapped file without deserialization :
+//! opening only validates the archive, signatures and names are then read in place.
+//! This enables fast startup of comparison jobs over millions of sketches.
+//! The signature matrix is stored flattened, row i being signature i.
+//!
+//! Compared to [sketchcontainer](super::sketchcontainer) the layout is the one of rkyv and the file is validated at opening.
+//! Validation reads the whole archive, for archives known to be intact [MappedSketchArchive::open_unchecked] skips it.
+//!
+//! Requires feature *rkyv*.
+
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use memmap2::Mmap;
+use rkyv::{Archive, Archived, Deserialize, Serialize};
+use rkyv::rancor::Error as RkyvError;
+
+use crate::sketcharg::SeqSketcherParams;
+use super::sketchcontainer::SketchMetadata;
+
+
+/// the archived structure
+#[derive(Archive, Serialize, Deserialize, Debug)]
+pub struct SketchArchive<Sig> {
+    /// sketching parameters, json encoded
+    pub params : String,
+    /// sketch size, length of each signature
+    pub sketch_size : u64,
+    /// sequence names
+    pub names : Vec<String>,
+    /// sequence lengths
+    pub seq_lens : Vec<u64>,
+    /// signatures concatenated
+    pub signatures : Vec<Sig>,
+}
+
+
+impl <Sig : Copy> SketchArchive<Sig> {
+    /// builds an archive from signatures and their metadata. metadata\[i\] describes signatures\[i\]
+    pub fn new(params : &SeqSketcherParams, metadata : &[SketchMetadata], signatures : &[Vec<Sig>]) -> Result<Self, String> {
+        if metadata.len() != signatures.len() {
+            log::error!("SketchArchive : {} metadata for {} signatures", metadata.len(), signatures.len());
+            return Err("SketchArchive : metadata and signatures must have same length".to_string());
+        }
+        let sketch_size = params.get_sketch_size();
+        if signatures.iter().any(|s| s.len() != sketch_size) {
+            log::error!("SketchArchive : signature size does not match sketch size {}", sketch_size);
+            return Err("SketchArchive : signature size does not match sketch size".to_string());
+        }
+        let params = serde_json::to_string(params).map_err(|e| e.to_string())?;
+        Ok(SketchArchive{params, sketch_size : sketch_size as u64,
+                names : metadata.iter().map(|m| m.name.clone()).collect(),
+                seq_lens : metadata.iter().map(|m| m.seq_len).collect(),
+                signatures : signatures.iter().flatten().copied().collect()})
+    }
+} // end of impl SketchArchive
+
+
+
+/// dumps signatures in a rkyv archive file. Returns the number of bytes written.
+pub fn dump_rkyv_archive<Sig>(path : &Path, params : &SeqSketcherParams, metadata : &[SketchMetadata], signatures : &[Vec<Sig>]) -> Result<usize, String>
+        where   Sig : Copy,
+                SketchArchive<Sig> : for<'a> Serialize<rkyv::api::high::HighSerializer<rkyv::util::AlignedVec, rkyv::ser::allocator::ArenaHandle<'a>, RkyvError>> {
+    //
+    log::info!("dumping {} signatures in rkyv archive {:?}", signatures.len(), path);
+    //
+    let archive = SketchArchive::new(params, metadata, signatures)?;
+    let bytes = rkyv::to_bytes::<RkyvError>(&archive).map_err(|e| {
+            log::error!("dump_rkyv_archive : serialization failed : {}", e);
+            e.to_string()
+        })?;
+    let fileres = OpenOptions::new().write(true).create(true).truncate(true).open(path);
+    if fileres.is_err() {
+        log::error!("dump_rkyv_archive could not open file {:?}", path.as_os_str());
+        println!("dump_rkyv_archive could not open file {:?}", path.as_os_str());
+        return Err("dump_rkyv_archive could not open file".to_string());
+    }
+    fileres.unwrap().write_all(&bytes).map_err(|e| e.to_string())?;
+    Ok(bytes.len())
+} // end of dump_rkyv_archive
+
+
+
+/// A memory mapped rkyv archive of signatures.
+pub struct MappedSketchArchive<Sig> {
+    mmap : Mmap,
+    params : SeqSketcherParams,
+    _sig_marker : std::marker::PhantomData<Sig>,
+}
+
+
+impl <Sig> MappedSketchArchive<Sig>
+        where   Sig : Archive,
+                Archived<SketchArchive<Sig>> : for<'a> rkyv::bytecheck::CheckBytes<rkyv::api::high::HighValidator<'a, RkyvError>> {
+    /// maps and validates an archive. Sig must be the signature type used at dump.
+    /// Validation is proportional to the archive size.
+    pub fn open(path : &Path) -> Result<Self, String> {
+        //
+        log::info!("opening rkyv archive {:?}", path);
+        //
+        let mmap = map_archive(path)?;
+        rkyv::access::<Archived<SketchArchive<Sig>>, RkyvError>(&mmap).map_err(|e| {
+                log::error!("MappedSketchArchive : invalid archive {:?} : {}", path.as_os_str(), e);
+                e.to_string()
+            })?;
+        MappedSketchArchive::from_validated(path, mmap)
+    } // end of open
+
+    /// maps an archive without validating it, in constant time.
+    ///
+    /// # Safety
+    /// The file must have been written by [dump_rkyv_archive] with the same Sig and not modified since,
+    /// otherwise accessing it is undefined behaviour.
+    pub unsafe fn open_unchecked(path : &Path) -> Result<Self, String> {
+        //
+        log::info!("opening rkyv archive {:?} without validation", path);
+        //
+        let mmap = map_archive(path)?;
+        MappedSketchArchive::from_validated(path, mmap)
+    } // end of open_unchecked
+
+    // decodes parameters and checks the archive is consistent with them
+    fn from_validated(path : &Path, mmap : Mmap) -> Result<Self, String> {
+        let archived = unsafe { rkyv::access_unchecked::<Archived<SketchArchive<Sig>>>(&mmap) };
+        let params : SeqSketcherParams = serde_json::from_str(archived.params.as_str()).map_err(|e| e.to_string())?;
+        let nb_sig = archived.names.len();
+        let consistent = archived.sketch_size.to_native() == params.get_sketch_size() as u64
+                && archived.seq_lens.len() == nb_sig
+                && nb_sig.checked_mul(params.get_sketch_size()) == Some(archived.signatures.len());
+        if !consistent {
+            log::error!("MappedSketchArchive : archive {:?} has {} names, {} lengths and {} signature values for sketch size {}",
+                    path.as_os_str(), nb_sig, archived.seq_lens.len(), archived.signatures.len(), params.get_sketch_size());
+            return Err("MappedSketchArchive : inconsistent archive".to_string());
+        }
+        log::info!("MappedSketchArchive, nb sketches : {}", nb_sig);
+        Ok(MappedSketchArchive{mmap, params, _sig_marker : std::marker::PhantomData})
+    } // end of from_validated
+
+    // the archive was validated at opening, or trusted by the caller of open_unchecked
+    fn archive(&self) -> &Archived<SketchArchive<Sig>> {
+        unsafe { rkyv::access_unchecked::<Archived<SketchArchive<Sig>>>(&self.mmap) }
+    }
+
+    /// returns sketching parameters
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
+
+    /// returns the number of signatures
+    pub fn len(&self) -> usize {
+        self.archive().names.len()
+    }
+
+    /// true if archive has no signature
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// returns name of signature i, None if i is out of range
+    pub fn get_name(&self, i : usize) -> Option<&str> {
+        self.archive().names.get(i).map(|name| name.as_str())
+    }
+
+    /// returns sequence length of signature i, None if i is out of range
+    pub fn get_seq_len(&self, i : usize) -> Option<u64> {
+        self.archive().seq_lens.get(i).map(|len| len.to_native())
+    }
+
+    /// returns signature i, in archived (little endian) form, without copy. None if i is out of range
+    pub fn get_signature(&self, i : usize) -> Option<&[Archived<Sig>]> {
+        if i >= self.len() {
+            return None;
+        }
+        // lengths were checked at opening, the range is in the signature array
+        let sketch_size = self.params.get_sketch_size();
+        self.archive().signatures.get(i * sketch_size..(i + 1) * sketch_size)
+    }
+} // end of impl MappedSketchArchive
+
+
+
+// maps file at path. The file must not be modified while mapped, archives are written once.
+fn map_archive(path : &Path) -> Result<Mmap, String> {
+    let fileres = OpenOptions::new().read(true).open(path);
+    if fileres.is_err() {
+        log::error!("MappedSketchArchive could not open file {:?}", path.as_os_str());
+        println!("MappedSketchArchive could not open file {:?}", path.as_os_str());
+        return Err("MappedSketchArchive could not open file".to_string());
+    }
+    unsafe { Mmap::map(&fileres.unwrap()) }.map_err(|e| e.to_string())
+} // end of map_archive
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sketcharg::{SketchAlgo, DataType};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_rkyv_archive() {
+        log_init_test();
+        let dir = crate::test_dir("rkyv");
+        let params = SeqSketcherParams::new(21, 4, SketchAlgo::SUPER, DataType::DNA);
+        let signatures : Vec<Vec<f32>> = (0..3).map(|i| (0..4).map(|j| (i * 10 + j) as f32 / 7.).collect()).collect();
+        let metadata : Vec<SketchMetadata> = (0..3).map(|i| SketchMetadata{name : format!("seq{}", i), seq_len : 100 + i}).collect();
+        let path = dir.join("kmerutils_test_archive.rkyv");
+        dump_rkyv_archive(&path, &params, &metadata, &signatures).unwrap();
+        let archive = MappedSketchArchive::<f32>::open(&path).unwrap();
+        assert_eq!(archive.len(), 3);
+        assert_eq!(archive.get_params().get_kmer_size(), 21);
+        assert_eq!(archive.get_name(1), Some("seq1"));
+        assert_eq!(archive.get_seq_len(2), Some(102));
+        let sig : Vec<f32> = archive.get_signature(2).unwrap().iter().map(|v| v.to_native()).collect();
+        assert_eq!(sig, signatures[2]);
+        assert!(archive.get_signature(3).is_none());
+        assert!(archive.get_name(3).is_none());
+        let unchecked = unsafe { MappedSketchArchive::<f32>::open_unchecked(&path) }.unwrap();
+        assert_eq!(unchecked.get_signature(1).unwrap().len(), 4);
+        // garbage is rejected
+        std::fs::write(&path, b"not an archive").unwrap();
+        assert!(MappedSketchArchive::<f32>::open(&path).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_rkyv_archive
+
+} // end of mod tests
@@ -0,0 +1,186 @@
+//! This module screens a mixture (a metagenome, a set of reads) against reference sketches, in the spirit of *mash screen* :
+//! which references are present in the sample.
+//!
+//! The hashes of all the kmers of the mixture (or only those kept by FracMinHash, see [MixtureHashes::with_scale]) are stored
+//! in a hash table with their multiplicity. For each reference sketch (bottom-k or FracMinHash, see [containment](super::containment)),
+//! the fraction of its hashes found in the mixture estimates the containment of the reference in the mixture,
+//! which is converted to an identity estimate C^(1/k).
+//!
+//! Reference sketches and mixture hashes must be computed with the same kmer size and hash function.
+
+
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+
+use crate::parallel::*;
+use serde::{Deserialize, Serialize};
+
+use crate::nohasher::NoHashHasher;
+use super::mashdist::ani_from_containment;
+
+
+/// The hashes of a mixture with their multiplicity
+pub struct MixtureHashes {
+    counts : HashMap<u64, u32, BuildHasherDefault<NoHashHasher>>,
+    // only hashes below threshold are stored
+    threshold : u64,
+    nb_inserted : u64,
+}
+
+
+impl Default for MixtureHashes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl MixtureHashes {
+    /// stores all hashes
+    pub fn new() -> Self {
+        MixtureHashes{counts : HashMap::default(), threshold : u64::MAX, nb_inserted : 0}
+    }
+
+    /// stores only hashes below u64::MAX / scale (FracMinHash), keeping about 1 hash out of scale
+    pub fn with_scale(scale : u64) -> Self {
+        assert!(scale > 0, "scale must be > 0");
+        MixtureHashes{counts : HashMap::default(), threshold : u64::MAX / scale, nb_inserted : 0}
+    }
+
+    /// inserts hashes of mixture kmers
+    pub fn insert<I : IntoIterator<Item = u64>>(&mut self, hashes : I) {
+        for h in hashes {
+            self.nb_inserted += 1;
+            if h <= self.threshold {
+                *self.counts.entry(h).or_insert(0) += 1;
+            }
+        }
+    } // end of insert
+
+    /// returns multiplicity of a hash, 0 if absent
+    pub fn get_count(&self, hash : u64) -> u32 {
+        self.counts.get(&hash).copied().unwrap_or(0)
+    }
+
+    /// returns number of distinct hashes stored
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// true if no hash is stored
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// returns number of hashes inserted, stored or not
+    pub fn get_nb_inserted(&self) -> u64 {
+        self.nb_inserted
+    }
+
+    /// returns the largest hash stored
+    pub fn get_threshold(&self) -> u64 {
+        self.threshold
+    }
+} // end of impl MixtureHashes
+
+
+
+/// result of screening a reference
+#[derive(Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct ScreenHit {
+    /// reference name
+    pub name : String,
+    /// fraction of reference hashes found in the mixture, estimate of containment of reference in mixture
+    pub containment : f64,
+    /// number of reference hashes found in the mixture
+    pub shared : usize,
+    /// number of reference hashes compared (those below the mixture threshold)
+    pub nb_compared : usize,
+    /// identity estimate C^(1/k)
+    pub identity : f64,
+    /// median multiplicity in the mixture of the reference hashes found, a proxy for coverage
+    pub median_multiplicity : u32,
+}
+
+
+/// screens one reference sketch (hashes in any order) against the mixture
+pub fn screen_reference(mixture : &MixtureHashes, name : &str, sketch : &[u64], kmer_size : usize) -> ScreenHit {
+    let mut hashes : Vec<u64> = sketch.iter().copied().filter(|h| *h <= mixture.threshold).collect();
+    hashes.sort_unstable();
+    hashes.dedup();
+    let mut multiplicities : Vec<u32> = hashes.iter().map(|h| mixture.get_count(*h)).filter(|c| *c > 0).collect();
+    let shared = multiplicities.len();
+    let containment = if hashes.is_empty() { 0. } else { shared as f64 / hashes.len() as f64 };
+    let median_multiplicity = if shared == 0 { 0 } else { *multiplicities.select_nth_unstable(shared / 2).1 };
+    ScreenHit{name : name.to_string(), containment, shared, nb_compared : hashes.len(),
+            identity : ani_from_containment(containment, kmer_size), median_multiplicity}
+} // end of screen_reference
+
+
+
+/// screens references (name, sketch) against the mixture, references being processed in parallel.
+/// Returns references with identity >= min_identity, by decreasing identity.
+pub fn screen(mixture : &MixtureHashes, references : &[(String, Vec<u64>)], kmer_size : usize, min_identity : f64) -> Vec<ScreenHit> {
+    log::debug!("screen, nb references : {}, mixture nb hashes : {}", references.len(), mixture.len());
+    let mut hits : Vec<ScreenHit> = references.par_iter()
+            .map(|(name, sketch)| screen_reference(mixture, name, sketch, kmer_size))
+            .filter(|hit| hit.nb_compared > 0 && hit.identity >= min_identity)
+            .collect();
+    hits.sort_by(|a, b| b.identity.total_cmp(&a.identity));
+    log::debug!("screen, nb hits : {}", hits.len());
+    hits
+} // end of screen
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use crate::sketching::containment::fracminhash_sketch;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_screen() {
+        log_init_test();
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(3109);
+        // 3 genomes of 20000 kmers, the mixture contains genome 0 at coverage 3 and half of genome 1 once
+        let genomes : Vec<Vec<u64>> = (0..3).map(|_| (0..20_000).map(|_| rng.gen::<u64>()).collect()).collect();
+        let mut mixture_hashes = Vec::<u64>::new();
+        for _ in 0..3 {
+            mixture_hashes.extend_from_slice(&genomes[0]);
+        }
+        mixture_hashes.extend_from_slice(&genomes[1][..10_000]);
+        let references : Vec<(String, Vec<u64>)> = genomes.iter().enumerate()
+                .map(|(i, g)| (format!("g{}", i), fracminhash_sketch(g.iter().copied(), 10))).collect();
+        //
+        let mut mixture = MixtureHashes::new();
+        mixture.insert(mixture_hashes.iter().copied());
+        assert_eq!(mixture.len(), 30_000);
+        let hits = screen(&mixture, &references, 21, 0.5);
+        log::info!("hits : {:?}", hits);
+        assert_eq!(hits.len(), 2);
+        assert_eq!((hits[0].name.as_str(), hits[0].containment, hits[0].median_multiplicity), ("g0", 1., 3));
+        assert_eq!(hits[1].name, "g1");
+        assert!((hits[1].containment - 0.5).abs() < 0.05);
+        assert!((hits[1].identity - 0.5f64.powf(1. / 21.)).abs() < 0.01);
+        // with a FracMinHash mixture
+        let mut frac_mixture = MixtureHashes::with_scale(10);
+        frac_mixture.insert(mixture_hashes.iter().copied());
+        assert!(frac_mixture.len() < 4000);
+        assert_eq!(frac_mixture.get_nb_inserted(), 70_000);
+        let frac_hits = screen(&frac_mixture, &references, 21, 0.);
+        assert_eq!(frac_hits.len(), 3);
+        assert_eq!(frac_hits[0].containment, 1.);
+        assert_eq!(frac_hits[2].name, "g2");
+        assert_eq!(frac_hits[2].shared, 0);
+    } // end of test_screen
+
+} // end of mod tests
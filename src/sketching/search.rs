@@ -0,0 +1,104 @@
+//! This module compares a query signature to a database of signatures.
+//!
+//! [compare_one_to_many] consumes any iterator of (metadata, signature) results, for example
+//! [SketchContainer::iter_signatures](super::sketchcontainer::SketchContainer::iter_signatures) which decodes
+//! signatures one at a time from a memory mapped file and fails on corrupted entries. The database is processed by chunks of [SEARCH_CHUNK_SIZE]
+//! signatures compared in parallel, so memory use does not depend on the database size.
+
+
+use crate::parallel::*;
+
+use super::csvexport::PairwiseResult;
+use super::sketchcontainer::SketchMetadata;
+
+
+/// number of database signatures held in memory and compared in parallel
+pub const SEARCH_CHUNK_SIZE : usize = 4096;
+
+
+// compares query to a chunk and appends hits
+fn compare_chunk<Sig>(query_name : &str, query_sig : &[Sig], chunk : &[(SketchMetadata, Vec<Sig>)], kmer_size : usize, threshold : f64, hits : &mut Vec<PairwiseResult>) -> Result<(), String>
+        where Sig : PartialEq + Sync {
+    if let Some((m, _)) = chunk.iter().find(|(_, sig)| sig.len() != query_sig.len()) {
+        log::error!("compare_one_to_many : signature of {} has size different from query size {}", m.name, query_sig.len());
+        return Err(format!("signature of {} has size different from query", m.name));
+    }
+    let chunk_hits : Vec<PairwiseResult> = chunk.par_iter()
+            .map(|(m, sig)| PairwiseResult::from_signatures(query_name, &m.name, query_sig, sig, kmer_size))
+            .filter(|res| res.jaccard >= threshold)
+            .collect();
+    hits.extend(chunk_hits);
+    Ok(())
+} // end of compare_chunk
+
+
+
+/// compares a query signature to all signatures of db_iter, obtained with kmers of size kmer_size,
+/// and returns the hits with Jaccard index >= threshold, sorted by decreasing Jaccard index.
+/// Signatures must have the size of the query. The first erroneous item of db_iter stops the comparison.
+pub fn compare_one_to_many<Sig, I>(query_name : &str, query_sig : &[Sig], db_iter : I, kmer_size : usize, threshold : f64) -> Result<Vec<PairwiseResult>, String>
+        where   Sig : PartialEq + Send + Sync,
+                I : IntoIterator<Item = Result<(SketchMetadata, Vec<Sig>), String>> {
+    //
+    log::debug!("compare_one_to_many, query : {}, threshold : {}", query_name, threshold);
+    //
+    let mut hits = Vec::<PairwiseResult>::new();
+    let mut chunk = Vec::<(SketchMetadata, Vec<Sig>)>::with_capacity(SEARCH_CHUNK_SIZE);
+    let mut nb_compared = 0;
+    for item in db_iter {
+        chunk.push(item?);
+        if chunk.len() == SEARCH_CHUNK_SIZE {
+            compare_chunk(query_name, query_sig, &chunk, kmer_size, threshold, &mut hits)?;
+            nb_compared += chunk.len();
+            chunk.clear();
+        }
+    }
+    compare_chunk(query_name, query_sig, &chunk, kmer_size, threshold, &mut hits)?;
+    nb_compared += chunk.len();
+    hits.sort_by(|a, b| b.jaccard.total_cmp(&a.jaccard));
+    log::debug!("compare_one_to_many, nb compared : {}, nb hits : {}", nb_compared, hits.len());
+    Ok(hits)
+} // end of compare_one_to_many
+
+
+//=========================================================================================
+
+
+#[cfg(all(test, feature="formats"))]
+mod tests {
+
+    use super::*;
+    use crate::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
+    use crate::sketching::sketchcontainer::{dump_sketch_container, SketchContainer};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_compare_one_to_many() {
+        log_init_test();
+        let dir = crate::test_dir("search");
+        // signature i shares i slots with query
+        let sketch_size = 10;
+        let query : Vec<u64> = (0..sketch_size as u64).collect();
+        let nb = SEARCH_CHUNK_SIZE + 11;
+        let signatures : Vec<Vec<u64>> = (0..nb).map(|i| (0..sketch_size).map(|j| if j < i % (sketch_size + 1) { j as u64 } else { 1000 + j as u64 }).collect()).collect();
+        let metadata : Vec<SketchMetadata> = (0..nb).map(|i| SketchMetadata{name : format!("g{}", i), seq_len : 1000}).collect();
+        let params = SeqSketcherParams::new(21, sketch_size, SketchAlgo::PROB3A, DataType::DNA);
+        let path = dir.join("kmerutils_test_search.sk");
+        dump_sketch_container(&path, &params, &metadata, &signatures).unwrap();
+        let container = SketchContainer::open(&path).unwrap();
+        let hits = compare_one_to_many("query", &query, container.iter_signatures::<u64>().unwrap(), 21, 0.8).unwrap();
+        let expected = (0..nb).filter(|i| i % (sketch_size + 1) >= 8).count();
+        assert_eq!(hits.len(), expected);
+        assert_eq!((hits[0].jaccard, hits[0].common), (1., 10));
+        assert_eq!(hits.last().unwrap().jaccard, 0.8);
+        assert_eq!(hits[0].query, "query");
+        assert!(container.iter_signatures::<u32>().is_err());
+        assert!(compare_one_to_many("query", &query[..5], container.iter_signatures::<u64>().unwrap(), 21, 0.8).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_compare_one_to_many
+
+} // end of mod tests
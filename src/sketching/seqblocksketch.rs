@@ -22,7 +22,7 @@ use crate::nohasher::*;
 
 use crate::base::kmergenerator::*;
 
-use rayon::prelude::*;
+use crate::parallel::*;
 
 use hnsw_rs::prelude::*;
 use probminhash::probminhasher::*;
@@ -115,7 +115,7 @@ impl BlockSeqSketcher {
         // get a kmer generator generate all kmers include in range arg. dependance upon kmer_size
         //
         let mut kmergen = KmerSeqIterator::<Kmer32bit>::new(self.kmer_size as u8, &seq);
-        kmergen.set_range(0, seq.size()).unwrap();
+        kmergen.set_range(0..seq.size()).unwrap();
         let mut wa: FnvHashMap<u32, f64> =
             FnvHashMap::with_capacity_and_hasher(self.block_size, FnvBuildHasher::default());
         // loop on blocks and compute signature of each block
@@ -243,13 +243,13 @@ pub struct SigBlockSketchFileReader {
     /// block size inside a sequence
     block_size: u32,
     /// read buffer
-    signature_buf: io::BufReader<fs::File>,
+    signature_buf: io::BufReader<Box<dyn Read + Send>>,
 } // end of struct SigBlockSketchFileReader
 
 impl SigBlockSketchFileReader {
     /// initialize the fields fname, sketch_size, kmer_size and allocates signature_buf but signatures will be read by next.
     pub fn new(fname: &String) -> Result<SigBlockSketchFileReader, String> {
-        let dumpfile_res = OpenOptions::new().read(true).open(&fname);
+        let dumpfile_res = crate::io::open_decompressed(std::path::Path::new(&fname));
         let dumpfile;
         if dumpfile_res.is_ok() {
             dumpfile = dumpfile_res.unwrap();
@@ -257,7 +257,7 @@ impl SigBlockSketchFileReader {
             println!("cannot open {}", fname);
             std::process::exit(1);
         }
-        let mut signature_buf: io::BufReader<fs::File> =
+        let mut signature_buf: io::BufReader<Box<dyn Read + Send>> =
             io::BufReader::with_capacity(1_000_000_000, dumpfile);
         let mut buf_u32 = [0u8; 4];
         let mut io_res;
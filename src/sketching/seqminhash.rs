@@ -27,11 +27,10 @@ pub fn sketch_seqrange_superminhash(seq: &Sequence, range:&Range<usize>, kmer_si
     match kmer_size {
         16 => {
             let mut kmergen = KmerSeqIterator::<Kmer16b32bit>::new(16, &seq);
-            kmergen.set_range(range.start, range.end).unwrap();
+            kmergen.set_range(range.start..range.end).unwrap();
             loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        let canonical =  kmer.reverse_complement().min(kmer);
+                match kmergen.next_canonical() {
+                    Some(canonical) => {
                         let hashval = invhash::int32_hash(canonical.0);
                         sminhash.sketch(&hashval).unwrap();
                     },
@@ -43,11 +42,10 @@ pub fn sketch_seqrange_superminhash(seq: &Sequence, range:&Range<usize>, kmer_si
         },
         9..=15 => {
             let mut kmergen = KmerSeqIterator::<Kmer32bit>::new(kmer_size as u8, &seq);
-            kmergen.set_range(range.start, range.end).unwrap();
+            kmergen.set_range(range.start..range.end).unwrap();
             loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        let canonical =  kmer.reverse_complement().min(kmer);
+                match kmergen.next_canonical() {
+                    Some(canonical) => {
                         let hashval = invhash::int32_hash(canonical.0);
                         sminhash.sketch(&hashval).unwrap();
                     },
@@ -73,14 +71,13 @@ pub fn sketch_seqrange_minhash(seq: &Sequence, range:&Range<usize>, kmer_size:us
     match kmer_size {
         16 => {
             let mut kmergen = KmerSeqIterator::<Kmer16b32bit>::new(16, &seq);
-            if let Err(_) = kmergen.set_range(range.start, range.end) {
+            if kmergen.set_range(range.start..range.end).is_err() {
                 println!("sketch_seqrange_minhash: bad range, start = {} , end = {}", range.start, range.end);
                 panic!("bad range");
             }
             loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        let canonical = kmer.reverse_complement().min(kmer);
+                match kmergen.next_canonical() {
+                    Some(canonical) => {
                         let hashval = invhash::int32_hash(canonical.0);
 //                        let hashval = kmer.0;
 //                        trace!("pushed hasv : {} ",  hashval);
@@ -93,15 +90,14 @@ pub fn sketch_seqrange_minhash(seq: &Sequence, range:&Range<usize>, kmer_size:us
         },
         9..=15 => {
             let mut kmergen = KmerSeqIterator::<Kmer32bit>::new(kmer_size as u8, &seq);
-            if let Err(_) = kmergen.set_range(range.start, range.end) {
+            if kmergen.set_range(range.start..range.end).is_err() {
                 println!("sketch_seqrange_minhash: bad range, start = {} , end = {}", range.start, range.end);
                 panic!("bad range");
             }
             
             loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        let canonical =  kmer.reverse_complement().min(kmer);
+                match kmergen.next_canonical() {
+                    Some(canonical) => {
                         let hashval = invhash::int32_hash(canonical.0);
 //                        debug!("pushed hasval : {} ",  hashval);
                         minhash.push(&hashval);
@@ -145,10 +141,9 @@ mod tests {
         // union of the 2 sets of kmer has cardinal 59
         let resdist:MinHashDist  = minhash_distance(&sk1, &sk2);
         //
-        println!("distance minhash (contain, dist, common, total): {}  {}   {}  {} ",
-               resdist.0, resdist.1, resdist.2, resdist.3);
+        println!("distance minhash : {:?}", resdist);
         // sketch size id 20 i.e 1/3 of total 
-        assert!(resdist.3 >= 3);
+        assert!(resdist.total >= 3);
     } // end of  test_minhash_overlapping_ranges
 
 
@@ -174,9 +169,9 @@ mod tests {
         // union of the 2 sets of kmer has cardinal 65
         let resdist:MinHashDist  = minhash_distance(&sk1, &sk2);
         //
-        println!("distance super minhash (contain, dist, common, total): {} {} {} {}  ", resdist.0, resdist.1, resdist.2, resdist.3);
+        println!("distance super minhash : {:?}", resdist);
         //
-        assert!(resdist.3 == 20);
+        assert!(resdist.total == 20);
     } // end of  test_superminhash_overlapping_ranges
 
 
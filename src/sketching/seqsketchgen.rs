@@ -0,0 +1,750 @@
+//! This module gathers the sketching code shared by DNA sequences ([Sequence]) and amino acid sequences
+//! ([SequenceAA](crate::aautils::kmeraa::SequenceAA)).
+//!
+//! A sequence type only needs to implement [KmerSequence] (kmer generation along the sequence), the sketching algorithms
+//! are then written once, generically over the sequence and kmer types :
+//! - [probminhash3a_signature] for ProbMinHash3a (weighted by kmer multiplicities),
+//! - [superminhash_signature], [optdens_signature] and [revoptdens_signature] for SuperMinHash, OptDensMinHash and RevOptDensMinHash.
+//!
+//! The sketchers of [setsketchert](super::setsketchert) and [aautils::setsketchert](crate::aautils::setsketchert) delegate to these functions.
+//! Each function sketches a collection of sequences into one signature, [sketch_each] (or [try_sketch_each] for fallible algorithms)
+//! dispatches sequences in parallel to get one signature by sequence.
+//!
+//! Raw bytes (a fasta record) can be sketched without copy in a [Sequence] by wrapping them in a [DnaSlice]
+//! (or [AaSlice](crate::aautils::kmeraa::AaSlice) for amino acids), bases being checked during kmer generation.
+
+
+use std::fmt::Debug;
+use std::ops::Range;
+use std::hash::{BuildHasher, BuildHasherDefault};
+use std::collections::HashMap;
+
+
+use ahash::AHasher;
+use rand_distr::uniform::SampleUniform;
+use crate::parallel::*;
+
+use probminhash::{probminhasher::ProbMinHash3a, superminhasher::SuperMinHash, densminhash::*};
+
+use crate::nohasher::NoHashHasher;
+use crate::base::{kmer::*, kmergenerator::*};
+pub use crate::base::kmersequence::{KmerSequence, DnaSlice, CanonicalKmers};
+use crate::sketcharg::SeqSketcherParams;
+
+use super::kmerfilter::SolidKmerFilter;
+use crate::base::scratch::{with_scratch, with_scratch_weights};
+use super::smallseq::{probminhash3a_small_signature_stats, SMALL_SEQ_MAX_BASES};
+use super::sketchstats::{SketchStats, SigsWithStats};
+
+
+/// hasher of kmer weight maps : ahash with fixed seeds, so the iteration order of a map does not change between runs.  
+/// ahash is much faster than Fnv on u32/u64 keys (Fnv reads them byte by byte). Seeds are stored in the BuildHasher,
+/// a BuildHasherDefault\<AHasher\> would fetch them for each hashed kmer.
+#[derive(Clone,Debug)]
+pub struct BuildKmerWeightHasher(ahash::RandomState);
+
+impl Default for BuildKmerWeightHasher {
+    fn default() -> Self {
+        BuildKmerWeightHasher(ahash::RandomState::with_seeds(0x243f_6a88_85a3_08d3, 0x1319_8a2e_0370_7344, 0xa409_3822_299f_31d0, 0x082e_fa98_ec4e_6c89))
+    }
+}
+
+impl BuildHasher for BuildKmerWeightHasher {
+    type Hasher = AHasher;
+    #[inline]
+    fn build_hasher(&self) -> AHasher {
+        self.0.build_hasher()
+    }
+}
+
+/// map from kmer hash values to their multiplicity, filled by [accumulate_kmer_weights] and sketched by ProbMinHash3a
+pub type KmerWeights<V> = HashMap<V, u64, BuildKmerWeightHasher>;
+
+
+/// number of sequences buffered by the *_iter* methods of sketchers before sketching them in parallel
+pub const SKETCH_ITER_BLOCK_SIZE : usize = 1000;
+
+
+
+
+
+/// guess of the number of kmers of a collection of sequences
+pub fn get_nbkmer_guess_seqs<Kmer, Seq>(vseq : &[&Seq]) -> usize
+        where Seq : KmerSequence<Kmer> + ?Sized {
+    if vseq.len() == 1 {
+        return vseq[0].get_nbkmer_guess();
+    }
+    let total_nb_base = vseq.iter().fold(0, |acc, seq | acc + seq.get_nb_bases());
+    // for small files do not forget upperbound by size of file (or seq list)
+    total_nb_base.min(10_000_000 * (1usize + total_nb_base.max(1).ilog2() as usize))
+} // end of get_nbkmer_guess_seqs
+
+
+
+/// upper bound of the number of distinct kmers of size kmer_size, 2^(bits per base * kmer_size), or usize::MAX if it overflows.
+/// Bits per base are deduced from the number of bases a compressed kmer can hold (2 for DNA, 5 for amino acids).  
+/// Weight maps are allocated for at most this number of entries : for small kmers a map sized on sequence length
+/// would be mostly empty and its accesses would miss cache.
+pub fn get_nb_distinct_kmer_max<Kmer>(kmer_size : usize) -> usize
+        where Kmer : CompressedKmerT {
+    let nb_bits = 8 * std::mem::size_of::<Kmer::Val>() / Kmer::get_nb_base_max() * kmer_size;
+    if nb_bits < usize::BITS as usize { 1usize << nb_bits } else { usize::MAX }
+} // end of get_nb_distinct_kmer_max
+
+
+
+/// generates kmers of all sequences and calls f with each kmer and its hash value.
+/// Kmers whose hash is not solid are skipped if a filter is given (see [SolidKmerFilter]).
+/// Returns the number of kmers generated, filtered kmers included.
+pub fn hash_kmers<Kmer, Seq, F, G>(vseq : &[&Seq], kmer_size : usize, fhash : &F, solid_filter : Option<&SolidKmerFilter>, mut f : G) -> u64
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : std::hash::Hash,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                F : Fn(&Kmer) -> Kmer::Val,
+                G : FnMut(&Kmer, Kmer::Val) {
+    //
+    let mut nb_kmer_generated : u64 = 0;
+    for seq in vseq {
+        seq.for_each_kmer(kmer_size, |kmer| {
+            nb_kmer_generated += 1;
+            if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % 500_000_000 == 0 {
+                log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
+            }
+            let hashval = fhash(&kmer);
+            if let Some(filter) = solid_filter {
+                if !filter.is_solid(&hashval) {
+                    return;
+                }
+            }
+            f(&kmer, hashval);
+        });
+    }
+    nb_kmer_generated
+} // end of hash_kmers
+
+
+
+/// number of kmers buffered by [hash_kmers_batched] before hashing them
+pub const KMER_HASH_BATCH_SIZE : usize = 1024;
+
+
+/// same as [hash_kmers], kmers being buffered by batches of [KMER_HASH_BATCH_SIZE] and hashed in a tight loop
+/// before f is called with the kmers of a batch and their hash values (in kmer generation order).
+/// This spares a call chain by kmer between kmer generation, hashing and sketching, and lets the compiler vectorize fhash.
+pub fn hash_kmers_batched<Kmer, Seq, F, G>(vseq : &[&Seq], kmer_size : usize, fhash : &F, solid_filter : Option<&SolidKmerFilter>, mut f : G) -> u64
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : std::hash::Hash,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                F : Fn(&Kmer) -> Kmer::Val,
+                G : FnMut(&[Kmer], &[Kmer::Val]) {
+    //
+    let mut kmers = Vec::<Kmer>::with_capacity(KMER_HASH_BATCH_SIZE);
+    let mut hashvals = Vec::<Kmer::Val>::with_capacity(KMER_HASH_BATCH_SIZE);
+    let mut nb_kmer_generated : u64 = 0;
+    let mut flush = |kmers : &mut Vec<Kmer>, hashvals : &mut Vec<Kmer::Val>| {
+        hashvals.clear();
+        hashvals.extend(kmers.iter().map(fhash));
+        if let Some(filter) = solid_filter {
+            // we keep kmers and hash values aligned
+            let mut nb_kept = 0;
+            for i in 0..kmers.len() {
+                if filter.is_solid(&hashvals[i]) {
+                    kmers[nb_kept] = kmers[i];
+                    hashvals[nb_kept] = hashvals[i];
+                    nb_kept += 1;
+                }
+            }
+            kmers.truncate(nb_kept);
+            hashvals.truncate(nb_kept);
+        }
+        f(kmers, hashvals);
+        kmers.clear();
+    };
+    for seq in vseq {
+        seq.for_each_kmer(kmer_size, |kmer| {
+            kmers.push(kmer);
+            if kmers.len() == KMER_HASH_BATCH_SIZE {
+                nb_kmer_generated += KMER_HASH_BATCH_SIZE as u64;
+                if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % (500_000 * KMER_HASH_BATCH_SIZE as u64) == 0 {
+                    log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
+                }
+                flush(&mut kmers, &mut hashvals);
+            }
+        });
+    }
+    let nb_last = kmers.len() as u64;
+    if nb_last > 0 {
+        flush(&mut kmers, &mut hashvals);
+    }
+    nb_kmer_generated + nb_last
+} // end of hash_kmers_batched
+
+
+
+/// adds the multiplicities of kmer hashes of sequences to weights
+pub fn accumulate_kmer_weights<Kmer, Seq, F>(vseq : &[&Seq], kmer_size : usize, fhash : &F, weights : &mut KmerWeights<Kmer::Val>)
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : num::PrimInt + Debug,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                F : Fn(&Kmer) -> Kmer::Val {
+    hash_kmers(vseq, kmer_size, fhash, None, |_, hashval| *weights.entry(hashval).or_insert(0) += 1);
+} // end of accumulate_kmer_weights
+
+
+
+/// sketches each sequence in its own signature, sequences being processed in parallel.
+/// The returned signatures are in the order of vseq : the indexed parallel iterator writes each signature at the rank
+/// of its sequence, so the order does not depend on scheduling (work stealing) and results are deterministic.  
+/// Sketching functions must collect this way (or use an indexed write) rather than gathering results as threads complete them.
+pub fn sketch_each<Seq, Sig, G>(vseq : &[&Seq], sketch_one : G) -> Vec<Vec<Sig>>
+        where   Seq : Sync + ?Sized,
+                Sig : Send,
+                G : Fn(&Seq) -> Vec<Sig> + Sync {
+    vseq.par_iter().map(|seq| sketch_one(seq)).collect()
+} // end of sketch_each
+
+
+
+/// same as [sketch_each] for a fallible sketching of a sequence. The first error encountered is returned.
+pub fn try_sketch_each<Seq, Sig, G>(vseq : &[&Seq], sketch_one : G) -> Result<Vec<Vec<Sig>>, String>
+        where   Seq : Sync + ?Sized,
+                Sig : Send,
+                G : Fn(&Seq) -> Result<Vec<Sig>, String> + Sync {
+    vseq.par_iter().map(|seq| sketch_one(seq)).collect()
+} // end of try_sketch_each
+
+
+
+/// same as [try_sketch_each], sketch_one filling also the statistics of the kmers it sketched (see [SketchStats]).
+pub fn try_sketch_each_stats<Seq, Sig, G>(vseq : &[&Seq], sketch_one : G) -> Result<SigsWithStats<Sig>, String>
+        where   Seq : Sync + ?Sized,
+                Sig : Send,
+                G : Fn(&Seq, &mut SketchStats) -> Result<Vec<Sig>, String> + Sync {
+    let sketched : Vec<(Vec<Sig>, SketchStats)> = vseq.par_iter().map(|seq| {
+            let mut stats = SketchStats::default();
+            sketch_one(seq, &mut stats).map(|sig| (sig, stats))
+        }).collect::<Result<_, String>>()?;
+    Ok(sketched.into_iter().unzip())
+} // end of try_sketch_each_stats
+
+
+
+/// ProbMinHash3a signature of a weight map, applying the abundance filter of params if any.
+pub fn probminhash3a_weights_signature<V>(mut weights : KmerWeights<V>, params : &SeqSketcherParams) -> Vec<V>
+        where V : num::PrimInt + Debug + Default + std::hash::Hash {
+    weights_signature(&mut weights, params, None)
+} // end of probminhash3a_weights_signature
+
+
+// same as probminhash3a_weights_signature, weights being left filtered in place so that a scratch map can be reused.
+// Counts of the filtered weights are added to stats if given.
+fn weights_signature<V>(weights : &mut KmerWeights<V>, params : &SeqSketcherParams, stats : Option<&mut SketchStats>) -> Vec<V>
+        where V : num::PrimInt + Debug + Default + std::hash::Hash {
+    if params.has_abundance_filter() {
+        weights.retain(|_, c| params.is_count_accepted(*c));
+    }
+    if let Some(stats) = stats {
+        stats.add_counts(weights.values().copied());
+    }
+    let mut pminhash = ProbMinHash3a::<V,NoHashHasher>::new(params.get_sketch_size(), V::default());
+    pminhash.hash_weigthed_hashmap(weights);
+    pminhash.get_signature().clone()
+} // end of weights_signature
+
+
+
+/// ProbMinHash3a signature of sequences, kmers being weighted by their multiplicity.
+/// The weight map is a scratch buffer of the current thread (see [scratch](crate::base::scratch)).
+/// Sequences with at most [SMALL_SEQ_MAX_BASES] bases in total count kmers in a sorted vector instead (see [smallseq](super::smallseq)).
+pub fn probminhash3a_signature<Kmer, Seq, F>(vseq : &[&Seq], params : &SeqSketcherParams, fhash : &F) -> Vec<Kmer::Val>
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : num::PrimInt + Debug + 'static,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                F : Fn(&Kmer) -> Kmer::Val {
+    probminhash3a_signature_stats(vseq, params, fhash, None)
+} // end of probminhash3a_signature
+
+
+
+/// same as [probminhash3a_signature], adding to stats (if given) the statistics of the kmers sketched, after abundance filtering.
+pub fn probminhash3a_signature_stats<Kmer, Seq, F>(vseq : &[&Seq], params : &SeqSketcherParams, fhash : &F, mut stats : Option<&mut SketchStats>) -> Vec<Kmer::Val>
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : num::PrimInt + Debug + 'static,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                F : Fn(&Kmer) -> Kmer::Val {
+    let nb_bases : usize = vseq.iter().map(|seq| seq.get_nb_bases()).sum();
+    let mut nb_generated : u64 = 0;
+    let signature = if nb_bases <= SMALL_SEQ_MAX_BASES && params.get_sketch_size() > 1 {
+        probminhash3a_small_signature_stats(params, |hashvals| {
+            nb_generated = hash_kmers(vseq, params.get_kmer_size(), fhash, None, |_, hashval| hashvals.push(hashval))
+        }, stats.as_deref_mut())
+    }
+    else {
+        // if we get very large sequence (many Gb length) we must be cautious on size of hashmap; i.e about number of different kmers!!!
+        let nb_kmer = get_nbkmer_guess_seqs(vseq).min(get_nb_distinct_kmer_max::<Kmer>(params.get_kmer_size()));
+        with_scratch_weights(nb_kmer, |weights| {
+            accumulate_kmer_weights(vseq, params.get_kmer_size(), fhash, weights);
+            if stats.is_some() {
+                nb_generated = weights.values().sum();
+            }
+            weights_signature(weights, params, stats.as_deref_mut())
+        })
+    };
+    if let Some(stats) = stats {
+        stats.add_bases(vseq, params.get_kmer_size(), nb_generated);
+    }
+    signature
+} // end of probminhash3a_signature_stats
+
+
+
+// number of hash values remembered by superminhash_signature to skip repeated values, a power of 2
+const RECENT_HASH_CACHE_SIZE : usize = 1024;
+
+
+/// SuperMinHash signature of sequences. The type argument S is f32 or f64.  
+/// Returns an error if a kmer could not be sketched (the remaining kmers are not sketched).
+pub fn superminhash_signature<Kmer, Seq, S, F>(vseq : &[&Seq], params : &SeqSketcherParams, fhash : &F, solid_filter : Option<&SolidKmerFilter>) -> Result<Vec<S>, String>
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : num::PrimInt + Debug + 'static,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                S : num::Float + SampleUniform + Debug + 'static,
+                F : Fn(&Kmer) -> Kmer::Val {
+    superminhash_signature_stats(vseq, params, fhash, solid_filter, None)
+} // end of superminhash_signature
+
+
+
+/// same as [superminhash_signature], adding to stats (if given) the statistics of the kmers sketched, after solid filtering.
+pub fn superminhash_signature_stats<Kmer, Seq, S, F>(vseq : &[&Seq], params : &SeqSketcherParams, fhash : &F, solid_filter : Option<&SolidKmerFilter>,
+            stats : Option<&mut SketchStats>) -> Result<Vec<S>, String>
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : num::PrimInt + Debug + 'static,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                S : num::Float + SampleUniform + Debug + 'static,
+                F : Fn(&Kmer) -> Kmer::Val {
+    //
+    let mut counts = stats.is_some().then(KmerWeights::<Kmer::Val>::default);
+    let sketch_size = params.get_sketch_size();
+    let new = || SuperMinHash::<S, Kmer::Val, NoHashHasher>::new(sketch_size, BuildHasherDefault::<NoHashHasher>::default());
+    with_scratch(sketch_size, new, |sminhash| {
+            let mut error : Option<String> = None;
+            // sketching again a value does not change a SuperMinHash signature, so values recently sketched
+            // (repeats, low complexity regions) are skipped
+            let mut recent = [(false, Kmer::Val::default()); RECENT_HASH_CACHE_SIZE];
+            let nb_generated = hash_kmers_batched(vseq, params.get_kmer_size(), fhash, solid_filter, |kmers, hashvals| {
+                if error.is_some() {
+                    return;
+                }
+                if let Some(counts) = counts.as_mut() {
+                    hashvals.iter().for_each(|hashval| *counts.entry(*hashval).or_insert(0) += 1);
+                }
+                for (kmer, hashval) in kmers.iter().zip(hashvals) {
+                    let slot = &mut recent[num::ToPrimitive::to_u64(hashval).unwrap() as usize & (RECENT_HASH_CACHE_SIZE - 1)];
+                    if slot.0 && slot.1 == *hashval {
+                        continue;
+                    }
+                    *slot = (true, *hashval);
+                    if sminhash.sketch(hashval).is_err() {
+                        log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
+                        error = Some(format!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer()));
+                        return;
+                    }
+                }
+            });
+            if let Some(e) = error {
+                return Err(e);
+            }
+            if let (Some(stats), Some(counts)) = (stats, counts) {
+                stats.add_counts(counts.into_values());
+                stats.add_bases(vseq, params.get_kmer_size(), nb_generated);
+            }
+            Ok(sminhash.get_hsketch().clone())
+        },
+        |sminhash| { sminhash.reinit(); true })
+} // end of superminhash_signature_stats
+
+
+
+/// OptDensMinHash signature of sequences. The type argument S is f32 or f64.
+pub fn optdens_signature<Kmer, Seq, S, F>(vseq : &[&Seq], params : &SeqSketcherParams, fhash : &F, solid_filter : Option<&SolidKmerFilter>) -> Vec<S>
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : num::PrimInt + Debug + 'static,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                S : num::Float + SampleUniform + Debug + 'static,
+                F : Fn(&Kmer) -> Kmer::Val {
+    optdens_signature_stats(vseq, params, fhash, solid_filter, None)
+} // end of optdens_signature
+
+
+
+/// same as [optdens_signature], adding to stats (if given) the statistics of the kmers sketched, after solid filtering.
+pub fn optdens_signature_stats<Kmer, Seq, S, F>(vseq : &[&Seq], params : &SeqSketcherParams, fhash : &F, solid_filter : Option<&SolidKmerFilter>,
+            stats : Option<&mut SketchStats>) -> Vec<S>
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : num::PrimInt + Debug + 'static,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                S : num::Float + SampleUniform + Debug + 'static,
+                F : Fn(&Kmer) -> Kmer::Val {
+    //
+    let mut counts = stats.is_some().then(KmerWeights::<Kmer::Val>::default);
+    let sketch_size = params.get_sketch_size();
+    let new = || OptDensMinHash::<S, Kmer::Val, NoHashHasher>::new(sketch_size, BuildHasherDefault::<NoHashHasher>::default());
+    with_scratch(sketch_size, new, |sminhash| {
+            let nb_generated = hash_kmers(vseq, params.get_kmer_size(), fhash, solid_filter, |_, hashval| {
+                if let Some(counts) = counts.as_mut() {
+                    *counts.entry(hashval).or_insert(0) += 1;
+                }
+                sminhash.sketch(&hashval)
+            });
+            // do not forget to close sketching (it calls densification!)
+            sminhash.end_sketch();
+            if let (Some(stats), Some(counts)) = (stats, counts) {
+                stats.add_counts(counts.into_values());
+                stats.add_bases(vseq, params.get_kmer_size(), nb_generated);
+            }
+            sminhash.get_hsketch().clone()
+        },
+        |sminhash| { sminhash.reinit(); true })
+} // end of optdens_signature_stats
+
+
+
+/// RevOptDensMinHash signature of sequences. The type argument S is f32 or f64.
+pub fn revoptdens_signature<Kmer, Seq, S, F>(vseq : &[&Seq], params : &SeqSketcherParams, fhash : &F, solid_filter : Option<&SolidKmerFilter>) -> Vec<S>
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : num::PrimInt + Debug + 'static,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                S : num::Float + SampleUniform + Debug + 'static,
+                F : Fn(&Kmer) -> Kmer::Val {
+    revoptdens_signature_stats(vseq, params, fhash, solid_filter, None)
+} // end of revoptdens_signature
+
+
+
+/// same as [revoptdens_signature], adding to stats (if given) the statistics of the kmers sketched, after solid filtering.
+pub fn revoptdens_signature_stats<Kmer, Seq, S, F>(vseq : &[&Seq], params : &SeqSketcherParams, fhash : &F, solid_filter : Option<&SolidKmerFilter>,
+            stats : Option<&mut SketchStats>) -> Vec<S>
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : num::PrimInt + Debug + 'static,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                S : num::Float + SampleUniform + Debug + 'static,
+                F : Fn(&Kmer) -> Kmer::Val {
+    //
+    let mut counts = stats.is_some().then(KmerWeights::<Kmer::Val>::default);
+    let sketch_size = params.get_sketch_size();
+    let new = || RevOptDensMinHash::<S, Kmer::Val, NoHashHasher>::new(sketch_size, BuildHasherDefault::<NoHashHasher>::default());
+    with_scratch(sketch_size, new, |sminhash| {
+            let nb_generated = hash_kmers(vseq, params.get_kmer_size(), fhash, solid_filter, |_, hashval| {
+                if let Some(counts) = counts.as_mut() {
+                    *counts.entry(hashval).or_insert(0) += 1;
+                }
+                sminhash.sketch(&hashval)
+            });
+            // do not forget to close sketching (it calls densification!)
+            sminhash.end_sketch();
+            if let (Some(stats), Some(counts)) = (stats, counts) {
+                stats.add_counts(counts.into_values());
+                stats.add_bases(vseq, params.get_kmer_size(), nb_generated);
+            }
+            sminhash.get_hsketch().clone()
+        },
+        |sminhash| { sminhash.reinit(); true })
+} // end of revoptdens_signature_stats
+
+
+//=========================================================================================
+//  sketching of one huge sequence by chunks
+
+
+/// splits positions 0..nb_bases of a sequence in chunks of chunk_size kmer starts, each chunk overlapping the next one
+/// by kmer_size - 1 bases, so each kmer of the sequence is in exactly one chunk. Returns an error if chunk_size is 0.
+pub fn get_chunk_ranges(nb_bases : usize, kmer_size : usize, chunk_size : usize) -> Result<Vec<Range<usize>>, String> {
+    if chunk_size == 0 || kmer_size == 0 {
+        log::error!("get_chunk_ranges : chunk size {} and kmer size {} must be positive", chunk_size, kmer_size);
+        return Err(format!("chunk size {} and kmer size {} must be positive", chunk_size, kmer_size));
+    }
+    let nb_kmers = (nb_bases + 1).saturating_sub(kmer_size);
+    let ranges = (0..nb_kmers).step_by(chunk_size).map(|start| start..nb_bases.min(start + chunk_size + kmer_size - 1)).collect();
+    Ok(ranges)
+} // end of get_chunk_ranges
+
+
+
+/// views on the chunks of a DNA sequence, see [get_chunk_ranges]
+pub fn get_chunk_views(seq : &Sequence, kmer_size : usize, chunk_size : usize) -> Result<Vec<SequenceView<'_>>, String> {
+    get_chunk_ranges(seq.size(), kmer_size, chunk_size)?.into_iter().map(|range| seq.view(range)).collect()
+} // end of get_chunk_views
+
+
+
+/// ProbMinHash3a signature of the union of chunks of a sequence (see [get_chunk_ranges]).
+/// Kmer weights of chunks are counted in parallel and summed before sketching, so the signature is the one of the whole sequence.
+pub fn probminhash3a_chunked_signature<Kmer, Seq, F>(chunks : &[&Seq], params : &SeqSketcherParams, fhash : &F) -> Vec<Kmer::Val>
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : num::PrimInt + Debug + Send,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                F : Fn(&Kmer) -> Kmer::Val + Sync {
+    //
+    let nb_kmer_max = get_nb_distinct_kmer_max::<Kmer>(params.get_kmer_size());
+    let v_weights : Vec<KmerWeights<Kmer::Val>> = chunks.par_iter().map(|chunk| {
+            let mut weights = KmerWeights::<Kmer::Val>::with_capacity_and_hasher(nb_kmer_max.min(chunk.get_nbkmer_guess()), Default::default());
+            accumulate_kmer_weights(&[*chunk], params.get_kmer_size(), fhash, &mut weights);
+            weights
+        }).collect();
+    let weights = v_weights.into_iter().fold(KmerWeights::<Kmer::Val>::default(), |mut acc, mut weights| {
+            // we add the smaller map to the larger
+            if acc.len() < weights.len() {
+                std::mem::swap(&mut acc, &mut weights);
+            }
+            for (hashval, count) in weights {
+                *acc.entry(hashval).or_insert(0) += count;
+            }
+            acc
+        });
+    probminhash3a_weights_signature(weights, params)
+} // end of probminhash3a_chunked_signature
+
+
+
+/// SuperMinHash signature of the union of chunks of a sequence (see [get_chunk_ranges]).
+/// Chunks are sketched in parallel, a SuperMinHash signature being the componentwise minimum of the values of sketched items,
+/// the componentwise minimum of chunk signatures is the signature of the whole sequence.
+pub fn superminhash_chunked_signature<Kmer, Seq, S, F>(chunks : &[&Seq], params : &SeqSketcherParams, fhash : &F) -> Result<Vec<S>, String>
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : num::PrimInt + Debug + 'static,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                S : num::Float + SampleUniform + Debug + Send + 'static,
+                F : Fn(&Kmer) -> Kmer::Val + Sync {
+    //
+    let signatures = try_sketch_each(chunks, |chunk| superminhash_signature(&[chunk], params, fhash, None))?;
+    let merged = signatures.into_iter().reduce(|mut acc, sig| {
+            acc.iter_mut().zip(sig).for_each(|(a, s)| if s < *a { *a = s; });
+            acc
+        });
+    match merged {
+        Some(sig) => Ok(sig),
+        // no chunk (sequence shorter than kmer size), the signature of an empty sequence
+        None => superminhash_signature::<Kmer, Seq, S, F>(&[], params, fhash, None),
+    }
+} // end of superminhash_chunked_signature
+
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::aautils::kmeraa::{KmerAA32bit, SequenceAA};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_generic_sketch_dna_aa() {
+        log_init_test();
+        // same generic code sketches DNA and AA sequences
+        let seqs = [Sequence::new(b"ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGC", 2),
+                    Sequence::new(b"TTTAGAAAATTTCCGGATCATCGTACGG", 2)];
+        let vseq : Vec<&Sequence> = seqs.iter().collect();
+        let params = SeqSketcherParams::new(8, 50, crate::sketcharg::SketchAlgo::PROB3A, crate::sketcharg::DataType::DNA);
+        let fhash = | kmer : &Kmer32bit | -> u32 { kmer.get_compressed_value() };
+        let mut nb_kmers = 0;
+        hash_kmers(&vseq[..1], 8, &fhash, None, |_, _| nb_kmers += 1);
+        assert_eq!(nb_kmers, seqs[0].size() - 7);
+        let sigs = sketch_each(&vseq, |seq| probminhash3a_signature(&[seq], &params, &fhash));
+        assert_eq!(sigs.len(), 2);
+        assert_eq!(sigs[0], probminhash3a_signature(&vseq[..1], &params, &fhash));
+        let sketch_one = |seq : &Sequence| probminhash3a_signature(&[seq], &params, &fhash);
+        // a failure on one sequence is reported, not a panic
+        let res = try_sketch_each(&vseq, |seq| if seq.size() == seqs[1].size() { Err("bad sequence".to_string()) } else { Ok(sketch_one(seq)) });
+        assert_eq!(res, Err("bad sequence".to_string()));
+        let sig = superminhash_signature::<Kmer32bit, _, f64, _>(&vseq, &params, &fhash, None).unwrap();
+        assert_eq!(sig.len(), 50);
+        //
+        let seqaa = [SequenceAA::new(b"MTEYKLVVVGAGGVGKSALTIQLIQNHFVDEYDPTIEDSY").unwrap()];
+        let vseqaa : Vec<&SequenceAA> = seqaa.iter().collect();
+        let paramsaa = SeqSketcherParams::new(5, 20, crate::sketcharg::SketchAlgo::OPTDENS, crate::sketcharg::DataType::AA);
+        let fhashaa = | kmer : &KmerAA32bit | -> u32 { kmer.get_compressed_value() };
+        let mut nb_kmers = 0;
+        hash_kmers(&vseqaa, 5, &fhashaa, None, |_, _| nb_kmers += 1);
+        assert_eq!(nb_kmers, seqaa[0].size() - 4);
+        let sig = optdens_signature::<KmerAA32bit, _, f32, _>(&vseqaa, &paramsaa, &fhashaa, None);
+        assert_eq!(sig.len(), 20);
+        assert_eq!(sig, optdens_signature::<KmerAA32bit, _, f32, _>(&vseqaa, &paramsaa, &fhashaa, None));
+    } // end of test_generic_sketch_dna_aa
+
+
+    #[test]
+    fn test_sketch_each_order() {
+        log_init_test();
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        // first items take longer, so that with several threads later items complete first
+        let items : Vec<usize> = (0..64).collect();
+        let vitems : Vec<&usize> = items.iter().collect();
+        let completed = AtomicUsize::new(0);
+        let sketch_one = |i : &usize| -> Vec<usize> {
+            std::thread::sleep(std::time::Duration::from_micros((64 - *i as u64) * 50));
+            vec![*i, completed.fetch_add(1, Ordering::Relaxed)]
+        };
+        let run = || (sketch_each(&vitems, sketch_one), try_sketch_each(&vitems, |i| Ok(sketch_one(i))).unwrap());
+        #[cfg(feature="parallel")]
+        let (sigs, try_sigs) = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap().install(run);
+        #[cfg(not(feature="parallel"))]
+        let (sigs, try_sigs) = run();
+        for sigs in [&sigs, &try_sigs] {
+            assert_eq!(sigs.iter().map(|s| s[0]).collect::<Vec<usize>>(), items);
+            // threads completed items out of input order
+            #[cfg(feature="parallel")]
+            assert!(sigs.windows(2).any(|w| w[0][1] > w[1][1]));
+        }
+    } // end of test_sketch_each_order
+
+
+    #[test]
+    fn test_sketch_bytes() {
+        log_init_test();
+        use crate::sketching::setsketchert::{SeqSketcherT, ProbHash3aSketch, SuperHashSketch};
+        use crate::aautils::setsketchert::{SeqSketcherAAT, OptDensHashSketch};
+        // raw record with lower case and a N : same kmers as ACGT runs
+        let raw : &[u8] = b"ATCATGCCCCTTTAGAAAATTTccggatcaNTCGTACGGAGCATGCGTACAACGTCGATGC";
+        let runs = crate::io::split_acgt_runs(raw, 8);
+        assert_eq!(runs.len(), 2);
+        let vruns : Vec<&Sequence> = runs.iter().collect();
+        let mut from_runs = Vec::<Kmer32bit>::new();
+        for seq in &vruns {
+            KmerSequence::<Kmer32bit>::for_each_kmer(*seq, 8, |k| from_runs.push(k));
+        }
+        let mut from_bytes = Vec::<Kmer32bit>::new();
+        DnaSlice(raw).for_each_kmer(8, |k| from_bytes.push(k));
+        assert_eq!(from_bytes, from_runs);
+        //
+        let params = SeqSketcherParams::new(8, 50, crate::sketcharg::SketchAlgo::PROB3A, crate::sketcharg::DataType::DNA);
+        let fhash = | kmer : &Kmer32bit | -> u32 { kmer.get_compressed_value() };
+        let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&params);
+        let sig = sketcher.sketch_compressedkmer_bytes(&[raw, &raw[..30]], fhash).unwrap();
+        assert_eq!(sig[0], sketcher.sketch_compressedkmer_seqs(&vruns, fhash).unwrap()[0]);
+        assert_eq!(sig[1], sketcher.sketch_compressedkmer_bytes_seqs(&[&raw[..30]], fhash).unwrap()[0]);
+        let super_sketcher = SuperHashSketch::<Kmer32bit, f64>::new(&params);
+        assert_eq!(super_sketcher.sketch_compressedkmer_bytes_seqs(&[raw], fhash).unwrap(), super_sketcher.sketch_compressedkmer_seqs(&vruns, fhash).unwrap());
+        // amino acids
+        let rawaa : &[u8] = b"MTEYKLVVVGAGGVGKSALTIQLIQNHFVDEYDPTIEDSY";
+        let seqaa = SequenceAA::new(rawaa).unwrap();
+        let paramsaa = SeqSketcherParams::new(5, 20, crate::sketcharg::SketchAlgo::OPTDENS, crate::sketcharg::DataType::AA);
+        let fhashaa = | kmer : &KmerAA32bit | -> u32 { kmer.get_compressed_value() };
+        let sketcheraa = OptDensHashSketch::<KmerAA32bit, f32>::new(&paramsaa);
+        let lower : Vec<u8> = rawaa.to_ascii_lowercase();
+        assert_eq!(sketcheraa.sketch_compressedkmeraa_bytes(&[&lower], fhashaa).unwrap(), sketcheraa.sketch_compressedkmeraa(&vec![&seqaa], fhashaa).unwrap());
+    } // end of test_sketch_bytes
+
+
+    #[test]
+    fn test_canonical_kmers() {
+        log_init_test();
+        use crate::base::Kmer64bit;
+        // canonical kmers of raw bytes (with a N), packed sequences and views are those computed from reverse complements
+        let raw : &[u8] = b"ATCATGCCCCTTTAGAAAATTTccggatcaNTCGTACGGAGCATGCGTACAACGTCGATGCGGATTTACAGGCATTAGAC";
+        let mut expected = Vec::<Kmer32bit>::new();
+        DnaSlice(raw).for_each_kmer(13, |k : Kmer32bit| expected.push(k.reverse_complement().min(k)));
+        let mut canonical = Vec::<Kmer32bit>::new();
+        DnaSlice(raw).for_each_canonical_kmer(13, |k| canonical.push(k));
+        assert_eq!(canonical, expected);
+        let seq = Sequence::new(&raw[31..], 2);
+        let mut expected = Vec::<Kmer64bit>::new();
+        KmerSequence::<Kmer64bit>::for_each_kmer(&seq, 21, |k| expected.push(k.reverse_complement().min(k)));
+        let mut canonical = Vec::<Kmer64bit>::new();
+        CanonicalKmers(&seq).for_each_kmer(21, |k| canonical.push(k));
+        assert_eq!(canonical, expected);
+        let view = seq.view(5..40).unwrap();
+        let mut canonical = Vec::<Kmer64bit>::new();
+        view.for_each_canonical_kmer(21, |k| canonical.push(k));
+        assert_eq!(canonical, expected[5..=19]);
+        // sketching canonical sequences with a forward hash is sketching with a canonical hash
+        let params = SeqSketcherParams::new(13, 50, crate::sketcharg::SketchAlgo::PROB3A, crate::sketcharg::DataType::DNA);
+        let forward = | kmer : &Kmer32bit | -> u32 { probminhash::invhash::int32_hash(kmer.get_compressed_value()) };
+        let canonical_hash = | kmer : &Kmer32bit | -> u32 { forward(&kmer.reverse_complement().min(*kmer)) };
+        let slices = [CanonicalKmers(&DnaSlice(raw))];
+        assert_eq!(probminhash3a_signature(&[&slices[0]], &params, &forward), probminhash3a_signature(&[&DnaSlice(raw)], &params, &canonical_hash));
+    } // end of test_canonical_kmers
+
+    #[test]
+    fn test_scratch_signatures() {
+        log_init_test();
+        use crate::base::scratch::clear_scratch;
+        // signatures computed with reused scratch buffers are those computed with fresh ones
+        let seqs = [SequenceAA::new(b"MTEYKLVVVGAGGVGKSALTIQLIQNHFVDEYDPTIEDSY").unwrap(),
+                    SequenceAA::new(b"MKTAYIAKQRQISFVKSHFSRQ").unwrap(),
+                    SequenceAA::new(b"GAGGVGKSALTIQLIQNHF").unwrap()];
+        let fhash = | kmer : &KmerAA32bit | -> u32 { kmer.get_compressed_value() };
+        let params = SeqSketcherParams::new(4, 30, crate::sketcharg::SketchAlgo::PROB3A, crate::sketcharg::DataType::AA);
+        // each algorithm uses its own buffer type
+        let sketch_one = |seq : &SequenceAA| (probminhash3a_signature(&[seq], &params, &fhash),
+                    superminhash_signature::<KmerAA32bit, _, f64, _>(&[seq], &params, &fhash, None).unwrap(),
+                    optdens_signature::<KmerAA32bit, _, f32, _>(&[seq], &params, &fhash, None),
+                    revoptdens_signature::<KmerAA32bit, _, f32, _>(&[seq], &params, &fhash, None));
+        let fresh : Vec<_> = seqs.iter().map(|seq| { clear_scratch(); sketch_one(seq) }).collect();
+        for _ in 0..2 {
+            let reused : Vec<_> = seqs.iter().map(sketch_one).collect();
+            assert_eq!(reused, fresh);
+        }
+    } // end of test_scratch_signatures
+
+
+    #[test]
+    fn test_kmer_weights() {
+        log_init_test();
+        use crate::base::{Kmer32bit, Kmer64bit};
+        use crate::aautils::kmeraa::KmerAA64bit;
+        // weight maps are never sized beyond the number of possible kmers
+        assert_eq!(get_nb_distinct_kmer_max::<Kmer32bit>(8), 1 << 16);
+        assert_eq!(get_nb_distinct_kmer_max::<Kmer64bit>(31), 1 << 62);
+        assert_eq!(get_nb_distinct_kmer_max::<KmerAA32bit>(4), 1 << 20);
+        assert_eq!(get_nb_distinct_kmer_max::<KmerAA64bit>(12), 1 << 60);
+        assert_eq!(get_nb_distinct_kmer_max::<Kmer64bit>(32), usize::MAX);
+        // the hasher has fixed seeds, two maps filled the same way iterate in the same order
+        let seq = SequenceAA::new(b"MTEYKLVVVGAGGVGKSALTIQLIQNHFVDEYDPTIEDSYMTEYKLVVV").unwrap();
+        let fhash = | kmer : &KmerAA32bit | -> u32 { kmer.get_compressed_value() };
+        let mut w1 = KmerWeights::<u32>::default();
+        let mut w2 = KmerWeights::<u32>::default();
+        accumulate_kmer_weights(&[&seq], 4, &fhash, &mut w1);
+        accumulate_kmer_weights(&[&seq], 4, &fhash, &mut w2);
+        assert_eq!(w1.iter().collect::<Vec<_>>(), w2.iter().collect::<Vec<_>>());
+        assert_eq!(w1.values().sum::<u64>(), 46);
+        assert_eq!(w1.values().filter(|c| **c == 2).count(), 6);
+    } // end of test_kmer_weights
+
+
+    #[test]
+    fn test_hash_kmers_batched() {
+        log_init_test();
+        use crate::base::Kmer32bit;
+        use crate::sketching::kmerfilter::build_solid_filter;
+        // a sequence with more kmers than a batch, with low complexity regions
+        let bases : Vec<u8> = (0..3 * KMER_HASH_BATCH_SIZE + 17).map(|i| if (i / 300) % 2 == 0 { b"ACGGTCATTGCA"[(i * i + 7 * i) % 12] } else { b"AC"[i % 2] }).collect();
+        let seq = Sequence::new(&bases, 2);
+        let fhash = | kmer : &Kmer32bit | -> u32 { probminhash::invhash::int32_hash(kmer.get_compressed_value()) };
+        let filter = build_solid_filter::<Kmer32bit, _, _>(&[&seq], 9, &fhash);
+        for solid_filter in [None, Some(&filter)] {
+            let mut expected = Vec::<(u32, u32)>::new();
+            hash_kmers(&[&seq, &seq], 9, &fhash, solid_filter, |kmer : &Kmer32bit, hashval| expected.push((kmer.get_compressed_value(), hashval)));
+            let mut batched = Vec::<(u32, u32)>::new();
+            hash_kmers_batched(&[&seq, &seq], 9, &fhash, solid_filter, |kmers : &[Kmer32bit], hashvals| {
+                assert!(kmers.len() <= KMER_HASH_BATCH_SIZE);
+                batched.extend(kmers.iter().map(|k| k.get_compressed_value()).zip(hashvals.iter().copied()));
+            });
+            assert_eq!(batched, expected);
+        }
+        // skipping repeated values does not change SuperMinHash signature
+        let params = SeqSketcherParams::new(9, 200, crate::sketcharg::SketchAlgo::SUPER, crate::sketcharg::DataType::DNA);
+        let mut sminhash = SuperMinHash::<f64, u32, NoHashHasher>::new(200, BuildHasherDefault::<NoHashHasher>::default());
+        hash_kmers(&[&seq], 9, &fhash, None, |_ : &Kmer32bit, hashval| sminhash.sketch(&hashval).unwrap());
+        assert_eq!(&superminhash_signature::<Kmer32bit, _, f64, _>(&[&seq], &params, &fhash, None).unwrap(), sminhash.get_hsketch());
+    } // end of test_hash_kmers_batched
+
+
+} // end of mod tests
@@ -20,34 +20,30 @@
 use log::*;
 
 use std::io;
-use std::io::{Write,Read,ErrorKind, BufReader, BufWriter };
+use std::io::{Write,Read,ErrorKind};
 
 use std::fs;
 use std::fs::OpenOptions;
 use std::fmt::Debug;
-use std::path::{Path, PathBuf};
 
-use std::hash::{BuildHasherDefault, Hasher, Hash};
-
-use serde::{Deserialize, Serialize};
-use serde_json::to_writer;
+use std::hash::{Hasher, Hash};
 
 use indexmap::IndexMap;
 use fnv::{FnvHashMap, FnvBuildHasher};
 
 use num;
 
-use rand_distr::uniform::SampleUniform;
-
 use crate::nohasher::*;
+use super::legacysketch::{LegacySeqSketcher, Dna};
 
 use crate::base::{kmer::*, kmergenerator::*, kmergenerator::KmerSeqIteratorT};
 use super::nbkmerguess::*;
+use super::seqsketchgen::sketch_each;
 
-use rayon::prelude::*;
+use crate::parallel::*;
 
 
-use probminhash::{probminhasher::*, superminhasher::SuperMinHash};
+use probminhash::probminhasher::*;
 
 
 use probminhash::jaccard::compute_probminhash_jaccard;
@@ -106,134 +102,14 @@ pub fn probminhash_get_jaccard_objects<D:Eq+Copy>(siga : &Vec<D>, sigb : &Vec<D>
 //=======================================================================================================
 
 
-/// This structure (prefer ProbHash3aSketch and SuperHashSketch based upon the trait SeqSketcherT) describes 
-/// the kmer size used in computing sketches and the number of sketch we want.  
-/// 
+/// The deprecated DNA sketcher (prefer ProbHash3aSketch and SuperHashSketch based upon the trait SeqSketcherT), shared with the AA case.
+/// See [legacysketch](super::legacysketch).
+///
 /// It gathers methods for sketch_superminhash, sketch_probminhash3a and sketch_probminhash3
-#[derive(Serialize,Deserialize,Copy,Clone)]
-pub struct SeqSketcher {
-    kmer_size : usize,
-    sketch_size : usize
-}  // end of SeqSketcher
+pub type SeqSketcher = LegacySeqSketcher<Dna>;
 
 
 impl SeqSketcher {
-    /// 
-    pub fn new(kmer_size: usize, sketch_size : usize) -> Self {
-        SeqSketcher{kmer_size, sketch_size}
-    }
-
-    /// returns kmer size
-    pub fn get_kmer_size(&self) -> usize {
-        self.kmer_size
-    }
-
-    /// return sketch size
-    pub fn get_sketch_size(&self) -> usize {
-        self.sketch_size
-    }  
-    
-    /// serialized dump
-    pub fn dump_json(&self, filename : &String) -> Result<(), String> {
-        //
-        let filepath = PathBuf::from(filename.clone());
-        //
-        log::info!("dumping sketching parameters in json file : {}", filename);
-        //
-        let fileres = OpenOptions::new().write(true).create(true).truncate(true).open(&filepath);
-        if fileres.is_err() {
-            log::error!("SeqSketcher dump : dump could not open file {:?}", filepath.as_os_str());
-            println!("SeqSketcher dump: could not open file {:?}", filepath.as_os_str());
-            return Err("SeqSketcher dump failed".to_string());
-        }
-        // 
-        let mut writer = BufWriter::new(fileres.unwrap());
-        let _ = to_writer(&mut writer, &self).unwrap();
-        //
-        Ok(())
-    } // end of dump
-
-
-    /// reload from a json dump
-    pub fn reload_json(dirpath : &Path) -> Result<SeqSketcher, String> {
-        log::info!("in reload_json");
-        //
-        let filepath = dirpath.join("sketchparams_dump.json");
-        let fileres = OpenOptions::new().read(true).open(&filepath);
-        if fileres.is_err() {
-            log::error!("Sketcher reload_json : reload could not open file {:?}", filepath.as_os_str());
-            println!("Sketcher reload_json: could not open file {:?}", filepath.as_os_str());
-            return Err("Sketcher reload_json could not open file".to_string());            
-        }
-        //
-        let loadfile = fileres.unwrap();
-        let reader = BufReader::new(loadfile);
-        let sketch_params:SeqSketcher = serde_json::from_reader(reader).unwrap();
-        //
-        log::info!("SeqSketcher reload, kmer_size : {}, sketch_size : {}", 
-            sketch_params.get_kmer_size(), sketch_params.get_sketch_size());     
-        //
-        Ok(sketch_params)
-    } // end of reload_json
-
-
-
-
-
-    /// This function computes and return signatures of a vector of sequences by generating kmers of size kmer_size. 
-    /// The sketch is done with probminhash3a algorithm. See the crate [probminhash](https://crates.io/crates/probminhash)  
-    /// It is a generic implementation of probminhash3a  against our standard compressed Kmer types.  
-    /// Kmer::Val is the base type u32, u64 on which compressed kmer representations relies.  
-    ///    
-    /// fhash is any hash function, but usually it is identity, invhash on kmer or on min of kmer and reverse complement.
-    /// These are the hash functions that make possible to get back to the original kmers (or at least partially in the case using the min)..
-    /// 
-    /// The argument type of the hashing function F specify the type of Kmer to generate along the sequence.  
-    pub fn sketch_probminhash3a<'b, Kmer : CompressedKmerT + KmerBuilder<Kmer>, F>(&self, vseq : &'b Vec<&Sequence>, fhash : F) -> Vec<Vec<Kmer::Val> >
-        where F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
-              Kmer::Val : num::PrimInt + Send + Sync + Debug,
-              KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer> {
-        //
-        log::debug!("entering sketch_probminhash3a_compressedkmer");
-        //
-        let comput_closure = | seqb : &Sequence, i:usize | -> (usize,Vec<Kmer::Val>) {
-            // if we get very large sequence (many Gb length) we must be cautious on size of hashmap; i.e about number of different kmers!!! 
-            let nb_kmer = get_nbkmer_guess(seqb);
-            let mut wb : FnvHashMap::<Kmer::Val,u64> = FnvHashMap::with_capacity_and_hasher(nb_kmer, FnvBuildHasher::default());
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.kmer_size as u8, &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        let hashval = fhash(&kmer);
-                        *wb.entry(hashval).or_insert(0) += 1;
-                    },
-                    None => break,
-                }
-            }  // end loop 
-            let mut pminhashb = ProbMinHash3a::<Kmer::Val,NoHashHasher>::new(self.sketch_size, 
-                <Kmer::Val>::default());
-            pminhashb.hash_weigthed_hashmap(&wb);
-            let sigb = pminhashb.get_signature();
-            // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
-        };
-        //
-        let sig_with_rank : Vec::<(usize,Vec<Kmer::Val>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<Kmer::Val>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
-        jaccard_vec
-    }  // end of sketch_probminhash3a_compressedkmer
-
-
 
     //   Probminhash3
     //  ==============
@@ -251,12 +127,12 @@ impl SeqSketcher {
                 Kmer::Val : num::PrimInt + Send + Sync + Debug,
                 KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer>  {
         //
-        let comput_closure = | seqb : &Sequence, i:usize | -> (usize,Vec<Kmer::Val>) {
+        let comput_closure = | seqb : &Sequence | -> Vec<Kmer::Val> {
             // if we get very large sequence (many Gb length) we must be cautious on size of hashmap; i.e about number of different kmers!!! 
             let nb_kmer = get_nbkmer_guess(seqb);
             let mut wb : FnvHashMap::<Kmer::Val,u64> = FnvHashMap::with_capacity_and_hasher(nb_kmer, FnvBuildHasher::default());
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.kmer_size as u8, &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
+            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size() as u8, &seqb);
+            kmergen.set_range(0..seqb.size()).unwrap();
             loop {
                 match kmergen.next() {
                     Some(kmer) => {
@@ -266,87 +142,19 @@ impl SeqSketcher {
                     None => break,
                 }
             }  // end loop 
-            let mut pminhashb = ProbMinHash3::<Kmer::Val,NoHashHasher>::new(self.sketch_size, 
+            let mut pminhashb = ProbMinHash3::<Kmer::Val,NoHashHasher>::new(self.get_sketch_size(), 
                                     <Kmer::Val>::default());
             pminhashb.hash_weigthed_hashmap(&wb);
             let sigb = pminhashb.get_signature();
             // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
+            return sigb.clone();
         };
         //
-        let sig_with_rank : Vec::<(usize,Vec<Kmer::Val>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<Kmer::Val>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
-        jaccard_vec
+        // signatures are collected in the order of vseq
+        sketch_each(vseq, comput_closure)
     }  // end of sketchprobminhash3_kmer32bit
 
 
-
-    //  Superminhash
-
-    /// a generic implementation of superminhash  against our standard compressed Kmer types.  
-    /// Kmer::Val is the base type u32, u64 on which compressed kmer representations relies.  
-    /// S is for f32 of f64 depending on the signature we want from SuperMinHash.  
-    /// F is a hash function returning morally a u32, usize or u64.  
-    /// The argument type of the hashing function F specify the type of Kmer to generate along the sequence.  
-    pub fn sketch_superminhash<'b, Kmer : CompressedKmerT + KmerBuilder<Kmer>, S, F>(&self, vseq : &'b Vec<&Sequence>, fhash : F) -> Vec<Vec<S> >
-        where F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
-              Kmer::Val : num::PrimInt + Send + Sync + Debug,
-              KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer>,
-              S : num::Float + SampleUniform + Debug + Send + Sync {
-        //
-        log::debug!("entering sketch_superminhash_compressedkmer");
-        //
-        let comput_closure = | seqb : &Sequence, i:usize | -> (usize,Vec<S>) {
-            //
-            log::debug!(" in sketch_superminhash_compressedkmer, closure");
-            //
-            let bh = BuildHasherDefault::<fnv::FnvHasher>::default();
-            // generic arg is here type sent to sketching
-            let mut sminhash : SuperMinHash<S, Kmer::Val, fnv::FnvHasher>= SuperMinHash::<S, Kmer::Val, fnv::FnvHasher>::new(self.sketch_size, bh);
-
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.kmer_size as u8, &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        let hashval = fhash(&kmer);
-                        if sminhash.sketch(&hashval).is_err() {
-                            log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                            std::panic!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                        }
-                    },
-                    None => break,
-                }
-            }  // end loop 
-            let sigb = sminhash.get_hsketch();
-            // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
-        };
-        //
-        let sig_with_rank : Vec::<(usize,Vec<S>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<S>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
-        jaccard_vec
-    } // end of sketch_superminhash_compressedkmer
-
-
     /// initialize dump file. Nota we intialize with size of key signature : 4 bytes.  
     /// 
     /// Format of file is :
@@ -365,8 +173,8 @@ impl SeqSketcher {
             std::process::exit(1);
         }
         let sig_size : u32 = 4;
-        let sketch_size_u32 = self.sketch_size as u32;
-        let kmer_size_u32 = self.kmer_size as u32;
+        let sketch_size_u32 = self.get_sketch_size() as u32;
+        let kmer_size_u32 = self.get_kmer_size() as u32;
         let mut sigbuf : io::BufWriter<fs::File> = io::BufWriter::with_capacity(1_000_000_000, dumpfile);
         sigbuf.write(& MAGIC_SIG_DUMP.to_le_bytes()).unwrap();
         sigbuf.write(& sig_size.to_le_bytes()).unwrap();
@@ -399,11 +207,6 @@ pub fn jaccard_index_probminhash3a< Kmer : CompressedKmerT + KmerBuilder<Kmer>,
               KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer>{
     //
     debug!("seqsketcher : entering compute_jaccard_index_probminhash3a");
-    // a vector to return results
-    let mut jaccard_vec = Vec::<f64>::with_capacity(vseqb.len());
-    for _ in 0..vseqb.len() {
-        jaccard_vec.push(0.);
-    }
     // default is invertible hash and then superminhash without any hashing
     let mut pminhasha = ProbMinHash3a::<<Kmer as CompressedKmerT>::Val,NoHashHasher>::new(sketch_size, Kmer::Val::default());
     // if we get very large sequence (many Gb length) we must be cautious on size of hashmap; i.e about number of different kmers!!! 
@@ -413,7 +216,7 @@ pub fn jaccard_index_probminhash3a< Kmer : CompressedKmerT + KmerBuilder<Kmer>,
     // generate all kmers include in range arg. dependance upon kmer_size 
     // seqa
     let mut kmergen = KmerSeqIterator::<Kmer>::new(kmer_size, &seqa);
-    kmergen.set_range(0, seqa.size()).unwrap();
+    kmergen.set_range(0..seqa.size()).unwrap();
     loop {
         match kmergen.next() {
             Some(kmer) => {
@@ -428,12 +231,12 @@ pub fn jaccard_index_probminhash3a< Kmer : CompressedKmerT + KmerBuilder<Kmer>,
     let siga = pminhasha.get_signature();
     trace!("siga = {:?}", siga);
     // loop on vseqb to // with rayon
-    let comput_closure = | seqb : &Sequence, i:usize | -> (usize,f64) {
+    let comput_closure = | seqb : &Sequence | -> f64 {
         // if we get very large sequence (many Gb length) we must be cautious on size of hashmap; i.e about number of different kmers!!! 
         let nb_kmer = get_nbkmer_guess(seqb);
         let mut wb : FnvHashMap::<Kmer::Val, u64> = FnvHashMap::with_capacity_and_hasher(nb_kmer, FnvBuildHasher::default());
         let mut kmergen = KmerSeqIterator::<Kmer>::new(kmer_size, &seqb);
-        kmergen.set_range(0, seqb.size()).unwrap();
+        kmergen.set_range(0..seqb.size()).unwrap();
         loop {
             match kmergen.next() {
             Some(kmer) => {
@@ -446,17 +249,11 @@ pub fn jaccard_index_probminhash3a< Kmer : CompressedKmerT + KmerBuilder<Kmer>,
         let mut pminhashb = ProbMinHash3a::<Kmer::Val,NoHashHasher>::new(sketch_size, Kmer::Val::default());
         pminhashb.hash_weigthed_hashmap(&wb);
         let sigb = pminhashb.get_signature();
-        let jac = compute_probminhash_jaccard(siga, sigb);        
-        return (i,jac);
+        compute_probminhash_jaccard(siga, sigb)
     };
     //
-    let jac_with_rank : Vec::<(usize,f64)> = (0..vseqb.len()).into_par_iter().map(|i| comput_closure(&vseqb[i],i)).collect();
-    // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-    for i in 0..jac_with_rank.len() {
-        let slot = jac_with_rank[i].0;
-        jaccard_vec[slot] = jac_with_rank[i].1;
-    }
-    return jaccard_vec;
+    // results are collected in the order of vseqb
+    vseqb.par_iter().map(comput_closure).collect()
 } // end of sketch_seqrange_probminhash3a
 
 
@@ -470,11 +267,6 @@ pub fn jaccard_index_probminhash3_kmer32bit<F>(seqa: &Sequence, vseqb : &Vec<Seq
                     where F : Fn(&Kmer32bit) -> u32 + Send + Sync {
     //
     debug!("seqsketcher : entering compute_jaccard_index_probminhash3a_kmer32bit");
-    // a vector to return results
-    let mut jaccard_vec = Vec::<f64>::with_capacity(vseqb.len());
-    for _ in 0..vseqb.len() {
-        jaccard_vec.push(0.);
-    }
     // default is invertible hash and then superminhash without any hashing
     let mut pminhasha = ProbMinHash3::<usize,NoHashHasher>::new(sketch_size, 0);
     // if we get very large sequence (many Gb length) we must be cautious on size of hashmap; i.e about number of different kmers!!! 
@@ -484,7 +276,7 @@ pub fn jaccard_index_probminhash3_kmer32bit<F>(seqa: &Sequence, vseqb : &Vec<Seq
     // generate all kmers include in range arg. dependance upon kmer_size 
     // seqa
     let mut kmergen = KmerSeqIterator::<Kmer32bit>::new(kmer_size, &seqa);
-    kmergen.set_range(0, seqa.size()).unwrap();
+    kmergen.set_range(0..seqa.size()).unwrap();
     loop {
         match kmergen.next() {
             Some(kmer) => {
@@ -499,12 +291,12 @@ pub fn jaccard_index_probminhash3_kmer32bit<F>(seqa: &Sequence, vseqb : &Vec<Seq
     let siga = pminhasha.get_signature();
     trace!("siga = {:?}", siga);
     // loop on vseqb to // with rayon
-    let comput_closure = | seqb : &Sequence, i:usize | -> (usize,f64) {
+    let comput_closure = | seqb : &Sequence | -> f64 {
         // if we get very large sequence (many Gb length) we must be cautious on size of hashmap; i.e about number of different kmers!!! 
         let nb_kmer = get_nbkmer_guess(seqb);
         let mut wb : FnvHashMap::<usize,f64> = FnvHashMap::with_capacity_and_hasher(nb_kmer, FnvBuildHasher::default());
         let mut kmergen = KmerSeqIterator::<Kmer32bit>::new(kmer_size, &seqb);
-        kmergen.set_range(0, seqb.size()).unwrap();
+        kmergen.set_range(0..seqb.size()).unwrap();
         loop {
             match kmergen.next() {
                 Some(kmer) => {
@@ -517,16 +309,10 @@ pub fn jaccard_index_probminhash3_kmer32bit<F>(seqa: &Sequence, vseqb : &Vec<Seq
         let mut pminhashb = ProbMinHash3::<usize,NoHashHasher>::new(sketch_size, 0);
         pminhashb.hash_weigthed_hashmap(&wb);
         let sigb = pminhashb.get_signature();
-        let jac = compute_probminhash_jaccard(siga, sigb);        
-        return (i,jac);
+        compute_probminhash_jaccard(siga, sigb)
     };
-    let jac_with_rank : Vec::<(usize,f64)> = (0..vseqb.len()).into_par_iter().map(|i| comput_closure(&vseqb[i],i)).collect();
-    // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-    for i in 0..jac_with_rank.len() {
-        let slot = jac_with_rank[i].0;
-        jaccard_vec[slot] = jac_with_rank[i].1;
-    }
-    return jaccard_vec;
+    // results are collected in the order of vseqb
+    vseqb.par_iter().map(comput_closure).collect()
 } // end of sketch_seqrange_probminhash3_kmer32bit
 
 
@@ -563,7 +349,7 @@ pub struct SigSketchFileReader {
     /// size of kmers used in sketching.
     kmer_size : u8,
     /// read buffer 
-    signature_buf:io::BufReader<fs::File>
+    signature_buf:io::BufReader<Box<dyn Read + Send>>
 }
 
 
@@ -571,7 +357,7 @@ pub struct SigSketchFileReader {
 impl SigSketchFileReader {
     /// initialize the fields fname, sketch_size, kmer_size and allocates signature_buf but signatures will be read by next.
     pub fn new(fname:&String) -> Result<SigSketchFileReader, String> {
-        let dumpfile_res = OpenOptions::new().read(true).open(&fname);
+        let dumpfile_res = crate::io::open_decompressed(std::path::Path::new(&fname));
         let dumpfile;
         if dumpfile_res.is_ok() {
             dumpfile = dumpfile_res.unwrap();
@@ -579,7 +365,7 @@ impl SigSketchFileReader {
             println!("cannot open {}", fname);
             return Err(String::from("SigSketchFileReader : could not open dumpfile"))
         }
-        let mut signature_buf : io::BufReader<fs::File> = io::BufReader::with_capacity(1_000_000_000, dumpfile);
+        let mut signature_buf : io::BufReader<Box<dyn Read + Send>> = io::BufReader::with_capacity(1_000_000_000, dumpfile);
         let mut buf_u32 = [0u8;4];
         let mut io_res;
         // check magic
@@ -751,12 +537,12 @@ mod tests {
             let mut pminhasha = ProbMinHash3a::<u32,NoHashHasher>::new(sketch_size, 0);
             // generate all kmers include in range arg. dependance upon kmer_size in seqa 
             let mut kmergen = KmerSeqIterator::<Kmer32bit>::new(kmer_size, &seqa);
-            kmergen.set_range(0, seqa.size()).unwrap();
+            kmergen.set_range(0..seqa.size()).unwrap();
             loop {
                 match kmergen.next() {
                     Some(kmer) => {
                         let hashval = kmer_identity(&kmer);
-                        debug!(" kmer in seqa {:?}, hvalval  {:?} ", String::from_utf8(kmer.get_uncompressed_kmer()).unwrap(), hashval);
+                        debug!(" kmer in seqa {}, hvalval  {:?} ", kmer, hashval);
                         *wa.entry(hashval).or_insert(0.) += 1.;
                     },
                     None => break,
@@ -768,7 +554,7 @@ mod tests {
             let mut pminhashb = ProbMinHash3a::<u32,NoHashHasher>::new(sketch_size, 0);
             // generate all kmers include in range arg. dependance upon kmer_size 
             let mut kmergen = KmerSeqIterator::<Kmer32bit>::new(kmer_size, &seqarevcomp);
-            kmergen.set_range(0, seqarevcomp.size()).unwrap();
+            kmergen.set_range(0..seqarevcomp.size()).unwrap();
             loop {
                 match kmergen.next() {
                     Some(kmer) => {
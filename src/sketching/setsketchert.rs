@@ -13,11 +13,12 @@
 */
 
 use std::marker::PhantomData;
+use std::borrow::Borrow;
 
 use std::fmt::Debug;
 
 
-use std::hash::{BuildHasherDefault, Hasher};
+use std::hash::{BuildHasherDefault, Hasher, Hash};
 
 use serde::{Deserialize, Serialize};
 
@@ -30,16 +31,19 @@ use rand_distr::uniform::SampleUniform;
 
 use crate::nohasher::*;
 
-use crate::base::{kmer::*, kmergenerator::*, kmergenerator::KmerSeqIteratorT};
+use crate::base::{kmer::*, kmergenerator::*, kmergenerator::KmerSeqIteratorT, kmertable::KmerCountTable};
 
-use super::nbkmerguess::*;
+use super::kmerfilter::*;
+use super::seqsketchgen::*;
+use super::sketchstats::*;
+use crate::io::split_acgt_runs;
 
 
-use rayon::prelude::*;
+use crate::parallel::*;
 
 use crate::sketcharg::{SeqSketcherParams, SketchAlgo};
 
-use probminhash::{probminhasher::*, superminhasher::SuperMinHash, densminhash::*, setsketcher::SetSketcher, setsketcher::SetSketchParams};
+use probminhash::{probminhasher::*, setsketcher::SetSketcher, setsketcher::SetSketchParams};
 
 
 #[cfg(feature="sminhash2")]
@@ -62,24 +66,275 @@ pub trait SeqSketcherT<Kmer>
     //
     fn get_algo(&self) -> SketchAlgo;
     /// This function receive a vector of (possibly concatenated) sequences and returns for each sequence a sketch.  
-    /// The function returns a vector of Sketches (one for each sequence).
+    /// The function returns a vector of Sketches (one for each sequence, in the order of vseq whatever the order in which threads
+    /// complete them), or an error if the sketcher failed on a sequence.
     /// F is a hashing function (possibly just extracting Kmer::Val) to apply to kmer before sending to sketcher.
-    fn sketch_compressedkmer<F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<Self::Sig> > 
+    fn sketch_compressedkmer<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String> 
                     where F : Fn(&Kmer) -> Kmer::Val + Send + Sync;
     /// This function implements the sketching a file of Sequences, 
     /// (The sequence are not concatenated, so we have many sequences) and make one sketch Vector for the sequence collection (for the file).  
     /// **It returns the same signature as sketch_compressedkmer for interface homogeneity (same msg system for //)
     /// but the returned intern vec has size 1!**
-    fn sketch_compressedkmer_seqs<F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<Self::Sig> > 
-                    where F : Fn(&Kmer) -> Kmer::Val + Send + Sync;                
+    fn sketch_compressedkmer_seqs<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String> 
+                    where F : Fn(&Kmer) -> Kmer::Val + Send + Sync;
+
+    /// same as [Self::sketch_compressedkmer] for any iterable of sequences, references (slice, Vec\<&Sequence\>...) or owned sequences (a stream of records).  
+    /// Sequences are sketched by blocks of [SKETCH_ITER_BLOCK_SIZE], so owned sequences are released once sketched.
+    fn sketch_compressedkmer_iter<I, S, F>(&self, seqs : I, fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+                    where   I : IntoIterator<Item = S>,
+                            S : Borrow<Sequence>,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let mut sigs = Vec::<Vec<Self::Sig>>::new();
+        let mut seqs = seqs.into_iter();
+        loop {
+            let block : Vec<S> = seqs.by_ref().take(SKETCH_ITER_BLOCK_SIZE).collect();
+            if block.is_empty() {
+                break;
+            }
+            let vseq : Vec<&Sequence> = block.iter().map(|s| s.borrow()).collect();
+            sigs.append(&mut self.sketch_compressedkmer(&vseq, &fhash)?);
+        }
+        Ok(sigs)
+    } // end of sketch_compressedkmer_iter
+
+    /// same as [Self::sketch_compressedkmer_seqs] for any iterable of sequences, references or owned sequences.
+    fn sketch_compressedkmer_seqs_iter<I, S, F>(&self, seqs : I, fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+                    where   I : IntoIterator<Item = S>,
+                            S : Borrow<Sequence>,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let seqs : Vec<S> = seqs.into_iter().collect();
+        let vseq : Vec<&Sequence> = seqs.iter().map(|s| s.borrow()).collect();
+        self.sketch_compressedkmer_seqs(&vseq, fhash)
+    } // end of sketch_compressedkmer_seqs_iter
+
+    /// sketches each sequence of any [KmerSequence] type (raw bytes in a [DnaSlice], views ...) in its own signature.  
+    /// Sketchers built on [seqsketchgen](crate::sketching::seqsketchgen) implement it, so that the *_bytes and *_views methods
+    /// sketch without copy. The default returns None, these methods then copy sequences in [Sequence]s.
+    fn sketch_kmerseq_each<Seq, F>(&self, _vseq : &[&Seq], _fhash : &F) -> Option<Result<Vec<Vec<Self::Sig>>, String>>
+                    where   Seq : KmerSequence<Kmer> + ?Sized,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        None
+    }
+
+    /// same as [Self::sketch_kmerseq_each], all sequences being sketched in one signature.
+    fn sketch_kmerseq_seqs<Seq, F>(&self, _vseq : &[&Seq], _fhash : &F) -> Option<Result<Vec<Self::Sig>, String>>
+                    where   Seq : KmerSequence<Kmer> + ?Sized,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        None
+    }
+
+    /// same as [Self::sketch_kmerseq_each], returning also the statistics of the kmers sketched for each sequence,
+    /// collected in the sketching pass after filtering (see [SketchStats]). The default returns None, the *_stats methods
+    /// then compute statistics in an additional pass on kmers.
+    fn sketch_kmerseq_stats<Seq, F>(&self, _vseq : &[&Seq], _fhash : &F) -> Option<Result<SigsWithStats<Self::Sig>, String>>
+                    where   Seq : KmerSequence<Kmer> + ?Sized,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        None
+    }
+
+    /// sketches each raw DNA sequence (for example a fasta record, lower case accepted) in its own signature.
+    /// Kmers containing a byte outside ACGT are skipped.  
+    /// Bytes are sketched without copy by [Self::sketch_kmerseq_each] if the sketcher implements it, otherwise valid runs are copied in [Sequence]s.
+    fn sketch_compressedkmer_bytes<F>(&self, vseq : &[&[u8]], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+                    where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let slices : Vec<DnaSlice> = vseq.iter().map(|raw| DnaSlice(raw)).collect();
+        if let Some(sigs) = self.sketch_kmerseq_each(&slices.iter().collect::<Vec<&DnaSlice>>(), &fhash) {
+            return sigs;
+        }
+        vseq.iter().map(|raw| self.sketch_compressedkmer_bytes_seqs(&[*raw], &fhash).map(|mut v| v.pop().unwrap())).collect()
+    }
+
+    /// sketches raw DNA sequences in one signature, see [Self::sketch_compressedkmer_bytes]. The returned vec has size 1.
+    fn sketch_compressedkmer_bytes_seqs<F>(&self, vseq : &[&[u8]], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+                    where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let slices : Vec<DnaSlice> = vseq.iter().map(|raw| DnaSlice(raw)).collect();
+        if let Some(sig) = self.sketch_kmerseq_seqs(&slices.iter().collect::<Vec<&DnaSlice>>(), &fhash) {
+            return sig.map(|sig| vec![sig]);
+        }
+        let runs : Vec<Sequence> = vseq.iter().flat_map(|raw| split_acgt_runs(raw, self.get_kmer_size())).collect();
+        let refs : Vec<&Sequence> = runs.iter().collect();
+        self.sketch_compressedkmer_seqs(&refs, fhash)
+    }
+
+    /// sketches each view of a sequence (see [Sequence::view]) in its own signature, as [Self::sketch_compressedkmer] on the viewed bases.  
+    /// Views are sketched without copy by [Self::sketch_kmerseq_each] if the sketcher implements it, otherwise copied in [Sequence]s.
+    fn sketch_compressedkmer_views<F>(&self, vseq : &[SequenceView], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+                    where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        if let Some(sigs) = self.sketch_kmerseq_each(&vseq.iter().collect::<Vec<&SequenceView>>(), &fhash) {
+            return sigs;
+        }
+        let seqs : Vec<Sequence> = vseq.iter().map(|v| v.to_sequence()).collect();
+        self.sketch_compressedkmer(&seqs.iter().collect::<Vec<&Sequence>>(), fhash)
+    }
+
+    /// sketches one huge sequence (a chromosome) with all threads : the sequence is split in chunks of chunk_size kmers
+    /// overlapping by kmer_size - 1 bases (see [get_chunk_ranges]), chunks are sketched in parallel and their sketch states merged.
+    /// The signature is the one of [Self::sketch_compressedkmer] on the sequence, the returned vec has size 1.  
+    /// The default implementation, used by sketchers whose states cannot be merged (OptDens, RevOptDens, SuperHash2),
+    /// sketches the sequence on one thread.
+    fn sketch_compressedkmer_chunked<F>(&self, seq : &Sequence, chunk_size : usize, fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+                    where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        get_chunk_ranges(seq.size(), self.get_kmer_size(), chunk_size)?;
+        log::info!("sketch_compressedkmer_chunked : {:?} states cannot be merged, sketching on one thread", self.get_algo());
+        self.sketch_compressedkmer(&vec![seq], fhash)
+    }
+
+    /// same as [Self::sketch_compressedkmer], returning also the kmer statistics of each sequence (see [SketchStats]).
+    /// Statistics are collected in the sketching pass by [Self::sketch_kmerseq_stats] if the sketcher implements it,
+    /// otherwise they need an additional pass on kmers.
+    fn sketch_compressedkmer_stats<F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Result<SigsWithStats<Self::Sig>, String>
+                    where   Kmer : KmerBuilder<Kmer>,
+                            Kmer::Val : Hash + Debug,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        if let Some(sigs) = self.sketch_kmerseq_stats(vseq, &fhash) {
+            return sigs;
+        }
+        let sigs = self.sketch_compressedkmer(vseq, &fhash)?;
+        Ok((sigs, kmer_stats_each(vseq, self.get_kmer_size(), &fhash)))
+    }
+
+    /// same as [Self::sketch_compressedkmer_bytes], returning also the kmer statistics of each sequence,
+    /// including the number of kmers skipped as containing a byte outside ACGT.
+    fn sketch_compressedkmer_bytes_stats<F>(&self, vseq : &[&[u8]], fhash : F) -> Result<SigsWithStats<Self::Sig>, String>
+                    where   Kmer : KmerBuilder<Kmer>,
+                            Kmer::Val : Hash + Debug,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let slices : Vec<DnaSlice> = vseq.iter().map(|raw| DnaSlice(raw)).collect();
+        if let Some(sigs) = self.sketch_kmerseq_stats(&slices.iter().collect::<Vec<&DnaSlice>>(), &fhash) {
+            return sigs;
+        }
+        let sigs = self.sketch_compressedkmer_bytes(vseq, &fhash)?;
+        Ok((sigs, kmer_stats_each(&slices.iter().collect::<Vec<&DnaSlice>>(), self.get_kmer_size(), &fhash)))
+    }
 } // end of SeqSketcherT<Kmer>
 
 
+/// Sketches DNA kmers in canonical form (min of kmer and its reverse complement) with any sketcher.  
+/// The hash function given to the sketching methods is applied to canonical kmers, so it must not compute reverse complements itself
+/// (see [get_dna_kmer_forward_hash](crate::sketching::filesketch::get_dna_kmer_forward_hash)).
+/// Sketchers implementing [SeqSketcherT::sketch_kmerseq_each] generate canonical kmers with reverse complements updated
+/// as bases are pushed (see [CanonicalKmers]), so canonical sketching costs the same as forward sketching.
+/// Other sketchers (HyperLogLog, SuperHash2) compute the reverse complement of each kmer.
+#[derive(Serialize,Deserialize,Copy,Clone,Debug)]
+pub struct CanonicalSketcher<Sketcher> {
+    sketcher : Sketcher,
+}
+
+
+impl <Sketcher> CanonicalSketcher<Sketcher> {
+
+    pub fn new(sketcher : Sketcher) -> Self {
+        CanonicalSketcher{sketcher}
+    }
+
+    /// returns the wrapped sketcher
+    pub fn get_sketcher(&self) -> &Sketcher {
+        &self.sketcher
+    }
+} // end of impl CanonicalSketcher
+
+
+// hash of the canonical form of a kmer, for sketchers without canonical kmer generation
+fn canonical_hash<'a, Kmer, F>(fhash : &'a F) -> impl Fn(&Kmer) -> Kmer::Val + Send + Sync + 'a
+        where   Kmer : CompressedKmerT,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+    move | kmer : &Kmer | fhash(&kmer.reverse_complement().min(*kmer))
+}
+
+
+impl <Kmer, Sketcher> SeqSketcherT<Kmer> for CanonicalSketcher<Sketcher>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                Sketcher : SeqSketcherT<Kmer> {
+    type Sig = Sketcher::Sig;
+
+    fn get_kmer_size(&self) -> usize {
+        self.sketcher.get_kmer_size()
+    }
+
+    fn get_sketch_size(&self) -> usize {
+        self.sketcher.get_sketch_size()
+    }
+
+    fn get_algo(&self) -> SketchAlgo {
+        self.sketcher.get_algo()
+    }
+
+    fn sketch_compressedkmer<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+                    where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        if let Some(sigs) = self.sketch_kmerseq_each(vseq, &fhash) {
+            return sigs;
+        }
+        self.sketcher.sketch_compressedkmer(vseq, canonical_hash(&fhash))
+    }
+
+    fn sketch_compressedkmer_seqs<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+                    where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        if let Some(sig) = self.sketch_kmerseq_seqs(vseq, &fhash) {
+            return sig.map(|sig| vec![sig]);
+        }
+        self.sketcher.sketch_compressedkmer_seqs(vseq, canonical_hash(&fhash))
+    }
+
+    fn sketch_kmerseq_each<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Vec<Self::Sig>>, String>>
+                    where   Seq : KmerSequence<Kmer> + ?Sized,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let canonical : Vec<CanonicalKmers<Seq>> = vseq.iter().map(|seq| CanonicalKmers(*seq)).collect();
+        self.sketcher.sketch_kmerseq_each(&canonical.iter().collect::<Vec<&CanonicalKmers<Seq>>>(), fhash)
+    }
+
+    fn sketch_kmerseq_seqs<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Self::Sig>, String>>
+                    where   Seq : KmerSequence<Kmer> + ?Sized,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let canonical : Vec<CanonicalKmers<Seq>> = vseq.iter().map(|seq| CanonicalKmers(*seq)).collect();
+        self.sketcher.sketch_kmerseq_seqs(&canonical.iter().collect::<Vec<&CanonicalKmers<Seq>>>(), fhash)
+    }
+
+    fn sketch_kmerseq_stats<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<SigsWithStats<Self::Sig>, String>>
+                    where   Seq : KmerSequence<Kmer> + ?Sized,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let canonical : Vec<CanonicalKmers<Seq>> = vseq.iter().map(|seq| CanonicalKmers(*seq)).collect();
+        self.sketcher.sketch_kmerseq_stats(&canonical.iter().collect::<Vec<&CanonicalKmers<Seq>>>(), fhash)
+    }
+
+    // chunks are sketched by the wrapped sketcher, computing reverse complements for each kmer
+    fn sketch_compressedkmer_chunked<F>(&self, seq : &Sequence, chunk_size : usize, fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+                    where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        self.sketcher.sketch_compressedkmer_chunked(seq, chunk_size, canonical_hash(&fhash))
+    }
+
+    fn sketch_compressedkmer_stats<F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Result<SigsWithStats<Self::Sig>, String>
+                    where   Kmer : KmerBuilder<Kmer>,
+                            Kmer::Val : Hash + Debug,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        if let Some(sigs) = self.sketch_kmerseq_stats(vseq, &fhash) {
+            return sigs;
+        }
+        let sigs = self.sketch_compressedkmer(vseq, &fhash)?;
+        let canonical : Vec<CanonicalKmers<Sequence>> = vseq.iter().map(|seq| CanonicalKmers(*seq)).collect();
+        Ok((sigs, kmer_stats_each(&canonical.iter().collect::<Vec<&CanonicalKmers<Sequence>>>(), self.get_kmer_size(), &fhash)))
+    }
+
+    fn sketch_compressedkmer_bytes_stats<F>(&self, vseq : &[&[u8]], fhash : F) -> Result<SigsWithStats<Self::Sig>, String>
+                    where   Kmer : KmerBuilder<Kmer>,
+                            Kmer::Val : Hash + Debug,
+                            F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let slices : Vec<DnaSlice> = vseq.iter().map(|raw| DnaSlice(raw)).collect();
+        if let Some(sigs) = self.sketch_kmerseq_stats(&slices.iter().collect::<Vec<&DnaSlice>>(), &fhash) {
+            return sigs;
+        }
+        let sigs = self.sketch_compressedkmer_bytes(vseq, &fhash)?;
+        let canonical : Vec<CanonicalKmers<DnaSlice>> = slices.iter().map(CanonicalKmers).collect();
+        Ok((sigs, kmer_stats_each(&canonical.iter().collect::<Vec<&CanonicalKmers<DnaSlice>>>(), self.get_kmer_size(), &fhash)))
+    }
+} // end of impl SeqSketcherT for CanonicalSketcher
+
+
 
 
 /// A structure providing ProbMinHash3a sketching implementing the generic trait SeqSketcherT\<Kmer\>.  
 /// 
-#[derive(Serialize,Deserialize,Copy,Clone)]
+#[derive(Serialize,Deserialize,Copy,Clone,Debug)]
 pub struct ProbHash3aSketch<Kmer> {
     //
     _kmer_marker: PhantomData<Kmer>,
@@ -95,13 +350,120 @@ impl <Kmer> ProbHash3aSketch<Kmer> {
         ProbHash3aSketch{_kmer_marker : PhantomData,  params : params.clone()}
     }
 
+    /// returns the sketching parameters
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
+
+    /// sketch an exact count table (for example reloaded from a Jellyfish or KMC database) using counts as weights.  
+    /// This avoids recounting kmers already counted by an external tool. The kmer size of the table must be the one of the sketcher.
+    pub fn sketch_count_table<F>(&self, table : &KmerCountTable<Kmer>, fhash : F) -> Result<Vec<Kmer::Val>, String>
+            where   Kmer : CompressedKmerT + Hash,
+                    Kmer::Val : num::PrimInt + Debug,
+                    F : Fn(&Kmer) -> Kmer::Val {
+        //
+        log::debug!("entering sketch_count_table");
+        //
+        if table.get_kmer_size() != self.params.get_kmer_size() {
+            log::error!("sketch_count_table : table kmer size {} , sketcher kmer size {}", table.get_kmer_size(), self.params.get_kmer_size());
+            return Err("sketch_count_table : kmer size mismatch".to_string());
+        }
+        let mut wb = KmerWeights::<Kmer::Val>::with_capacity_and_hasher(table.len(), BuildKmerWeightHasher::default());
+        for (kmer, count) in table.iter() {
+            *wb.entry(fhash(kmer)).or_insert(0) += *count as u64;
+        }
+        Ok(probminhash3a_weights_signature(wb, &self.params))
+    } // end of sketch_count_table
+
+
+    /// adds the kmer counts of sequences to a weight map. This enables incremental sketching of a stream of sequences
+    /// in chunks, the signature being computed at the end by [Self::sketch_weights].
+    pub fn accumulate_weights<F>(&self, vseq : &[&Sequence], fhash : &F, weights : &mut KmerWeights<Kmer::Val>)
+            where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                    Kmer::Val : num::PrimInt + Debug,
+                    F : Fn(&Kmer) -> Kmer::Val {
+        accumulate_kmer_weights(vseq, self.params.get_kmer_size(), fhash, weights);
+    } // end of accumulate_weights
+
+
+    /// computes the signature of a weight map filled by [Self::accumulate_weights], applying the abundance filter if any.
+    pub fn sketch_weights(&self, weights : KmerWeights<Kmer::Val>) -> Vec<Kmer::Val>
+            where   Kmer : CompressedKmerT,
+                    Kmer::Val : num::PrimInt + Debug {
+        probminhash3a_weights_signature(weights, &self.params)
+    } // end of sketch_weights
+
+
+    // computes the sketch of sequences given with their quality bytes (phred + 33).
+    // Each kmer occurrence is weighted by the product over its bases of (1 - error probability).
+    fn sketch_qual_block<F>(&self, vseq : &[(&Sequence, &[u8])], fhash : &F) -> Vec<Kmer::Val>
+            where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                    Kmer::Val : num::PrimInt + Debug,
+                    F : Fn(&Kmer) -> Kmer::Val {
+        //
+        let kmer_size = self.params.get_kmer_size();
+        let nb_kmer = vseq.iter().map(|(s, _)| s.size()).sum::<usize>();
+        // weight and count of each kmer
+        let mut wb : FnvHashMap::<Kmer::Val,(f64, u64)> = FnvHashMap::with_capacity_and_hasher(nb_kmer, FnvBuildHasher::default());
+        for (seq, qual) in vseq {
+            assert_eq!(seq.size(), qual.len(), "sketch_compressedkmer_qual : sequence and quality must have same length");
+            // prefix sums of log(1-p) to get kmer weight in constant time
+            let mut log_sum = Vec::<f64>::with_capacity(qual.len() + 1);
+            log_sum.push(0.);
+            for q in qual.iter() {
+                let proba = 10f64.powf(-(q.saturating_sub(33) as f64) / 10.).min(0.75);
+                log_sum.push(log_sum.last().unwrap() + (1. - proba).ln());
+            }
+            let mut kmergen = KmerSeqIterator::<Kmer>::new(kmer_size as u8, seq);
+            kmergen.set_range(0..seq.size()).unwrap();
+            let mut pos = 0;
+            while let Some(kmer) = kmergen.next() {
+                let weight = (log_sum[pos + kmer_size] - log_sum[pos]).exp();
+                let entry = wb.entry(fhash(&kmer)).or_insert((0., 0));
+                entry.0 += weight;
+                entry.1 += 1;
+                pos += 1;
+            }
+        }
+        // abundance filtering is done on counts
+        let weights : FnvHashMap::<Kmer::Val,f64> = wb.into_iter().filter(|(_, (_, c))| self.params.is_count_accepted(*c)).map(|(k, (w, _))| (k, w)).collect();
+        let mut pminhashb = ProbMinHash3a::<Kmer::Val,NoHashHasher>::new(self.params.get_sketch_size(), <Kmer::Val>::default());
+        pminhashb.hash_weigthed_hashmap(&weights);
+        pminhashb.get_signature().clone()
+    } // end of sketch_qual_block
+
+
+    /// sketches each sequence, given with its quality bytes (phred + 33, fastq encoding), in its own signature.
+    /// Each kmer occurrence has weight the product over its bases of (1 - error probability) instead of 1,
+    /// see [SeqSketcherParams::set_quality_weighting]. The abundance filter, if any, applies to kmer counts.
+    pub fn sketch_compressedkmer_qual<F>(&self, vseq : &Vec<(&Sequence, &[u8])>, fhash : F) -> Vec<Vec<Kmer::Val>>
+            where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
+                    Kmer::Val : num::PrimInt + Send + Sync + Debug,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        //
+        log::debug!("entering sketch_compressedkmer_qual for ProbHash3aSketch");
+        vseq.par_iter().map(|s| self.sketch_qual_block(&[*s], &fhash)).collect()
+    } // end of sketch_compressedkmer_qual
+
+
+    /// sketches a collection of sequences, given with their quality bytes, in one signature.
+    /// See [Self::sketch_compressedkmer_qual]. The returned vector has size 1.
+    pub fn sketch_compressedkmer_qual_seqs<F>(&self, vseq : &Vec<(&Sequence, &[u8])>, fhash : F) -> Vec<Vec<Kmer::Val>>
+            where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                    Kmer::Val : num::PrimInt + Debug,
+                    F : Fn(&Kmer) -> Kmer::Val {
+        //
+        log::debug!("entering sketch_compressedkmer_qual_seqs for ProbHash3aSketch");
+        vec![self.sketch_qual_block(vseq, &fhash)]
+    } // end of sketch_compressedkmer_qual_seqs
+
 } // end of impl ProbHash3aSketch
 
 
 
 impl <Kmer> SeqSketcherT<Kmer> for ProbHash3aSketch<Kmer> 
         where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
-                Kmer::Val : num::PrimInt + Send + Sync + Debug + Clone + Serialize,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + Clone + Serialize + 'static,
                 KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer> {
 
     type Sig = Kmer::Val;
@@ -119,96 +481,54 @@ impl <Kmer> SeqSketcherT<Kmer> for ProbHash3aSketch<Kmer>
         SketchAlgo::PROB3A
     }
 
-    fn sketch_compressedkmer<F> (&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<Self::Sig> > 
+    fn sketch_compressedkmer<F> (&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String> 
             where  F : Fn(&Kmer) -> Kmer::Val + Send + Sync   {
         //
-        log::debug!("entering sketch_probminhash3a_compressedkmer");
-        //
-        let comput_closure = | seqb : &Sequence, i:usize | -> (usize,Vec<Kmer::Val>) {
-            // if we get very large sequence (many Gb length) we must be cautious on size of hashmap; i.e about number of different kmers!!! 
-            let nb_kmer = get_nbkmer_guess(seqb);
-            let mut wb : FnvHashMap::<Kmer::Val,u64> = FnvHashMap::with_capacity_and_hasher(nb_kmer, FnvBuildHasher::default());
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size() as u8, &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        let hashval = fhash(&kmer);
-                        *wb.entry(hashval).or_insert(0) += 1;
-                    },
-                    None => break,
-                }
-            }  // end loop 
-            let mut pminhashb = ProbMinHash3a::<Kmer::Val,NoHashHasher>::new(self.get_sketch_size(), 
-                <Kmer::Val>::default());
-            pminhashb.hash_weigthed_hashmap(&wb);
-            let sigb = pminhashb.get_signature();
-            // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
-        };
+        log::debug!("entering sketch_compressedkmer for ProbHash3aSketch");
         //
-        let sig_with_rank : Vec::<(usize,Vec<Kmer::Val>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<Kmer::Val>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
-        log::debug!("exiting sketch_probminhash3a_compressedkmer");
-        jaccard_vec
+        Ok(sketch_each(vseq, |seq| probminhash3a_signature(&[seq], &self.params, &fhash)))
+    }
+
+
+    fn sketch_compressedkmer_chunked<F>(&self, seq : &Sequence, chunk_size : usize, fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+            where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let views = get_chunk_views(seq, self.get_kmer_size(), chunk_size)?;
+        Ok(vec![probminhash3a_chunked_signature(&views.iter().collect::<Vec<&SequenceView>>(), &self.params, &fhash)])
     }
 
 
 
     // This functin implement the sketching a File of Sequences, (The sequence are not concatenated, so we have many sequences) and make one sketch Vector 
-    fn sketch_compressedkmer_seqs<F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<Self::Sig> > 
+    fn sketch_compressedkmer_seqs<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String> 
         where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
                 F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
                 Kmer::Val : num::PrimInt + Send + Sync + Debug,
                 KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer> {
         //
-        log::debug!("entering sketch_compressedkmer_seqs for ProHash3aSketch");
-        //
-        // we must estimate nb kmer to avoid reallocation in FnvHashMap
-        let nb_kmer = get_nbkmer_guess_seqs(vseq);
-        //
-        let mut wb : FnvHashMap::<Kmer::Val,u64> = FnvHashMap::with_capacity_and_hasher(nb_kmer, FnvBuildHasher::default());
+        log::debug!("entering sketch_compressedkmer_seqs for ProbHash3aSketch");
         //
-        let mut nb_kmer_generated : u64 = 0;
-        // we loop on sequences and generate kmer. TODO // on sequences
-        for seq in vseq {
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size() as u8, &seq);
-            kmergen.set_range(0, seq.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        nb_kmer_generated += 1;
-                        let hashval = fhash(&kmer);
-                        *wb.entry(hashval).or_insert(0) += 1;
-                    },
-                    None => break,
-                }
-                if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % 500_000_000 == 0 {
-                    log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
-                }
-            }  // end loop 
-        }
-        let mut pminhashb : ProbMinHash3a<Kmer::Val, NoHashHasher> = ProbMinHash3a::<Kmer::Val,NoHashHasher>::new(self.get_sketch_size(),
-                    <Kmer::Val>::default());
-        //
-        pminhashb.hash_weigthed_hashmap(&wb);
-        let sigb = pminhashb.get_signature();
-        //
-        let mut v = Vec::<Vec<Self::Sig>>::with_capacity(1);
-        v.push(sigb.clone());
-        //
-        return v;        
+        Ok(vec![probminhash3a_signature(vseq, &self.params, &fhash)])
     } // end of sketch_compressedkmer_seqs
 
+    // raw bytes and views are sketched without copy by the generic functions of seqsketchgen
+    fn sketch_kmerseq_each<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Vec<Self::Sig>>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(Ok(sketch_each(vseq, |seq| probminhash3a_signature(&[seq], &self.params, fhash))))
+    }
+
+    fn sketch_kmerseq_seqs<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(Ok(probminhash3a_signature(vseq, &self.params, fhash)))
+    }
+
+    fn sketch_kmerseq_stats<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<SigsWithStats<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(try_sketch_each_stats(vseq, |seq, stats| Ok(probminhash3a_signature_stats(&[seq], &self.params, fhash, Some(stats)))))
+    }
+
 }  // end of impl SeqSketcherT for ProHash3aSketch
 
 
@@ -217,7 +537,7 @@ impl <Kmer> SeqSketcherT<Kmer> for ProbHash3aSketch<Kmer>
 ///
 ///  A structure providing SuperMinHash sketching implementing the generic trait SeqSketcherT\<Kmer\>.  
 ///  The type argument S encodes for f32 or f64 as the SuperMinHash can sketch to f32 or f64
-#[derive(Serialize,Deserialize,Copy,Clone)]
+#[derive(Serialize,Deserialize,Copy,Clone,Debug)]
 pub struct SuperHashSketch<Kmer, S: num::Float> {
     //
     _kmer_marker: PhantomData<Kmer>,
@@ -235,6 +555,11 @@ impl <Kmer, S : num::Float> SuperHashSketch<Kmer,S> {
         SuperHashSketch{_kmer_marker : PhantomData, _sig_marker: PhantomData,  params : params.clone()}
     }
 
+    /// returns the sketching parameters
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
+
 } // end of impl SuperHashSketch
 
 
@@ -242,9 +567,9 @@ impl <Kmer, S : num::Float> SuperHashSketch<Kmer,S> {
 
 impl <Kmer,S> SeqSketcherT<Kmer> for SuperHashSketch<Kmer, S> 
         where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
-                Kmer::Val : num::PrimInt + Send + Sync + Debug,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + 'static,
                 KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer>,
-                S : num::Float + SampleUniform + Send + Sync + Debug + Serialize  {
+                S : num::Float + SampleUniform + Send + Sync + Debug + Serialize + 'static {
 
     type Sig = S;
 
@@ -264,96 +589,57 @@ impl <Kmer,S> SeqSketcherT<Kmer> for SuperHashSketch<Kmer, S>
     /// Kmer::Val is the base type u32, u64 on which compressed kmer representations relies.
     /// F is a hash function returning morally a u32, usize or u64.  
     /// The argument type of the hashing function F specify the type of Kmer to generate along the sequence.  
-    fn sketch_compressedkmer<F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmer<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
         where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
         //
-        log::debug!("entering sketch_superminhash_compressedkmer");
+        log::debug!("entering sketch_compressedkmer for SuperHashSketch");
         //
-        let comput_closure = | seqb : &Sequence, i:usize | -> (usize,Vec<Self::Sig>) {
-            //
-            log::debug!(" in sketch_compressedkmer, closure");
-            let mut nb_kmer_generated : u64 = 0;
-            //
-            let bh = BuildHasherDefault::<NoHashHasher>::default();
-            let mut sminhash : SuperMinHash<Self::Sig, Kmer::Val, NoHashHasher>= SuperMinHash::new(self.get_sketch_size(), bh);
-
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size() as u8, &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        nb_kmer_generated += 1;
-                        let hashval = fhash(&kmer);
-                        if sminhash.sketch(&hashval).is_err() {
-                            log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                            std::panic!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                        }
-                    },
-                    None => break,
-                }
-                if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % 500_000_000 == 0 {
-                    log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
-                }
-            }  // end loop 
-            let sigb = sminhash.get_hsketch();
-            // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
-        };
-        //
-        let sig_with_rank : Vec::<(usize,Vec<Self::Sig>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<Self::Sig>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
-        jaccard_vec
+        try_sketch_each(vseq, |seq| superminhash_signature(&[seq], &self.params, &fhash, None))
     } // end of sketch_compressedkmer
 
 
-    fn sketch_compressedkmer_seqs<F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmer_chunked<F>(&self, seq : &Sequence, chunk_size : usize, fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+            where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let views = get_chunk_views(seq, self.get_kmer_size(), chunk_size)?;
+        Ok(vec![superminhash_chunked_signature(&views.iter().collect::<Vec<&SequenceView>>(), &self.params, &fhash)?])
+    }
+
+
+    fn sketch_compressedkmer_seqs<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
             where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
                     F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
                     Kmer::Val : num::PrimInt + Send + Sync + Debug,
                     KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer> {
         //
-        log::debug!("entering  sketch_compressedkmer_seqs for SuperMinHashSketch");
-        //
-        let bh = BuildHasherDefault::<NoHashHasher>::default();
-        let mut setsketch : SuperMinHash<Self::Sig, Kmer::Val, NoHashHasher> = SuperMinHash::new(self.get_sketch_size(), bh);
+        log::debug!("entering sketch_compressedkmer_seqs for SuperHashSketch");
         //
-        let mut nb_kmer_generated : u64 = 0;
-        // we loop on sequences and generate kmer. TODO // on sequences
-        for seq in vseq {
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size() as u8, &seq);
-            kmergen.set_range(0, seq.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        nb_kmer_generated += 1;
-                        let hashval = fhash(&kmer);
-                        if setsketch.sketch(&hashval).is_err() {
-                            log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                            std::panic!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                        }
-                    },
-                    None => break,
-                }
-                if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % 500_000_000 == 0 {
-                    log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
-                }
-            }  // end loop 
-        }
-        //
-        let mut v = Vec::<Vec<Self::Sig>>::with_capacity(1);
-        let sig = setsketch.get_hsketch();
-        v.push(sig.clone());
-        //
-        return v;
+        // in singleton removal mode a first pass records kmers seen at least twice
+        let solid_filter = if self.params.get_singleton_removal() {
+            Some(build_solid_kmer_filter::<Kmer, _>(vseq, self.get_kmer_size(), &fhash))
+        } else { None };
+        Ok(vec![superminhash_signature(vseq, &self.params, &fhash, solid_filter.as_ref())?])
+    }
+
+    // raw bytes and views are sketched without copy by the generic functions of seqsketchgen
+    fn sketch_kmerseq_each<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Vec<Self::Sig>>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(try_sketch_each(vseq, |seq| superminhash_signature(&[seq], &self.params, fhash, None)))
+    }
+
+    fn sketch_kmerseq_seqs<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let solid_filter = if self.params.get_singleton_removal() {
+            Some(build_solid_filter(vseq, self.get_kmer_size(), fhash))
+        } else { None };
+        Some(superminhash_signature(vseq, &self.params, fhash, solid_filter.as_ref()))
+    }
+
+    fn sketch_kmerseq_stats<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<SigsWithStats<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(try_sketch_each_stats(vseq, |seq, stats| superminhash_signature_stats(&[seq], &self.params, fhash, None, Some(stats))))
     }
 
 
@@ -380,15 +666,20 @@ impl <Kmer, S : num::Float> OptDensHashSketch<Kmer,S> {
     pub fn new(params : &SeqSketcherParams) -> Self {
         OptDensHashSketch{_kmer_marker : PhantomData, _sig_marker: PhantomData,  params : params.clone()}
     }
+
+    /// returns the sketching parameters
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
 }  // end of OptDensMinHashSketch
 
 
 
 impl <Kmer,S> SeqSketcherT<Kmer> for OptDensHashSketch<Kmer, S> 
         where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
-                Kmer::Val : num::PrimInt + Send + Sync + Debug,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + 'static,
                 KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer>,
-                S : num::Float + SampleUniform + Send + Sync + Debug + Serialize  {
+                S : num::Float + SampleUniform + Send + Sync + Debug + Serialize + 'static {
 
     type Sig = S;
 
@@ -404,97 +695,52 @@ impl <Kmer,S> SeqSketcherT<Kmer> for OptDensHashSketch<Kmer, S>
         SketchAlgo::OPTDENS
     }
 
-    fn sketch_compressedkmer<F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmer<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
     where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
         //
-       log::debug!("entering OptDensHashSketch::sketch_compressedkmer");
-              //
-            let comput_closure = | seqb : &Sequence, i:usize | -> (usize,Vec<Self::Sig>) {
-            //
-            log::debug!(" in sketch_compressedkmer, closure");
-            let mut nb_kmer_generated : u64 = 0;
-            //
-            let bh = BuildHasherDefault::<NoHashHasher>::default();
-            let mut sminhash : OptDensMinHash<Self::Sig, Kmer::Val, NoHashHasher>= OptDensMinHash::new(self.get_sketch_size(), bh);
-
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size() as u8, &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        nb_kmer_generated += 1;
-                        let hashval = fhash(&kmer);
-                        sminhash.sketch(&hashval);
-                    },
-                    None => break,
-                }
-                if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % 500_000_000 == 0 {
-                    log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
-                }
-            }  // end loop 
-            // do not forget to close sketching (it calls densification!)
-            sminhash.end_sketch();
-            let sigb = sminhash.get_hsketch();
-            // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
-        };
+        log::debug!("entering sketch_compressedkmer for OptDensHashSketch");
         //
-        let sig_with_rank : Vec::<(usize,Vec<Self::Sig>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<Self::Sig>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
-        //
-        jaccard_vec
+        Ok(sketch_each(vseq, |seq| optdens_signature(&[seq], &self.params, &fhash, None)))
     } // end of sketch_compressedkmer
 
 
-    fn sketch_compressedkmer_seqs<F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmer_seqs<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
             where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
                     F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
                     Kmer::Val : num::PrimInt + Send + Sync + Debug,
                     KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer> {
         //
-        log::debug!("entering  OptDensHashSketch::sketch_compressedkmer_seqs");
-        //
-        let bh = BuildHasherDefault::<NoHashHasher>::default();
-        let mut setsketch : OptDensMinHash<Self::Sig, Kmer::Val, NoHashHasher> = OptDensMinHash::new(self.get_sketch_size(), bh);
-        //
-        let mut nb_kmer_generated : u64 = 0;
-        // we loop on sequences and generate kmer. TODO // on sequences
-        for seq in vseq {
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size() as u8, &seq);
-            kmergen.set_range(0, seq.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        nb_kmer_generated += 1;
-                        let hashval = fhash(&kmer);
-                        setsketch.sketch(&hashval);
-                    },
-                    None => break,
-                }
-                if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % 500_000_000 == 0 {
-                    log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
-                }
-            }  // end loop 
-        }
-        //
-        let mut v = Vec::<Vec<Self::Sig>>::with_capacity(1);
-        // do not forget to close sketching (it calls densification!)
-        setsketch.end_sketch();
-        let sig = setsketch.get_hsketch();
-        v.push(sig.clone());
+        log::debug!("entering sketch_compressedkmer_seqs for OptDensHashSketch");
         //
-        return v;
+        // in singleton removal mode a first pass records kmers seen at least twice
+        let solid_filter = if self.params.get_singleton_removal() {
+            Some(build_solid_kmer_filter::<Kmer, _>(vseq, self.get_kmer_size(), &fhash))
+        } else { None };
+        Ok(vec![optdens_signature(vseq, &self.params, &fhash, solid_filter.as_ref())])
     } // end of sketch_compressedkmer_seqs
 
+    // raw bytes and views are sketched without copy by the generic functions of seqsketchgen
+    fn sketch_kmerseq_each<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Vec<Self::Sig>>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(Ok(sketch_each(vseq, |seq| optdens_signature(&[seq], &self.params, fhash, None))))
+    }
+
+    fn sketch_kmerseq_seqs<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let solid_filter = if self.params.get_singleton_removal() {
+            Some(build_solid_filter(vseq, self.get_kmer_size(), fhash))
+        } else { None };
+        Some(Ok(optdens_signature(vseq, &self.params, fhash, solid_filter.as_ref())))
+    }
+
+    fn sketch_kmerseq_stats<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<SigsWithStats<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(try_sketch_each_stats(vseq, |seq, stats| Ok(optdens_signature_stats(&[seq], &self.params, fhash, None, Some(stats)))))
+    }
+
 } // end of impl SeqSketcherT<Kmer> for OptDensHashSketch
 
 //====================================================================================
@@ -521,14 +767,19 @@ impl <Kmer, S : num::Float> RevOptDensHashSketch<Kmer,S> {
     pub fn new(params : &SeqSketcherParams) -> Self {
         RevOptDensHashSketch{_kmer_marker : PhantomData, _sig_marker: PhantomData,  params : params.clone()}
     } 
+
+    /// returns the sketching parameters
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
 }  // end of RevOptDensMinHashSketch
 
 
 impl <Kmer,S> SeqSketcherT<Kmer> for RevOptDensHashSketch<Kmer, S> 
         where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
-                Kmer::Val : num::PrimInt + Send + Sync + Debug,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + 'static,
                 KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer>,
-                S : num::Float + SampleUniform + Send + Sync + Debug + Serialize  {
+                S : num::Float + SampleUniform + Send + Sync + Debug + Serialize + 'static {
 
     type Sig = S;
 
@@ -544,97 +795,52 @@ impl <Kmer,S> SeqSketcherT<Kmer> for RevOptDensHashSketch<Kmer, S>
         SketchAlgo::REVOPTDENS
     }
 
-    fn sketch_compressedkmer<F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmer<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
     where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
         //
-       log::debug!("entering RevOptDensHashSketch::sketch_compressedkmer");
-              //
-            let comput_closure = | seqb : &Sequence, i:usize | -> (usize,Vec<Self::Sig>) {
-            //
-            log::debug!(" in sketch_compressedkmer, closure");
-            let mut nb_kmer_generated : u64 = 0;
-            //
-            let bh = BuildHasherDefault::<NoHashHasher>::default();
-            let mut sminhash : RevOptDensMinHash<Self::Sig, Kmer::Val, NoHashHasher>= RevOptDensMinHash::new(self.get_sketch_size(), bh);
-
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size() as u8, &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        nb_kmer_generated += 1;
-                        let hashval = fhash(&kmer);
-                        sminhash.sketch(&hashval);
-                    },
-                    None => break,
-                }
-                if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % 500_000_000 == 0 {
-                    log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
-                }
-            }  // end loop 
-            // do not forget to close sketching (it calls densification!)
-            sminhash.end_sketch();
-            let sigb = sminhash.get_hsketch();
-            // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
-        };
-        //
-        let sig_with_rank : Vec::<(usize,Vec<Self::Sig>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<Self::Sig>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
+        log::debug!("entering sketch_compressedkmer for RevOptDensHashSketch");
         //
-        jaccard_vec
+        Ok(sketch_each(vseq, |seq| revoptdens_signature(&[seq], &self.params, &fhash, None)))
     } // end of sketch_compressedkmer
 
 
-    fn sketch_compressedkmer_seqs<F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmer_seqs<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
             where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
                     F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
                     Kmer::Val : num::PrimInt + Send + Sync + Debug,
                     KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer> {
         //
-        log::debug!("entering  RevOptDensHashSketch::sketch_compressedkmer_seqs");
+        log::debug!("entering sketch_compressedkmer_seqs for RevOptDensHashSketch");
         //
-        let bh = BuildHasherDefault::<NoHashHasher>::default();
-        let mut setsketch : RevOptDensMinHash<Self::Sig, Kmer::Val, NoHashHasher> = RevOptDensMinHash::new(self.get_sketch_size(), bh);
-        //
-        let mut nb_kmer_generated : u64 = 0;
-        // we loop on sequences and generate kmer. TODO // on sequences
-        for seq in vseq {
-            let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size() as u8, &seq);
-            kmergen.set_range(0, seq.size()).unwrap();
-            loop {
-                match kmergen.next() {
-                    Some(kmer) => {
-                        nb_kmer_generated += 1;
-                        let hashval = fhash(&kmer);
-                        setsketch.sketch(&hashval);
-                    },
-                    None => break,
-                }
-                if log::log_enabled!(log::Level::Debug) && nb_kmer_generated % 500_000_000 == 0 {
-                    log::debug!("nb kmer generated : {:#}", nb_kmer_generated);
-                }
-            }  // end loop 
-        }
-        //
-        let mut v = Vec::<Vec<Self::Sig>>::with_capacity(1);
-        // do not forget to close sketching (it calls densification!)
-        setsketch.end_sketch();
-        let sig = setsketch.get_hsketch();
-        v.push(sig.clone());
-        //
-        return v;
+        // in singleton removal mode a first pass records kmers seen at least twice
+        let solid_filter = if self.params.get_singleton_removal() {
+            Some(build_solid_kmer_filter::<Kmer, _>(vseq, self.get_kmer_size(), &fhash))
+        } else { None };
+        Ok(vec![revoptdens_signature(vseq, &self.params, &fhash, solid_filter.as_ref())])
     } // end of sketch_compressedkmer_seqs
 
+    // raw bytes and views are sketched without copy by the generic functions of seqsketchgen
+    fn sketch_kmerseq_each<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Vec<Self::Sig>>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(Ok(sketch_each(vseq, |seq| revoptdens_signature(&[seq], &self.params, fhash, None))))
+    }
+
+    fn sketch_kmerseq_seqs<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<Vec<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let solid_filter = if self.params.get_singleton_removal() {
+            Some(build_solid_filter(vseq, self.get_kmer_size(), fhash))
+        } else { None };
+        Some(Ok(revoptdens_signature(vseq, &self.params, fhash, solid_filter.as_ref())))
+    }
+
+    fn sketch_kmerseq_stats<Seq, F>(&self, vseq : &[&Seq], fhash : &F) -> Option<Result<SigsWithStats<Self::Sig>, String>>
+            where   Seq : KmerSequence<Kmer> + ?Sized,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        Some(try_sketch_each_stats(vseq, |seq, stats| Ok(revoptdens_signature_stats(&[seq], &self.params, fhash, None, Some(stats)))))
+    }
+
 } // end of impl SeqSketcherT<Kmer> for RevOptDensHashSketch
 
 
@@ -684,7 +890,7 @@ impl Default for HllSeqsThreading {
 ///  **The number of threads is defined by (nb_bases/thread_threshold).ilog(3).min(HllSeqsThreading::nb_iter_thread).max(1).**  
 ///  This is useful if the caller use also multithreading.  
 
-#[derive(Serialize,Deserialize,Copy,Clone)]
+#[derive(Serialize,Deserialize,Copy,Clone,Debug)]
 pub struct HyperLogLogSketch<Kmer, S: num::Integer> {
     //
     params : SeqSketcherParams,
@@ -706,8 +912,48 @@ impl <Kmer, S : Integer> HyperLogLogSketch<Kmer, S> {
                 _kmer_marker :  PhantomData, _sig_marker: PhantomData}
     }
 
+    /// returns the sketching parameters
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
+
+    /// returns the hyperloglog parameters
+    pub fn get_hll_params(&self) -> SetSketchParams {
+        self.hll_params
+    }
+
     // building block for sketch_compressedkmer_seqs. sketch a list of sequence and return a sketch to merge!
-    pub fn sketch_compressedkmer_seqs_block<F>(&self, vseq : &[&Sequence], fhash : F) -> SetSketcher<S, Kmer::Val, NoHashHasher>
+    pub fn sketch_compressedkmer_seqs_block<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<SetSketcher<S, Kmer::Val, NoHashHasher>, String>
+            where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
+                    F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
+                    Kmer::Val : num::PrimInt + Send + Sync + Debug,
+                    KmerGenerator<Kmer> :  KmerGenerationPattern<Kmer>,
+                    S : Integer + Bounded + Copy + Clone + FromPrimitive + ToPrimitive + Send + Sync + Debug + Serialize {
+        self.sketch_compressedkmer_seqs_block_filtered(vseq, fhash, None)
+    }
+
+    // signature of the union of sketches
+    fn merge_setsketches(&self, v_sketch : &[SetSketcher<S, Kmer::Val, NoHashHasher>]) -> Result<Vec<S>, String>
+            where   Kmer : CompressedKmerT,
+                    Kmer::Val : num::PrimInt + Debug,
+                    S : Integer + Bounded + Copy + Clone + FromPrimitive + ToPrimitive + Send + Sync + Debug + Serialize {
+        // we allocate a sketcher that will contain the union. Signature is initialized to 0.
+        let bh = BuildHasherDefault::<NoHashHasher>::default();
+        let mut setsketch : SetSketcher<S, Kmer::Val, NoHashHasher>= SetSketcher::new(self.hll_params, bh);
+        // now we can merge signatures
+        for sketch in v_sketch {
+            if setsketch.merge(sketch).is_err() {
+                log::error!("an error occurred in merging signatures");
+                return Err("an error occurred in merging signatures".to_string());
+            }
+        }
+        Ok(setsketch.get_signature().clone())
+    } // end of merge_setsketches
+
+
+    // same as sketch_compressedkmer_seqs_block but skips kmers not in solid_filter if any.
+    // The filter must be built on all sequences before dispatching blocks.
+    fn sketch_compressedkmer_seqs_block_filtered<F>(&self, vseq : &[&Sequence], fhash : F, solid_filter : Option<&SolidKmerFilter>) -> Result<SetSketcher<S, Kmer::Val, NoHashHasher>, String>
             where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
                     F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
                     Kmer::Val : num::PrimInt + Send + Sync + Debug,
@@ -723,15 +969,20 @@ impl <Kmer, S : Integer> HyperLogLogSketch<Kmer, S> {
         // we loop on sequences and generate kmer. TODO // on sequences
         for seq in vseq {
             let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size() as u8, &seq);
-            kmergen.set_range(0, seq.size()).unwrap();
+            kmergen.set_range(0..seq.size()).unwrap();
             loop {
                 match kmergen.next() {
                     Some(kmer) => {
                         nb_kmer_generated += 1;
                         let hashval = fhash(&kmer);
+                        if let Some(filter) = solid_filter {
+                            if !filter.is_solid(&hashval) {
+                                continue;
+                            }
+                        }
                         if setsketch.sketch(&hashval).is_err() {
                             log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                            std::panic!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
+                            return Err(format!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer()));
                         }
                     },
                     None => break,
@@ -742,7 +993,7 @@ impl <Kmer, S : Integer> HyperLogLogSketch<Kmer, S> {
             }  // end loop 
         }
         //
-        return setsketch;
+        Ok(setsketch)
     }
 
 
@@ -771,12 +1022,12 @@ impl <Kmer,S> SeqSketcherT<Kmer> for HyperLogLogSketch<Kmer, S>
 
     // This funtions sketch a list of of Sequence and returns a Sketch vector for each one.
     // In fact each sequence is a file that was concatenated in a sequence.
-    fn sketch_compressedkmer<F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmer<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
         where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
         //
         log::debug!("entering sketch_compressedkmer for HyperLogLogSketch");
         //
-        let comput_closure = | seqb : &Sequence, i:usize | -> (usize,Vec<Self::Sig>) {
+        let comput_closure = | seqb : &Sequence | -> Result<Vec<Self::Sig>, String> {
             //
             log::debug!(" in sketch_compressedkmer, closure");
             let mut nb_kmer_generated : u64 = 0;
@@ -785,7 +1036,7 @@ impl <Kmer,S> SeqSketcherT<Kmer> for HyperLogLogSketch<Kmer, S>
             let mut setsketch : SetSketcher<Self::Sig, Kmer::Val, NoHashHasher>= SetSketcher::new(self.hll_params, bh);
 
             let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size() as u8, &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
+            kmergen.set_range(0..seqb.size()).unwrap();
             loop {
                 match kmergen.next() {
                     Some(kmer) => {
@@ -793,7 +1044,7 @@ impl <Kmer,S> SeqSketcherT<Kmer> for HyperLogLogSketch<Kmer, S>
                         let hashval = fhash(&kmer);
                         if setsketch.sketch(&hashval).is_err() {
                             log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                            std::panic!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
+                            return Err(format!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer()));
                         }
                     },
                     None => break,
@@ -806,21 +1057,11 @@ impl <Kmer,S> SeqSketcherT<Kmer> for HyperLogLogSketch<Kmer, S>
             // closure in function in // iter, we drop explicitly
             drop(setsketch);
             // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb);
+            Ok(sigb)
         };
         //
-        let sig_with_rank : Vec::<(usize,Vec<Self::Sig>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<Self::Sig>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
-        jaccard_vec
+        // signatures are collected in the order of vseq
+        try_sketch_each(vseq, comput_closure)
     } // end of sketch_compressedkmer
 
 
@@ -830,28 +1071,34 @@ impl <Kmer,S> SeqSketcherT<Kmer> for HyperLogLogSketch<Kmer, S>
     // The sequence are not concatenated, so we have many sequences. We dispatch sequences to sketch_compressedkmer_seqs_block
     // by parallelizing and merge sketch Vector.  
     // 
-    fn sketch_compressedkmer_seqs<F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<Self::Sig>>
+    fn sketch_compressedkmer_seqs<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
         where F : Fn(&Kmer) -> Kmer::Val + Send + Sync  {
         //
         if log::log_enabled!(log::Level::Debug) {
             log::debug!("entering sketch_compressedkmer_seqs for HyperLogLogSketch");
+            #[cfg(feature="io")]
             log::debug!("memory  : {:?}", memory_stats::memory_stats().unwrap());
         }
         // Now we will try to dispatch work. We use hll for large sequence (>= 10^7) otherwise we propably should use probminhash
         let thread_threshold = self.hll_threads.get_thread_threshold();
         const BASE_LOG : usize = 3;
         let total_size = vseq.iter().fold(0, |acc, s| acc + s.size() );
+        // in singleton removal mode a first pass records kmers seen at least twice
+        let solid_filter = if self.params.get_singleton_removal() {
+            Some(build_solid_kmer_filter::<Kmer, _>(vseq, self.get_kmer_size(), &fhash))
+        } else { None };
         if total_size <= BASE_LOG * thread_threshold {
             log::debug!("  calling directly sketch_compressedkmer_seqs_block, total size : {}", total_size);
-            let sketch =  self.sketch_compressedkmer_seqs_block(vseq, fhash);
+            let sketch =  self.sketch_compressedkmer_seqs_block_filtered(vseq, fhash, solid_filter.as_ref())?;
             let mut v_sketch = Vec::<Vec<Self::Sig>>::with_capacity(1);
             v_sketch.push(sketch.get_signature().clone());
             drop(sketch);
             if log::log_enabled!(log::Level::Debug) {
                 log::debug!("exiting sketch_compressedkmer_seqs for HyperLogLogSketch");
-                log::debug!("memory  : {:?}", memory_stats::memory_stats().unwrap());
+                #[cfg(feature="io")]
+            log::debug!("memory  : {:?}", memory_stats::memory_stats().unwrap());
             }
-            return v_sketch;
+            return Ok(v_sketch);
         }
         // we must split work in equal parts.  A few threads , we do not have so many threads. The threading must be treated at a higher level 
         // to correctly dispatch tasks.
@@ -874,34 +1121,45 @@ impl <Kmer,S> SeqSketcherT<Kmer> for HyperLogLogSketch<Kmer, S>
         }
         frontiers.push(nb_sequences);
         //
-        let v_sketch : Vec<SetSketcher<S, Kmer::Val, NoHashHasher> > = (0..nb_blocks).into_par_iter().map(|i| self.sketch_compressedkmer_seqs_block(&vseq[frontiers[i]..frontiers[i+1]], &fhash)).collect();
-        // we allocate a sketcher that will contain the union. Signature is initialized to 0.
-        let bh = BuildHasherDefault::<NoHashHasher>::default();
-        let mut setsketch : SetSketcher<S, Kmer::Val, NoHashHasher>= SetSketcher::new(self.hll_params, bh);
-        // now we can merge signatures
-        for sketch in &v_sketch {
-            let res = setsketch.merge(&sketch);
-            if res.is_err() {
-                log::error!("an error occurred in merging signatures");
-                std::panic!("an error occurred in merging signatures");
-            }
-        }
-        //
-        let sig = setsketch.get_signature().clone();
+        let v_sketch : Vec<SetSketcher<S, Kmer::Val, NoHashHasher> > = (0..nb_blocks).into_par_iter().map(|i| self.sketch_compressedkmer_seqs_block_filtered(&vseq[frontiers[i]..frontiers[i+1]], &fhash, solid_filter.as_ref())).collect::<Result<_,_>>()?;
+        let sig = self.merge_setsketches(&v_sketch)?;
         let mut v = Vec::<Vec<Self::Sig>>::with_capacity(1);
         v.push(sig);
         // explicit drop to monitor memory
         drop(v_sketch);
-        drop(setsketch);
         if log::log_enabled!(log::Level::Debug) {
             log::debug!("exiting sketch_compressedkmer_seqs for HyperLogLogSketch");
+            #[cfg(feature="io")]
             log::debug!("memory  : {:?}", memory_stats::memory_stats().unwrap());
         }
         //
-        return v;
+        Ok(v)
     } // end of sketch_compressedkmer_seqs
 
 
+    // chunks are sketched in parallel in SetSketchers and merged
+    fn sketch_compressedkmer_chunked<F>(&self, seq : &Sequence, chunk_size : usize, fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+            where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        let views = get_chunk_views(seq, self.get_kmer_size(), chunk_size)?;
+        let v_sketch : Vec<SetSketcher<S, Kmer::Val, NoHashHasher>> = views.par_iter().map(|view| {
+                let bh = BuildHasherDefault::<NoHashHasher>::default();
+                let mut setsketch : SetSketcher<S, Kmer::Val, NoHashHasher> = SetSketcher::new(self.hll_params, bh);
+                let mut error : Option<String> = None;
+                hash_kmers(&[view], self.get_kmer_size(), &fhash, None, |kmer : &Kmer, hashval| {
+                    if error.is_none() && setsketch.sketch(&hashval).is_err() {
+                        log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
+                        error = Some(format!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer()));
+                    }
+                });
+                match error {
+                    Some(e) => Err(e),
+                    None => Ok(setsketch),
+                }
+            }).collect::<Result<_,_>>()?;
+        Ok(vec![self.merge_setsketches(&v_sketch)?])
+    } // end of sketch_compressedkmer_chunked
+
+
 } // end of impl for HyperLogLogSketch
 
 
@@ -926,6 +1184,14 @@ pub struct SuperHash2Sketch<Kmer, S: Integer  + Unsigned, H : Hasher + Default>
     params : SeqSketcherParams,
 }
 
+// hashers do not implement Debug
+#[cfg(feature="sminhash2")]
+impl <Kmer, S : Integer + Unsigned, H : Hasher + Default> Debug for SuperHash2Sketch<Kmer, S, H> {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SuperHash2Sketch").field("params", &self.params).finish()
+    }
+}
+
 #[cfg(feature="sminhash2")]
 impl <Kmer, S :  Integer  + Unsigned,  H : Hasher + Default> SuperHash2Sketch<Kmer,S, H> {
 
@@ -934,6 +1200,11 @@ impl <Kmer, S :  Integer  + Unsigned,  H : Hasher + Default> SuperHash2Sketch<Km
         SuperHash2Sketch{_kmer_marker : PhantomData, _sig_marker: PhantomData,  build_hasher, params : params.clone()}
     }
 
+    /// returns the sketching parameters
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
+
 } 
 #[cfg(feature="sminhash2")]
 impl <Kmer,S, H> SeqSketcherT<Kmer> for SuperHash2Sketch<Kmer, S, H> 
@@ -961,12 +1232,12 @@ impl <Kmer,S, H> SeqSketcherT<Kmer> for SuperHash2Sketch<Kmer, S, H>
     /// Kmer::Val is the base type u32, u64 on which compressed kmer representations relies.
     /// F is a hash function returning morally a u32, usize or u64.  
     /// The argument type of the hashing function F specify the type of Kmer to generate along the sequence.  
-    fn sketch_compressedkmer<F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmer<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
         where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
         //
         log::debug!("entering sketch_compressedkmer for superminhash2");
         //
-        let comput_closure = | seqb : &Sequence, i:usize | -> (usize,Vec<Self::Sig>) {
+        let comput_closure = | seqb : &Sequence | -> Result<Vec<Self::Sig>, String> {
             //
             log::debug!(" in sketch_compressedkmer (superminhash), closure");
             let mut nb_kmer_generated : u64 = 0;
@@ -974,7 +1245,7 @@ impl <Kmer,S, H> SeqSketcherT<Kmer> for SuperHash2Sketch<Kmer, S, H>
             let mut sminhash : SuperMinHash2<Self::Sig, Kmer::Val, H>= SuperMinHash2::new(self.get_sketch_size(), self.build_hasher.clone());
 
             let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size() as u8, &seqb);
-            kmergen.set_range(0, seqb.size()).unwrap();
+            kmergen.set_range(0..seqb.size()).unwrap();
             loop {
                 match kmergen.next() {
                     Some(kmer) => {
@@ -982,7 +1253,7 @@ impl <Kmer,S, H> SeqSketcherT<Kmer> for SuperHash2Sketch<Kmer, S, H>
                         let hashval = fhash(&kmer);
                         if sminhash.sketch(&hashval).is_err() {
                             log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                            std::panic!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
+                            return Err(format!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer()));
                         }
                     },
                     None => break,
@@ -993,28 +1264,18 @@ impl <Kmer,S, H> SeqSketcherT<Kmer> for SuperHash2Sketch<Kmer, S, H>
             }  // end loop 
             let sigb = sminhash.get_hsketch();
             // get back from usize to Kmer32bit ?. If fhash is inversible possible, else NO.
-            return (i,sigb.clone());
+            Ok(sigb.clone())
         };
         //
-        let sig_with_rank : Vec::<(usize,Vec<Self::Sig>)> = (0..vseq.len()).into_par_iter().map(|i| comput_closure(vseq[i],i)).collect();
-        // re-order from jac_with_rank to jaccard_vec as the order of return can be random!!
-        let mut jaccard_vec = Vec::<Vec<Self::Sig>>::with_capacity(vseq.len());
-        for _ in 0..vseq.len() {
-            jaccard_vec.push(Vec::new());
-        }
-        // CAVEAT , boxing would avoid the clone?
-        for i in 0..sig_with_rank.len() {
-            let slot = sig_with_rank[i].0;
-            jaccard_vec[slot] = sig_with_rank[i].1.clone();
-        }
-        jaccard_vec
+        // signatures are collected in the order of vseq
+        try_sketch_each(vseq, comput_closure)
     } // end of sketch_compressedkmer
 
 
 
 
     #[cfg(feature="sminhash2")]
-    fn sketch_compressedkmer_seqs<F>(&self, vseq : &Vec<&Sequence>, fhash : F) -> Vec<Vec<Self::Sig> >
+    fn sketch_compressedkmer_seqs<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
             where   Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
                     F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
                     Kmer::Val : num::PrimInt + Send + Sync + Debug,
@@ -1027,17 +1288,26 @@ impl <Kmer,S, H> SeqSketcherT<Kmer> for SuperHash2Sketch<Kmer, S, H>
         //
         let mut nb_kmer_generated : u64 = 0;
         // we loop on sequences and generate kmer. TODO // on sequences
+        // in singleton removal mode a first pass records kmers seen at least twice
+        let solid_filter = if self.params.get_singleton_removal() {
+            Some(build_solid_kmer_filter::<Kmer, _>(vseq, self.get_kmer_size(), &fhash))
+        } else { None };
         for seq in vseq {
             let mut kmergen = KmerSeqIterator::<Kmer>::new(self.get_kmer_size() as u8, &seq);
-            kmergen.set_range(0, seq.size()).unwrap();
+            kmergen.set_range(0..seq.size()).unwrap();
             loop {
                 match kmergen.next() {
                     Some(kmer) => {
                         nb_kmer_generated += 1;
                         let hashval = fhash(&kmer);
+                        if let Some(filter) = &solid_filter {
+                            if !filter.is_solid(&hashval) {
+                                continue;
+                            }
+                        }
                         if setsketch.sketch(&hashval).is_err() {
                             log::error!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
-                            std::panic!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer());
+                            return Err(format!("could not hash kmer : {:?}", kmer.get_uncompressed_kmer()));
                         }
                     },
                     None => break,
@@ -1052,7 +1322,7 @@ impl <Kmer,S, H> SeqSketcherT<Kmer> for SuperHash2Sketch<Kmer, S, H>
         let sig = setsketch.get_hsketch();
         v.push(sig.clone());
         //
-        return v;
+        Ok(v)
     } // end of sketch_compressedkmer_seqs
 
 
@@ -1117,7 +1387,7 @@ use crate::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
         // first we sketch with OptDensHashSketch<f64>
         log::info!("calling sketch_compressedkmeraa for OptDensHashSketch::<Kmer32bit, f64>");
         let sketcher_f64 = OptDensHashSketch::<Kmer32bit, f64>::new(&sketch_args);
-        let signatures = sketcher_f64.sketch_compressedkmer(&vseq, kmer_hash_fn); 
+        let signatures = sketcher_f64.sketch_compressedkmer(&vseq, kmer_hash_fn).unwrap(); 
         // get distance between the 2 strings  
         let sig1 = &signatures[0];
         let sig2 = &signatures[1];
@@ -1129,7 +1399,7 @@ use crate::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
         //
         // now we sketch with OptDensHashSketch<f32>
         let sketcher_f32 = OptDensHashSketch::<Kmer32bit, f32>::new(&sketch_args);
-        let signatures = sketcher_f32.sketch_compressedkmer(&vseq, kmer_hash_fn); 
+        let signatures = sketcher_f32.sketch_compressedkmer(&vseq, kmer_hash_fn).unwrap(); 
         // get distance between the 2 strings  
         let sig1 = &signatures[0];
         let sig2 = &signatures[1];
@@ -1171,7 +1441,7 @@ use crate::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
         // first we sketch with OptDensHashSketch<f64>
         log::info!("calling sketch_compressedkmeraa for RevOptDensHashSketch::<Kmer32bit, f64>");
         let sketcher_f64 = RevOptDensHashSketch::<Kmer32bit, f64>::new(&sketch_args);
-        let signatures = sketcher_f64.sketch_compressedkmer(&vseq, kmer_hash_fn); 
+        let signatures = sketcher_f64.sketch_compressedkmer(&vseq, kmer_hash_fn).unwrap(); 
         // get distance between the 2 strings  
         let sig1 = &signatures[0];
         let sig2 = &signatures[1];
@@ -1183,7 +1453,7 @@ use crate::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
         //
         // now we sketch with OptDensHashSketch<f32>
         let sketcher_f32 = RevOptDensHashSketch::<Kmer32bit, f32>::new(&sketch_args);
-        let signatures = sketcher_f32.sketch_compressedkmer(&vseq, kmer_hash_fn); 
+        let signatures = sketcher_f32.sketch_compressedkmer(&vseq, kmer_hash_fn).unwrap(); 
         // get distance between the 2 strings  
         let sig1 = &signatures[0];
         let sig2 = &signatures[1];
@@ -1196,5 +1466,263 @@ use crate::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
 
 
 
+    #[test]
+    fn test_sketch_count_table() {
+        log_init_test();
+        //
+        let str1 = "ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGC";
+        let seq1 = ascii_to_seq(str1).unwrap();
+        let vseq = vec![&seq1, &seq1];
+        let kmer_size = 7;
+        let sketch_args = SeqSketcherParams::new(kmer_size, 100, SketchAlgo::PROB3A, DataType::DNA);
+        let kmer_hash_fn = | kmer : &Kmer32bit | -> <Kmer32bit as CompressedKmerT>::Val {
+            let mask : <Kmer32bit as CompressedKmerT>::Val = num::NumCast::from::<u64>((0b1 << 2*kmer.get_nb_base()) - 1).unwrap();
+            kmer.get_compressed_value() & mask
+        };
+        let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&sketch_args);
+        let sig_seqs = sketcher.sketch_compressedkmer_seqs(&vseq, kmer_hash_fn).unwrap();
+        // sketching the count table must give the same signature as sketching sequences
+        let table = KmerCountTable::<Kmer32bit>::from_sequences(&vseq, kmer_size, false);
+        let sig_table = sketcher.sketch_count_table(&table, kmer_hash_fn).unwrap();
+        assert_eq!(sig_seqs[0], sig_table);
+        // kmer size mismatch must be detected
+        let table = KmerCountTable::<Kmer32bit>::from_sequences(&vseq, kmer_size - 1, false);
+        assert!(sketcher.sketch_count_table(&table, kmer_hash_fn).is_err());
+    } // end of test_sketch_count_table
+
+
+    #[test]
+    fn test_prob3a_abundance_filter() {
+        log_init_test();
+        // the second half repeats a part of the first one
+        let str1 = "ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGCATCATGCCCCTTTAGAAAATTTCCGG";
+        let seq1 = ascii_to_seq(str1).unwrap();
+        let vseq = vec![&seq1];
+        let kmer_size = 6;
+        let mut sketch_args = SeqSketcherParams::new(kmer_size, 50, SketchAlgo::PROB3A, DataType::DNA);
+        assert!(!sketch_args.has_abundance_filter());
+        sketch_args.set_abundance_filter(2, u32::MAX);
+        let kmer_hash_fn = | kmer : &Kmer32bit | -> <Kmer32bit as CompressedKmerT>::Val {
+            let mask : <Kmer32bit as CompressedKmerT>::Val = num::NumCast::from::<u64>((0b1 << 2*kmer.get_nb_base()) - 1).unwrap();
+            kmer.get_compressed_value() & mask
+        };
+        let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&sketch_args);
+        let sigs = sketcher.sketch_compressedkmer(&vseq, kmer_hash_fn).unwrap();
+        // all kmers retained in signature must have been seen at least twice
+        let table = KmerCountTable::<Kmer32bit>::from_sequences(&vseq, kmer_size, false);
+        let repeated : Vec<u32> = table.iter().filter(|(_, c)| **c >= 2).map(|(k, _)| kmer_hash_fn(k)).collect();
+        assert!(!repeated.is_empty());
+        for v in &sigs[0] {
+            assert!(repeated.contains(v));
+        }
+        // check parameters dumped before abundance filter still reload
+        let old_json = r#"{"kmer_size":6,"sketch_size":50,"algo":"PROB3A","data_t":"DNA"}"#;
+        let params : SeqSketcherParams = serde_json::from_str(old_json).unwrap();
+        assert!(!params.has_abundance_filter());
+        assert_eq!(params.get_max_count(), u32::MAX);
+    } // end of test_prob3a_abundance_filter
+
+
+    #[test]
+    fn test_singleton_removal() {
+        log_init_test();
+        // a read seen twice and a read with kmers seen once
+        let read = ascii_to_seq("ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGC").unwrap();
+        let error_read = ascii_to_seq("GGGTACCATTGACAAGTTGCAATTGCCCAGTTTAACCCGGTGAGTTAC").unwrap();
+        let kmer_size = 11;
+        let kmer_hash_fn = | kmer : &Kmer32bit | -> <Kmer32bit as CompressedKmerT>::Val {
+            let mask : <Kmer32bit as CompressedKmerT>::Val = num::NumCast::from::<u64>((0b1 << 2*kmer.get_nb_base()) - 1).unwrap();
+            kmer.get_compressed_value() & mask
+        };
+        let mut sketch_args = SeqSketcherParams::new(kmer_size, 200, SketchAlgo::REVOPTDENS, DataType::DNA);
+        let reference = RevOptDensHashSketch::<Kmer32bit, f64>::new(&sketch_args).sketch_compressedkmer_seqs(&vec![&read], kmer_hash_fn).unwrap();
+        sketch_args.set_singleton_removal(true);
+        let sketcher = RevOptDensHashSketch::<Kmer32bit, f64>::new(&sketch_args);
+        let filtered = sketcher.sketch_compressedkmer_seqs(&vec![&read, &error_read, &read], kmer_hash_fn).unwrap();
+        let equal = reference[0].iter().zip(filtered[0].iter()).filter(|(a,b)| a == b).count();
+        log::info!("singleton removal, nb equal slots : {}", equal);
+        // kmers of error read can survive only as Bloom filter false positives
+        assert!(equal as f64 >= 0.9 * reference[0].len() as f64);
+        // singleton removal in weighted sketcher is done by exact counting
+        let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&sketch_args);
+        let filtered = sketcher.sketch_compressedkmer_seqs(&vec![&read, &error_read, &read], kmer_hash_fn).unwrap();
+        let reference = ProbHash3aSketch::<Kmer32bit>::new(&sketch_args).sketch_compressedkmer_seqs(&vec![&read, &read], kmer_hash_fn).unwrap();
+        assert_eq!(filtered, reference);
+    } // end of test_singleton_removal
+
+
+    #[test]
+    fn test_prob3a_quality_weighting() {
+        log_init_test();
+        let str1 = "ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGCATCATGCCCCTTTAGAAAATTTCCGG";
+        let seq1 = ascii_to_seq(str1).unwrap();
+        let kmer_size = 8;
+        let sketch_args = SeqSketcherParams::new(kmer_size, 50, SketchAlgo::PROB3A, DataType::DNA);
+        let kmer_hash_fn = | kmer : &Kmer32bit | -> <Kmer32bit as CompressedKmerT>::Val {
+            let mask : <Kmer32bit as CompressedKmerT>::Val = num::NumCast::from::<u64>((0b1 << 2*kmer.get_nb_base()) - 1).unwrap();
+            kmer.get_compressed_value() & mask
+        };
+        let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&sketch_args);
+        let reference = sketcher.sketch_compressedkmer(&vec![&seq1], kmer_hash_fn).unwrap();
+        // uniform qualities scale all weights by the same factor, so signature is unchanged
+        let qual = vec![b'I'; str1.len()];
+        let sigs = sketcher.sketch_compressedkmer_qual(&vec![(&seq1, qual.as_slice())], kmer_hash_fn);
+        assert_eq!(sigs[0], reference[0]);
+        let sigs = sketcher.sketch_compressedkmer_qual_seqs(&vec![(&seq1, qual.as_slice()), (&seq1, qual.as_slice())], kmer_hash_fn);
+        assert_eq!(sigs.len(), 1);
+        assert_eq!(sigs[0], reference[0]);
+        // a low quality tail changes weights
+        let mut qual = vec![b'I'; str1.len()];
+        qual[60..].iter_mut().for_each(|q| *q = b'#');
+        let sigs = sketcher.sketch_compressedkmer_qual(&vec![(&seq1, qual.as_slice())], kmer_hash_fn);
+        assert_ne!(sigs[0], reference[0]);
+    } // end of test_prob3a_quality_weighting
+
+
+    #[test]
+    fn test_sketcher_params_access() {
+        log_init_test();
+        let params = SeqSketcherParams::new(12, 100, SketchAlgo::SUPER, DataType::DNA);
+        let sketcher = SuperHashSketch::<Kmer32bit, f32>::new(&params);
+        let copy = sketcher;
+        assert_eq!(copy.get_params(), &params);
+        assert!(format!("{:?}", copy).contains("kmer_size: 12"));
+        let mut other = params;
+        other.set_abundance_filter(2, 100);
+        assert_ne!(ProbHash3aSketch::<Kmer32bit>::new(&other).get_params(), sketcher.get_params());
+    } // end of test_sketcher_params_access
+
+
+    #[test]
+    fn test_sketch_views() {
+        log_init_test();
+        // sketching views must give the same signatures as sketching copies of the viewed bases
+        let seq = ascii_to_seq("ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGCATCATGCCCCTTTAGAAAATTTCCGGATC").unwrap();
+        let views = vec![seq.view(0..60).unwrap(), seq.view(30..).unwrap(), seq.view(10..40).unwrap()];
+        let copies : Vec<Sequence> = views.iter().map(|v| v.to_sequence()).collect();
+        let vcopies : Vec<&Sequence> = copies.iter().collect();
+        let fhash = |kmer : &Kmer32bit| kmer.get_compressed_value();
+        //
+        let params = SeqSketcherParams::new(7, 100, SketchAlgo::PROB3A, DataType::DNA);
+        let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&params);
+        assert_eq!(sketcher.sketch_compressedkmer_views(&views, fhash).unwrap(), sketcher.sketch_compressedkmer(&vcopies, fhash).unwrap());
+        let sketcher = SuperHashSketch::<Kmer32bit, f32>::new(&params);
+        assert_eq!(sketcher.sketch_compressedkmer_views(&views, fhash).unwrap(), sketcher.sketch_compressedkmer(&vcopies, fhash).unwrap());
+        let sketcher = RevOptDensHashSketch::<Kmer32bit, f32>::new(&params);
+        assert_eq!(sketcher.sketch_compressedkmer_views(&views, fhash).unwrap(), sketcher.sketch_compressedkmer(&vcopies, fhash).unwrap());
+        // default implementation
+        #[cfg(feature="sminhash2")]
+        {
+            let sketcher = SuperHash2Sketch::<Kmer32bit, u64, fnv::FnvHasher>::new(&params, BuildHasherDefault::<fnv::FnvHasher>::default());
+            assert_eq!(sketcher.sketch_compressedkmer_views(&views, fhash).unwrap(), sketcher.sketch_compressedkmer(&vcopies, fhash).unwrap());
+        }
+    } // end of test_sketch_views
+
+
+    #[test]
+    fn test_sketch_chunked() {
+        log_init_test();
+        // merging sketch states of overlapping chunks must give the signature of the whole sequence
+        let seq = ascii_to_seq("ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGCATCATGCCCCTTTAGAAAATTTCCGGATC").unwrap();
+        let fhash = |kmer : &Kmer32bit| kmer.get_compressed_value();
+        let params = SeqSketcherParams::new(7, 100, SketchAlgo::PROB3A, DataType::DNA);
+        //
+        let ranges = get_chunk_ranges(seq.size(), 7, 20).unwrap();
+        assert_eq!(ranges.first().unwrap().start, 0);
+        assert_eq!(ranges.last().unwrap().end, seq.size());
+        assert!(ranges.windows(2).all(|w| w[0].end == w[1].start + 6));
+        assert!(get_chunk_ranges(seq.size(), 7, 0).is_err());
+        assert!(get_chunk_ranges(5, 7, 10).unwrap().is_empty());
+        //
+        for chunk_size in [1, 10, 33, 1000] {
+            let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&params);
+            assert_eq!(sketcher.sketch_compressedkmer_chunked(&seq, chunk_size, fhash).unwrap(), sketcher.sketch_compressedkmer(&vec![&seq], fhash).unwrap());
+            let sketcher = SuperHashSketch::<Kmer32bit, f32>::new(&params);
+            assert_eq!(sketcher.sketch_compressedkmer_chunked(&seq, chunk_size, fhash).unwrap(), sketcher.sketch_compressedkmer(&vec![&seq], fhash).unwrap());
+            let sketcher = HyperLogLogSketch::<Kmer32bit, u16>::new(&params, SetSketchParams::default(), HllSeqsThreading::default());
+            assert_eq!(sketcher.sketch_compressedkmer_chunked(&seq, chunk_size, fhash).unwrap(), sketcher.sketch_compressedkmer(&vec![&seq], fhash).unwrap());
+            // default implementation
+            let sketcher = RevOptDensHashSketch::<Kmer32bit, f32>::new(&params);
+            assert_eq!(sketcher.sketch_compressedkmer_chunked(&seq, chunk_size, fhash).unwrap(), sketcher.sketch_compressedkmer(&vec![&seq], fhash).unwrap());
+        }
+    } // end of test_sketch_chunked
+
+
+    #[test]
+    fn test_sketch_order() {
+        log_init_test();
+        use rand::{Rng, SeedableRng};
+        // long and short sequences alternate so that threads complete them out of order,
+        // signatures must nevertheless be in the order of sequences
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(11);
+        let seqs : Vec<Sequence> = (0..16).map(|i| {
+                let len = if i % 2 == 0 { 3000 } else { 50 };
+                let bases : Vec<u8> = (0..len).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect();
+                Sequence::new(&bases, 2)
+            }).collect();
+        let vseq : Vec<&Sequence> = seqs.iter().collect();
+        let views : Vec<SequenceView> = seqs.iter().map(|s| s.view(..).unwrap()).collect();
+        let fhash = |kmer : &Kmer32bit| probminhash::invhash::int32_hash(kmer.get_compressed_value());
+        let params = SeqSketcherParams::new(11, 64, SketchAlgo::PROB3A, DataType::DNA);
+        //
+        let check = |sketcher : &dyn Fn(&Vec<&Sequence>) -> Vec<Vec<u64>>, views_sketcher : &dyn Fn(&[SequenceView]) -> Vec<Vec<u64>>| {
+            let alone : Vec<Vec<u64>> = vseq.iter().map(|s| sketcher(&vec![*s]).pop().unwrap()).collect();
+            assert_eq!(sketcher(&vseq), alone);
+            assert_eq!(views_sketcher(&views), alone);
+        };
+        let run = || {
+            let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&params);
+            let to_u64 = |sigs : Vec<Vec<u32>>| -> Vec<Vec<u64>> { sigs.into_iter().map(|s| s.into_iter().map(|v| v as u64).collect()).collect() };
+            check(&|v| to_u64(sketcher.sketch_compressedkmer(v, fhash).unwrap()), &|v| to_u64(sketcher.sketch_compressedkmer_views(v, fhash).unwrap()));
+            let to_u64 = |sigs : Vec<Vec<f32>>| -> Vec<Vec<u64>> { sigs.into_iter().map(|s| s.into_iter().map(|v| v.to_bits() as u64).collect()).collect() };
+            let sketcher = SuperHashSketch::<Kmer32bit, f32>::new(&params);
+            check(&|v| to_u64(sketcher.sketch_compressedkmer(v, fhash).unwrap()), &|v| to_u64(sketcher.sketch_compressedkmer_views(v, fhash).unwrap()));
+            let sketcher = OptDensHashSketch::<Kmer32bit, f32>::new(&params);
+            check(&|v| to_u64(sketcher.sketch_compressedkmer(v, fhash).unwrap()), &|v| to_u64(sketcher.sketch_compressedkmer_views(v, fhash).unwrap()));
+            let sketcher = RevOptDensHashSketch::<Kmer32bit, f32>::new(&params);
+            check(&|v| to_u64(sketcher.sketch_compressedkmer(v, fhash).unwrap()), &|v| to_u64(sketcher.sketch_compressedkmer_views(v, fhash).unwrap()));
+            let to_u64 = |sigs : Vec<Vec<u16>>| -> Vec<Vec<u64>> { sigs.into_iter().map(|s| s.into_iter().map(|v| v as u64).collect()).collect() };
+            let sketcher = HyperLogLogSketch::<Kmer32bit, u16>::new(&params, SetSketchParams::default(), HllSeqsThreading::default());
+            check(&|v| to_u64(sketcher.sketch_compressedkmer(v, fhash).unwrap()), &|v| to_u64(sketcher.sketch_compressedkmer_views(v, fhash).unwrap()));
+            #[cfg(feature="sminhash2")]
+            {
+                let sketcher = SuperHash2Sketch::<Kmer32bit, u64, fnv::FnvHasher>::new(&params, BuildHasherDefault::<fnv::FnvHasher>::default());
+                check(&|v| sketcher.sketch_compressedkmer(v, fhash).unwrap(), &|v| sketcher.sketch_compressedkmer_views(v, fhash).unwrap());
+            }
+        };
+        #[cfg(feature="parallel")]
+        rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap().install(run);
+        #[cfg(not(feature="parallel"))]
+        run();
+    } // end of test_sketch_order
+
+
+    #[test]
+    fn test_canonical_sketcher() {
+        log_init_test();
+        // canonical kmers generated by the wrapper must give the signatures of the per kmer canonical hash
+        let raw : Vec<&[u8]> = vec![b"ATCATGCCCCTTTAGAAAATTTCCGGATCATCGTACGGAGCATGCGTACAACGTCGATGC", b"ggatcNNATTTCCGGATCATCATGCCCCTTTAGAAAATTTCCGGATC"];
+        let seqs : Vec<Sequence> = raw.iter().flat_map(|r| split_acgt_runs(r, 7)).collect();
+        let vseq : Vec<&Sequence> = seqs.iter().collect();
+        let forward = |kmer : &Kmer32bit| probminhash::invhash::int32_hash(kmer.get_compressed_value());
+        let canonical = |kmer : &Kmer32bit| forward(&kmer.reverse_complement().min(*kmer));
+        let params = SeqSketcherParams::new(7, 64, SketchAlgo::PROB3A, DataType::DNA);
+        //
+        let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&params);
+        let wrapped = CanonicalSketcher::new(sketcher);
+        assert_eq!(wrapped.sketch_compressedkmer(&vseq, forward).unwrap(), sketcher.sketch_compressedkmer(&vseq, canonical).unwrap());
+        assert_eq!(wrapped.sketch_compressedkmer_seqs(&vseq, forward).unwrap(), sketcher.sketch_compressedkmer_seqs(&vseq, canonical).unwrap());
+        assert_eq!(wrapped.sketch_compressedkmer_bytes(&raw, forward).unwrap(), sketcher.sketch_compressedkmer_bytes(&raw, canonical).unwrap());
+        assert_ne!(wrapped.sketch_compressedkmer(&vseq, forward).unwrap(), sketcher.sketch_compressedkmer(&vseq, forward).unwrap());
+        let sketcher = OptDensHashSketch::<Kmer32bit, f32>::new(&params);
+        let wrapped = CanonicalSketcher::new(sketcher);
+        assert_eq!(wrapped.sketch_compressedkmer_bytes_seqs(&raw, forward).unwrap(), sketcher.sketch_compressedkmer_bytes_seqs(&raw, canonical).unwrap());
+        // sketcher without canonical kmer generation
+        let sketcher = HyperLogLogSketch::<Kmer32bit, u16>::new(&params, SetSketchParams::default(), HllSeqsThreading::default());
+        let wrapped = CanonicalSketcher::new(sketcher.clone());
+        assert_eq!(wrapped.sketch_compressedkmer(&vseq, forward).unwrap(), sketcher.sketch_compressedkmer(&vseq, canonical).unwrap());
+        assert_eq!(wrapped.sketch_compressedkmer_bytes_seqs(&raw, forward).unwrap(), sketcher.sketch_compressedkmer_bytes_seqs(&raw, canonical).unwrap());
+    } // end of test_canonical_sketcher
+
 
 } // end of mod test
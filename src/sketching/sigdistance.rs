@@ -0,0 +1,331 @@
+//! This module defines the trait [SignatureDistance] giving Jaccard index, containment and distance between two signatures
+//! of the same representation, so that code comparing signatures (matrices, nearest neighbours ...) can be generic over sketch types.
+//!
+//! Implementations :
+//! - slot signatures Vec\<u16\>, Vec\<u32\>, Vec\<u64\> (ProbMinHash, SuperMinHash2 ...) and Vec\<f32\>, Vec\<f64\> (SuperMinHash, OptDens, RevOptDens).
+//!   Jaccard is the fraction of equal slots. Without cardinalities, containment supposes sets of equal size. Wrap them in
+//!   a [SizedSignature] to give cardinalities.
+//! - [BbitSignature] : slot signatures of u64 hashes reduced to their b lowest bits, Jaccard being corrected for random collisions.
+//! - [HllSignature] : SetSketch (HyperLogLog like) registers with their parameters, cardinalities being estimated from registers.
+//! - Vec\<HashCount\> : bottom-k sketches as returned by [MinHashCount::get_sketchcount](super::minhash::MinHashCount::get_sketchcount).
+//!
+//! For sets A and B with Jaccard index J, |A ∩ B| = J (|A| + |B|) / (1 + J), from which containments are deduced
+//! (see [containment_from_jaccard](super::containment::containment_from_jaccard)).
+//!
+//! Distance matrices and nearest neighbours of any of these signatures are given by
+//! [compute_signature_distance_matrix](super::distances::compute_signature_distance_matrix) and
+//! [knn_signatures](super::blockcompare::knn_signatures).
+
+
+use std::hash::Hash;
+
+use num::{Bounded, FromPrimitive, Integer, ToPrimitive};
+use probminhash::setsketcher::{MleJaccard, SetSketchParams};
+
+use crate::hashed::{HashCount, SketchHashT};
+use super::minhash::minhash_distance;
+use super::containment::containment_from_jaccard;
+use super::blockcompare::count_equal;
+use super::bbitsig::count_diff_slots;
+
+
+/// distances between two signatures of the same type, obtained with the same sketching parameters.
+pub trait SignatureDistance {
+    /// estimate of Jaccard index |A ∩ B| / |A ∪ B|
+    fn jaccard(&self, other : &Self) -> f64;
+    /// estimate of containment of self in other |A ∩ B| / |A|
+    fn containment(&self, other : &Self) -> f64;
+    /// number of slots (registers, hashes) of the signature, on which the precision of the Jaccard estimate depends
+    fn get_sketch_size(&self) -> usize;
+    /// distance, 1 - Jaccard index by default
+    fn distance(&self, other : &Self) -> f64 {
+        1. - self.jaccard(other)
+    }
+} // end of trait SignatureDistance
+
+
+// fraction of equal slots
+fn equal_slots_fraction<T : PartialEq>(siga : &[T], sigb : &[T]) -> f64 {
+    assert_eq!(siga.len(), sigb.len(), "signatures must have same size");
+    if siga.is_empty() {
+        return 0.;
+    }
+    count_equal(siga, sigb) as f64 / siga.len() as f64
+}
+
+
+macro_rules! impl_slot_distance {
+    ($t:ty) => {
+        impl SignatureDistance for Vec<$t> {
+            fn jaccard(&self, other : &Self) -> f64 {
+                equal_slots_fraction(self, other)
+            }
+            fn containment(&self, other : &Self) -> f64 {
+                containment_from_jaccard(self.jaccard(other), 1, 1)
+            }
+            fn get_sketch_size(&self) -> usize {
+                self.len()
+            }
+        }
+    };
+}
+
+impl_slot_distance!(u16);
+impl_slot_distance!(u32);
+impl_slot_distance!(u64);
+impl_slot_distance!(f32);
+impl_slot_distance!(f64);
+
+
+
+/// A signature with the cardinality (number of distinct kmers) of the sketched set, so that containment is estimated
+/// for sets of different sizes.
+#[derive(Clone, Debug)]
+pub struct SizedSignature<S> {
+    /// the signature of the set
+    pub signature : S,
+    /// number of distinct kmers of the set, exact or estimated (see [SketchStats](super::sketchstats::SketchStats)), rounded to an integer to estimate containment
+    pub cardinal : f64,
+}
+
+impl <S> SizedSignature<S> {
+    /// associates a signature with the cardinality of its set
+    pub fn new(signature : S, cardinal : f64) -> Self {
+        SizedSignature{signature, cardinal}
+    }
+}
+
+impl <S : SignatureDistance> SignatureDistance for SizedSignature<S> {
+    fn jaccard(&self, other : &Self) -> f64 {
+        self.signature.jaccard(&other.signature)
+    }
+    fn containment(&self, other : &Self) -> f64 {
+        containment_from_jaccard(self.jaccard(other), self.cardinal.round() as u64, other.cardinal.round() as u64)
+    }
+    fn get_sketch_size(&self) -> usize {
+        self.signature.get_sketch_size()
+    }
+}
+
+
+
+/// b-bit signature : the b lowest bits of each slot of a u64 signature (ProbMinHash), packed in u64 words.
+/// Memory is divided by 64/b at the cost of random collisions, 2 slots being equal by chance with probability 2^-b.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BbitSignature {
+    words : Vec<u64>,
+    nb_bits : u8,
+    size : usize,
+}
+
+impl BbitSignature {
+    /// keeps nb_bits bits (1 <= nb_bits <= 32) of each slot
+    pub fn from_slots(slots : &[u64], nb_bits : u8) -> Result<Self, String> {
+        if nb_bits == 0 || nb_bits > 32 || 64 % nb_bits as u32 != 0 {
+            log::error!("BbitSignature : nb_bits {} must be 1, 2, 4, 8, 16 or 32", nb_bits);
+            return Err("BbitSignature : nb_bits must divide 64 and be <= 32".to_string());
+        }
+        let by_word = 64 / nb_bits as usize;
+        let mask = (1u64 << nb_bits) - 1;
+        let mut words = vec![0u64; slots.len().div_ceil(by_word)];
+        for (i, slot) in slots.iter().enumerate() {
+            words[i / by_word] |= (slot & mask) << ((i % by_word) * nb_bits as usize);
+        }
+        Ok(BbitSignature{words, nb_bits, size : slots.len()})
+    } // end of from_slots
+
+    /// returns number of bits kept by slot
+    pub fn get_nb_bits(&self) -> u8 {
+        self.nb_bits
+    }
+
+    /// returns number of slots
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// true if signature has no slot
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// returns value of slot i
+    pub fn get_slot(&self, i : usize) -> u64 {
+        let by_word = 64 / self.nb_bits as usize;
+        let mask = (1u64 << self.nb_bits) - 1;
+        (self.words[i / by_word] >> ((i % by_word) * self.nb_bits as usize)) & mask
+    }
+
+    /// returns the number of equal slots, counted on packed words by XOR and popcount.
+    /// Signatures must have the same number of slots and bits.
+    pub fn count_equal(&self, other : &BbitSignature) -> Result<usize, String> {
+        if self.nb_bits != other.nb_bits || self.size != other.size {
+            log::error!("BbitSignature::count_equal : signatures differ in number of bits or slots");
+            return Err("b-bit signatures differ in number of bits or slots".to_string());
+        }
+        Ok(self.size - count_diff_slots(&self.words, &other.words, self.nb_bits as u32))
+    } // end of count_equal
+} // end of impl BbitSignature
+
+
+impl SignatureDistance for BbitSignature {
+    /// fraction of equal slots corrected for collisions : (P - 2^-b) / (1 - 2^-b)
+    fn jaccard(&self, other : &Self) -> f64 {
+        let equal = self.count_equal(other).expect("b-bit signatures must have same nb bits and size");
+        if self.size == 0 {
+            return 0.;
+        }
+        let collision = 1. / (1u64 << self.nb_bits) as f64;
+        ((equal as f64 / self.size as f64 - collision) / (1. - collision)).max(0.)
+    }
+    fn containment(&self, other : &Self) -> f64 {
+        containment_from_jaccard(self.jaccard(other), 1, 1)
+    }
+    fn get_sketch_size(&self) -> usize {
+        self.size
+    }
+}
+
+
+
+/// SetSketch (HyperLogLog like) registers with the parameters used to sketch them.
+#[derive(Clone, Debug)]
+pub struct HllSignature<I> {
+    /// registers, as returned by probminhash::setsketcher::SetSketcher::get_signature
+    pub registers : Vec<I>,
+    /// parameters of the SetSketcher, needed to estimate Jaccard index and cardinalities from registers
+    pub params : SetSketchParams,
+}
+
+impl <I> HllSignature<I>
+        where I : Integer + Bounded + ToPrimitive + FromPrimitive + Copy + Send + Sync {
+    /// associates registers with the parameters they were sketched with
+    pub fn new(registers : Vec<I>, params : SetSketchParams) -> Self {
+        HllSignature{registers, params}
+    }
+
+    /// estimate of the number of distinct items sketched
+    pub fn get_cardinal(&self) -> f64 {
+        MleJaccard::from(self.params).get_cardinal_estimate(&self.registers)
+    }
+} // end of impl HllSignature
+
+
+impl <I> SignatureDistance for HllSignature<I>
+        where I : Integer + Bounded + ToPrimitive + FromPrimitive + Copy + Send + Sync {
+    /// fraction of equal registers, corrected by the middle of SetSketchParams::get_jaccard_bounds
+    fn jaccard(&self, other : &Self) -> f64 {
+        let equal = equal_slots_fraction(&self.registers, &other.registers);
+        let (low, up) = self.params.get_jaccard_bounds(equal);
+        ((low + up) / 2.).min(1.)
+    }
+    fn containment(&self, other : &Self) -> f64 {
+        containment_from_jaccard(self.jaccard(other), self.get_cardinal().round() as u64, other.get_cardinal().round() as u64)
+    }
+    fn get_sketch_size(&self) -> usize {
+        self.registers.len()
+    }
+}
+
+
+
+/// bottom-k sketches, see [minhash_distance]
+impl <T : Hash + Clone + Copy, Hv : SketchHashT> SignatureDistance for Vec<HashCount<T, Hv>> {
+    fn jaccard(&self, other : &Self) -> f64 {
+        minhash_distance(self, other).jaccard
+    }
+    fn containment(&self, other : &Self) -> f64 {
+        minhash_distance(self, other).containment_a_in_b
+    }
+    fn get_sketch_size(&self) -> usize {
+        self.len()
+    }
+}
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::hash::BuildHasherDefault;
+    use fnv::FnvHasher;
+    use probminhash::setsketcher::SetSketcher;
+    use rand::{Rng, SeedableRng};
+    use crate::sketching::minhash::MinHashCount;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    // generic user of the trait
+    fn all_distances<S : SignatureDistance>(a : &S, b : &S) -> (f64, f64, f64) {
+        (a.jaccard(b), a.containment(b), a.distance(b))
+    }
+
+    #[test]
+    fn test_signature_distance() {
+        log_init_test();
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(3106);
+        // slot signatures sharing 3/4 of slots
+        let siga : Vec<u64> = (0..1024).map(|_| rng.gen()).collect();
+        let sigb : Vec<u64> = siga.iter().enumerate().map(|(i, v)| if i % 4 == 0 { rng.gen() } else { *v }).collect();
+        let (jaccard, containment, distance) = all_distances(&siga, &sigb);
+        assert_eq!((jaccard, distance), (0.75, 0.25));
+        assert!((containment - 1.5 / 1.75).abs() < 1.0e-10);
+        let sized_a = SizedSignature::new(siga.clone(), 300.);
+        // jaccard 3/4 with |B| = 4/3 |A| : A is in B
+        let sized_b = SizedSignature::new(sigb.clone(), 400.);
+        assert!((sized_a.containment(&sized_b) - 1.).abs() < 1.0e-10);
+        assert!((sized_b.containment(&sized_a) - 0.75).abs() < 1.0e-10);
+        let sigf : Vec<f32> = vec![0.5, 0.25, 0.1, 0.2];
+        assert_eq!(sigf.jaccard(&vec![0.5, 0.25, 0.3, 0.4]), 0.5);
+        // b-bit signatures : 8 bits, jaccard corrected for collisions
+        let bbita = BbitSignature::from_slots(&siga, 8).unwrap();
+        let bbitb = BbitSignature::from_slots(&sigb, 8).unwrap();
+        assert_eq!(bbita.get_slot(5), siga[5] & 0xff);
+        assert!((bbita.jaccard(&bbitb) - 0.75).abs() < 0.02);
+        assert_eq!(bbita.jaccard(&bbita), 1.);
+        assert!(BbitSignature::from_slots(&siga, 3).is_err());
+        // packed count for all sizes, number of slots not multiple of slots by word
+        for nb_bits in [1u8, 2, 4, 8, 16, 32] {
+            let (ba, bb) = (BbitSignature::from_slots(&siga[..1001], nb_bits).unwrap(), BbitSignature::from_slots(&sigb[..1001], nb_bits).unwrap());
+            let mask = (1u64 << nb_bits) - 1;
+            let expected = siga[..1001].iter().zip(&sigb[..1001]).filter(|(a, b)| *a & mask == *b & mask).count();
+            assert_eq!(ba.count_equal(&bb).unwrap(), expected, "nb_bits {}", nb_bits);
+            assert_eq!(ba.count_equal(&ba).unwrap(), 1001);
+        }
+        assert!(bbita.count_equal(&BbitSignature::from_slots(&sigb, 4).unwrap()).is_err());
+        assert!(bbita.count_equal(&BbitSignature::from_slots(&sigb[1..], 8).unwrap()).is_err());
+        // bottom-k sketches of [0..1000] and [0..3000]
+        let mut minhash_a : MinHashCount<usize, FnvHasher> = MinHashCount::new(200, false);
+        minhash_a.sketch_slice(&(0..1000).collect::<Vec<usize>>());
+        let mut minhash_b : MinHashCount<usize, FnvHasher> = MinHashCount::new(200, false);
+        minhash_b.sketch_slice(&(0..3000).collect::<Vec<usize>>());
+        let (jaccard, containment, _) = all_distances(&minhash_a.get_sketchcount(), &minhash_b.get_sketchcount());
+        assert!((jaccard - 1. / 3.).abs() < 0.1);
+        assert_eq!(containment, 1.);
+    } // end of test_signature_distance
+
+    #[test]
+    fn test_hll_signature_distance() {
+        log_init_test();
+        // [0..50000] and [25000..75000] : jaccard 1/3, containment 1/2
+        let params = SetSketchParams::default();
+        let mut sketcher_a = SetSketcher::<u16, usize, FnvHasher>::new(params, BuildHasherDefault::<FnvHasher>::default());
+        sketcher_a.sketch_slice(&(0..50000).collect::<Vec<usize>>()).unwrap();
+        let mut sketcher_b = SetSketcher::<u16, usize, FnvHasher>::new(params, BuildHasherDefault::<FnvHasher>::default());
+        sketcher_b.sketch_slice(&(25000..75000).collect::<Vec<usize>>()).unwrap();
+        let hlla = HllSignature::new(sketcher_a.get_signature().clone(), params);
+        let hllb = HllSignature::new(sketcher_b.get_signature().clone(), params);
+        assert!((hlla.get_cardinal() - 50000.).abs() < 5000.);
+        let (jaccard, containment, _) = all_distances(&hlla, &hllb);
+        log::info!("hll jaccard : {:.3e}, containment : {:.3e}", jaccard, containment);
+        assert!((jaccard - 1. / 3.).abs() < 0.05);
+        assert!((containment - 0.5).abs() < 0.05);
+    } // end of test_hll_signature_distance
+
+} // end of mod tests
@@ -0,0 +1,621 @@
+//! This module provides a versioned binary container for sketches.
+//!
+//! A container file stores the sketching parameters, per sequence metadata (name and length) and the signature matrix.
+//! It is memory mapped at reload so that opening a database of millions of sketches costs O(1),
+//! signatures and names being decoded only when accessed.
+//!
+//! Layout (all integers little endian) :
+//! - magic : u32 [MAGIC_SKETCH_CONTAINER], version : u32, header length : u64,
+//! - header : json encoded [SketchContainerHeader], padded with spaces to a multiple of 8 bytes.
+//!   From version 2 it contains a [Provenance] : crate version, creation time, xxh3 checksums of the input files
+//!   and of the data following the header, checked on demand (see [SketchContainer::open_verified]),
+//! - signature matrix : nb_sketches x sketch_size values of sig_size bytes, row by row, padded to a multiple of 8 bytes,
+//! - sequence lengths : nb_sketches u64,
+//! - name offsets : nb_sketches + 1 u64 (relative to the start of the name block), followed by the utf8 names.
+//!
+//! Dump and reload of containers need the feature *formats* (default), the header being json encoded.
+
+
+#[cfg(feature="formats")]
+use std::borrow::Cow;
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+#[cfg(feature="formats")]
+use std::io::BufWriter;
+use std::path::Path;
+
+#[cfg(all(feature="io", feature="formats"))]
+use memmap2::Mmap;
+use xxhash_rust::xxh3::Xxh3;
+#[cfg(feature="formats")]
+use xxhash_rust::xxh3::xxh3_64;
+use serde::{Deserialize, Serialize};
+
+use crate::sketcharg::SeqSketcherParams;
+
+
+/// magic number at beginning of a sketch container
+pub const MAGIC_SKETCH_CONTAINER : u32 = 0xceabeade;
+
+// without feature io (wasm targets) containers are read in memory instead of mapped
+#[cfg(all(feature="formats", not(feature="io")))]
+type Mmap = Vec<u8>;
+
+/// current version of the container format
+pub const SKETCH_CONTAINER_VERSION : u32 = 2;
+
+// size of magic, version and header length
+#[cfg(feature="formats")]
+const PREAMBLE_SIZE : usize = 16;
+
+
+/// signature values that can be stored in a container
+pub trait SketchContainerSig : Copy {
+    /// name stored in header to check type at reload
+    const NAME : &'static str;
+    /// size in bytes
+    const SIZE : usize;
+    /// writes the little endian representation
+    fn write_le(&self, out : &mut dyn Write) -> std::io::Result<()>;
+    /// decodes from a little endian slice of SIZE bytes
+    fn from_le_slice(bytes : &[u8]) -> Self;
+    /// reinterprets little endian bytes as values without copy, if their layout allows it (host endianness and alignment).
+    /// The default returns None, values are then decoded with [Self::from_le_slice].
+    fn cast_le_slice(_bytes : &[u8]) -> Option<&[Self]> where Self : Sized {
+        None
+    }
+}
+
+
+macro_rules! impl_container_sig {
+    ($t:ty, $name:expr) => {
+        impl SketchContainerSig for $t {
+            const NAME : &'static str = $name;
+            const SIZE : usize = std::mem::size_of::<$t>();
+            fn write_le(&self, out : &mut dyn Write) -> std::io::Result<()> {
+                out.write_all(&self.to_le_bytes())
+            }
+            fn from_le_slice(bytes : &[u8]) -> Self {
+                <$t>::from_le_bytes(bytes.try_into().unwrap())
+            }
+            fn cast_le_slice(bytes : &[u8]) -> Option<&[Self]> {
+                if cfg!(target_endian = "big") {
+                    return None;
+                }
+                // any bit pattern is a valid value of primitive integers and floats
+                let (prefix, values, suffix) = unsafe { bytes.align_to::<$t>() };
+                if prefix.is_empty() && suffix.is_empty() { Some(values) } else { None }
+            }
+        }
+    };
+}
+
+impl_container_sig!(u16, "u16");
+impl_container_sig!(u32, "u32");
+impl_container_sig!(u64, "u64");
+impl_container_sig!(f32, "f32");
+impl_container_sig!(f64, "f64");
+
+
+
+/// metadata associated to each sketch
+#[derive(Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct SketchMetadata {
+    /// sequence (or file) name
+    pub name : String,
+    /// sequence length in bases
+    pub seq_len : u64,
+}
+
+
+/// header of a container
+#[derive(Clone,Serialize,Deserialize,Debug)]
+pub struct SketchContainerHeader {
+    /// sketching parameters
+    pub params : SeqSketcherParams,
+    /// name of signature type (u16, u32, u64, f32, f64)
+    pub sig_type : String,
+    /// size in bytes of a signature value
+    pub sig_size : usize,
+    /// number of sketches stored
+    pub nb_sketches : usize,
+    /// provenance, absent in version 1 containers
+    #[serde(default)]
+    pub provenance : Option<Provenance>,
+}
+
+
+/// checksum of an input file
+#[derive(Clone,Serialize,Deserialize,Debug,PartialEq)]
+pub struct InputChecksum {
+    /// path of the file at sketching time
+    pub path : String,
+    /// size in bytes
+    pub size : u64,
+    /// xxh3 64 bits hash of the file content
+    pub xxh3 : u64,
+}
+
+
+impl InputChecksum {
+    /// computes the checksum of a file
+    pub fn new(path : &Path) -> Result<Self, String> {
+        let file = OpenOptions::new().read(true).open(path).map_err(|e| {
+                log::error!("InputChecksum could not open file {:?} : {}", path.as_os_str(), e);
+                e.to_string()
+            })?;
+        let mut reader = std::io::BufReader::new(file);
+        let mut hasher = Xxh3::new();
+        let mut buf = vec![0u8; 1 << 16];
+        let mut size = 0u64;
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    hasher.update(&buf[..n]);
+                    size += n as u64;
+                },
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        Ok(InputChecksum{path : path.to_string_lossy().to_string(), size, xxh3 : hasher.digest()})
+    }
+} // end of impl InputChecksum
+
+
+/// provenance of a container : who wrote it, when, from which inputs, and checksum of its data
+#[derive(Clone,Serialize,Deserialize,Debug)]
+pub struct Provenance {
+    /// version of kmerutils that wrote the container
+    pub crate_version : String,
+    /// creation time, in seconds since unix epoch
+    pub created : u64,
+    /// checksums of sketched files
+    pub inputs : Vec<InputChecksum>,
+    /// xxh3 hash of the data following the header (signatures and metadata)
+    pub data_xxh3 : u64,
+}
+
+
+#[cfg(feature="formats")]
+fn get_unix_time() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+
+// returns size rounded to a multiple of 8
+#[cfg(feature="formats")]
+fn pad8(size : usize) -> usize {
+    size.div_ceil(8) * 8
+}
+
+
+/// dumps sketches in a container file. metadata\[i\] describes signatures\[i\].
+/// Returns the number of bytes written. See [dump_sketch_container_with_inputs] to record input files in provenance.
+#[cfg(feature="formats")]
+pub fn dump_sketch_container<Sig : SketchContainerSig>(path : &Path, params : &SeqSketcherParams, metadata : &[SketchMetadata], signatures : &[Vec<Sig>]) -> Result<usize, String> {
+    dump_sketch_container_with_inputs(path, params, metadata, signatures, &[])
+}
+
+
+/// dumps sketches in a container file, recording in provenance the checksums of the sketched input files.
+/// Returns the number of bytes written.
+#[cfg(feature="formats")]
+pub fn dump_sketch_container_with_inputs<Sig : SketchContainerSig>(path : &Path, params : &SeqSketcherParams, metadata : &[SketchMetadata], signatures : &[Vec<Sig>],
+                inputs : &[&Path]) -> Result<usize, String> {
+    let inputs = inputs.iter().map(|p| InputChecksum::new(p)).collect::<Result<Vec<InputChecksum>, String>>()?;
+    dump_sketch_container_with_checksums(path, params, metadata, signatures, inputs)
+} // end of dump_sketch_container_with_inputs
+
+
+
+/// dumps sketches in a container file, recording in provenance already computed checksums of input files,
+/// for example those of the provenance of containers being merged. Returns the number of bytes written.
+#[cfg(feature="formats")]
+pub fn dump_sketch_container_with_checksums<Sig : SketchContainerSig>(path : &Path, params : &SeqSketcherParams, metadata : &[SketchMetadata], signatures : &[Vec<Sig>],
+                inputs : Vec<InputChecksum>) -> Result<usize, String> {
+    //
+    log::info!("dumping {} sketches in container {:?}", signatures.len(), path);
+    //
+    if metadata.len() != signatures.len() {
+        log::error!("dump_sketch_container : {} metadata for {} signatures", metadata.len(), signatures.len());
+        return Err("dump_sketch_container : metadata and signatures must have same length".to_string());
+    }
+    let sketch_size = params.get_sketch_size();
+    if let Some(sig) = signatures.iter().find(|s| s.len() != sketch_size) {
+        log::error!("dump_sketch_container : signature of size {} , expected {}", sig.len(), sketch_size);
+        return Err("dump_sketch_container : signature size does not match sketch size".to_string());
+    }
+    // body : signature matrix and metadata
+    let mut body = Vec::<u8>::new();
+    for sig in signatures {
+        for v in sig {
+            v.write_le(&mut body).unwrap();
+        }
+    }
+    body.resize(pad8(body.len()), 0);
+    for m in metadata {
+        body.extend_from_slice(&m.seq_len.to_le_bytes());
+    }
+    let mut offset = 0u64;
+    body.extend_from_slice(&offset.to_le_bytes());
+    for m in metadata {
+        offset += m.name.len() as u64;
+        body.extend_from_slice(&offset.to_le_bytes());
+    }
+    for m in metadata {
+        body.extend_from_slice(m.name.as_bytes());
+    }
+    //
+    let provenance = Provenance{crate_version : env!("CARGO_PKG_VERSION").to_string(), created : get_unix_time(), inputs, data_xxh3 : xxh3_64(&body)};
+    let header = SketchContainerHeader{params : *params, sig_type : Sig::NAME.to_string(), sig_size : Sig::SIZE, nb_sketches : signatures.len(),
+                provenance : Some(provenance)};
+    let mut header_str = serde_json::to_string(&header).map_err(|e| e.to_string())?;
+    // matrix must be aligned on 8 bytes
+    let header_len = pad8(PREAMBLE_SIZE + header_str.len()) - PREAMBLE_SIZE;
+    header_str.extend(std::iter::repeat_n(' ', header_len - header_str.len()));
+    //
+    let fileres = OpenOptions::new().write(true).create(true).truncate(true).open(path);
+    if fileres.is_err() {
+        log::error!("dump_sketch_container could not open file {:?}", path.as_os_str());
+        println!("dump_sketch_container could not open file {:?}", path.as_os_str());
+        return Err("dump_sketch_container could not open file".to_string());
+    }
+    let mut bufw = BufWriter::new(fileres.unwrap());
+    let write = |bufw : &mut BufWriter<std::fs::File>| -> std::io::Result<usize> {
+        bufw.write_all(&MAGIC_SKETCH_CONTAINER.to_le_bytes())?;
+        bufw.write_all(&SKETCH_CONTAINER_VERSION.to_le_bytes())?;
+        bufw.write_all(&(header_len as u64).to_le_bytes())?;
+        bufw.write_all(header_str.as_bytes())?;
+        bufw.write_all(&body)?;
+        bufw.flush()?;
+        Ok(PREAMBLE_SIZE + header_len + body.len())
+    };
+    let nb_bytes = write(&mut bufw).map_err(|e| {
+            log::error!("dump_sketch_container : write error {}", e);
+            e.to_string()
+        })?;
+    log::debug!("dump_sketch_container, nb bytes written : {}", nb_bytes);
+    Ok(nb_bytes)
+} // end of dump_sketch_container_with_checksums
+
+
+
+/// A memory mapped sketch container. Opening only decodes the header, signatures and metadata are read on access.
+#[cfg(feature="formats")]
+pub struct SketchContainer {
+    header : SketchContainerHeader,
+    version : u32,
+    mmap : Mmap,
+    // offsets in mmap of the different blocks
+    matrix_offset : usize,
+    seqlen_offset : usize,
+    name_index_offset : usize,
+    names_offset : usize,
+}
+
+
+#[cfg(feature="formats")]
+impl SketchContainer {
+    /// maps a container file and verifies the checksum of its data (if written by version >= 2).
+    /// This reads the whole file, see [Self::open] for a constant time opening.
+    pub fn open_verified(path : &Path) -> Result<Self, String> {
+        let container = Self::open(path)?;
+        container.verify()?;
+        Ok(container)
+    }
+
+    /// maps a container file, decoding only its header and checking that the blocks it describes fit in the file.
+    /// The data checksum is not verified, see [Self::verify] and [Self::open_verified].
+    pub fn open(path : &Path) -> Result<Self, String> {
+        //
+        log::info!("opening sketch container {:?}", path);
+        //
+        let fileres = OpenOptions::new().read(true).open(path);
+        if fileres.is_err() {
+            log::error!("SketchContainer could not open file {:?}", path.as_os_str());
+            println!("SketchContainer could not open file {:?}", path.as_os_str());
+            return Err("SketchContainer could not open file".to_string());
+        }
+        // the file must not be modified while mapped, containers are written once.
+        #[cfg(feature="io")]
+        let mmap = unsafe { Mmap::map(&fileres.unwrap()) }.map_err(|e| {
+                log::error!("SketchContainer could not map file {:?} : {}", path.as_os_str(), e);
+                e.to_string()
+            })?;
+        #[cfg(not(feature="io"))]
+        let mmap = {
+            let mut data = Mmap::new();
+            fileres.unwrap().read_to_end(&mut data).map_err(|e| {
+                    log::error!("SketchContainer could not read file {:?} : {}", path.as_os_str(), e);
+                    e.to_string()
+                })?;
+            data
+        };
+        if mmap.len() < PREAMBLE_SIZE || u32::from_le_bytes(mmap[0..4].try_into().unwrap()) != MAGIC_SKETCH_CONTAINER {
+            log::error!("file {:?} is not a sketch container", path.as_os_str());
+            return Err("file is not a sketch container".to_string());
+        }
+        let version = u32::from_le_bytes(mmap[4..8].try_into().unwrap());
+        if version > SKETCH_CONTAINER_VERSION {
+            log::error!("SketchContainer : unsupported container version {}", version);
+            return Err(format!("unsupported container version {}", version));
+        }
+        let header_len = u64::from_le_bytes(mmap[8..16].try_into().unwrap());
+        if header_len > (mmap.len() - PREAMBLE_SIZE) as u64 {
+            return Err("SketchContainer : truncated header".to_string());
+        }
+        let header_len = header_len as usize;
+        let header : SketchContainerHeader = serde_json::from_slice(&mmap[PREAMBLE_SIZE..PREAMBLE_SIZE + header_len]).map_err(|e| {
+                log::error!("SketchContainer : could not decode header : {}", e);
+                e.to_string()
+            })?;
+        let nb = header.nb_sketches;
+        let matrix_offset = PREAMBLE_SIZE + header_len;
+        // sizes come from the file, they are checked against the mapped length before any access
+        let offsets = header.params.get_sketch_size().checked_mul(header.sig_size)
+                .and_then(|row_size| row_size.checked_mul(nb))
+                .and_then(|matrix_size| matrix_size.checked_next_multiple_of(8))
+                .and_then(|matrix_size| matrix_offset.checked_add(matrix_size))
+                .and_then(|seqlen_offset| {
+                    let name_index_offset = seqlen_offset.checked_add(nb.checked_mul(8)?)?;
+                    let names_offset = name_index_offset.checked_add(nb.checked_add(1)?.checked_mul(8)?)?;
+                    Some((seqlen_offset, name_index_offset, names_offset))
+                });
+        let (seqlen_offset, name_index_offset, names_offset) = match offsets {
+            Some(offsets) if offsets.2 <= mmap.len() => offsets,
+            _ => {
+                log::error!("SketchContainer : file {:?} is truncated or has an inconsistent header", path.as_os_str());
+                return Err("SketchContainer : truncated file".to_string());
+            },
+        };
+        let container = SketchContainer{header, version, mmap, matrix_offset, seqlen_offset, name_index_offset, names_offset};
+        // the last name offset gives the size of the name block
+        if container.read_u64(name_index_offset, nb) > (container.mmap.len() - names_offset) as u64 {
+            log::error!("SketchContainer : file {:?} is truncated", path.as_os_str());
+            return Err("SketchContainer : truncated file".to_string());
+        }
+        log::info!("SketchContainer, version : {}, nb sketches : {}", version, nb);
+        Ok(container)
+    } // end of open
+
+    /// checks the data of the container against the checksum recorded in provenance. This reads the whole file.
+    pub fn verify(&self) -> Result<(), String> {
+        if let Some(provenance) = &self.header.provenance {
+            if xxh3_64(&self.mmap[self.matrix_offset..]) != provenance.data_xxh3 {
+                log::error!("SketchContainer : data checksum mismatch, container is corrupted");
+                return Err("SketchContainer : data checksum mismatch".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// returns provenance if container was written with version >= 2
+    pub fn get_provenance(&self) -> Option<&Provenance> {
+        self.header.provenance.as_ref()
+    }
+
+    /// returns the input files recorded in provenance that are missing or whose content changed since sketching,
+    /// meaning the sketches are stale.
+    pub fn get_stale_inputs(&self) -> Vec<String> {
+        let inputs = match &self.header.provenance {
+            Some(provenance) => &provenance.inputs,
+            None => return Vec::new(),
+        };
+        inputs.iter().filter(|input| {
+                match InputChecksum::new(Path::new(&input.path)) {
+                    Ok(current) => current != **input,
+                    Err(_) => true,
+                }
+            }).map(|input| input.path.clone()).collect()
+    }
+
+    /// returns the format version of the file
+    pub fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    /// returns the header
+    pub fn get_header(&self) -> &SketchContainerHeader {
+        &self.header
+    }
+
+    /// returns sketching parameters
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.header.params
+    }
+
+    /// returns the number of sketches
+    pub fn len(&self) -> usize {
+        self.header.nb_sketches
+    }
+
+    /// true if container has no sketch
+    pub fn is_empty(&self) -> bool {
+        self.header.nb_sketches == 0
+    }
+
+    // reads u64 at rank i of a block
+    fn read_u64(&self, block_offset : usize, i : usize) -> u64 {
+        let start = block_offset + 8 * i;
+        u64::from_le_bytes(self.mmap[start..start + 8].try_into().unwrap())
+    }
+
+    /// returns the raw little endian bytes of signature i
+    pub fn get_signature_bytes(&self, i : usize) -> Result<&[u8], String> {
+        if i >= self.len() {
+            return Err(format!("SketchContainer : sketch rank {} out of range", i));
+        }
+        // the matrix was checked to fit in the file at opening
+        let row_size = self.header.params.get_sketch_size() * self.header.sig_size;
+        let start = self.matrix_offset + i * row_size;
+        Ok(&self.mmap[start..start + row_size])
+    }
+
+    /// decodes signature i. Sig must be the type used at dump.
+    pub fn get_signature<Sig : SketchContainerSig>(&self, i : usize) -> Result<Vec<Sig>, String> {
+        if Sig::NAME != self.header.sig_type {
+            log::error!("SketchContainer : asked signature type {} , container has {}", Sig::NAME, self.header.sig_type);
+            return Err(format!("container stores {} signatures", self.header.sig_type));
+        }
+        Ok(self.get_signature_bytes(i)?.chunks_exact(Sig::SIZE).map(Sig::from_le_slice).collect())
+    }
+
+    /// returns signature i borrowed from the mapped file when the host layout allows it (see [SketchContainerSig::cast_le_slice]),
+    /// decoded otherwise. Sig must be the type used at dump.
+    pub fn get_signature_ref<Sig : SketchContainerSig>(&self, i : usize) -> Result<Cow<'_, [Sig]>, String> {
+        if Sig::NAME != self.header.sig_type {
+            log::error!("SketchContainer : asked signature type {} , container has {}", Sig::NAME, self.header.sig_type);
+            return Err(format!("container stores {} signatures", self.header.sig_type));
+        }
+        let bytes = self.get_signature_bytes(i)?;
+        match Sig::cast_le_slice(bytes) {
+            Some(values) => Ok(Cow::Borrowed(values)),
+            None => Ok(Cow::Owned(bytes.chunks_exact(Sig::SIZE).map(Sig::from_le_slice).collect())),
+        }
+    }
+
+    /// returns sequence length of sketch i
+    pub fn get_seq_len(&self, i : usize) -> u64 {
+        assert!(i < self.len());
+        self.read_u64(self.seqlen_offset, i)
+    }
+
+    /// returns name of sketch i. The name offsets are read from the file and checked at each access,
+    /// so a corrupted name index gives an error.
+    pub fn get_name(&self, i : usize) -> Result<&str, String> {
+        if i >= self.len() {
+            return Err(format!("SketchContainer : sketch rank {} out of range", i));
+        }
+        let block_size = (self.mmap.len() - self.names_offset) as u64;
+        let start = self.read_u64(self.name_index_offset, i);
+        let end = self.read_u64(self.name_index_offset, i + 1);
+        if start > end || end > block_size {
+            log::error!("SketchContainer : name of sketch {} has invalid offsets {} {}", i, start, end);
+            return Err(format!("SketchContainer : name of sketch {} has invalid offsets", i));
+        }
+        std::str::from_utf8(&self.mmap[self.names_offset + start as usize..self.names_offset + end as usize])
+                .map_err(|_| format!("SketchContainer : name of sketch {} is not utf8", i))
+    }
+
+    /// returns metadata of sketch i
+    pub fn get_metadata(&self, i : usize) -> Result<SketchMetadata, String> {
+        Ok(SketchMetadata{name : self.get_name(i)?.to_string(), seq_len : self.get_seq_len(i)})
+    }
+
+    /// returns an iterator over metadata and signatures, decoded one at a time from the mapped file.
+    /// Sig must be the type used at dump. An item is an error if its name is corrupted.
+    pub fn iter_signatures<Sig : SketchContainerSig>(&self) -> Result<impl Iterator<Item = Result<(SketchMetadata, Vec<Sig>), String>> + '_, String> {
+        if Sig::NAME != self.header.sig_type {
+            log::error!("SketchContainer : asked signature type {} , container has {}", Sig::NAME, self.header.sig_type);
+            return Err(format!("container stores {} signatures", self.header.sig_type));
+        }
+        Ok((0..self.len()).map(move |i| {
+                let sig = self.get_signature_bytes(i)?.chunks_exact(Sig::SIZE).map(Sig::from_le_slice).collect();
+                Ok((self.get_metadata(i)?, sig))
+            }))
+    }
+} // end of impl SketchContainer
+
+
+//=========================================================================================
+
+
+#[cfg(all(test, feature="formats"))]
+mod tests {
+
+    use super::*;
+    use crate::sketcharg::{SketchAlgo, DataType};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_sketch_container() {
+        log_init_test();
+        let dir = crate::test_dir("container");
+        let params = SeqSketcherParams::new(21, 5, SketchAlgo::PROB3A, DataType::DNA);
+        let signatures : Vec<Vec<u64>> = (0..3).map(|i| (0..5).map(|j| i * 100 + j).collect()).collect();
+        let metadata : Vec<SketchMetadata> = (0..3).map(|i| SketchMetadata{name : format!("seq_é{}", i), seq_len : 1000 + i}).collect();
+        let path = dir.join("kmerutils_test_container.sk");
+        let nb_bytes = dump_sketch_container(&path, &params, &metadata, &signatures).unwrap();
+        assert_eq!(nb_bytes as u64, std::fs::metadata(&path).unwrap().len());
+        //
+        let container = SketchContainer::open_verified(&path).unwrap();
+        assert_eq!(container.get_version(), SKETCH_CONTAINER_VERSION);
+        assert_eq!(container.len(), 3);
+        assert_eq!(container.get_params().get_kmer_size(), 21);
+        for i in 0..3 {
+            assert_eq!(container.get_signature::<u64>(i).unwrap(), signatures[i]);
+            assert_eq!(container.get_signature_ref::<u64>(i).unwrap().as_ref(), signatures[i].as_slice());
+            assert_eq!(container.get_metadata(i).unwrap(), metadata[i]);
+        }
+        // the signature matrix is aligned in the mapped file
+        #[cfg(all(feature="io", target_endian="little"))]
+        assert!(matches!(container.get_signature_ref::<u64>(1).unwrap(), Cow::Borrowed(_)));
+        assert!(container.get_signature::<f32>(0).is_err());
+        assert!(container.get_signature_ref::<f32>(0).is_err());
+        // bad signature size
+        assert!(dump_sketch_container(&path, &params, &metadata[0..1], &[vec![1u32; 4]]).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_sketch_container
+
+
+    #[test]
+    fn test_container_corrupted_names() {
+        log_init_test();
+        let dir = crate::test_dir("container_names");
+        let params = SeqSketcherParams::new(21, 2, SketchAlgo::PROB3A, DataType::DNA);
+        let metadata : Vec<SketchMetadata> = ["a", "bb"].iter().map(|n| SketchMetadata{name : n.to_string(), seq_len : 10}).collect();
+        let path = dir.join("kmerutils_test_container_names.sk");
+        dump_sketch_container(&path, &params, &metadata, &[vec![1u32, 2], vec![3, 4]]).unwrap();
+        // the file ends with the 3 name offsets (u64) followed by the 3 bytes of names, corrupt the middle offset
+        let mut bytes = std::fs::read(&path).unwrap();
+        let len = bytes.len();
+        bytes[len - 3 - 16 + 6] = 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+        let container = SketchContainer::open(&path).unwrap();
+        assert!(container.get_name(0).is_err());
+        assert!(container.get_name(1).is_err());
+        assert!(container.get_name(2).is_err());
+        assert_eq!(container.get_signature::<u32>(1).unwrap(), vec![3, 4]);
+        assert!(container.get_signature_bytes(2).is_err());
+        assert!(container.iter_signatures::<u32>().unwrap().all(|item| item.is_err()));
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_container_corrupted_names
+
+
+    #[test]
+    fn test_container_provenance() {
+        log_init_test();
+        let dir = crate::test_dir("provenance");
+        let params = SeqSketcherParams::new(21, 2, SketchAlgo::PROB3A, DataType::DNA);
+        let input = dir.join("kmerutils_test_provenance.fa");
+        std::fs::write(&input, ">r\nACGT\n").unwrap();
+        let path = dir.join("kmerutils_test_provenance.sk");
+        let metadata = vec![SketchMetadata{name : "r".to_string(), seq_len : 4}];
+        dump_sketch_container_with_inputs(&path, &params, &metadata, &[vec![1u32, 2]], &[input.as_path()]).unwrap();
+        let container = SketchContainer::open(&path).unwrap();
+        let provenance = container.get_provenance().unwrap();
+        assert_eq!(provenance.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(provenance.inputs[0].size, 8);
+        assert!(container.get_stale_inputs().is_empty());
+        std::fs::write(&input, ">r\nACGG\n").unwrap();
+        assert_eq!(container.get_stale_inputs().len(), 1);
+        drop(container);
+        // corrupt the first signature value. The file ends with 8 bytes of signatures, 8 bytes of sequence length,
+        // 2 name offsets of 8 bytes and the 1 byte name
+        let mut bytes = std::fs::read(&path).unwrap();
+        let len = bytes.len();
+        bytes[len - 33] ^= 1;
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(SketchContainer::open_verified(&path).is_err());
+        let container = SketchContainer::open(&path).unwrap();
+        assert_eq!(container.get_signature::<u32>(0).unwrap(), vec![0, 2]);
+        assert!(container.verify().is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_container_provenance
+
+} // end of mod tests
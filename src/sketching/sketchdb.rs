@@ -0,0 +1,366 @@
+//! This module provides an appendable database of sketches stored in a directory.
+//!
+//! The directory contains :
+//! - a [sketch container](super::sketchcontainer) [DB_BASE_NAME], memory mapped, holding the sketches present at last compaction,
+//! - a delta file [DB_DELTA_NAME] (bincode) with the sketches appended and the base sketches deleted since.
+//!   It records the data checksum of the base it applies to, so that a delta left by an interrupted compaction
+//!   is not applied to the new base.
+//!
+//! New genomes can be appended and entries deleted without rewriting the base container.
+//! [SketchDb::compact] merges the delta in a new base container.
+//! Sketches are identified by their (unique) name.
+//!
+//! [SketchDb::query_topk] returns the sketches most similar to a query by a parallel scan of the mapped base and the delta.
+//! With feature *hnsw*, [SketchDb::build_index] builds an ann index over the database for sub-linear queries.
+
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use fnv::FnvHashMap;
+use crate::parallel::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::io::{dump_bin, reload_bin};
+use crate::sketcharg::SeqSketcherParams;
+use super::blockcompare::count_equal;
+use super::csvexport::PairwiseResult;
+use super::mashdist::identity_from_jaccard;
+use super::sketchcontainer::{dump_sketch_container, SketchContainer, SketchContainerSig, SketchMetadata};
+#[cfg(feature="hnsw")]
+use super::sketchhnsw::{HnswIndexParams, SketchIndex};
+
+
+/// name of the base container in the database directory
+pub const DB_BASE_NAME : &str = "sketchdb.sk";
+
+/// name of the delta file in the database directory
+pub const DB_DELTA_NAME : &str = "sketchdb.delta";
+
+
+// modifications since last compaction
+#[derive(Serialize,Deserialize)]
+struct SketchDbDelta<Sig> {
+    // data checksum of the base container the delta applies to
+    base_xxh3 : u64,
+    appended : Vec<(SketchMetadata, Vec<Sig>)>,
+    // ranks of deleted sketches in base
+    deleted : Vec<usize>,
+}
+
+
+// location of a sketch
+#[derive(Copy,Clone,Debug)]
+enum Location {
+    Base(usize),
+    Appended(usize),
+}
+
+
+/// An appendable sketch database. Modifications are written to disk at each call.
+pub struct SketchDb<Sig> {
+    dir : PathBuf,
+    params : SeqSketcherParams,
+    base : SketchContainer,
+    // data checksum of base, identifies the base generation
+    base_xxh3 : u64,
+    appended : Vec<(SketchMetadata, Vec<Sig>)>,
+    deleted : HashSet<usize>,
+    // name to location of live sketches
+    index : FnvHashMap<String, Location>,
+}
+
+
+impl <Sig> SketchDb<Sig>
+        where Sig : SketchContainerSig + Serialize + DeserializeOwned {
+
+    /// creates an empty database in directory dir (created if necessary). Fails if a database already exists in dir.
+    pub fn create(dir : &Path, params : &SeqSketcherParams) -> Result<Self, String> {
+        log::info!("SketchDb creating database in {:?}", dir);
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let base_path = dir.join(DB_BASE_NAME);
+        if base_path.exists() {
+            log::error!("SketchDb::create : a database already exists in {:?}", dir);
+            return Err(format!("a database already exists in {:?}", dir));
+        }
+        dump_sketch_container::<Sig>(&base_path, params, &[], &[])?;
+        let _ = std::fs::remove_file(dir.join(DB_DELTA_NAME));
+        Self::open(dir)
+    } // end of create
+
+
+    /// opens an existing database
+    pub fn open(dir : &Path) -> Result<Self, String> {
+        log::info!("SketchDb opening database in {:?}", dir);
+        let base = SketchContainer::open(&dir.join(DB_BASE_NAME))?;
+        if base.get_header().sig_type != Sig::NAME {
+            log::error!("SketchDb::open : database stores {} signatures, asked {}", base.get_header().sig_type, Sig::NAME);
+            return Err(format!("database stores {} signatures", base.get_header().sig_type));
+        }
+        let params = *base.get_params();
+        let base_xxh3 = base.get_provenance().map_or(0, |p| p.data_xxh3);
+        let delta_path = dir.join(DB_DELTA_NAME);
+        let mut delta : SketchDbDelta<Sig> = if delta_path.exists() { reload_bin(&delta_path)? }
+                                            else { SketchDbDelta{base_xxh3, appended : Vec::new(), deleted : Vec::new()} };
+        if delta.base_xxh3 != base_xxh3 {
+            // compaction was interrupted after the new base was written, the delta is already merged in it
+            log::warn!("SketchDb::open : delta file of a previous base in {:?}, ignored", dir);
+            delta = SketchDbDelta{base_xxh3, appended : Vec::new(), deleted : Vec::new()};
+            std::fs::remove_file(&delta_path).map_err(|e| e.to_string())?;
+        }
+        let deleted : HashSet<usize> = delta.deleted.into_iter().collect();
+        let mut index = FnvHashMap::<String, Location>::default();
+        for i in (0..base.len()).filter(|i| !deleted.contains(i)) {
+            index.insert(base.get_name(i)?.to_string(), Location::Base(i));
+        }
+        for (i, (m, _)) in delta.appended.iter().enumerate() {
+            index.insert(m.name.clone(), Location::Appended(i));
+        }
+        log::info!("SketchDb, nb sketches : {}, nb appended : {}, nb deleted : {}", index.len(), delta.appended.len(), deleted.len());
+        Ok(SketchDb{dir : dir.to_path_buf(), params, base, base_xxh3, appended : delta.appended, deleted, index})
+    } // end of open
+
+
+    /// returns sketching parameters of the database
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
+
+    /// returns the number of live sketches
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// true if database has no sketch
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// returns true if a sketch has this name
+    pub fn contains(&self, name : &str) -> bool {
+        self.index.contains_key(name)
+    }
+
+    /// returns names of live sketches, base sketches first
+    pub fn get_names(&self) -> Vec<String> {
+        let mut names : Vec<(usize, String)> = self.index.iter().map(|(name, loc)| {
+                let rank = match loc {
+                    Location::Base(i) => *i,
+                    Location::Appended(i) => self.base.len() + i,
+                };
+                (rank, name.clone())
+            }).collect();
+        names.sort_unstable();
+        names.into_iter().map(|(_, name)| name).collect()
+    }
+
+    /// returns metadata and signature of a sketch
+    pub fn get(&self, name : &str) -> Option<(SketchMetadata, Vec<Sig>)> {
+        match self.index.get(name)? {
+            Location::Base(i) => Some((self.base.get_metadata(*i).ok()?, self.base.get_signature::<Sig>(*i).ok()?)),
+            Location::Appended(i) => Some(self.appended[*i].clone()),
+        }
+    }
+
+    // writes delta file, through a temporary file so that a crash does not corrupt the database
+    fn write_delta(&self) -> Result<(), String> {
+        let mut deleted : Vec<usize> = self.deleted.iter().copied().collect();
+        deleted.sort_unstable();
+        let delta = SketchDbDelta{base_xxh3 : self.base_xxh3, appended : self.appended.clone(), deleted};
+        let tmp = self.dir.join(format!("{}.tmp", DB_DELTA_NAME));
+        dump_bin(&delta, &tmp)?;
+        std::fs::rename(&tmp, self.dir.join(DB_DELTA_NAME)).map_err(|e| e.to_string())
+    }
+
+    /// appends a sketch. Fails if the name is already used or the signature size is not the sketch size.
+    pub fn append(&mut self, metadata : SketchMetadata, signature : Vec<Sig>) -> Result<(), String> {
+        if signature.len() != self.params.get_sketch_size() {
+            log::error!("SketchDb::append : signature size {} , expected {}", signature.len(), self.params.get_sketch_size());
+            return Err("signature size does not match sketch size".to_string());
+        }
+        if self.index.contains_key(&metadata.name) {
+            log::error!("SketchDb::append : name {} already in database", metadata.name);
+            return Err(format!("name {} already in database", metadata.name));
+        }
+        self.index.insert(metadata.name.clone(), Location::Appended(self.appended.len()));
+        self.appended.push((metadata, signature));
+        self.write_delta()
+    } // end of append
+
+    /// deletes a sketch. Returns false if there is no sketch with this name.
+    pub fn delete(&mut self, name : &str) -> Result<bool, String> {
+        match self.index.remove(name) {
+            Some(Location::Base(i)) => {
+                self.deleted.insert(i);
+            },
+            Some(Location::Appended(i)) => {
+                self.appended.remove(i);
+                // shift locations of following appended sketches
+                for loc in self.index.values_mut() {
+                    if let Location::Appended(j) = loc {
+                        if *j > i {
+                            *j -= 1;
+                        }
+                    }
+                }
+            },
+            None => return Ok(false),
+        }
+        self.write_delta()?;
+        Ok(true)
+    } // end of delete
+
+    /// rewrites the base container with all live sketches and removes the delta file.
+    /// If interrupted between the two steps, the stale delta is detected and ignored at next opening.
+    pub fn compact(&mut self) -> Result<(), String> {
+        log::info!("SketchDb compacting {:?}, nb sketches : {}", self.dir, self.len());
+        let names = self.get_names();
+        let mut metadata = Vec::<SketchMetadata>::with_capacity(names.len());
+        let mut signatures = Vec::<Vec<Sig>>::with_capacity(names.len());
+        for name in &names {
+            let (m, sig) = self.get(name).ok_or_else(|| format!("SketchDb::compact : could not read sketch {}", name))?;
+            metadata.push(m);
+            signatures.push(sig);
+        }
+        let tmp = self.dir.join(format!("{}.tmp", DB_BASE_NAME));
+        dump_sketch_container(&tmp, &self.params, &metadata, &signatures)?;
+        std::fs::rename(&tmp, self.dir.join(DB_BASE_NAME)).map_err(|e| e.to_string())?;
+        let _ = std::fs::remove_file(self.dir.join(DB_DELTA_NAME));
+        *self = Self::open(&self.dir)?;
+        Ok(())
+    } // end of compact
+
+
+    /// returns the k sketches most similar to a query signature, by decreasing Jaccard index (ties by name),
+    /// with their ANI (AAI for amino acid sketches) and number of shared signature values. The database is scanned in parallel.
+    pub fn query_topk(&self, query_name : &str, query_sig : &[Sig], k : usize) -> Result<Vec<PairwiseResult>, String>
+            where Sig : PartialEq + Send + Sync {
+        //
+        if query_sig.len() != self.params.get_sketch_size() {
+            log::error!("SketchDb::query_topk : query size {} , expected {}", query_sig.len(), self.params.get_sketch_size());
+            return Err("query size does not match sketch size".to_string());
+        }
+        let live : Vec<(&String, &Location)> = self.index.iter().collect();
+        let mut scores : Vec<(usize, &String)> = live.par_iter().map(|(name, loc)| {
+                let common = match loc {
+                    Location::Base(i) => count_equal(query_sig, &self.base.get_signature_ref::<Sig>(*i)?),
+                    Location::Appended(i) => count_equal(query_sig, &self.appended[*i].1),
+                };
+                Ok((common, *name))
+            }).collect::<Result<Vec<(usize, &String)>, String>>()?;
+        let order = |a : &(usize, &String), b : &(usize, &String)| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1));
+        if k < scores.len() {
+            scores.select_nth_unstable_by(k, order);
+            scores.truncate(k);
+        }
+        scores.sort_unstable_by(order);
+        let sketch_size = query_sig.len() as f64;
+        Ok(scores.into_iter().map(|(common, name)| {
+                let jaccard = common as f64 / sketch_size;
+                PairwiseResult{query : query_name.to_string(), target : name.clone(), jaccard, ani : identity_from_jaccard(jaccard, &self.params), common}
+            }).collect())
+    } // end of query_topk
+
+
+    /// builds an ann index over the live sketches of the database, see [SketchIndex::search].
+    /// The index is not updated by later modifications of the database.
+    #[cfg(feature="hnsw")]
+    pub fn build_index(&self, hnsw_params : HnswIndexParams) -> Result<SketchIndex<Sig>, String>
+            where Sig : PartialEq + Send + Sync + 'static {
+        let mut metadata = Vec::<SketchMetadata>::with_capacity(self.len());
+        let mut signatures = Vec::<Vec<Sig>>::with_capacity(self.len());
+        for name in self.get_names() {
+            let (m, sig) = self.get(&name).ok_or_else(|| format!("SketchDb::build_index : could not read sketch {}", name))?;
+            metadata.push(m);
+            signatures.push(sig);
+        }
+        SketchIndex::new(&self.params, metadata, &signatures, hnsw_params)
+    } // end of build_index
+
+} // end of impl SketchDb
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sketcharg::{SketchAlgo, DataType};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    fn entry(i : u64) -> (SketchMetadata, Vec<u32>) {
+        (SketchMetadata{name : format!("genome{}", i), seq_len : 1000 * i}, vec![i as u32; 3])
+    }
+
+    #[test]
+    fn test_sketch_db() {
+        log_init_test();
+        let dir = crate::test_dir("sketchdb");
+        let params = SeqSketcherParams::new(21, 3, SketchAlgo::PROB3A, DataType::DNA);
+        let mut db = SketchDb::<u32>::create(&dir, &params).unwrap();
+        assert!(SketchDb::<u32>::create(&dir, &params).is_err());
+        for i in 0..4 {
+            let (m, s) = entry(i);
+            db.append(m, s).unwrap();
+        }
+        let (m, s) = entry(1);
+        assert!(db.append(m, s).is_err());
+        assert!(db.delete("genome1").unwrap());
+        assert!(!db.delete("genome1").unwrap());
+        // reopen and compact
+        let mut db = SketchDb::<u32>::open(&dir).unwrap();
+        assert_eq!(db.get_names(), vec!["genome0", "genome2", "genome3"]);
+        assert_eq!(db.get("genome3").unwrap(), entry(3));
+        db.compact().unwrap();
+        assert!(!dir.join(DB_DELTA_NAME).exists());
+        assert!(db.delete("genome0").unwrap());
+        let (m, s) = entry(7);
+        db.append(m, s).unwrap();
+        let db = SketchDb::<u32>::open(&dir).unwrap();
+        assert_eq!(db.get_names(), vec!["genome2", "genome3", "genome7"]);
+        assert_eq!(db.get("genome2").unwrap(), entry(2));
+        assert!(SketchDb::<u64>::open(&dir).is_err());
+        // compaction interrupted before removal of the delta file
+        let delta = std::fs::read(dir.join(DB_DELTA_NAME)).unwrap();
+        let mut db = SketchDb::<u32>::open(&dir).unwrap();
+        db.compact().unwrap();
+        std::fs::write(dir.join(DB_DELTA_NAME), &delta).unwrap();
+        let db = SketchDb::<u32>::open(&dir).unwrap();
+        assert_eq!(db.get_names(), vec!["genome2", "genome3", "genome7"]);
+        assert!(!dir.join(DB_DELTA_NAME).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_sketch_db
+
+
+    #[test]
+    fn test_query_topk() {
+        log_init_test();
+        let dir = crate::test_dir("sketchdb_topk");
+        let params = SeqSketcherParams::new(21, 4, SketchAlgo::PROB3A, DataType::DNA);
+        let mut db = SketchDb::<u32>::create(&dir, &params).unwrap();
+        // genome i shares i slots with query
+        let query = vec![0u32, 1, 2, 3];
+        for i in 0..5u32 {
+            let sig : Vec<u32> = (0..4).map(|j| if j < i { j } else { 100 + j }).collect();
+            db.append(SketchMetadata{name : format!("genome{}", i), seq_len : 1000}, sig).unwrap();
+        }
+        db.compact().unwrap();
+        db.append(SketchMetadata{name : "copy".to_string(), seq_len : 1000}, query.clone()).unwrap();
+        db.delete("genome4").unwrap();
+        let hits = db.query_topk("query", &query, 3).unwrap();
+        let names : Vec<&str> = hits.iter().map(|h| h.target.as_str()).collect();
+        assert_eq!(names, vec!["copy", "genome3", "genome2"]);
+        assert_eq!((hits[0].jaccard, hits[0].ani, hits[0].common), (1., 1., 4));
+        assert_eq!((hits[1].jaccard, hits[1].common), (0.75, 3));
+        assert_eq!(db.query_topk("query", &query, 10).unwrap().len(), 5);
+        assert!(db.query_topk("query", &query[..2], 3).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    } // end of test_query_topk
+
+} // end of mod tests
@@ -0,0 +1,163 @@
+//! This module builds an approximate nearest neighbour index ([hnsw_rs]) over a collection of signatures,
+//! so that the genomes nearest to a query are found in sub-linear time.
+//!
+//! The distance between two signatures is [DistSlotJaccard] : the fraction of differing slots (a normalized Hamming distance),
+//! which is 1 - Jaccard estimate for integer signatures (ProbMinHash, SuperMinHash2, HyperLogLog)
+//! as for float signatures (SuperMinHash, OptDens, RevOptDens).
+//!
+//! Requires feature *hnsw*.
+
+
+use hnsw_rs::prelude::*;
+
+use crate::sketcharg::SeqSketcherParams;
+use super::csvexport::PairwiseResult;
+use super::mashdist::ani_from_jaccard;
+use super::sketchcontainer::{SketchContainer, SketchContainerSig, SketchMetadata};
+
+
+/// Distance between signatures : fraction of slots with different values, in \[0, 1\].
+#[derive(Default, Copy, Clone, Debug)]
+pub struct DistSlotJaccard {}
+
+impl <T : PartialEq + Send + Sync> Distance<T> for DistSlotJaccard {
+    fn eval(&self, va : &[T], vb : &[T]) -> f32 {
+        assert_eq!(va.len(), vb.len());
+        let nb_diff = va.iter().zip(vb).filter(|(a, b)| a != b).count();
+        nb_diff as f32 / va.len() as f32
+    }
+} // end of impl Distance for DistSlotJaccard
+
+
+
+/// parameters of the hnsw graph
+#[derive(Copy, Clone, Debug)]
+pub struct HnswIndexParams {
+    /// number of neighbours stored by layer, must be <= 256
+    pub max_nb_connection : usize,
+    /// number of neighbours explored at construction
+    pub ef_construction : usize,
+    /// number of neighbours explored at search, increased to k if smaller
+    pub ef_search : usize,
+}
+
+impl Default for HnswIndexParams {
+    fn default() -> Self {
+        HnswIndexParams{max_nb_connection : 24, ef_construction : 400, ef_search : 64}
+    }
+}
+
+
+/// An ann index over signatures with their metadata.
+pub struct SketchIndex<Sig : Clone + PartialEq + Send + Sync + 'static> {
+    hnsw : Hnsw<'static, Sig, DistSlotJaccard>,
+    metadata : Vec<SketchMetadata>,
+    kmer_size : usize,
+    ef_search : usize,
+}
+
+
+impl <Sig> SketchIndex<Sig>
+        where Sig : Clone + PartialEq + Send + Sync + 'static {
+
+    /// indexes signatures obtained with params. metadata\[i\] describes signatures\[i\].
+    pub fn new(params : &SeqSketcherParams, metadata : Vec<SketchMetadata>, signatures : &[Vec<Sig>], hnsw_params : HnswIndexParams) -> Result<Self, String> {
+        if metadata.len() != signatures.len() {
+            log::error!("SketchIndex : {} metadata for {} signatures", metadata.len(), signatures.len());
+            return Err("SketchIndex : metadata and signatures must have same length".to_string());
+        }
+        if signatures.iter().any(|s| s.len() != params.get_sketch_size()) {
+            log::error!("SketchIndex : signature size does not match sketch size {}", params.get_sketch_size());
+            return Err("SketchIndex : signature size does not match sketch size".to_string());
+        }
+        log::info!("SketchIndex indexing {} signatures, {:?}", signatures.len(), hnsw_params);
+        let hnsw = Hnsw::<Sig, DistSlotJaccard>::new(hnsw_params.max_nb_connection, signatures.len().max(1), 16,
+                        hnsw_params.ef_construction, DistSlotJaccard::default());
+        let data : Vec<(&Vec<Sig>, usize)> = signatures.iter().enumerate().map(|(i, s)| (s, i)).collect();
+        hnsw.parallel_insert(&data);
+        Ok(SketchIndex{hnsw, metadata, kmer_size : params.get_kmer_size(), ef_search : hnsw_params.ef_search})
+    } // end of new
+
+
+    /// returns the number of indexed signatures
+    pub fn len(&self) -> usize {
+        self.metadata.len()
+    }
+
+    /// true if no signature is indexed
+    pub fn is_empty(&self) -> bool {
+        self.metadata.is_empty()
+    }
+
+    /// returns metadata of signature i
+    pub fn get_metadata(&self, i : usize) -> &SketchMetadata {
+        &self.metadata[i]
+    }
+
+    /// returns the (approximately) k nearest signatures to a query, by decreasing Jaccard index.
+    pub fn search(&self, query_name : &str, query_sig : &[Sig], k : usize) -> Vec<PairwiseResult> {
+        if self.is_empty() || k == 0 {
+            return Vec::new();
+        }
+        let neighbours = self.hnsw.search(query_sig, k, self.ef_search.max(k));
+        neighbours.iter().map(|n| {
+                let jaccard = 1. - n.distance as f64;
+                PairwiseResult{query : query_name.to_string(), target : self.metadata[n.d_id].name.clone(), jaccard,
+                        ani : ani_from_jaccard(jaccard, self.kmer_size), common : (jaccard * query_sig.len() as f64).round() as usize}
+            }).collect()
+    } // end of search
+
+} // end of impl SketchIndex
+
+
+impl <Sig> SketchIndex<Sig>
+        where Sig : SketchContainerSig + PartialEq + Send + Sync + 'static {
+    /// indexes all signatures of a container
+    pub fn from_container(container : &SketchContainer, hnsw_params : HnswIndexParams) -> Result<Self, String> {
+        let (metadata, signatures) : (Vec<SketchMetadata>, Vec<Vec<Sig>>) = container.iter_signatures::<Sig>()?
+                .collect::<Result<Vec<_>, _>>()?.into_iter().unzip();
+        Self::new(container.get_params(), metadata, &signatures, hnsw_params)
+    }
+}
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use crate::sketcharg::{SketchAlgo, DataType};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_sketch_index() {
+        log_init_test();
+        let mut rng = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(2024);
+        let sketch_size = 64;
+        let params = SeqSketcherParams::new(21, sketch_size, SketchAlgo::PROB3A, DataType::DNA);
+        // values in a small range so that distances are spread around 0.75
+        let signatures : Vec<Vec<u64>> = (0..500).map(|_| (0..sketch_size).map(|_| rng.gen_range(0..4)).collect()).collect();
+        let metadata : Vec<SketchMetadata> = (0..500).map(|i| SketchMetadata{name : format!("g{}", i), seq_len : 1000}).collect();
+        let index = SketchIndex::new(&params, metadata, &signatures, HnswIndexParams::default()).unwrap();
+        assert_eq!(index.len(), 500);
+        // query is genome 123 with 16 slots changed
+        let mut query = signatures[123].clone();
+        for v in query.iter_mut().take(16) {
+            *v += 10;
+        }
+        let hits = index.search("query", &query, 3);
+        assert_eq!(hits.len(), 3);
+        assert_eq!(hits[0].target, "g123");
+        assert_eq!((hits[0].jaccard, hits[0].common), (0.75, 48));
+        assert!(hits[1].jaccard < 0.6 && hits[1].jaccard >= hits[2].jaccard);
+        assert_eq!(DistSlotJaccard::default().eval(&[1.5f32, 2.], &[1.5f32, 3.]), 0.5);
+    } // end of test_sketch_index
+
+} // end of mod tests
@@ -0,0 +1,219 @@
+//! This module makes sketching run in a caller provided rayon thread pool instead of the global one.
+//!
+//! Sketchers parallelize with rayon, by default in the global pool. When kmerutils is embedded in a service,
+//! the global pool is shared with other tasks : [PooledSketcher] wraps any sketcher ([SeqSketcherT], [SeqSketcherAAT]
+//! or [DynSeqSketcher]) so that all its parallel iterations run in a given pool, whose size bounds the number of threads used by sketching.
+//!
+//! ```
+//! use std::sync::Arc;
+//! use kmerutils::base::{sequence::Sequence, kmer::*, kmergenerator::*};
+//! use kmerutils::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
+//! use kmerutils::sketching::setsketchert::{SeqSketcherT, ProbHash3aSketch};
+//! use kmerutils::sketching::sketchpool::PooledSketcher;
+//!
+//! let params = SeqSketcherParams::new(8, 50, SketchAlgo::PROB3A, DataType::DNA);
+//! let pool = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap());
+//! let sketcher = PooledSketcher::new(ProbHash3aSketch::<Kmer32bit>::new(&params), pool);
+//! let seq = Sequence::new(b"ACGTTGCAAGGCTTACCGATTGACCAGT", 2);
+//! let sigs = sketcher.sketch_compressedkmer(&vec![&seq], |kmer : &Kmer32bit| kmer.get_compressed_value()).unwrap();
+//! assert_eq!(sigs.len(), 1);
+//! ```
+
+
+use std::sync::Arc;
+
+use rayon::ThreadPool;
+
+use crate::base::{kmertraits::*, kmergenerator::*};
+use crate::aautils::kmeraa::{self as kmeraa, SequenceAA};
+use crate::aautils::setsketchert::SeqSketcherAAT;
+use crate::sketcharg::{SeqSketcherParams, SketchAlgo};
+
+use super::setsketchert::SeqSketcherT;
+use super::dynsketch::{DynSeqSketcher, SigEnum, make_dyn_sketcher};
+
+
+/// a sketcher running in a given thread pool
+pub struct PooledSketcher<S> {
+    sketcher : S,
+    pool : Arc<ThreadPool>,
+}
+
+
+impl <S> PooledSketcher<S> {
+
+    /// sketcher will run in pool, the pool can be shared by many sketchers
+    pub fn new(sketcher : S, pool : Arc<ThreadPool>) -> Self {
+        PooledSketcher{sketcher, pool}
+    }
+
+    /// sketcher will run in its own pool of nb_threads threads (0 means rayon default)
+    pub fn with_num_threads(sketcher : S, nb_threads : usize) -> Result<Self, String> {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(nb_threads).build().map_err(|e| {
+                log::error!("PooledSketcher could not build thread pool : {}", e);
+                e.to_string()
+            })?;
+        Ok(PooledSketcher{sketcher, pool : Arc::new(pool)})
+    }
+
+    pub fn get_sketcher(&self) -> &S {
+        &self.sketcher
+    }
+
+    pub fn get_pool(&self) -> &Arc<ThreadPool> {
+        &self.pool
+    }
+
+} // end of impl PooledSketcher
+
+
+
+impl <Kmer, S> SeqSketcherT<Kmer> for PooledSketcher<S>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                S : SeqSketcherT<Kmer> + Sync {
+
+    type Sig = S::Sig;
+
+    fn get_kmer_size(&self) -> usize {
+        self.sketcher.get_kmer_size()
+    }
+
+    fn get_sketch_size(&self) -> usize {
+        self.sketcher.get_sketch_size()
+    }
+
+    fn get_algo(&self) -> SketchAlgo {
+        self.sketcher.get_algo()
+    }
+
+    fn sketch_compressedkmer<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+            where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        self.pool.install(|| self.sketcher.sketch_compressedkmer(vseq, fhash))
+    }
+
+    fn sketch_compressedkmer_seqs<F>(&self, vseq : &[&Sequence], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+            where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        self.pool.install(|| self.sketcher.sketch_compressedkmer_seqs(vseq, fhash))
+    }
+
+    fn sketch_compressedkmer_bytes<F>(&self, vseq : &[&[u8]], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+            where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        self.pool.install(|| self.sketcher.sketch_compressedkmer_bytes(vseq, fhash))
+    }
+
+    fn sketch_compressedkmer_bytes_seqs<F>(&self, vseq : &[&[u8]], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+            where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        self.pool.install(|| self.sketcher.sketch_compressedkmer_bytes_seqs(vseq, fhash))
+    }
+} // end of impl SeqSketcherT for PooledSketcher
+
+
+
+impl <Kmer, S> SeqSketcherAAT<Kmer> for PooledSketcher<S>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                kmeraa::KmerGenerator<Kmer> : kmeraa::KmerGenerationPattern<Kmer>,
+                S : SeqSketcherAAT<Kmer> + Sync {
+
+    type Sig = S::Sig;
+
+    fn get_kmer_size(&self) -> usize {
+        self.sketcher.get_kmer_size()
+    }
+
+    fn get_sketch_size(&self) -> usize {
+        self.sketcher.get_sketch_size()
+    }
+
+    fn get_algo(&self) -> SketchAlgo {
+        self.sketcher.get_algo()
+    }
+
+    fn sketch_compressedkmeraa<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+            where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        self.pool.install(|| self.sketcher.sketch_compressedkmeraa(vseq, fhash))
+    }
+
+    fn sketch_compressedkmeraa_seqs<F>(&self, vseq : &[&SequenceAA], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+            where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        self.pool.install(|| self.sketcher.sketch_compressedkmeraa_seqs(vseq, fhash))
+    }
+
+    fn sketch_compressedkmeraa_bytes<F>(&self, vseq : &[&[u8]], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+            where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        self.pool.install(|| self.sketcher.sketch_compressedkmeraa_bytes(vseq, fhash))
+    }
+
+    fn sketch_compressedkmeraa_bytes_seqs<F>(&self, vseq : &[&[u8]], fhash : F) -> Result<Vec<Vec<Self::Sig>>, String>
+            where F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+        self.pool.install(|| self.sketcher.sketch_compressedkmeraa_bytes_seqs(vseq, fhash))
+    }
+} // end of impl SeqSketcherAAT for PooledSketcher
+
+
+
+impl DynSeqSketcher for PooledSketcher<Box<dyn DynSeqSketcher>> {
+
+    fn get_params(&self) -> &SeqSketcherParams {
+        self.sketcher.get_params()
+    }
+
+    fn sketch_bytes(&self, vseq : &[&[u8]]) -> Result<Vec<SigEnum>, String> {
+        self.pool.install(|| self.sketcher.sketch_bytes(vseq))
+    }
+
+    fn sketch_bytes_seqs(&self, vseq : &[&[u8]]) -> Result<SigEnum, String> {
+        self.pool.install(|| self.sketcher.sketch_bytes_seqs(vseq))
+    }
+} // end of impl DynSeqSketcher for PooledSketcher
+
+
+
+/// same as [make_dyn_sketcher], the sketcher running in pool.
+pub fn make_dyn_sketcher_in_pool(params : &SeqSketcherParams, pool : Arc<ThreadPool>) -> Result<Box<dyn DynSeqSketcher>, String> {
+    let sketcher = make_dyn_sketcher(params)?;
+    Ok(Box::new(PooledSketcher::new(sketcher, pool)))
+}
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::sketcharg::DataType;
+    use crate::sketching::setsketchert::SuperHashSketch;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_pooled_sketcher() {
+        log_init_test();
+        let seqs = [Sequence::new(b"ACGTTGCAAGGCTTACCGATTGACCAGT", 2), Sequence::new(b"GGGCATTACCAGGTTACATTGACC", 2)];
+        let vseq : Vec<&Sequence> = seqs.iter().collect();
+        let params = SeqSketcherParams::new(8, 50, SketchAlgo::SUPER, DataType::DNA);
+        let sketcher = SuperHashSketch::<Kmer32bit, f32>::new(&params);
+        let pooled = PooledSketcher::with_num_threads(sketcher, 3).unwrap();
+        // kmers are hashed in threads of the pool
+        let max_nb_threads = AtomicUsize::new(0);
+        let fhash = |kmer : &Kmer32bit| {
+            if rayon::current_thread_index().is_some() {
+                max_nb_threads.fetch_max(rayon::current_num_threads(), Ordering::Relaxed);
+            }
+            kmer.get_compressed_value()
+        };
+        let sigs = pooled.sketch_compressedkmer(&vseq, &fhash).unwrap();
+        assert_eq!(max_nb_threads.into_inner(), 3);
+        assert_eq!(sigs, sketcher.sketch_compressedkmer(&vseq, |kmer : &Kmer32bit| kmer.get_compressed_value()).unwrap());
+        // dynamic sketcher
+        let dynsketcher = make_dyn_sketcher_in_pool(&params, pooled.get_pool().clone()).unwrap();
+        assert_eq!(dynsketcher.sketch_bytes(&[b"ACGTTGCAAGGCTTACCGATTGACCAGT"]).unwrap().len(), 1);
+    } // end of test_pooled_sketcher
+
+} // end of mod tests
@@ -0,0 +1,319 @@
+//! This module provides [SketchSet], a sketching result keeping with each signature the description of the sketched item.
+//!
+//! Sketchers return a `Vec<Vec<Sig>>` whose order is the order of input sequences, the caller having to keep names and lengths aside.
+//! A SketchSet stores for each item its id (rank of insertion), its name, its length, the number of kmers hashed
+//! and its signature, together with the parameters used, so that signatures can be passed around (and serialized) without
+//! mixing up their inputs. Signatures are kept, one `Vec<Sig>` per item, in the layout returned by sketchers so that
+//! [get_signatures](SketchSet::get_signatures) can be passed directly to [compute_distance_matrix](super::distances::compute_distance_matrix).
+//!
+//! [sketch_set] and [sketch_set_aa] run a sketcher on named sequences and return a SketchSet.
+//!
+//! ```
+//! use kmerutils::base::{sequence::Sequence, kmer::*};
+//! use kmerutils::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
+//! use kmerutils::sketching::setsketchert::ProbHash3aSketch;
+//! use kmerutils::sketching::sketchset::sketch_set;
+//!
+//! let params = SeqSketcherParams::new(8, 50, SketchAlgo::PROB3A, DataType::DNA);
+//! let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&params);
+//! let seqs = [Sequence::new(b"ACGTTGCAAGGCTTACCGATTGACCAGT", 2), Sequence::new(b"GGGCATTACCAGGTTACATTGACC", 2)];
+//! let names = vec!["seq1".to_string(), "seq2".to_string()];
+//! let set = sketch_set(&sketcher, &params, &names, &seqs.iter().collect(), |kmer : &Kmer32bit| kmer.get_compressed_value()).unwrap();
+//! let item = set.get_by_name("seq2").unwrap();
+//! assert_eq!(item.seq_len, 24);
+//! assert_eq!(item.nb_kmers, 17);
+//! assert_eq!(set.get_signature(item.id).len(), 50);
+//! ```
+
+
+#[cfg(feature="formats")]
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::base::{kmertraits::*, kmergenerator::*};
+use crate::aautils::kmeraa::{self as kmeraa, SequenceAA};
+use crate::aautils::setsketchert::SeqSketcherAAT;
+use crate::sketcharg::{SeqSketcherParams, IncompatibleParams};
+
+use super::setsketchert::SeqSketcherT;
+use super::sigdistance::SignatureDistance;
+#[cfg(feature="formats")]
+use super::sketchcontainer::{dump_sketch_container, SketchContainerSig, SketchMetadata};
+
+
+/// description of an item of a [SketchSet]
+#[derive(Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct SketchInfo {
+    /// rank of the item in its set
+    pub id : usize,
+    /// sequence (or file) name
+    pub name : String,
+    /// sequence length in bases (or residues)
+    pub seq_len : u64,
+    /// number of kmers sent to the sketcher
+    pub nb_kmers : u64,
+}
+
+
+/// signatures with their description and the parameters used to compute them
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct SketchSet<Sig> {
+    params : SeqSketcherParams,
+    infos : Vec<SketchInfo>,
+    signatures : Vec<Vec<Sig>>,
+}
+
+
+impl <Sig> SketchSet<Sig> {
+
+    /// an empty set of sketches computed with params
+    pub fn new(params : &SeqSketcherParams) -> Self {
+        SketchSet{params : *params, infos : Vec::new(), signatures : Vec::new()}
+    }
+
+    /// builds a set from the result of a sketcher. names, seq_lens and nb_kmers must be in the order of signatures.
+    pub fn from_signatures(params : &SeqSketcherParams, names : &[String], seq_lens : &[u64], nb_kmers : &[u64], signatures : Vec<Vec<Sig>>) -> Result<Self, String> {
+        let nb = signatures.len();
+        if names.len() != nb || seq_lens.len() != nb || nb_kmers.len() != nb {
+            log::error!("SketchSet::from_signatures : {} signatures but {} names, {} lengths, {} kmer counts", nb, names.len(), seq_lens.len(), nb_kmers.len());
+            return Err(format!("SketchSet::from_signatures : {} signatures but {} names, {} lengths, {} kmer counts", nb, names.len(), seq_lens.len(), nb_kmers.len()));
+        }
+        let infos = (0..nb).map(|i| SketchInfo{id : i, name : names[i].clone(), seq_len : seq_lens[i], nb_kmers : nb_kmers[i]}).collect();
+        Ok(SketchSet{params : *params, infos, signatures})
+    } // end of from_signatures
+
+    /// adds an item, returns its id
+    pub fn push(&mut self, name : &str, seq_len : u64, nb_kmers : u64, signature : Vec<Sig>) -> usize {
+        let id = self.infos.len();
+        self.infos.push(SketchInfo{id, name : name.to_string(), seq_len, nb_kmers});
+        self.signatures.push(signature);
+        id
+    }
+
+    pub fn get_params(&self) -> &SeqSketcherParams {
+        &self.params
+    }
+
+    pub fn len(&self) -> usize {
+        self.infos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.infos.is_empty()
+    }
+
+    /// description of item id
+    pub fn get_info(&self, id : usize) -> &SketchInfo {
+        &self.infos[id]
+    }
+
+    /// signature of item id
+    pub fn get_signature(&self, id : usize) -> &[Sig] {
+        &self.signatures[id]
+    }
+
+    /// description of first item named name
+    pub fn get_by_name(&self, name : &str) -> Option<&SketchInfo> {
+        self.infos.iter().find(|info| info.name == name)
+    }
+
+    pub fn get_infos(&self) -> &[SketchInfo] {
+        &self.infos
+    }
+
+    /// signatures in order of ids
+    pub fn get_signatures(&self) -> &[Vec<Sig>] {
+        &self.signatures
+    }
+
+    /// names in order of ids, as required by [write_phylip](super::distances::write_phylip)
+    pub fn get_names(&self) -> Vec<String> {
+        self.infos.iter().map(|info| info.name.clone()).collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&SketchInfo, &[Sig])> {
+        self.infos.iter().zip(self.signatures.iter().map(|s| s.as_slice()))
+    }
+
+    /// returns descriptions and signatures
+    pub fn into_parts(self) -> (SeqSketcherParams, Vec<SketchInfo>, Vec<Vec<Sig>>) {
+        (self.params, self.infos, self.signatures)
+    }
+
+} // end of impl SketchSet
+
+
+
+impl <Sig> SketchSet<Sig> where Vec<Sig> : SignatureDistance {
+
+    /// checks that signatures of self and other can be compared, see [SeqSketcherParams::check_compatible]
+    pub fn check_compatible(&self, other : &SketchSet<Sig>) -> Result<(), IncompatibleParams> {
+        self.params.check_compatible(&other.params)
+    }
+
+    /// Jaccard index between item id of self and item other_id of other.
+    /// Returns an error if the two sets were not sketched with compatible parameters.
+    pub fn jaccard_with(&self, id : usize, other : &SketchSet<Sig>, other_id : usize) -> Result<f64, IncompatibleParams> {
+        self.check_compatible(other)?;
+        Ok(self.signatures[id].jaccard(&other.signatures[other_id]))
+    }
+
+    /// distance (1 - Jaccard) between item id of self and item other_id of other, see [Self::jaccard_with]
+    pub fn distance_with(&self, id : usize, other : &SketchSet<Sig>, other_id : usize) -> Result<f64, IncompatibleParams> {
+        self.check_compatible(other)?;
+        Ok(self.signatures[id].distance(&other.signatures[other_id]))
+    }
+
+    /// distances between each item of self (rows) and each item of other (columns).
+    /// Returns an error if the two sets were not sketched with compatible parameters.
+    pub fn cross_distances(&self, other : &SketchSet<Sig>) -> Result<Vec<Vec<f64>>, IncompatibleParams> {
+        self.check_compatible(other)?;
+        Ok(self.signatures.iter().map(|sig| other.signatures.iter().map(|other_sig| sig.distance(other_sig)).collect()).collect())
+    }
+
+} // end of impl SketchSet for distances
+
+
+
+#[cfg(feature="formats")]
+impl <Sig : SketchContainerSig> SketchSet<Sig> {
+
+    /// dumps the set in a [sketch container](super::sketchcontainer). The number of kmers is not stored.
+    pub fn dump_container(&self, path : &Path) -> Result<usize, String> {
+        let metadata : Vec<SketchMetadata> = self.infos.iter().map(|info| SketchMetadata{name : info.name.clone(), seq_len : info.seq_len}).collect();
+        dump_sketch_container(path, &self.params, &metadata, &self.signatures)
+    }
+
+} // end of impl SketchSet
+
+
+
+// number of kmers generated from a sequence of seq_len bases
+fn get_nb_kmers(seq_len : usize, kmer_size : usize) -> u64 {
+    if kmer_size == 0 || seq_len < kmer_size {
+        0
+    }
+    else {
+        (seq_len - kmer_size + 1) as u64
+    }
+}
+
+
+/// sketches each sequence of vseq (named by names) with sketcher and returns a SketchSet.
+/// params must be those used to build the sketcher.
+pub fn sketch_set<Kmer, S, F>(sketcher : &S, params : &SeqSketcherParams, names : &[String], vseq : &Vec<&Sequence>, fhash : F) -> Result<SketchSet<S::Sig>, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                S : SeqSketcherT<Kmer>,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+    if names.len() != vseq.len() {
+        log::error!("sketch_set : {} sequences but {} names", vseq.len(), names.len());
+        return Err(format!("sketch_set : {} sequences but {} names", vseq.len(), names.len()));
+    }
+    let signatures = sketcher.sketch_compressedkmer(vseq, fhash)?;
+    let seq_lens : Vec<u64> = vseq.iter().map(|s| s.size() as u64).collect();
+    let nb_kmers : Vec<u64> = vseq.iter().map(|s| get_nb_kmers(s.size(), sketcher.get_kmer_size())).collect();
+    SketchSet::from_signatures(params, names, &seq_lens, &nb_kmers, signatures)
+} // end of sketch_set
+
+
+
+/// sketches each amino acid sequence of vseq (named by names) with sketcher and returns a SketchSet.
+/// params must be those used to build the sketcher.
+pub fn sketch_set_aa<Kmer, S, F>(sketcher : &S, params : &SeqSketcherParams, names : &[String], vseq : &Vec<&SequenceAA>, fhash : F) -> Result<SketchSet<S::Sig>, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                kmeraa::KmerGenerator<Kmer> : kmeraa::KmerGenerationPattern<Kmer>,
+                S : SeqSketcherAAT<Kmer>,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+    if names.len() != vseq.len() {
+        log::error!("sketch_set_aa : {} sequences but {} names", vseq.len(), names.len());
+        return Err(format!("sketch_set_aa : {} sequences but {} names", vseq.len(), names.len()));
+    }
+    let signatures = sketcher.sketch_compressedkmeraa(vseq, fhash)?;
+    let seq_lens : Vec<u64> = vseq.iter().map(|s| s.len() as u64).collect();
+    let nb_kmers : Vec<u64> = vseq.iter().map(|s| get_nb_kmers(s.len(), sketcher.get_kmer_size())).collect();
+    SketchSet::from_signatures(params, names, &seq_lens, &nb_kmers, signatures)
+} // end of sketch_set_aa
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::sketcharg::{SketchAlgo, DataType};
+    use crate::aautils::kmeraa::KmerAA32bit;
+    use crate::aautils::setsketchert::SuperHashSketch as SuperHashSketchAA;
+    use crate::sketching::setsketchert::ProbHash3aSketch;
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_sketch_set() {
+        log_init_test();
+        let params = SeqSketcherParams::new(8, 50, SketchAlgo::PROB3A, DataType::DNA);
+        let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&params);
+        let seqs = [Sequence::new(b"ACGTTGCAAGGCTTACCGATTGACCAGT", 2), Sequence::new(b"GGGCATTACCAGGTTACATTGACC", 2), Sequence::new(b"ACGT", 2)];
+        let vseq : Vec<&Sequence> = seqs.iter().collect();
+        let names : Vec<String> = (0..3).map(|i| format!("seq{}", i)).collect();
+        let fhash = |kmer : &Kmer32bit| kmer.get_compressed_value();
+        let set = sketch_set(&sketcher, &params, &names, &vseq, fhash).unwrap();
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.get_signatures(), sketcher.sketch_compressedkmer(&vseq, fhash).unwrap().as_slice());
+        assert_eq!(set.get_info(0), &SketchInfo{id : 0, name : "seq0".to_string(), seq_len : 28, nb_kmers : 21});
+        assert_eq!(set.get_by_name("seq2").unwrap().nb_kmers, 0);
+        assert!(sketch_set(&sketcher, &params, &names[0..2], &vseq, fhash).is_err());
+        // serde round trip
+        let json = serde_json::to_string(&set).unwrap();
+        let reloaded : SketchSet<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(reloaded.get_infos(), set.get_infos());
+        assert_eq!(reloaded.get_signatures(), set.get_signatures());
+        assert_eq!(reloaded.get_params().get_kmer_size(), 8);
+    } // end of test_sketch_set
+
+
+    #[test]
+    fn test_sketch_set_aa() {
+        log_init_test();
+        let params = SeqSketcherParams::new(3, 20, SketchAlgo::SUPER, DataType::AA);
+        let sketcher = SuperHashSketchAA::<KmerAA32bit, f32>::new(&params);
+        let seqs = [SequenceAA::new(b"MTEYKLVVVGAGGVGKSALTIQ").unwrap(), SequenceAA::new(b"MKVLAAGIVGLLLA").unwrap()];
+        let names = vec!["p1".to_string(), "p2".to_string()];
+        let set = sketch_set_aa(&sketcher, &params, &names, &seqs.iter().collect(), |kmer : &KmerAA32bit| kmer.get_compressed_value()).unwrap();
+        let mut set2 = SketchSet::<f32>::new(&params);
+        for (info, sig) in set.iter() {
+            assert_eq!(set2.push(&info.name, info.seq_len, info.nb_kmers, sig.to_vec()), info.id);
+        }
+        assert_eq!(set2.get_infos(), set.get_infos());
+        assert_eq!(set2.get_info(1).nb_kmers, 12);
+    } // end of test_sketch_set_aa
+
+    #[test]
+    fn test_sketch_set_compatibility() {
+        log_init_test();
+        let seqs = [Sequence::new(b"ACGTTGCAAGGCTTACCGATTGACCAGT", 2), Sequence::new(b"GGGCATTACCAGGTTACATTGACC", 2)];
+        let vseq : Vec<&Sequence> = seqs.iter().collect();
+        let names = vec!["a".to_string(), "b".to_string()];
+        let fhash = |kmer : &Kmer32bit| kmer.get_compressed_value();
+        let params = SeqSketcherParams::new(8, 50, SketchAlgo::PROB3A, DataType::DNA);
+        let set = sketch_set(&ProbHash3aSketch::<Kmer32bit>::new(&params), &params, &names, &vseq, fhash).unwrap();
+        let set2 = sketch_set(&ProbHash3aSketch::<Kmer32bit>::new(&params), &params, &names[..1], &vseq[..1].to_vec(), fhash).unwrap();
+        assert_eq!(set.jaccard_with(0, &set2, 0), Ok(1.));
+        let cross = set.cross_distances(&set2).unwrap();
+        assert_eq!(cross.len(), 2);
+        assert_eq!(cross[1][0], set.distance_with(1, &set2, 0).unwrap());
+        // same signature type, other kmer size
+        let params9 = SeqSketcherParams::new(9, 50, SketchAlgo::PROB3A, DataType::DNA);
+        let set9 = sketch_set(&ProbHash3aSketch::<Kmer32bit>::new(&params9), &params9, &names, &vseq, fhash).unwrap();
+        assert_eq!(set.jaccard_with(0, &set9, 0), Err(IncompatibleParams::KmerSize{left : 8, right : 9}));
+        assert!(set.cross_distances(&set9).is_err());
+    } // end of test_sketch_set_compatibility
+
+
+} // end of mod tests
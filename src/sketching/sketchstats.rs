@@ -0,0 +1,195 @@
+//! This module computes statistics on the kmers sketched : number of kmers generated, number of kmers skipped because
+//! they contain an invalid base (when sketching raw bytes, see [DnaSlice](super::seqsketchgen::DnaSlice)),
+//! number of distinct kmer hashes and maximal multiplicity of a kmer hash.
+//!
+//! Statistics need a hash table of counts, so they are computed only on request, by the `*_stats` methods of sketchers
+//! (see [SeqSketcherT::sketch_compressedkmer_stats](super::setsketchert::SeqSketcherT::sketch_compressedkmer_stats))
+//! or directly by [kmer_stats] and [kmer_stats_each].  
+//! Sketchers built on [seqsketchgen](super::seqsketchgen) collect them in the sketching pass, on the kmers left by the abundance
+//! and singleton filters. Other sketchers (HyperLogLog, SuperHash2) need an additional pass on kmers.
+
+
+use std::fmt::Debug;
+
+use crate::parallel::*;
+use serde::{Deserialize, Serialize};
+
+use crate::base::kmertraits::*;
+
+use super::seqsketchgen::{KmerSequence, KmerWeights, BuildKmerWeightHasher, get_nbkmer_guess_seqs, hash_kmers};
+
+
+/// statistics on the kmers of a sketch
+#[derive(Copy,Clone,Debug,Default,PartialEq,Eq,Serialize,Deserialize)]
+pub struct SketchStats {
+    /// number of bases (or residues) of sequences
+    pub nb_bases : u64,
+    /// number of kmers sent to the sketcher, after filtering
+    pub nb_kmers : u64,
+    /// number of kmer positions skipped as they contain an invalid base
+    pub nb_skipped : u64,
+    /// number of distinct kmer hashes sent to the sketcher, an estimate of the number of distinct kmers (exact if the hash is injective)
+    pub nb_distinct : u64,
+    /// maximal number of occurrences of a kmer hash
+    pub max_multiplicity : u64,
+}
+
+
+impl SketchStats {
+
+    /// fraction of kmer occurrences repeating an already seen kmer hash
+    pub fn get_redundancy(&self) -> f64 {
+        if self.nb_kmers == 0 {
+            return 0.;
+        }
+        1. - self.nb_distinct as f64 / self.nb_kmers as f64
+    }
+
+    // adds the multiplicities of kmer hashes sent to the sketcher
+    pub(crate) fn add_counts(&mut self, counts : impl Iterator<Item = u64>) {
+        for count in counts {
+            self.nb_kmers += count;
+            self.nb_distinct += 1;
+            self.max_multiplicity = self.max_multiplicity.max(count);
+        }
+    }
+
+    // adds the bases of sequences and the kmer positions skipped, nb_generated kmers having been generated from them
+    pub(crate) fn add_bases<Kmer, Seq>(&mut self, vseq : &[&Seq], kmer_size : usize, nb_generated : u64)
+            where Seq : KmerSequence<Kmer> + ?Sized {
+        let mut nb_positions : u64 = 0;
+        for seq in vseq {
+            let nb_bases = seq.get_nb_bases();
+            self.nb_bases += nb_bases as u64;
+            if kmer_size > 0 && nb_bases >= kmer_size {
+                nb_positions += (nb_bases - kmer_size + 1) as u64;
+            }
+        }
+        self.nb_skipped += nb_positions.saturating_sub(nb_generated);
+    }
+
+} // end of impl SketchStats
+
+
+/// signatures of sequences with the statistics of their kmers, as returned by the `*_stats` methods of sketchers
+pub type SigsWithStats<Sig> = (Vec<Vec<Sig>>, Vec<SketchStats>);
+
+
+
+/// computes statistics of kmers of size kmer_size, hashed by fhash, of all sequences of vseq considered as one sketch, without filtering.
+pub fn kmer_stats<Kmer, Seq, F>(vseq : &[&Seq], kmer_size : usize, fhash : &F) -> SketchStats
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : std::hash::Hash + Debug,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                F : Fn(&Kmer) -> Kmer::Val {
+    //
+    let mut counts = KmerWeights::<Kmer::Val>::with_capacity_and_hasher(get_nbkmer_guess_seqs(vseq), BuildKmerWeightHasher::default());
+    let nb_generated = hash_kmers(vseq, kmer_size, fhash, None, |_, hashval| *counts.entry(hashval).or_insert(0) += 1);
+    let mut stats = SketchStats::default();
+    stats.add_counts(counts.into_values());
+    stats.add_bases(vseq, kmer_size, nb_generated);
+    log::debug!("kmer_stats : {:?}", stats);
+    stats
+} // end of kmer_stats
+
+
+
+/// computes the statistics of each sequence of vseq, in parallel. Results are in the order of vseq.
+pub fn kmer_stats_each<Kmer, Seq, F>(vseq : &[&Seq], kmer_size : usize, fhash : &F) -> Vec<SketchStats>
+        where   Kmer : CompressedKmerT,
+                Kmer::Val : std::hash::Hash + Debug,
+                Seq : KmerSequence<Kmer> + ?Sized,
+                F : Fn(&Kmer) -> Kmer::Val + Sync {
+    vseq.par_iter().map(|seq| kmer_stats(&[*seq], kmer_size, fhash)).collect()
+} // end of kmer_stats_each
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::base::{kmer::*, kmergenerator::*};
+    use crate::aautils::kmeraa::{KmerAA32bit, AaSlice, SequenceAA};
+    use crate::aautils::setsketchert::{SeqSketcherAAT, ProbHash3aSketch as ProbHash3aSketchAA};
+    use crate::sketcharg::{SeqSketcherParams, SketchAlgo, DataType};
+    use crate::sketching::seqsketchgen::DnaSlice;
+    use crate::sketching::setsketchert::{SeqSketcherT, ProbHash3aSketch, SuperHashSketch};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_kmer_stats() {
+        log_init_test();
+        let fhash = |kmer : &Kmer32bit| kmer.get_compressed_value();
+        // 4 times the same 4-mer and one other
+        let seq = Sequence::new(b"AAAAAAAC", 2);
+        let stats = kmer_stats(&[&seq], 4, &fhash);
+        assert_eq!(stats, SketchStats{nb_bases : 8, nb_kmers : 5, nb_skipped : 0, nb_distinct : 2, max_multiplicity : 4});
+        // N breaks 4 kmers
+        let raw = b"ACGTTGCANGGCTTACC";
+        let stats = kmer_stats(&[&DnaSlice(raw)], 4, &fhash);
+        assert_eq!(stats.nb_kmers, 10);
+        assert_eq!(stats.nb_skipped, 4);
+        // through sketchers
+        let params = SeqSketcherParams::new(4, 20, SketchAlgo::PROB3A, DataType::DNA);
+        let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&params);
+        let (sigs, vstats) = sketcher.sketch_compressedkmer_bytes_stats(&[raw, b"AAAAAAAC"], fhash).unwrap();
+        assert_eq!(sigs, sketcher.sketch_compressedkmer_bytes(&[raw, b"AAAAAAAC"], fhash).unwrap());
+        assert_eq!(vstats[0], stats);
+        assert_eq!(vstats[1].max_multiplicity, 4);
+        let (_, vstats) = sketcher.sketch_compressedkmer_stats(&vec![&seq], fhash).unwrap();
+        assert_eq!(vstats[0].nb_distinct, 2);
+        let sketcher = SuperHashSketch::<Kmer32bit, f32>::new(&params);
+        let (_, vstats) = sketcher.sketch_compressedkmer_bytes_stats(&[raw], fhash).unwrap();
+        assert_eq!(vstats[0], stats);
+    } // end of test_kmer_stats
+
+
+    #[test]
+    fn test_kmer_stats_filtered() {
+        log_init_test();
+        let fhash = |kmer : &Kmer32bit| kmer.get_compressed_value();
+        let params = SeqSketcherParams::builder().kmer_size(4).sketch_size(20).abundance_filter(2, 1000).build::<Kmer32bit>().unwrap();
+        let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&params);
+        // statistics are collected on kmers kept by the abundance filter : AAAA seen 4 times, AAAC once is dropped
+        let seq = Sequence::new(b"AAAAAAAC", 2);
+        let (_, vstats) = sketcher.sketch_compressedkmer_stats(&vec![&seq], fhash).unwrap();
+        assert_eq!(vstats[0], SketchStats{nb_bases : 8, nb_kmers : 4, nb_skipped : 0, nb_distinct : 1, max_multiplicity : 4});
+        // same on a sequence long enough to be sketched through a weight map
+        let long : Vec<u8> = b"ACGTTGCAAGGCTTACCGATC".iter().cycle().take(5000).copied().collect();
+        let seq = Sequence::new(&long, 2);
+        let (_, vstats) = sketcher.sketch_compressedkmer_stats(&vec![&seq], fhash).unwrap();
+        let unfiltered = kmer_stats(&[&seq], 4, &fhash);
+        assert_eq!(vstats[0].nb_kmers, unfiltered.nb_kmers);
+        assert_eq!(vstats[0].nb_distinct, unfiltered.nb_distinct);
+        let mut with_singleton = long.clone();
+        with_singleton.extend_from_slice(b"NTTTT");
+        let (_, vstats) = sketcher.sketch_compressedkmer_bytes_stats(&[&with_singleton], fhash).unwrap();
+        assert_eq!(vstats[0].nb_kmers, unfiltered.nb_kmers);
+        assert_eq!(vstats[0].nb_skipped, 4);
+    } // end of test_kmer_stats_filtered
+
+
+    #[test]
+    fn test_kmer_stats_aa() {
+        log_init_test();
+        let params = SeqSketcherParams::new(3, 20, SketchAlgo::PROB3A, DataType::AA);
+        let sketcher = ProbHash3aSketchAA::<KmerAA32bit>::new(&params);
+        let fhash = |kmer : &KmerAA32bit| kmer.get_compressed_value();
+        let raw = b"MKVLAXAGIVG";
+        let (_, vstats) = sketcher.sketch_compressedkmeraa_bytes_stats(&[raw], fhash).unwrap();
+        assert_eq!(vstats[0], kmer_stats(&[&AaSlice(raw)], 3, &fhash));
+        assert_eq!(vstats[0].nb_skipped, 3);
+        let seq = SequenceAA::new(b"MKVLAAGIVG").unwrap();
+        let (_, vstats) = sketcher.sketch_compressedkmeraa_stats(&vec![&seq], fhash).unwrap();
+        assert_eq!(vstats[0].nb_kmers, 8);
+        assert_eq!(vstats[0].nb_skipped, 0);
+    } // end of test_kmer_stats_aa
+
+} // end of mod tests
@@ -0,0 +1,268 @@
+//! This module provides the ProbMinHash3a path of short sequences (proteins, short reads).
+//!
+//! Protein databases are dominated by sequences of a few hundred residues. For them the weight map of
+//! [probminhash3a_signature](super::seqsketchgen::probminhash3a_signature) costs more than sketching : each entry is hashed,
+//! and the scratch map (which keeps the capacity reached by the largest sequence seen by the thread) is cleared and
+//! iterated bucket by bucket. Sequences with at most [SMALL_SEQ_MAX_BASES] bases instead collect their kmer hashes
+//! in a vector, sorted to get multiplicities, and ProbMinHash3a runs on the sorted (hash, count) pairs.
+//!
+//! The signature is the one of the weight map path : the ProbMinHash3a slot of a kmer is the minimum over the values
+//! drawn from a generator seeded by the kmer hash, which does not depend on the order of kmers.
+
+
+use std::hash::{BuildHasher, BuildHasherDefault};
+use std::fmt::Debug;
+
+use rand::distributions::{Distribution, Uniform};
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use probminhash::exp01::ExpRestricted01;
+
+use crate::nohasher::NoHashHasher;
+use crate::sketcharg::SeqSketcherParams;
+
+use crate::base::scratch::with_scratch;
+use super::sketchstats::SketchStats;
+
+
+/// sequences (or collections of sequences) with at most this number of bases are sketched by ProbMinHash3a without weight map
+pub const SMALL_SEQ_MAX_BASES : usize = 512;
+
+
+// maximum of slot values, as a tournament tree : leaves 0..m are the slots, the parent of node k is m + k/2 and the root is 2m-2
+struct SlotMaxTracker {
+    m : usize,
+    values : Vec<f64>,
+}
+
+impl SlotMaxTracker {
+    fn reset(&mut self, m : usize) {
+        self.m = m;
+        self.values.clear();
+        self.values.resize(2 * m - 1, f64::MAX);
+    }
+
+    #[inline]
+    fn get_max(&self) -> f64 {
+        self.values[2 * self.m - 2]
+    }
+
+    #[inline]
+    fn get_value(&self, k : usize) -> f64 {
+        self.values[k]
+    }
+
+    // sets slot k to value, lower than its current value, and propagates maxima to the root
+    fn update(&mut self, k : usize, value : f64) {
+        self.values[k] = value;
+        let mut node = k;
+        while node < 2 * self.m - 2 {
+            let parent = self.m + node / 2;
+            let node_max = self.values[node].max(self.values[node ^ 1]);
+            if node_max >= self.values[parent] {
+                break;
+            }
+            self.values[parent] = node_max;
+            node = parent;
+        }
+    }
+} // end of impl SlotMaxTracker
+
+
+// buffers of a thread reused between short sequences
+struct SmallSeqScratch<V> {
+    hashvals : Vec<V>,
+    weights : Vec<(V, u64)>,
+    tracker : SlotMaxTracker,
+    pending : Vec<(V, f64, Xoshiro256PlusPlus)>,
+}
+
+
+
+/// ProbMinHash3a signature of kmer hashes given by fill (called with the vector to extend), kmers being weighted by
+/// their multiplicity, with the abundance filter of params if any. Meant for short sequences, see module doc.
+pub fn probminhash3a_small_signature<V, G>(params : &SeqSketcherParams, fill : G) -> Vec<V>
+        where   V : num::PrimInt + Debug + Default + std::hash::Hash + 'static,
+                G : FnOnce(&mut Vec<V>) {
+    probminhash3a_small_signature_stats(params, fill, None)
+} // end of probminhash3a_small_signature
+
+
+
+/// same as [probminhash3a_small_signature], adding to stats (if given) the counts of kmer hashes sketched, after abundance filtering.
+pub fn probminhash3a_small_signature_stats<V, G>(params : &SeqSketcherParams, fill : G, stats : Option<&mut SketchStats>) -> Vec<V>
+        where   V : num::PrimInt + Debug + Default + std::hash::Hash + 'static,
+                G : FnOnce(&mut Vec<V>) {
+    let sketch_size = params.get_sketch_size();
+    let new = || SmallSeqScratch{hashvals : Vec::<V>::with_capacity(SMALL_SEQ_MAX_BASES), weights : Vec::with_capacity(SMALL_SEQ_MAX_BASES),
+                tracker : SlotMaxTracker{m : 0, values : Vec::new()}, pending : Vec::new()};
+    with_scratch(sketch_size, new, |scratch| {
+            fill(&mut scratch.hashvals);
+            scratch.hashvals.sort_unstable();
+            for hashval in scratch.hashvals.iter() {
+                match scratch.weights.last_mut() {
+                    Some((last, count)) if last == hashval => *count += 1,
+                    _ => scratch.weights.push((*hashval, 1)),
+                }
+            }
+            if params.has_abundance_filter() {
+                scratch.weights.retain(|(_, c)| params.is_count_accepted(*c));
+            }
+            if let Some(stats) = stats {
+                stats.add_counts(scratch.weights.iter().map(|(_, c)| *c));
+            }
+            probminhash3a_sorted(&scratch.weights, sketch_size, &mut scratch.tracker, &mut scratch.pending)
+        },
+        |scratch| {
+            scratch.hashvals.clear();
+            scratch.weights.clear();
+            scratch.pending.clear();
+            scratch.hashvals.capacity() <= 4 * SMALL_SEQ_MAX_BASES
+        })
+} // end of probminhash3a_small_signature_stats
+
+
+
+// ProbMinHash3a (Ertl, algorithm 3 with the second pass on buffered items) as in crate probminhash, on (value, weight) pairs.
+// Random generators are seeded by the NoHashHasher hash of values, as ProbMinHash3a::<V, NoHashHasher> does.
+fn probminhash3a_sorted<V>(weights : &[(V, u64)], sketch_size : usize, tracker : &mut SlotMaxTracker, pending : &mut Vec<(V, f64, Xoshiro256PlusPlus)>) -> Vec<V>
+        where V : num::PrimInt + Debug + Default + std::hash::Hash {
+    assert!(sketch_size >= 2, "ProbMinHash3a needs at least 2 slots");
+    let lambda = (sketch_size as f64 / (sketch_size - 1) as f64).ln();
+    let exp01 = ExpRestricted01::new(lambda);
+    let unif0m = Uniform::<usize>::new(0, sketch_size);
+    let b_hasher = BuildHasherDefault::<NoHashHasher>::default();
+    tracker.reset(sketch_size);
+    let mut signature = vec![V::default(); sketch_size];
+    //
+    for (value, weight) in weights {
+        let winv = 1. / *weight as f64;
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(b_hasher.hash_one(value));
+        let h = winv * exp01.sample(&mut rng);
+        if h < tracker.get_max() {
+            let k = unif0m.sample(&mut rng);
+            if h < tracker.get_value(k) {
+                signature[k] = *value;
+                tracker.update(k, h);
+            }
+            if winv < tracker.get_max() {
+                pending.push((*value, winv, rng));
+            }
+        }
+    }
+    // values that can still improve a slot draw their next values
+    let mut i = 2;
+    while !pending.is_empty() {
+        pending.retain_mut(|(value, winv, rng)| {
+            let h = *winv * (i - 1) as f64;
+            if h >= tracker.get_max() {
+                return false;
+            }
+            let h = h + *winv * exp01.sample(rng);
+            let k = unif0m.sample(rng);
+            if h < tracker.get_value(k) {
+                signature[k] = *value;
+                tracker.update(k, h);
+            }
+            *winv * (i as f64) < tracker.get_max()
+        });
+        i += 1;
+    }
+    signature
+} // end of probminhash3a_sorted
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::Rng;
+    use crate::sketching::seqsketchgen::{KmerWeights, accumulate_kmer_weights, probminhash3a_weights_signature, probminhash3a_signature};
+    use crate::base::{Sequence, Kmer32bit, CompressedKmerT};
+    use crate::aautils::kmeraa::{KmerAA32bit, SequenceAA};
+    use crate::sketcharg::{SketchAlgo, DataType};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_small_signature() {
+        log_init_test();
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(7);
+        for (sketch_size, nb_values) in [(2, 5), (50, 40), (200, 500), (1000, 300)] {
+            let mut params = SeqSketcherParams::new(5, sketch_size, SketchAlgo::PROB3A, DataType::AA);
+            // values with repeats so that weights differ
+            let values : Vec<u32> = (0..nb_values).map(|_| rng.gen_range(0..(nb_values as u32 / 2).max(1))).collect();
+            let mut weights = KmerWeights::<u32>::default();
+            values.iter().for_each(|v| *weights.entry(*v).or_insert(0) += 1);
+            let small = probminhash3a_small_signature(&params, |hashvals : &mut Vec<u32>| hashvals.extend(&values));
+            assert_eq!(small, probminhash3a_weights_signature(weights.clone(), &params), "sketch size {}", sketch_size);
+            // with abundance filter
+            params.set_abundance_filter(2, 5);
+            let small = probminhash3a_small_signature(&params, |hashvals : &mut Vec<u32>| hashvals.extend(&values));
+            assert_eq!(small, probminhash3a_weights_signature(weights, &params));
+        }
+        // short sequences go through the small path of probminhash3a_signature
+        let residues : Vec<u8> = (0..300).map(|_| b"ACDEFGHIKLMNPQRSTVWY"[rng.gen_range(0..20)]).collect();
+        let seqaa = SequenceAA::new(&residues).unwrap();
+        let fhash_aa = | kmer : &KmerAA32bit | -> u32 { kmer.get_compressed_value() };
+        let params = SeqSketcherParams::new(3, 100, SketchAlgo::PROB3A, DataType::AA);
+        let mut weights = KmerWeights::<u32>::default();
+        accumulate_kmer_weights(&[&seqaa, &seqaa], 3, &fhash_aa, &mut weights);
+        assert_eq!(probminhash3a_signature(&[&seqaa, &seqaa], &params, &fhash_aa), probminhash3a_weights_signature(weights, &params));
+        let bases : Vec<u8> = (0..400).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect();
+        let seq = Sequence::new(&bases, 2);
+        let fhash = | kmer : &Kmer32bit | -> u32 { probminhash::invhash::int32_hash(kmer.get_compressed_value()) };
+        let params = SeqSketcherParams::new(5, 64, SketchAlgo::PROB3A, DataType::DNA);
+        let mut weights = KmerWeights::<u32>::default();
+        accumulate_kmer_weights(&[&seq], 5, &fhash, &mut weights);
+        assert_eq!(probminhash3a_signature(&[&seq], &params, &fhash), probminhash3a_weights_signature(weights, &params));
+        // slot max tracker
+        let mut tracker = SlotMaxTracker{m : 0, values : Vec::new()};
+        tracker.reset(5);
+        assert_eq!(tracker.get_max(), f64::MAX);
+        for (k, v) in [(0, 1.), (1, 2.), (2, 3.), (3, 4.)] {
+            tracker.update(k, v);
+        }
+        assert_eq!(tracker.get_max(), f64::MAX);
+        tracker.update(4, 0.5);
+        assert_eq!(tracker.get_max(), 4.);
+        tracker.update(3, 0.1);
+        assert_eq!(tracker.get_max(), 3.);
+    } // end of test_small_signature
+
+
+    // pins the small path to ProbMinHash3a of crate probminhash on random inputs, for both hash widths
+    #[test]
+    fn test_small_signature_pinned() {
+        log_init_test();
+        fn check<V>(rng : &mut Xoshiro256PlusPlus, sketch_size : usize, nb_values : usize, nb_distinct : usize)
+                where   V : num::PrimInt + Debug + Default + std::hash::Hash + 'static,
+                        rand::distributions::Standard : Distribution<V> {
+            let distinct : Vec<V> = (0..nb_distinct).map(|_| rng.gen::<V>()).collect();
+            let values : Vec<V> = (0..nb_values).map(|_| distinct[rng.gen_range(0..nb_distinct)]).collect();
+            let mut weights = KmerWeights::<V>::default();
+            values.iter().for_each(|v| *weights.entry(*v).or_insert(0) += 1);
+            let mut pminhash = probminhash::probminhasher::ProbMinHash3a::<V, NoHashHasher>::new(sketch_size, V::default());
+            pminhash.hash_weigthed_hashmap(&weights);
+            let params = SeqSketcherParams::new(5, sketch_size, SketchAlgo::PROB3A, DataType::DNA);
+            let small = probminhash3a_small_signature(&params, |hashvals : &mut Vec<V>| hashvals.extend(&values));
+            assert_eq!(&small, pminhash.get_signature(), "sketch size {} nb values {} nb distinct {}", sketch_size, nb_values, nb_distinct);
+        }
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(3161);
+        for _ in 0..200 {
+            let sketch_size = rng.gen_range(2..400);
+            let nb_values = rng.gen_range(1..SMALL_SEQ_MAX_BASES);
+            let nb_distinct = rng.gen_range(1..=nb_values);
+            check::<u32>(&mut rng, sketch_size, nb_values, nb_distinct);
+            check::<u64>(&mut rng, sketch_size, nb_values, nb_distinct);
+        }
+    } // end of test_small_signature_pinned
+
+} // end of mod tests
@@ -0,0 +1,314 @@
+//! This module provides sketching of fasta/fastq data read from any [std::io::Read] : stdin, pipes, network streams...
+//!
+//! The stream is parsed with needletail (compressed streams are recognized) and records are consumed in chunks
+//! of a fixed number of records, so that memory stays bounded whatever the length of the stream and kmerutils
+//! can sit in a Unix pipeline without temporary files.
+//!
+//! - [sketch_stream_records] computes one signature by record and hands them to a callback chunk by chunk,
+//! - [sketch_stream_prob3a] and [sketch_stream_hll] compute one signature for the whole stream,
+//!   accumulating kmer counts (resp. merging HyperLogLog sketches) chunk after chunk.
+//! - [sketch_stream_prob3a_countmin] computes one ProbMinHash signature for the whole stream with kmer counts
+//!   estimated in a Count-Min sketch, so memory does not depend on the number of distinct kmers (see [countmin](super::countmin)).
+//!
+//! Records are split at non ACGT bases as in [filesketch](super::filesketch). To sketch canonical kmers, wrap the sketcher in a [CanonicalSketcher]
+//! and hash with [get_dna_kmer_forward_hash](super::filesketch::get_dna_kmer_forward_hash), so that reverse complements are not computed for each kmer.
+
+
+use std::fmt::Debug;
+use std::io::Read;
+use std::hash::BuildHasherDefault;
+
+use num::{Integer, Bounded, FromPrimitive, ToPrimitive};
+use serde::Serialize;
+
+use probminhash::setsketcher::SetSketcher;
+
+use crate::base::{kmer::*, kmergenerator::*};
+use crate::io::{open_decompressed_reader, FastxRecord};
+use crate::nohasher::NoHashHasher;
+
+use super::setsketchert::*;
+use super::seqsketchgen::KmerWeights;
+use super::countmin::{CountMinParams, CountMinProbHash};
+use super::filesketch::split_record;
+
+
+/// default number of records read from a stream before sketching them
+pub const DEFAULT_STREAM_CHUNK_SIZE : usize = 10_000;
+
+
+/// reads fasta/fastq records from a stream by chunks of a given number of records
+pub struct FastxChunkReader {
+    reader : Box<dyn needletail::FastxReader>,
+    chunk_size : usize,
+    nb_read : usize,
+} // end of FastxChunkReader
+
+
+impl FastxChunkReader {
+    /// chunk_size is the maximum number of records returned by each call to [Self::next_chunk].
+    /// Compressed streams are decompressed on the fly, see [open_decompressed_reader].
+    pub fn new<R : Read + Send + 'static>(input : R, chunk_size : usize) -> Result<Self, String> {
+        let input = open_decompressed_reader(input).map_err(|e| {
+                log::error!("FastxChunkReader : could not read stream : {}", e);
+                format!("could not read stream : {}", e)
+            })?;
+        let reader = needletail::parse_fastx_reader(input).map_err(|e| {
+                log::error!("FastxChunkReader : could not parse stream : {}", e);
+                format!("could not parse stream : {}", e)
+            })?;
+        Ok(FastxChunkReader{reader, chunk_size : chunk_size.max(1), nb_read : 0})
+    }
+
+    /// returns the next chunk of records, None at end of stream
+    pub fn next_chunk(&mut self) -> Result<Option<Vec<FastxRecord>>, String> {
+        let mut records = Vec::<FastxRecord>::with_capacity(self.chunk_size);
+        while records.len() < self.chunk_size {
+            match self.reader.next() {
+                Some(record) => {
+                    let seqrec = record.map_err(|e| format!("invalid record : {}", e))?;
+                    let id = String::from_utf8_lossy(seqrec.id()).to_string();
+                    let qual = seqrec.qual().map(|q| q.to_vec());
+                    records.push(FastxRecord{id, seq : seqrec.seq().to_vec(), qual});
+                },
+                None => break,
+            }
+        }
+        self.nb_read += records.len();
+        if records.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(records))
+    } // end of next_chunk
+
+    /// returns the number of records read so far
+    pub fn get_nb_read(&self) -> usize {
+        self.nb_read
+    }
+} // end of impl FastxChunkReader
+
+
+
+/// sketches each DNA record of a stream in its own signature. Records are read by chunks of chunk_size records,
+/// and after each chunk callback is called with the ids and signatures of its records (records without kmers are skipped).
+/// For fastq records, kmers containing a base with Phred quality below min_quality are skipped (0 disables filtering).
+/// Returns the number of records read.
+pub fn sketch_stream_records<R, Kmer, Sketcher, F, C>(input : R, sketcher : &Sketcher, chunk_size : usize, min_quality : u8, fhash : F, mut callback : C) -> Result<usize, String>
+        where   R : Read + Send + 'static,
+                Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                Sketcher : SeqSketcherT<Kmer>,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync,
+                C : FnMut(Vec<String>, Vec<Vec<Sketcher::Sig>>) {
+    //
+    let mut reader = FastxChunkReader::new(input, chunk_size)?;
+    while let Some(records) = reader.next_chunk()? {
+        let (names, signatures) = sketch_records_each(records, sketcher, min_quality, &fhash)?;
+        callback(names, signatures);
+    }
+    log::debug!("sketch_stream_records, nb records read : {}", reader.get_nb_read());
+    Ok(reader.get_nb_read())
+} // end of sketch_stream_records
+
+
+
+// ids and signatures of the records of a chunk
+pub(crate) type SketchedChunk<Sig> = (Vec<String>, Vec<Vec<Sig>>);
+
+// sketches each record of a chunk in its own signature, returns ids and signatures of records having kmers
+pub(crate) fn sketch_records_each<Kmer, Sketcher, F>(records : Vec<FastxRecord>, sketcher : &Sketcher, min_quality : u8, fhash : &F) -> Result<SketchedChunk<Sketcher::Sig>, String>
+        where   Kmer : CompressedKmerT + KmerBuilder<Kmer>,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                Sketcher : SeqSketcherT<Kmer>,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+    //
+    let kmer_size = sketcher.get_kmer_size();
+    let runs : Vec<Vec<Sequence>> = records.iter().map(|r| split_record(r, kmer_size, min_quality)).collect();
+    let mut names = Vec::<String>::with_capacity(records.len());
+    let mut signatures = Vec::<Vec<Sketcher::Sig>>::with_capacity(records.len());
+    // records made of one run are sketched together
+    let single : Vec<usize> = (0..runs.len()).filter(|i| runs[*i].len() == 1).collect();
+    let vseq : Vec<&Sequence> = single.iter().map(|i| &runs[*i][0]).collect();
+    let mut single_sigs = if vseq.is_empty() { Vec::new() } else { sketcher.sketch_compressedkmer(&vseq, fhash)? }.into_iter();
+    for (record, record_runs) in records.into_iter().zip(runs.iter()) {
+        let sig = match record_runs.len() {
+            0 => None,
+            1 => single_sigs.next(),
+            _ => {
+                let vseq : Vec<&Sequence> = record_runs.iter().collect();
+                sketcher.sketch_compressedkmer_seqs(&vseq, fhash)?.pop()
+            },
+        };
+        match sig {
+            Some(sig) => {
+                names.push(record.id);
+                signatures.push(sig);
+            },
+            None => log::warn!("sketch_stream_records : record {} has no kmer, skipped", record.id),
+        }
+    }
+    Ok((names, signatures))
+} // end of sketch_records_each
+
+
+
+/// sketches all DNA records of a stream in one ProbMinHash3a signature.
+/// Kmer counts are accumulated chunk after chunk, so memory is proportional to the number of distinct kmers.
+pub fn sketch_stream_prob3a<R, Kmer, F>(input : R, sketcher : &ProbHash3aSketch<Kmer>, chunk_size : usize, min_quality : u8, fhash : F) -> Result<Vec<Kmer::Val>, String>
+        where   R : Read + Send + 'static,
+                Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + Serialize + 'static,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+    //
+    let mut reader = FastxChunkReader::new(input, chunk_size)?;
+    let kmer_size = sketcher.get_kmer_size();
+    let mut weights = KmerWeights::<Kmer::Val>::default();
+    while let Some(records) = reader.next_chunk()? {
+        let runs : Vec<Sequence> = records.iter().flat_map(|r| split_record(r, kmer_size, min_quality)).collect();
+        let vseq : Vec<&Sequence> = runs.iter().collect();
+        sketcher.accumulate_weights(&vseq, &fhash, &mut weights);
+    }
+    if weights.is_empty() {
+        log::error!("sketch_stream_prob3a : no kmer found in stream");
+        return Err(String::from("no kmer found in stream"));
+    }
+    log::debug!("sketch_stream_prob3a, nb records : {}, nb distinct kmers : {}", reader.get_nb_read(), weights.len());
+    Ok(sketcher.sketch_weights(weights))
+} // end of sketch_stream_prob3a
+
+
+
+/// same as [sketch_stream_prob3a], kmer counts being estimated by a Count-Min sketch of dimensions cm_params instead of counted exactly.
+/// Memory is bounded by the Count-Min sketch whatever the number of distinct kmers, see [countmin](super::countmin) for the accuracy trade-off.
+pub fn sketch_stream_prob3a_countmin<R, Kmer, F>(input : R, sketcher : &ProbHash3aSketch<Kmer>, cm_params : &CountMinParams, chunk_size : usize, min_quality : u8, fhash : F) -> Result<Vec<Kmer::Val>, String>
+        where   R : Read + Send + 'static,
+                Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + Serialize + 'static,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+    //
+    let mut reader = FastxChunkReader::new(input, chunk_size)?;
+    let kmer_size = sketcher.get_kmer_size();
+    let mut cmsketch = CountMinProbHash::<Kmer::Val>::new(sketcher.get_params(), cm_params);
+    while let Some(records) = reader.next_chunk()? {
+        let runs : Vec<Sequence> = records.iter().flat_map(|r| split_record(r, kmer_size, min_quality)).collect();
+        let vseq : Vec<&Sequence> = runs.iter().collect();
+        cmsketch.add_seqs(&vseq, &fhash);
+    }
+    if cmsketch.get_nb_kmers() == 0 {
+        log::error!("sketch_stream_prob3a_countmin : no kmer found in stream");
+        return Err(String::from("no kmer found in stream"));
+    }
+    log::debug!("sketch_stream_prob3a_countmin, nb records : {}, nb kmers : {}", reader.get_nb_read(), cmsketch.get_nb_kmers());
+    Ok(cmsketch.get_signature())
+} // end of sketch_stream_prob3a_countmin
+
+
+
+/// sketches all DNA records of a stream in one HyperLogLog signature.
+/// Each chunk is sketched and merged into the stream sketch, so memory does not depend on stream length.
+/// The singleton removal mode of [HyperLogLogSketch] needs all kmers before sketching and is not applied.
+pub fn sketch_stream_hll<R, Kmer, S, F>(input : R, sketcher : &HyperLogLogSketch<Kmer, S>, chunk_size : usize, min_quality : u8, fhash : F) -> Result<Vec<S>, String>
+        where   R : Read + Send + 'static,
+                Kmer : CompressedKmerT + KmerBuilder<Kmer> + Send + Sync,
+                Kmer::Val : num::PrimInt + Send + Sync + Debug + Serialize,
+                KmerGenerator<Kmer> : KmerGenerationPattern<Kmer>,
+                S : Integer + Bounded + Copy + Clone + FromPrimitive + ToPrimitive + Send + Sync + Debug + Serialize,
+                F : Fn(&Kmer) -> Kmer::Val + Send + Sync {
+    //
+    let mut reader = FastxChunkReader::new(input, chunk_size)?;
+    let kmer_size = sketcher.get_kmer_size();
+    let bh = BuildHasherDefault::<NoHashHasher>::default();
+    let mut setsketch : SetSketcher<S, Kmer::Val, NoHashHasher> = SetSketcher::new(sketcher.get_hll_params(), bh);
+    let mut has_kmer = false;
+    while let Some(records) = reader.next_chunk()? {
+        let runs : Vec<Sequence> = records.iter().flat_map(|r| split_record(r, kmer_size, min_quality)).collect();
+        if runs.is_empty() {
+            continue;
+        }
+        has_kmer = true;
+        let vseq : Vec<&Sequence> = runs.iter().collect();
+        let chunk_sketch = sketcher.sketch_compressedkmer_seqs_block(&vseq, &fhash)?;
+        if setsketch.merge(&chunk_sketch).is_err() {
+            log::error!("sketch_stream_hll : an error occurred in merging signatures");
+            return Err(String::from("an error occurred in merging signatures"));
+        }
+    }
+    if !has_kmer {
+        log::error!("sketch_stream_hll : no kmer found in stream");
+        return Err(String::from("no kmer found in stream"));
+    }
+    log::debug!("sketch_stream_hll, nb records : {}", reader.get_nb_read());
+    Ok(setsketch.get_signature().clone())
+} // end of sketch_stream_hll
+
+
+//=========================================================================================
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::io::Cursor;
+    use probminhash::setsketcher::SetSketchParams;
+    use crate::sketcharg::{SeqSketcherParams, SketchAlgo};
+
+    fn log_init_test() {
+        let mut builder = env_logger::Builder::from_default_env();
+        let _ = builder.is_test(true).try_init();
+    }
+
+    const FASTA : &str = ">r1\nACGTTGCAAGGCTTACCGATTGACCAGT\n>r2\nTTGCAAGGNNCTTACCGATTGACGGACCTAG\n>r3\nGGGCATTACCAGGTTACATTGACCAGTAGCTA\n>r4\nACG\n";
+
+    fn hash32(kmer : &Kmer32bit) -> u32 {
+        kmer.get_compressed_value() & 0x00ffffff
+    }
+
+    #[test]
+    fn test_stream_records() {
+        log_init_test();
+        let params = SeqSketcherParams::new(12, 50, SketchAlgo::PROB3A, crate::sketcharg::DataType::DNA);
+        let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&params);
+        // chunks of 2 records
+        let mut names = Vec::<String>::new();
+        let mut sigs = Vec::<Vec<u32>>::new();
+        let mut nb_calls = 0;
+        let nb_read = sketch_stream_records(Cursor::new(FASTA.as_bytes().to_vec()), &sketcher, 2, 0, hash32, |n, s| {
+                nb_calls += 1;
+                names.extend(n);
+                sigs.extend(s);
+            }).unwrap();
+        assert_eq!(nb_read, 4);
+        assert_eq!(nb_calls, 2);
+        // r4 has no kmer
+        assert_eq!(names, vec!["r1", "r2", "r3"]);
+        let r1 = Sequence::new(b"ACGTTGCAAGGCTTACCGATTGACCAGT", 2);
+        assert_eq!(sigs[0], sketcher.sketch_compressedkmer(&vec![&r1], hash32).unwrap()[0]);
+    } // end of test_stream_records
+
+
+    #[test]
+    fn test_stream_whole() {
+        log_init_test();
+        let params = SeqSketcherParams::new(12, 50, SketchAlgo::PROB3A, crate::sketcharg::DataType::DNA);
+        let sketcher = ProbHash3aSketch::<Kmer32bit>::new(&params);
+        let seqs : Vec<Sequence> = vec![Sequence::new(b"ACGTTGCAAGGCTTACCGATTGACCAGT", 2), Sequence::new(b"TTGCAAGG", 2),
+                    Sequence::new(b"CTTACCGATTGACGGACCTAG", 2), Sequence::new(b"GGGCATTACCAGGTTACATTGACCAGTAGCTA", 2)];
+        let vseq : Vec<&Sequence> = seqs.iter().collect();
+        let sig = sketch_stream_prob3a(Cursor::new(FASTA.as_bytes().to_vec()), &sketcher, 1, 0, hash32).unwrap();
+        assert_eq!(sig, sketcher.sketch_compressedkmer_seqs(&vseq, hash32).unwrap()[0]);
+        // hll merged chunk by chunk equals hll of all sequences
+        let hll = HyperLogLogSketch::<Kmer32bit, u16>::new(&params, SetSketchParams::default(), HllSeqsThreading::default());
+        let sig = sketch_stream_hll(Cursor::new(FASTA.as_bytes().to_vec()), &hll, 1, 0, hash32).unwrap();
+        assert_eq!(sig, hll.sketch_compressedkmer_seqs(&vseq, hash32).unwrap()[0]);
+        // count-min estimates are exact on a small stream
+        let sig = sketch_stream_prob3a_countmin(Cursor::new(FASTA.as_bytes().to_vec()), &sketcher, &CountMinParams::new(4, 1 << 12).unwrap(), 1, 0, hash32).unwrap();
+        assert_eq!(sig, sketcher.sketch_compressedkmer_seqs(&vseq, hash32).unwrap()[0]);
+        // empty stream
+        assert!(sketch_stream_prob3a(Cursor::new(b">r\nACG\n".to_vec()), &sketcher, 1, 0, hash32).is_err());
+        assert!(sketch_stream_prob3a_countmin(Cursor::new(b">r\nACG\n".to_vec()), &sketcher, &CountMinParams::default(), 1, 0, hash32).is_err());
+    } // end of test_stream_whole
+
+} // end of mod tests
@@ -10,7 +10,7 @@ use ::ndarray::*;
 
 
 // rayon usage
-use ::rayon::prelude::*;
+use crate::parallel::*;
 
 
 // general use